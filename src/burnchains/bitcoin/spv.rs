@@ -42,8 +42,8 @@ use rusqlite::Transaction;
 use rusqlite::{Connection, OpenFlags, NO_PARAMS};
 
 use util::db::{
-    query_row, query_rows, tx_begin_immediate, tx_busy_handler, u64_to_sql, DBConn, DBTx,
-    Error as db_error, FromColumn, FromRow,
+    query_row, query_rows, tx_begin_immediate, tx_busy_handler, u64_to_sql, DBConn, DBSchema, DBTx,
+    Error as db_error, FromColumn, FromRow, SchemaMigration,
 };
 use util::get_epoch_time_secs;
 use util::hash::{hex_bytes, to_hex};
@@ -65,8 +65,6 @@ const BITCOIN_GENESIS_BLOCK_HASH_REGTEST: &'static str =
 pub const BLOCK_DIFFICULTY_CHUNK_SIZE: u64 = 2016;
 const BLOCK_DIFFICULTY_INTERVAL: u32 = 14 * 24 * 60 * 60; // two weeks, in seconds
 
-pub const SPV_DB_VERSION: &'static str = "1";
-
 const SPV_INITIAL_SCHEMA: &[&'static str] = &[
     r#"
     CREATE TABLE headers(
@@ -82,6 +80,15 @@ const SPV_INITIAL_SCHEMA: &[&'static str] = &[
     "CREATE TABLE db_config(version TEXT NOT NULL);",
 ];
 
+pub const SPV_DB_SCHEMA: DBSchema = DBSchema {
+    name: "spv headers",
+    migrations: &[SchemaMigration {
+        version: 1,
+        description: "initial schema",
+        statements: SPV_INITIAL_SCHEMA,
+    }],
+};
+
 pub struct SpvClient {
     pub headers_path: String,
     pub start_block_height: u64,
@@ -179,13 +186,11 @@ impl SpvClient {
     fn db_instantiate(conn: &mut DBConn) -> Result<(), btc_error> {
         let tx = tx_begin_immediate(conn)?;
 
-        for row_text in SPV_INITIAL_SCHEMA {
-            tx.execute_batch(row_text).map_err(db_error::SqliteError)?;
-        }
+        SPV_DB_SCHEMA.instantiate(&tx)?;
 
         tx.execute(
             "INSERT INTO db_config (version) VALUES (?1)",
-            &[&SPV_DB_VERSION],
+            &[&SPV_DB_SCHEMA.latest_version().to_string()],
         )
         .map_err(db_error::SqliteError)?;
 
@@ -220,6 +225,8 @@ impl SpvClient {
 
         if create_flag {
             SpvClient::db_instantiate(&mut conn)?;
+        } else {
+            SPV_DB_SCHEMA.migrate(&mut conn, readwrite)?;
         }
 
         Ok(conn)
@@ -27,8 +27,8 @@ use burnchains::{Burnchain, BurnchainBlock, BurnchainBlockHeader, Error as Burnc
 use chainstate::burn::operations::BlockstackOperationType;
 use chainstate::stacks::index::MarfTrieId;
 use util::db::{
-    query_row, query_rows, sql_pragma, tx_begin_immediate, tx_busy_handler, u64_to_sql,
-    Error as DBError, FromColumn, FromRow,
+    query_row, query_rows, sql_pragma, tx_begin_immediate, tx_busy_handler, u64_to_sql, DBSchema,
+    Error as DBError, FromColumn, FromRow, SchemaMigration,
 };
 
 use crate::types::chainstate::BurnchainHeaderHash;
@@ -110,8 +110,6 @@ impl FromRow<BlockstackOperationType> for BlockstackOperationType {
     }
 }
 
-pub const BURNCHAIN_DB_VERSION: &'static str = "1";
-
 const BURNCHAIN_DB_INITIAL_SCHEMA: &'static str = "
 CREATE TABLE burnchain_db_block_headers (
     block_height INTEGER NOT NULL,
@@ -132,6 +130,15 @@ CREATE TABLE burnchain_db_block_ops (
 
 CREATE TABLE db_config(version TEXT NOT NULL);";
 
+pub const BURNCHAIN_DB_SCHEMA: DBSchema = DBSchema {
+    name: "burnchain",
+    migrations: &[SchemaMigration {
+        version: 1,
+        description: "initial schema",
+        statements: &[BURNCHAIN_DB_INITIAL_SCHEMA],
+    }],
+};
+
 impl<'a> BurnchainDBTransaction<'a> {
     fn store_burnchain_db_entry(
         &self,
@@ -219,11 +226,11 @@ impl BurnchainDB {
         if create_flag {
             let db_tx = db.tx_begin()?;
             sql_pragma(&db_tx.sql_tx, "PRAGMA journal_mode = WAL;")?;
-            db_tx.sql_tx.execute_batch(BURNCHAIN_DB_INITIAL_SCHEMA)?;
+            BURNCHAIN_DB_SCHEMA.instantiate(&db_tx.sql_tx)?;
 
             db_tx.sql_tx.execute(
                 "INSERT INTO db_config (version) VALUES (?1)",
-                &[&BURNCHAIN_DB_VERSION],
+                &[&BURNCHAIN_DB_SCHEMA.latest_version().to_string()],
             )?;
 
             let first_block_header = BurnchainBlockHeader {
@@ -236,6 +243,8 @@ impl BurnchainDB {
 
             db_tx.store_burnchain_db_entry(&first_block_header)?;
             db_tx.commit()?;
+        } else {
+            BURNCHAIN_DB_SCHEMA.migrate(&mut db.conn, readwrite)?;
         }
 
         Ok(db)
@@ -247,8 +256,9 @@ impl BurnchainDB {
         } else {
             OpenFlags::SQLITE_OPEN_READ_ONLY
         };
-        let conn = Connection::open_with_flags(path, open_flags)?;
+        let mut conn = Connection::open_with_flags(path, open_flags)?;
         conn.busy_handler(Some(tx_busy_handler))?;
+        BURNCHAIN_DB_SCHEMA.migrate(&mut conn, readwrite)?;
 
         Ok(BurnchainDB { conn })
     }
@@ -358,7 +368,7 @@ impl BurnchainDB {
         Ok(blockstack_ops)
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "fork-sim"))]
     pub fn raw_store_burnchain_block(
         &mut self,
         header: BurnchainBlockHeader,
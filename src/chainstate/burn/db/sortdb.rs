@@ -38,6 +38,7 @@ use burnchains::{
     BurnchainStateTransitionOps, BurnchainTransaction, BurnchainView, Error as BurnchainError,
     PoxConstants,
 };
+use chainstate::burn::distribution::BurnSamplePoint;
 use chainstate::burn::operations::{
     leader_block_commit::{MissedBlockCommit, RewardSetInfo, OUTPUTS_PER_COMMIT},
     BlockstackOperationType, LeaderBlockCommitOp, LeaderKeyRegisterOp, PreStxOp, StackStxOp,
@@ -63,7 +64,7 @@ use util::db::tx_busy_handler;
 use util::db::Error as db_error;
 use util::db::{
     db_mkdirs, query_count, query_row, query_row_columns, query_row_panic, query_rows, sql_pragma,
-    u64_to_sql, DBConn, FromColumn, FromRow, IndexDBConn, IndexDBTx,
+    u64_to_sql, DBConn, DBSchema, FromColumn, FromRow, IndexDBConn, IndexDBTx, SchemaMigration,
 };
 use util::get_epoch_time_secs;
 use util::hash::{hex_bytes, to_hex, Hash160, Sha512Trunc256Sum};
@@ -417,8 +418,6 @@ impl FromRow<AcceptedStacksBlockHeader> for AcceptedStacksBlockHeader {
     }
 }
 
-pub const SORTITION_DB_VERSION: &'static str = "1";
-
 const SORTITION_DB_INITIAL_SCHEMA: &'static [&'static str] = &[
     r#"
     PRAGMA foreign_keys = ON;
@@ -581,6 +580,29 @@ const SORTITION_DB_INITIAL_SCHEMA: &'static [&'static str] = &[
     "CREATE TABLE db_config(version TEXT NOT NULL);",
 ];
 
+const SORTITION_DB_BURN_DISTRIBUTION_SCHEMA: &'static [&'static str] = &[r#"
+    -- burn distribution computed for each sortition, as a JSON list of BurnSamplePoints
+    CREATE TABLE IF NOT EXISTS snapshot_burn_distributions(
+        sortition_id TEXT PRIMARY KEY,
+        data TEXT NOT NULL
+    );"#];
+
+pub const SORTITION_DB_SCHEMA: DBSchema = DBSchema {
+    name: "sortition",
+    migrations: &[
+        SchemaMigration {
+            version: 1,
+            description: "initial schema",
+            statements: SORTITION_DB_INITIAL_SCHEMA,
+        },
+        SchemaMigration {
+            version: 2,
+            description: "store burn distributions",
+            statements: SORTITION_DB_BURN_DISTRIBUTION_SCHEMA,
+        },
+    ],
+};
+
 pub struct SortitionDB {
     pub readwrite: bool,
     pub marf: MARF<SortitionId>,
@@ -1995,6 +2017,8 @@ impl SortitionDB {
             index_path
         );
 
+        SORTITION_DB_SCHEMA.migrate_path(&index_path, readwrite)?;
+
         let marf = SortitionDB::open_index(&index_path)?;
         let first_snapshot = SortitionDB::get_first_block_snapshot(marf.sqlite_conn())?;
 
@@ -2041,6 +2065,10 @@ impl SortitionDB {
             index_path
         );
 
+        if !create_flag {
+            SORTITION_DB_SCHEMA.migrate_path(&index_path, readwrite)?;
+        }
+
         let marf = SortitionDB::open_index(&index_path)?;
 
         let mut db = SortitionDB {
@@ -2119,13 +2147,11 @@ impl SortitionDB {
             BurnchainHeaderHash::sentinel()
         );
 
-        for row_text in SORTITION_DB_INITIAL_SCHEMA {
-            db_tx.execute_batch(row_text)?;
-        }
+        SORTITION_DB_SCHEMA.instantiate(&db_tx)?;
 
         db_tx.execute(
             "INSERT INTO db_config (version) VALUES (?1)",
-            &[&SORTITION_DB_VERSION],
+            &[&SORTITION_DB_SCHEMA.latest_version().to_string()],
         )?;
 
         db_tx.instantiate_index()?;
@@ -2426,6 +2452,22 @@ impl SortitionDB {
         Ok(Some((snapshot, transition_ops)))
     }
 
+    /// Get the burn distribution that was sampled for the given sortition.  Returns None if the
+    /// sortition was processed before burn distributions were stored.
+    pub fn get_burn_distribution(
+        &self,
+        id: &SortitionId,
+    ) -> Result<Option<Vec<BurnSamplePoint>>, db_error> {
+        let sql = "SELECT data FROM snapshot_burn_distributions WHERE sortition_id = ?";
+        let data: Option<String> = self
+            .conn()
+            .query_row(sql, &[id], |row| row.get(0))
+            .optional()?;
+        Ok(data.map(|data| {
+            serde_json::from_str(&data).expect("CORRUPTION: DB stored bad burn distribution")
+        }))
+    }
+
     ///
     /// # Arguments
     /// * `burn_header` - the burnchain block header to process sortition for
@@ -3270,28 +3312,18 @@ impl<'a> SortitionHandleTx<'a> {
             .map(|s| s.parse().expect("BUG: bad mining bonus stored in DB")))
     }
 
-    #[cfg(test)]
     fn store_burn_distribution(
         &mut self,
         new_sortition: &SortitionId,
         transition: &BurnchainStateTransition,
-    ) {
-        let create = "CREATE TABLE IF NOT EXISTS snapshot_burn_distributions (sortition_id TEXT PRIMARY KEY, data TEXT NOT NULL);";
-        self.execute(create, NO_PARAMS).unwrap();
+    ) -> Result<(), db_error> {
         let sql = "INSERT INTO snapshot_burn_distributions (sortition_id, data) VALUES (?, ?)";
         let args: &[&dyn ToSql] = &[
             new_sortition,
             &serde_json::to_string(&transition.burn_dist).unwrap(),
         ];
-        self.execute(sql, args).unwrap();
-    }
-
-    #[cfg(not(test))]
-    fn store_burn_distribution(
-        &mut self,
-        _new_sortition: &SortitionId,
-        _transition: &BurnchainStateTransition,
-    ) {
+        self.execute(sql, args)?;
+        Ok(())
     }
 
     fn store_transition_ops(
@@ -3306,7 +3338,7 @@ impl<'a> SortitionHandleTx<'a> {
             &serde_json::to_string(&transition.consumed_leader_keys).unwrap(),
         ];
         self.execute(sql, args)?;
-        self.store_burn_distribution(new_sortition, transition);
+        self.store_burn_distribution(new_sortition, transition)?;
         Ok(())
     }
 
@@ -133,7 +133,7 @@ impl LeaderBlockCommitOp {
         }
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "fork-sim"))]
     pub fn set_burn_height(&mut self, height: u64) {
         self.block_height = height;
         self.burn_parent_modulus = if height > 0 {
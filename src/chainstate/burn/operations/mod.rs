@@ -336,7 +336,7 @@ impl BlockstackOperationType {
         }
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "fork-sim"))]
     pub fn set_block_height(&mut self, height: u64) {
         match self {
             BlockstackOperationType::LeaderKeyRegister(ref mut data) => data.block_height = height,
@@ -350,7 +350,7 @@ impl BlockstackOperationType {
         };
     }
 
-    #[cfg(test)]
+    #[cfg(any(test, feature = "fork-sim"))]
     pub fn set_burn_header_hash(&mut self, hash: BurnchainHeaderHash) {
         match self {
             BlockstackOperationType::LeaderKeyRegister(ref mut data) => {
@@ -25,7 +25,8 @@ use burnchains::{
 };
 use chainstate::burn::{
     db::sortdb::SortitionDB, operations::leader_block_commit::RewardSetInfo,
-    operations::BlockstackOperationType, BlockSnapshot, ConsensusHash,
+    operations::BlockstackOperationType, operations::LeaderBlockCommitOp, BlockSnapshot,
+    ConsensusHash,
 };
 use chainstate::coordinator::comm::{
     ArcCounterCoordinatorNotices, CoordinatorEvents, CoordinatorNotices, CoordinatorReceivers,
@@ -60,9 +61,18 @@ use crate::util::boot::boot_code_id;
 pub use self::comm::CoordinatorCommunication;
 
 pub mod comm;
+#[cfg(any(test, feature = "fork-sim"))]
+pub mod sim;
 #[cfg(test)]
 pub mod tests;
 
+/// How many Stacks blocks the canonical tip must advance by between Clarity state pruning passes.
+/// Each pass walks all of the live state, so passes are spaced out.
+pub const CLARITY_PRUNE_INTERVAL: u64 = 100;
+/// The most tries a single Clarity state pruning pass compacts.  A node that enables pruning with
+/// a long history works through it over several passes, rather than stalling block processing.
+pub const CLARITY_PRUNE_BATCH: usize = 500;
+
 /// The 3 different states for the current
 ///  reward cycle's relationship to its PoX anchor
 #[derive(Debug, PartialEq)]
@@ -156,6 +166,10 @@ pub struct ChainsCoordinator<
     reward_set_provider: R,
     notifier: N,
     atlas_config: AtlasConfig,
+    /// If set, prune Clarity state more than this many blocks behind the canonical Stacks tip
+    clarity_prune_depth: Option<u32>,
+    /// Canonical Stacks tip height at the last pruning pass, and whether it left a backlog
+    last_clarity_prune: Option<(u64, bool)>,
 }
 
 #[derive(Debug)]
@@ -257,6 +271,7 @@ impl<'a, T: BlockEventDispatcher>
         dispatcher: &mut T,
         comms: CoordinatorReceivers,
         atlas_config: AtlasConfig,
+        clarity_prune_depth: Option<u32>,
     ) where
         T: BlockEventDispatcher,
     {
@@ -288,6 +303,8 @@ impl<'a, T: BlockEventDispatcher>
             notifier: arc_notices,
             reward_set_provider: OnChainRewardSetProvider(),
             atlas_config,
+            clarity_prune_depth,
+            last_clarity_prune: None,
         };
 
         loop {
@@ -311,12 +328,13 @@ impl<'a, T: BlockEventDispatcher>
                 }
                 CoordinatorEvents::TIMEOUT => {}
             }
+            inst.prune_clarity_state();
         }
     }
 }
 
 impl<'a, T: BlockEventDispatcher, U: RewardSetProvider> ChainsCoordinator<'a, T, (), U> {
-    #[cfg(test)]
+    #[cfg(any(test, feature = "fork-sim"))]
     pub fn test_new(
         burnchain: &Burnchain,
         chain_id: u32,
@@ -355,6 +373,8 @@ impl<'a, T: BlockEventDispatcher, U: RewardSetProvider> ChainsCoordinator<'a, T,
             notifier: (),
             attachments_tx,
             atlas_config: AtlasConfig::default(false),
+            clarity_prune_depth: None,
+            last_clarity_prune: None,
         }
     }
 }
@@ -442,26 +462,37 @@ pub fn get_reward_cycle_info<U: RewardSetProvider>(
     }
 }
 
-struct PaidRewards {
-    pox: Vec<(StacksAddress, u64)>,
-    burns: u64,
+/// The PoX recipients and burn address paid by the block-commits in a burnchain block
+pub struct PaidRewards {
+    pub pox: Vec<(StacksAddress, u64)>,
+    pub burns: u64,
 }
 
 fn calculate_paid_rewards(ops: &[BlockstackOperationType]) -> PaidRewards {
+    calculate_paid_commit_rewards(ops.iter().filter_map(|op| match op {
+        BlockstackOperationType::LeaderBlockCommit(commit) => Some(commit),
+        _ => None,
+    }))
+}
+
+/// Sum up how much each PoX recipient (and the burn address) was paid by the given block-commits.
+/// Each commit's burn fee is split evenly over its `commit_outs`.
+pub fn calculate_paid_commit_rewards<'a, I>(commits: I) -> PaidRewards
+where
+    I: IntoIterator<Item = &'a LeaderBlockCommitOp>,
+{
     let mut reward_recipients: HashMap<_, u64> = HashMap::new();
     let mut burn_amt = 0;
-    for op in ops.iter() {
-        if let BlockstackOperationType::LeaderBlockCommit(commit) = op {
-            let amt_per_address = commit.burn_fee / (commit.commit_outs.len() as u64);
-            for addr in commit.commit_outs.iter() {
-                if addr.is_burn() {
-                    burn_amt += amt_per_address;
+    for commit in commits.into_iter() {
+        let amt_per_address = commit.burn_fee / (commit.commit_outs.len() as u64);
+        for addr in commit.commit_outs.iter() {
+            if addr.is_burn() {
+                burn_amt += amt_per_address;
+            } else {
+                if let Some(prior_amt) = reward_recipients.get_mut(addr) {
+                    *prior_amt += amt_per_address;
                 } else {
-                    if let Some(prior_amt) = reward_recipients.get_mut(addr) {
-                        *prior_amt += amt_per_address;
-                    } else {
-                        reward_recipients.insert(addr.clone(), amt_per_address);
-                    }
+                    reward_recipients.insert(addr.clone(), amt_per_address);
                 }
             }
         }
@@ -500,6 +531,73 @@ fn dispatcher_announce_burn_ops<T: BlockEventDispatcher>(
 impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
     ChainsCoordinator<'a, T, N, U>
 {
+    /// Compact a batch of the Clarity state that has fallen more than the prune depth behind the
+    /// canonical Stacks tip.  A pass runs once the tip has advanced CLARITY_PRUNE_INTERVAL blocks
+    /// since the last one, or right away if the last one left a backlog.  Failures are logged,
+    /// and the next pass tries again.
+    ///
+    /// Pruning is permanent: a block that forks off of a pruned block, or that reads pruned
+    /// state via `at-block`, can't be processed, and stays staged (see
+    /// StacksChainState::prune_clarity_state()).  Until the node is resynced with a deeper prune
+    /// depth, that stalls the node if such a block is on the canonical fork.
+    pub fn prune_clarity_state(&mut self) {
+        let depth = match self.clarity_prune_depth {
+            Some(depth) => depth,
+            None => return,
+        };
+        let tip = match SortitionDB::get_canonical_burn_chain_tip(self.sortition_db.conn()) {
+            Ok(tip) => tip,
+            Err(e) => {
+                warn!(
+                    "Not pruning Clarity state: failed to load burnchain tip: {:?}",
+                    &e
+                );
+                return;
+            }
+        };
+        let tip_height = tip.canonical_stacks_tip_height;
+        if tip_height <= depth as u64 {
+            return;
+        }
+        if let Some((last_height, backlog)) = self.last_clarity_prune {
+            if !backlog && tip_height < last_height.saturating_add(CLARITY_PRUNE_INTERVAL) {
+                return;
+            }
+        }
+
+        let tip_index_hash = StacksBlockHeader::make_index_block_hash(
+            &tip.canonical_stacks_tip_consensus_hash,
+            &tip.canonical_stacks_tip_hash,
+        );
+        let backlog = match self.chain_state_db.prune_clarity_state(
+            &tip_index_hash,
+            depth,
+            CLARITY_PRUNE_BATCH,
+        ) {
+            Ok((stats, pruned_values)) => {
+                info!(
+                    "Pruned Clarity state behind {}: {} tries pruned, {} retained ({} left for later), {} -> {} trie bytes, {} side-store values removed",
+                    &tip_index_hash,
+                    stats.pruned_tries,
+                    stats.retained_tries,
+                    stats.backlog_tries,
+                    stats.bytes_before,
+                    stats.bytes_after,
+                    pruned_values
+                );
+                stats.backlog_tries > 0
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to prune Clarity state behind {}: {:?}",
+                    &tip_index_hash, &e
+                );
+                false
+            }
+        };
+        self.last_clarity_prune = Some((tip_height, backlog));
+    }
+
     pub fn handle_new_stacks_block(&mut self) -> Result<(), Error> {
         if let Some(pox_anchor) = self.process_ready_blocks()? {
             self.process_new_pox_anchor(pox_anchor)
@@ -736,15 +834,13 @@ impl<'a, T: BlockEventDispatcher, N: CoordinatorNotices, U: RewardSetProvider>
                         .expect("FAIL: could not find block snapshot for winning block hash")
                         .winning_block_txid;
 
-                        let block: StacksBlock = {
-                            let block_path = StacksChainState::get_block_path(
-                                &self.chain_state_db.blocks_path,
-                                &metadata.consensus_hash,
-                                &block_hash,
-                            )
-                            .unwrap();
-                            StacksChainState::consensus_load(&block_path).unwrap()
-                        };
+                        let block: StacksBlock = StacksChainState::load_block(
+                            &self.chain_state_db.blocks_path,
+                            &metadata.consensus_hash,
+                            &block_hash,
+                        )
+                        .unwrap()
+                        .expect("FAIL: processed block is not stored");
                         let stacks_block =
                             StacksBlockId::new(&metadata.consensus_hash, &block_hash);
 
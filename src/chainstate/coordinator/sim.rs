@@ -0,0 +1,881 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Scripted fork simulation.
+//!
+//! A `ForkSimulator` runs N in-process miners against a mock burnchain.  Each miner has its own
+//! node (sortition DB, burnchain DB, chainstate and coordinator), and every node sees every burn
+//! block.  Callers decide, one burn block at a time, which miners submit block-commits, which
+//! Stacks block each commit builds on, which microblock fork it confirms, whether the commit
+//! misses its target burn block, and whether the winning block is withheld from the other
+//! nodes.  Withholding a block mined in a prepare phase is how a PoX anchor block is made
+//! unavailable; `reveal()` makes it available again.  Callers then assert on each node's
+//! canonical Stacks tip, PoX ID and account balances.
+//!
+//! Available in test builds, and to other crates via the `fork-sim` feature.
+
+use std::collections::HashSet;
+use std::sync::mpsc::sync_channel;
+
+use address;
+use burnchains::db::BurnchainDB;
+use burnchains::{Burnchain, BurnchainBlockHeader, BurnchainSigner, PoxConstants, Txid};
+use chainstate::burn::db::sortdb::SortitionDB;
+use chainstate::burn::operations::leader_block_commit::BURN_BLOCK_MINED_AT_MODULUS;
+use chainstate::burn::operations::{
+    BlockstackOperationType, LeaderBlockCommitOp, LeaderKeyRegisterOp,
+};
+use chainstate::coordinator::{
+    get_next_recipients, BlockEventDispatcher, ChainsCoordinator, Error, MinerRewardInfo,
+    OnChainRewardSetProvider,
+};
+use chainstate::stacks::db::{
+    accounts::MinerReward, ChainStateBootData, ClarityTx, StacksChainState, StacksHeaderInfo,
+};
+use chainstate::stacks::events::StacksTransactionReceipt;
+use chainstate::stacks::Error as ChainstateError;
+use chainstate::stacks::*;
+use core::{CHAIN_ID_TESTNET, FIRST_STACKS_BLOCK_HASH};
+use net::atlas::AttachmentInstance;
+use util::hash::Hash160;
+use util::vrf::{VRFPrivateKey, VRFPublicKey, VRF};
+use vm::costs::ExecutionCost;
+use vm::types::PrincipalData;
+use vm::Value;
+
+use crate::types::chainstate::{
+    BlockHeaderHash, BurnchainHeaderHash, PoxId, StacksAddress, StacksBlockId, VRFSeed,
+};
+use crate::util::boot::boot_code_id;
+
+/// Event dispatcher for simulated nodes.  Simulated nodes don't announce anything.
+pub struct SimEventDispatcher;
+
+impl BlockEventDispatcher for SimEventDispatcher {
+    fn announce_block(
+        &self,
+        _block: StacksBlock,
+        _metadata: StacksHeaderInfo,
+        _receipts: Vec<StacksTransactionReceipt>,
+        _parent: &StacksBlockId,
+        _winner_txid: Txid,
+        _rewards: Vec<MinerReward>,
+        _rewards_info: Option<MinerRewardInfo>,
+    ) {
+    }
+
+    fn announce_burn_block(
+        &self,
+        _burn_block: &BurnchainHeaderHash,
+        _burn_block_height: u64,
+        _rewards: Vec<(StacksAddress, u64)>,
+        _burns: u64,
+        _slot_holders: Vec<StacksAddress>,
+    ) {
+    }
+
+    fn dispatch_boot_receipts(&mut self, _receipts: Vec<StacksTransactionReceipt>) {}
+}
+
+/// A simulated miner's keys
+#[derive(Debug, Clone)]
+pub struct SimMiner {
+    pub privkey: StacksPrivateKey,
+    pub vrf_key: VRFPrivateKey,
+}
+
+impl SimMiner {
+    pub fn address(&self) -> StacksAddress {
+        p2pkh_from(&self.privkey)
+    }
+}
+
+/// Which Stacks block a simulated commit builds on
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimParent {
+    /// The canonical Stacks tip in the committing miner's node
+    Tip,
+    /// The boot state
+    Genesis,
+    /// A specific block, which the committing miner's node must have processed
+    Block(BlockHeaderHash),
+}
+
+/// A scripted block-commit: which miner submits it, how much it burns, and what its block
+/// contains.  At most one commit per miner may appear in a burn block.
+#[derive(Debug, Clone)]
+pub struct SimCommit {
+    pub miner: usize,
+    pub burn: u64,
+    pub parent: SimParent,
+    /// Last microblock of the parent's stream to confirm, or None to confirm no microblocks.
+    pub microblock_tail: Option<BlockHeaderHash>,
+    /// Transactions to mine after the coinbase
+    pub txs: Vec<StacksTransaction>,
+    /// If set, the commit is sent with the wrong burn parent modulus, so it lands on the
+    /// burnchain but misses its target block and cannot win.
+    pub missed: bool,
+    /// If set and the commit wins, its block is only given to the miner's own node until it is
+    /// revealed.
+    pub withheld: bool,
+}
+
+impl SimCommit {
+    pub fn new(miner: usize, burn: u64) -> SimCommit {
+        SimCommit {
+            miner,
+            burn,
+            parent: SimParent::Tip,
+            microblock_tail: None,
+            txs: vec![],
+            missed: false,
+            withheld: false,
+        }
+    }
+}
+
+struct SimBlock {
+    miner: usize,
+    block: StacksBlock,
+    microblock_privkey: StacksPrivateKey,
+    microblocks: Vec<StacksMicroblock>,
+    withheld: bool,
+}
+
+struct SimNode {
+    chainstate_path: String,
+    burnchain: Burnchain,
+    burnchain_db: BurnchainDB,
+    sortdb: SortitionDB,
+    coord: ChainsCoordinator<'static, SimEventDispatcher, (), OnChainRewardSetProvider>,
+}
+
+impl SimNode {
+    fn open_chainstate(&self) -> Result<StacksChainState, Error> {
+        let (chainstate, _) =
+            StacksChainState::open(false, CHAIN_ID_TESTNET, &self.chainstate_path)?;
+        Ok(chainstate)
+    }
+}
+
+pub struct ForkSimulator {
+    miners: Vec<SimMiner>,
+    nodes: Vec<SimNode>,
+    /// winning blocks, in the order they were mined
+    blocks: Vec<SimBlock>,
+    next_burn_header: u64,
+    next_txid: u64,
+}
+
+pub fn p2pkh_from(sk: &StacksPrivateKey) -> StacksAddress {
+    let pk = StacksPublicKey::from_private(sk);
+    StacksAddress::from_public_keys(
+        C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+        &address::AddressHashMode::SerializeP2PKH,
+        1,
+        &vec![pk],
+    )
+    .unwrap()
+}
+
+/// Make a signed STX transfer that can be mined in either an anchored block or a microblock
+pub fn make_stx_transfer(
+    sender: &StacksPrivateKey,
+    nonce: u64,
+    fee: u64,
+    recipient: &PrincipalData,
+    amount: u64,
+) -> StacksTransaction {
+    let mut tx = StacksTransaction::new(
+        TransactionVersion::Testnet,
+        TransactionAuth::from_p2pkh(sender).unwrap(),
+        TransactionPayload::TokenTransfer(recipient.clone(), amount, TokenTransferMemo([0; 34])),
+    );
+    tx.chain_id = CHAIN_ID_TESTNET;
+    tx.anchor_mode = TransactionAnchorMode::Any;
+    tx.post_condition_mode = TransactionPostConditionMode::Allow;
+    tx.set_tx_fee(fee);
+    tx.set_origin_nonce(nonce);
+
+    let mut tx_signer = StacksTransactionSigner::new(&tx);
+    tx_signer.sign_origin(sender).unwrap();
+    tx_signer.get_tx().unwrap()
+}
+
+fn make_coinbase(miner: &StacksPrivateKey, nonce: u64) -> StacksTransaction {
+    let mut tx = StacksTransaction::new(
+        TransactionVersion::Testnet,
+        TransactionAuth::from_p2pkh(miner).unwrap(),
+        TransactionPayload::Coinbase(CoinbasePayload([0u8; 32])),
+    );
+    tx.chain_id = CHAIN_ID_TESTNET;
+    tx.anchor_mode = TransactionAnchorMode::OnChainOnly;
+    tx.set_origin_nonce(nonce);
+
+    let mut tx_signer = StacksTransactionSigner::new(&tx);
+    tx_signer.sign_origin(miner).unwrap();
+    tx_signer.get_tx().unwrap()
+}
+
+impl ForkSimulator {
+    /// PoX constants with short reward cycles (5 blocks, 3 of them prepare phase) and no sunset
+    pub fn default_pox_constants() -> PoxConstants {
+        PoxConstants::new(5, 3, 3, 25, 5, u64::max_value(), u64::max_value())
+    }
+
+    /// Instantiate `num_miners` miners and their nodes under `path`, which must not already
+    /// exist.  Every miner registers a VRF key in burn block 1, and the boot state credits
+    /// `initial_balances`.
+    pub fn new(
+        path: &str,
+        num_miners: usize,
+        pox_constants: PoxConstants,
+        initial_balances: Vec<(PrincipalData, u64)>,
+    ) -> Result<ForkSimulator, Error> {
+        let miners: Vec<_> = (0..num_miners)
+            .map(|_| SimMiner {
+                privkey: StacksPrivateKey::new(),
+                vrf_key: VRFPrivateKey::new(),
+            })
+            .collect();
+
+        let mut sim = ForkSimulator {
+            miners,
+            nodes: vec![],
+            blocks: vec![],
+            next_burn_header: 1,
+            next_txid: 1,
+        };
+
+        let mut dbs = vec![];
+        for ix in 0..num_miners {
+            let node_path = format!("{}/node-{}", path, ix);
+            let mut burnchain = Burnchain::regtest(&format!("{}/burnchain/db/", &node_path));
+            burnchain.pox_constants = pox_constants.clone();
+
+            let sortdb = SortitionDB::connect(
+                &burnchain.get_db_path(),
+                burnchain.first_block_height,
+                &burnchain.first_block_hash,
+                burnchain.first_block_timestamp.into(),
+                true,
+            )?;
+            let burnchain_db = BurnchainDB::connect(
+                &burnchain.get_burnchaindb_path(),
+                burnchain.first_block_height,
+                &burnchain.first_block_hash,
+                burnchain.first_block_timestamp as u64,
+                true,
+            )?;
+
+            let chainstate_path = format!("{}/chainstate/", &node_path);
+            let mut boot_data = ChainStateBootData::new(&burnchain, initial_balances.clone(), None);
+            let pox_burnchain = burnchain.clone();
+            boot_data.post_flight_callback = Some(Box::new(move |clarity_tx: &mut ClarityTx| {
+                let contract = boot_code_id("pox", false);
+                let sender = PrincipalData::from(contract.clone());
+
+                clarity_tx.connection().as_transaction(|conn| {
+                    conn.run_contract_call(
+                        &sender,
+                        &contract,
+                        "set-burnchain-parameters",
+                        &[
+                            Value::UInt(pox_burnchain.first_block_height as u128),
+                            Value::UInt(pox_burnchain.pox_constants.prepare_length as u128),
+                            Value::UInt(pox_burnchain.pox_constants.reward_cycle_length as u128),
+                            Value::UInt(pox_burnchain.pox_constants.pox_rejection_fraction as u128),
+                        ],
+                        |_, _| false,
+                    )
+                    .expect("Failed to set burnchain parameters in PoX contract");
+                });
+            }));
+            StacksChainState::open_and_exec(
+                false,
+                CHAIN_ID_TESTNET,
+                &chainstate_path,
+                Some(&mut boot_data),
+                ExecutionCost::max_value(),
+            )?;
+
+            dbs.push((node_path, chainstate_path, burnchain, burnchain_db, sortdb));
+        }
+
+        // all VRF keys are registered in burn block 1
+        let first_sortition = SortitionDB::get_canonical_burn_chain_tip(dbs[0].4.conn())?;
+        let mut registers = vec![];
+        for (ix, miner) in sim.miners.clone().into_iter().enumerate() {
+            registers.push(BlockstackOperationType::LeaderKeyRegister(
+                LeaderKeyRegisterOp {
+                    public_key: VRFPublicKey::from_private(&miner.vrf_key),
+                    consensus_hash: first_sortition.consensus_hash.clone(),
+                    memo: vec![0],
+                    address: miner.address(),
+                    vtxindex: 1 + ix as u32,
+                    block_height: 0,
+                    burn_header_hash: BurnchainHeaderHash([0; 32]),
+                    txid: sim.next_txid(),
+                },
+            ));
+        }
+
+        for (node_path, chainstate_path, burnchain, burnchain_db, sortdb) in dbs.into_iter() {
+            let (attachments_tx, _) = sync_channel::<HashSet<AttachmentInstance>>(100000);
+            let coord = ChainsCoordinator::test_new(
+                &burnchain,
+                CHAIN_ID_TESTNET,
+                &node_path,
+                OnChainRewardSetProvider(),
+                attachments_tx,
+            );
+            sim.nodes.push(SimNode {
+                chainstate_path,
+                burnchain,
+                burnchain_db,
+                sortdb,
+                coord,
+            });
+        }
+
+        sim.produce_burn_block(registers)?;
+        Ok(sim)
+    }
+
+    fn next_burn_header_hash(&mut self) -> BurnchainHeaderHash {
+        let cur = self.next_burn_header;
+        self.next_burn_header += 1;
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&cur.to_le_bytes());
+        bytes.extend_from_slice(&[0; 24]);
+        BurnchainHeaderHash::from_bytes(&bytes).unwrap()
+    }
+
+    fn next_txid(&mut self) -> Txid {
+        let cur = self.next_txid;
+        self.next_txid += 1;
+        let mut bytes = vec![];
+        bytes.extend_from_slice(&cur.to_le_bytes());
+        bytes.extend_from_slice(&[1; 24]);
+        Txid::from_bytes(&bytes).unwrap()
+    }
+
+    pub fn miners(&self) -> &[SimMiner] {
+        &self.miners
+    }
+
+    /// Store a burn block with the given (height-stamped) operations in every node's burnchain
+    /// DB, and have every node process it.
+    fn produce_burn_block(
+        &mut self,
+        mut ops: Vec<BlockstackOperationType>,
+    ) -> Result<BurnchainHeaderHash, Error> {
+        let parent = self.nodes[0].burnchain_db.get_canonical_chain_tip()?;
+        let block_hash = self.next_burn_header_hash();
+        let header = BurnchainBlockHeader {
+            block_height: parent.block_height + 1,
+            timestamp: parent.timestamp + 1,
+            num_txs: ops.len() as u64,
+            block_hash: block_hash.clone(),
+            parent_block_hash: parent.block_hash.clone(),
+        };
+
+        for op in ops.iter_mut() {
+            if op.block_height() != header.block_height {
+                op.set_block_height(header.block_height);
+            }
+            op.set_burn_header_hash(block_hash.clone());
+        }
+
+        for node in self.nodes.iter_mut() {
+            node.burnchain_db
+                .raw_store_burnchain_block(header.clone(), ops.clone())?;
+            node.coord.handle_new_burnchain_block()?;
+        }
+        Ok(block_hash)
+    }
+
+    /// Mine `commit`'s block in the committing miner's node, and make the block-commit for it.
+    fn make_commit(
+        &mut self,
+        commit: &SimCommit,
+        burn_height: u64,
+    ) -> Result<(LeaderBlockCommitOp, StacksBlock, StacksPrivateKey), Error> {
+        let miner = self.miners[commit.miner].clone();
+        let txid = self.next_txid();
+        let node = &mut self.nodes[commit.miner];
+        let mut chainstate = node.open_chainstate()?;
+
+        let sortition_tip = SortitionDB::get_canonical_burn_chain_tip(node.sortdb.conn())?;
+        let parent_block = match commit.parent {
+            SimParent::Tip => {
+                let (_, tip_hash) =
+                    SortitionDB::get_canonical_stacks_chain_tip_hash(node.sortdb.conn())?;
+                if tip_hash == FIRST_STACKS_BLOCK_HASH {
+                    None
+                } else {
+                    Some(tip_hash)
+                }
+            }
+            SimParent::Genesis => None,
+            SimParent::Block(ref block_hash) => Some(block_hash.clone()),
+        };
+
+        let (parent_header, parent_block_ptr, parent_vtxindex, total_burn) = match parent_block {
+            None => (StacksHeaderInfo::regtest_genesis(), 0, 0, 0),
+            Some(ref parent_block) => {
+                let parent_sn = SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                    &node.sortdb.index_conn(),
+                    &sortition_tip.sortition_id,
+                    parent_block,
+                )?
+                .ok_or(ChainstateError::NoSuchBlockError)?;
+                let parent_vtxindex = SortitionDB::get_block_winning_vtxindex(
+                    node.sortdb.conn(),
+                    &parent_sn.sortition_id,
+                )?
+                .ok_or(ChainstateError::NoSuchBlockError)?;
+                let parent_header = StacksChainState::get_anchored_block_header_info(
+                    chainstate.db(),
+                    &parent_sn.consensus_hash,
+                    parent_block,
+                )?
+                .ok_or(ChainstateError::NoSuchBlockError)?;
+                (
+                    parent_header,
+                    parent_sn.block_height as u32,
+                    parent_vtxindex,
+                    parent_sn.total_burn,
+                )
+            }
+        };
+
+        let recipients = get_next_recipients(
+            &sortition_tip,
+            &mut chainstate,
+            &mut node.sortdb,
+            &node.burnchain,
+            &OnChainRewardSetProvider(),
+        )?;
+        let commit_outs = if let Some(ref recipients) = recipients {
+            let mut commit_outs = recipients
+                .recipients
+                .iter()
+                .map(|(a, _)| a.clone())
+                .collect::<Vec<StacksAddress>>();
+            if commit_outs.len() == 1 {
+                // Padding with burn address if required
+                commit_outs.push(StacksAddress::burn_address(false))
+            }
+            commit_outs
+        } else if node.burnchain.is_in_prepare_phase(burn_height) {
+            vec![StacksAddress::burn_address(false)]
+        } else {
+            vec![]
+        };
+
+        let microblock_privkey = StacksPrivateKey::new();
+        let mut microblock_pubkey = StacksPublicKey::from_private(&microblock_privkey);
+        microblock_pubkey.set_compressed(true);
+
+        let proof = VRF::prove(&miner.vrf_key, sortition_tip.sortition_hash.as_bytes());
+        let mut builder = StacksBlockBuilder::make_regtest_block_builder(
+            &parent_header,
+            proof.clone(),
+            total_burn,
+            Hash160::from_node_public_key(&microblock_pubkey),
+        )?;
+        builder.set_parent_microblock_tail(commit.microblock_tail.as_ref());
+
+        let iconn = node.sortdb.index_conn();
+        let mut epoch_tx = builder.epoch_begin(&mut chainstate, &iconn)?;
+        let nonce =
+            StacksChainState::get_account(&mut epoch_tx, &PrincipalData::from(miner.address()))
+                .nonce;
+
+        let mut mined = builder.try_mine_tx(&mut epoch_tx, &make_coinbase(&miner.privkey, nonce));
+        for tx in commit.txs.iter() {
+            if mined.is_err() {
+                break;
+            }
+            mined = builder.try_mine_tx(&mut epoch_tx, tx);
+        }
+        if let Err(e) = mined {
+            builder.epoch_finish(epoch_tx);
+            return Err(e.into());
+        }
+
+        let block = builder.mine_anchored_block(&mut epoch_tx);
+        builder.epoch_finish(epoch_tx);
+
+        let commit_op = LeaderBlockCommitOp {
+            sunset_burn: 0,
+            block_header_hash: block.block_hash(),
+            burn_fee: commit.burn,
+            input: (Txid([0; 32]), 0),
+            apparent_sender: BurnchainSigner {
+                num_sigs: 1,
+                hash_mode: address::AddressHashMode::SerializeP2PKH,
+                public_keys: vec![StacksPublicKey::from_private(&miner.privkey)],
+            },
+            key_block_ptr: 1, // all registers happen in block height 1
+            key_vtxindex: (1 + commit.miner) as u16,
+            memo: vec![],
+            new_seed: VRFSeed::from_proof(&proof),
+            commit_outs,
+
+            parent_block_ptr,
+            parent_vtxindex,
+
+            txid,
+            vtxindex: (1 + commit.miner) as u32,
+            block_height: 0,
+            burn_parent_modulus: (BURN_BLOCK_MINED_AT_MODULUS - 1) as u8,
+            burn_header_hash: BurnchainHeaderHash([0; 32]),
+        };
+
+        Ok((commit_op, block, microblock_privkey))
+    }
+
+    /// Produce the next burn block, containing the given block-commits, and have every node
+    /// process it.  Commit blocks are mined in each committer's node.  The sortition winner's
+    /// block is given to every node, unless it was withheld.  Returns the winning miner and
+    /// block, if there was a sortition.
+    ///
+    /// Sortition is weighted-random among valid commits, so to script a winner, have only that
+    /// miner submit a commit that can win; the others can submit missed commits or none.
+    pub fn advance(
+        &mut self,
+        commits: &[SimCommit],
+    ) -> Result<Option<(usize, BlockHeaderHash)>, Error> {
+        let burn_height = self.nodes[0]
+            .burnchain_db
+            .get_canonical_chain_tip()?
+            .block_height
+            + 1;
+
+        let mut ops = vec![];
+        let mut candidates = vec![];
+        for commit in commits.iter() {
+            assert!(
+                commits.iter().filter(|c| c.miner == commit.miner).count() == 1,
+                "Miner {} submitted more than one commit in the same burn block",
+                commit.miner
+            );
+
+            let (mut op, block, microblock_privkey) = self.make_commit(commit, burn_height)?;
+            op.set_burn_height(burn_height);
+            if commit.missed {
+                // intended for the burn block before this one
+                op.burn_parent_modulus = ((burn_height - 2) % BURN_BLOCK_MINED_AT_MODULUS) as u8;
+            }
+            ops.push(BlockstackOperationType::LeaderBlockCommit(op));
+            candidates.push(SimBlock {
+                miner: commit.miner,
+                block,
+                microblock_privkey,
+                microblocks: vec![],
+                withheld: commit.withheld,
+            });
+        }
+
+        self.produce_burn_block(ops)?;
+
+        let mut winner = None;
+        for candidate in candidates.into_iter() {
+            let block_hash = candidate.block.block_hash();
+            let tip = SortitionDB::get_canonical_burn_chain_tip(
+                self.nodes[candidate.miner].sortdb.conn(),
+            )?;
+            if tip.sortition && tip.winning_stacks_block_hash == block_hash {
+                winner = Some((candidate.miner, block_hash));
+                self.blocks.push(candidate);
+            }
+        }
+
+        self.sync_nodes()?;
+        Ok(winner)
+    }
+
+    /// Mine a signed microblock on top of the winning block `anchored`, extending the microblock
+    /// `parent` (or starting the stream, if None).  Extending the same parent twice creates a
+    /// microblock fork.  The microblock is given to every node that was given `anchored`.
+    pub fn mine_microblock(
+        &mut self,
+        anchored: &BlockHeaderHash,
+        parent: Option<&BlockHeaderHash>,
+        txs: Vec<StacksTransaction>,
+    ) -> Result<BlockHeaderHash, Error> {
+        let sim_block = self
+            .blocks
+            .iter_mut()
+            .find(|b| b.block.block_hash() == *anchored)
+            .ok_or(ChainstateError::NoSuchBlockError)?;
+
+        let mut microblock = match parent {
+            None => StacksMicroblock::first_unsigned(anchored, txs),
+            Some(parent_hash) => {
+                let parent = sim_block
+                    .microblocks
+                    .iter()
+                    .find(|mblock| mblock.block_hash() == *parent_hash)
+                    .ok_or(ChainstateError::NoSuchBlockError)?;
+                StacksMicroblock::from_parent_unsigned(&parent.header, txs)
+                    .ok_or(ChainstateError::NoSuchBlockError)?
+            }
+        };
+        microblock
+            .sign(&sim_block.microblock_privkey)
+            .map_err(ChainstateError::NetError)?;
+
+        let microblock_hash = microblock.block_hash();
+        sim_block.microblocks.push(microblock);
+
+        self.sync_nodes()?;
+        Ok(microblock_hash)
+    }
+
+    /// Give a withheld block, and its microblocks, to every node.
+    pub fn reveal(&mut self, block_hash: &BlockHeaderHash) -> Result<(), Error> {
+        let sim_block = self
+            .blocks
+            .iter_mut()
+            .find(|b| b.block.block_hash() == *block_hash)
+            .ok_or(ChainstateError::NoSuchBlockError)?;
+        sim_block.withheld = false;
+        self.sync_nodes()
+    }
+
+    /// Give every node every block it is allowed to see, in the order they were mined.  Blocks
+    /// that a node already has are skipped, so this is also how a node catches up after a PoX
+    /// reorg changes the sortitions its blocks were stored under.
+    fn sync_nodes(&mut self) -> Result<(), Error> {
+        for (node_ix, node) in self.nodes.iter_mut().enumerate() {
+            for sim_block in self.blocks.iter() {
+                if sim_block.withheld && sim_block.miner != node_ix {
+                    continue;
+                }
+                ForkSimulator::deliver_block(node, sim_block)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn deliver_block(node: &mut SimNode, sim_block: &SimBlock) -> Result<(), Error> {
+        let block_hash = sim_block.block.block_hash();
+        let mut chainstate = node.open_chainstate()?;
+        {
+            let ic = node.sortdb.index_conn();
+            let sortition_tip = SortitionDB::get_canonical_sortition_tip(node.sortdb.conn())?;
+            let snapshot = match SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                &ic,
+                &sortition_tip,
+                &block_hash,
+            )? {
+                Some(sn) => sn,
+                // this block didn't win in this node's PoX fork
+                None => return Ok(()),
+            };
+            let parent_sn = match SortitionDB::get_block_snapshot_for_winning_stacks_block(
+                &ic,
+                &snapshot.sortition_id,
+                &sim_block.block.header.parent_block,
+            )? {
+                Some(sn) => sn,
+                None => return Ok(()),
+            };
+
+            chainstate.preprocess_anchored_block(
+                &ic,
+                &snapshot.consensus_hash,
+                &sim_block.block,
+                &parent_sn.consensus_hash,
+                0,
+            )?;
+            for microblock in sim_block.microblocks.iter() {
+                chainstate.preprocess_streamed_microblock(
+                    &snapshot.consensus_hash,
+                    &block_hash,
+                    microblock,
+                )?;
+            }
+        }
+        node.coord.handle_new_stacks_block()
+    }
+
+    /// The canonical Stacks chain tip in `node`'s view, or None if it has no blocks yet.
+    pub fn canonical_tip(&self, node: usize) -> Result<Option<BlockHeaderHash>, Error> {
+        let (_, tip_hash) =
+            SortitionDB::get_canonical_stacks_chain_tip_hash(self.nodes[node].sortdb.conn())?;
+        if tip_hash == FIRST_STACKS_BLOCK_HASH {
+            Ok(None)
+        } else {
+            Ok(Some(tip_hash))
+        }
+    }
+
+    /// The PoX ID of `node`'s canonical sortition tip
+    pub fn pox_id(&self, node: usize) -> Result<PoxId, Error> {
+        let pox_id = self.nodes[node].sortdb.index_handle_at_tip().get_pox_id()?;
+        Ok(pox_id)
+    }
+
+    /// `principal`'s total STX balance at `node`'s canonical Stacks chain tip
+    pub fn get_balance(&self, node: usize, principal: &PrincipalData) -> Result<u128, Error> {
+        let node = &self.nodes[node];
+        let (consensus_hash, block_hash) =
+            SortitionDB::get_canonical_stacks_chain_tip_hash(node.sortdb.conn())?;
+        let mut chainstate = node.open_chainstate()?;
+        chainstate
+            .with_read_only_clarity_tx(
+                &node.sortdb.index_conn(),
+                &StacksBlockId::new(&consensus_hash, &block_hash),
+                |conn| {
+                    StacksChainState::get_account(conn, principal)
+                        .stx_balance
+                        .get_total_balance()
+                },
+            )
+            .ok_or(ChainstateError::NoSuchBlockError.into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn new_sim(
+        path: &str,
+        num_miners: usize,
+        initial_balances: Vec<(PrincipalData, u64)>,
+    ) -> ForkSimulator {
+        let _r = std::fs::remove_dir_all(path);
+        ForkSimulator::new(
+            path,
+            num_miners,
+            ForkSimulator::default_pox_constants(),
+            initial_balances,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn fork_sim_longest_fork_wins() {
+        let mut sim = new_sim("/tmp/stacks-blockchain-fork-sim-longest-fork", 2, vec![]);
+
+        let (miner, a1) = sim.advance(&[SimCommit::new(0, 10000)]).unwrap().unwrap();
+        assert_eq!(miner, 0);
+        let (_, a2) = sim.advance(&[SimCommit::new(0, 10000)]).unwrap().unwrap();
+        for node in 0..2 {
+            assert_eq!(sim.canonical_tip(node).unwrap(), Some(a2.clone()));
+        }
+
+        // miner 1 forks off of a1, and overtakes a2
+        let mut commit = SimCommit::new(1, 10000);
+        commit.parent = SimParent::Block(a1.clone());
+        let (_, b2) = sim.advance(&[commit]).unwrap().unwrap();
+
+        let mut commit = SimCommit::new(1, 10000);
+        commit.parent = SimParent::Block(b2.clone());
+        let (_, b3) = sim.advance(&[commit]).unwrap().unwrap();
+
+        for node in 0..2 {
+            assert_eq!(sim.canonical_tip(node).unwrap(), Some(b3.clone()));
+        }
+    }
+
+    #[test]
+    fn fork_sim_missed_commit_cannot_win() {
+        let mut sim = new_sim("/tmp/stacks-blockchain-fork-sim-missed-commit", 2, vec![]);
+
+        let mut missed = SimCommit::new(1, 1_000_000);
+        missed.missed = true;
+        let (miner, block) = sim
+            .advance(&[SimCommit::new(0, 10000), missed.clone()])
+            .unwrap()
+            .unwrap();
+        assert_eq!(miner, 0);
+        assert_eq!(sim.canonical_tip(1).unwrap(), Some(block));
+
+        // only a missed commit means no sortition
+        assert!(sim.advance(&[missed]).unwrap().is_none());
+    }
+
+    #[test]
+    fn fork_sim_microblock_fork() {
+        let user = StacksPrivateKey::new();
+        let user_addr = PrincipalData::from(p2pkh_from(&user));
+        let alice = PrincipalData::from(p2pkh_from(&StacksPrivateKey::new()));
+        let bob = PrincipalData::from(p2pkh_from(&StacksPrivateKey::new()));
+
+        let mut sim = new_sim(
+            "/tmp/stacks-blockchain-fork-sim-microblock-fork",
+            2,
+            vec![(user_addr.clone(), 1_000_000)],
+        );
+
+        let (_, a1) = sim.advance(&[SimCommit::new(0, 10000)]).unwrap().unwrap();
+
+        // two microblock streams off of a1, spending the same nonce
+        let _to_alice = sim
+            .mine_microblock(&a1, None, vec![make_stx_transfer(&user, 0, 1, &alice, 100)])
+            .unwrap();
+        let to_bob = sim
+            .mine_microblock(&a1, None, vec![make_stx_transfer(&user, 0, 1, &bob, 200)])
+            .unwrap();
+
+        // miner 1 confirms bob's fork
+        let mut commit = SimCommit::new(1, 10000);
+        commit.microblock_tail = Some(to_bob);
+        let (_, a2) = sim.advance(&[commit]).unwrap().unwrap();
+
+        for node in 0..2 {
+            assert_eq!(sim.canonical_tip(node).unwrap(), Some(a2.clone()));
+            assert_eq!(sim.get_balance(node, &bob).unwrap(), 200);
+            assert_eq!(sim.get_balance(node, &alice).unwrap(), 0);
+            assert_eq!(sim.get_balance(node, &user_addr).unwrap(), 1_000_000 - 201);
+        }
+    }
+
+    #[test]
+    fn fork_sim_withheld_anchor_block() {
+        let mut sim = new_sim("/tmp/stacks-blockchain-fork-sim-withheld-anchor", 2, vec![]);
+
+        // miner 0 withholds its first block, which becomes the first reward cycle's anchor
+        // block, and keeps building on it.
+        let mut commit = SimCommit::new(0, 10000);
+        commit.withheld = true;
+        let (_, anchor) = sim.advance(&[commit]).unwrap().unwrap();
+
+        let mut last = anchor.clone();
+        for _ in 1..10 {
+            let (_, block) = sim.advance(&[SimCommit::new(0, 10000)]).unwrap().unwrap();
+            last = block;
+        }
+
+        assert_eq!(sim.canonical_tip(0).unwrap(), Some(last.clone()));
+        assert_eq!(sim.canonical_tip(1).unwrap(), None);
+        // the ten blocks span two reward cycles.  Node 1 is missing both of their anchor blocks,
+        // since the second one descends from the withheld block.
+        assert_eq!(sim.pox_id(0).unwrap().to_string(), "1111");
+        assert_eq!(sim.pox_id(1).unwrap().to_string(), "1100");
+
+        sim.reveal(&anchor).unwrap();
+
+        assert_eq!(sim.canonical_tip(1).unwrap(), Some(last));
+        assert_eq!(sim.pox_id(1).unwrap().to_string(), "1111");
+    }
+}
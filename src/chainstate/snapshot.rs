@@ -0,0 +1,945 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Chainstate snapshots, for bootstrapping a node without replaying the chain from genesis.
+//!
+//! A snapshot is a directory holding copies of the sortition DB, the Stacks headers DB (with
+//! its MARF index) and the Clarity state MARF, together with a `manifest.json` that names the
+//! Stacks block the snapshot was taken at, the MARF root hashes at that block, and the size and
+//! SHA-256 of each file.
+//!
+//! Importing a snapshot only trusts the index block hash of the snapshot's block, which the
+//! operator supplies out-of-band.  Everything else is checked against it: the block's header
+//! must hash to the trusted index block hash, the Clarity MARF root must be the header's
+//! `state_index_root`, the headers MARF root must be the root recorded for the block, and the
+//! sortition that selected the block must carry the consensus hash committed to by the index
+//! block hash and have the sortition MARF root it recorded.  Those roots only commit to the rest
+//! of the state through the hashes stored in the tries, so every trie's hashes are recomputed,
+//! and every side-store value is checked against the hash it is stored under.  The files are
+//! only moved into place once all of this holds.
+
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::{error, fmt};
+
+use rusqlite::{Connection, OpenFlags, NO_PARAMS};
+use sha2::{Digest, Sha256};
+
+use chainstate::burn::db::sortdb::SortitionDB;
+use chainstate::burn::ConsensusHash;
+use chainstate::stacks::db::StacksChainState;
+use chainstate::stacks::index::marf::{MarfConnection, MARF};
+use chainstate::stacks::index::Error as marf_error;
+use chainstate::stacks::Error as chainstate_error;
+use util::db::Error as db_error;
+use util::hash::Sha256Sum;
+
+use crate::types::chainstate::{
+    BlockHeaderHash, MARFValue, SortitionId, StacksBlockHeader, StacksBlockId,
+};
+use crate::types::proof::TrieHash;
+
+pub const SNAPSHOT_MANIFEST_FILE: &'static str = "manifest.json";
+pub const SNAPSHOT_FORMAT_VERSION: u32 = 1;
+
+/// Paths of the snapshotted DBs, relative to the snapshot directory.  The `chainstate/` and
+/// `sortition/` prefixes are stripped when the files are installed into a node's chainstate
+/// and sortition DB directories.
+const SNAPSHOT_HEADERS_DB: &'static str = "chainstate/vm/index.sqlite";
+const SNAPSHOT_CLARITY_DB: &'static str = "chainstate/vm/clarity/marf.sqlite";
+const SNAPSHOT_SORTITION_DB: &'static str = "sortition/marf.sqlite";
+
+#[derive(Debug)]
+pub enum Error {
+    /// I/O error reading or writing snapshot files
+    IOError(io::Error),
+    /// Error querying one of the DBs
+    DBError(db_error),
+    /// Error querying the Stacks chainstate
+    ChainstateError(chainstate_error),
+    /// Error reading a MARF
+    MARFError(marf_error),
+    /// Malformed or unreadable manifest
+    ManifestError(String),
+    /// The destination of an export or import already holds data
+    DestinationExists(String),
+    /// The block is not in the snapshotted chainstate
+    NoSuchBlock(StacksBlockId),
+    /// The snapshot is of a different block than the trusted one
+    UntrustedBlock(StacksBlockId, StacksBlockId),
+    /// A snapshot file's size or hash doesn't match the manifest
+    FileMismatch(String),
+    /// The snapshotted state is inconsistent with the trusted block
+    VerificationFailed(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::IOError(ref e) => fmt::Display::fmt(e, f),
+            Error::DBError(ref e) => fmt::Display::fmt(e, f),
+            Error::ChainstateError(ref e) => fmt::Display::fmt(e, f),
+            Error::MARFError(ref e) => fmt::Display::fmt(e, f),
+            Error::ManifestError(ref s) => write!(f, "Bad snapshot manifest: {}", s),
+            Error::DestinationExists(ref s) => write!(f, "Destination already exists: {}", s),
+            Error::NoSuchBlock(ref b) => write!(f, "No such Stacks block: {}", b),
+            Error::UntrustedBlock(ref actual, ref trusted) => write!(
+                f,
+                "Snapshot is of block {}, but the trusted block is {}",
+                actual, trusted
+            ),
+            Error::FileMismatch(ref s) => write!(f, "Snapshot file does not match manifest: {}", s),
+            Error::VerificationFailed(ref s) => write!(f, "Snapshot verification failed: {}", s),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn cause(&self) -> Option<&dyn error::Error> {
+        match *self {
+            Error::IOError(ref e) => Some(e),
+            Error::DBError(ref e) => Some(e),
+            Error::ChainstateError(ref e) => Some(e),
+            Error::MARFError(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::IOError(e)
+    }
+}
+
+impl From<db_error> for Error {
+    fn from(e: db_error) -> Error {
+        Error::DBError(e)
+    }
+}
+
+impl From<chainstate_error> for Error {
+    fn from(e: chainstate_error) -> Error {
+        Error::ChainstateError(e)
+    }
+}
+
+impl From<marf_error> for Error {
+    fn from(e: marf_error) -> Error {
+        Error::MARFError(e)
+    }
+}
+
+/// A file in a snapshot
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotFile {
+    /// path relative to the snapshot directory
+    pub path: String,
+    pub size: u64,
+    pub sha256: Sha256Sum,
+}
+
+/// Describes a snapshot: the block it was taken at, the MARF roots at that block, and its files
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub version: u32,
+    pub index_block_hash: StacksBlockId,
+    pub consensus_hash: ConsensusHash,
+    pub anchored_block_hash: BlockHeaderHash,
+    pub stacks_block_height: u64,
+    pub burn_block_height: u64,
+    /// root of the Stacks headers MARF at this block
+    pub headers_root_hash: TrieHash,
+    /// root of the Clarity state MARF at this block (the header's `state_index_root`)
+    pub clarity_root_hash: TrieHash,
+    /// root of the sortition MARF at the sortition that selected this block
+    pub sortition_root_hash: TrieHash,
+    pub files: Vec<SnapshotFile>,
+}
+
+impl SnapshotManifest {
+    pub fn load(snapshot_dir: &Path) -> Result<SnapshotManifest, Error> {
+        let manifest_file = fs::File::open(snapshot_dir.join(SNAPSHOT_MANIFEST_FILE))?;
+        let manifest: SnapshotManifest = serde_json::from_reader(manifest_file)
+            .map_err(|e| Error::ManifestError(e.to_string()))?;
+        if manifest.version != SNAPSHOT_FORMAT_VERSION {
+            return Err(Error::ManifestError(format!(
+                "unsupported version {}",
+                manifest.version
+            )));
+        }
+        Ok(manifest)
+    }
+
+    fn store(&self, snapshot_dir: &Path) -> Result<(), Error> {
+        let manifest_file = fs::File::create(snapshot_dir.join(SNAPSHOT_MANIFEST_FILE))?;
+        serde_json::to_writer_pretty(manifest_file, self)
+            .map_err(|e| Error::ManifestError(e.to_string()))
+    }
+}
+
+/// The facts about a block that a snapshot commits to, as read from the snapshotted DBs
+#[derive(Debug, Clone, PartialEq)]
+struct SnapshotBlockInfo {
+    consensus_hash: ConsensusHash,
+    anchored_block_hash: BlockHeaderHash,
+    stacks_block_height: u64,
+    burn_block_height: u64,
+    headers_root_hash: TrieHash,
+    clarity_root_hash: TrieHash,
+    sortition_root_hash: TrieHash,
+}
+
+/// Where a snapshot's DBs are on disk
+struct SnapshotDBPaths {
+    headers_db: PathBuf,
+    clarity_db: PathBuf,
+    sortition_db: PathBuf,
+}
+
+impl SnapshotDBPaths {
+    /// The DBs in the snapshot directory `snapshot_dir`
+    fn in_snapshot(snapshot_dir: &Path) -> SnapshotDBPaths {
+        SnapshotDBPaths {
+            headers_db: snapshot_dir.join(SNAPSHOT_HEADERS_DB),
+            clarity_db: snapshot_dir.join(SNAPSHOT_CLARITY_DB),
+            sortition_db: snapshot_dir.join(SNAPSHOT_SORTITION_DB),
+        }
+    }
+
+    /// The DBs of a node with the given chainstate and sortition DB directories
+    fn in_node(chainstate_dir: &Path, sortdb_dir: &Path) -> SnapshotDBPaths {
+        SnapshotDBPaths {
+            headers_db: chainstate_dir.join("vm/index.sqlite"),
+            clarity_db: chainstate_dir.join("vm/clarity/marf.sqlite"),
+            sortition_db: sortdb_dir.join("marf.sqlite"),
+        }
+    }
+
+    /// Each DB's path relative to a snapshot directory, and its path here
+    fn files(&self) -> [(&'static str, &Path); 3] {
+        [
+            (SNAPSHOT_HEADERS_DB, &self.headers_db),
+            (SNAPSHOT_CLARITY_DB, &self.clarity_db),
+            (SNAPSHOT_SORTITION_DB, &self.sortition_db),
+        ]
+    }
+}
+
+/// Read the given block's header and sortition out of the snapshotted DBs, and check that the
+/// three MARFs agree with each other and with `index_block_hash`.
+fn read_block_info(
+    dbs: &SnapshotDBPaths,
+    index_block_hash: &StacksBlockId,
+) -> Result<SnapshotBlockInfo, Error> {
+    let mut headers_marf: MARF<StacksBlockId> = MARF::from_path(&path_str(&dbs.headers_db)?)?;
+    let mut clarity_marf: MARF<StacksBlockId> = MARF::from_path(&path_str(&dbs.clarity_db)?)?;
+    let mut sortition_marf: MARF<SortitionId> = MARF::from_path(&path_str(&dbs.sortition_db)?)?;
+
+    let header_info = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+        headers_marf.sqlite_conn(),
+        index_block_hash,
+    )?
+    .ok_or_else(|| Error::NoSuchBlock(index_block_hash.clone()))?;
+
+    // the index block hash commits to both the header and the consensus hash of the
+    // sortition that selected it
+    let anchored_block_hash = header_info.anchored_header.block_hash();
+    let computed_index_block_hash =
+        StacksBlockHeader::make_index_block_hash(&header_info.consensus_hash, &anchored_block_hash);
+    if computed_index_block_hash != *index_block_hash {
+        return Err(Error::VerificationFailed(format!(
+            "header and consensus hash hash to {}, not {}",
+            computed_index_block_hash, index_block_hash
+        )));
+    }
+
+    let headers_root_hash = headers_marf.get_root_hash_at(index_block_hash)?;
+    if headers_root_hash != header_info.index_root {
+        return Err(Error::VerificationFailed(format!(
+            "headers MARF root is {}, but the block recorded {}",
+            headers_root_hash, header_info.index_root
+        )));
+    }
+
+    let clarity_root_hash = clarity_marf.get_root_hash_at(index_block_hash)?;
+    if clarity_root_hash != header_info.anchored_header.state_index_root {
+        return Err(Error::VerificationFailed(format!(
+            "Clarity MARF root is {}, but the block header commits to {}",
+            clarity_root_hash, header_info.anchored_header.state_index_root
+        )));
+    }
+
+    let snapshot = SortitionDB::get_block_snapshot_consensus(
+        sortition_marf.sqlite_conn(),
+        &header_info.consensus_hash,
+    )?
+    .ok_or_else(|| {
+        Error::VerificationFailed(format!(
+            "no sortition with consensus hash {}",
+            header_info.consensus_hash
+        ))
+    })?;
+    if !snapshot.sortition || snapshot.winning_stacks_block_hash != anchored_block_hash {
+        return Err(Error::VerificationFailed(format!(
+            "sortition {} did not select block {}",
+            snapshot.consensus_hash, anchored_block_hash
+        )));
+    }
+    if snapshot.burn_header_hash != header_info.burn_header_hash {
+        return Err(Error::VerificationFailed(format!(
+            "sortition {} is in burn block {}, but the block recorded {}",
+            snapshot.consensus_hash, snapshot.burn_header_hash, header_info.burn_header_hash
+        )));
+    }
+
+    let sortition_root_hash = sortition_marf.get_root_hash_at(&snapshot.sortition_id)?;
+    if sortition_root_hash != snapshot.index_root {
+        return Err(Error::VerificationFailed(format!(
+            "sortition MARF root is {}, but sortition {} recorded {}",
+            sortition_root_hash, snapshot.consensus_hash, snapshot.index_root
+        )));
+    }
+
+    Ok(SnapshotBlockInfo {
+        consensus_hash: header_info.consensus_hash,
+        anchored_block_hash,
+        stacks_block_height: header_info.block_height,
+        burn_block_height: snapshot.block_height,
+        headers_root_hash,
+        clarity_root_hash,
+        sortition_root_hash,
+    })
+}
+
+/// Recompute the hashes of every trie in the snapshotted MARFs, and check that every value in
+/// their side stores is stored under its own hash.  The MARF roots at the snapshot's block only
+/// commit to the rest of the state through these hashes, so this is what ties all of the
+/// snapshotted state (and not just the roots) to the trusted block.
+fn verify_state(dbs: &SnapshotDBPaths) -> Result<(), Error> {
+    let mut headers_marf: MARF<StacksBlockId> = MARF::from_path(&path_str(&dbs.headers_db)?)?;
+    let mut clarity_marf: MARF<StacksBlockId> = MARF::from_path(&path_str(&dbs.clarity_db)?)?;
+    let mut sortition_marf: MARF<SortitionId> = MARF::from_path(&path_str(&dbs.sortition_db)?)?;
+
+    let num_tries = headers_marf.verify_hashes()?
+        + clarity_marf.verify_hashes()?
+        + sortition_marf.verify_hashes()?;
+    let num_values = verify_side_store(headers_marf.sqlite_conn(), "__fork_storage", "value_hash")?
+        + verify_side_store(clarity_marf.sqlite_conn(), "data_table", "key")?
+        + verify_side_store(sortition_marf.sqlite_conn(), "__fork_storage", "value_hash")?;
+
+    debug!(
+        "Verified the hashes of {} tries and {} side-store values",
+        num_tries, num_values
+    );
+    Ok(())
+}
+
+/// Check that every value in a MARF's side store is stored under the hash the MARF would hold
+/// for it.  Returns the number of values checked.
+fn verify_side_store(conn: &Connection, table: &str, hash_column: &str) -> Result<u64, Error> {
+    let sql = format!("SELECT {}, value FROM {}", hash_column, table);
+    let mut stmt = conn.prepare(&sql).map_err(db_error::SqliteError)?;
+    let mut rows = stmt.query(NO_PARAMS).map_err(db_error::SqliteError)?;
+
+    let mut num_checked = 0;
+    while let Some(row) = rows.next().map_err(db_error::SqliteError)? {
+        let value_hash: String = row.get(0).map_err(db_error::SqliteError)?;
+        let value: String = row.get(1).map_err(db_error::SqliteError)?;
+        if MARFValue::from_value(&value).to_hex() != value_hash {
+            return Err(Error::VerificationFailed(format!(
+                "{} holds a value under {}, but it does not hash to that",
+                table, value_hash
+            )));
+        }
+        num_checked += 1;
+    }
+    Ok(num_checked)
+}
+
+fn path_str(path: &Path) -> Result<String, Error> {
+    path.to_str()
+        .map(|s| s.to_string())
+        .ok_or_else(|| Error::IOError(io::Error::new(io::ErrorKind::InvalidInput, "bad path")))
+}
+
+/// Size and SHA-256 of a file, read in chunks so that large DBs aren't loaded into memory
+fn hash_file(path: &Path) -> Result<(u64, Sha256Sum), Error> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = vec![0u8; 1 << 20];
+    let mut size = 0u64;
+    loop {
+        let nread = file.read(&mut buf)?;
+        if nread == 0 {
+            break;
+        }
+        hasher.input(&buf[0..nread]);
+        size += nread as u64;
+    }
+    let mut sha256 = [0u8; 32];
+    sha256.copy_from_slice(hasher.result().as_slice());
+    Ok((size, Sha256Sum(sha256)))
+}
+
+/// Make a transactionally-consistent copy of the sqlite DB at `src` at `dest`.  This is safe to
+/// do while a node has the DB open.
+fn copy_db(src: &Path, dest: &Path) -> Result<(), Error> {
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let conn = Connection::open_with_flags(src, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(db_error::SqliteError)?;
+    conn.execute("VACUUM INTO ?1", &[&path_str(dest)?])
+        .map_err(db_error::SqliteError)?;
+    Ok(())
+}
+
+/// Export a snapshot of the chainstate at `chainstate_path` and the sortition DB at
+/// `sortdb_path`, taken at the Stacks block `index_block_hash`, to the new directory
+/// `snapshot_path`.
+pub fn export_snapshot(
+    chainstate_path: &str,
+    sortdb_path: &str,
+    index_block_hash: &StacksBlockId,
+    snapshot_path: &str,
+) -> Result<SnapshotManifest, Error> {
+    let snapshot_dir = PathBuf::from(snapshot_path);
+    if snapshot_dir.exists() {
+        return Err(Error::DestinationExists(snapshot_path.to_string()));
+    }
+
+    let sources =
+        SnapshotDBPaths::in_node(&PathBuf::from(chainstate_path), &PathBuf::from(sortdb_path));
+    let snapshot_dbs = SnapshotDBPaths::in_snapshot(&snapshot_dir);
+
+    let result = (|| -> Result<SnapshotManifest, Error> {
+        let mut files = vec![];
+        for ((path, src), (_, dest)) in sources.files().iter().zip(snapshot_dbs.files().iter()) {
+            debug!("Snapshot {:?} to {}", src, path);
+            copy_db(src, dest)?;
+        }
+
+        // the DBs are copied one after the other, so the copies may include blocks beyond
+        // `index_block_hash`, but each of them has everything up to it.  Checking the copies
+        // (rather than the originals) ensures that they agree with each other.
+        let block_info = read_block_info(&snapshot_dbs, index_block_hash)?;
+
+        for (path, db) in snapshot_dbs.files().iter() {
+            let (size, sha256) = hash_file(db)?;
+            files.push(SnapshotFile {
+                path: path.to_string(),
+                size,
+                sha256,
+            });
+        }
+
+        let manifest = SnapshotManifest {
+            version: SNAPSHOT_FORMAT_VERSION,
+            index_block_hash: index_block_hash.clone(),
+            consensus_hash: block_info.consensus_hash,
+            anchored_block_hash: block_info.anchored_block_hash,
+            stacks_block_height: block_info.stacks_block_height,
+            burn_block_height: block_info.burn_block_height,
+            headers_root_hash: block_info.headers_root_hash,
+            clarity_root_hash: block_info.clarity_root_hash,
+            sortition_root_hash: block_info.sortition_root_hash,
+            files,
+        };
+        manifest.store(&snapshot_dir)?;
+        Ok(manifest)
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&snapshot_dir);
+    }
+    result
+}
+
+/// Verify the snapshot at `snapshot_path` against the trusted Stacks block `trusted_block`.
+/// Checks that every file matches the manifest, that the snapshotted state at the block is
+/// consistent with the block and with the manifest's MARF roots, and that all of the
+/// snapshotted state hashes to those roots.
+pub fn verify_snapshot(
+    snapshot_path: &str,
+    trusted_block: &StacksBlockId,
+) -> Result<SnapshotManifest, Error> {
+    let snapshot_dir = PathBuf::from(snapshot_path);
+    let manifest = SnapshotManifest::load(&snapshot_dir)?;
+    verify_dbs(
+        &manifest,
+        &SnapshotDBPaths::in_snapshot(&snapshot_dir),
+        trusted_block,
+    )?;
+    Ok(manifest)
+}
+
+/// Check the DBs at `dbs` against `manifest` and `trusted_block`
+fn verify_dbs(
+    manifest: &SnapshotManifest,
+    dbs: &SnapshotDBPaths,
+    trusted_block: &StacksBlockId,
+) -> Result<(), Error> {
+    if manifest.index_block_hash != *trusted_block {
+        return Err(Error::UntrustedBlock(
+            manifest.index_block_hash.clone(),
+            trusted_block.clone(),
+        ));
+    }
+
+    let mut expected_paths: Vec<_> = manifest.files.iter().map(|f| f.path.as_str()).collect();
+    expected_paths.sort();
+    let mut snapshot_paths: Vec<_> = dbs.files().iter().map(|(path, _)| *path).collect();
+    snapshot_paths.sort();
+    if expected_paths != snapshot_paths {
+        return Err(Error::ManifestError(format!(
+            "expected files {:?}, got {:?}",
+            snapshot_paths, expected_paths
+        )));
+    }
+
+    for (path, db) in dbs.files().iter() {
+        let file = manifest
+            .files
+            .iter()
+            .find(|f| f.path == *path)
+            .expect("BUG: manifest file list was checked");
+        let (size, sha256) = hash_file(db)?;
+        if size != file.size || sha256 != file.sha256 {
+            return Err(Error::FileMismatch(file.path.clone()));
+        }
+    }
+
+    let block_info = read_block_info(dbs, trusted_block)?;
+    let recorded = SnapshotBlockInfo {
+        consensus_hash: manifest.consensus_hash.clone(),
+        anchored_block_hash: manifest.anchored_block_hash.clone(),
+        stacks_block_height: manifest.stacks_block_height,
+        burn_block_height: manifest.burn_block_height,
+        headers_root_hash: manifest.headers_root_hash.clone(),
+        clarity_root_hash: manifest.clarity_root_hash.clone(),
+        sortition_root_hash: manifest.sortition_root_hash.clone(),
+    };
+    if block_info != recorded {
+        return Err(Error::VerificationFailed(format!(
+            "manifest records {:?}, but the snapshotted state has {:?}",
+            recorded, block_info
+        )));
+    }
+
+    verify_state(dbs)
+}
+
+/// A directory that is missing or empty can be installed over
+fn is_vacant(dir: &Path) -> Result<bool, Error> {
+    if !dir.exists() {
+        return Ok(true);
+    }
+    Ok(fs::read_dir(dir)?.next().is_none())
+}
+
+/// Move the staged directory `staged` to `dest`, which must be vacant
+fn install_dir(staged: &Path, dest: &Path) -> Result<(), Error> {
+    if dest.exists() {
+        fs::remove_dir(dest)?;
+    }
+    fs::rename(staged, dest)?;
+    Ok(())
+}
+
+/// Verify the snapshot at `snapshot_path` against `trusted_block`, and if it checks out, install
+/// it as the chainstate at `chainstate_path` and the sortition DB at `sortdb_path`.  Both
+/// destination directories must be missing or empty.  The snapshot directory is left untouched.
+///
+/// Each destination is staged in full in a sibling directory (so on the same filesystem), and
+/// only renamed into place once the staged copies have been verified.  If the second rename
+/// fails, the first is undone, so the node is never left with half of a snapshot installed.
+pub fn import_snapshot(
+    snapshot_path: &str,
+    trusted_block: &StacksBlockId,
+    chainstate_path: &str,
+    sortdb_path: &str,
+) -> Result<SnapshotManifest, Error> {
+    let chainstate_dir = PathBuf::from(chainstate_path);
+    let sortdb_dir = PathBuf::from(sortdb_path);
+    let chainstate_staging = PathBuf::from(format!("{}.import", chainstate_path));
+    let sortdb_staging = PathBuf::from(format!("{}.import", sortdb_path));
+    for dest in [&chainstate_dir, &sortdb_dir].iter() {
+        if !is_vacant(dest)? {
+            return Err(Error::DestinationExists(path_str(dest)?));
+        }
+    }
+    for staging in [&chainstate_staging, &sortdb_staging].iter() {
+        if staging.exists() {
+            return Err(Error::DestinationExists(path_str(staging)?));
+        }
+    }
+
+    let snapshot_dir = PathBuf::from(snapshot_path);
+    let result = (|| -> Result<SnapshotManifest, Error> {
+        // verify private copies, so the files can't change between verification and
+        // installation
+        let manifest = SnapshotManifest::load(&snapshot_dir)?;
+        let staged_dbs = SnapshotDBPaths::in_node(&chainstate_staging, &sortdb_staging);
+        for (path, staged) in staged_dbs.files().iter() {
+            if let Some(parent) = staged.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::copy(snapshot_dir.join(path), staged)?;
+        }
+        fs::create_dir_all(chainstate_staging.join("blocks"))?;
+
+        verify_dbs(&manifest, &staged_dbs, trusted_block)?;
+
+        install_dir(&chainstate_staging, &chainstate_dir)?;
+        if let Err(e) = install_dir(&sortdb_staging, &sortdb_dir) {
+            if let Err(rollback_err) = fs::rename(&chainstate_dir, &chainstate_staging) {
+                error!(
+                    "Failed to roll back the installed chainstate at {:?}: {:?}",
+                    &chainstate_dir, &rollback_err
+                );
+            }
+            return Err(e);
+        }
+        Ok(manifest)
+    })();
+
+    let _ = fs::remove_dir_all(&chainstate_staging);
+    let _ = fs::remove_dir_all(&sortdb_staging);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rusqlite::ToSql;
+
+    use chainstate::stacks::db::StacksChainState;
+    use net::test::{TestPeer, TestPeerConfig};
+    use util::hash::hex_bytes;
+
+    fn fresh_dir(name: &str) -> String {
+        let path = format!("/tmp/stacks-node-tests/snapshot/{}", name);
+        if fs::metadata(&path).is_ok() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn test_export_verify_import() {
+        let mut peer = TestPeer::new(TestPeerConfig::new("test_export_verify_import", 5110, 5111));
+        for _ in 0..3 {
+            let (burn_ops, stacks_block, microblocks) = peer.make_default_tenure();
+            peer.next_burnchain_block(burn_ops);
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+        }
+        let tip = SortitionDB::get_canonical_burn_chain_tip(peer.sortdb().conn()).unwrap();
+        let tip_block = StacksBlockHeader::make_index_block_hash(
+            &tip.canonical_stacks_tip_consensus_hash,
+            &tip.canonical_stacks_tip_hash,
+        );
+        let chainstate_path = peer.chainstate_path.clone();
+        let sortdb_path = peer.config.burnchain.get_db_path();
+
+        let snapshot_path = fresh_dir("export");
+        let manifest =
+            export_snapshot(&chainstate_path, &sortdb_path, &tip_block, &snapshot_path).unwrap();
+        assert_eq!(manifest.index_block_hash, tip_block);
+        assert_eq!(manifest.stacks_block_height, 3);
+        assert_eq!(manifest.files.len(), 3);
+
+        // exporting over an existing snapshot fails
+        match export_snapshot(&chainstate_path, &sortdb_path, &tip_block, &snapshot_path) {
+            Err(Error::DestinationExists(_)) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        assert_eq!(
+            verify_snapshot(&snapshot_path, &tip_block).unwrap(),
+            manifest
+        );
+
+        // a snapshot of some other block is rejected
+        let other_block = StacksBlockId([0x11; 32]);
+        match verify_snapshot(&snapshot_path, &other_block) {
+            Err(Error::UntrustedBlock(actual, trusted)) => {
+                assert_eq!(actual, tip_block);
+                assert_eq!(trusted, other_block);
+            }
+            res => panic!("Unexpected result: {:?}", res),
+        }
+
+        let import_chainstate_path = fresh_dir("import/chainstate");
+        let import_sortdb_path = fresh_dir("import/sortition");
+        import_snapshot(
+            &snapshot_path,
+            &tip_block,
+            &import_chainstate_path,
+            &import_sortdb_path,
+        )
+        .unwrap();
+
+        // the imported node picks up at the snapshotted block
+        let (chainstate, _) =
+            StacksChainState::open(false, peer.config.network_id, &import_chainstate_path).unwrap();
+        let header_info = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            chainstate.db(),
+            &tip_block,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(header_info.block_height, 3);
+        let sortdb = SortitionDB::open(&import_sortdb_path, false).unwrap();
+        assert_eq!(
+            SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())
+                .unwrap()
+                .consensus_hash,
+            tip.consensus_hash
+        );
+
+        // importing over existing state fails
+        match import_snapshot(
+            &snapshot_path,
+            &tip_block,
+            &import_chainstate_path,
+            &import_sortdb_path,
+        ) {
+            Err(Error::DestinationExists(_)) => {}
+            res => panic!("Unexpected result: {:?}", res),
+        }
+    }
+
+    #[test]
+    fn test_import_boots_and_processes_next_block() {
+        let mut peer = TestPeer::new(TestPeerConfig::new(
+            "test_import_boots_and_processes_next_block",
+            5114,
+            5115,
+        ));
+        for _ in 0..3 {
+            let (burn_ops, stacks_block, microblocks) = peer.make_default_tenure();
+            peer.next_burnchain_block(burn_ops);
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+        }
+
+        // the next sortition happens before the snapshot is taken, but the block it selects is
+        // first processed by the node that imports the snapshot
+        let (burn_ops, next_block, next_microblocks) = peer.make_default_tenure();
+        peer.next_burnchain_block(burn_ops);
+        let tip = SortitionDB::get_canonical_burn_chain_tip(peer.sortdb().conn()).unwrap();
+        let snapshot_block = StacksBlockHeader::make_index_block_hash(
+            &tip.canonical_stacks_tip_consensus_hash,
+            &tip.canonical_stacks_tip_hash,
+        );
+
+        let snapshot_path = fresh_dir("boot");
+        export_snapshot(
+            &peer.chainstate_path,
+            &peer.config.burnchain.get_db_path(),
+            &snapshot_block,
+            &snapshot_path,
+        )
+        .unwrap();
+        let import_chainstate_path = fresh_dir("boot-import/chainstate");
+        let import_sortdb_path = fresh_dir("boot-import/sortition");
+        import_snapshot(
+            &snapshot_path,
+            &snapshot_block,
+            &import_chainstate_path,
+            &import_sortdb_path,
+        )
+        .unwrap();
+
+        let (mut chainstate, _) =
+            StacksChainState::open(false, peer.config.network_id, &import_chainstate_path).unwrap();
+        let mut sortdb = SortitionDB::open(&import_sortdb_path, true).unwrap();
+        {
+            let ic = sortdb.index_conn();
+            let sn = SortitionDB::get_canonical_burn_chain_tip(&ic).unwrap();
+            assert_eq!(sn.consensus_hash, tip.consensus_hash);
+            chainstate
+                .preprocess_stacks_epoch(&ic, &sn, &next_block, &next_microblocks)
+                .unwrap();
+        }
+        let receipts = chainstate.process_blocks_at_tip(&mut sortdb, 1).unwrap();
+        assert_eq!(receipts.len(), 1);
+        let receipt = receipts[0].0.as_ref().unwrap();
+        assert_eq!(receipt.header.block_height, 4);
+        assert_eq!(
+            receipt.header.anchored_header.block_hash(),
+            next_block.block_hash()
+        );
+
+        // the imported node ends up with the same state as the node that took the snapshot
+        peer.process_stacks_epoch_at_tip(&next_block, &next_microblocks);
+        let next_block_id =
+            StacksBlockHeader::make_index_block_hash(&tip.consensus_hash, &next_block.block_hash());
+        let header_info = StacksChainState::get_stacks_block_header_info_by_index_block_hash(
+            peer.chainstate().db(),
+            &next_block_id,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(receipt.header.index_root, header_info.index_root);
+    }
+
+    /// Export a snapshot of `tip_block`, tamper with the snapshotted DB `db`, and update the
+    /// manifest to match, so only the checks of the snapshotted state itself can catch it.
+    /// Importing the snapshot must fail without installing anything.
+    fn assert_tampered_snapshot_rejected<F: FnOnce(&Connection)>(
+        peer: &TestPeer,
+        tip_block: &StacksBlockId,
+        name: &str,
+        db: &str,
+        tamper: F,
+    ) {
+        let snapshot_path = fresh_dir(name);
+        export_snapshot(
+            &peer.chainstate_path,
+            &peer.config.burnchain.get_db_path(),
+            tip_block,
+            &snapshot_path,
+        )
+        .unwrap();
+
+        {
+            let conn = Connection::open(&PathBuf::from(&snapshot_path).join(db)).unwrap();
+            tamper(&conn);
+        }
+        let mut manifest = SnapshotManifest::load(&PathBuf::from(&snapshot_path)).unwrap();
+        for file in manifest.files.iter_mut() {
+            let (size, sha256) =
+                hash_file(&PathBuf::from(&snapshot_path).join(&file.path)).unwrap();
+            file.size = size;
+            file.sha256 = sha256;
+        }
+        manifest.store(&PathBuf::from(&snapshot_path)).unwrap();
+
+        let import_chainstate_path = fresh_dir(&format!("{}-import/chainstate", name));
+        let import_sortdb_path = fresh_dir(&format!("{}-import/sortition", name));
+        match import_snapshot(
+            &snapshot_path,
+            tip_block,
+            &import_chainstate_path,
+            &import_sortdb_path,
+        ) {
+            Err(e) => debug!("Rejected tampered snapshot {}: {:?}", name, &e),
+            Ok(_) => panic!("Imported tampered snapshot {}", name),
+        }
+
+        // nothing was installed, and nothing was left staged
+        assert!(!PathBuf::from(&import_chainstate_path).exists());
+        assert!(!PathBuf::from(&import_sortdb_path).exists());
+        assert!(!PathBuf::from(format!("{}.import", &import_chainstate_path)).exists());
+        assert!(!PathBuf::from(format!("{}.import", &import_sortdb_path)).exists());
+    }
+
+    #[test]
+    fn test_import_rejects_tampered_snapshot() {
+        let mut peer = TestPeer::new(TestPeerConfig::new(
+            "test_import_rejects_tampered_snapshot",
+            5112,
+            5113,
+        ));
+        let mut consensus_hash = ConsensusHash([0u8; 20]);
+        let mut stacks_block_hash = BlockHeaderHash([0u8; 32]);
+        for _ in 0..3 {
+            let (burn_ops, stacks_block, microblocks) = peer.make_default_tenure();
+            let (_, _, ch) = peer.next_burnchain_block(burn_ops);
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+            consensus_hash = ch;
+            stacks_block_hash = stacks_block.block_hash();
+        }
+        let tip_block =
+            StacksBlockHeader::make_index_block_hash(&consensus_hash, &stacks_block_hash);
+
+        // the trie at the snapshot's block
+        assert_tampered_snapshot_rejected(
+            &peer,
+            &tip_block,
+            "tampered-tip",
+            SNAPSHOT_CLARITY_DB,
+            |conn| {
+                conn.execute(
+                    "UPDATE marf_data SET data = zeroblob(length(data)) WHERE block_hash = ?1",
+                    &[&tip_block],
+                )
+                .unwrap();
+            },
+        );
+
+        // a leaf in an ancestor's trie, which the roots at the snapshot's block only commit to
+        // through the hashes stored in the tries
+        assert_tampered_snapshot_rejected(
+            &peer,
+            &tip_block,
+            "tampered-ancestor",
+            SNAPSHOT_CLARITY_DB,
+            |conn| {
+                let mut stmt = conn.prepare("SELECT key FROM data_table").unwrap();
+                let value_hashes: Vec<Vec<u8>> = stmt
+                    .query_map(NO_PARAMS, |row| row.get::<_, String>(0))
+                    .unwrap()
+                    .map(|key| hex_bytes(&key.unwrap()).unwrap())
+                    .collect();
+                let mut stmt = conn
+                    .prepare("SELECT block_id, data FROM marf_data WHERE block_hash != ?1")
+                    .unwrap();
+                let tries: Vec<(u32, Vec<u8>)> = stmt
+                    .query_map(&[&tip_block], |row| Ok((row.get(0)?, row.get(1)?)))
+                    .unwrap()
+                    .map(|row| row.unwrap())
+                    .collect();
+                for (block_id, mut data) in tries.into_iter() {
+                    for value_hash in value_hashes.iter() {
+                        if let Some(pos) = data
+                            .windows(value_hash.len())
+                            .position(|w| w == &value_hash[..])
+                        {
+                            data[pos] ^= 0x01;
+                            conn.execute(
+                                "UPDATE marf_data SET data = ?1 WHERE block_id = ?2",
+                                &[&data as &dyn ToSql, &block_id],
+                            )
+                            .unwrap();
+                            return;
+                        }
+                    }
+                }
+                panic!("No ancestor trie holds a side-store value");
+            },
+        );
+
+        // a value in the Clarity side store
+        assert_tampered_snapshot_rejected(
+            &peer,
+            &tip_block,
+            "tampered-value",
+            SNAPSHOT_CLARITY_DB,
+            |conn| {
+                conn.execute(
+                    "UPDATE data_table SET value = value || ' ' \
+                     WHERE rowid = (SELECT MIN(rowid) FROM data_table)",
+                    NO_PARAMS,
+                )
+                .unwrap();
+            },
+        );
+    }
+}
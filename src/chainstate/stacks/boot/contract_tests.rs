@@ -8,6 +8,10 @@ use chainstate::stacks::boot::{
     BOOT_CODE_COST_VOTING_TESTNET as BOOT_CODE_COST_VOTING, BOOT_CODE_POX_TESTNET,
 };
 use chainstate::stacks::db::{MinerPaymentSchedule, StacksHeaderInfo};
+use chainstate::stacks::events::{
+    STXDelegateEventData, STXDelegateStackEventData, STXEventType, STXLockEventData,
+    STXRevokeDelegateEventData, StacksTransactionEvent,
+};
 use chainstate::stacks::index::MarfTrieId;
 use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
 use chainstate::stacks::*;
@@ -887,6 +891,178 @@ fn delegation_tests() {
     });
 }
 
+#[test]
+fn delegation_events() {
+    let mut sim = ClarityTestSim::new();
+    let delegator = StacksPrivateKey::new();
+    let delegate: PrincipalData = (&delegator).into();
+    let stacker: PrincipalData = (&USER_KEYS[0]).into();
+
+    sim.execute_next_block(|env| {
+        env.initialize_contract(POX_CONTRACT_TESTNET.clone(), &BOOT_CODE_POX_TESTNET)
+            .unwrap()
+    });
+    sim.execute_next_block(|env| {
+        let (result, _, events) = env
+            .execute_transaction(
+                stacker.clone(),
+                POX_CONTRACT_TESTNET.clone(),
+                "delegate-stx",
+                &symbols_from_values(vec![
+                    Value::UInt(USTX_PER_HOLDER),
+                    (&delegator).into(),
+                    Value::none(),
+                    Value::some(POX_ADDRS[0].clone()).unwrap(),
+                ]),
+            )
+            .unwrap();
+        assert_eq!(result, Value::okay_true());
+        assert_eq!(
+            events,
+            vec![StacksTransactionEvent::STXEvent(
+                STXEventType::STXDelegateEvent(STXDelegateEventData {
+                    stacker: stacker.clone(),
+                    delegate_to: delegate.clone(),
+                    amount_ustx: USTX_PER_HOLDER,
+                    until_burn_height: None,
+                    pox_addr: Some(POX_ADDRS[0].clone()),
+                })
+            )]
+        );
+
+        // failed delegations are not reported
+        let (result, _, events) = env
+            .execute_transaction(
+                stacker.clone(),
+                POX_CONTRACT_TESTNET.clone(),
+                "delegate-stx",
+                &symbols_from_values(vec![
+                    Value::UInt(USTX_PER_HOLDER),
+                    (&delegator).into(),
+                    Value::none(),
+                    Value::none(),
+                ]),
+            )
+            .unwrap();
+        assert_eq!(result, Value::error(Value::Int(20)).unwrap());
+        assert!(events.is_empty());
+
+        // only a revocation that removes a delegation is reported
+        let revoker: PrincipalData = (&USER_KEYS[1]).into();
+        env.execute_transaction(
+            revoker.clone(),
+            POX_CONTRACT_TESTNET.clone(),
+            "delegate-stx",
+            &symbols_from_values(vec![
+                Value::UInt(USTX_PER_HOLDER),
+                (&delegator).into(),
+                Value::some(Value::UInt(300)).unwrap(),
+                Value::none(),
+            ]),
+        )
+        .unwrap();
+        let (result, _, events) = env
+            .execute_transaction(
+                revoker.clone(),
+                POX_CONTRACT_TESTNET.clone(),
+                "revoke-delegate-stx",
+                &[],
+            )
+            .unwrap();
+        assert_eq!(result, Value::okay_true());
+        assert_eq!(
+            events,
+            vec![StacksTransactionEvent::STXEvent(
+                STXEventType::STXRevokeDelegateEvent(STXRevokeDelegateEventData {
+                    stacker: revoker.clone(),
+                })
+            )]
+        );
+
+        let (result, _, events) = env
+            .execute_transaction(
+                revoker.clone(),
+                POX_CONTRACT_TESTNET.clone(),
+                "revoke-delegate-stx",
+                &[],
+            )
+            .unwrap();
+        assert_eq!(result.to_string(), "(ok false)".to_string());
+        assert!(events.is_empty());
+
+        // the expiration height is reported as given, even if it doesn't fit a burnchain height
+        let (result, _, events) = env
+            .execute_transaction(
+                revoker.clone(),
+                POX_CONTRACT_TESTNET.clone(),
+                "delegate-stx",
+                &symbols_from_values(vec![
+                    Value::UInt(USTX_PER_HOLDER),
+                    (&delegator).into(),
+                    Value::some(Value::UInt(u128::MAX)).unwrap(),
+                    Value::none(),
+                ]),
+            )
+            .unwrap();
+        assert_eq!(result, Value::okay_true());
+        assert_eq!(
+            events,
+            vec![StacksTransactionEvent::STXEvent(
+                STXEventType::STXDelegateEvent(STXDelegateEventData {
+                    stacker: revoker.clone(),
+                    delegate_to: delegate.clone(),
+                    amount_ustx: USTX_PER_HOLDER,
+                    until_burn_height: Some(u128::MAX),
+                    pox_addr: None,
+                })
+            )]
+        );
+    });
+    sim.execute_next_block(|env| {
+        let burn_height = env.eval_raw("burn-block-height").unwrap().0;
+        let (result, _, events) = env
+            .execute_transaction(
+                delegate.clone(),
+                POX_CONTRACT_TESTNET.clone(),
+                "delegate-stack-stx",
+                &symbols_from_values(vec![
+                    (&USER_KEYS[0]).into(),
+                    Value::UInt(*MIN_THRESHOLD),
+                    POX_ADDRS[0].clone(),
+                    burn_height,
+                    Value::UInt(2),
+                ]),
+            )
+            .unwrap();
+        let unlock_height = result
+            .expect_result_ok()
+            .expect_tuple()
+            .get("unlock-burn-height")
+            .unwrap()
+            .to_owned()
+            .expect_u128() as u64;
+        assert_eq!(
+            events,
+            vec![
+                StacksTransactionEvent::STXEvent(STXEventType::STXDelegateStackEvent(
+                    STXDelegateStackEventData {
+                        stacker: stacker.clone(),
+                        delegate: delegate.clone(),
+                        locked_amount: *MIN_THRESHOLD,
+                        unlock_height,
+                        pox_addr: POX_ADDRS[0].clone(),
+                    }
+                )),
+                StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(STXLockEventData {
+                    locked_amount: *MIN_THRESHOLD,
+                    unlock_height,
+                    locked_address: stacker.clone(),
+                })),
+            ]
+        );
+    });
+}
+
 #[test]
 fn test_vote_withdrawal() {
     let mut sim = ClarityTestSim::new();
@@ -2861,6 +2861,122 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn test_pox_lockup_backfill_stx_locks() {
+        let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash::zero());
+        burnchain.pox_constants.reward_cycle_length = 5;
+        burnchain.pox_constants.prepare_length = 2;
+        burnchain.pox_constants.anchor_threshold = 1;
+
+        let (mut peer, mut keys) =
+            instantiate_pox_peer(&burnchain, "test-pox-lockup-backfill-stx-locks", 6014);
+
+        let alice = keys.pop().unwrap();
+        let alice_principal: PrincipalData = key_to_stacks_addr(&alice).into();
+
+        let mut tip_index_block = StacksBlockId([0u8; 32]);
+        for tenure_id in 0..2 {
+            let microblock_privkey = StacksPrivateKey::new();
+            let microblock_pubkeyhash =
+                Hash160::from_node_public_key(&StacksPublicKey::from_private(&microblock_privkey));
+            let tip =
+                SortitionDB::get_canonical_burn_chain_tip(&peer.sortdb.as_ref().unwrap().conn())
+                    .unwrap();
+
+            let (burn_ops, stacks_block, microblocks) = peer.make_tenure(
+                |ref mut miner,
+                 ref mut sortdb,
+                 ref mut chainstate,
+                 vrf_proof,
+                 ref parent_opt,
+                 ref parent_microblock_header_opt| {
+                    let parent_tip = get_parent_tip(parent_opt, chainstate, sortdb);
+                    let coinbase_tx = make_coinbase(miner, tenure_id);
+
+                    let mut block_txs = vec![coinbase_tx];
+                    if tenure_id == 1 {
+                        let alice_lockup = make_pox_lockup(
+                            &alice,
+                            0,
+                            1024 * POX_THRESHOLD_STEPS_USTX,
+                            AddressHashMode::SerializeP2PKH,
+                            key_to_stacks_addr(&alice).bytes,
+                            1,
+                            tip.block_height,
+                        );
+                        block_txs.push(alice_lockup);
+                    }
+
+                    let block_builder = StacksBlockBuilder::make_regtest_block_builder(
+                        &parent_tip,
+                        vrf_proof,
+                        tip.total_burn,
+                        microblock_pubkeyhash,
+                    )
+                    .unwrap();
+                    let (anchored_block, _size, _cost) =
+                        StacksBlockBuilder::make_anchored_block_from_txs(
+                            block_builder,
+                            chainstate,
+                            &sortdb.index_conn(),
+                            block_txs,
+                        )
+                        .unwrap();
+                    (anchored_block, vec![])
+                },
+            );
+
+            let (_, _, consensus_hash) = peer.next_burnchain_block(burn_ops.clone());
+            peer.process_stacks_epoch_at_tip(&stacks_block, &microblocks);
+            tip_index_block = StacksBlockHeader::make_index_block_hash(
+                &consensus_hash,
+                &stacks_block.block_hash(),
+            );
+        }
+
+        let alice_account = get_account(&mut peer, &alice_principal);
+        assert!(alice_account.stx_balance.amount_locked > 0);
+        let expected_lock = (
+            alice_principal.to_string(),
+            alice_account.stx_balance.unlock_height as i64,
+            tip_index_block.to_hex(),
+        );
+
+        let get_locks = |chainstate: &StacksChainState| -> Vec<(String, i64, String)> {
+            let mut stmt = chainstate
+                .db()
+                .prepare("SELECT address, unlock_height, index_block_hash FROM stx_locks")
+                .unwrap();
+            stmt.query_map(rusqlite::NO_PARAMS, |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap()
+            .map(|lock| lock.unwrap())
+            .collect()
+        };
+
+        // the lock was recorded as its block was processed
+        assert_eq!(get_locks(peer.chainstate()), vec![expected_lock.clone()]);
+
+        // a chainstate migrated after the lock was applied has it recorded from Alice's balance
+        peer.chainstate()
+            .db()
+            .execute_batch(
+                "DELETE FROM stx_locks; INSERT INTO stx_locks_backfill (pending) VALUES (1);",
+            )
+            .unwrap();
+        peer.chainstate().backfill_stx_locks().unwrap();
+        assert_eq!(get_locks(peer.chainstate()), vec![expected_lock]);
+
+        // and the backfill only runs once
+        peer.chainstate()
+            .db()
+            .execute_batch("DELETE FROM stx_locks;")
+            .unwrap();
+        peer.chainstate().backfill_stx_locks().unwrap();
+        assert!(get_locks(peer.chainstate()).is_empty());
+    }
+
     #[test]
     fn test_pox_lockup_unlock_relock() {
         let mut burnchain = Burnchain::default_unittest(0, &BurnchainHeaderHash::zero());
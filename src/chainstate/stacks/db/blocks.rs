@@ -18,11 +18,9 @@ use std::cmp;
 use std::collections::{HashMap, HashSet};
 use std::convert::From;
 use std::fmt;
-use std::fs;
 use std::io;
 use std::io::prelude::*;
 use std::io::{Read, Seek, SeekFrom, Write};
-use std::path::{Path, PathBuf};
 
 use rand::thread_rng;
 use rand::Rng;
@@ -36,6 +34,7 @@ use chainstate::burn::db::sortdb::*;
 use chainstate::burn::operations::*;
 use chainstate::burn::BlockSnapshot;
 use chainstate::stacks::db::accounts::MinerReward;
+use chainstate::stacks::db::packs::{BlockPackCompaction, BlockPackStore};
 use chainstate::stacks::db::transactions::TransactionNonceMismatch;
 use chainstate::stacks::db::*;
 use chainstate::stacks::index::MarfTrieId;
@@ -69,8 +68,8 @@ use vm::contracts::Contract;
 use vm::costs::LimitedCostTracker;
 use vm::database::{BurnStateDB, ClarityDatabase, NULL_BURN_STATE_DB, NULL_HEADER_DB};
 use vm::types::{
-    AssetIdentifier, PrincipalData, QualifiedContractIdentifier, SequenceData,
-    StandardPrincipalData, TupleData, TypeSignature, Value,
+    AssetIdentifier, ListData, OptionalData, PrincipalData, QualifiedContractIdentifier,
+    ResponseData, SequenceData, StandardPrincipalData, TupleData, TypeSignature, Value,
 };
 
 use crate::types::chainstate::{
@@ -145,6 +144,26 @@ pub enum MemPoolRejection {
     BadTransactionVersion,
     TransferRecipientIsSender(PrincipalData),
     TransferAmountMustBePositive,
+    TooManyPendingTransactions {
+        max_pending: u64,
+        principal: PrincipalData,
+    },
+    FeeRateTooLow {
+        payload_type: &'static str,
+        actual: u64,
+        expected: u64,
+    },
+    ContractTooLarge {
+        max_size: u64,
+        actual_size: u64,
+    },
+    NonceGapTooLarge {
+        max_gap: u64,
+        expected_nonce: u64,
+        actual_nonce: u64,
+        principal: PrincipalData,
+    },
+    MemPoolFull,
     DBError(db_error),
     Other(String),
 }
@@ -190,6 +209,62 @@ impl MemPoolRejection {
                 Some(json!({"recipient": recipient.to_string()})),
             ),
             TransferAmountMustBePositive => ("TransferAmountMustBePositive", None),
+            TooManyPendingTransactions {
+                max_pending,
+                principal,
+            } => (
+                "TooManyPendingTransactions",
+                Some(json!({
+                    "message": "Too many pending transactions from this principal in the mempool",
+                    "expected": max_pending,
+                    "principal": principal.to_string()
+                })),
+            ),
+            FeeRateTooLow {
+                payload_type,
+                actual,
+                expected,
+            } => (
+                "FeeRateTooLow",
+                Some(json!({
+                    "message": "Fee rate is below this node's minimum for this payload type",
+                    "payload_type": payload_type,
+                    "expected": expected,
+                    "actual": actual
+                })),
+            ),
+            ContractTooLarge {
+                max_size,
+                actual_size,
+            } => (
+                "ContractTooLarge",
+                Some(json!({
+                    "message": "Contract is larger than this node accepts",
+                    "expected": max_size,
+                    "actual": actual_size
+                })),
+            ),
+            NonceGapTooLarge {
+                max_gap,
+                expected_nonce,
+                actual_nonce,
+                principal,
+            } => (
+                "NonceGapTooLarge",
+                Some(json!({
+                    "message": "Nonce is too far ahead of the next expected nonce",
+                    "max_gap": max_gap,
+                    "expected": expected_nonce,
+                    "actual": actual_nonce,
+                    "principal": principal.to_string()
+                })),
+            ),
+            MemPoolFull => (
+                "MempoolFull",
+                Some(json!({
+                    "message": "Mempool is full, and the fee rate is too low to displace a pending transaction"
+                })),
+            ),
             BadNonces(TransactionNonceMismatch {
                 expected,
                 actual,
@@ -518,164 +593,14 @@ impl BlockStreamData {
 }
 
 impl StacksChainState {
-    fn get_index_block_pathbuf(blocks_dir: &str, index_block_hash: &StacksBlockId) -> PathBuf {
-        let block_hash_bytes = index_block_hash.as_bytes();
-        let mut block_path = PathBuf::from(blocks_dir);
-
-        block_path.push(to_hex(&block_hash_bytes[0..2]));
-        block_path.push(to_hex(&block_hash_bytes[2..4]));
-        block_path.push(format!("{}", index_block_hash));
-
-        block_path
-    }
-
-    /// Get the path to a block in the chunk store
-    pub fn get_index_block_path(
-        blocks_dir: &str,
-        index_block_hash: &StacksBlockId,
-    ) -> Result<String, Error> {
-        let block_path = StacksChainState::get_index_block_pathbuf(blocks_dir, index_block_hash);
-
-        let blocks_path_str = block_path
-            .to_str()
-            .ok_or_else(|| Error::DBError(db_error::ParseError))?
-            .to_string();
-        Ok(blocks_path_str)
-    }
-
-    /// Get the path to a block in the chunk store, given the burn header hash and block hash.
-    pub fn get_block_path(
-        blocks_dir: &str,
-        consensus_hash: &ConsensusHash,
-        block_hash: &BlockHeaderHash,
-    ) -> Result<String, Error> {
-        let index_block_hash = StacksBlockHeader::make_index_block_hash(consensus_hash, block_hash);
-        StacksChainState::get_index_block_path(blocks_dir, &index_block_hash)
-    }
-
-    /// Make a directory tree for storing this block to the chunk store, and return the block's path
-    fn make_block_dir(
-        blocks_dir: &str,
-        consensus_hash: &ConsensusHash,
-        block_hash: &BlockHeaderHash,
-    ) -> Result<String, Error> {
-        let index_block_hash = StacksBlockHeader::make_index_block_hash(consensus_hash, block_hash);
-        let block_hash_bytes = index_block_hash.as_bytes();
-        let mut block_path = PathBuf::from(blocks_dir);
-
-        block_path.push(to_hex(&block_hash_bytes[0..2]));
-        block_path.push(to_hex(&block_hash_bytes[2..4]));
-
-        let _ = StacksChainState::mkdirs(&block_path)?;
-
-        block_path.push(format!("{}", to_hex(block_hash_bytes)));
-        let blocks_path_str = block_path
-            .to_str()
-            .ok_or_else(|| Error::DBError(db_error::ParseError))?
-            .to_string();
-        Ok(blocks_path_str)
-    }
-
-    pub fn atomic_file_store<F>(
-        path: &String,
-        delete_on_error: bool,
-        mut writer: F,
-    ) -> Result<(), Error>
-    where
-        F: FnMut(&mut fs::File) -> Result<(), Error>,
-    {
-        let path_tmp = format!("{}.tmp", path);
-        let mut fd = fs::OpenOptions::new()
-            .read(false)
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&path_tmp)
-            .map_err(|e| {
-                if e.kind() == io::ErrorKind::NotFound {
-                    error!("File not found: {:?}", &path_tmp);
-                    Error::DBError(db_error::NotFoundError)
-                } else {
-                    error!("Failed to open {:?}: {:?}", &path_tmp, &e);
-                    Error::DBError(db_error::IOError(e))
-                }
-            })?;
-
-        writer(&mut fd).map_err(|e| {
-            if delete_on_error {
-                // abort
-                let _ = fs::remove_file(&path_tmp);
-            }
-            e
-        })?;
-
-        fd.sync_all()
-            .map_err(|e| Error::DBError(db_error::IOError(e)))?;
-
-        // atomically put this file in place
-        // TODO: this is atomic but not crash-consistent!  need to fsync the dir as well
-        trace!("Rename {:?} to {:?}", &path_tmp, &path);
-        fs::rename(&path_tmp, &path).map_err(|e| Error::DBError(db_error::IOError(e)))?;
-
-        Ok(())
-    }
-
-    pub fn atomic_file_write(path: &String, bytes: &Vec<u8>) -> Result<(), Error> {
-        StacksChainState::atomic_file_store(path, false, |ref mut fd| {
-            fd.write_all(bytes)
-                .map_err(|e| Error::DBError(db_error::IOError(e)))
-        })
-    }
-
-    pub fn get_file_size(path: &String) -> Result<u64, Error> {
-        let sz = match fs::metadata(path) {
-            Ok(md) => md.len(),
-            Err(e) => {
-                if e.kind() == io::ErrorKind::NotFound {
-                    return Err(Error::DBError(db_error::NotFoundError));
-                } else {
-                    error!("Failed to stat {:?}: {:?}", &path, &e);
-                    return Err(Error::DBError(db_error::IOError(e)));
-                }
-            }
-        };
-        Ok(sz)
-    }
-
-    pub fn consensus_load<T: StacksMessageCodec>(path: &String) -> Result<T, Error> {
-        let mut fd = fs::OpenOptions::new()
-            .read(true)
-            .write(false)
-            .open(path)
-            .map_err(|e| {
-                if e.kind() == io::ErrorKind::NotFound {
-                    Error::DBError(db_error::NotFoundError)
-                } else {
-                    Error::DBError(db_error::IOError(e))
-                }
-            })?;
-
-        let mut bound_reader = BoundReader::from_reader(&mut fd, MAX_MESSAGE_LEN as u64);
-        let inst = T::consensus_deserialize(&mut bound_reader).map_err(Error::CodecError)?;
-        Ok(inst)
-    }
-
     /// Do we have a stored a block in the chunk store?
     pub fn has_block_indexed(
         blocks_dir: &String,
         index_block_hash: &StacksBlockId,
     ) -> Result<bool, Error> {
-        let block_path = StacksChainState::get_index_block_path(blocks_dir, index_block_hash)?;
-        match fs::metadata(block_path) {
-            Ok(_) => Ok(true),
-            Err(e) => {
-                if e.kind() == io::ErrorKind::NotFound {
-                    Ok(false)
-                } else {
-                    Err(Error::DBError(db_error::IOError(e)))
-                }
-            }
-        }
+        BlockPackStore::with_store(blocks_dir, |store| {
+            Ok(store.get_entry(index_block_hash)?.is_some())
+        })
     }
 
     /// Have we processed and stored a particular block?
@@ -710,16 +635,23 @@ impl StacksChainState {
         block: &StacksBlock,
     ) -> Result<(), Error> {
         let block_hash = block.block_hash();
-        let block_path = StacksChainState::make_block_dir(blocks_dir, consensus_hash, &block_hash)?;
+        let index_block_hash =
+            StacksBlockHeader::make_index_block_hash(consensus_hash, &block_hash);
+
+        let mut block_bytes = vec![];
+        block
+            .consensus_serialize(&mut block_bytes)
+            .map_err(Error::CodecError)?;
 
         test_debug!(
-            "Store {}/{} to {}",
+            "Store {}/{} ({}) to {}",
             consensus_hash,
             &block_hash,
-            &block_path
+            &index_block_hash,
+            blocks_dir
         );
-        StacksChainState::atomic_file_store(&block_path, true, |ref mut fd| {
-            block.consensus_serialize(fd).map_err(Error::CodecError)
+        BlockPackStore::with_store(blocks_dir, |store| {
+            store.put(&index_block_hash, &block_bytes)
         })
     }
 
@@ -730,55 +662,24 @@ impl StacksChainState {
         consensus_hash: &ConsensusHash,
         block_hash: &BlockHeaderHash,
     ) -> Result<(), Error> {
-        let block_path =
-            StacksChainState::make_block_dir(blocks_path, consensus_hash, &block_hash)?;
-        StacksChainState::atomic_file_write(&block_path, &vec![])
+        let index_block_hash = StacksBlockHeader::make_index_block_hash(consensus_hash, block_hash);
+        BlockPackStore::with_store(blocks_path, |store| store.put_empty(&index_block_hash))
     }
 
-    /// Mark a block in the filesystem as invalid
+    /// Mark a block in the chunk store as invalid.  Its bytes, if we have them, are kept for
+    /// further analysis until orphaned blocks are dropped.
     fn free_block(
         blocks_dir: &str,
         consensus_hash: &ConsensusHash,
         block_header_hash: &BlockHeaderHash,
-    ) -> () {
-        let block_path =
-            StacksChainState::make_block_dir(blocks_dir, consensus_hash, &block_header_hash)
-                .expect("FATAL: failed to create block directory");
-
-        // already freed?
-        let sz = StacksChainState::get_file_size(&block_path)
-            .expect(&format!("FATAL: failed to stat {}", &block_path));
-
-        if sz > 0 {
-            // try make this thread-safe. It's okay if this block gets copied more than once; we
-            // only care that at least one copy survives for further analysis.
-            let random_bytes = thread_rng().gen::<[u8; 8]>();
-            let random_bytes_str = to_hex(&random_bytes);
-            let index_block_hash = StacksBlockId::new(consensus_hash, block_header_hash);
-            let mut invalid_path =
-                StacksChainState::get_index_block_pathbuf(blocks_dir, &index_block_hash);
-            invalid_path
-                .file_name()
-                .expect("FATAL: index block path did not have file name");
-            invalid_path.set_extension(&format!("invalid-{}", &random_bytes_str));
-
-            fs::copy(&block_path, &invalid_path).expect(&format!(
-                "FATAL: failed to copy '{}' to '{}'",
-                &block_path,
-                &invalid_path.to_string_lossy(),
-            ));
-
-            // truncate the original
-            fs::OpenOptions::new()
-                .read(false)
-                .write(true)
-                .truncate(true)
-                .open(&block_path)
-                .expect(&format!(
-                    "FATAL: Failed to mark block path '{}' as free",
-                    &block_path
-                ));
-        }
+    ) -> Result<(), Error> {
+        let index_block_hash = StacksBlockId::new(consensus_hash, block_header_hash);
+        BlockPackStore::with_store(blocks_dir, |store| {
+            if !store.mark_invalid(&index_block_hash)? {
+                store.put_empty(&index_block_hash)?;
+            }
+            Ok(())
+        })
     }
 
     /// Free up all state for an invalid block
@@ -786,7 +687,7 @@ impl StacksChainState {
         blocks_path: &String,
         consensus_hash: &ConsensusHash,
         block_header: &StacksBlockHeader,
-    ) -> () {
+    ) -> Result<(), Error> {
         StacksChainState::free_block(blocks_path, consensus_hash, &block_header.block_hash())
     }
 
@@ -845,33 +746,49 @@ impl StacksChainState {
         consensus_hash: &ConsensusHash,
         block_hash: &BlockHeaderHash,
     ) -> Result<Option<Vec<u8>>, Error> {
-        let block_path = StacksChainState::get_block_path(blocks_dir, consensus_hash, block_hash)?;
-        let sz = StacksChainState::get_file_size(&block_path)?;
-        if sz == 0 {
-            debug!("Zero-sized block {}", block_hash);
-            return Ok(None);
-        }
-        if sz > MAX_MESSAGE_LEN as u64 {
-            debug!("Invalid block {}: too big", block_hash);
-            return Ok(None);
-        }
+        let index_block_hash = StacksBlockHeader::make_index_block_hash(consensus_hash, block_hash);
+        BlockPackStore::with_store(blocks_dir, |store| {
+            let entry = store
+                .get_entry(&index_block_hash)?
+                .ok_or(Error::DBError(db_error::NotFoundError))?;
+            if entry.is_empty() {
+                debug!("Zero-sized block {}", block_hash);
+                return Ok(None);
+            }
+            if entry.raw_length > MAX_MESSAGE_LEN as u64 {
+                debug!("Invalid block {}: too big", block_hash);
+                return Ok(None);
+            }
 
-        let mut fd = fs::OpenOptions::new()
-            .read(true)
-            .write(false)
-            .open(&block_path)
-            .map_err(|e| {
-                if e.kind() == io::ErrorKind::NotFound {
-                    Error::DBError(db_error::NotFoundError)
-                } else {
-                    Error::DBError(db_error::IOError(e))
-                }
-            })?;
+            let bytes = store.read_entry(&entry)?;
+            Ok(Some(bytes))
+        })
+    }
 
-        let mut ret = vec![];
-        fd.read_to_end(&mut ret)
-            .map_err(|e| Error::DBError(db_error::IOError(e)))?;
-        Ok(Some(ret))
+    /// Decode the start of a block's bytes in the chunk store as a T.
+    /// Returns Ok(Some(T)) if found.
+    /// Returns Ok(None) if this block was found, but is known to be invalid
+    /// Returns Err(...) on not found or I/O error
+    fn consensus_load_block<T: StacksMessageCodec>(
+        blocks_dir: &String,
+        consensus_hash: &ConsensusHash,
+        block_hash: &BlockHeaderHash,
+    ) -> Result<Option<T>, Error> {
+        let index_block_hash = StacksBlockHeader::make_index_block_hash(consensus_hash, block_hash);
+        BlockPackStore::with_store(blocks_dir, |store| {
+            let entry = store
+                .get_entry(&index_block_hash)?
+                .ok_or(Error::DBError(db_error::NotFoundError))?;
+            if entry.is_empty() {
+                debug!("Zero-sized block {}", &block_hash);
+                return Ok(None);
+            }
+
+            let mut reader = store.open_entry(&entry)?;
+            let mut bound_reader = BoundReader::from_reader(&mut reader, MAX_MESSAGE_LEN as u64);
+            let inst = T::consensus_deserialize(&mut bound_reader).map_err(Error::CodecError)?;
+            Ok(Some(inst))
+        })
     }
 
     /// Load up a block from the chunk store (staging or confirmed)
@@ -883,15 +800,7 @@ impl StacksChainState {
         consensus_hash: &ConsensusHash,
         block_hash: &BlockHeaderHash,
     ) -> Result<Option<StacksBlock>, Error> {
-        let block_path = StacksChainState::get_block_path(blocks_dir, consensus_hash, block_hash)?;
-        let sz = StacksChainState::get_file_size(&block_path)?;
-        if sz == 0 {
-            debug!("Zero-sized block {}", &block_hash);
-            return Ok(None);
-        }
-
-        let block: StacksBlock = StacksChainState::consensus_load(&block_path)?;
-        Ok(Some(block))
+        StacksChainState::consensus_load_block(blocks_dir, consensus_hash, block_hash)
     }
 
     /// Load up an anchored block header from the chunk store.
@@ -903,15 +812,44 @@ impl StacksChainState {
         consensus_hash: &ConsensusHash,
         block_hash: &BlockHeaderHash,
     ) -> Result<Option<StacksBlockHeader>, Error> {
-        let block_path = StacksChainState::get_block_path(blocks_dir, consensus_hash, block_hash)?;
-        let sz = StacksChainState::get_file_size(&block_path)?;
-        if sz == 0 {
-            debug!("Zero-sized block {}", &block_hash);
-            return Ok(None);
-        }
+        StacksChainState::consensus_load_block(blocks_dir, consensus_hash, block_hash)
+    }
+
+    /// Set whether or not blocks stored from now on are compressed.  Blocks that are already
+    /// stored are unaffected.
+    pub fn set_block_compression(&self, compress: bool) -> Result<(), Error> {
+        self.block_packs
+            .lock()
+            .expect("FATAL: block pack store lock poisoned")
+            .set_compression(compress)
+    }
+
+    /// Drop the stored bytes of every orphaned anchored block, and compact the chunk store to
+    /// reclaim the space.  Orphaned blocks remain known as invalid, so they won't be downloaded
+    /// again.  Nothing else may read blocks while this runs (see BlockPackStore::compact()).
+    /// Returns the number of blocks dropped, and what compaction did.
+    pub fn drop_orphaned_blocks(&self) -> Result<(u64, BlockPackCompaction), Error> {
+        let sql = "SELECT index_block_hash FROM staging_blocks WHERE orphaned = 1";
+        let orphans =
+            query_row_columns::<StacksBlockId, _>(self.db(), sql, NO_PARAMS, "index_block_hash")
+                .map_err(Error::DBError)?;
+
+        let mut store = self
+            .block_packs
+            .lock()
+            .expect("FATAL: block pack store lock poisoned");
+        let dropped = store.drop_blocks(&orphans)?;
+        let compaction = store.compact()?;
 
-        let block_header: StacksBlockHeader = StacksChainState::consensus_load(&block_path)?;
-        Ok(Some(block_header))
+        debug!(
+            "Dropped {} of {} orphaned blocks; compacted {} block packs from {} to {} bytes",
+            dropped,
+            orphans.len(),
+            compaction.packs_rewritten,
+            compaction.bytes_before,
+            compaction.bytes_after
+        );
+        Ok((dropped, compaction))
     }
 
     /// Closure for defaulting to an empty microblock stream if a microblock stream file is not found
@@ -2092,16 +2030,7 @@ impl StacksChainState {
         }
 
         // mark the block as invalid if we haven't already
-        let block_path =
-            StacksChainState::get_block_path(blocks_path, consensus_hash, anchored_block_hash)?;
-        match fs::metadata(&block_path) {
-            Ok(_) => {
-                StacksChainState::free_block(blocks_path, consensus_hash, anchored_block_hash);
-            }
-            Err(_) => {
-                StacksChainState::atomic_file_write(&block_path, &vec![])?;
-            }
-        }
+        StacksChainState::free_block(blocks_path, consensus_hash, anchored_block_hash)?;
 
         Ok(())
     }
@@ -2127,8 +2056,6 @@ impl StacksChainState {
             consensus_hash,
             anchored_block_hash,
         )?;
-        let _block_path =
-            StacksChainState::make_block_dir(blocks_path, consensus_hash, anchored_block_hash)?;
 
         let rows = query_rows::<StagingBlock, _>(tx, &sql, args).map_err(Error::DBError)?;
         let block = match rows.len() {
@@ -2271,16 +2198,7 @@ impl StacksChainState {
             .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
 
         // mark the block as empty if we haven't already
-        let block_path =
-            StacksChainState::get_block_path(blocks_path, consensus_hash, anchored_block_hash)?;
-        match fs::metadata(&block_path) {
-            Ok(_) => {
-                StacksChainState::free_block(blocks_path, consensus_hash, anchored_block_hash);
-            }
-            Err(_) => {
-                StacksChainState::atomic_file_write(&block_path, &vec![])?;
-            }
-        }
+        StacksChainState::free_block(blocks_path, consensus_hash, anchored_block_hash)?;
 
         Ok(())
     }
@@ -2796,28 +2714,25 @@ impl StacksChainState {
         stream: &mut BlockStreamData,
         count: u64,
     ) -> Result<u64, Error> {
-        let block_path =
-            StacksChainState::get_index_block_path(blocks_path, &stream.index_block_hash)?;
-
-        // The reason we open a file on each call to stream data is because we don't want to
-        // exhaust the supply of file descriptors.  Maybe a future version of this code will do
-        // something like cache the set of open files so we don't have to keep re-opening them.
-        let mut file_fd = fs::OpenOptions::new()
-            .read(true)
-            .write(false)
-            .create(false)
-            .truncate(false)
-            .open(&block_path)
-            .map_err(|e| {
-                if e.kind() == io::ErrorKind::NotFound {
-                    error!("File not found: {:?}", &block_path);
-                    Error::NoSuchBlockError
-                } else {
-                    Error::ReadError(e)
+        let buf = BlockPackStore::with_store(blocks_path, |store| {
+            let entry = match store.get_entry(&stream.index_block_hash)? {
+                Some(entry) => entry,
+                None => {
+                    error!("Block not found: {}", &stream.index_block_hash);
+                    return Err(Error::NoSuchBlockError);
                 }
-            })?;
+            };
+            if entry.is_empty() {
+                return Ok(vec![]);
+            }
+            store.read_entry_range(&entry, stream.offset, count)
+        })?;
+        fd.write_all(&buf).map_err(Error::WriteError)?;
+
+        stream.offset += buf.len() as u64;
+        stream.total_bytes += buf.len() as u64;
 
-        StacksChainState::stream_data(fd, stream, &mut file_fd, count)
+        Ok(buf.len() as u64)
     }
 
     /// Stream block data from the chain state.
@@ -3946,6 +3861,7 @@ impl StacksChainState {
                             stx_burned: 0,
                             contract_analysis: None,
                             execution_cost,
+                            vm_error: None,
                         };
 
                         all_receipts.push(receipt);
@@ -3999,6 +3915,7 @@ impl StacksChainState {
                                 stx_burned: 0,
                                 contract_analysis: None,
                                 execution_cost: ExecutionCost::zero(),
+                                vm_error: None,
                             }),
                             Err(e) => {
                                 info!("TransferStx burn op processing error.";
@@ -4140,6 +4057,243 @@ impl StacksChainState {
             .map_err(Error::ClarityError)
     }
 
+    /// Find all PoX locks that expire at this block's burnchain height, and report them as unlock
+    /// events.  STX locked by PoX are unlocked lazily -- the account balance isn't touched until
+    /// it next changes -- so this only reports the unlocks; it does not alter any state.
+    /// A lock expires in the first block whose burnchain block height (as seen by Clarity) reaches
+    /// its unlock height.  Only locks recorded in the `stx_locks` table can be found.  Locks applied
+    /// before the table existed are only found in the fork that was the highest when they were
+    /// backfilled.
+    pub fn process_pox_unlocks<'a>(
+        clarity_tx: &mut ClarityTx<'a>,
+        headers_conn: &DBConn,
+    ) -> Result<Vec<StacksTransactionEvent>, Error> {
+        let (burn_height, prev_burn_height) = clarity_tx.with_clarity_db_readonly(|db| {
+            let burn_height = db.get_current_burnchain_block_height() as u64;
+            let stacks_height = db.get_current_block_height();
+            let prev_burn_height = if stacks_height >= 2 {
+                let grandparent_id = db.get_index_block_header_hash(stacks_height - 2);
+                db.get_burnchain_block_height(&grandparent_id)
+                    .expect("BUG: no burnchain block height for ancestor block")
+                    as u64
+            } else {
+                0
+            };
+            (burn_height, prev_burn_height)
+        });
+
+        if burn_height <= prev_burn_height {
+            return Ok(vec![]);
+        }
+
+        let sql = "SELECT DISTINCT address FROM stx_locks WHERE unlock_height > ?1 AND unlock_height <= ?2 ORDER BY address";
+        let args: &[&dyn ToSql] = &[&u64_to_sql(prev_burn_height)?, &u64_to_sql(burn_height)?];
+        let mut stmt = headers_conn
+            .prepare(sql)
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        let addresses = stmt
+            .query_map(args, |row| row.get::<_, String>(0))
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+        // the table holds locks from every fork, so only report the ones that apply to this one
+        let events = clarity_tx.with_clarity_db_readonly(|db| {
+            let mut events = vec![];
+            for address in addresses.iter() {
+                let principal = match PrincipalData::parse(address) {
+                    Ok(principal) => principal,
+                    Err(e) => {
+                        warn!("Unparseable address in stx_locks: {}: {:?}", address, &e);
+                        continue;
+                    }
+                };
+                let balance = db.get_account_stx_balance(&principal);
+                if balance.amount_locked > 0
+                    && balance.unlock_height > prev_burn_height
+                    && balance.unlock_height <= burn_height
+                {
+                    events.push(StacksTransactionEvent::STXEvent(
+                        STXEventType::STXUnlockEvent(STXUnlockEventData {
+                            unlocked_amount: balance.amount_locked,
+                            unlock_height: balance.unlock_height,
+                            unlocked_address: principal,
+                        }),
+                    ));
+                }
+            }
+            events
+        });
+        Ok(events)
+    }
+
+    /// Add every principal that a transaction names to `principals`: its origin and sponsor, the
+    /// contract it calls or deploys, its token transfer recipient, and any principal passed as a
+    /// contract-call argument.
+    fn collect_tx_principals(tx: &StacksTransaction, principals: &mut HashSet<PrincipalData>) {
+        fn collect_value_principals(value: &Value, principals: &mut HashSet<PrincipalData>) {
+            match value {
+                Value::Principal(principal) => {
+                    principals.insert(principal.clone());
+                }
+                Value::Optional(OptionalData {
+                    data: Some(ref inner),
+                }) => collect_value_principals(inner, principals),
+                Value::Response(ResponseData { ref data, .. }) => {
+                    collect_value_principals(data, principals)
+                }
+                Value::Sequence(SequenceData::List(ListData { ref data, .. })) => {
+                    for item in data.iter() {
+                        collect_value_principals(item, principals);
+                    }
+                }
+                Value::Tuple(TupleData { ref data_map, .. }) => {
+                    for item in data_map.values() {
+                        collect_value_principals(item, principals);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        principals.insert(tx.origin_address().into());
+        if let Some(sponsor) = tx.sponsor_address() {
+            principals.insert(sponsor.into());
+        }
+        match tx.payload {
+            TransactionPayload::TokenTransfer(ref recipient, ..) => {
+                principals.insert(recipient.clone());
+            }
+            TransactionPayload::ContractCall(ref cc) => {
+                principals.insert(PrincipalData::Contract(QualifiedContractIdentifier::new(
+                    cc.address.clone().into(),
+                    cc.contract_name.clone(),
+                )));
+                for arg in cc.function_args.iter() {
+                    collect_value_principals(arg, principals);
+                }
+            }
+            TransactionPayload::SmartContract(ref sc) => {
+                principals.insert(PrincipalData::Contract(QualifiedContractIdentifier::new(
+                    tx.origin_address().into(),
+                    sc.name.clone(),
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    /// Record the PoX locks that were applied before the `stx_locks` table existed, so that they
+    /// produce unlock events too.  Lock events aren't stored, so every principal named by a
+    /// processed transaction is looked up in the Clarity state of the highest processed block(s),
+    /// and its lock is recorded there if it hasn't expired yet.  Only runs once, on the first
+    /// open after the chainstate is migrated to the schema with `stx_locks`.
+    pub fn backfill_stx_locks(&mut self) -> Result<(), Error> {
+        let pending = query_count(
+            self.db(),
+            "SELECT COUNT(*) FROM stx_locks_backfill",
+            NO_PARAMS,
+        )?;
+        if pending == 0 {
+            return Ok(());
+        }
+
+        let tips: Vec<StacksHeaderInfo> = query_rows(
+            self.db(),
+            "SELECT * FROM block_headers WHERE block_height = (SELECT MAX(block_height) FROM block_headers)",
+            NO_PARAMS,
+        )?;
+
+        let mut principals = HashSet::new();
+        if tips.len() > 0 {
+            let blocks: Vec<StagingBlock> = query_rows(
+                self.db(),
+                "SELECT * FROM staging_blocks WHERE processed = 1 AND orphaned = 0 ORDER BY height",
+                NO_PARAMS,
+            )?;
+            info!(
+                "Scan {} processed blocks for PoX locks made before stx_locks existed",
+                blocks.len()
+            );
+
+            for block_info in blocks.iter() {
+                let block = match StacksChainState::load_block(
+                    &self.blocks_path,
+                    &block_info.consensus_hash,
+                    &block_info.anchored_block_hash,
+                ) {
+                    Ok(Some(block)) => block,
+                    Ok(None) | Err(_) => {
+                        warn!(
+                            "Processed block {}/{} could not be loaded; locks it applied will not produce unlock events",
+                            &block_info.consensus_hash, &block_info.anchored_block_hash
+                        );
+                        continue;
+                    }
+                };
+                for tx in block.txs.iter() {
+                    StacksChainState::collect_tx_principals(tx, &mut principals);
+                }
+
+                if block_info.parent_microblock_hash != EMPTY_MICROBLOCK_PARENT_HASH {
+                    let microblocks = StacksChainState::load_processed_microblock_stream_fork(
+                        self.db(),
+                        &block_info.parent_consensus_hash,
+                        &block_info.parent_anchored_block_hash,
+                        &block_info.parent_microblock_hash,
+                    )?
+                    .unwrap_or_default();
+                    for microblock in microblocks.iter() {
+                        for tx in microblock.txs.iter() {
+                            StacksChainState::collect_tx_principals(tx, &mut principals);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut locks = vec![];
+        for tip in tips.iter() {
+            let tip_id = tip.index_block_hash();
+            let burn_height = tip.burn_header_height as u64;
+            let tip_locks = self
+                .with_read_only_clarity_tx(&NULL_BURN_STATE_DB, &tip_id, |conn| {
+                    conn.with_clarity_db_readonly(|db| {
+                        let mut tip_locks = vec![];
+                        for principal in principals.iter() {
+                            let balance = db.get_account_stx_balance(principal);
+                            if balance.amount_locked > 0 && balance.unlock_height > burn_height {
+                                tip_locks.push((principal.to_string(), balance.unlock_height));
+                            }
+                        }
+                        tip_locks
+                    })
+                })
+                .unwrap_or_default();
+            for (address, unlock_height) in tip_locks.into_iter() {
+                locks.push((address, unlock_height, tip_id.clone()));
+            }
+        }
+
+        let tx = self.db_tx_begin()?;
+        let insert = "INSERT OR IGNORE INTO stx_locks (address, unlock_height, index_block_hash) VALUES (?1, ?2, ?3)";
+        for (address, unlock_height, tip_id) in locks.iter() {
+            let params: &[&dyn ToSql] = &[address, &u64_to_sql(*unlock_height)?, tip_id];
+            tx.execute(insert, params)
+                .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        }
+        tx.execute("DELETE FROM stx_locks_backfill", NO_PARAMS)
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        tx.commit()
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+        info!(
+            "Recorded {} PoX locks made before stx_locks existed",
+            locks.len()
+        );
+        Ok(())
+    }
+
     /// Given the list of matured miners, find the miner reward schedule that produced the parent
     /// of the block whose coinbase just matured.
     pub fn get_parent_matured_miner(
@@ -4287,18 +4441,28 @@ impl StacksChainState {
             let transfer_burn_ops =
                 SortitionDB::get_transfer_stx_ops(&burn_dbconn.tx(), &parent_burn_hash)?;
 
+            let parent_index_hash = StacksBlockHeader::make_index_block_hash(
+                &parent_consensus_hash,
+                &parent_block_hash,
+            );
             let parent_block_cost = StacksChainState::get_stacks_block_anchored_cost(
                 &chainstate_tx.deref().deref(),
-                &StacksBlockHeader::make_index_block_hash(
-                    &parent_consensus_hash,
-                    &parent_block_hash,
-                ),
+                &parent_index_hash,
             )?
             .expect(&format!(
                 "BUG: no execution cost found for parent block {}/{}",
                 parent_consensus_hash, parent_block_hash
             ));
 
+            if clarity_instance
+                .with_marf(|marf| marf.is_block_pruned(&parent_index_hash))
+                .map_err(Error::MARFError)?
+            {
+                // a fork off of a block this node has pruned.  It can't build on the parent's
+                // state, but the block may well be valid.
+                return Err(Error::ClarityStatePruned(parent_index_hash));
+            }
+
             let mut clarity_tx = StacksChainState::chainstate_block_begin(
                 chainstate_tx,
                 clarity_instance,
@@ -4315,6 +4479,12 @@ impl StacksChainState {
             );
             clarity_tx.reset_cost(parent_block_cost.clone());
 
+            // find PoX locks that expire in this block, before any transaction can touch them
+            let mut pox_unlock_events = StacksChainState::process_pox_unlocks(
+                &mut clarity_tx,
+                &chainstate_tx.deref().deref(),
+            )?;
+
             let matured_miner_rewards_opt = match StacksChainState::find_mature_miner_rewards(
                 &mut clarity_tx,
                 parent_chain_tip,
@@ -4371,6 +4541,11 @@ impl StacksChainState {
                     &mut clarity_tx,
                     &microblocks,
                 ) {
+                    Err((Error::ClarityStatePruned(pruned_block), _)) => {
+                        // not the microblock's fault -- this node just can't evaluate it
+                        clarity_tx.rollback_block();
+                        return Err(Error::ClarityStatePruned(pruned_block));
+                    }
                     Err((e, offending_mblock_header_hash)) => {
                         let msg = format!(
                             "Invalid Stacks microblocks {},{} (offender {}): {:?}",
@@ -4421,6 +4596,11 @@ impl StacksChainState {
             // process anchored block
             let (block_fees, block_burns, txs_receipts) =
                 match StacksChainState::process_block_transactions(&mut clarity_tx, &block) {
+                    Err(Error::ClarityStatePruned(pruned_block)) => {
+                        // not the block's fault -- this node just can't evaluate it
+                        clarity_tx.rollback_block();
+                        return Err(Error::ClarityStatePruned(pruned_block));
+                    }
                     Err(e) => {
                         let msg = format!("Invalid Stacks block {}: {:?}", block.block_hash(), &e);
                         warn!("{}", &msg);
@@ -4478,6 +4658,8 @@ impl StacksChainState {
             let (new_unlocked_ustx, mut lockup_events) =
                 StacksChainState::process_stx_unlocks(&mut clarity_tx)?;
 
+            lockup_events.append(&mut pox_unlock_events);
+
             // if any, append lockups events to the coinbase receipt
             if lockup_events.len() > 0 {
                 // Receipts are appended in order, so the first receipt should be
@@ -4606,6 +4788,7 @@ impl StacksChainState {
         .expect("FATAL: failed to advance chain tip");
 
         chainstate_tx.log_transactions_processed(&new_tip.index_block_hash(), &tx_receipts);
+        chainstate_tx.log_stx_locks(&new_tip.index_block_hash(), &tx_receipts)?;
 
         let epoch_receipt = StacksEpochReceipt {
             header: new_tip,
@@ -4927,6 +5110,18 @@ impl StacksChainState {
             &user_supports,
         ) {
             Ok(next_chain_tip_info) => next_chain_tip_info,
+            Err(Error::ClarityStatePruned(pruned_block)) => {
+                // the block reads or builds on state this node has pruned.  Archival nodes can
+                // still evaluate it, so it must not be marked invalid -- leave it staged, and stop
+                // here.
+                error!(
+                    "Cannot process block {}/{}: it needs Clarity state at {}, which this node has pruned. Resync the node, or raise its prune depth.",
+                    &next_staging_block.consensus_hash,
+                    &block.block_hash(),
+                    &pruned_block
+                );
+                return Err(Error::ClarityStatePruned(pruned_block));
+            }
             Err(e) => {
                 // something's wrong with this epoch -- either a microblock was invalid, or the
                 // anchored block was invalid.  Either way, the anchored block will _never be_
@@ -4948,7 +5143,7 @@ impl StacksChainState {
                     &blocks_path,
                     &next_staging_block.consensus_hash,
                     &block.header,
-                );
+                )?;
 
                 match e {
                     Error::InvalidStacksMicroblock(ref msg, ref header_hash) => {
@@ -5280,6 +5475,41 @@ impl StacksChainState {
         }
     }
 
+    /// Get the nonce of an account as of the given chain tip, or as of the unconfirmed
+    /// microblock stream trailing off of it if we have one and it's further along.
+    /// Used to apply mempool admission policies that depend on the account's next nonce.
+    pub fn get_mempool_account_nonce(
+        &mut self,
+        current_consensus_hash: &ConsensusHash,
+        current_block: &BlockHeaderHash,
+        principal: &PrincipalData,
+    ) -> Result<u64, MemPoolRejection> {
+        let current_tip =
+            StacksChainState::get_parent_index_block(current_consensus_hash, current_block);
+        let confirmed_nonce = self
+            .with_read_only_clarity_tx(&NULL_BURN_STATE_DB, &current_tip, |conn| {
+                conn.with_clarity_db_readonly(|db| db.get_account_nonce(principal))
+            })
+            .ok_or_else(|| {
+                MemPoolRejection::NoSuchChainTip(
+                    current_consensus_hash.clone(),
+                    current_block.clone(),
+                )
+            })?;
+
+        let unconfirmed_nonce = if self.unconfirmed_state.is_some() {
+            self.with_read_only_unconfirmed_clarity_tx(&NULL_BURN_STATE_DB, |conn| {
+                conn.with_clarity_db_readonly(|db| db.get_account_nonce(principal))
+            })
+            .unwrap_or(None)
+            .unwrap_or(0)
+        } else {
+            0
+        };
+
+        Ok(cmp::max(confirmed_nonce, unconfirmed_nonce))
+    }
+
     /// Given an outstanding clarity connection, can we append the tx to the chain state?
     /// Used when mining transactions.
     fn can_include_tx<T: ClarityConnection>(
@@ -6156,13 +6386,14 @@ pub mod test {
         let chainstate =
             instantiate_chainstate(false, 0x80000000, "stacks_db_block_load_store_empty");
 
-        let path = StacksChainState::get_block_path(
-            &chainstate.blocks_path,
+        let index_block_hash = StacksBlockHeader::make_index_block_hash(
             &ConsensusHash([1u8; 20]),
             &BlockHeaderHash([2u8; 32]),
-        )
-        .unwrap();
-        assert!(fs::metadata(&path).is_err());
+        );
+        assert!(
+            !StacksChainState::has_block_indexed(&chainstate.blocks_path, &index_block_hash)
+                .unwrap()
+        );
         assert!(!StacksChainState::has_stored_block(
             &chainstate.db(),
             &chainstate.blocks_path,
@@ -6177,7 +6408,10 @@ pub mod test {
             &BlockHeaderHash([2u8; 32]),
         )
         .unwrap();
-        assert!(fs::metadata(&path).is_ok());
+        assert!(
+            StacksChainState::has_block_indexed(&chainstate.blocks_path, &index_block_hash)
+                .unwrap()
+        );
         assert!(StacksChainState::has_stored_block(
             &chainstate.db(),
             &chainstate.blocks_path,
@@ -6208,13 +6442,14 @@ pub mod test {
         block.header.parent_microblock_sequence = 0;
         block.header.parent_microblock = EMPTY_MICROBLOCK_PARENT_HASH.clone();
 
-        let path = StacksChainState::get_block_path(
-            &chainstate.blocks_path,
+        let index_block_hash = StacksBlockHeader::make_index_block_hash(
             &ConsensusHash([1u8; 20]),
             &block.block_hash(),
-        )
-        .unwrap();
-        assert!(fs::metadata(&path).is_err());
+        );
+        assert!(
+            !StacksChainState::has_block_indexed(&chainstate.blocks_path, &index_block_hash)
+                .unwrap()
+        );
         assert!(!StacksChainState::has_stored_block(
             &chainstate.db(),
             &chainstate.blocks_path,
@@ -6225,7 +6460,10 @@ pub mod test {
 
         StacksChainState::store_block(&chainstate.blocks_path, &ConsensusHash([1u8; 20]), &block)
             .unwrap();
-        assert!(fs::metadata(&path).is_ok());
+        assert!(
+            StacksChainState::has_block_indexed(&chainstate.blocks_path, &index_block_hash)
+                .unwrap()
+        );
         assert!(StacksChainState::has_stored_block(
             &chainstate.db(),
             &chainstate.blocks_path,
@@ -6265,7 +6503,8 @@ pub mod test {
             &chainstate.blocks_path,
             &ConsensusHash([1u8; 20]),
             &block.header,
-        );
+        )
+        .unwrap();
 
         assert!(StacksChainState::has_stored_block(
             &chainstate.db(),
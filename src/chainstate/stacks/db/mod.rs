@@ -43,6 +43,7 @@ use chainstate::stacks::events::*;
 use chainstate::stacks::index::marf::{
     MarfConnection, BLOCK_HASH_TO_HEIGHT_MAPPING_KEY, BLOCK_HEIGHT_TO_HASH_MAPPING_KEY, MARF,
 };
+use chainstate::stacks::index::prune::PruneStats;
 use chainstate::stacks::index::storage::TrieFileStorage;
 use chainstate::stacks::index::MarfTrieId;
 use chainstate::stacks::Error;
@@ -60,8 +61,8 @@ use net::atlas::BNS_CHARS_REGEX;
 use net::Error as net_error;
 use util::db::Error as db_error;
 use util::db::{
-    db_mkdirs, query_count, query_row, tx_begin_immediate, tx_busy_handler, DBConn, DBTx,
-    FromColumn, FromRow, IndexDBConn, IndexDBTx,
+    db_mkdirs, query_count, query_row, tx_begin_immediate, tx_busy_handler, u64_to_sql, DBConn,
+    DBSchema, DBTx, FromColumn, FromRow, IndexDBConn, IndexDBTx, SchemaMigration,
 };
 use util::hash::to_hex;
 use vm::analysis::analysis_db::AnalysisDatabase;
@@ -89,6 +90,7 @@ pub mod accounts;
 pub mod blocks;
 pub mod contracts;
 pub mod headers;
+pub mod packs;
 pub mod transactions;
 pub mod unconfirmed;
 
@@ -103,6 +105,7 @@ pub struct StacksChainState {
     pub clarity_state: ClarityInstance,
     pub state_index: MARF<StacksBlockId>,
     pub blocks_path: String,
+    pub block_packs: packs::SharedBlockPackStore,
     pub clarity_state_index_path: String, // path to clarity MARF
     pub clarity_state_index_root: String, // path to dir containing clarity MARF and side-store
     pub root_path: String,
@@ -428,6 +431,32 @@ impl<'a> ChainstateTx<'a> {
             }
         }
     }
+
+    /// Record every PoX lock applied in this block, so that the block which expires it can
+    /// report an unlock event.
+    pub fn log_stx_locks(
+        &self,
+        block_id: &StacksBlockId,
+        receipts: &[StacksTransactionReceipt],
+    ) -> Result<(), Error> {
+        let insert = "INSERT OR IGNORE INTO stx_locks (address, unlock_height, index_block_hash) VALUES (?1, ?2, ?3)";
+        for receipt in receipts.iter() {
+            for event in receipt.events.iter() {
+                if let StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(ref data)) =
+                    event
+                {
+                    let address = data.locked_address.to_string();
+                    let unlock_height = u64_to_sql(data.unlock_height)?;
+                    let params: &[&dyn ToSql] = &[&address, &unlock_height, block_id];
+                    self.tx
+                        .tx()
+                        .execute(insert, params)
+                        .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 impl<'a> Deref for ChainstateTx<'a> {
@@ -461,8 +490,6 @@ pub struct BlockStreamData {
     num_mblocks_ptr: usize,
 }
 
-pub const CHAINSTATE_VERSION: &'static str = "1";
-
 const CHAINSTATE_INITIAL_SCHEMA: &'static [&'static str] = &[
     "PRAGMA foreign_keys = ON;",
     r#"
@@ -613,6 +640,42 @@ const CHAINSTATE_INITIAL_SCHEMA: &'static [&'static str] = &[
     "CREATE INDEX index_block_hash_tx_index ON transactions(index_block_hash);",
 ];
 
+/// Locks are recorded as blocks are processed.  Locks applied before the migration are backfilled
+/// from the Clarity state the first time the migrated chainstate is opened; see
+/// `backfill_stx_locks()`.
+const CHAINSTATE_STX_LOCKS_SCHEMA: &'static [&'static str] = &[
+    r#"
+    -- PoX locks, so that their expiry can be reported without scanning every account
+    CREATE TABLE IF NOT EXISTS stx_locks(
+        address TEXT NOT NULL,
+        unlock_height INTEGER NOT NULL,
+        index_block_hash TEXT NOT NULL,
+        PRIMARY KEY(address, unlock_height, index_block_hash)
+    );"#,
+    "CREATE INDEX IF NOT EXISTS stx_locks_unlock_height ON stx_locks(unlock_height);",
+    r#"
+    -- non-empty until the locks applied before stx_locks existed have been recorded
+    CREATE TABLE IF NOT EXISTS stx_locks_backfill(pending INTEGER NOT NULL);
+    "#,
+    "INSERT INTO stx_locks_backfill (pending) VALUES (1);",
+];
+
+pub const CHAINSTATE_SCHEMA: DBSchema = DBSchema {
+    name: "chainstate",
+    migrations: &[
+        SchemaMigration {
+            version: 1,
+            description: "initial schema",
+            statements: CHAINSTATE_INITIAL_SCHEMA,
+        },
+        SchemaMigration {
+            version: 2,
+            description: "index stx locks",
+            statements: CHAINSTATE_STX_LOCKS_SCHEMA,
+        },
+    ],
+};
+
 #[cfg(test)]
 pub const MINER_REWARD_MATURITY: u64 = 2; // small for testing purposes
 
@@ -718,14 +781,12 @@ impl StacksChainState {
         {
             let tx = dbtx.tx();
 
-            for cmd in CHAINSTATE_INITIAL_SCHEMA {
-                tx.execute_batch(cmd)?;
-            }
+            CHAINSTATE_SCHEMA.instantiate(tx)?;
 
             tx.execute(
                 "INSERT INTO db_config (version,mainnet,chain_id) VALUES (?1,?2,?3)",
                 &[
-                    &CHAINSTATE_VERSION,
+                    &CHAINSTATE_SCHEMA.latest_version().to_string(),
                     &(if mainnet { 1 } else { 0 }) as &dyn ToSql,
                     &chain_id as &dyn ToSql,
                 ],
@@ -748,6 +809,8 @@ impl StacksChainState {
             // instantiate!
             StacksChainState::instantiate_db(mainnet, chain_id, index_path)
         } else {
+            CHAINSTATE_SCHEMA.migrate_path(index_path, true)?;
+
             let marf = StacksChainState::open_index(index_path)?;
             // sanity check
             let db_config = query_row::<DBConfig, _>(
@@ -765,14 +828,6 @@ impl StacksChainState {
                 return Err(Error::InvalidChainstateDB);
             }
 
-            if db_config.version != CHAINSTATE_VERSION {
-                error!(
-                    "Invalid chain state database: expected version = {}, got {}",
-                    CHAINSTATE_VERSION, db_config.version
-                );
-                return Err(Error::InvalidChainstateDB);
-            }
-
             if db_config.chain_id != chain_id {
                 error!(
                     "Invalid chain ID: expected {}, got {}",
@@ -1377,6 +1432,9 @@ impl StacksChainState {
             .ok_or_else(|| Error::DBError(db_error::ParseError))?
             .to_string();
 
+        // moves any blocks stored a file apiece into block packs
+        let block_packs = packs::BlockPackStore::instantiate_shared(&blocks_path_root)?;
+
         let mut state_path = path;
 
         state_path.push("vm");
@@ -1427,6 +1485,7 @@ impl StacksChainState {
             clarity_state: clarity_state,
             state_index: state_index,
             blocks_path: blocks_path_root,
+            block_packs: block_packs,
             clarity_state_index_path: clarity_state_index_marf,
             clarity_state_index_root: clarity_state_index_root,
             root_path: path_str.to_string(),
@@ -1449,6 +1508,8 @@ impl StacksChainState {
             (false, _) => {}
         }
 
+        chainstate.backfill_stx_locks()?;
+
         Ok((chainstate, receipts))
     }
 
@@ -1456,7 +1517,7 @@ impl StacksChainState {
         DBConfig {
             mainnet: self.mainnet,
             chain_id: self.chain_id,
-            version: CHAINSTATE_VERSION.to_string(),
+            version: CHAINSTATE_SCHEMA.latest_version().to_string(),
         }
     }
 
@@ -1641,6 +1702,29 @@ impl StacksChainState {
         self.clarity_state.with_marf(f)
     }
 
+    /// Prune the Clarity state of blocks more than `depth` blocks below `tip`, oldest first and
+    /// at most `max_tries` of them per call.  Afterwards, the state at those blocks can no longer
+    /// be read, and they can no longer be built upon.  This means that a block whose
+    /// transactions `at-block` into a pruned block can't be processed, and stays staged (see
+    /// Error::ClarityStatePruned), as does a block that forks off of a pruned block.
+    /// Returns the trie pruning stats and the number of side-store values deleted.
+    pub fn prune_clarity_state(
+        &mut self,
+        tip: &StacksBlockId,
+        depth: u32,
+        max_tries: usize,
+    ) -> Result<(PruneStats, u64), Error> {
+        self.clarity_state
+            .prune_state(tip, depth, max_tries)
+            .map_err(Error::MARFError)
+    }
+
+    /// Has the Clarity state at this block been pruned?
+    pub fn is_clarity_state_pruned(&mut self, index_block: &StacksBlockId) -> Result<bool, Error> {
+        self.with_clarity_marf(|marf| marf.is_block_pruned(index_block))
+            .map_err(Error::MARFError)
+    }
+
     fn begin_read_only_clarity_tx<'a>(
         &'a mut self,
         burn_dbconn: &'a dyn BurnStateDB,
@@ -1675,6 +1759,20 @@ impl StacksChainState {
                 return None;
             }
         }
+        match self.is_clarity_state_pruned(parent_tip) {
+            Ok(false) => {}
+            Ok(true) => {
+                warn!(
+                    "Cannot read Clarity state at {}: it has been pruned",
+                    parent_tip
+                );
+                return None;
+            }
+            Err(e) => {
+                warn!("Failed to query for {}: {:?}", parent_tip, &e);
+                return None;
+            }
+        }
         let mut conn = self.begin_read_only_clarity_tx(burn_dbconn, parent_tip);
         let result = to_do(&mut conn);
         Some(result)
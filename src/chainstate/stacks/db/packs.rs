@@ -0,0 +1,1075 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Packed storage for anchored Stacks blocks.
+//!
+//! Blocks are appended to a few large pack files in the chainstate's blocks directory, instead of
+//! being written to a file apiece.  A SQLite index (`packs.sqlite`) maps each block's index block
+//! hash to its pack, offset, and length.  An append writes past the end of the pack that the index
+//! knows about, and syncs the pack before the index is updated, so a crash mid-append only leaves
+//! unreferenced bytes behind for the next append to overwrite.
+//!
+//! Entries can be stored deflate-compressed.  Compression is a setting of the store, and each
+//! entry records whether it was compressed, so changing the setting only affects new entries.
+//!
+//! An invalid block's entry keeps its bytes for later analysis, but reads as an empty block.
+//! Dropping a block empties its entry, and `compact()` reclaims the space it used.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+
+use libflate::deflate;
+use rusqlite::types::ToSql;
+use rusqlite::{Connection, OpenFlags, Row, NO_PARAMS};
+
+use chainstate::stacks::Error;
+use util::db::Error as db_error;
+use util::db::{
+    query_int, query_row, query_rows, tx_begin_immediate, tx_busy_handler, u64_to_sql, DBConn,
+    DBTx, FromColumn, FromRow,
+};
+use util::hash::to_hex;
+
+use crate::codec::MAX_MESSAGE_LEN;
+use crate::types::chainstate::StacksBlockId;
+
+/// Name of the pack index within the blocks directory
+pub const BLOCK_PACKS_INDEX: &'static str = "packs.sqlite";
+
+/// Appends go to a new pack once the current one would grow past this size
+pub const BLOCK_PACK_MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Legacy block files are moved into packs in batches of up to this many bytes
+const MIGRATION_BATCH_BYTES: u64 = 64 * 1024 * 1024;
+
+const BLOCK_PACKS_SCHEMA: &'static [&'static str] = &[
+    r#"
+    CREATE TABLE IF NOT EXISTS block_packs(
+        pack_id INTEGER PRIMARY KEY NOT NULL,
+        -- end of the last entry written to this pack.  Anything after it is garbage.
+        size INTEGER NOT NULL
+    );"#,
+    r#"
+    CREATE TABLE IF NOT EXISTS block_pack_entries(
+        index_block_hash TEXT PRIMARY KEY NOT NULL,
+        pack_id INTEGER NOT NULL,
+        offset INTEGER NOT NULL,
+        length INTEGER NOT NULL,        -- number of bytes stored in the pack
+        raw_length INTEGER NOT NULL,    -- number of bytes in the block
+        compressed INTEGER NOT NULL,
+        invalid INTEGER NOT NULL
+    );"#,
+    "CREATE INDEX IF NOT EXISTS block_pack_entries_by_pack ON block_pack_entries(pack_id,offset);",
+    r#"
+    CREATE TABLE IF NOT EXISTS block_packs_config(
+        compress INTEGER NOT NULL
+    );"#,
+    "INSERT INTO block_packs_config (compress) SELECT 0 WHERE NOT EXISTS (SELECT 1 FROM block_packs_config);",
+];
+
+/// Where a block's bytes live
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockPackEntry {
+    pub index_block_hash: StacksBlockId,
+    pub pack_id: u64,
+    pub offset: u64,
+    /// Number of bytes stored in the pack
+    pub length: u64,
+    /// Number of bytes in the block
+    pub raw_length: u64,
+    pub compressed: bool,
+    pub invalid: bool,
+}
+
+impl BlockPackEntry {
+    /// Does this entry read as an empty block?  This is the case for invalid and dropped blocks.
+    pub fn is_empty(&self) -> bool {
+        self.invalid || self.raw_length == 0
+    }
+}
+
+impl FromRow<BlockPackEntry> for BlockPackEntry {
+    fn from_row<'a>(row: &'a Row) -> Result<BlockPackEntry, db_error> {
+        let index_block_hash = StacksBlockId::from_column(row, "index_block_hash")?;
+        let pack_id = u64::from_column(row, "pack_id")?;
+        let offset = u64::from_column(row, "offset")?;
+        let length = u64::from_column(row, "length")?;
+        let raw_length = u64::from_column(row, "raw_length")?;
+        let compressed_i64: i64 = row.get_unwrap("compressed");
+        let invalid_i64: i64 = row.get_unwrap("invalid");
+
+        Ok(BlockPackEntry {
+            index_block_hash,
+            pack_id,
+            offset,
+            length,
+            raw_length,
+            compressed: compressed_i64 != 0,
+            invalid: invalid_i64 != 0,
+        })
+    }
+}
+
+/// What a call to compact() did
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockPackCompaction {
+    /// Number of packs that were rewritten
+    pub packs_rewritten: u64,
+    /// Total size of all packs before compaction
+    pub bytes_before: u64,
+    /// Total size of all packs after compaction
+    pub bytes_after: u64,
+}
+
+/// A block to append, and whether or not it is invalid
+struct PackAppend<'a> {
+    index_block_hash: StacksBlockId,
+    bytes: &'a [u8],
+    invalid: bool,
+}
+
+pub struct BlockPackStore {
+    blocks_dir: String,
+    conn: DBConn,
+}
+
+/// A pack store handle that is shared by everything reading and writing one blocks directory
+pub type SharedBlockPackStore = Arc<Mutex<BlockPackStore>>;
+
+lazy_static! {
+    /// The shared pack stores, by blocks directory.  A StacksChainState owns the handle for its
+    /// blocks directory; this only keeps a weak reference, so the store is closed when the last
+    /// chainstate using it is dropped.
+    static ref SHARED_BLOCK_PACK_STORES: Mutex<HashMap<String, Weak<Mutex<BlockPackStore>>>> =
+        Mutex::new(HashMap::new());
+}
+
+fn io_error(e: io::Error) -> Error {
+    Error::DBError(db_error::IOError(e))
+}
+
+fn deflate_bytes(bytes: &[u8]) -> Result<Vec<u8>, Error> {
+    let mut encoder = deflate::Encoder::new(Vec::with_capacity(bytes.len()));
+    encoder.write_all(bytes).map_err(io_error)?;
+    encoder.finish().into_result().map_err(io_error)
+}
+
+fn is_hex_dir_name(name: &str) -> bool {
+    name.len() == 4 && name.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+impl BlockPackStore {
+    fn index_path(blocks_dir: &str) -> PathBuf {
+        let mut path = PathBuf::from(blocks_dir);
+        path.push(BLOCK_PACKS_INDEX);
+        path
+    }
+
+    fn pack_path(blocks_dir: &str, pack_id: u64) -> PathBuf {
+        let mut path = PathBuf::from(blocks_dir);
+        path.push(format!("pack-{:08}.dat", pack_id));
+        path
+    }
+
+    fn open_index(blocks_dir: &str, create: bool) -> Result<BlockPackStore, Error> {
+        let index_path = BlockPackStore::index_path(blocks_dir);
+        if !create && fs::metadata(&index_path).is_err() {
+            error!("No block pack index at {:?}", &index_path);
+            return Err(Error::DBError(db_error::NoDBError));
+        }
+
+        let mut open_flags = OpenFlags::SQLITE_OPEN_READ_WRITE;
+        if create {
+            open_flags |= OpenFlags::SQLITE_OPEN_CREATE;
+        }
+        let conn = Connection::open_with_flags(&index_path, open_flags)
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+        conn.busy_handler(Some(tx_busy_handler))
+            .map_err(|e| Error::DBError(db_error::SqliteError(e)))?;
+
+        Ok(BlockPackStore {
+            blocks_dir: blocks_dir.to_string(),
+            conn,
+        })
+    }
+
+    /// Create the pack store in the given blocks directory if it doesn't exist yet, and move any
+    /// blocks stored in the legacy one-file-per-block layout into it.
+    pub fn instantiate(blocks_dir: &str) -> Result<BlockPackStore, Error> {
+        let mut store = BlockPackStore::open_index(blocks_dir, true)?;
+        {
+            let tx = tx_begin_immediate(&mut store.conn)?;
+            for cmd in BLOCK_PACKS_SCHEMA.iter() {
+                tx.execute_batch(cmd)?;
+            }
+            tx.commit()?;
+        }
+        store.migrate_legacy_blocks()?;
+        Ok(store)
+    }
+
+    /// Open an instantiated pack store
+    pub fn open(blocks_dir: &str) -> Result<BlockPackStore, Error> {
+        BlockPackStore::open_index(blocks_dir, false)
+    }
+
+    /// Instantiate the pack store in the given blocks directory, and share the handle with
+    /// with_store() callers for as long as the caller keeps it.  This replaces any handle already
+    /// shared for this directory.
+    pub fn instantiate_shared(blocks_dir: &str) -> Result<SharedBlockPackStore, Error> {
+        let store = Arc::new(Mutex::new(BlockPackStore::instantiate(blocks_dir)?));
+        let mut shared = SHARED_BLOCK_PACK_STORES
+            .lock()
+            .expect("FATAL: shared block pack stores lock poisoned");
+        shared.retain(|_, store| store.strong_count() > 0);
+        shared.insert(blocks_dir.to_string(), Arc::downgrade(&store));
+        Ok(store)
+    }
+
+    /// Run `f` on the pack store in the given blocks directory.  Uses the shared handle if there
+    /// is one, and opens the pack index otherwise.  `f` must not call with_store() itself.
+    pub fn with_store<F, R>(blocks_dir: &str, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut BlockPackStore) -> Result<R, Error>,
+    {
+        let shared = SHARED_BLOCK_PACK_STORES
+            .lock()
+            .expect("FATAL: shared block pack stores lock poisoned")
+            .get(blocks_dir)
+            .and_then(|store| store.upgrade());
+
+        match shared {
+            Some(store) => {
+                let mut store = store.lock().expect("FATAL: block pack store lock poisoned");
+                f(&mut store)
+            }
+            None => f(&mut BlockPackStore::open(blocks_dir)?),
+        }
+    }
+
+    /// Are new entries compressed?
+    pub fn get_compression(&self) -> Result<bool, Error> {
+        let compress = query_int(
+            &self.conn,
+            "SELECT compress FROM block_packs_config",
+            NO_PARAMS,
+        )?;
+        Ok(compress != 0)
+    }
+
+    /// Set whether or not new entries are compressed.  Existing entries are unaffected.
+    pub fn set_compression(&mut self, compress: bool) -> Result<(), Error> {
+        let args: &[&dyn ToSql] = &[&(if compress { 1i64 } else { 0i64 })];
+        self.conn
+            .execute("UPDATE block_packs_config SET compress = ?1", args)?;
+        Ok(())
+    }
+
+    pub fn get_entry(
+        &self,
+        index_block_hash: &StacksBlockId,
+    ) -> Result<Option<BlockPackEntry>, Error> {
+        let sql = "SELECT * FROM block_pack_entries WHERE index_block_hash = ?1";
+        query_row(&self.conn, sql, &[index_block_hash]).map_err(Error::DBError)
+    }
+
+    /// Open a pack for reading
+    fn open_pack_for_read(blocks_dir: &str, pack_id: u64) -> Result<fs::File, Error> {
+        let pack_path = BlockPackStore::pack_path(blocks_dir, pack_id);
+        fs::OpenOptions::new()
+            .read(true)
+            .write(false)
+            .open(&pack_path)
+            .map_err(|e| {
+                error!("Failed to open block pack {:?}: {:?}", &pack_path, &e);
+                io_error(e)
+            })
+    }
+
+    /// Read the bytes an entry stores in its pack, as they are stored
+    fn read_stored(&self, entry: &BlockPackEntry) -> Result<Vec<u8>, Error> {
+        if entry.length == 0 {
+            return Ok(vec![]);
+        }
+        if entry.length > MAX_MESSAGE_LEN as u64 {
+            error!(
+                "Block pack entry for {} is too big ({} bytes)",
+                &entry.index_block_hash, entry.length
+            );
+            return Err(Error::DBError(db_error::Corruption));
+        }
+
+        let mut fd = BlockPackStore::open_pack_for_read(&self.blocks_dir, entry.pack_id)?;
+        fd.seek(SeekFrom::Start(entry.offset)).map_err(io_error)?;
+        let mut buf = vec![0u8; entry.length as usize];
+        fd.read_exact(&mut buf).map_err(io_error)?;
+        Ok(buf)
+    }
+
+    /// Read an entry's block bytes, decompressing them if need be.  An entry's bytes are returned
+    /// even if it is invalid; check BlockPackEntry::is_empty() first to get the usual semantics.
+    pub fn read_entry(&self, entry: &BlockPackEntry) -> Result<Vec<u8>, Error> {
+        let stored = self.read_stored(entry)?;
+        if !entry.compressed {
+            return Ok(stored);
+        }
+
+        let mut bytes = vec![];
+        deflate::Decoder::new(&stored[..])
+            .take(entry.raw_length + 1)
+            .read_to_end(&mut bytes)
+            .map_err(io_error)?;
+
+        if bytes.len() as u64 != entry.raw_length {
+            error!(
+                "Block pack entry for {} decompressed to {} bytes, expected {}",
+                &entry.index_block_hash,
+                bytes.len(),
+                entry.raw_length
+            );
+            return Err(Error::DBError(db_error::Corruption));
+        }
+        Ok(bytes)
+    }
+
+    /// Read up to `count` of an entry's block bytes, starting at `offset`.  Uncompressed entries
+    /// are read in place; compressed entries have to be decompressed in full.
+    pub fn read_entry_range(
+        &self,
+        entry: &BlockPackEntry,
+        offset: u64,
+        count: u64,
+    ) -> Result<Vec<u8>, Error> {
+        if offset >= entry.raw_length {
+            return Ok(vec![]);
+        }
+        let count = count.min(entry.raw_length - offset);
+
+        if entry.compressed {
+            let bytes = self.read_entry(entry)?;
+            return Ok(bytes[(offset as usize)..((offset + count) as usize)].to_vec());
+        }
+
+        let pack_path = BlockPackStore::pack_path(&self.blocks_dir, entry.pack_id);
+        let mut fd = fs::OpenOptions::new()
+            .read(true)
+            .write(false)
+            .open(&pack_path)
+            .map_err(io_error)?;
+
+        fd.seek(SeekFrom::Start(entry.offset + offset))
+            .map_err(io_error)?;
+        let mut buf = vec![0u8; count as usize];
+        fd.read_exact(&mut buf).map_err(io_error)?;
+        Ok(buf)
+    }
+
+    /// Open a reader over an entry's block bytes, decompressing them as they are read.  Useful
+    /// for decoding only the start of a block, like its header.
+    pub fn open_entry(&self, entry: &BlockPackEntry) -> Result<Box<dyn Read>, Error> {
+        let pack_path = BlockPackStore::pack_path(&self.blocks_dir, entry.pack_id);
+        let mut fd = fs::OpenOptions::new()
+            .read(true)
+            .write(false)
+            .open(&pack_path)
+            .map_err(io_error)?;
+
+        fd.seek(SeekFrom::Start(entry.offset)).map_err(io_error)?;
+        let stored = fd.take(entry.length);
+        if entry.compressed {
+            Ok(Box::new(deflate::Decoder::new(stored)))
+        } else {
+            Ok(Box::new(stored))
+        }
+    }
+
+    /// Get the pack that appends go to, and how much of it is in use
+    fn get_current_pack(tx: &DBTx) -> Result<(u64, u64), Error> {
+        let sql = "SELECT pack_id, size FROM block_packs ORDER BY pack_id DESC LIMIT 1";
+        let mut stmt = tx.prepare(sql)?;
+        let mut rows = stmt.query(NO_PARAMS)?;
+        match rows.next()? {
+            Some(row) => Ok((
+                u64::from_column(row, "pack_id")?,
+                u64::from_column(row, "size")?,
+            )),
+            None => Ok((0, 0)),
+        }
+    }
+
+    fn set_pack_size(tx: &DBTx, pack_id: u64, size: u64) -> Result<(), Error> {
+        let args: &[&dyn ToSql] = &[&u64_to_sql(pack_id)?, &u64_to_sql(size)?];
+        tx.execute(
+            "INSERT OR REPLACE INTO block_packs (pack_id, size) VALUES (?1, ?2)",
+            args,
+        )?;
+        Ok(())
+    }
+
+    fn insert_entry(tx: &DBTx, entry: &BlockPackEntry) -> Result<(), Error> {
+        let args: &[&dyn ToSql] = &[
+            &entry.index_block_hash,
+            &u64_to_sql(entry.pack_id)?,
+            &u64_to_sql(entry.offset)?,
+            &u64_to_sql(entry.length)?,
+            &u64_to_sql(entry.raw_length)?,
+            &(if entry.compressed { 1i64 } else { 0i64 }),
+            &(if entry.invalid { 1i64 } else { 0i64 }),
+        ];
+        tx.execute(
+            "INSERT OR REPLACE INTO block_pack_entries \
+             (index_block_hash, pack_id, offset, length, raw_length, compressed, invalid) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            args,
+        )?;
+        Ok(())
+    }
+
+    /// Open a pack for writing
+    fn open_pack_for_write(blocks_dir: &str, pack_id: u64) -> Result<fs::File, Error> {
+        let pack_path = BlockPackStore::pack_path(blocks_dir, pack_id);
+        fs::OpenOptions::new()
+            .read(false)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&pack_path)
+            .map_err(|e| {
+                error!("Failed to open block pack {:?}: {:?}", &pack_path, &e);
+                io_error(e)
+            })
+    }
+
+    /// Append a batch of blocks to the current pack, rolling over to new packs as needed, and
+    /// index them.  Existing entries for the same blocks are replaced.
+    fn append(&mut self, blocks: &[PackAppend]) -> Result<(), Error> {
+        let compress = self.get_compression()?;
+        let blocks_dir = self.blocks_dir.clone();
+        let tx = tx_begin_immediate(&mut self.conn)?;
+        let (mut pack_id, mut size) = BlockPackStore::get_current_pack(&tx)?;
+        let mut pack_fd: Option<fs::File> = None;
+        let mut entries = Vec::with_capacity(blocks.len());
+
+        for block in blocks.iter() {
+            if block.bytes.len() == 0 {
+                entries.push(BlockPackEntry {
+                    index_block_hash: block.index_block_hash.clone(),
+                    pack_id: 0,
+                    offset: 0,
+                    length: 0,
+                    raw_length: 0,
+                    compressed: false,
+                    invalid: block.invalid,
+                });
+                continue;
+            }
+
+            let stored: Cow<[u8]> = if compress {
+                Cow::Owned(deflate_bytes(block.bytes)?)
+            } else {
+                Cow::Borrowed(block.bytes)
+            };
+
+            if size > 0 && size + (stored.len() as u64) > BLOCK_PACK_MAX_SIZE {
+                if let Some(fd) = pack_fd.take() {
+                    fd.sync_all().map_err(io_error)?;
+                }
+                BlockPackStore::set_pack_size(&tx, pack_id, size)?;
+                pack_id += 1;
+                size = 0;
+            }
+
+            if pack_fd.is_none() {
+                let mut fd = BlockPackStore::open_pack_for_write(&blocks_dir, pack_id)?;
+                fd.seek(SeekFrom::Start(size)).map_err(io_error)?;
+                pack_fd = Some(fd);
+            }
+            if let Some(ref mut fd) = pack_fd {
+                fd.write_all(&stored).map_err(io_error)?;
+            }
+
+            entries.push(BlockPackEntry {
+                index_block_hash: block.index_block_hash.clone(),
+                pack_id,
+                offset: size,
+                length: stored.len() as u64,
+                raw_length: block.bytes.len() as u64,
+                compressed: compress,
+                invalid: block.invalid,
+            });
+            size += stored.len() as u64;
+        }
+
+        // the data must be durable before the index refers to it
+        if let Some(fd) = pack_fd.take() {
+            fd.sync_all().map_err(io_error)?;
+        }
+        BlockPackStore::set_pack_size(&tx, pack_id, size)?;
+        for entry in entries.iter() {
+            BlockPackStore::insert_entry(&tx, entry)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Store a block's bytes, replacing any existing entry for it
+    pub fn put(&mut self, index_block_hash: &StacksBlockId, bytes: &[u8]) -> Result<(), Error> {
+        self.append(&[PackAppend {
+            index_block_hash: index_block_hash.clone(),
+            bytes,
+            invalid: false,
+        }])
+    }
+
+    /// Store an empty entry for a block, which marks it as known but invalid
+    pub fn put_empty(&mut self, index_block_hash: &StacksBlockId) -> Result<(), Error> {
+        self.append(&[PackAppend {
+            index_block_hash: index_block_hash.clone(),
+            bytes: &[],
+            invalid: true,
+        }])
+    }
+
+    /// Mark a block's entry as invalid.  Its bytes are kept for later analysis until it is
+    /// dropped.  Returns false if there is no entry for this block.
+    pub fn mark_invalid(&mut self, index_block_hash: &StacksBlockId) -> Result<bool, Error> {
+        let num_changed = self.conn.execute(
+            "UPDATE block_pack_entries SET invalid = 1 WHERE index_block_hash = ?1",
+            &[index_block_hash],
+        )?;
+        Ok(num_changed > 0)
+    }
+
+    /// Drop the bytes of the given blocks, leaving empty entries behind so they're still known
+    /// as stored-but-invalid.  The space isn't reclaimed until compact() is called.  Returns the
+    /// number of entries that were emptied.
+    pub fn drop_blocks(&mut self, index_block_hashes: &[StacksBlockId]) -> Result<u64, Error> {
+        let tx = tx_begin_immediate(&mut self.conn)?;
+        let mut dropped = 0;
+        for index_block_hash in index_block_hashes.iter() {
+            dropped += tx.execute(
+                "UPDATE block_pack_entries SET pack_id = 0, offset = 0, length = 0, raw_length = 0, \
+                 compressed = 0, invalid = 1 WHERE index_block_hash = ?1 AND length > 0",
+                &[index_block_hash],
+            )? as u64;
+        }
+        tx.commit()?;
+        Ok(dropped)
+    }
+
+    /// Rewrite every pack that holds bytes no entry refers to.  The live entries of those packs
+    /// are copied (as stored) into new packs one at a time, and the old packs are deleted.
+    ///
+    /// Concurrent readers may find an entry's old pack gone, so this should only run while
+    /// nothing else is reading blocks (e.g. while the node is stopped).
+    pub fn compact(&mut self) -> Result<BlockPackCompaction, Error> {
+        let packs: Vec<(u64, u64)> = {
+            let mut stmt = self
+                .conn
+                .prepare("SELECT pack_id, size FROM block_packs ORDER BY pack_id")?;
+            let rows = stmt.query_and_then(NO_PARAMS, |row| -> Result<(u64, u64), Error> {
+                Ok((
+                    u64::from_column(row, "pack_id")?,
+                    u64::from_column(row, "size")?,
+                ))
+            })?;
+            rows.collect::<Result<_, _>>()?
+        };
+        let bytes_before: u64 = packs.iter().map(|(_, size)| *size).sum();
+
+        let mut live_sizes: HashMap<u64, u64> = HashMap::new();
+        {
+            let mut stmt = self.conn.prepare(
+                "SELECT pack_id, SUM(length) AS live FROM block_pack_entries WHERE length > 0 GROUP BY pack_id",
+            )?;
+            let rows = stmt.query_and_then(NO_PARAMS, |row| -> Result<(u64, u64), Error> {
+                Ok((
+                    u64::from_column(row, "pack_id")?,
+                    u64::from_column(row, "live")?,
+                ))
+            })?;
+            for row in rows {
+                let (pack_id, live) = row?;
+                live_sizes.insert(pack_id, live);
+            }
+        }
+
+        let dirty: Vec<u64> = packs
+            .iter()
+            .filter(|(pack_id, size)| live_sizes.get(pack_id).cloned().unwrap_or(0) < *size)
+            .map(|(pack_id, _)| *pack_id)
+            .collect();
+
+        if dirty.len() == 0 {
+            return Ok(BlockPackCompaction {
+                packs_rewritten: 0,
+                bytes_before,
+                bytes_after: bytes_before,
+            });
+        }
+
+        let dirty_paths: Vec<PathBuf> = dirty
+            .iter()
+            .map(|id| BlockPackStore::pack_path(&self.blocks_dir, *id))
+            .collect();
+        let mut live_entries = vec![];
+        for pack_id in dirty.iter() {
+            let mut entries: Vec<BlockPackEntry> = query_rows(
+                &self.conn,
+                "SELECT * FROM block_pack_entries WHERE pack_id = ?1 AND length > 0 ORDER BY offset",
+                &[&u64_to_sql(*pack_id)?],
+            )?;
+            live_entries.append(&mut entries);
+        }
+
+        // live entries move to packs after the current one, so appends never reuse a pack ID
+        let blocks_dir = self.blocks_dir.clone();
+        let max_pack_id = packs.last().map(|(pack_id, _)| *pack_id).unwrap_or(0);
+
+        let tx = tx_begin_immediate(&mut self.conn)?;
+        let mut pack_id = max_pack_id + 1;
+        let mut size = 0;
+        let mut pack_fd: Option<fs::File> = None;
+        let mut src_fd: Option<(u64, fs::File)> = None;
+        for entry in live_entries.iter() {
+            if size > 0 && size + entry.length > BLOCK_PACK_MAX_SIZE {
+                if let Some(fd) = pack_fd.take() {
+                    fd.sync_all().map_err(io_error)?;
+                }
+                BlockPackStore::set_pack_size(&tx, pack_id, size)?;
+                pack_id += 1;
+                size = 0;
+            }
+            if pack_fd.is_none() {
+                // a failed compaction may have left this pack behind
+                let fd = BlockPackStore::open_pack_for_write(&blocks_dir, pack_id)?;
+                fd.set_len(0).map_err(io_error)?;
+                pack_fd = Some(fd);
+            }
+            if src_fd.as_ref().map(|(src_pack_id, _)| *src_pack_id) != Some(entry.pack_id) {
+                let fd = BlockPackStore::open_pack_for_read(&blocks_dir, entry.pack_id)?;
+                src_fd = Some((entry.pack_id, fd));
+            }
+            if let (Some((_, src)), Some(fd)) = (src_fd.as_mut(), pack_fd.as_mut()) {
+                src.seek(SeekFrom::Start(entry.offset)).map_err(io_error)?;
+                let copied = io::copy(&mut src.take(entry.length), fd).map_err(io_error)?;
+                if copied != entry.length {
+                    error!(
+                        "Block pack entry for {} is truncated ({} of {} bytes)",
+                        &entry.index_block_hash, copied, entry.length
+                    );
+                    return Err(Error::DBError(db_error::Corruption));
+                }
+            }
+
+            let mut moved = entry.clone();
+            moved.pack_id = pack_id;
+            moved.offset = size;
+            BlockPackStore::insert_entry(&tx, &moved)?;
+            size += entry.length;
+        }
+        if let Some(fd) = pack_fd.take() {
+            fd.sync_all().map_err(io_error)?;
+        }
+        if live_entries.len() > 0 {
+            BlockPackStore::set_pack_size(&tx, pack_id, size)?;
+        }
+        for pack_id in dirty.iter() {
+            tx.execute(
+                "DELETE FROM block_packs WHERE pack_id = ?1",
+                &[&u64_to_sql(*pack_id)?],
+            )?;
+        }
+        let bytes_after = query_int(
+            &tx,
+            "SELECT IFNULL(SUM(size), 0) FROM block_packs",
+            NO_PARAMS,
+        )? as u64;
+        tx.commit()?;
+
+        for path in dirty_paths.iter() {
+            if let Err(e) = fs::remove_file(path) {
+                warn!("Failed to remove compacted block pack {:?}: {:?}", path, &e);
+            }
+        }
+
+        Ok(BlockPackCompaction {
+            packs_rewritten: dirty.len() as u64,
+            bytes_before,
+            bytes_after,
+        })
+    }
+
+    /// Path to a block in the legacy one-file-per-block layout
+    fn legacy_block_path(blocks_dir: &str, index_block_hash: &StacksBlockId) -> PathBuf {
+        let block_hash_bytes = index_block_hash.as_bytes();
+        let mut block_path = PathBuf::from(blocks_dir);
+        block_path.push(to_hex(&block_hash_bytes[0..2]));
+        block_path.push(to_hex(&block_hash_bytes[2..4]));
+        block_path.push(format!("{}", index_block_hash));
+        block_path
+    }
+
+    /// Move every block in the legacy one-file-per-block layout into packs.  The legacy layout
+    /// stores block `H` at `XXXX/YYYY/H`, where `XXXXYYYY` is the hex encoding of `H`'s first 4
+    /// bytes.  An invalid block is a zero-length file, and may have a copy of its original
+    /// bytes alongside it at `H.invalid-*`.  Each file is deleted once its block is in a pack,
+    /// so an interrupted migration resumes where it stopped.  Returns the number of blocks moved.
+    pub fn migrate_legacy_blocks(&mut self) -> Result<u64, Error> {
+        let mut migrated = 0;
+        let mut announced = false;
+
+        for level_1 in BlockPackStore::list_hex_dirs(Path::new(&self.blocks_dir))?.iter() {
+            for level_2 in BlockPackStore::list_hex_dirs(level_1)?.iter() {
+                if !announced {
+                    info!(
+                        "Moving blocks in {} into block packs; this may take a while",
+                        &self.blocks_dir
+                    );
+                    announced = true;
+                }
+                migrated += self.migrate_legacy_dir(level_2)?;
+                if let Err(e) = fs::remove_dir(level_2) {
+                    warn!(
+                        "Failed to remove legacy block directory {:?}: {:?}",
+                        level_2, &e
+                    );
+                }
+            }
+            if let Err(e) = fs::remove_dir(level_1) {
+                warn!(
+                    "Failed to remove legacy block directory {:?}: {:?}",
+                    level_1, &e
+                );
+            }
+        }
+
+        if announced {
+            info!("Moved {} blocks into block packs", migrated);
+        }
+        Ok(migrated)
+    }
+
+    fn list_hex_dirs(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+        let mut dirs = vec![];
+        for dirent in fs::read_dir(dir).map_err(io_error)? {
+            let dirent = dirent.map_err(io_error)?;
+            let is_hex_dir = dirent.file_type().map_err(io_error)?.is_dir()
+                && dirent
+                    .file_name()
+                    .to_str()
+                    .map(is_hex_dir_name)
+                    .unwrap_or(false);
+            if is_hex_dir {
+                dirs.push(dirent.path());
+            }
+        }
+        dirs.sort();
+        Ok(dirs)
+    }
+
+    fn migrate_legacy_dir(&mut self, dir: &Path) -> Result<u64, Error> {
+        let mut names = vec![];
+        for dirent in fs::read_dir(dir).map_err(io_error)? {
+            let dirent = dirent.map_err(io_error)?;
+            if let Some(name) = dirent.file_name().to_str() {
+                names.push(name.to_string());
+            }
+        }
+        names.sort();
+
+        let mut migrated = 0;
+        let mut batch: Vec<(StacksBlockId, Vec<u8>, bool)> = vec![];
+        let mut batch_files: Vec<PathBuf> = vec![];
+        let mut batch_bytes = 0;
+
+        for name in names.iter() {
+            let path = dir.join(name);
+            if name.ends_with(".tmp") {
+                // left behind by an interrupted write
+                let _ = fs::remove_file(&path);
+                continue;
+            }
+            let index_block_hash = match StacksBlockId::from_hex(name) {
+                Ok(h) => h,
+                Err(_) => continue,
+            };
+            debug_assert_eq!(
+                BlockPackStore::legacy_block_path(&self.blocks_dir, &index_block_hash),
+                path
+            );
+
+            let invalid_prefix = format!("{}.invalid-", name);
+            let invalid_copies: Vec<PathBuf> = names
+                .iter()
+                .filter(|n| n.starts_with(&invalid_prefix))
+                .map(|n| dir.join(n))
+                .collect();
+
+            if self.get_entry(&index_block_hash)?.is_none() {
+                let mut bytes = fs::read(&path).map_err(io_error)?;
+                let mut invalid = false;
+                if bytes.len() == 0 {
+                    invalid = true;
+                    if let Some(copy) = invalid_copies.first() {
+                        bytes = fs::read(copy).map_err(io_error)?;
+                    }
+                }
+                batch_bytes += bytes.len() as u64;
+                batch.push((index_block_hash, bytes, invalid));
+            }
+            batch_files.push(path);
+            batch_files.extend(invalid_copies.into_iter());
+
+            if batch_bytes >= MIGRATION_BATCH_BYTES {
+                migrated += self.flush_migration_batch(&mut batch, &mut batch_files)?;
+                batch_bytes = 0;
+            }
+        }
+        migrated += self.flush_migration_batch(&mut batch, &mut batch_files)?;
+        Ok(migrated)
+    }
+
+    fn flush_migration_batch(
+        &mut self,
+        batch: &mut Vec<(StacksBlockId, Vec<u8>, bool)>,
+        batch_files: &mut Vec<PathBuf>,
+    ) -> Result<u64, Error> {
+        let appends: Vec<PackAppend> = batch
+            .iter()
+            .map(|(index_block_hash, bytes, invalid)| PackAppend {
+                index_block_hash: index_block_hash.clone(),
+                bytes: &bytes[..],
+                invalid: *invalid,
+            })
+            .collect();
+        if appends.len() > 0 {
+            self.append(&appends)?;
+        }
+
+        for path in batch_files.drain(..) {
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Failed to remove migrated block file {:?}: {:?}", &path, &e);
+            }
+        }
+        let migrated = batch.len() as u64;
+        batch.clear();
+        Ok(migrated)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn setup_blocks_dir(test_name: &str) -> String {
+        let path = format!("/tmp/blockstack-test-block-packs-{}", test_name);
+        if fs::metadata(&path).is_ok() {
+            fs::remove_dir_all(&path).unwrap();
+        }
+        fs::create_dir_all(&path).unwrap();
+        path
+    }
+
+    fn block_bytes(seed: u8, len: usize) -> Vec<u8> {
+        (0..len).map(|i| seed.wrapping_add((i % 7) as u8)).collect()
+    }
+
+    #[test]
+    fn block_packs_put_and_read() {
+        let blocks_dir = setup_blocks_dir("put_and_read");
+        let mut store = BlockPackStore::instantiate(&blocks_dir).unwrap();
+
+        let ibh_1 = StacksBlockId([1u8; 32]);
+        let ibh_2 = StacksBlockId([2u8; 32]);
+        let bytes_1 = block_bytes(1, 1000);
+        let bytes_2 = block_bytes(2, 2000);
+
+        assert!(store.get_entry(&ibh_1).unwrap().is_none());
+
+        store.put(&ibh_1, &bytes_1).unwrap();
+        store.set_compression(true).unwrap();
+        store.put(&ibh_2, &bytes_2).unwrap();
+
+        let entry_1 = store.get_entry(&ibh_1).unwrap().unwrap();
+        let entry_2 = store.get_entry(&ibh_2).unwrap().unwrap();
+        assert!(!entry_1.compressed);
+        assert!(entry_2.compressed);
+        assert!(entry_2.length < entry_2.raw_length);
+        assert_eq!(entry_2.offset, entry_1.offset + entry_1.length);
+
+        assert_eq!(store.read_entry(&entry_1).unwrap(), bytes_1);
+        assert_eq!(store.read_entry(&entry_2).unwrap(), bytes_2);
+        assert_eq!(
+            store.read_entry_range(&entry_2, 100, 50).unwrap(),
+            bytes_2[100..150].to_vec()
+        );
+        assert_eq!(
+            store.read_entry_range(&entry_1, 990, 50).unwrap(),
+            bytes_1[990..].to_vec()
+        );
+        assert_eq!(store.read_entry_range(&entry_1, 1000, 50).unwrap().len(), 0);
+
+        let mut streamed = vec![];
+        store
+            .open_entry(&entry_2)
+            .unwrap()
+            .read_to_end(&mut streamed)
+            .unwrap();
+        assert_eq!(streamed, bytes_2);
+
+        // entries survive reopening
+        let store = BlockPackStore::open(&blocks_dir).unwrap();
+        assert!(store.get_compression().unwrap());
+        let entry_1 = store.get_entry(&ibh_1).unwrap().unwrap();
+        assert_eq!(store.read_entry(&entry_1).unwrap(), bytes_1);
+    }
+
+    #[test]
+    fn block_packs_invalid_and_empty() {
+        let blocks_dir = setup_blocks_dir("invalid_and_empty");
+        let mut store = BlockPackStore::instantiate(&blocks_dir).unwrap();
+
+        let ibh_1 = StacksBlockId([1u8; 32]);
+        let ibh_2 = StacksBlockId([2u8; 32]);
+        let bytes_1 = block_bytes(1, 1000);
+
+        store.put(&ibh_1, &bytes_1).unwrap();
+        assert!(!store.get_entry(&ibh_1).unwrap().unwrap().is_empty());
+
+        // invalid blocks keep their bytes
+        assert!(store.mark_invalid(&ibh_1).unwrap());
+        let entry_1 = store.get_entry(&ibh_1).unwrap().unwrap();
+        assert!(entry_1.is_empty());
+        assert_eq!(store.read_entry(&entry_1).unwrap(), bytes_1);
+
+        assert!(!store.mark_invalid(&ibh_2).unwrap());
+        store.put_empty(&ibh_2).unwrap();
+        let entry_2 = store.get_entry(&ibh_2).unwrap().unwrap();
+        assert!(entry_2.is_empty());
+        assert_eq!(entry_2.length, 0);
+    }
+
+    #[test]
+    fn block_packs_drop_and_compact() {
+        let blocks_dir = setup_blocks_dir("drop_and_compact");
+        let mut store = BlockPackStore::instantiate(&blocks_dir).unwrap();
+
+        let mut blocks = vec![];
+        for i in 0..10 {
+            let ibh = StacksBlockId([i as u8; 32]);
+            let bytes = block_bytes(i as u8, 1000 + i);
+            store.put(&ibh, &bytes).unwrap();
+            blocks.push((ibh, bytes));
+        }
+
+        // nothing to reclaim yet
+        let compaction = store.compact().unwrap();
+        assert_eq!(compaction.packs_rewritten, 0);
+        assert_eq!(compaction.bytes_before, compaction.bytes_after);
+
+        let dropped: Vec<StacksBlockId> = blocks
+            .iter()
+            .step_by(2)
+            .map(|(ibh, _)| ibh.clone())
+            .collect();
+        assert_eq!(store.drop_blocks(&dropped).unwrap(), 5);
+        // dropping again is a no-op
+        assert_eq!(store.drop_blocks(&dropped).unwrap(), 0);
+
+        let compaction = store.compact().unwrap();
+        assert_eq!(compaction.packs_rewritten, 1);
+        let live_bytes: u64 = blocks
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .map(|(_, bytes)| bytes.len() as u64)
+            .sum();
+        assert_eq!(compaction.bytes_after, live_bytes);
+        assert!(compaction.bytes_after < compaction.bytes_before);
+        assert!(fs::metadata(&BlockPackStore::pack_path(&blocks_dir, 0)).is_err());
+
+        for (i, (ibh, bytes)) in blocks.iter().enumerate() {
+            let entry = store.get_entry(ibh).unwrap().unwrap();
+            if i % 2 == 0 {
+                assert!(entry.is_empty());
+                assert_eq!(entry.length, 0);
+            } else {
+                assert!(!entry.is_empty());
+                assert_eq!(&store.read_entry(&entry).unwrap(), bytes);
+            }
+        }
+
+        // appends still work after compaction
+        let ibh = StacksBlockId([0xff; 32]);
+        let bytes = block_bytes(0xff, 500);
+        store.put(&ibh, &bytes).unwrap();
+        let entry = store.get_entry(&ibh).unwrap().unwrap();
+        assert_eq!(store.read_entry(&entry).unwrap(), bytes);
+    }
+
+    #[test]
+    fn block_packs_migrate_legacy_blocks() {
+        let blocks_dir = setup_blocks_dir("migrate_legacy_blocks");
+
+        let ibh_valid = StacksBlockId([0x11; 32]);
+        let ibh_invalid = StacksBlockId([0x22; 32]);
+        let ibh_empty = StacksBlockId([0x33; 32]);
+        let bytes_valid = block_bytes(0x11, 1000);
+        let bytes_invalid = block_bytes(0x22, 1500);
+
+        let write_legacy = |ibh: &StacksBlockId, suffix: &str, bytes: &[u8]| {
+            let mut path = BlockPackStore::legacy_block_path(&blocks_dir, ibh);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            path.set_file_name(format!("{}{}", ibh, suffix));
+            fs::write(&path, bytes).unwrap();
+        };
+        write_legacy(&ibh_valid, "", &bytes_valid);
+        write_legacy(&ibh_valid, ".tmp", &[1, 2, 3]);
+        write_legacy(&ibh_invalid, "", &[]);
+        write_legacy(&ibh_invalid, ".invalid-0123456789abcdef", &bytes_invalid);
+        write_legacy(&ibh_empty, "", &[]);
+
+        let store = BlockPackStore::instantiate(&blocks_dir).unwrap();
+
+        let entry = store.get_entry(&ibh_valid).unwrap().unwrap();
+        assert!(!entry.is_empty());
+        assert_eq!(store.read_entry(&entry).unwrap(), bytes_valid);
+
+        let entry = store.get_entry(&ibh_invalid).unwrap().unwrap();
+        assert!(entry.is_empty());
+        assert_eq!(store.read_entry(&entry).unwrap(), bytes_invalid);
+
+        let entry = store.get_entry(&ibh_empty).unwrap().unwrap();
+        assert!(entry.is_empty());
+        assert_eq!(entry.length, 0);
+
+        // the legacy layout is gone
+        for ibh in [&ibh_valid, &ibh_invalid, &ibh_empty].iter() {
+            let path = BlockPackStore::legacy_block_path(&blocks_dir, ibh);
+            assert!(fs::metadata(path.parent().unwrap().parent().unwrap()).is_err());
+        }
+
+        // migrating again finds nothing to do
+        let mut store = BlockPackStore::open(&blocks_dir).unwrap();
+        assert_eq!(store.migrate_legacy_blocks().unwrap(), 0);
+    }
+}
@@ -24,6 +24,7 @@ use std::path::{Path, PathBuf};
 
 use chainstate::burn::db::sortdb::*;
 use chainstate::stacks::db::*;
+use chainstate::stacks::index::Error as marf_error;
 use chainstate::stacks::Error;
 use chainstate::stacks::*;
 use clarity_vm::clarity::{
@@ -48,6 +49,7 @@ use vm::costs::CostTracker;
 use vm::costs::ExecutionCost;
 use vm::database::ClarityDatabase;
 use vm::errors::Error as InterpreterError;
+use vm::errors::{IncomparableError, InterpreterError as VMInterpreterError};
 use vm::representations::ClarityName;
 use vm::representations::ContractName;
 use vm::types::{
@@ -55,7 +57,7 @@ use vm::types::{
     StandardPrincipalData, TupleData, TypeSignature, Value,
 };
 
-use crate::types::chainstate::StacksMicroblockHeader;
+use crate::types::chainstate::{StacksBlockId, StacksMicroblockHeader};
 
 // make it possible to have a set of Values
 impl std::hash::Hash for Value {
@@ -82,6 +84,7 @@ impl StacksTransactionReceipt {
             contract_analysis: None,
             transaction: tx.into(),
             execution_cost: cost,
+            vm_error: None,
         }
     }
 
@@ -100,6 +103,7 @@ impl StacksTransactionReceipt {
             stx_burned: burned,
             contract_analysis: None,
             execution_cost: cost,
+            vm_error: None,
         }
     }
 
@@ -118,6 +122,7 @@ impl StacksTransactionReceipt {
             stx_burned: burned,
             contract_analysis: None,
             execution_cost: cost,
+            vm_error: None,
         }
     }
 
@@ -136,6 +141,7 @@ impl StacksTransactionReceipt {
             stx_burned: burned,
             contract_analysis: Some(analysis),
             execution_cost: cost,
+            vm_error: None,
         }
     }
 
@@ -154,6 +160,7 @@ impl StacksTransactionReceipt {
             stx_burned: burned,
             contract_analysis: Some(analysis),
             execution_cost: cost,
+            vm_error: None,
         }
     }
 
@@ -166,6 +173,7 @@ impl StacksTransactionReceipt {
             stx_burned: 0,
             contract_analysis: None,
             execution_cost: ExecutionCost::zero(),
+            vm_error: None,
         }
     }
 
@@ -181,6 +189,7 @@ impl StacksTransactionReceipt {
             stx_burned: 0,
             contract_analysis: None,
             execution_cost: analysis_cost,
+            vm_error: None,
         }
     }
 
@@ -197,6 +206,7 @@ impl StacksTransactionReceipt {
             stx_burned: 0,
             contract_analysis: None,
             execution_cost: cost,
+            vm_error: None,
         }
     }
 
@@ -254,19 +264,21 @@ enum ClarityRuntimeTxError {
     },
     AbortedByCallback(Option<Value>, AssetMap, Vec<StacksTransactionEvent>),
     CostError(ExecutionCost, ExecutionCost),
+    /// State the transaction read has been pruned, so this node can't evaluate it
+    PrunedState(StacksBlockId),
     Rejectable(clarity_error),
 }
 
 fn handle_clarity_runtime_error(error: clarity_error) -> ClarityRuntimeTxError {
     match error {
         // runtime errors are okay
-        clarity_error::Interpreter(InterpreterError::Runtime(_, _)) => {
+        clarity_error::Interpreter(InterpreterError::Runtime(_, _, _)) => {
             ClarityRuntimeTxError::Acceptable {
                 error,
                 err_type: "runtime error",
             }
         }
-        clarity_error::Interpreter(InterpreterError::ShortReturn(_)) => {
+        clarity_error::Interpreter(InterpreterError::ShortReturn(_, _)) => {
             ClarityRuntimeTxError::Acceptable {
                 error,
                 err_type: "short return/panic",
@@ -276,6 +288,11 @@ fn handle_clarity_runtime_error(error: clarity_error) -> ClarityRuntimeTxError {
             ClarityRuntimeTxError::AbortedByCallback(val, assets, events)
         }
         clarity_error::CostError(cost, budget) => ClarityRuntimeTxError::CostError(cost, budget),
+        clarity_error::Interpreter(InterpreterError::Interpreter(
+            VMInterpreterError::MarfFailure(IncomparableError {
+                err: marf_error::PrunedBlockError(block),
+            }),
+        )) => ClarityRuntimeTxError::PrunedState(StacksBlockId(block)),
         unhandled_error => ClarityRuntimeTxError::Rejectable(unhandled_error),
     }
 }
@@ -879,6 +896,7 @@ impl StacksChainState {
                     .sub(&cost_before)
                     .expect("BUG: total block cost decreased");
 
+                let mut vm_error = None;
                 let (result, asset_map, events) = match contract_call_resp {
                     Ok((return_value, asset_map, events)) => {
                         info!("Contract-call successfully processed";
@@ -896,6 +914,9 @@ impl StacksChainState {
                                       "function_name" => %contract_call.function_name,
                                       "function_args" => %VecDisplay(&contract_call.function_args),
                                       "error" => ?error);
+                            if let clarity_error::Interpreter(ref e) = error {
+                                vm_error = Some(VMErrorData::from_interpreter_error(e));
+                            }
                             (Value::err_none(), AssetMap::new(), vec![])
                         }
                         ClarityRuntimeTxError::AbortedByCallback(value, assets, events) => {
@@ -915,6 +936,9 @@ impl StacksChainState {
                             warn!("Block compute budget exceeded: if included, this will invalidate a block"; "txid" => %tx.txid(), "cost" => %cost_after, "budget" => %budget);
                             return Err(Error::CostOverflowError(cost_before, cost_after, budget));
                         }
+                        ClarityRuntimeTxError::PrunedState(block) => {
+                            return Err(Error::ClarityStatePruned(block));
+                        }
                         ClarityRuntimeTxError::Rejectable(e) => {
                             error!("Unexpected error invalidating transaction: if included, this will invalidate a block";
                                        "contract_name" => %contract_id,
@@ -926,13 +950,14 @@ impl StacksChainState {
                     },
                 };
 
-                let receipt = StacksTransactionReceipt::from_contract_call(
+                let mut receipt = StacksTransactionReceipt::from_contract_call(
                     tx.clone(),
                     events,
                     result,
                     asset_map.get_stx_burned_total(),
                     total_cost,
                 );
+                receipt.vm_error = vm_error;
                 Ok(receipt)
             }
             TransactionPayload::SmartContract(ref smart_contract) => {
@@ -1026,6 +1051,7 @@ impl StacksChainState {
                     .sub(&cost_before)
                     .expect("BUG: total block cost decreased");
 
+                let mut vm_error = None;
                 let (asset_map, events) = match initialize_resp {
                     Ok(x) => {
                         // store analysis -- if this fails, then the have some pretty bad problems
@@ -1040,6 +1066,9 @@ impl StacksChainState {
                                       "contract" => %contract_id,
                                       "code" => %contract_code_str,
                                       "error" => ?error);
+                            if let clarity_error::Interpreter(ref e) = error {
+                                vm_error = Some(VMErrorData::from_interpreter_error(e));
+                            }
                             (AssetMap::new(), vec![])
                         }
                         ClarityRuntimeTxError::AbortedByCallback(_, assets, events) => {
@@ -1060,6 +1089,9 @@ impl StacksChainState {
                                       "budget" => %budget);
                             return Err(Error::CostOverflowError(cost_before, cost_after, budget));
                         }
+                        ClarityRuntimeTxError::PrunedState(block) => {
+                            return Err(Error::ClarityStatePruned(block));
+                        }
                         ClarityRuntimeTxError::Rejectable(e) => {
                             error!("Unexpected error invalidating transaction: if included, this will invalidate a block";
                                        "contract_name" => %contract_id,
@@ -1070,13 +1102,14 @@ impl StacksChainState {
                     },
                 };
 
-                let receipt = StacksTransactionReceipt::from_smart_contract(
+                let mut receipt = StacksTransactionReceipt::from_smart_contract(
                     tx.clone(),
                     events,
                     asset_map.get_stx_burned_total(),
                     contract_analysis,
                     total_cost,
                 );
+                receipt.vm_error = vm_error;
                 Ok(receipt)
             }
             TransactionPayload::PoisonMicroblock(ref mblock_header_1, ref mblock_header_2) => {
@@ -1171,6 +1204,7 @@ pub mod test {
     use vm::database::NULL_BURN_STATE_DB;
     use vm::representations::ClarityName;
     use vm::representations::ContractName;
+    use vm::representations::Span;
     use vm::types::*;
 
     use super::*;
@@ -2141,9 +2175,28 @@ pub mod test {
                 StacksChainState::get_account(&mut conn, &addr_2.to_account_principal());
             assert_eq!(account_2.nonce, next_nonce);
 
-            let (_fee, _) =
+            let (_fee, receipt) =
                 StacksChainState::process_transaction(&mut conn, &signed_tx_2, false).unwrap();
 
+            // only the runtime error is reported, at the division that raised it
+            if contract_function == "set-bar" {
+                let vm_error = receipt.vm_error.unwrap();
+                assert_eq!(vm_error.message, "DivisionByZero");
+                let location = vm_error.location.unwrap();
+                assert_eq!(location.contract_identifier, contract_id);
+                assert_eq!(
+                    location.span,
+                    Span {
+                        start_line: 5,
+                        start_column: 31,
+                        end_line: 5,
+                        end_column: 37,
+                    }
+                );
+            } else {
+                assert!(receipt.vm_error.is_none());
+            }
+
             // nonce should have incremented
             next_nonce += 1;
             let account_2 =
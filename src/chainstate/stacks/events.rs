@@ -20,6 +20,8 @@ use burnchains::Txid;
 use chainstate::stacks::StacksTransaction;
 use vm::analysis::ContractAnalysis;
 use vm::costs::ExecutionCost;
+use vm::errors::{Error as InterpreterError, ErrorLocation};
+use vm::representations::Span;
 use vm::types::{
     AssetIdentifier, PrincipalData, QualifiedContractIdentifier, StandardPrincipalData, Value,
 };
@@ -60,6 +62,36 @@ pub struct StacksTransactionReceipt {
     pub stx_burned: u128,
     pub contract_analysis: Option<ContractAnalysis>,
     pub execution_cost: ExecutionCost,
+    /// set when a contract-call or contract deployment aborted with a runtime error
+    ///   or short return
+    pub vm_error: Option<VMErrorData>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VMErrorData {
+    pub message: String,
+    pub location: Option<ErrorLocation>,
+}
+
+impl VMErrorData {
+    pub fn from_interpreter_error(error: &InterpreterError) -> VMErrorData {
+        let message = match error {
+            InterpreterError::Runtime(err, ..) => format!("{}", err),
+            InterpreterError::ShortReturn(err, _) => format!("{:?}", err),
+            _ => format!("{}", error),
+        };
+        VMErrorData {
+            message,
+            location: error.location().cloned(),
+        }
+    }
+
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "message": self.message,
+            "location": self.location.as_ref().map(|location| location.json_serialize()),
+        })
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -113,6 +145,38 @@ impl StacksTransactionEvent {
                 "type": "stx_lock_event",
                 "stx_lock_event": event_data.json_serialize()
             }),
+            StacksTransactionEvent::STXEvent(STXEventType::STXUnlockEvent(event_data)) => json!({
+                "txid": format!("0x{:?}", txid),
+                "event_index": event_index,
+                "committed": committed,
+                "type": "stx_unlock_event",
+                "stx_unlock_event": event_data.json_serialize()
+            }),
+            StacksTransactionEvent::STXEvent(STXEventType::STXDelegateEvent(event_data)) => json!({
+                "txid": format!("0x{:?}", txid),
+                "event_index": event_index,
+                "committed": committed,
+                "type": "stx_delegate_event",
+                "stx_delegate_event": event_data.json_serialize()
+            }),
+            StacksTransactionEvent::STXEvent(STXEventType::STXRevokeDelegateEvent(event_data)) => {
+                json!({
+                    "txid": format!("0x{:?}", txid),
+                    "event_index": event_index,
+                    "committed": committed,
+                    "type": "stx_revoke_delegate_event",
+                    "stx_revoke_delegate_event": event_data.json_serialize()
+                })
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXDelegateStackEvent(event_data)) => {
+                json!({
+                    "txid": format!("0x{:?}", txid),
+                    "event_index": event_index,
+                    "committed": committed,
+                    "type": "stx_delegate_stack_event",
+                    "stx_delegate_stack_event": event_data.json_serialize()
+                })
+            }
             StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(event_data)) => json!({
                 "txid": format!("0x{:?}", txid),
                 "event_index": event_index,
@@ -165,6 +229,10 @@ pub enum STXEventType {
     STXMintEvent(STXMintEventData),
     STXBurnEvent(STXBurnEventData),
     STXLockEvent(STXLockEventData),
+    STXUnlockEvent(STXUnlockEventData),
+    STXDelegateEvent(STXDelegateEventData),
+    STXRevokeDelegateEvent(STXRevokeDelegateEventData),
+    STXDelegateStackEvent(STXDelegateStackEventData),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -230,6 +298,87 @@ impl STXLockEventData {
     }
 }
 
+/// A PoX lock that expired.  This is reported in the first block whose burnchain height reaches the
+/// lock's unlock height, even though the account's balance is only consolidated the next time it
+/// changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct STXUnlockEventData {
+    pub unlocked_amount: u128,
+    pub unlock_height: u64,
+    pub unlocked_address: PrincipalData,
+}
+
+impl STXUnlockEventData {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "unlocked_amount": format!("{}", self.unlocked_amount),
+            "unlock_height": format!("{}", self.unlock_height),
+            "unlocked_address": format!("{}", self.unlocked_address),
+        })
+    }
+}
+
+/// A successful call to PoX's `delegate-stx`
+#[derive(Debug, Clone, PartialEq)]
+pub struct STXDelegateEventData {
+    pub stacker: PrincipalData,
+    pub delegate_to: PrincipalData,
+    pub amount_ustx: u128,
+    /// as passed to `delegate-stx`, so it may exceed any real burnchain height
+    pub until_burn_height: Option<u128>,
+    /// the PoX address tuple the delegate must stack to, if any
+    pub pox_addr: Option<Value>,
+}
+
+impl STXDelegateEventData {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "stacker": format!("{}", self.stacker),
+            "delegate_to": format!("{}", self.delegate_to),
+            "amount_ustx": format!("{}", self.amount_ustx),
+            "until_burn_height": self.until_burn_height.map(|height| format!("{}", height)),
+            "pox_addr": self.pox_addr.as_ref().map(|pox_addr| pox_addr.to_json()),
+        })
+    }
+}
+
+/// A call to PoX's `revoke-delegate-stx` that removed a delegation
+#[derive(Debug, Clone, PartialEq)]
+pub struct STXRevokeDelegateEventData {
+    pub stacker: PrincipalData,
+}
+
+impl STXRevokeDelegateEventData {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "stacker": format!("{}", self.stacker),
+        })
+    }
+}
+
+/// A successful call to PoX's `delegate-stack-stx`.  The lock itself is also reported as an
+/// `STXLockEvent`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct STXDelegateStackEventData {
+    pub stacker: PrincipalData,
+    pub delegate: PrincipalData,
+    pub locked_amount: u128,
+    pub unlock_height: u64,
+    pub pox_addr: Value,
+}
+
+impl STXDelegateStackEventData {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "stacker": format!("{}", self.stacker),
+            "delegate": format!("{}", self.delegate),
+            "locked_amount": format!("{}", self.locked_amount),
+            "unlock_height": format!("{}", self.unlock_height),
+            "pox_addr": self.pox_addr.to_json(),
+        })
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct STXBurnEventData {
     pub sender: PrincipalData,
@@ -267,6 +416,7 @@ impl NFTTransferEventData {
             "recipient": format!("{}",self.recipient),
             "value": self.value,
             "raw_value": format!("0x{}", raw_value.join("")),
+            "json_value": self.value.to_json(),
         })
     }
 }
@@ -291,6 +441,7 @@ impl NFTMintEventData {
             "recipient": format!("{}",self.recipient),
             "value": self.value,
             "raw_value": format!("0x{}", raw_value.join("")),
+            "json_value": self.value.to_json(),
         })
     }
 }
@@ -315,6 +466,7 @@ impl NFTBurnEventData {
             "sender": format!("{}",self.sender),
             "value": self.value,
             "raw_value": format!("0x{}", raw_value.join("")),
+            "json_value": self.value.to_json(),
         })
     }
 }
@@ -376,6 +528,8 @@ impl FTBurnEventData {
 pub struct SmartContractEventData {
     pub key: (QualifiedContractIdentifier, String),
     pub value: Value,
+    /// span of the printed expression in the emitting contract's source
+    pub span: Span,
 }
 
 impl SmartContractEventData {
@@ -391,6 +545,8 @@ impl SmartContractEventData {
             "topic": self.key.1,
             "value": self.value,
             "raw_value": format!("0x{}", raw_value.join("")),
+            "json_value": self.value.to_json(),
+            "span": self.span,
         })
     }
 }
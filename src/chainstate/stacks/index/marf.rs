@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::HashSet;
 use std::error;
 use std::fmt;
 use std::fs;
@@ -30,12 +31,13 @@ use chainstate::stacks::index::node::{
     clear_backptr, is_backptr, set_backptr, CursorError, TrieCursor, TrieNode, TrieNode16,
     TrieNode256, TrieNode4, TrieNode48, TrieNodeID, TrieNodeType, TriePath, TriePtr, TRIEPTR_SIZE,
 };
+use chainstate::stacks::index::prune::{prune_tries, PruneStats};
 use chainstate::stacks::index::storage::{
     TrieFileStorage, TrieStorageConnection, TrieStorageTransaction,
 };
 use chainstate::stacks::index::trie::Trie;
 use chainstate::stacks::index::Error;
-use chainstate::stacks::index::MarfTrieId;
+use chainstate::stacks::index::{trie_sql, MarfTrieId};
 use util::db::Error as db_error;
 use util::hash::Sha512Trunc256Sum;
 use util::log;
@@ -88,12 +90,14 @@ pub trait MarfConnection<T: MarfTrieId> {
         key: &str,
     ) -> Result<Option<(MARFValue, TrieMerkleProof<T>)>, Error> {
         self.with_conn(|conn| {
-            let marf_value = match MARF::get_by_key(conn, block_hash, key)? {
-                None => return Ok(None),
-                Some(x) => x,
-            };
-            let proof = TrieMerkleProof::from_raw_entry(conn, key, &marf_value, block_hash)?;
-            Ok(Some((marf_value, proof)))
+            conn.with_read_snapshot(|conn| {
+                let marf_value = match MARF::get_by_key(conn, block_hash, key)? {
+                    None => return Ok(None),
+                    Some(x) => x,
+                };
+                let proof = TrieMerkleProof::from_raw_entry(conn, key, &marf_value, block_hash)?;
+                Ok(Some((marf_value, proof)))
+            })
         })
     }
 
@@ -144,6 +148,10 @@ pub trait MarfConnection<T: MarfTrieId> {
                 ));
             }
 
+            if conn.is_block_pruned(bhh)? {
+                return Err(Error::PrunedBlockError(bhh.clone().to_bytes()));
+            }
+
             // test open
             let result = conn.open_block(bhh);
 
@@ -300,6 +308,10 @@ impl<'a, T: MarfTrieId> MarfTransaction<'a, T> {
             error!("Block data already exists: {}", next_chain_tip);
             return Err(Error::ExistsError);
         }
+        if self.storage.is_block_pruned(chain_tip)? {
+            error!("Cannot build on pruned block {}", chain_tip);
+            return Err(Error::PrunedBlockError(chain_tip.clone().to_bytes()));
+        }
 
         let block_height = self.inner_get_extension_height(chain_tip, next_chain_tip)?;
         MARF::extend_trie(&mut self.storage, next_chain_tip)?;
@@ -473,6 +485,10 @@ impl<'a, T: MarfTrieId> MarfTransaction<'a, T> {
             error!("No such confirmed block {}", chain_tip);
             return Err(Error::NotFoundError);
         }
+        if self.storage.is_block_pruned(chain_tip)? {
+            error!("Cannot build on pruned block {}", chain_tip);
+            return Err(Error::PrunedBlockError(chain_tip.clone().to_bytes()));
+        }
 
         let unconfirmed_tip = MARF::make_unconfirmed_chain_tip(chain_tip);
 
@@ -1037,7 +1053,7 @@ impl<T: MarfTrieId> MARF<T> {
         Ok(MARF::from_storage(file_storage))
     }
 
-    pub fn get_by_key(
+    fn inner_get_by_key(
         storage: &mut TrieStorageConnection<T>,
         block_hash: &T,
         key: &str,
@@ -1066,6 +1082,14 @@ impl<T: MarfTrieId> MARF<T> {
         result.map(|option_result| option_result.map(|leaf| leaf.data))
     }
 
+    pub fn get_by_key(
+        storage: &mut TrieStorageConnection<T>,
+        block_hash: &T,
+        key: &str,
+    ) -> Result<Option<MARFValue>, Error> {
+        storage.with_read_snapshot(|storage| MARF::inner_get_by_key(storage, block_hash, key))
+    }
+
     pub fn get_block_height_miner_tip(
         storage: &mut TrieStorageConnection<T>,
         block_hash: &T,
@@ -1216,8 +1240,70 @@ impl<T: MarfTrieId> MARF<T> {
     }
 
     /// Target the MARF's storage at a given block.
+    /// Fails with PrunedBlockError if the block's state has been pruned.
     pub fn open_block(&mut self, block_hash: &T) -> Result<(), Error> {
-        self.storage.connection().open_block(block_hash)
+        let mut conn = self.storage.connection();
+        if conn.is_block_pruned(block_hash)? {
+            return Err(Error::PrunedBlockError(block_hash.clone().to_bytes()));
+        }
+        conn.open_block(block_hash)
+    }
+
+    /// Has the state at this block been pruned?
+    pub fn is_block_pruned(&mut self, block_hash: &T) -> Result<bool, Error> {
+        self.storage.connection().is_block_pruned(block_hash)
+    }
+
+    /// Prune trie state that is not needed to read the state of the blocks within `depth` blocks
+    /// of `tip`, or to build on them, compacting at most `max_tries` tries.  See
+    /// chainstate::stacks::index::prune for details.  Returns the pass's stats and the hashes of
+    /// the values that are still live, so the caller can prune its side store.
+    pub fn prune(
+        &mut self,
+        tip: &T,
+        depth: u32,
+        max_tries: usize,
+    ) -> Result<(PruneStats, HashSet<String>), Error> {
+        if self.storage.readonly() {
+            return Err(Error::ReadOnlyError);
+        }
+        if self.open_chain_tip.is_some() {
+            return Err(Error::InProgressError);
+        }
+        let tx = self.storage_tx()?;
+        let result = prune_tries(&tx, tip, depth, max_tries)?;
+        tx.commit()?;
+
+        // cached block IDs are unaffected, but cached ancestor hashes may refer to rewritten tries
+        self.storage
+            .connection()
+            .clear_cached_ancestor_hashes_bytes();
+        Ok(result)
+    }
+
+    /// Recompute and check the hashes of every confirmed trie in the MARF (see
+    /// Trie::verify_hashes).  Returns the number of tries checked.
+    pub fn verify_hashes(&mut self) -> Result<u64, Error> {
+        if self.open_chain_tip.is_some() {
+            return Err(Error::InProgressError);
+        }
+        let block_ids = trie_sql::read_all_block_ids::<T>(self.sqlite_conn())?;
+        let mut conn = self.storage.connection();
+        let (cur_block_hash, cur_block_id) = conn.get_cur_block_and_id();
+
+        let mut num_checked = 0;
+        let result = block_ids
+            .iter()
+            .filter(|(_, _, unconfirmed)| !unconfirmed)
+            .try_for_each(|(block_id, block_hash, _)| {
+                conn.open_block_known_id(block_hash, *block_id)?;
+                Trie::verify_hashes(&mut conn)?;
+                num_checked += 1;
+                Ok(())
+            });
+
+        conn.open_block_maybe_id(&cur_block_hash, cur_block_id)?;
+        result.map(|_| num_checked)
     }
 
     pub fn get_with_proof(
@@ -1225,13 +1311,14 @@ impl<T: MarfTrieId> MARF<T> {
         block_hash: &T,
         key: &str,
     ) -> Result<Option<(MARFValue, TrieMerkleProof<T>)>, Error> {
-        let mut conn = self.storage.connection();
-        let marf_value = match MARF::get_by_key(&mut conn, block_hash, key)? {
-            None => return Ok(None),
-            Some(x) => x,
-        };
-        let proof = TrieMerkleProof::from_raw_entry(&mut conn, key, &marf_value, block_hash)?;
-        Ok(Some((marf_value, proof)))
+        self.storage.connection().with_read_snapshot(|conn| {
+            let marf_value = match MARF::get_by_key(conn, block_hash, key)? {
+                None => return Ok(None),
+                Some(x) => x,
+            };
+            let proof = TrieMerkleProof::from_raw_entry(conn, key, &marf_value, block_hash)?;
+            Ok(Some((marf_value, proof)))
+        })
     }
 
     pub fn get_bhh_at_height(&mut self, block_hash: &T, height: u32) -> Result<Option<T>, Error> {
@@ -38,6 +38,7 @@ pub mod bits;
 pub mod marf;
 pub mod node;
 pub mod proofs;
+pub mod prune;
 pub mod storage;
 pub mod trie;
 pub mod trie_sql;
@@ -250,6 +251,7 @@ pub enum Error {
     CursorError(node::CursorError),
     RestoreMarfBlockError(Box<Error>),
     NonMatchingForks([u8; 32], [u8; 32]),
+    PrunedBlockError([u8; 32]),
 }
 
 impl From<io::Error> for Error {
@@ -312,6 +314,10 @@ impl fmt::Display for Error {
             Error::NonMatchingForks(_, _) => {
                 write!(f, "The supplied blocks are not in the same fork")
             }
+            Error::PrunedBlockError(_) => write!(
+                f,
+                "The state at the supplied block has been pruned from this MARF"
+            ),
             Error::RequestedIdentifierForExtensionTrie => {
                 write!(f, "BUG: MARF requested the identifier for a RAM trie")
             }
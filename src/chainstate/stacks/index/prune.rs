@@ -0,0 +1,861 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Pruning of historical trie state.
+//!
+//! Every block's trie is kept forever by default, since each new trie back-points into its
+//! ancestors' tries.  A non-archival node only needs the state reachable from its most recent
+//! blocks, so pruning compacts the tries of all blocks more than `depth` blocks below the chain
+//! tip.  A compacted trie keeps:
+//!
+//! * every node that is reachable from a trie that is still intact (i.e. live state),
+//! * the in-trie ancestors of those nodes, so Merkle proofs of live state can still be built,
+//! * the nodes on the paths to the MARF's block-height bookkeeping keys, so the skip-list of
+//!   ancestor root hashes can still be computed, and
+//! * its root node, plus the hashes (but not the children) of the siblings of all of the above.
+//!
+//! Everything else is dropped, and the remaining nodes' pointers are rewritten.  The block IDs of
+//! compacted tries do not change, and neither do their root hashes, so consensus hashing is
+//! unaffected.  Queries _at_ a compacted block fail with `Error::PrunedBlockError`.
+//!
+//! A pass compacts a bounded batch of tries, so a node can prune as it goes.  Other connections
+//! may be reading the MARF while a pass commits, since pruning rewrites trie blobs in place:
+//! MARF lookups outside of a transaction run in a read snapshot for this reason (see
+//! TrieStorageConnection::with_read_snapshot()).
+
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::io::Cursor;
+
+use rusqlite::{Connection, NO_PARAMS};
+
+use chainstate::stacks::index::bits::{get_node_byte_len, read_nodetype, write_nodetype_bytes};
+use chainstate::stacks::index::marf::{BLOCK_HEIGHT_TO_HASH_MAPPING_KEY, OWN_BLOCK_HEIGHT_KEY};
+use chainstate::stacks::index::node::{
+    clear_backptr, is_backptr, TrieNodeID, TrieNodeType, TriePath, TriePtr,
+};
+use chainstate::stacks::index::storage::TrieStorageConnection;
+use chainstate::stacks::index::{trie_sql, Error, MarfTrieId};
+
+use crate::types::proof::{TrieHash, TrieLeaf};
+
+/// A node in the MARF, identified by its trie's local block ID and its offset in that trie.
+type NodeRef = (u32, u32);
+
+/// What happened during a pruning pass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PruneStats {
+    /// Height of the tip the pass was relative to
+    pub tip_height: u32,
+    /// Number of tries this pass compacted
+    pub pruned_tries: u64,
+    /// Number of tries that were left intact, including the backlog
+    pub retained_tries: u64,
+    /// Number of tries that are old enough to compact, but were left for a later pass
+    pub backlog_tries: u64,
+    /// Total size of all trie blobs before the pass
+    pub bytes_before: u64,
+    /// Total size of all trie blobs after the pass
+    pub bytes_after: u64,
+}
+
+/// A compacted trie, ready to be written.
+struct CompactedTrie {
+    block_id: u32,
+    header: Vec<u8>,
+    /// (old offset, node, hash, is-stub), ordered by old offset
+    nodes: Vec<(u32, TrieNodeType, TrieHash, bool)>,
+    /// old offset --> new offset
+    remap: HashMap<u32, u32>,
+}
+
+fn root_ptr_disk<T: MarfTrieId>() -> u32 {
+    TrieStorageConnection::<T>::root_ptr_disk()
+}
+
+/// Does this pointer lead to a node that a compaction dropped?  Offset 0 is the trie's header,
+/// so no live pointer can have it.
+fn is_dropped(ptr: &TriePtr) -> bool {
+    ptr.id() != TrieNodeID::Empty as u8 && ptr.ptr() == 0
+}
+
+/// Where does a child pointer of a node in block_id lead?
+fn child_ref(block_id: u32, ptr: &TriePtr) -> Option<(NodeRef, u8)> {
+    if ptr.id() == TrieNodeID::Empty as u8 || is_dropped(ptr) {
+        None
+    } else if is_backptr(ptr.id()) {
+        Some(((ptr.back_block(), ptr.ptr()), clear_backptr(ptr.id())))
+    } else {
+        Some(((block_id, ptr.ptr()), ptr.id()))
+    }
+}
+
+fn read_node(conn: &Connection, node: NodeRef, id: u8) -> Result<(TrieNodeType, TrieHash), Error> {
+    trie_sql::read_node_type(conn, node.0, &TriePtr::new(id, 0, node.1))
+}
+
+/// Walk `path` from the root of the trie in block_id, following back-pointers, and pass every
+/// node visited to `visit`.  Returns the leaf the path resolves to, if any.
+fn walk_path<T: MarfTrieId, F: FnMut(NodeRef)>(
+    conn: &Connection,
+    block_id: u32,
+    path: &TriePath,
+    visit: &mut F,
+) -> Result<Option<TrieLeaf>, Error> {
+    let path_bytes = path.as_bytes();
+    let mut cur = (block_id, root_ptr_disk::<T>());
+    let mut cur_id = TrieNodeID::Node256 as u8;
+    let mut index = 0;
+
+    for _ in 0..(path_bytes.len() + 1) {
+        visit(cur);
+        let (node, _) = read_node(conn, cur, cur_id)?;
+        for chr in node.path_bytes().iter() {
+            if index >= path_bytes.len() || *chr != path_bytes[index] {
+                return Ok(None);
+            }
+            index += 1;
+        }
+
+        if let TrieNodeType::Leaf(leaf) = node {
+            return Ok(Some(leaf));
+        }
+        if index >= path_bytes.len() {
+            return Ok(None);
+        }
+
+        let chr = path_bytes[index];
+        index += 1;
+        match node.walk(chr).and_then(|ptr| child_ref(cur.0, &ptr)) {
+            Some((next, next_id)) => {
+                cur = next;
+                cur_id = next_id;
+            }
+            None => {
+                return Ok(None);
+            }
+        }
+    }
+
+    Err(Error::CorruptionError("Trie has a cycle".to_string()))
+}
+
+/// Read the block height a trie records for itself.
+fn read_own_height<T: MarfTrieId>(conn: &Connection, block_id: u32) -> Result<u32, Error> {
+    let path = TriePath::from_key(OWN_BLOCK_HEIGHT_KEY);
+    walk_path::<T, _>(conn, block_id, &path, &mut |_| {})?
+        .map(|leaf| u32::from(leaf.data))
+        .ok_or_else(|| {
+            Error::CorruptionError(format!("No block height recorded in trie {}", block_id))
+        })
+}
+
+/// Read all of the nodes stored in a trie blob, in breadth-first order from the root.  Returns
+/// (offset, node ID, node, hash) for each node, and a map from each non-root node's offset to
+/// its parent's offset.
+fn read_trie_nodes<T: MarfTrieId>(
+    blob: &[u8],
+) -> Result<(Vec<(u32, u8, TrieNodeType, TrieHash)>, HashMap<u32, u32>), Error> {
+    let mut f = Cursor::new(blob);
+    let mut nodes = vec![];
+    let mut parents = HashMap::new();
+    let mut frontier = vec![(root_ptr_disk::<T>(), TrieNodeID::Node256 as u8)];
+
+    let mut i = 0;
+    while i < frontier.len() {
+        let (offset, id) = frontier[i];
+        i += 1;
+
+        let (node, hash) = read_nodetype(&mut f, &TriePtr::new(id, 0, offset))?;
+        if !node.is_leaf() {
+            for ptr in node.ptrs().iter() {
+                if ptr.id() != TrieNodeID::Empty as u8 && !is_backptr(ptr.id()) && !is_dropped(ptr)
+                {
+                    if parents.insert(ptr.ptr(), offset).is_some() {
+                        return Err(Error::CorruptionError(format!(
+                            "Trie node at {} has more than one parent",
+                            ptr.ptr()
+                        )));
+                    }
+                    frontier.push((ptr.ptr(), ptr.id()));
+                }
+            }
+        }
+        nodes.push((offset, id, node, hash));
+    }
+
+    Ok((nodes, parents))
+}
+
+/// Find every node reachable from the roots of the given tries.  Returns the set of nodes and the
+/// set of leaf values (as hex strings, the way side stores key them) found.
+fn find_reachable(
+    conn: &Connection,
+    roots: &[NodeRef],
+) -> Result<(HashSet<NodeRef>, HashSet<String>), Error> {
+    let mut visited = HashSet::new();
+    let mut values = HashSet::new();
+    let mut frontier: Vec<(NodeRef, u8)> = roots
+        .iter()
+        .map(|root| (root.clone(), TrieNodeID::Node256 as u8))
+        .collect();
+
+    while let Some((node_ref, id)) = frontier.pop() {
+        if !visited.insert(node_ref) {
+            continue;
+        }
+        let (node, _) = read_node(conn, node_ref, id)?;
+        if let TrieNodeType::Leaf(ref leaf) = node {
+            values.insert(leaf.data.to_hex());
+            continue;
+        }
+        for ptr in node.ptrs().iter() {
+            if let Some((child, child_id)) = child_ref(node_ref.0, ptr) {
+                if !visited.contains(&child) {
+                    frontier.push((child, child_id));
+                }
+            }
+        }
+    }
+
+    Ok((visited, values))
+}
+
+/// Plan the compaction of a single trie, given the nodes in it that must be kept whole.
+fn plan_compaction<T: MarfTrieId>(
+    conn: &Connection,
+    block_id: u32,
+    keep: &HashSet<u32>,
+) -> Result<CompactedTrie, Error> {
+    let blob = trie_sql::read_trie_blob(conn, block_id)?;
+    let root_offset = root_ptr_disk::<T>();
+    let (nodes, parents) = read_trie_nodes::<T>(&blob)?;
+
+    // keep the root, and the in-trie ancestors of every node we must keep
+    let mut full = HashSet::new();
+    full.insert(root_offset);
+    for offset in keep.iter() {
+        let mut cur = *offset;
+        while full.insert(cur) {
+            match parents.get(&cur) {
+                Some(parent) => cur = *parent,
+                None => {
+                    if cur != root_offset {
+                        return Err(Error::CorruptionError(format!(
+                            "Live node at {} is not in trie {}",
+                            cur, block_id
+                        )));
+                    }
+                    break;
+                }
+            }
+        }
+    }
+
+    // siblings of kept nodes are retained as stubs, so their hashes can still be read
+    let mut kept = vec![];
+    for (offset, _id, node, hash) in nodes.into_iter() {
+        if full.contains(&offset) {
+            kept.push((offset, node, hash, false));
+        } else if parents
+            .get(&offset)
+            .map(|parent| full.contains(parent))
+            .unwrap_or(false)
+        {
+            kept.push((offset, node, hash, true));
+        }
+    }
+    kept.sort_by_key(|(offset, ..)| *offset);
+
+    let mut remap = HashMap::new();
+    let mut next_offset = root_offset;
+    for (offset, node, _, _) in kept.iter() {
+        remap.insert(*offset, next_offset);
+        next_offset += get_node_byte_len(node) as u32;
+    }
+
+    Ok(CompactedTrie {
+        block_id,
+        header: blob[0..(root_offset as usize)].to_vec(),
+        nodes: kept,
+        remap,
+    })
+}
+
+/// Rewrite a back-pointer into a compacted trie.  Returns true if the pointer changed.
+fn remap_backptr(
+    ptr: &mut TriePtr,
+    compacted: &HashMap<u32, CompactedTrie>,
+    must_exist: bool,
+) -> Result<bool, Error> {
+    if ptr.id() == TrieNodeID::Empty as u8 || !is_backptr(ptr.id()) {
+        return Ok(false);
+    }
+    let target = match compacted.get(&ptr.back_block()) {
+        Some(target) => target,
+        None => return Ok(false),
+    };
+    match target.remap.get(&ptr.ptr()) {
+        Some(new_offset) => {
+            let changed = ptr.ptr != *new_offset;
+            ptr.ptr = *new_offset;
+            Ok(changed)
+        }
+        None if must_exist => Err(Error::CorruptionError(format!(
+            "Live back-pointer to pruned node {} in trie {}",
+            ptr.ptr(),
+            ptr.back_block()
+        ))),
+        None => {
+            // the target was dropped.  The pointer's ID, character, and block are still needed
+            // for hashing, but it can no longer be followed (see is_dropped()).
+            let changed = ptr.ptr != 0;
+            ptr.ptr = 0;
+            Ok(changed)
+        }
+    }
+}
+
+fn write_compacted_trie(
+    trie: &CompactedTrie,
+    compacted: &HashMap<u32, CompactedTrie>,
+) -> Result<Vec<u8>, Error> {
+    let mut f = Cursor::new(Vec::with_capacity(trie.header.len()));
+    f.get_mut().extend_from_slice(&trie.header);
+    f.set_position(trie.header.len() as u64);
+
+    for (offset, node, hash, is_stub) in trie.nodes.iter() {
+        let mut node = node.clone();
+        if !node.is_leaf() {
+            for ptr in node.ptrs_mut().iter_mut() {
+                if ptr.id() == TrieNodeID::Empty as u8 {
+                    continue;
+                }
+                if *is_stub {
+                    // only this node's hash is needed.  The pointer's ID, character, and block
+                    // are kept, since the node's children hashes are derived from them.
+                    ptr.ptr = 0;
+                } else if is_backptr(ptr.id()) {
+                    remap_backptr(ptr, compacted, false)?;
+                } else {
+                    ptr.ptr = *trie.remap.get(&ptr.ptr()).ok_or_else(|| {
+                        Error::CorruptionError(format!(
+                            "Child {} of kept node {} in trie {} was not kept",
+                            ptr.ptr(),
+                            offset,
+                            trie.block_id
+                        ))
+                    })?;
+                }
+            }
+        }
+
+        assert_eq!(Some(&(f.position() as u32)), trie.remap.get(offset));
+        write_nodetype_bytes(&mut f, &node, hash.clone())?;
+    }
+
+    Ok(f.into_inner())
+}
+
+/// Rewrite the back-pointers of an intact trie that point into compacted tries.  Returns the new
+/// blob, or None if nothing changed.
+fn rewrite_retained_trie<T: MarfTrieId>(
+    blob: Vec<u8>,
+    compacted: &HashMap<u32, CompactedTrie>,
+) -> Result<Option<Vec<u8>>, Error> {
+    let (nodes, _) = read_trie_nodes::<T>(&blob)?;
+    let mut f = Cursor::new(blob);
+    let mut changed = false;
+
+    for (offset, _id, mut node, hash) in nodes.into_iter() {
+        if node.is_leaf() {
+            continue;
+        }
+        let mut node_changed = false;
+        for ptr in node.ptrs_mut().iter_mut() {
+            node_changed |= remap_backptr(ptr, compacted, true)?;
+        }
+        if node_changed {
+            // node sizes don't depend on pointer values, so this overwrites the node exactly
+            f.set_position(offset as u64);
+            write_nodetype_bytes(&mut f, &node, hash)?;
+            changed = true;
+        }
+    }
+
+    Ok(if changed { Some(f.into_inner()) } else { None })
+}
+
+/// Compact the tries of confirmed blocks more than `depth` blocks below the given tip, at most
+/// `max_tries` of them per pass, oldest first.  Unconfirmed tries, the tries of all blocks within
+/// `depth` blocks of the tip's height (on any fork), and the tries left for a later pass are kept
+/// intact.  A compacted trie is never compacted again: it keeps the state that was live when it
+/// left the window.  Since a trie only back-points into its ancestors, compacting the oldest
+/// tries first means no compacted trie ever points into one that a later pass will move.
+///
+/// This must run in a transaction, while no trie is being extended.  Tries in the mined_blocks
+/// table are never read back, and are not rewritten.
+///
+/// Returns the pass's stats, and the set of value hashes (hex-encoded) that are still live, so
+/// the caller can prune its side store with prune_side_store().
+pub fn prune_tries<T: MarfTrieId>(
+    conn: &Connection,
+    tip: &T,
+    depth: u32,
+    max_tries: usize,
+) -> Result<(PruneStats, HashSet<String>), Error> {
+    trie_sql::create_pruned_table_if_needed(conn)?;
+
+    let blocks = trie_sql::read_all_block_ids::<T>(conn)?;
+    let tip_id = trie_sql::get_confirmed_block_identifier(conn, tip)?.ok_or_else(|| {
+        warn!("Cannot prune from unknown tip {}", tip);
+        Error::NotFoundError
+    })?;
+    // decide which tries stay intact.  A trie compacted by an earlier pass can't be walked
+    // anymore, so it stays compacted even if this pass's window would otherwise include it.
+    let already_pruned = trie_sql::get_pruned_block_ids(conn)?;
+    if already_pruned.contains(&tip_id) {
+        return Err(Error::PrunedBlockError(tip.clone().to_bytes()));
+    }
+    let tip_height = read_own_height::<T>(conn, tip_id)?;
+    let bytes_before = trie_sql::get_total_trie_bytes(conn)?;
+    let mut roots = vec![];
+    let mut prunable = vec![];
+    for (block_id, _, unconfirmed) in blocks.iter() {
+        if already_pruned.contains(block_id) {
+            continue;
+        }
+        if *unconfirmed {
+            roots.push((*block_id, root_ptr_disk::<T>()));
+            continue;
+        }
+        let height = read_own_height::<T>(conn, *block_id)?;
+        if height.saturating_add(depth) >= tip_height {
+            roots.push((*block_id, root_ptr_disk::<T>()));
+        } else {
+            prunable.push((*block_id, height));
+        }
+    }
+
+    prunable.sort_by_key(|(block_id, height)| (*height, *block_id));
+    let backlog = prunable.split_off(cmp::min(max_tries, prunable.len()));
+    for (block_id, _) in backlog.iter() {
+        roots.push((*block_id, root_ptr_disk::<T>()));
+    }
+
+    debug!(
+        "Prune MARF to {} ({} blocks below height {}): {} tries retained, {} compacted, {} left for a later pass",
+        tip,
+        depth,
+        tip_height,
+        roots.len(),
+        prunable.len(),
+        backlog.len()
+    );
+
+    let (reachable, live_values) = find_reachable(conn, &roots)?;
+
+    // pin the paths needed to compute each compacted trie's ancestor hashes
+    let mut keep: HashMap<u32, HashSet<u32>> = HashMap::new();
+    for (block_id, offset) in reachable.iter() {
+        keep.entry(*block_id)
+            .or_insert_with(HashSet::new)
+            .insert(*offset);
+    }
+    for (block_id, height) in prunable.iter() {
+        let mut keys = vec![OWN_BLOCK_HEIGHT_KEY.to_string()];
+        let mut log_depth = 0;
+        while log_depth < 32 && (1u32 << log_depth) <= *height {
+            keys.push(format!(
+                "{}::{}",
+                BLOCK_HEIGHT_TO_HASH_MAPPING_KEY,
+                height - (1u32 << log_depth)
+            ));
+            log_depth += 1;
+        }
+        for key in keys.iter() {
+            walk_path::<T, _>(conn, *block_id, &TriePath::from_key(key), &mut |node_ref| {
+                keep.entry(node_ref.0)
+                    .or_insert_with(HashSet::new)
+                    .insert(node_ref.1);
+            })?;
+        }
+    }
+
+    let mut compacted = HashMap::new();
+    let no_nodes = HashSet::new();
+    for (block_id, _) in prunable.iter() {
+        let trie = plan_compaction::<T>(conn, *block_id, keep.get(block_id).unwrap_or(&no_nodes))?;
+        compacted.insert(*block_id, trie);
+    }
+
+    for trie in compacted.values() {
+        let blob = write_compacted_trie(trie, &compacted)?;
+        trie_sql::update_trie_blob(conn, trie.block_id, &blob)?;
+        trie_sql::mark_block_pruned(conn, trie.block_id)?;
+    }
+
+    for (block_id, _) in roots.iter() {
+        let blob = trie_sql::read_trie_blob(conn, *block_id)?;
+        if let Some(new_blob) = rewrite_retained_trie::<T>(blob, &compacted)? {
+            trie_sql::update_trie_blob(conn, *block_id, &new_blob)?;
+        }
+    }
+
+    let bytes_after = trie_sql::get_total_trie_bytes(conn)?;
+
+    let stats = PruneStats {
+        tip_height,
+        pruned_tries: compacted.len() as u64,
+        retained_tries: roots.len() as u64,
+        backlog_tries: backlog.len() as u64,
+        bytes_before,
+        bytes_after,
+    };
+
+    Ok((stats, live_values))
+}
+
+/// Delete the values in a MARF side store whose hashes are no longer in any live leaf.
+/// `table` and `key_column` name the side store's table and its value-hash column.
+/// Returns the number of values deleted.
+pub fn prune_side_store(
+    conn: &Connection,
+    table: &str,
+    key_column: &str,
+    live_values: &HashSet<String>,
+) -> Result<u64, Error> {
+    let dead: Vec<String> = {
+        let mut s = conn.prepare(&format!("SELECT {} FROM {}", key_column, table))?;
+        let rows = s.query_and_then(NO_PARAMS, |row| -> Result<String, Error> {
+            Ok(row.get(0)?)
+        })?;
+        let mut dead = vec![];
+        for key in rows {
+            let key = key?;
+            if !live_values.contains(&key) {
+                dead.push(key);
+            }
+        }
+        dead
+    };
+
+    let sql = format!("DELETE FROM {} WHERE {} = ?", table, key_column);
+    for key in dead.iter() {
+        conn.execute(&sql, &[key])?;
+    }
+    Ok(dead.len() as u64)
+}
+
+#[cfg(test)]
+mod test {
+    use std::ops::Range;
+
+    use chainstate::stacks::index::marf::*;
+    use chainstate::stacks::index::test::merkle_test_marf_key_value;
+    use chainstate::stacks::index::MARFValue;
+
+    use crate::types::chainstate::BlockHeaderHash;
+    use crate::types::proof::ClarityMarfTrieId;
+
+    use super::*;
+
+    fn value_at(key: &str, height: u8) -> String {
+        format!("{}@{}", key, height)
+    }
+
+    /// Build one block per height, each of which overwrites a counter and adds its own key.
+    fn extend_chain(
+        marf: &mut MARF<BlockHeaderHash>,
+        parent: &BlockHeaderHash,
+        heights: Range<u8>,
+    ) -> Vec<BlockHeaderHash> {
+        let mut parent = parent.clone();
+        let mut blocks = vec![];
+        for i in heights {
+            let block = BlockHeaderHash([i + 1; 32]);
+            marf.begin(&parent, &block).unwrap();
+
+            let counter = format!("counter-{}", i % 4);
+            let own = format!("block-{}", i);
+            marf.insert(&counter, MARFValue::from_value(&value_at(&counter, i)))
+                .unwrap();
+            marf.insert(&own, MARFValue::from_value(&value_at(&own, i)))
+                .unwrap();
+            marf.commit().unwrap();
+
+            parent = block.clone();
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    fn live_keys(tip_height: u8) -> Vec<(String, String)> {
+        let mut keys = vec![];
+        for i in 0..(tip_height + 1) {
+            let own = format!("block-{}", i);
+            keys.push((own.clone(), value_at(&own, i)));
+        }
+        for i in (tip_height - 3)..(tip_height + 1) {
+            let counter = format!("counter-{}", i % 4);
+            keys.push((counter.clone(), value_at(&counter, i)));
+        }
+        keys
+    }
+
+    #[test]
+    fn prune_preserves_tip_state_and_hashes() {
+        let mut pruned = MARF::from_path(":memory:").unwrap();
+        let mut archive = MARF::from_path(":memory:").unwrap();
+        let blocks = extend_chain(&mut pruned, &BlockHeaderHash::sentinel(), 0..24);
+        extend_chain(&mut archive, &BlockHeaderHash::sentinel(), 0..24);
+        let tip = blocks[23].clone();
+
+        let root_hashes: Vec<_> = blocks
+            .iter()
+            .map(|b| pruned.get_root_hash_at(b).unwrap())
+            .collect();
+
+        let (stats, live_values) = pruned.prune(&tip, 4, usize::MAX).unwrap();
+        assert_eq!(stats.tip_height, 23);
+        assert_eq!(stats.pruned_tries, 19);
+        assert_eq!(stats.retained_tries, 5);
+        assert_eq!(stats.backlog_tries, 0);
+        assert!(stats.bytes_after < stats.bytes_before);
+
+        // root hashes are part of consensus, and must not change
+        for (block, root_hash) in blocks.iter().zip(root_hashes.iter()) {
+            assert_eq!(&pruned.get_root_hash_at(block).unwrap(), root_hash);
+        }
+
+        // the compacted tries still hash to them
+        assert_eq!(
+            pruned.verify_hashes().unwrap(),
+            archive.verify_hashes().unwrap()
+        );
+
+        // all live state is still readable and provable from the tip
+        let mut root_to_block = None;
+        for (key, value) in live_keys(23).iter() {
+            assert_eq!(
+                pruned.get(&tip, key).unwrap(),
+                Some(MARFValue::from_value(value))
+            );
+            assert_eq!(
+                pruned.get(&tip, key).unwrap(),
+                archive.get(&tip, key).unwrap()
+            );
+            assert!(live_values.contains(&MARFValue::from_value(value).to_hex()));
+            root_to_block = Some(merkle_test_marf_key_value(
+                &mut pruned.borrow_storage_backend(),
+                &tip,
+                key,
+                value,
+                root_to_block,
+            ));
+        }
+
+        // overwritten values are gone
+        assert!(!live_values.contains(&MARFValue::from_value(&value_at("counter-0", 0)).to_hex()));
+
+        // block heights of compacted blocks can still be resolved
+        assert_eq!(
+            MARF::get_block_height(&mut pruned.borrow_storage_backend(), &blocks[0], &tip).unwrap(),
+            Some(0)
+        );
+        assert_eq!(
+            MARF::get_block_at_height(&mut pruned.borrow_storage_backend(), 0, &tip).unwrap(),
+            Some(blocks[0].clone())
+        );
+
+        assert!(pruned.is_block_pruned(&blocks[18]).unwrap());
+        assert!(!pruned.is_block_pruned(&blocks[19]).unwrap());
+        match pruned.open_block(&blocks[0]) {
+            Err(Error::PrunedBlockError(_)) => {}
+            x => panic!("Opened pruned block: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn prune_then_extend_matches_archive() {
+        let mut pruned = MARF::from_path(":memory:").unwrap();
+        let mut archive = MARF::from_path(":memory:").unwrap();
+        let blocks = extend_chain(&mut pruned, &BlockHeaderHash::sentinel(), 0..16);
+        extend_chain(&mut archive, &BlockHeaderHash::sentinel(), 0..16);
+
+        pruned.prune(&blocks[15], 2, usize::MAX).unwrap();
+
+        match pruned.begin(&blocks[0], &BlockHeaderHash([0xfe; 32])) {
+            Err(Error::PrunedBlockError(_)) => {}
+            x => panic!("Built on pruned block: {:?}", x),
+        }
+
+        // building on the tip after pruning yields the same tries as building without pruning
+        let new_blocks = extend_chain(&mut pruned, &blocks[15], 16..32);
+        extend_chain(&mut archive, &blocks[15], 16..32);
+        for block in new_blocks.iter() {
+            assert_eq!(
+                pruned.get_root_hash_at(block).unwrap(),
+                archive.get_root_hash_at(block).unwrap()
+            );
+        }
+
+        // pruning again moves the window, and is idempotent
+        let tip = new_blocks[15].clone();
+        let (stats, _) = pruned.prune(&tip, 2, usize::MAX).unwrap();
+        assert_eq!(stats.pruned_tries, 16);
+        assert_eq!(stats.retained_tries, 3);
+
+        let (again, _) = pruned.prune(&tip, 2, usize::MAX).unwrap();
+        assert_eq!(again.pruned_tries, 0);
+        assert_eq!(again.bytes_before, stats.bytes_after);
+        assert_eq!(again.bytes_after, stats.bytes_after);
+
+        let mut root_to_block = None;
+        for (key, value) in live_keys(31).iter() {
+            assert_eq!(
+                pruned.get(&tip, key).unwrap(),
+                archive.get(&tip, key).unwrap()
+            );
+            root_to_block = Some(merkle_test_marf_key_value(
+                &mut pruned.borrow_storage_backend(),
+                &tip,
+                key,
+                value,
+                root_to_block,
+            ));
+        }
+    }
+
+    #[test]
+    fn prune_in_batches_matches_archive() {
+        let mut pruned = MARF::from_path(":memory:").unwrap();
+        let mut archive = MARF::from_path(":memory:").unwrap();
+        let blocks = extend_chain(&mut pruned, &BlockHeaderHash::sentinel(), 0..24);
+        extend_chain(&mut archive, &BlockHeaderHash::sentinel(), 0..24);
+        let tip = blocks[23].clone();
+
+        // 19 tries are old enough to compact, so this takes 4 passes, oldest tries first
+        let mut compacted = 0;
+        for (pass, expected) in [5, 5, 5, 4].iter().enumerate() {
+            let (stats, _) = pruned.prune(&tip, 4, 5).unwrap();
+            assert_eq!(stats.pruned_tries, *expected);
+            assert_eq!(stats.backlog_tries, 19 - compacted - *expected);
+            compacted += *expected;
+
+            assert!(pruned
+                .is_block_pruned(&blocks[compacted as usize - 1])
+                .unwrap());
+            assert!(!pruned.is_block_pruned(&blocks[compacted as usize]).unwrap());
+            assert_eq!(
+                pruned.verify_hashes().unwrap(),
+                archive.verify_hashes().unwrap(),
+                "pass {}",
+                pass
+            );
+            for (key, value) in live_keys(23).iter() {
+                assert_eq!(
+                    pruned.get(&tip, key).unwrap(),
+                    Some(MARFValue::from_value(value))
+                );
+            }
+            // state at the oldest intact block is still readable
+            let oldest = &blocks[compacted as usize];
+            assert_eq!(
+                pruned.get(oldest, "counter-0").unwrap(),
+                archive.get(oldest, "counter-0").unwrap()
+            );
+        }
+
+        let (done, _) = pruned.prune(&tip, 4, 5).unwrap();
+        assert_eq!(done.pruned_tries, 0);
+        assert_eq!(done.backlog_tries, 0);
+
+        // building on the tip still yields the same tries as building without pruning
+        let new_blocks = extend_chain(&mut pruned, &tip, 24..28);
+        extend_chain(&mut archive, &tip, 24..28);
+        for block in new_blocks.iter() {
+            assert_eq!(
+                pruned.get_root_hash_at(block).unwrap(),
+                archive.get_root_hash_at(block).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn pruned_blocks_known_after_reopen() {
+        let path = "/tmp/rust_marf_pruned_blocks_known_after_reopen";
+        if std::fs::metadata(path).is_ok() {
+            std::fs::remove_file(path).unwrap();
+        }
+
+        let blocks = {
+            let mut marf = MARF::from_path(path).unwrap();
+            let blocks = extend_chain(&mut marf, &BlockHeaderHash::sentinel(), 0..8);
+            assert!(!marf.is_block_pruned(&blocks[0]).unwrap());
+
+            // a connection that is open while another one prunes sees the pass too
+            let mut reader = MARF::from_path(path).unwrap();
+            assert!(!reader.is_block_pruned(&blocks[0]).unwrap());
+            marf.prune(&blocks[7], 2, usize::MAX).unwrap();
+            assert!(reader.is_block_pruned(&blocks[0]).unwrap());
+            match reader.open_block(&blocks[0]) {
+                Err(Error::PrunedBlockError(_)) => {}
+                x => panic!("Opened pruned block: {:?}", x),
+            }
+            assert_eq!(
+                reader.get(&blocks[7], "counter-3").unwrap(),
+                Some(MARFValue::from_value(&value_at("counter-3", 7)))
+            );
+            blocks
+        };
+
+        let mut marf = MARF::from_path(path).unwrap();
+        assert!(marf.is_block_pruned(&blocks[0]).unwrap());
+        assert!(!marf.is_block_pruned(&blocks[7]).unwrap());
+        match marf.open_block(&blocks[0]) {
+            Err(Error::PrunedBlockError(_)) => {}
+            x => panic!("Opened pruned block: {:?}", x),
+        }
+
+        let mut ro_marf = marf.reopen_readonly().unwrap();
+        assert!(ro_marf.is_block_pruned(&blocks[0]).unwrap());
+    }
+
+    #[test]
+    fn prune_side_store_drops_dead_values() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE data_table (key TEXT PRIMARY KEY, value TEXT);
+             INSERT INTO data_table VALUES ('aa', 'live'), ('bb', 'dead'), ('cc', 'dead');",
+        )
+        .unwrap();
+
+        let mut live = HashSet::new();
+        live.insert("aa".to_string());
+        assert_eq!(
+            prune_side_store(&conn, "data_table", "key", &live).unwrap(),
+            2
+        );
+
+        let remaining: Vec<String> = {
+            let mut s = conn.prepare("SELECT key FROM data_table").unwrap();
+            let rows = s.query_map(NO_PARAMS, |row| row.get(0)).unwrap();
+            rows.map(|r| r.unwrap()).collect()
+        };
+        assert_eq!(remaining, vec!["aa".to_string()]);
+    }
+}
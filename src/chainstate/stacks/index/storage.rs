@@ -802,6 +802,8 @@ impl<T: MarfTrieId> TrieFileStorage<T> {
 
         if create_flag {
             trie_sql::create_tables_if_needed(&mut db)?;
+        } else if !readonly {
+            trie_sql::create_pruned_table_if_needed(&db)?;
         }
 
         debug!("Opened TrieFileStorage {};", db_path);
@@ -1358,6 +1360,32 @@ impl<'a, T: MarfTrieId> TrieStorageConnection<'a, T> {
         Ok(self.has_confirmed_block(bhh)? || self.has_unconfirmed_block(bhh)?)
     }
 
+    /// Has the trie for this block been compacted by pruning?  This is looked up each time,
+    /// since another connection may prune the MARF while this one is open.
+    pub fn is_block_pruned(&self, bhh: &T) -> Result<bool, Error> {
+        match trie_sql::get_confirmed_block_identifier(&self.db, bhh)? {
+            Some(block_id) => trie_sql::is_block_id_pruned(&self.db, block_id),
+            None => Ok(false),
+        }
+    }
+
+    /// Run `f` against a single consistent view of the trie blobs.  Pruning rewrites blobs in
+    /// place, so a walk outside of a transaction could otherwise read a node from before another
+    /// connection's pruning pass committed, and its child from after.  Inside a transaction, the
+    /// transaction already gives `f` such a view.
+    pub fn with_read_snapshot<F, R>(&mut self, f: F) -> Result<R, Error>
+    where
+        F: FnOnce(&mut Self) -> Result<R, Error>,
+    {
+        if !self.db.is_autocommit() {
+            return f(self);
+        }
+        self.db.execute_batch("BEGIN DEFERRED")?;
+        let result = f(self);
+        self.db.execute_batch("COMMIT")?;
+        result
+    }
+
     // used for providing a option<block identifier> when re-opening a block --
     //   because the previously open block may have been the last_extended block,
     //   id may have been None.
@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::collections::HashSet;
 use std::error;
 /// This module defines the methods for reading and inserting into a Trie
 use std::fmt;
@@ -138,11 +139,19 @@ impl Trie {
                 // shouldn't happen
                 return Err(Error::CorruptionError("ptr is empty".to_string()));
             }
+            if ptr.ptr() == 0 {
+                // child was dropped when this trie was pruned
+                return Err(Error::PrunedBlockError(storage.get_cur_block().to_bytes()));
+            }
             let (node, node_hash) = storage.read_nodetype(ptr)?;
             return Ok((node, node_hash, ptr.clone()));
         } else {
             // ptr is a backptr -- find the block
             let back_block_hash = storage.get_block_from_local_id(ptr.back_block())?.clone();
+            if ptr.ptr() == 0 {
+                // child was dropped when this pointer's trie was pruned
+                return Err(Error::PrunedBlockError(storage.get_cur_block().to_bytes()));
+            }
             storage.open_block_known_id(&back_block_hash, ptr.back_block())?;
 
             let backptr = ptr.from_backptr();
@@ -730,6 +739,67 @@ impl Trie {
         }
     }
 
+    /// Recompute the hash of every node in the currently-open trie from its contents and its
+    /// children's stored hashes, and check that it matches the node's stored hash.  The root's
+    /// hash includes its ancestors' root hashes, so checking every trie in a MARF this way checks
+    /// all of its state against the tips' root hashes.  Nodes whose children were dropped by
+    /// pruning can't be recomputed, so only their hashes are taken as given.
+    pub fn verify_hashes<T: MarfTrieId>(
+        storage: &mut TrieStorageConnection<T>,
+    ) -> Result<(), Error> {
+        let root_ptr = storage.root_trieptr();
+        let (root, root_hash) = Trie::read_root(storage)?;
+        let mut frontier = vec![(root_ptr.ptr(), root, root_hash)];
+        let mut visited = HashSet::new();
+
+        while let Some((offset, node, stored_hash)) = frontier.pop() {
+            if !visited.insert(offset) {
+                return Err(Error::CorruptionError(format!(
+                    "Trie node at {} in {} has more than one parent",
+                    offset,
+                    storage.get_cur_block()
+                )));
+            }
+
+            let mut is_stub = false;
+            if !node.is_leaf() {
+                for ptr in node.ptrs().iter() {
+                    if ptr.id() == TrieNodeID::Empty as u8 || is_backptr(ptr.id()) {
+                        continue;
+                    }
+                    if ptr.ptr() == 0 {
+                        // offset 0 is the trie header, so this child was dropped
+                        is_stub = true;
+                        continue;
+                    }
+                    let (child, child_hash) = storage.read_nodetype(ptr)?;
+                    frontier.push((ptr.ptr(), child, child_hash));
+                }
+            }
+            if is_stub {
+                continue;
+            }
+
+            let mut hash = match node {
+                TrieNodeType::Leaf(ref leaf) => get_leaf_hash(leaf),
+                _ => get_nodetype_hash(storage, &node)?,
+            };
+            if offset == root_ptr.ptr() {
+                hash = Trie::get_trie_root_hash(storage, &hash)?;
+            }
+            if hash != stored_hash {
+                return Err(Error::CorruptionError(format!(
+                    "Trie node at {} in {} hashes to {}, but its stored hash is {}",
+                    offset,
+                    storage.get_cur_block(),
+                    hash,
+                    stored_hash
+                )));
+            }
+        }
+        Ok(())
+    }
+
     /// Unwind a TrieCursor to update the Merkle root of the trie.
     /// The root hashes of each trie form a Merkle skip-list -- the hash of Trie i is calculated
     /// from the hash of its children, plus the hash Tries i-1, i-2, i-4, i-8, ..., i-2**j, ...
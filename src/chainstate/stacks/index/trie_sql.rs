@@ -82,6 +82,10 @@ static SQL_EXTENSION_LOCKS_TABLE: &str = "
 CREATE TABLE IF NOT EXISTS block_extension_locks (block_hash TEXT PRIMARY KEY);
 ";
 
+static SQL_MARF_PRUNED_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS pruned_blocks (block_id INTEGER PRIMARY KEY);
+";
+
 pub fn create_tables_if_needed(conn: &mut Connection) -> Result<(), Error> {
     sql_pragma(conn, "PRAGMA journal_mode = WAL;")?;
 
@@ -90,10 +94,85 @@ pub fn create_tables_if_needed(conn: &mut Connection) -> Result<(), Error> {
     tx.execute_batch(SQL_MARF_DATA_TABLE)?;
     tx.execute_batch(SQL_MARF_MINED_TABLE)?;
     tx.execute_batch(SQL_EXTENSION_LOCKS_TABLE)?;
+    tx.execute_batch(SQL_MARF_PRUNED_TABLE)?;
 
     tx.commit().map_err(|e| e.into())
 }
 
+/// Create the table of pruned tries.  MARFs created before pruning existed don't have it, so
+/// it's created whenever a MARF is opened for writing.
+pub fn create_pruned_table_if_needed(conn: &Connection) -> Result<(), Error> {
+    conn.execute_batch(SQL_MARF_PRUNED_TABLE)?;
+    Ok(())
+}
+
+/// Get the local IDs of all tries that have been compacted by pruning.
+pub fn get_pruned_block_ids(conn: &Connection) -> Result<HashSet<u32>, Error> {
+    let mut s = conn.prepare("SELECT block_id FROM pruned_blocks")?;
+    let rows = s.query_and_then(NO_PARAMS, |row| -> Result<u32, Error> {
+        Ok(row.get("block_id")?)
+    })?;
+    rows.collect()
+}
+
+/// Has the trie with this local ID been compacted by pruning?
+pub fn is_block_id_pruned(conn: &Connection, block_id: u32) -> Result<bool, Error> {
+    let found: Option<u32> = conn
+        .query_row(
+            "SELECT block_id FROM pruned_blocks WHERE block_id = ?",
+            &[block_id],
+            |row| row.get("block_id"),
+        )
+        .optional()?;
+    Ok(found.is_some())
+}
+
+pub fn mark_block_pruned(conn: &Connection, block_id: u32) -> Result<(), Error> {
+    conn.execute(
+        "INSERT OR IGNORE INTO pruned_blocks (block_id) VALUES (?)",
+        &[block_id],
+    )?;
+    Ok(())
+}
+
+/// Get the local ID, block hash, and unconfirmed flag of every trie in marf_data.
+pub fn read_all_block_ids<T: MarfTrieId>(conn: &Connection) -> Result<Vec<(u32, T, bool)>, Error> {
+    let mut s =
+        conn.prepare("SELECT block_id, block_hash, unconfirmed FROM marf_data ORDER BY block_id")?;
+    let rows = s.query_and_then(NO_PARAMS, |row| -> Result<(u32, T, bool), Error> {
+        let block_id: u32 = row.get("block_id")?;
+        let block_hash: T = row.get("block_hash")?;
+        let unconfirmed: i64 = row.get("unconfirmed")?;
+        Ok((block_id, block_hash, unconfirmed != 0))
+    })?;
+    rows.collect()
+}
+
+/// Get the total size of all trie blobs in marf_data.
+pub fn get_total_trie_bytes(conn: &Connection) -> Result<u64, Error> {
+    let bytes: i64 = conn.query_row(
+        "SELECT IFNULL(SUM(length(data)), 0) FROM marf_data",
+        NO_PARAMS,
+        |row| row.get(0),
+    )?;
+    Ok(bytes as u64)
+}
+
+pub fn read_trie_blob(conn: &Connection, block_id: u32) -> Result<Vec<u8>, Error> {
+    conn.query_row(
+        "SELECT data FROM marf_data WHERE block_id = ?",
+        &[block_id],
+        |row| row.get("data"),
+    )
+    .map_err(|e| e.into())
+}
+
+pub fn update_trie_blob(conn: &Connection, block_id: u32, data: &[u8]) -> Result<(), Error> {
+    let args: &[&dyn ToSql] = &[&data, &block_id];
+    conn.execute("UPDATE marf_data SET data = ? WHERE block_id = ?", args)?;
+    Ok(())
+}
+
 pub fn get_block_identifier<T: MarfTrieId>(conn: &Connection, bhh: &T) -> Result<u32, Error> {
     conn.query_row(
         "SELECT block_id FROM marf_data WHERE block_hash = ?",
@@ -436,5 +515,6 @@ pub fn clear_tables(tx: &Transaction) -> Result<(), Error> {
     tx.execute("DELETE FROM block_extension_locks", NO_PARAMS)?;
     tx.execute("DELETE FROM marf_data", NO_PARAMS)?;
     tx.execute("DELETE FROM mined_blocks", NO_PARAMS)?;
+    tx.execute("DELETE FROM pruned_blocks", NO_PARAMS)?;
     Ok(())
 }
@@ -99,6 +99,25 @@ impl From<&UnconfirmedState> for MicroblockMinerRuntime {
 ///     StacksMicroblockBuilder holds a mutable reference to the provided chainstate in the
 ///       new function. This is required for the `clarity_tx` -- basically, to append transactions
 ///       as new microblocks, the builder _needs_ to be able to keep the current clarity_tx "open"
+/// Signs the headers of the microblocks a `StacksMicroblockBuilder` produces.  Usually this is the
+/// miner's microblock private key, but the key may also be held outside of the node.
+pub trait MicroblockHeaderSigner {
+    /// Hash of the public key the miner committed to in its anchored block
+    fn microblock_pubkey_hash(&self) -> Hash160;
+    /// Sign the header in place
+    fn sign_microblock_header(&mut self, header: &mut StacksMicroblockHeader) -> Result<(), Error>;
+}
+
+impl MicroblockHeaderSigner for Secp256k1PrivateKey {
+    fn microblock_pubkey_hash(&self) -> Hash160 {
+        Hash160::from_node_public_key(&StacksPublicKey::from_private(self))
+    }
+
+    fn sign_microblock_header(&mut self, header: &mut StacksMicroblockHeader) -> Result<(), Error> {
+        header.sign(self).map_err(Error::NetError)
+    }
+}
+
 pub struct StacksMicroblockBuilder<'a> {
     anchor_block: BlockHeaderHash,
     anchor_block_consensus_hash: ConsensusHash,
@@ -249,10 +268,9 @@ impl<'a> StacksMicroblockBuilder<'a> {
     fn make_next_microblock(
         &mut self,
         txs: Vec<StacksTransaction>,
-        miner_key: &Secp256k1PrivateKey,
+        signer: &mut dyn MicroblockHeaderSigner,
     ) -> Result<StacksMicroblock, Error> {
-        let miner_pubkey_hash =
-            Hash160::from_node_public_key(&StacksPublicKey::from_private(miner_key));
+        let miner_pubkey_hash = signer.microblock_pubkey_hash();
         if txs.len() == 0 {
             return Err(Error::NoTransactionsToMine);
         }
@@ -270,8 +288,10 @@ impl<'a> StacksMicroblockBuilder<'a> {
                 StacksMicroblockHeader::first_unsigned(&self.anchor_block, &tx_merkle_root)
             };
 
-        next_microblock_header.sign(miner_key).unwrap();
-        next_microblock_header.verify(&miner_pubkey_hash).unwrap();
+        signer.sign_microblock_header(&mut next_microblock_header)?;
+        next_microblock_header
+            .verify(&miner_pubkey_hash)
+            .map_err(Error::NetError)?;
 
         self.runtime.prev_microblock_header = Some(next_microblock_header.clone());
 
@@ -412,13 +432,24 @@ impl<'a> StacksMicroblockBuilder<'a> {
             _ => {}
         }
 
-        return self.make_next_microblock(txs_included, miner_key);
+        let mut signer = miner_key.clone();
+        return self.make_next_microblock(txs_included, &mut signer);
     }
 
     pub fn mine_next_microblock(
         &mut self,
         mem_pool: &MemPoolDB,
         miner_key: &Secp256k1PrivateKey,
+    ) -> Result<StacksMicroblock, Error> {
+        let mut signer = miner_key.clone();
+        self.mine_next_microblock_with_signer(mem_pool, &mut signer)
+    }
+
+    /// Mine the next microblock from the mempool, and have `signer` sign its header.
+    pub fn mine_next_microblock_with_signer(
+        &mut self,
+        mem_pool: &MemPoolDB,
+        signer: &mut dyn MicroblockHeaderSigner,
     ) -> Result<StacksMicroblock, Error> {
         let mut txs_included = vec![];
 
@@ -495,7 +526,7 @@ impl<'a> StacksMicroblockBuilder<'a> {
             }
         }
 
-        return self.make_next_microblock(txs_included, miner_key);
+        return self.make_next_microblock(txs_included, signer);
     }
 
     pub fn get_bytes_so_far(&self) -> u64 {
@@ -665,6 +696,18 @@ impl StacksBlockBuilder {
         self.header.parent_microblock_sequence = parent_mblock_seq;
     }
 
+    /// Choose how much of the parent's microblock stream this block will confirm: the fork ending
+    /// in the given microblock, or no microblocks at all if `None`.  Absent a call to this method,
+    /// the builder confirms the parent chain tip's microblock tail, or failing that, every parent
+    /// microblock it knows about.  Must be called before `epoch_begin()`.
+    pub fn set_parent_microblock_tail(&mut self, parent_mblock_hash: Option<&BlockHeaderHash>) {
+        self.parent_microblock_hash = Some(
+            parent_mblock_hash
+                .cloned()
+                .unwrap_or(EMPTY_MICROBLOCK_PARENT_HASH.clone()),
+        );
+    }
+
     /// Set the block header's public key hash
     pub fn set_microblock_pubkey_hash(&mut self, pubkh: Hash160) -> bool {
         if self.anchored_done {
@@ -1018,6 +1061,11 @@ impl StacksBlockBuilder {
         parent_index_hash: &StacksBlockId,
     ) -> Result<Vec<StacksMicroblock>, Error> {
         if let Some(microblock_parent_hash) = self.parent_microblock_hash.as_ref() {
+            if *microblock_parent_hash == EMPTY_MICROBLOCK_PARENT_HASH {
+                // explicitly confirming no microblocks
+                return Ok(vec![]);
+            }
+
             // load up a microblock fork
             let microblocks = StacksChainState::load_microblock_stream_fork(
                 &chainstate.db(),
@@ -1545,6 +1593,7 @@ pub mod test {
     use chainstate::burn::*;
     use chainstate::stacks::db::test::*;
     use chainstate::stacks::db::*;
+    use chainstate::stacks::Error as ChainstateError;
     use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
     use chainstate::stacks::*;
     use core::BLOCK_LIMIT_MAINNET;
@@ -6164,6 +6213,173 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn process_block_reading_pruned_state_is_not_invalid() {
+        let full_test_name = "process-block-reading-pruned-state";
+        let mut burn_node = TestBurnchainNode::new();
+        let mut miner_factory = TestMinerFactory::new();
+        let mut miner =
+            miner_factory.next_miner(&burn_node.burnchain, 1, 1, AddressHashMode::SerializeP2PKH);
+
+        let mut node = TestStacksNode::new(
+            false,
+            0x80000000,
+            full_test_name,
+            vec![miner.origin_address().unwrap()],
+        );
+
+        let first_snapshot =
+            SortitionDB::get_first_block_snapshot(burn_node.sortdb.conn()).unwrap();
+        let mut fork = TestBurnchainFork::new(
+            first_snapshot.block_height,
+            &first_snapshot.burn_header_hash,
+            &first_snapshot.index_root,
+            0,
+        );
+
+        let mut first_burn_block =
+            TestStacksNode::next_burn_block(&mut burn_node.sortdb, &mut fork);
+        node.add_key_register(&mut first_burn_block, &mut miner);
+        fork.append_block(first_burn_block);
+        burn_node.mine_fork(&mut fork);
+
+        let rounds = 5;
+        let mut first_block_id: Option<StacksBlockId> = None;
+        let mut chain_tip_id: Option<StacksBlockId> = None;
+        for i in 0..rounds {
+            let mut burn_block = {
+                let ic = burn_node.sortdb.index_conn();
+                fork.next_block(&ic)
+            };
+
+            let last_key = node.get_last_key(&miner);
+            let parent_block_opt = node.get_last_accepted_anchored_block(&burn_node.sortdb, &miner);
+            node.add_key_register(&mut burn_block, &mut miner);
+
+            // the last block reads the state of the first, which is pruned before it's processed
+            let at_block_id = if i == rounds - 1 {
+                first_block_id.clone()
+            } else {
+                None
+            };
+            let (stacks_block, microblocks, block_commit_op) = node.mine_stacks_block(
+                &mut burn_node.sortdb,
+                &mut miner,
+                &mut burn_block,
+                &last_key,
+                parent_block_opt.as_ref(),
+                1000,
+                |mut builder, ref mut miner, ref sortdb| {
+                    let mut miner_chainstate = open_chainstate(false, 0x80000000, full_test_name);
+                    let sort_iconn = sortdb.index_conn();
+                    let mut epoch = builder
+                        .epoch_begin(&mut miner_chainstate, &sort_iconn)
+                        .unwrap();
+                    let (stacks_block, microblocks) = match at_block_id {
+                        None => mine_empty_anchored_block(&mut epoch, &mut builder, miner, i, None),
+                        Some(ref block_id) => {
+                            let miner_account = StacksChainState::get_account(
+                                &mut epoch,
+                                &miner.origin_address().unwrap().to_account_principal(),
+                            );
+                            miner.set_nonce(miner_account.nonce);
+
+                            let tx_coinbase_signed = make_coinbase(miner, i);
+                            builder
+                                .try_mine_tx(&mut epoch, &tx_coinbase_signed)
+                                .unwrap();
+
+                            let contract = format!(
+                                "(define-constant old-height (at-block 0x{} block-height))",
+                                block_id.to_hex()
+                            );
+                            let mut tx_contract = StacksTransaction::new(
+                                TransactionVersion::Testnet,
+                                miner.as_transaction_auth().unwrap(),
+                                TransactionPayload::new_smart_contract("read-old-state", &contract)
+                                    .unwrap(),
+                            );
+                            tx_contract.chain_id = 0x80000000;
+                            tx_contract.auth.set_origin_nonce(miner.get_nonce());
+                            tx_contract.set_tx_fee(0);
+
+                            let mut tx_signer = StacksTransactionSigner::new(&tx_contract);
+                            miner.sign_as_origin(&mut tx_signer);
+                            builder
+                                .try_mine_tx(&mut epoch, &tx_signer.get_tx().unwrap())
+                                .unwrap();
+
+                            (builder.mine_anchored_block(&mut epoch), vec![])
+                        }
+                    };
+                    builder.epoch_finish(epoch);
+                    (stacks_block, microblocks)
+                },
+            );
+
+            fork.append_block(burn_block);
+            let fork_snapshot = burn_node.mine_fork(&mut fork);
+            preprocess_stacks_block_data(
+                &mut node,
+                &mut burn_node,
+                &fork_snapshot,
+                &stacks_block,
+                &microblocks,
+                &block_commit_op,
+            );
+
+            if at_block_id.is_none() {
+                let tip_info_list = node
+                    .chainstate
+                    .process_blocks_at_tip(&mut burn_node.sortdb, 1)
+                    .unwrap();
+                let chain_tip = tip_info_list[0].0.clone().unwrap().header;
+                if first_block_id.is_none() {
+                    first_block_id = Some(chain_tip.index_block_hash());
+                }
+                chain_tip_id = Some(chain_tip.index_block_hash());
+                continue;
+            }
+
+            node.chainstate
+                .prune_clarity_state(chain_tip_id.as_ref().unwrap(), 1, usize::MAX)
+                .unwrap();
+            assert!(node
+                .chainstate
+                .is_clarity_state_pruned(first_block_id.as_ref().unwrap())
+                .unwrap());
+
+            // this node can't evaluate the block, but it must not be marked invalid, and can be
+            // retried (e.g. once the node is resynced)
+            for _ in 0..2 {
+                match node
+                    .chainstate
+                    .process_blocks_at_tip(&mut burn_node.sortdb, 1)
+                {
+                    Err(ChainstateError::ClarityStatePruned(pruned_block)) => {
+                        assert_eq!(&pruned_block, first_block_id.as_ref().unwrap());
+                    }
+                    x => panic!("Expected ClarityStatePruned, got {:?}", &x),
+                }
+                assert_eq!(
+                    StacksChainState::get_staging_block_status(
+                        &node.chainstate.db(),
+                        &fork_snapshot.consensus_hash,
+                        &stacks_block.block_hash()
+                    )
+                    .unwrap(),
+                    Some(false)
+                );
+                assert!(!StacksChainState::is_block_orphaned(
+                    &node.chainstate.db(),
+                    &fork_snapshot.consensus_hash,
+                    &stacks_block.block_hash()
+                )
+                .unwrap());
+            }
+        }
+    }
+
     // TODO: merge with vm/tests/integrations.rs.
     // Distinct here because we use a different testnet ID
     pub fn make_user_contract_publish(
@@ -166,6 +166,9 @@ pub enum Error {
     PoxAlreadyLocked,
     PoxInsufficientBalance,
     PoxNoRewardCycle,
+    /// A transaction read Clarity state (e.g. via `at-block`) at a block whose state this node
+    /// has pruned.  The block may well be valid; this node just can't evaluate it.
+    ClarityStatePruned(StacksBlockId),
 }
 
 impl From<marf_error> for Error {
@@ -229,6 +232,9 @@ impl fmt::Display for Error {
             Error::PoxAlreadyLocked => write!(f, "Account has already locked STX for PoX"),
             Error::PoxInsufficientBalance => write!(f, "Not enough STX to lock"),
             Error::PoxNoRewardCycle => write!(f, "No such reward cycle"),
+            Error::ClarityStatePruned(ref block) => {
+                write!(f, "Clarity state at {} has been pruned", block)
+            }
         }
     }
 }
@@ -261,6 +267,7 @@ impl error::Error for Error {
             Error::PoxAlreadyLocked => None,
             Error::PoxInsufficientBalance => None,
             Error::PoxNoRewardCycle => None,
+            Error::ClarityStatePruned(_) => None,
         }
     }
 }
@@ -293,6 +300,7 @@ impl Error {
             Error::PoxAlreadyLocked => "PoxAlreadyLocked",
             Error::PoxInsufficientBalance => "PoxInsufficientBalance",
             Error::PoxNoRewardCycle => "PoxNoRewardCycle",
+            Error::ClarityStatePruned(_) => "ClarityStatePruned",
         }
     }
 
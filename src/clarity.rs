@@ -31,6 +31,7 @@ use rusqlite::{Connection, OpenFlags, NO_PARAMS};
 
 use address::c32::c32_address;
 use chainstate::stacks::index::{storage::TrieFileStorage, MarfTrieId};
+use codegen::generate_rust_bindings;
 use util::db::FromColumn;
 use util::hash::Sha512Trunc256Sum;
 
@@ -39,17 +40,22 @@ use vm::ContractName;
 
 use vm::analysis;
 use vm::analysis::contract_interface_builder::build_contract_interface;
+use vm::analysis::cost_estimator::estimate_contract_costs;
+use vm::analysis::lint_checker::{LintChecker, LintConfig};
 use vm::analysis::{errors::CheckError, errors::CheckResult, AnalysisDatabase, ContractAnalysis};
-use vm::ast::build_ast;
+use vm::ast::formatter::format_source;
+use vm::ast::{build_ast, build_ast_with_diagnostics};
 use vm::contexts::{AssetMap, OwnedEnvironment};
-use vm::costs::ExecutionCost;
+use vm::costs::cost_functions::ClarityCostFunction;
 use vm::costs::LimitedCostTracker;
+use vm::costs::{CostTracker, ExecutionCost};
 use vm::database::{
     BurnStateDB, ClarityDatabase, HeadersDB, STXBalance, SqliteConnection, NULL_BURN_STATE_DB,
     NULL_HEADER_DB,
 };
+use vm::diagnostic::Diagnostic;
 use vm::errors::{Error, InterpreterResult, RuntimeErrorType};
-use vm::types::{PrincipalData, QualifiedContractIdentifier};
+use vm::types::{FunctionType, PrincipalData, QualifiedContractIdentifier, TypeSignature};
 use vm::{execute as vm_execute, SymbolicExpression, SymbolicExpressionType, Value};
 
 use burnchains::PoxConstants;
@@ -70,6 +76,7 @@ use std::convert::TryFrom;
 use crate::clarity_vm::database::marf::MarfedKV;
 use crate::clarity_vm::database::marf::WritableMarfStore;
 use crate::clarity_vm::database::MemoryBackingStore;
+use crate::clarity_vm::fuzz::{ContractFuzzer, FuzzConfig};
 use crate::types::chainstate::BlockHeaderHash;
 use crate::types::chainstate::BurnchainHeaderHash;
 use crate::types::chainstate::StacksAddress;
@@ -97,7 +104,10 @@ fn print_usage(invoked_by: &str) {
 where command is one of:
 
   initialize         to initialize a local VM state database.
-  check              to typecheck a potential contract definition.
+  check              to typecheck a potential contract definition, reporting every error found.
+  fmt                to format a contract's source code, preserving comments.
+  codegen            to generate typed Rust bindings for a contract's public interface.
+  fuzz               to check a contract's invariant functions against random sequences of calls.
   launch             to launch a initialize a new contract in the local state database.
   eval               to evaluate (in read-only mode) a program in a given contract context.
   eval_at_chaintip   like `eval`, but does not advance to a new block.
@@ -144,7 +154,7 @@ fn parse(
     Ok(ast.expressions)
 }
 
-trait ClarityStorage {
+pub(crate) trait ClarityStorage {
     fn get_clarity_db<'a>(
         &'a mut self,
         headers_db: &'a dyn HeadersDB,
@@ -223,6 +233,31 @@ fn run_analysis<C: ClarityStorage>(
     )
 }
 
+pub(crate) fn run_analysis_with_diagnostics<C: ClarityStorage>(
+    contract_identifier: &QualifiedContractIdentifier,
+    expressions: &mut [SymbolicExpression],
+    header_db: &CLIHeadersDB,
+    marf_kv: &mut C,
+) -> (ContractAnalysis, Vec<Diagnostic>) {
+    let mainnet = header_db.is_mainnet();
+    let cost_track = LimitedCostTracker::new(
+        mainnet,
+        if mainnet {
+            BLOCK_LIMIT_MAINNET.clone()
+        } else {
+            HELIUM_BLOCK_LIMIT.clone()
+        },
+        &mut marf_kv.get_clarity_db(header_db, &NULL_BURN_STATE_DB),
+    )
+    .unwrap();
+    analysis::run_analysis_with_diagnostics(
+        contract_identifier,
+        expressions,
+        &mut marf_kv.get_analysis_db(),
+        cost_track,
+    )
+}
+
 fn create_or_open_db(path: &String) -> Connection {
     let open_flags = if path == ":memory:" {
         OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE
@@ -313,6 +348,32 @@ fn get_cli_db_path(db_path: &str) -> String {
     cli_db_path
 }
 
+/// Look up the declared argument types of a contract's public function, exiting if the contract
+/// or function doesn't exist.
+fn public_function_arg_types(
+    marf: &mut WritableMarfStore,
+    contract_identifier: &QualifiedContractIdentifier,
+    function_name: &str,
+) -> Vec<TypeSignature> {
+    let function_type = friendly_expect(
+        marf.as_analysis_db()
+            .execute(|db| db.get_public_function_type(contract_identifier, function_name)),
+        "Failed to load contract analysis.",
+    );
+    match friendly_expect_opt(
+        function_type,
+        &format!("No public function named {}", function_name),
+    ) {
+        FunctionType::Fixed(function) => {
+            function.args.into_iter().map(|arg| arg.signature).collect()
+        }
+        _ => {
+            eprintln!("Unsupported function type for {}", function_name);
+            panic_test!();
+        }
+    }
+}
+
 // This function is pretty weird! But it helps cut down on
 //   repeating a lot of block initialization for the simulation commands.
 fn in_block<F, R>(
@@ -336,7 +397,7 @@ where
 
 // like in_block, but does _not_ advance the chain tip.  Used for read-only queries against the
 // chain tip itself.
-fn at_chaintip<F, R>(db_path: &String, mut marf_kv: MarfedKV, f: F) -> R
+pub(crate) fn at_chaintip<F, R>(db_path: &String, mut marf_kv: MarfedKV, f: F) -> R
 where
     F: FnOnce(WritableMarfStore) -> (WritableMarfStore, R),
 {
@@ -393,7 +454,7 @@ where
     (result, cost)
 }
 
-struct CLIHeadersDB {
+pub(crate) struct CLIHeadersDB {
     db_path: String,
     conn: Connection,
 }
@@ -685,7 +746,7 @@ fn consume_arg(
     }
 }
 
-fn install_boot_code<C: ClarityStorage>(header_db: &CLIHeadersDB, marf: &mut C) {
+pub(crate) fn install_boot_code<C: ClarityStorage>(header_db: &CLIHeadersDB, marf: &mut C) {
     let mainnet = header_db.is_mainnet();
     let boot_code = if mainnet {
         *STACKS_BOOT_CODE_MAINNET
@@ -766,6 +827,24 @@ pub fn add_assets(result: &mut serde_json::Value, assets: bool, asset_map: Asset
     }
 }
 
+/// Runtime errors and short returns carry the contract and span of the expression that
+///   raised them.
+fn add_error_location(result: &mut serde_json::Value, error: &Error) {
+    if let Some(location) = error.location() {
+        result["error"]["location"] = location.json_serialize();
+    }
+}
+
+fn runtime_error_json(error: &Error) -> serde_json::Value {
+    let mut error_json = json!({
+        "runtime": serde_json::to_value(&format!("{}", error)).unwrap()
+    });
+    if let Some(location) = error.location() {
+        error_json["location"] = location.json_serialize();
+    }
+    error_json
+}
+
 /// Returns (process-exit-code, Option<json-output>)
 pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_json::Value>) {
     if args.len() < 1 {
@@ -892,7 +971,7 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
         "check" => {
             if args.len() < 2 {
                 eprintln!(
-                    "Usage: {} {} [program-file.clar] [--contract_id CONTRACT_ID] [--output_analysis] [--costs] [--testnet] (vm-state.db)",
+                    "Usage: {} {} [program-file.clar] [--contract_id CONTRACT_ID] [--output_analysis] [--costs] [--estimate_costs] [--lint] [--lint-disable RULE,...] [--testnet] (vm-state.db)",
                     invoked_by, args[0]
                 );
                 panic_test!();
@@ -927,6 +1006,32 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 false
             };
 
+            let estimate_costs =
+                if let Ok(Some(_)) = consume_arg(&mut argv, &["--estimate_costs"], false) {
+                    true
+                } else {
+                    false
+                };
+
+            let lint = if let Ok(Some(_)) = consume_arg(&mut argv, &["--lint"], false) {
+                true
+            } else {
+                false
+            };
+            // --lint-disable implies --lint
+            let lint_config = match consume_arg(&mut argv, &["--lint-disable"], true) {
+                Ok(Some(names)) => Some(friendly_expect(
+                    LintConfig::from_disabled_names(&names),
+                    "Invalid argument for --lint-disable",
+                )),
+                Ok(None) if lint => Some(LintConfig::default()),
+                Ok(None) => None,
+                Err(_) => {
+                    eprintln!("Expected argument for --lint-disable");
+                    panic_test!();
+                }
+            };
+
             // NOTE: ignored if we're using a DB
             let mut testnet_given = false;
             let mainnet = if let Ok(Some(_)) = consume_arg(&mut argv, &["--testnet"], false) {
@@ -950,9 +1055,11 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 )
             };
 
-            let mut ast = friendly_expect(parse(&contract_id, &content), "Failed to parse program");
+            // even if parsing fails, analyze the forms that did parse, and report both.
+            let (mut ast, parse_diagnostics, parse_success) =
+                build_ast_with_diagnostics(&contract_id, &content, &mut ());
 
-            let contract_analysis_res = {
+            let (mut contract_analysis, analysis_diagnostics) = {
                 if argv.len() >= 3 {
                     // use a persisted marf
                     if testnet_given {
@@ -968,8 +1075,12 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                     );
 
                     let result = at_chaintip(&argv[2], marf_kv, |mut marf| {
-                        let result =
-                            run_analysis(&contract_id, &mut ast, &header_db, &mut marf, false);
+                        let result = run_analysis_with_diagnostics(
+                            &contract_id,
+                            &mut ast.expressions,
+                            &header_db,
+                            &mut marf,
+                        );
                         (marf, result)
                     });
                     result
@@ -978,46 +1089,360 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                     let mut analysis_marf = MemoryBackingStore::new();
 
                     install_boot_code(&header_db, &mut analysis_marf);
-                    run_analysis(
+                    run_analysis_with_diagnostics(
                         &contract_id,
-                        &mut ast,
+                        &mut ast.expressions,
                         &header_db,
                         &mut analysis_marf,
-                        false,
                     )
                 }
             };
 
-            let mut contract_analysis = match contract_analysis_res {
-                Ok(contract_analysis) => contract_analysis,
-                Err((e, cost_tracker)) => {
-                    let mut result = json!({
-                        "message": "Checks failed.",
-                        "error": {
-                            "analysis": serde_json::to_value(&e.diagnostic).unwrap(),
-                        }
-                    });
-                    add_costs(&mut result, costs, cost_tracker.get_total());
-                    return (1, Some(result));
-                }
-            };
+            if !parse_success || analysis_diagnostics.len() > 0 {
+                let error = if !parse_success {
+                    json!({ "parse": serde_json::to_value(&parse_diagnostics[0]).unwrap() })
+                } else {
+                    json!({ "analysis": serde_json::to_value(&analysis_diagnostics[0]).unwrap() })
+                };
+                let mut diagnostics = parse_diagnostics;
+                diagnostics.extend(analysis_diagnostics);
+                let mut result = json!({
+                    "message": if parse_success { "Checks failed." } else { "Parsing failed." },
+                    "error": error,
+                    "diagnostics": serde_json::to_value(&diagnostics).unwrap(),
+                });
+                add_costs(
+                    &mut result,
+                    costs,
+                    contract_analysis.take_contract_cost_tracker().get_total(),
+                );
+                return (1, Some(result));
+            }
 
             let mut result = json!({
                 "message": "Checks passed."
             });
 
+            if estimate_costs {
+                friendly_expect(
+                    estimate_contract_costs(&mut contract_analysis),
+                    "Failed to estimate costs",
+                );
+                let mut cost_tracker = contract_analysis.take_contract_cost_tracker();
+                // charged once per transaction, on top of the function's own cost.  Constants
+                //  are charged by the size of their value, which is at most the size of their type.
+                let data_size: u64 = contract_analysis
+                    .variable_types
+                    .values()
+                    .map(|constant_type| constant_type.size() as u64)
+                    .sum();
+                let load_contract = friendly_expect(
+                    cost_tracker
+                        .compute_cost(
+                            ClarityCostFunction::LoadContract,
+                            &[content.len() as u64 + data_size],
+                        )
+                        .map_err(|e| format!("{:?}", e)),
+                    "Failed to estimate costs",
+                );
+                let block_limit = cost_tracker.get_limit();
+                contract_analysis.replace_contract_cost_tracker(cost_tracker);
+
+                let mut estimates = serde_json::Map::new();
+                for (name, estimate) in contract_analysis.cost_estimates.iter() {
+                    let mut total = estimate.cost.clone();
+                    let exceeds_block_limit = match total.add(&load_contract) {
+                        Ok(_) => total.exceeds(&block_limit),
+                        Err(_) => true,
+                    };
+                    estimates.insert(
+                        name.to_string(),
+                        json!({
+                            "cost": estimate.cost,
+                            "calls_other_contracts": estimate.calls_other_contracts,
+                            "exceeds_block_limit": exceeds_block_limit,
+                        }),
+                    );
+                }
+                result["cost_estimates"] = json!({
+                    "load_contract": load_contract,
+                    "functions": estimates,
+                });
+            }
+
             add_costs(
                 &mut result,
                 costs,
                 contract_analysis.take_contract_cost_tracker().get_total(),
             );
 
+            if let Some(lint_config) = lint_config {
+                let warnings = LintChecker::run(&contract_analysis, &lint_config);
+                result["warnings"] = serde_json::to_value(&warnings).unwrap();
+            }
+
             if output_analysis {
                 result["analysis"] =
                     serde_json::to_value(&build_contract_interface(&contract_analysis)).unwrap();
             }
             (0, Some(result))
         }
+        "fmt" => {
+            if args.len() < 2 {
+                eprintln!(
+                    "Usage: {} {} [program-file.clar|-] [--check] [--write]",
+                    invoked_by, args[0]
+                );
+                panic_test!();
+            }
+
+            let mut argv: Vec<String> = args.into_iter().map(|x| x.clone()).collect();
+            let check = if let Ok(Some(_)) = consume_arg(&mut argv, &["--check"], false) {
+                true
+            } else {
+                false
+            };
+            let write = if let Ok(Some(_)) = consume_arg(&mut argv, &["--write"], false) {
+                true
+            } else {
+                false
+            };
+            if write && &argv[1] == "-" {
+                eprintln!("Cannot --write to stdin");
+                panic_test!();
+            }
+
+            let content: String = if &argv[1] == "-" {
+                let mut buffer = String::new();
+                friendly_expect(
+                    io::stdin().read_to_string(&mut buffer),
+                    "Error reading from stdin.",
+                );
+                buffer
+            } else {
+                friendly_expect(
+                    fs::read_to_string(&argv[1]),
+                    &format!("Error reading file: {}", argv[1]),
+                )
+            };
+
+            let formatted = match format_source(&content) {
+                Ok(formatted) => formatted,
+                Err(e) => {
+                    let result = json!({
+                        "message": "Formatting failed.",
+                        "error": {
+                            "parse": serde_json::to_value(&e.diagnostic).unwrap(),
+                        },
+                    });
+                    return (1, Some(result));
+                }
+            };
+
+            if check {
+                if formatted == content {
+                    (0, Some(json!({ "message": "Already formatted." })))
+                } else {
+                    (1, Some(json!({ "message": "Not formatted." })))
+                }
+            } else if write {
+                if formatted != content {
+                    friendly_expect(
+                        fs::write(&argv[1], &formatted),
+                        &format!("Error writing file: {}", argv[1]),
+                    );
+                }
+                (0, Some(json!({ "message": "Formatted." })))
+            } else {
+                print!("{}", formatted);
+                (0, None)
+            }
+        }
+        "codegen" => {
+            if args.len() < 3 {
+                eprintln!(
+                    "Usage: {} {} [contract-identifier] [program-file.clar|interface.json|-] [--output FILE] [--testnet]",
+                    invoked_by, args[0]
+                );
+                panic_test!();
+            }
+
+            let mut argv: Vec<String> = args.into_iter().map(|x| x.clone()).collect();
+            let output = match consume_arg(&mut argv, &["--output"], true) {
+                Ok(output) => output,
+                Err(_) => {
+                    eprintln!("Expected argument for --output");
+                    panic_test!();
+                }
+            };
+            let mainnet = if let Ok(Some(_)) = consume_arg(&mut argv, &["--testnet"], false) {
+                false
+            } else {
+                true
+            };
+
+            let contract_id = friendly_expect(
+                QualifiedContractIdentifier::parse(&argv[1]),
+                &format!("Error parsing contract identifier '{}'", &argv[1]),
+            );
+
+            let content: String = if &argv[2] == "-" {
+                let mut buffer = String::new();
+                friendly_expect(
+                    io::stdin().read_to_string(&mut buffer),
+                    "Error reading from stdin.",
+                );
+                buffer
+            } else {
+                friendly_expect(
+                    fs::read_to_string(&argv[2]),
+                    &format!("Error reading file: {}", argv[2]),
+                )
+            };
+
+            let interface = if argv[2].ends_with(".json") {
+                friendly_expect(
+                    serde_json::from_str(&content),
+                    "Failed to parse contract interface",
+                )
+            } else {
+                let (mut ast, parse_diagnostics, parse_success) =
+                    build_ast_with_diagnostics(&contract_id, &content, &mut ());
+                if !parse_success {
+                    let result = json!({
+                        "message": "Parsing failed.",
+                        "error": {
+                            "parse": serde_json::to_value(&parse_diagnostics[0]).unwrap(),
+                        },
+                    });
+                    return (1, Some(result));
+                }
+
+                let header_db = CLIHeadersDB::new_memory(mainnet);
+                let mut analysis_marf = MemoryBackingStore::new();
+                install_boot_code(&header_db, &mut analysis_marf);
+                let (contract_analysis, diagnostics) = run_analysis_with_diagnostics(
+                    &contract_id,
+                    &mut ast.expressions,
+                    &header_db,
+                    &mut analysis_marf,
+                );
+                if diagnostics.len() > 0 {
+                    let result = json!({
+                        "message": "Checks failed.",
+                        "error": {
+                            "analysis": serde_json::to_value(&diagnostics[0]).unwrap(),
+                        },
+                    });
+                    return (1, Some(result));
+                }
+                build_contract_interface(&contract_analysis)
+            };
+
+            let bindings = generate_rust_bindings(&contract_id, &interface);
+            if let Some(output) = output {
+                friendly_expect(
+                    fs::write(&output, &bindings),
+                    &format!("Error writing file: {}", output),
+                );
+                (0, Some(json!({ "message": "Generated bindings." })))
+            } else {
+                print!("{}", bindings);
+                (0, None)
+            }
+        }
+        "fuzz" => {
+            if args.len() < 3 {
+                eprintln!(
+                    "Usage: {} {} [contract-identifier] [program-file.clar] [--seed N] [--runs N] [--calls N]",
+                    invoked_by, args[0]
+                );
+                panic_test!();
+            }
+
+            let mut argv: Vec<String> = args.into_iter().map(|x| x.clone()).collect();
+            let mut numeric_arg = |flag: &str| match consume_arg(&mut argv, &[flag], true) {
+                Ok(Some(value)) => Some(friendly_expect(
+                    value.parse::<u64>(),
+                    &format!("Invalid argument for {}", flag),
+                )),
+                Ok(None) => None,
+                Err(_) => {
+                    eprintln!("Expected argument for {}", flag);
+                    panic_test!();
+                }
+            };
+            let mut config = FuzzConfig::default();
+            if let Some(seed) = numeric_arg("--seed") {
+                config.seed = seed;
+            }
+            if let Some(runs) = numeric_arg("--runs") {
+                config.runs = runs as u32;
+            }
+            if let Some(calls) = numeric_arg("--calls") {
+                config.calls_per_run = calls as u32;
+            }
+
+            let contract_id = friendly_expect(
+                QualifiedContractIdentifier::parse(&argv[1]),
+                &format!("Error parsing contract identifier '{}'", &argv[1]),
+            );
+            let content = friendly_expect(
+                fs::read_to_string(&argv[2]),
+                &format!("Error reading file: {}", argv[2]),
+            );
+
+            let runs = config.runs;
+            let mut fuzzer = match ContractFuzzer::new(&contract_id, &content, config) {
+                Ok(fuzzer) => fuzzer,
+                Err(error) => {
+                    let result = json!({
+                        "error": {
+                            "initialization": serde_json::to_value(&format!("{}", error)).unwrap()
+                        }
+                    });
+                    return (1, Some(result));
+                }
+            };
+            let invariants: Vec<_> = fuzzer
+                .invariants()
+                .iter()
+                .map(|invariant| invariant.to_string())
+                .collect();
+
+            match fuzzer.run() {
+                None => (
+                    0,
+                    Some(json!({
+                        "message": "No invariant violations found.",
+                        "invariants": invariants,
+                        "runs": runs,
+                    })),
+                ),
+                Some(failure) => {
+                    let calls: Vec<_> = failure
+                        .calls
+                        .iter()
+                        .map(|call| {
+                            json!({
+                                "sender": call.sender.to_string(),
+                                "function": call.function.to_string(),
+                                "args": call.args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>(),
+                            })
+                        })
+                        .collect();
+                    (
+                        1,
+                        Some(json!({
+                            "message": "Invariant violated.",
+                            "invariant": failure.invariant.to_string(),
+                            "outcome": failure.outcome,
+                            "calls": calls,
+                        })),
+                    )
+                }
+            }
+        }
         "repl" => {
             let mut argv: Vec<String> = args.into_iter().map(|x| x.clone()).collect();
             let mainnet = if let Ok(Some(_)) = consume_arg(&mut argv, &["--testnet"], false) {
@@ -1110,9 +1535,7 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                         Err(error) => (
                             1,
                             Some(json!({
-                                "error": {
-                                    "runtime": serde_json::to_value(&format!("{}", error)).unwrap()
-                                }
+                                "error": runtime_error_json(&error)
                             })),
                         ),
                     }
@@ -1167,9 +1590,7 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 }
                 (Err(error), cost) => {
                     let mut result_json = json!({
-                        "error": {
-                            "runtime": serde_json::to_value(&format!("{}", error)).unwrap()
-                        }
+                        "error": runtime_error_json(&error)
                     });
 
                     add_costs(&mut result_json, costs, cost);
@@ -1217,9 +1638,7 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 }
                 (Err(error), cost) => {
                     let mut result_json = json!({
-                        "error": {
-                            "runtime": serde_json::to_value(&format!("{}", error)).unwrap()
-                        }
+                        "error": runtime_error_json(&error)
                     });
 
                     add_costs(&mut result_json, costs, cost);
@@ -1287,9 +1706,7 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 }
                 (Err(error), cost) => {
                     let mut result_json = json!({
-                        "error": {
-                            "runtime": serde_json::to_value(&format!("{}", error)).unwrap()
-                        }
+                        "error": runtime_error_json(&error)
                     });
 
                     add_costs(&mut result_json, costs, cost);
@@ -1397,14 +1814,15 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
 
                     (1, Some(result))
                 }
-                Ok((_, (Err(error), ..))) => (
-                    1,
-                    Some(json!({
+                Ok((_, (Err(error), ..))) => {
+                    let mut result = json!({
                         "error": {
                             "initialization": serde_json::to_value(&format!("{}", error)).unwrap()
                         }
-                    })),
-                ),
+                    });
+                    add_error_location(&mut result, &error);
+                    (1, Some(result))
+                }
             }
         }
         "execute" => {
@@ -1420,9 +1838,14 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
             } else {
                 false
             };
+            let json_args = if let Ok(Some(_)) = consume_arg(&mut argv, &["--json"], false) {
+                true
+            } else {
+                false
+            };
 
             if argv.len() < 5 {
-                eprintln!("Usage: {} {} [--costs] [--assets] [vm-state.db] [contract-identifier] [public-function-name] [sender-address] [args...]", invoked_by, argv[0]);
+                eprintln!("Usage: {} {} [--costs] [--assets] [--json] [vm-state.db] [contract-identifier] [public-function-name] [sender-address] [args...]", invoked_by, argv[0]);
                 panic_test!();
             }
 
@@ -1451,22 +1874,54 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                 }
             };
 
-            let arguments: Vec<_> = argv[5..]
-                .iter()
-                .map(|argument| {
-                    let argument_parsed = friendly_expect(
-                        vm_execute(argument),
-                        &format!("Error parsing argument \"{}\"", argument),
-                    );
-                    let argument_value = friendly_expect_opt(
-                        argument_parsed,
-                        &format!("Failed to parse a value from the argument: {}", argument),
-                    );
-                    SymbolicExpression::atom_value(argument_value)
-                })
-                .collect();
+            // with --json, arguments are in the canonical JSON encoding, and are decoded once
+            //   the function's signature is loaded below.
+            let json_arguments: Vec<serde_json::Value> = if json_args {
+                argv[5..]
+                    .iter()
+                    .map(|argument| {
+                        friendly_expect(
+                            serde_json::from_str(argument),
+                            &format!("Error parsing JSON argument \"{}\"", argument),
+                        )
+                    })
+                    .collect()
+            } else {
+                vec![]
+            };
+            let arguments: Vec<_> = if json_args {
+                vec![]
+            } else {
+                argv[5..]
+                    .iter()
+                    .map(|argument| {
+                        let argument_parsed = friendly_expect(
+                            vm_execute(argument),
+                            &format!("Error parsing argument \"{}\"", argument),
+                        );
+                        let argument_value = friendly_expect_opt(
+                            argument_parsed,
+                            &format!("Failed to parse a value from the argument: {}", argument),
+                        );
+                        SymbolicExpression::atom_value(argument_value)
+                    })
+                    .collect()
+            };
 
             let (_, _, result_and_cost) = in_block(header_db, marf_kv, |header_db, mut marf| {
+                let arguments = if json_args {
+                    let arg_types =
+                        public_function_arg_types(&mut marf, &contract_identifier, tx_name);
+                    friendly_expect(
+                        TypeSignature::args_from_json(&arg_types, &json_arguments),
+                        "Failed to decode JSON arguments.",
+                    )
+                    .into_iter()
+                    .map(SymbolicExpression::atom_value)
+                    .collect()
+                } else {
+                    arguments
+                };
                 let result_and_cost = with_env_costs(mainnet, &header_db, &mut marf, |vm_env| {
                     vm_env.execute_transaction(sender, contract_identifier, &tx_name, &arguments)
                 });
@@ -1481,6 +1936,9 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                                 "message": "Transaction executed and committed.",
                                 "output": serde_json::to_value(&data.data).unwrap(),
                             });
+                            if json_args {
+                                result["output_json"] = data.data.to_json();
+                            }
 
                             add_costs(&mut result, costs, cost);
                             add_assets(&mut result, assets, asset_map);
@@ -1497,6 +1955,9 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                                 "message": "Aborted.",
                                 "output": serde_json::to_value(&data.data).unwrap(),
                             });
+                            if json_args {
+                                result["output_json"] = data.data.to_json();
+                            }
 
                             add_costs(&mut result, costs, cost);
 
@@ -1513,12 +1974,13 @@ pub fn invoke_command(invoked_by: &str, args: &[String]) -> (i32, Option<serde_j
                     }
                 }
                 (Err(error), _) => {
-                    let result = json!({
+                    let mut result = json!({
                         "error": {
                             "runtime": "Transaction execution error.",
                             "error": serde_json::to_value(&format!("{}", error)).unwrap()
                         }
                     });
+                    add_error_location(&mut result, &error);
                     (1, Some(result))
                 }
             }
@@ -1581,6 +2043,432 @@ mod test {
         assert_eq!(exit, 0);
     }
 
+    #[test]
+    fn test_fmt() {
+        let clar_name = format!("/tmp/test-fmt_{}.clar", rand::thread_rng().gen::<i32>());
+        fs::write(
+            &clar_name,
+            "(define-data-var   counter int 0) ;; a counter\n\n\n(define-read-only (get-counter) (var-get counter))",
+        )
+        .unwrap();
+
+        let invoked = invoke_command(
+            "test",
+            &["fmt".to_string(), clar_name.clone(), "--check".to_string()],
+        );
+        assert_eq!(invoked.0, 1);
+        assert_eq!(invoked.1.unwrap()["message"], "Not formatted.");
+
+        let invoked = invoke_command(
+            "test",
+            &["fmt".to_string(), clar_name.clone(), "--write".to_string()],
+        );
+        assert_eq!(invoked.0, 0);
+        assert_eq!(
+            fs::read_to_string(&clar_name).unwrap(),
+            "(define-data-var counter int 0) ;; a counter\n\n(define-read-only (get-counter) (var-get counter))\n"
+        );
+
+        let invoked = invoke_command(
+            "test",
+            &["fmt".to_string(), clar_name.clone(), "--check".to_string()],
+        );
+        assert_eq!(invoked.0, 0);
+        assert_eq!(invoked.1.unwrap()["message"], "Already formatted.");
+
+        fs::write(&clar_name, "(define-data-var counter int 0").unwrap();
+        let invoked = invoke_command("test", &["fmt".to_string(), clar_name]);
+        assert_eq!(invoked.0, 1);
+        assert_eq!(invoked.1.unwrap()["message"], "Formatting failed.");
+    }
+
+    #[test]
+    fn test_codegen() {
+        let clar_name = format!("/tmp/test-codegen_{}.clar", rand::thread_rng().gen::<i32>());
+        let rs_name = format!("/tmp/test-codegen_{}.rs", rand::thread_rng().gen::<i32>());
+        fs::write(
+            &clar_name,
+            "(define-read-only (get-balance (who principal)) (ok u1))",
+        )
+        .unwrap();
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "codegen".to_string(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.tokens".to_string(),
+                clar_name.clone(),
+                "--output".to_string(),
+                rs_name.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+        assert_eq!(invoked.1.unwrap()["message"], "Generated bindings.");
+
+        let bindings = fs::read_to_string(&rs_name).unwrap();
+        assert!(bindings.contains(
+            "pub const CONTRACT_ID: &str = \"S1G2081040G2081040G2081040G208105NK8PE5.tokens\";"
+        ));
+        assert!(bindings.contains("pub struct GetBalanceArgs {"));
+        assert!(bindings.contains("pub type GetBalanceOutput = Result<u128, ()>;"));
+
+        fs::write(&clar_name, "(define-read-only (get-balance) (+ 1 u1))").unwrap();
+        let invoked = invoke_command(
+            "test",
+            &[
+                "codegen".to_string(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.tokens".to_string(),
+                clar_name,
+            ],
+        );
+        assert_eq!(invoked.0, 1);
+        assert_eq!(invoked.1.unwrap()["message"], "Checks failed.");
+    }
+
+    #[test]
+    fn test_check_lint() {
+        let clar_name = format!(
+            "/tmp/test-check-lint_{}.clar",
+            rand::thread_rng().gen::<i32>()
+        );
+        fs::write(
+            &clar_name,
+            "(define-private (unused) 1)\n(define-read-only (f) (let ((a 1)) 2))",
+        )
+        .unwrap();
+
+        let invoked = invoke_command("test", &["check".to_string(), clar_name.clone()]);
+        assert_eq!(invoked.0, 0);
+        assert!(invoked.1.unwrap().get("warnings").is_none());
+
+        let invoked = invoke_command(
+            "test",
+            &["check".to_string(), clar_name.clone(), "--lint".to_string()],
+        );
+        assert_eq!(invoked.0, 0);
+        let result = invoked.1.unwrap();
+        assert_eq!(result["message"], "Checks passed.");
+        assert_eq!(result["warnings"].as_array().unwrap().len(), 2);
+        assert_eq!(result["warnings"][0]["level"], "Warning");
+        assert_eq!(result["warnings"][1]["spans"][0]["start_line"], 2);
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "check".to_string(),
+                clar_name,
+                "--lint-disable".to_string(),
+                "unused-let-binding".to_string(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+        assert_eq!(invoked.1.unwrap()["warnings"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_check_estimate_costs() {
+        let db_name = format!("/tmp/db_{}", rand::thread_rng().gen::<i32>());
+        let clar_name = format!(
+            "/tmp/test-estimate-costs_{}.clar",
+            rand::thread_rng().gen::<i32>()
+        );
+        fs::write(
+            &clar_name,
+            "(define-map scores principal uint)
+(define-data-var total uint u0)
+(define-private (add-score (amount uint) (sum uint)) (+ sum amount))
+(define-public (record (amounts (list 10 uint)))
+  (let ((sum (fold add-score amounts u0)))
+    (map-set scores tx-sender sum)
+    (var-set total (+ (var-get total) sum))
+    (if (> sum u100) (ok sum) (err u1))))",
+        )
+        .unwrap();
+
+        invoke_command("test", &["initialize".to_string(), db_name.clone()]);
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "check".to_string(),
+                clar_name.clone(),
+                "--estimate_costs".to_string(),
+                "--output_analysis".to_string(),
+                db_name.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+        let result = invoked.1.unwrap();
+        let estimates = &result["cost_estimates"];
+        let estimate: ExecutionCost =
+            serde_json::from_value(estimates["functions"]["record"]["cost"].clone()).unwrap();
+        let load_contract: ExecutionCost =
+            serde_json::from_value(estimates["load_contract"].clone()).unwrap();
+        assert_eq!(
+            estimates["functions"]["record"]["exceeds_block_limit"],
+            json!(false)
+        );
+        assert!(estimates["functions"].get("add-score").is_none());
+        assert_eq!(
+            result["analysis"]["functions"][1]["cost_estimate"]["cost"],
+            estimates["functions"]["record"]["cost"]
+        );
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "launch".to_string(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.scores".to_string(),
+                clar_name,
+                db_name.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "execute".to_string(),
+                "--costs".to_string(),
+                db_name,
+                "S1G2081040G2081040G2081040G208105NK8PE5.scores".to_string(),
+                "record".to_string(),
+                "SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR".to_string(),
+                "(list u20 u20 u20 u20 u20 u20 u20 u20 u20 u20)".to_string(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+        let result = invoked.1.unwrap();
+        assert_eq!(result["message"], "Transaction executed and committed.");
+        let actual: ExecutionCost = serde_json::from_value(result["costs"].clone()).unwrap();
+
+        let mut bound = estimate;
+        bound.add(&load_contract).unwrap();
+        assert!(actual.runtime > 0);
+        assert!(
+            !actual.exceeds(&bound),
+            "{:?} exceeds estimate {:?}",
+            actual,
+            bound
+        );
+    }
+
+    #[test]
+    fn test_fuzz() {
+        let clar_name = format!("/tmp/test-fuzz_{}.clar", rand::thread_rng().gen::<i32>());
+        fs::write(
+            &clar_name,
+            "(define-data-var total uint u0)
+(define-public (add (amount uint))
+  (begin (var-set total (+ (var-get total) amount)) (ok true)))
+(define-read-only (invariant-total-under-100) (< (var-get total) u100))",
+        )
+        .unwrap();
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "fuzz".to_string(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.totals".to_string(),
+                clar_name,
+                "--runs".to_string(),
+                "10".to_string(),
+            ],
+        );
+        assert_eq!(invoked.0, 1);
+        let result = invoked.1.unwrap();
+        assert_eq!(result["message"], "Invariant violated.");
+        assert_eq!(result["invariant"], "invariant-total-under-100");
+        assert_eq!(result["calls"].as_array().unwrap().len(), 1);
+        assert_eq!(result["calls"][0]["function"], "add");
+    }
+
+    #[test]
+    fn test_runtime_error_locations() {
+        let db_name = format!("/tmp/db_{}", rand::thread_rng().gen::<i32>());
+        let clar_name = format!(
+            "/tmp/test-locations_{}.clar",
+            rand::thread_rng().gen::<i32>()
+        );
+        fs::write(
+            &clar_name,
+            "(define-public (divide (x int))
+  (begin (print x) (ok (/ 10 x))))",
+        )
+        .unwrap();
+
+        invoke_command("test", &["initialize".to_string(), db_name.clone()]);
+        let invoked = invoke_command(
+            "test",
+            &[
+                "launch".to_string(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.divider".to_string(),
+                clar_name,
+                db_name.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+
+        let execute = |argument: &str| {
+            invoke_command(
+                "test",
+                &[
+                    "execute".to_string(),
+                    db_name.clone(),
+                    "S1G2081040G2081040G2081040G208105NK8PE5.divider".to_string(),
+                    "divide".to_string(),
+                    "SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR".to_string(),
+                    argument.to_string(),
+                ],
+            )
+        };
+
+        let invoked = execute("2");
+        assert_eq!(invoked.0, 0);
+        assert_eq!(
+            invoked.1.unwrap()["events"][0]["contract_event"]["span"],
+            json!({"start_line": 2, "start_column": 17, "end_line": 2, "end_column": 17})
+        );
+
+        let invoked = execute("0");
+        assert_eq!(invoked.0, 1);
+        assert_eq!(
+            invoked.1.unwrap()["error"]["location"],
+            json!({
+                "contract_identifier": "S1G2081040G2081040G2081040G208105NK8PE5.divider",
+                "span": {"start_line": 2, "start_column": 24, "end_line": 2, "end_column": 31},
+            })
+        );
+    }
+
+    #[test]
+    fn test_execute_json_arguments() {
+        let db_name = format!("/tmp/db_{}", rand::thread_rng().gen::<i32>());
+        let clar_name = format!("/tmp/test-json_{}.clar", rand::thread_rng().gen::<i32>());
+        fs::write(
+            &clar_name,
+            "(define-public (describe (amount uint) (memo (optional (buff 4))) (tags (list 2 (string-utf8 5))))
+  (ok { amount: (+ amount u1), memo: memo, tags: tags }))",
+        )
+        .unwrap();
+
+        invoke_command("test", &["initialize".to_string(), db_name.clone()]);
+        let invoked = invoke_command(
+            "test",
+            &[
+                "launch".to_string(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.describer".to_string(),
+                clar_name,
+                db_name.clone(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+
+        let invoked = invoke_command(
+            "test",
+            &[
+                "execute".to_string(),
+                "--json".to_string(),
+                db_name.clone(),
+                "S1G2081040G2081040G2081040G208105NK8PE5.describer".to_string(),
+                "describe".to_string(),
+                "SZ2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKQ9H6DPR".to_string(),
+                r#""340282366920938463463374607431768211454""#.to_string(),
+                r#"{"some": "0xcafe"}"#.to_string(),
+                r#"["héllo", "🌍"]"#.to_string(),
+            ],
+        );
+        assert_eq!(invoked.0, 0);
+        assert_eq!(
+            invoked.1.unwrap()["output_json"],
+            json!({
+                "amount": "340282366920938463463374607431768211455",
+                "memo": { "some": "0xcafe" },
+                "tags": ["héllo", "🌍"],
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_reports_all_errors() {
+        let clar_name = format!("/tmp/test-check_{}.clar", rand::thread_rng().gen::<i32>());
+
+        fs::write(
+            &clar_name,
+            r#"
+(define-constant a (+ 1 2)
+(define-constant b (ok 1))
+(define-constant c 0xzz)
+(define-constant d (+ 1 u1))
+"#,
+        )
+        .unwrap();
+
+        let invoked = invoke_command("test", &["check".to_string(), clar_name.clone()]);
+        let exit = invoked.0;
+        let result = invoked.1.unwrap();
+
+        // the forms that parsed are still analyzed
+        assert_eq!(exit, 1);
+        assert_eq!(result["message"], "Parsing failed.");
+        assert_eq!(result["diagnostics"].as_array().unwrap().len(), 3);
+        assert_eq!(result["diagnostics"][0]["spans"][0]["start_line"], 2);
+        assert_eq!(result["diagnostics"][1]["spans"][0]["start_line"], 4);
+        assert_eq!(result["diagnostics"][2]["spans"][0]["start_line"], 5);
+        assert_eq!(result["error"]["parse"], result["diagnostics"][0]);
+
+        fs::write(
+            &clar_name,
+            r#"
+(define-read-only (f) (+ 1 u1))
+(define-read-only (g) (ok 1))
+(define-read-only (h) (- u1 true))
+"#,
+        )
+        .unwrap();
+
+        let invoked = invoke_command("test", &["check".to_string(), clar_name.clone()]);
+        let exit = invoked.0;
+        let result = invoked.1.unwrap();
+
+        assert_eq!(exit, 1);
+        assert_eq!(result["message"], "Checks failed.");
+        assert_eq!(result["diagnostics"].as_array().unwrap().len(), 2);
+        assert_eq!(result["diagnostics"][0]["spans"][0]["start_line"], 2);
+        assert_eq!(result["diagnostics"][1]["spans"][0]["start_line"], 4);
+        assert_eq!(result["error"]["analysis"], result["diagnostics"][0]);
+
+        // uses of failed definitions aren't reported as well
+        fs::write(
+            &clar_name,
+            r#"
+(define-read-only (f) (+ 1 u1))
+(define-read-only (g) (f))
+(define-data-var v int u1)
+(define-read-only (h) (+ (var-get v) 1))
+(define-constant c (- u1 true))
+(define-read-only (k) (ok (- u1 true)))
+(define-read-only (l) c)
+(define-read-only (m) (l))
+"#,
+        )
+        .unwrap();
+
+        let invoked = invoke_command("test", &["check".to_string(), clar_name]);
+        let exit = invoked.0;
+        let result = invoked.1.unwrap();
+
+        assert_eq!(exit, 1);
+        let mut lines: Vec<u64> = result["diagnostics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|d| d["spans"][0]["start_line"].as_u64().unwrap())
+            .collect();
+        lines.sort();
+        assert_eq!(lines, vec![2, 4, 6, 7]);
+    }
+
     #[test]
     fn test_init_mainnet() {
         let db_name = format!("/tmp/db_{}", rand::thread_rng().gen::<i32>());
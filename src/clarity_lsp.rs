@@ -0,0 +1,100 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#![allow(unused_imports)]
+#![allow(dead_code)]
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+
+extern crate blockstack_lib;
+
+use blockstack_lib::lsp::vm_state::VmState;
+use blockstack_lib::lsp::LanguageServer;
+use blockstack_lib::vm::types::{PrincipalData, StandardPrincipalData};
+use std::env;
+use std::io;
+use std::process;
+
+const USAGE: &str = "clarity-lsp [--testnet] [--deployer ADDRESS] [--sources-dir DIR] (vm-state.db)
+
+Serves the Language Server Protocol for Clarity over stdin/stdout.
+
+  --testnet          check contracts against the testnet boot contracts (ignored if a
+                     VM state DB is given).
+  --deployer         standard principal that deploys contracts whose file name is not a
+                     fully-qualified contract identifier.
+  --sources-dir      where to write the sources of deployed contracts, for go-to-definition
+                     across contract-call?.  Defaults to a directory under the system's
+                     temporary directory.
+  vm-state.db        a clarity-cli VM state, whose deployed contracts open documents may
+                     call.  If not given, only the boot contracts are available.
+";
+
+fn main() {
+    let mut argv: Vec<String> = env::args().skip(1).collect();
+
+    let mut mainnet = true;
+    let mut deployer = StandardPrincipalData::transient();
+    let mut sources_dir = env::temp_dir().join("clarity-lsp");
+    let mut vm_state_path = None;
+
+    while argv.len() > 0 {
+        let arg = argv.remove(0);
+        match arg.as_str() {
+            "--testnet" => {
+                mainnet = false;
+            }
+            "--deployer" if argv.len() > 0 => {
+                let address = argv.remove(0);
+                deployer = PrincipalData::parse_standard_principal(&address).unwrap_or_else(|e| {
+                    eprintln!("Invalid deployer address '{}': {:?}", &address, &e);
+                    process::exit(1);
+                });
+            }
+            "--sources-dir" if argv.len() > 0 => {
+                sources_dir = argv.remove(0).into();
+            }
+            // passed by most LSP clients; stdio is the only transport we support
+            "--stdio" => {}
+            "-h" | "--help" => {
+                eprintln!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if !arg.starts_with("-") && vm_state_path.is_none() => {
+                vm_state_path = Some(arg);
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                process::exit(1);
+            }
+        }
+    }
+
+    let vm_state = match vm_state_path {
+        Some(path) => VmState::open(&path).unwrap_or_else(|e| {
+            eprintln!("Failed to open VM state {}: {}", &path, &e);
+            process::exit(1);
+        }),
+        None => VmState::new_memory(mainnet),
+    };
+
+    let mut server = LanguageServer::new(vm_state, deployer, sources_dir);
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let exit_code = server.run(&mut stdin.lock(), &mut stdout.lock());
+    process::exit(exit_code);
+}
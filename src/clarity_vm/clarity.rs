@@ -22,7 +22,8 @@ use chainstate::stacks::boot::{
 };
 use chainstate::stacks::events::StacksTransactionEvent;
 use chainstate::stacks::index::marf::MARF;
-use chainstate::stacks::index::MarfTrieId;
+use chainstate::stacks::index::prune::PruneStats;
+use chainstate::stacks::index::{Error as MarfError, MarfTrieId};
 use chainstate::stacks::Error as ChainstateError;
 use vm::analysis;
 use vm::analysis::AnalysisDatabase;
@@ -251,6 +252,18 @@ impl ClarityInstance {
         f(self.datastore.get_marf())
     }
 
+    /// Prune the Clarity state of blocks more than `depth` blocks below `tip`, at most
+    /// `max_tries` of them per call.  Returns the trie pruning stats and the number of side-store
+    /// values deleted.
+    pub fn prune_state(
+        &mut self,
+        tip: &StacksBlockId,
+        depth: u32,
+        max_tries: usize,
+    ) -> Result<(PruneStats, u64), MarfError> {
+        self.datastore.prune(tip, depth, max_tries)
+    }
+
     pub fn is_mainnet(&self) -> bool {
         self.mainnet
     }
@@ -3,6 +3,7 @@ use std::path::PathBuf;
 use rusqlite::Connection;
 
 use chainstate::stacks::index::marf::{MarfConnection, MarfTransaction, MARF};
+use chainstate::stacks::index::prune::{prune_side_store, PruneStats};
 use chainstate::stacks::index::{Error, MarfTrieId};
 use core::{FIRST_BURNCHAIN_CONSENSUS_HASH, FIRST_STACKS_BLOCK_HASH};
 use util::db::IndexDBConn;
@@ -10,7 +11,10 @@ use vm::analysis::AnalysisDatabase;
 use vm::database::{
     BurnStateDB, ClarityBackingStore, ClarityDatabase, HeadersDB, SqliteConnection,
 };
-use vm::errors::{IncomparableError, InterpreterError, InterpreterResult, RuntimeErrorType};
+use vm::errors::{
+    Error as ClarityVmError, IncomparableError, InterpreterError, InterpreterResult,
+    RuntimeErrorType,
+};
 use vm::types::QualifiedContractIdentifier;
 
 use crate::types::chainstate::{BlockHeaderHash, StacksBlockHeader};
@@ -143,9 +147,11 @@ impl MarfedKV {
                     "Failed to open read only connection at {}: {:?}",
                     at_block, &e
                 );
-                InterpreterError::MarfFailure(IncomparableError {
-                    err: Error::NotFoundError,
-                })
+                let err = match e {
+                    Error::PrunedBlockError(_) => e,
+                    _ => Error::NotFoundError,
+                };
+                InterpreterError::MarfFailure(IncomparableError { err })
             })?;
             at_block.clone()
         } else {
@@ -232,6 +238,28 @@ impl MarfedKV {
         &mut self.marf
     }
 
+    /// Prune the trie state of blocks more than `depth` blocks below `tip` (at most `max_tries`
+    /// of them), and then delete the side-store values that no intact trie refers to.  Contract metadata is kept.
+    /// Returns the trie pruning stats and the number of side-store values deleted.
+    pub fn prune(
+        &mut self,
+        tip: &StacksBlockId,
+        depth: u32,
+        max_tries: usize,
+    ) -> Result<(PruneStats, u64), Error> {
+        let (stats, live_values) = self.marf.prune(tip, depth, max_tries)?;
+
+        let tx = self.marf.storage_tx()?;
+        let deleted = prune_side_store(&tx, "data_table", "key", &live_values)?;
+        tx.commit()?;
+
+        debug!(
+            "Pruned Clarity state to {}: {} tries compacted, {} side-store values deleted",
+            tip, stats.pruned_tries, deleted
+        );
+        Ok((stats, deleted))
+    }
+
     #[cfg(test)]
     pub fn sql_conn(&self) -> &Connection {
         self.marf.sqlite_conn()
@@ -277,21 +305,27 @@ impl<'a> ClarityBackingStore for ReadOnlyMarfStore<'a> {
     fn set_block_hash(&mut self, bhh: StacksBlockId) -> InterpreterResult<StacksBlockId> {
         self.marf
             .check_ancestor_block_hash(&bhh)
-            .map_err(|e| match e {
-                Error::NotFoundError => {
-                    test_debug!("No such block {:?} (NotFoundError)", &bhh);
-                    RuntimeErrorType::UnknownBlockHeaderHash(BlockHeaderHash(bhh.0))
-                }
-                Error::NonMatchingForks(_bh1, _bh2) => {
-                    test_debug!(
-                        "No such block {:?} (NonMatchingForks({}, {}))",
-                        &bhh,
-                        BlockHeaderHash(_bh1),
-                        BlockHeaderHash(_bh2)
-                    );
-                    RuntimeErrorType::UnknownBlockHeaderHash(BlockHeaderHash(bhh.0))
+            .map_err(|e| -> ClarityVmError {
+                match e {
+                    Error::NotFoundError => {
+                        test_debug!("No such block {:?} (NotFoundError)", &bhh);
+                        RuntimeErrorType::UnknownBlockHeaderHash(BlockHeaderHash(bhh.0)).into()
+                    }
+                    Error::NonMatchingForks(_bh1, _bh2) => {
+                        test_debug!(
+                            "No such block {:?} (NonMatchingForks({}, {}))",
+                            &bhh,
+                            BlockHeaderHash(_bh1),
+                            BlockHeaderHash(_bh2)
+                        );
+                        RuntimeErrorType::UnknownBlockHeaderHash(BlockHeaderHash(bhh.0)).into()
+                    }
+                    Error::PrunedBlockError(_) => {
+                        warn!("Cannot open state at {}: it has been pruned", &bhh);
+                        InterpreterError::MarfFailure(IncomparableError { err: e }).into()
+                    }
+                    _ => panic!("ERROR: Unexpected MARF failure: {}", e),
                 }
-                _ => panic!("ERROR: Unexpected MARF failure: {}", e),
             })?;
 
         let result = Ok(self.chain_tip);
@@ -498,21 +532,27 @@ impl<'a> ClarityBackingStore for WritableMarfStore<'a> {
     fn set_block_hash(&mut self, bhh: StacksBlockId) -> InterpreterResult<StacksBlockId> {
         self.marf
             .check_ancestor_block_hash(&bhh)
-            .map_err(|e| match e {
-                Error::NotFoundError => {
-                    test_debug!("No such block {:?} (NotFoundError)", &bhh);
-                    RuntimeErrorType::UnknownBlockHeaderHash(BlockHeaderHash(bhh.0))
-                }
-                Error::NonMatchingForks(_bh1, _bh2) => {
-                    test_debug!(
-                        "No such block {:?} (NonMatchingForks({}, {}))",
-                        &bhh,
-                        BlockHeaderHash(_bh1),
-                        BlockHeaderHash(_bh2)
-                    );
-                    RuntimeErrorType::UnknownBlockHeaderHash(BlockHeaderHash(bhh.0))
+            .map_err(|e| -> ClarityVmError {
+                match e {
+                    Error::NotFoundError => {
+                        test_debug!("No such block {:?} (NotFoundError)", &bhh);
+                        RuntimeErrorType::UnknownBlockHeaderHash(BlockHeaderHash(bhh.0)).into()
+                    }
+                    Error::NonMatchingForks(_bh1, _bh2) => {
+                        test_debug!(
+                            "No such block {:?} (NonMatchingForks({}, {}))",
+                            &bhh,
+                            BlockHeaderHash(_bh1),
+                            BlockHeaderHash(_bh2)
+                        );
+                        RuntimeErrorType::UnknownBlockHeaderHash(BlockHeaderHash(bhh.0)).into()
+                    }
+                    Error::PrunedBlockError(_) => {
+                        warn!("Cannot open state at {}: it has been pruned", &bhh);
+                        InterpreterError::MarfFailure(IncomparableError { err: e }).into()
+                    }
+                    _ => panic!("ERROR: Unexpected MARF failure: {}", e),
                 }
-                _ => panic!("ERROR: Unexpected MARF failure: {}", e),
             })?;
 
         let result = Ok(self.chain_tip);
@@ -0,0 +1,626 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Property-based fuzzing of a contract's public functions.
+//!
+//! A `ContractFuzzer` deploys a contract into a private `ClarityInstance`, then runs
+//!   random sequences of public function calls with well-typed random arguments and
+//!   random senders. After deployment and after every call, each invariant -- a
+//!   read-only function with no arguments whose name starts with the configured prefix --
+//!   must return `true`. When one doesn't, the failing sequence is shrunk to a minimal
+//!   reproduction by dropping calls and simplifying senders and arguments.
+//!
+//! Every sequence is replayed on top of the same deployed block, and the block is rolled
+//!   back afterwards, so sequences never see each other's writes.
+
+use std::fmt;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+
+use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
+use clarity_vm::clarity::{ClarityBlockConnection, ClarityInstance, Error};
+use clarity_vm::database::marf::MarfedKV;
+use vm::analysis::ContractAnalysis;
+use vm::costs::ExecutionCost;
+use vm::database::{STXBalance, NULL_BURN_STATE_DB, NULL_HEADER_DB};
+use vm::types::signatures::{SequenceSubtype, StringSubtype};
+use vm::types::{
+    ASCIIData, BuffData, CharType, FunctionType, ListData, OptionalData, PrincipalData,
+    QualifiedContractIdentifier, ResponseData, SequenceData, StandardPrincipalData, TupleData,
+    TypeSignature, UTF8Data, Value,
+};
+use vm::ClarityName;
+
+use crate::types::chainstate::StacksBlockId;
+use crate::types::proof::ClarityMarfTrieId;
+
+#[cfg(test)]
+mod tests;
+
+pub struct FuzzConfig {
+    /// seeds the random number generator, so a fuzzing session can be reproduced
+    pub seed: u64,
+    /// number of call sequences to try
+    pub runs: u32,
+    /// number of calls in each sequence
+    pub calls_per_run: u32,
+    /// number of distinct senders calls are made from
+    pub senders: u8,
+    /// uSTX credited to each sender before the contract is deployed
+    pub initial_balance: u128,
+    /// upper bound on the length of generated lists, buffers and strings
+    pub max_sequence_len: u32,
+    /// read-only functions with no arguments and this name prefix are invariants
+    pub invariant_prefix: String,
+    /// upper bound on the number of sequences replayed while shrinking a failure
+    pub max_shrink_replays: u32,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> FuzzConfig {
+        FuzzConfig {
+            seed: 0,
+            runs: 100,
+            calls_per_run: 20,
+            senders: 4,
+            initial_balance: 1_000_000_000,
+            max_sequence_len: 16,
+            invariant_prefix: "invariant-".to_string(),
+            max_shrink_replays: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzCall {
+    pub sender: PrincipalData,
+    pub function: ClarityName,
+    pub args: Vec<Value>,
+}
+
+/// A minimal sequence of calls after which `invariant` no longer returns `true`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzFailure {
+    pub calls: Vec<FuzzCall>,
+    pub invariant: ClarityName,
+    /// what the invariant returned instead, or the error it failed with
+    pub outcome: String,
+}
+
+impl fmt::Display for FuzzCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}", self.function)?;
+        for arg in self.args.iter() {
+            write!(f, " {}", arg)?;
+        }
+        write!(f, ") from {}", self.sender)
+    }
+}
+
+impl fmt::Display for FuzzFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invariant {} {} after {} call(s)",
+            self.invariant,
+            self.outcome,
+            self.calls.len()
+        )?;
+        for call in self.calls.iter() {
+            write!(f, "\n  {}", call)?;
+        }
+        Ok(())
+    }
+}
+
+/// Generates random values of a given type, and simpler values of the same type.
+pub struct ValueGenerator {
+    pub principals: Vec<PrincipalData>,
+    pub max_sequence_len: u32,
+}
+
+impl ValueGenerator {
+    /// Returns None for types no value can be generated for (trait references).
+    pub fn generate<R: Rng>(&self, rng: &mut R, ty: &TypeSignature) -> Option<Value> {
+        let value = match ty {
+            TypeSignature::NoType | TypeSignature::TraitReferenceType(_) => return None,
+            TypeSignature::IntType => Value::Int(match rng.gen_range(0, 3) {
+                0 => *[0, 1, -1, i128::max_value(), i128::min_value()]
+                    .choose(rng)
+                    .unwrap(),
+                1 => rng.gen_range(-100, 100),
+                _ => rng.gen(),
+            }),
+            TypeSignature::UIntType => Value::UInt(match rng.gen_range(0, 3) {
+                0 => *[0, 1, u128::max_value()].choose(rng).unwrap(),
+                1 => rng.gen_range(0, 100),
+                _ => rng.gen(),
+            }),
+            TypeSignature::BoolType => Value::Bool(rng.gen()),
+            TypeSignature::PrincipalType => {
+                Value::Principal(self.principals.choose(rng).unwrap().clone())
+            }
+            TypeSignature::SequenceType(SequenceSubtype::BufferType(len)) => {
+                let len = self.sequence_len(rng, u32::from(len));
+                let bytes = (0..len).map(|_| rng.gen()).collect();
+                Value::buff_from(bytes).ok()?
+            }
+            TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::ASCII(len))) => {
+                let len = self.sequence_len(rng, u32::from(len));
+                let bytes = (0..len).map(|_| rng.gen_range(0x20, 0x7f)).collect();
+                Value::string_ascii_from_bytes(bytes).ok()?
+            }
+            TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::UTF8(len))) => {
+                let len = self.sequence_len(rng, u32::from(len));
+                let string: String = (0..len).map(|_| rng.gen::<char>()).collect();
+                Value::string_utf8_from_bytes(string.into_bytes()).ok()?
+            }
+            TypeSignature::SequenceType(SequenceSubtype::ListType(list_type)) => {
+                let len = self.sequence_len(rng, list_type.get_max_len());
+                let mut items = vec![];
+                for _ in 0..len {
+                    items.push(self.generate(rng, list_type.get_list_item_type())?);
+                }
+                Value::list_with_type(items, list_type.clone()).ok()?
+            }
+            TypeSignature::TupleType(tuple_type) => {
+                let mut fields = vec![];
+                for (name, field_type) in tuple_type.get_type_map().iter() {
+                    fields.push((name.clone(), self.generate(rng, field_type)?));
+                }
+                Value::Tuple(TupleData::from_data_typed(fields, tuple_type).ok()?)
+            }
+            TypeSignature::OptionalType(inner_type) => {
+                if rng.gen_range(0, 4) == 0 {
+                    Value::none()
+                } else {
+                    Value::some(self.generate(rng, inner_type)?).ok()?
+                }
+            }
+            TypeSignature::ResponseType(response_types) => {
+                let (ok_type, err_type) = response_types.as_ref();
+                if rng.gen() {
+                    Value::okay(self.generate(rng, ok_type)?).ok()?
+                } else {
+                    Value::error(self.generate(rng, err_type)?).ok()?
+                }
+            }
+        };
+        Some(value)
+    }
+
+    /// Biased towards the empty sequence and the declared maximum length.
+    fn sequence_len<R: Rng>(&self, rng: &mut R, max_len: u32) -> u32 {
+        let max_len = max_len.min(self.max_sequence_len);
+        match rng.gen_range(0, 4) {
+            0 => 0,
+            1 => max_len,
+            _ => rng.gen_range(0, max_len + 1),
+        }
+    }
+
+    /// Values of the same type as `value` that are strictly simpler, simplest first.
+    pub fn shrink(&self, value: &Value) -> Vec<Value> {
+        let mut candidates = vec![];
+        match value {
+            Value::Int(x) => {
+                if *x != 0 {
+                    candidates.push(Value::Int(0));
+                    if x / 2 != 0 {
+                        candidates.push(Value::Int(x / 2));
+                    }
+                }
+            }
+            Value::UInt(x) => {
+                if *x != 0 {
+                    candidates.push(Value::UInt(0));
+                    if x / 2 != 0 {
+                        candidates.push(Value::UInt(x / 2));
+                    }
+                }
+            }
+            Value::Bool(true) => candidates.push(Value::Bool(false)),
+            Value::Bool(false) => {}
+            Value::Principal(principal) => {
+                if principal != &self.principals[0] {
+                    candidates.push(Value::Principal(self.principals[0].clone()));
+                }
+            }
+            Value::Sequence(SequenceData::Buffer(buff)) => {
+                for data in shrink_items(&buff.data) {
+                    candidates.push(Value::Sequence(SequenceData::Buffer(BuffData { data })));
+                }
+            }
+            Value::Sequence(SequenceData::String(CharType::ASCII(string))) => {
+                for data in shrink_items(&string.data) {
+                    candidates.push(Value::Sequence(SequenceData::String(CharType::ASCII(
+                        ASCIIData { data },
+                    ))));
+                }
+            }
+            Value::Sequence(SequenceData::String(CharType::UTF8(string))) => {
+                for data in shrink_items(&string.data) {
+                    candidates.push(Value::Sequence(SequenceData::String(CharType::UTF8(
+                        UTF8Data { data },
+                    ))));
+                }
+            }
+            Value::Sequence(SequenceData::List(list)) => {
+                let mut shrunk_lists = shrink_items(&list.data);
+                for (i, item) in list.data.iter().enumerate() {
+                    for shrunk_item in self.shrink(item) {
+                        let mut data = list.data.clone();
+                        data[i] = shrunk_item;
+                        shrunk_lists.push(data);
+                    }
+                }
+                for data in shrunk_lists {
+                    candidates.push(Value::Sequence(SequenceData::List(ListData {
+                        data,
+                        type_signature: list.type_signature.clone(),
+                    })));
+                }
+            }
+            Value::Tuple(tuple) => {
+                for (name, field) in tuple.data_map.iter() {
+                    for shrunk_field in self.shrink(field) {
+                        let mut shrunk_tuple = tuple.clone();
+                        shrunk_tuple.data_map.insert(name.clone(), shrunk_field);
+                        candidates.push(Value::Tuple(shrunk_tuple));
+                    }
+                }
+            }
+            Value::Optional(OptionalData { data: Some(inner) }) => {
+                candidates.push(Value::none());
+                for shrunk_inner in self.shrink(inner) {
+                    candidates.push(Value::Optional(OptionalData {
+                        data: Some(Box::new(shrunk_inner)),
+                    }));
+                }
+            }
+            Value::Optional(OptionalData { data: None }) => {}
+            Value::Response(response) => {
+                for shrunk_inner in self.shrink(&response.data) {
+                    candidates.push(Value::Response(ResponseData {
+                        committed: response.committed,
+                        data: Box::new(shrunk_inner),
+                    }));
+                }
+            }
+        }
+        candidates
+    }
+}
+
+/// The empty sequence, the first half, and the sequence without its last item.
+fn shrink_items<T: Clone>(items: &[T]) -> Vec<Vec<T>> {
+    let mut shrunk = vec![];
+    if items.len() > 0 {
+        shrunk.push(vec![]);
+    }
+    if items.len() > 2 {
+        shrunk.push(items[..items.len() / 2].to_vec());
+    }
+    if items.len() > 1 {
+        shrunk.push(items[..items.len() - 1].to_vec());
+    }
+    shrunk
+}
+
+pub struct ContractFuzzer {
+    instance: ClarityInstance,
+    contract_identifier: QualifiedContractIdentifier,
+    /// public functions whose arguments can all be generated
+    functions: Vec<(ClarityName, Vec<TypeSignature>)>,
+    invariants: Vec<ClarityName>,
+    generator: ValueGenerator,
+    rng: ChaCha20Rng,
+    config: FuzzConfig,
+}
+
+/// The block every sequence is replayed on top of, holding the deployed contract.
+fn deployed_block() -> StacksBlockId {
+    StacksBlockId([0u8; 32])
+}
+
+fn replay_block() -> StacksBlockId {
+    StacksBlockId([1u8; 32])
+}
+
+impl ContractFuzzer {
+    /// Deploys `contract_source` as `contract_identifier`, failing if the contract doesn't
+    ///   analyze or initialize.
+    pub fn new(
+        contract_identifier: &QualifiedContractIdentifier,
+        contract_source: &str,
+        config: FuzzConfig,
+    ) -> Result<ContractFuzzer, Error> {
+        let mut instance =
+            ClarityInstance::new(false, MarfedKV::temporary(), ExecutionCost::max_value());
+        let senders: Vec<PrincipalData> = (1..=config.senders.max(1))
+            .map(|i| StandardPrincipalData(C32_ADDRESS_VERSION_TESTNET_SINGLESIG, [i; 20]).into())
+            .collect();
+
+        let analysis = {
+            let mut conn = instance.begin_test_genesis_block(
+                &StacksBlockId::sentinel(),
+                &deployed_block(),
+                &NULL_HEADER_DB,
+                &NULL_BURN_STATE_DB,
+            );
+            let result = ContractFuzzer::deploy(
+                &mut conn,
+                contract_identifier,
+                contract_source,
+                &senders,
+                config.initial_balance,
+            );
+            match result {
+                Ok(analysis) => {
+                    conn.commit_to_block(&deployed_block());
+                    analysis
+                }
+                Err(e) => {
+                    conn.rollback_block();
+                    return Err(e);
+                }
+            }
+        };
+
+        let mut principals = senders;
+        principals.push(contract_identifier.clone().into());
+        let generator = ValueGenerator {
+            principals,
+            max_sequence_len: config.max_sequence_len,
+        };
+
+        let functions = analysis
+            .public_function_types
+            .iter()
+            .filter_map(|(name, function_type)| match function_type {
+                FunctionType::Fixed(function) => {
+                    let arg_types: Vec<_> = function
+                        .args
+                        .iter()
+                        .map(|arg| arg.signature.clone())
+                        .collect();
+                    let generatable = arg_types.iter().all(|arg_type| match arg_type {
+                        TypeSignature::TraitReferenceType(_) => false,
+                        _ => true,
+                    });
+                    if generatable {
+                        Some((name.clone(), arg_types))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            })
+            .collect();
+
+        let invariants = analysis
+            .read_only_function_types
+            .iter()
+            .filter_map(|(name, function_type)| match function_type {
+                FunctionType::Fixed(function)
+                    if function.args.is_empty()
+                        && name.as_str().starts_with(&config.invariant_prefix) =>
+                {
+                    Some(name.clone())
+                }
+                _ => None,
+            })
+            .collect();
+
+        Ok(ContractFuzzer {
+            instance,
+            contract_identifier: contract_identifier.clone(),
+            functions,
+            invariants,
+            generator,
+            rng: ChaCha20Rng::seed_from_u64(config.seed),
+            config,
+        })
+    }
+
+    fn deploy(
+        conn: &mut ClarityBlockConnection,
+        contract_identifier: &QualifiedContractIdentifier,
+        contract_source: &str,
+        senders: &[PrincipalData],
+        initial_balance: u128,
+    ) -> Result<ContractAnalysis, Error> {
+        conn.as_transaction(|tx| {
+            tx.with_clarity_db(|db| {
+                for sender in senders.iter() {
+                    let mut snapshot = db.get_stx_balance_snapshot_genesis(sender);
+                    snapshot.set_balance(STXBalance::initial(initial_balance));
+                    snapshot.save();
+                }
+                Ok(())
+            })
+        })?;
+
+        conn.as_transaction(|tx| -> Result<ContractAnalysis, Error> {
+            let (ast, analysis) =
+                tx.analyze_smart_contract(contract_identifier, contract_source)?;
+            tx.initialize_smart_contract(contract_identifier, &ast, contract_source, |_, _| false)?;
+            tx.save_analysis(contract_identifier, &analysis)?;
+            Ok(analysis)
+        })
+    }
+
+    pub fn invariants(&self) -> &[ClarityName] {
+        &self.invariants
+    }
+
+    /// Runs `config.runs` random call sequences, returning the first invariant violation
+    ///   found, shrunk to a minimal reproduction.
+    pub fn run(&mut self) -> Option<FuzzFailure> {
+        if self.invariants.is_empty() {
+            return None;
+        }
+        for _ in 0..self.config.runs {
+            let calls = self.random_calls();
+            if let Some(failure) = self.replay(&calls) {
+                return Some(self.shrink(failure));
+            }
+        }
+        None
+    }
+
+    fn random_calls(&mut self) -> Vec<FuzzCall> {
+        let ContractFuzzer {
+            ref functions,
+            ref generator,
+            ref mut rng,
+            ref config,
+            ..
+        } = *self;
+        if functions.is_empty() {
+            return vec![];
+        }
+        // the contract principal is only a valid argument, not a valid sender
+        let senders = &generator.principals[..generator.principals.len() - 1];
+        let mut calls = vec![];
+        while calls.len() < config.calls_per_run as usize {
+            let (function, arg_types) = functions.choose(rng).unwrap();
+            let sender = senders.choose(rng).unwrap().clone();
+            let args: Option<Vec<_>> = arg_types
+                .iter()
+                .map(|arg_type| generator.generate(rng, arg_type))
+                .collect();
+            if let Some(args) = args {
+                calls.push(FuzzCall {
+                    sender,
+                    function: function.clone(),
+                    args,
+                });
+            }
+        }
+        calls
+    }
+
+    /// Replays `calls` from the deployed state, checking every invariant before the first
+    ///   call and after each one. The returned failure's calls end with the call that
+    ///   broke the invariant.
+    pub fn replay(&mut self, calls: &[FuzzCall]) -> Option<FuzzFailure> {
+        let contract_identifier = &self.contract_identifier;
+        let invariants = &self.invariants;
+        let checker = &self.generator.principals[0];
+
+        let mut conn = self.instance.begin_block(
+            &deployed_block(),
+            &replay_block(),
+            &NULL_HEADER_DB,
+            &NULL_BURN_STATE_DB,
+        );
+
+        let mut failure = check_invariants(&mut conn, contract_identifier, invariants, checker);
+        let mut executed = 0;
+        while failure.is_none() && executed < calls.len() {
+            let call = &calls[executed];
+            // runtime errors and aborted calls are fine: only the invariants matter
+            let _ = conn.as_transaction(|tx| {
+                tx.run_contract_call(
+                    &call.sender,
+                    contract_identifier,
+                    &call.function,
+                    &call.args,
+                    |_, _| false,
+                )
+            });
+            executed += 1;
+            failure = check_invariants(&mut conn, contract_identifier, invariants, checker);
+        }
+        conn.rollback_block();
+
+        failure.map(|(invariant, outcome)| FuzzFailure {
+            calls: calls[..executed].to_vec(),
+            invariant,
+            outcome,
+        })
+    }
+
+    /// Greedily applies the first simplification that still fails -- dropping a call,
+    ///   then simplifying a sender, then simplifying an argument -- until none does.
+    fn shrink(&mut self, mut failure: FuzzFailure) -> FuzzFailure {
+        let mut replays = 0;
+        'shrinking: loop {
+            for candidate in self.shrink_candidates(&failure.calls) {
+                if replays >= self.config.max_shrink_replays {
+                    break 'shrinking;
+                }
+                replays += 1;
+                if let Some(smaller) = self.replay(&candidate) {
+                    failure = smaller;
+                    continue 'shrinking;
+                }
+            }
+            break;
+        }
+        failure
+    }
+
+    fn shrink_candidates(&self, calls: &[FuzzCall]) -> Vec<Vec<FuzzCall>> {
+        let mut candidates = vec![];
+        for i in 0..calls.len() {
+            let mut candidate = calls.to_vec();
+            candidate.remove(i);
+            candidates.push(candidate);
+        }
+        let simplest_sender = &self.generator.principals[0];
+        for (i, call) in calls.iter().enumerate() {
+            if &call.sender != simplest_sender {
+                let mut candidate = calls.to_vec();
+                candidate[i].sender = simplest_sender.clone();
+                candidates.push(candidate);
+            }
+        }
+        for (i, call) in calls.iter().enumerate() {
+            for (j, arg) in call.args.iter().enumerate() {
+                for shrunk_arg in self.generator.shrink(arg) {
+                    let mut candidate = calls.to_vec();
+                    candidate[i].args[j] = shrunk_arg;
+                    candidates.push(candidate);
+                }
+            }
+        }
+        candidates
+    }
+}
+
+/// Returns the first invariant that doesn't return `true`, and what happened instead.
+fn check_invariants(
+    conn: &mut ClarityBlockConnection,
+    contract_identifier: &QualifiedContractIdentifier,
+    invariants: &[ClarityName],
+    sender: &PrincipalData,
+) -> Option<(ClarityName, String)> {
+    for invariant in invariants.iter() {
+        let result = conn.as_transaction(|tx| {
+            tx.run_contract_call(sender, contract_identifier, invariant, &[], |_, _| false)
+        });
+        match result {
+            Ok((Value::Bool(true), ..)) => {}
+            Ok((value, ..)) => return Some((invariant.clone(), format!("returned {}", value))),
+            Err(e) => return Some((invariant.clone(), format!("failed: {}", e))),
+        }
+    }
+    None
+}
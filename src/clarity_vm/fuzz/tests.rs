@@ -0,0 +1,130 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+
+use chainstate::stacks::C32_ADDRESS_VERSION_TESTNET_SINGLESIG;
+use clarity_vm::fuzz::{ContractFuzzer, FuzzCall, FuzzConfig, ValueGenerator};
+use vm::types::{
+    PrincipalData, QualifiedContractIdentifier, StandardPrincipalData, TypeSignature, Value,
+};
+use vm::ClarityName;
+
+fn fuzzer(contract: &str) -> ContractFuzzer {
+    let config = FuzzConfig {
+        runs: 20,
+        calls_per_run: 10,
+        ..FuzzConfig::default()
+    };
+    ContractFuzzer::new(
+        &QualifiedContractIdentifier::local("counter").unwrap(),
+        contract,
+        config,
+    )
+    .unwrap()
+}
+
+fn sender(i: u8) -> PrincipalData {
+    StandardPrincipalData(C32_ADDRESS_VERSION_TESTNET_SINGLESIG, [i; 20]).into()
+}
+
+#[test]
+fn test_finds_and_shrinks_violation() {
+    let mut fuzzer = fuzzer(
+        "(define-data-var counter uint u0)
+         (define-public (increment (by uint))
+           (begin (var-set counter (+ (var-get counter) (if (> by u0) u1 u0))) (ok true)))
+         (define-public (noise (items (list 5 int)) (memo (optional (string-utf8 10))))
+           (ok items))
+         (define-public (divide (x int)) (ok (/ 10 x)))
+         (define-read-only (invariant-small-counter) (< (var-get counter) u3))
+         (define-read-only (not-an-invariant) false)",
+    );
+    assert_eq!(
+        fuzzer.invariants(),
+        &[ClarityName::from("invariant-small-counter")]
+    );
+
+    let failure = fuzzer.run().unwrap();
+    assert_eq!(
+        failure.invariant,
+        ClarityName::from("invariant-small-counter")
+    );
+    assert_eq!(failure.outcome, "returned false");
+    let minimal_call = FuzzCall {
+        sender: sender(1),
+        function: ClarityName::from("increment"),
+        args: vec![Value::UInt(1)],
+    };
+    assert_eq!(failure.calls, vec![minimal_call; 3]);
+
+    // the reproduction replays to the same failure
+    assert_eq!(fuzzer.replay(&failure.calls), Some(failure));
+}
+
+#[test]
+fn test_invariants_hold() {
+    let mut fuzzer = fuzzer(
+        "(define-map owners uint principal)
+         (define-public (claim (id uint))
+           (begin (asserts! (map-insert owners id tx-sender) (err u1)) (ok id)))
+         (define-read-only (invariant-first-is-unowned-or-claimed)
+           (or (is-none (map-get? owners u0)) (is-some (map-get? owners u0))))",
+    );
+    assert_eq!(fuzzer.run(), None);
+}
+
+#[test]
+fn test_violated_at_deployment() {
+    let mut fuzzer = fuzzer(
+        "(define-public (f) (ok true))
+         (define-read-only (invariant-fails) (err u1))",
+    );
+    let failure = fuzzer.run().unwrap();
+    assert_eq!(failure.calls, vec![]);
+    assert_eq!(failure.outcome, "returned (err u1)");
+}
+
+#[test]
+fn test_generated_values_are_well_typed() {
+    let generator = ValueGenerator {
+        principals: vec![sender(1), sender(2)],
+        max_sequence_len: 8,
+    };
+    let mut rng = ChaCha20Rng::seed_from_u64(0);
+    let types = [
+        "int",
+        "uint",
+        "bool",
+        "principal",
+        "(buff 20)",
+        "(string-ascii 3)",
+        "(string-utf8 40)",
+        "(list 100 (optional (tuple (a int) (b (response uint bool)))))",
+    ];
+    for type_str in types.iter() {
+        let ty = TypeSignature::from(*type_str);
+        for _ in 0..50 {
+            let value = generator.generate(&mut rng, &ty).unwrap();
+            assert!(ty.admits(&value), "{} does not admit {}", type_str, value);
+            for shrunk in generator.shrink(&value) {
+                assert!(ty.admits(&shrunk), "{} does not admit {}", type_str, shrunk);
+                assert_ne!(shrunk, value);
+            }
+        }
+    }
+}
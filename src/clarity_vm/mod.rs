@@ -4,4 +4,7 @@ pub mod clarity;
 /// Stacks blockchain specific Clarity database implementations and wrappers
 pub mod database;
 
+/// Property-based fuzzing of contract public functions
+pub mod fuzz;
+
 mod tests;
@@ -0,0 +1,278 @@
+//! Bindings for the Clarity contract `SP000000000000000000002Q6VF78.example`.
+//! Generated by `clarity-cli codegen`; do not edit.
+
+use blockstack_lib::chainstate::stacks::TransactionPayload;
+use blockstack_lib::codegen::runtime::{self, ReadOnlyClient};
+use blockstack_lib::vm::types::{PrincipalData, QualifiedContractIdentifier, Value};
+
+pub const CONTRACT_ID: &str = "SP000000000000000000002Q6VF78.example";
+
+pub fn contract_id() -> QualifiedContractIdentifier {
+    QualifiedContractIdentifier::parse(CONTRACT_ID).expect("invalid contract identifier")
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterSettings {
+    pub limit: u128,
+    pub public: bool,
+    pub tags: Vec<String>,
+}
+
+impl RegisterSettings {
+    pub fn to_value(&self) -> runtime::Result<Value> {
+        runtime::tuple_to_value(vec![
+            ("limit", Ok::<_, runtime::Error>(Value::UInt(*&self.limit))?),
+            ("public", Ok::<_, runtime::Error>(Value::Bool(*&self.public))?),
+            ("tags", runtime::list_to_value(&self.tags, |v0| runtime::string_ascii_to_value(v0))?),
+        ])
+    }
+
+    pub fn from_value(value: Value) -> runtime::Result<RegisterSettings> {
+        let mut tuple = runtime::tuple_from_value(value)?;
+        Ok(RegisterSettings {
+            limit: runtime::uint_from_value(runtime::take_field(&mut tuple, "limit")?)?,
+            public: runtime::bool_from_value(runtime::take_field(&mut tuple, "public")?)?,
+            tags: runtime::list_from_value(runtime::take_field(&mut tuple, "tags")?, |v0| runtime::string_ascii_from_value(v0))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterResultOk {
+    pub limit: u128,
+    pub name: String,
+    pub referrer: Option<PrincipalData>,
+}
+
+impl RegisterResultOk {
+    pub fn to_value(&self) -> runtime::Result<Value> {
+        runtime::tuple_to_value(vec![
+            ("limit", Ok::<_, runtime::Error>(Value::UInt(*&self.limit))?),
+            ("name", runtime::string_ascii_to_value(&self.name)?),
+            ("referrer", runtime::optional_to_value(&self.referrer, |v0| Ok::<_, runtime::Error>(Value::Principal((v0).clone())))?),
+        ])
+    }
+
+    pub fn from_value(value: Value) -> runtime::Result<RegisterResultOk> {
+        let mut tuple = runtime::tuple_from_value(value)?;
+        Ok(RegisterResultOk {
+            limit: runtime::uint_from_value(runtime::take_field(&mut tuple, "limit")?)?,
+            name: runtime::string_ascii_from_value(runtime::take_field(&mut tuple, "name")?)?,
+            referrer: runtime::optional_from_value(runtime::take_field(&mut tuple, "referrer")?, |v0| runtime::principal_from_value(v0))?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GetProfileResultOkStats {
+    pub badges: Vec<Vec<u8>>,
+    pub score: i128,
+}
+
+impl GetProfileResultOkStats {
+    pub fn to_value(&self) -> runtime::Result<Value> {
+        runtime::tuple_to_value(vec![
+            ("badges", runtime::list_to_value(&self.badges, |v0| runtime::buff_to_value(v0))?),
+            ("score", Ok::<_, runtime::Error>(Value::Int(*&self.score))?),
+        ])
+    }
+
+    pub fn from_value(value: Value) -> runtime::Result<GetProfileResultOkStats> {
+        let mut tuple = runtime::tuple_from_value(value)?;
+        Ok(GetProfileResultOkStats {
+            badges: runtime::list_from_value(runtime::take_field(&mut tuple, "badges")?, |v0| runtime::buff_from_value(v0))?,
+            score: runtime::int_from_value(runtime::take_field(&mut tuple, "score")?)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GetProfileResultOk {
+    pub display_name: String,
+    pub note: Option<String>,
+    pub owner: PrincipalData,
+    pub stats: GetProfileResultOkStats,
+}
+
+impl GetProfileResultOk {
+    pub fn to_value(&self) -> runtime::Result<Value> {
+        runtime::tuple_to_value(vec![
+            ("display-name", runtime::string_utf8_to_value(&self.display_name)?),
+            ("note", runtime::optional_to_value(&self.note, |v0| runtime::string_ascii_to_value(v0))?),
+            ("owner", Ok::<_, runtime::Error>(Value::Principal((&self.owner).clone()))?),
+            ("stats", (&self.stats).to_value()?),
+        ])
+    }
+
+    pub fn from_value(value: Value) -> runtime::Result<GetProfileResultOk> {
+        let mut tuple = runtime::tuple_from_value(value)?;
+        Ok(GetProfileResultOk {
+            display_name: runtime::string_utf8_from_value(runtime::take_field(&mut tuple, "display-name")?)?,
+            note: runtime::optional_from_value(runtime::take_field(&mut tuple, "note")?, |v0| runtime::string_ascii_from_value(v0))?,
+            owner: runtime::principal_from_value(runtime::take_field(&mut tuple, "owner")?)?,
+            stats: GetProfileResultOkStats::from_value(runtime::take_field(&mut tuple, "stats")?)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupResultOkItem {
+    pub active: bool,
+    pub id: u128,
+}
+
+impl LookupResultOkItem {
+    pub fn to_value(&self) -> runtime::Result<Value> {
+        runtime::tuple_to_value(vec![
+            ("active", Ok::<_, runtime::Error>(Value::Bool(*&self.active))?),
+            ("id", Ok::<_, runtime::Error>(Value::UInt(*&self.id))?),
+        ])
+    }
+
+    pub fn from_value(value: Value) -> runtime::Result<LookupResultOkItem> {
+        let mut tuple = runtime::tuple_from_value(value)?;
+        Ok(LookupResultOkItem {
+            active: runtime::bool_from_value(runtime::take_field(&mut tuple, "active")?)?,
+            id: runtime::uint_from_value(runtime::take_field(&mut tuple, "id")?)?,
+        })
+    }
+}
+
+/// Arguments to `register`
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegisterArgs {
+    pub name: String,
+    pub display_name: String,
+    pub owner: PrincipalData,
+    pub token: QualifiedContractIdentifier,
+    pub salt: Vec<u8>,
+    pub scores: Vec<i128>,
+    pub referrer: Option<PrincipalData>,
+    pub settings: RegisterSettings,
+}
+
+impl RegisterArgs {
+    pub fn to_values(&self) -> runtime::Result<Vec<Value>> {
+        Ok(vec![
+            runtime::string_ascii_to_value(&self.name)?,
+            runtime::string_utf8_to_value(&self.display_name)?,
+            Ok::<_, runtime::Error>(Value::Principal((&self.owner).clone()))?,
+            Ok::<_, runtime::Error>(Value::Principal(PrincipalData::Contract((&self.token).clone())))?,
+            runtime::buff_to_value(&self.salt)?,
+            runtime::list_to_value(&self.scores, |v0| Ok::<_, runtime::Error>(Value::Int(*v0)))?,
+            runtime::optional_to_value(&self.referrer, |v0| Ok::<_, runtime::Error>(Value::Principal((v0).clone())))?,
+            (&self.settings).to_value()?,
+        ])
+    }
+
+    pub fn from_values(values: Vec<Value>) -> runtime::Result<RegisterArgs> {
+        if values.len() != 8 {
+            return Err(runtime::Error::Conversion(format!("expected 8 arguments, got {}", values.len())));
+        }
+        let mut values = values.into_iter();
+        Ok(RegisterArgs {
+            name: runtime::string_ascii_from_value(values.next().unwrap())?,
+            display_name: runtime::string_utf8_from_value(values.next().unwrap())?,
+            owner: runtime::principal_from_value(values.next().unwrap())?,
+            token: runtime::contract_from_value(values.next().unwrap())?,
+            salt: runtime::buff_from_value(values.next().unwrap())?,
+            scores: runtime::list_from_value(values.next().unwrap(), |v0| runtime::int_from_value(v0))?,
+            referrer: runtime::optional_from_value(values.next().unwrap(), |v0| runtime::principal_from_value(v0))?,
+            settings: RegisterSettings::from_value(values.next().unwrap())?,
+        })
+    }
+}
+
+/// The result of `register`
+pub type RegisterOutput = Result<RegisterResultOk, u128>;
+
+pub fn register_output_from_value(value: Value) -> runtime::Result<RegisterOutput> {
+    runtime::response_from_value(value, |v0| RegisterResultOk::from_value(v0), |v0| runtime::uint_from_value(v0))
+}
+
+/// The payload of a transaction that calls `register`
+pub fn register(args: &RegisterArgs) -> runtime::Result<TransactionPayload> {
+    runtime::contract_call_payload(&contract_id(), "register", args.to_values()?)
+}
+
+/// Arguments to `get-profile`
+#[derive(Debug, Clone, PartialEq)]
+pub struct GetProfileArgs {
+    pub who: PrincipalData,
+}
+
+impl GetProfileArgs {
+    pub fn to_values(&self) -> runtime::Result<Vec<Value>> {
+        Ok(vec![
+            Ok::<_, runtime::Error>(Value::Principal((&self.who).clone()))?,
+        ])
+    }
+
+    pub fn from_values(values: Vec<Value>) -> runtime::Result<GetProfileArgs> {
+        if values.len() != 1 {
+            return Err(runtime::Error::Conversion(format!("expected 1 arguments, got {}", values.len())));
+        }
+        let mut values = values.into_iter();
+        Ok(GetProfileArgs {
+            who: runtime::principal_from_value(values.next().unwrap())?,
+        })
+    }
+}
+
+/// The result of `get-profile`
+pub type GetProfileOutput = Result<GetProfileResultOk, ()>;
+
+pub fn get_profile_output_from_value(value: Value) -> runtime::Result<GetProfileOutput> {
+    runtime::response_from_value(value, |v0| GetProfileResultOk::from_value(v0), |v0| runtime::unit_from_value(v0))
+}
+
+/// Call `get-profile` through a node's RPC interface
+pub fn get_profile(
+    client: &ReadOnlyClient,
+    sender: &PrincipalData,
+    args: &GetProfileArgs,
+) -> runtime::Result<GetProfileOutput> {
+    let value = client.call_read_only(&contract_id(), "get-profile", sender, args.to_values()?)?;
+    get_profile_output_from_value(value)
+}
+
+/// Arguments to `lookup`
+#[derive(Debug, Clone, PartialEq)]
+pub struct LookupArgs {
+    pub id: u128,
+}
+
+impl LookupArgs {
+    pub fn to_values(&self) -> runtime::Result<Vec<Value>> {
+        Ok(vec![
+            Ok::<_, runtime::Error>(Value::UInt(*&self.id))?,
+        ])
+    }
+
+    pub fn from_values(values: Vec<Value>) -> runtime::Result<LookupArgs> {
+        if values.len() != 1 {
+            return Err(runtime::Error::Conversion(format!("expected 1 arguments, got {}", values.len())));
+        }
+        let mut values = values.into_iter();
+        Ok(LookupArgs {
+            id: runtime::uint_from_value(values.next().unwrap())?,
+        })
+    }
+}
+
+/// The result of `lookup`
+pub type LookupOutput = Result<Vec<LookupResultOkItem>, String>;
+
+pub fn lookup_output_from_value(value: Value) -> runtime::Result<LookupOutput> {
+    runtime::response_from_value(value, |v0| runtime::list_from_value(v0, |v1| LookupResultOkItem::from_value(v1)), |v0| runtime::string_ascii_from_value(v0))
+}
+
+/// Call `lookup` through a node's RPC interface
+pub fn lookup(
+    client: &ReadOnlyClient,
+    sender: &PrincipalData,
+    args: &LookupArgs,
+) -> runtime::Result<LookupOutput> {
+    let value = client.call_read_only(&contract_id(), "lookup", sender, args.to_values()?)?;
+    lookup_output_from_value(value)
+}
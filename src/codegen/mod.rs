@@ -0,0 +1,518 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Generation of Rust bindings from a contract interface.
+//!
+//! For each public and read-only function, the bindings have a struct holding its arguments
+//! (with conversions to and from `Value`s), a type alias for its result along with a function
+//! to decode it, and either a transaction payload builder (public functions) or a helper that
+//! calls the function through a node's RPC interface (read-only functions).  Tuples become
+//! structs of their own.  The generated code depends on `blockstack_lib::codegen::runtime`.
+
+pub mod runtime;
+
+use std::collections::HashSet;
+
+use vm::analysis::contract_interface_builder::{
+    ContractInterface, ContractInterfaceAtomType, ContractInterfaceFunction,
+    ContractInterfaceFunctionAccess,
+};
+use vm::types::QualifiedContractIdentifier;
+
+const RUST_KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+    "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in",
+    "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref",
+    "return", "self", "static", "struct", "super", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+
+/// A Clarity type, as it is represented in the bindings
+enum RustType {
+    Int,
+    UInt,
+    Bool,
+    Principal,
+    Contract,
+    Buffer,
+    StringAscii,
+    StringUtf8,
+    Unit,
+    Optional(Box<RustType>),
+    Response(Box<RustType>, Box<RustType>),
+    List(Box<RustType>),
+    /// The name of a generated struct
+    Tuple(String),
+}
+
+/// A field of an argument or tuple struct
+struct Field {
+    clarity_name: String,
+    rust_name: String,
+    rust_type: RustType,
+}
+
+/// `get-balance?` => `get_balance`
+fn snake_case(clarity_name: &str) -> String {
+    let mut name = String::new();
+    for c in clarity_name.chars() {
+        if c.is_ascii_alphanumeric() {
+            name.push(c.to_ascii_lowercase());
+        } else if c == '-' || c == '_' {
+            name.push('_');
+        }
+    }
+    let name = name.trim_matches('_').to_string();
+    if name.is_empty() {
+        "value".to_string()
+    } else if RUST_KEYWORDS.contains(&name.as_str()) {
+        format!("{}_", name)
+    } else {
+        name
+    }
+}
+
+/// `get-balance?` => `GetBalance`
+fn camel_case(clarity_name: &str) -> String {
+    clarity_name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            format!("{}{}", first, chars.as_str())
+        })
+        .collect()
+}
+
+/// Append a number to `name` if needed to make it unique within `used`.
+fn unique_name(used: &mut HashSet<String>, name: String) -> String {
+    let mut candidate = name.clone();
+    let mut suffix = 2;
+    while used.contains(&candidate) {
+        candidate = format!("{}{}", name, suffix);
+        suffix += 1;
+    }
+    used.insert(candidate.clone());
+    candidate
+}
+
+struct Generator {
+    /// Names of the items at the top level of the bindings
+    used_names: HashSet<String>,
+    /// Generated tuple structs
+    tuple_structs: Vec<String>,
+    uses_principal: bool,
+}
+
+impl Generator {
+    fn resolve_type(&mut self, atom_type: &ContractInterfaceAtomType, hint: &str) -> RustType {
+        use vm::analysis::contract_interface_builder::ContractInterfaceAtomType::*;
+        match atom_type {
+            none => RustType::Unit,
+            int128 => RustType::Int,
+            uint128 => RustType::UInt,
+            bool => RustType::Bool,
+            principal => {
+                self.uses_principal = true;
+                RustType::Principal
+            }
+            trait_reference => {
+                self.uses_principal = true;
+                RustType::Contract
+            }
+            buffer { .. } => RustType::Buffer,
+            string_ascii { .. } => RustType::StringAscii,
+            string_utf8 { .. } => RustType::StringUtf8,
+            optional(inner) => RustType::Optional(Box::new(self.resolve_type(inner, hint))),
+            response { ok, error } => RustType::Response(
+                Box::new(self.resolve_type(ok, &format!("{}Ok", hint))),
+                Box::new(self.resolve_type(error, &format!("{}Err", hint))),
+            ),
+            list { type_f, .. } => RustType::List(Box::new(
+                self.resolve_type(type_f, &format!("{}Item", hint)),
+            )),
+            tuple(entries) => {
+                let name = unique_name(&mut self.used_names, hint.to_string());
+                let mut field_names = HashSet::new();
+                let fields = entries
+                    .iter()
+                    .map(|entry| Field {
+                        clarity_name: entry.name.clone(),
+                        rust_name: unique_name(&mut field_names, snake_case(&entry.name)),
+                        rust_type: self.resolve_type(
+                            &entry.type_f,
+                            &format!("{}{}", hint, camel_case(&entry.name)),
+                        ),
+                    })
+                    .collect::<Vec<_>>();
+                let tuple_struct = self.tuple_struct(&name, &fields);
+                self.tuple_structs.push(tuple_struct);
+                RustType::Tuple(name)
+            }
+        }
+    }
+
+    fn tuple_struct(&self, name: &str, fields: &[Field]) -> String {
+        let mut out = String::new();
+        out.push_str("#[derive(Debug, Clone, PartialEq)]\n");
+        out.push_str(&format!("pub struct {} {{\n", name));
+        for field in fields.iter() {
+            out.push_str(&format!(
+                "    pub {}: {},\n",
+                field.rust_name,
+                type_str(&field.rust_type)
+            ));
+        }
+        out.push_str("}\n\n");
+
+        out.push_str(&format!("impl {} {{\n", name));
+        out.push_str("    pub fn to_value(&self) -> runtime::Result<Value> {\n");
+        out.push_str("        runtime::tuple_to_value(vec![\n");
+        for field in fields.iter() {
+            out.push_str(&format!(
+                "            (\"{}\", {}?),\n",
+                field.clarity_name,
+                to_value_expr(&field.rust_type, &format!("&self.{}", field.rust_name), 0)
+            ));
+        }
+        out.push_str("        ])\n");
+        out.push_str("    }\n\n");
+        out.push_str(&format!(
+            "    pub fn from_value(value: Value) -> runtime::Result<{}> {{\n",
+            name
+        ));
+        out.push_str("        let mut tuple = runtime::tuple_from_value(value)?;\n");
+        out.push_str(&format!("        Ok({} {{\n", name));
+        for field in fields.iter() {
+            out.push_str(&format!(
+                "            {}: {}?,\n",
+                field.rust_name,
+                from_value_expr(
+                    &field.rust_type,
+                    &format!(
+                        "runtime::take_field(&mut tuple, \"{}\")?",
+                        field.clarity_name
+                    ),
+                    0
+                )
+            ));
+        }
+        out.push_str("        })\n");
+        out.push_str("    }\n");
+        out.push_str("}\n\n");
+        out
+    }
+
+    fn function_bindings(&mut self, function: &ContractInterfaceFunction) -> String {
+        let fn_name = unique_name(&mut self.used_names, snake_case(&function.name));
+        let camel = camel_case(&function.name);
+        let args_name = unique_name(&mut self.used_names, format!("{}Args", camel));
+        let output_name = unique_name(&mut self.used_names, format!("{}Output", camel));
+        let decode_name = unique_name(
+            &mut self.used_names,
+            format!("{}_output_from_value", fn_name),
+        );
+
+        let mut field_names = HashSet::new();
+        let fields = function
+            .args
+            .iter()
+            .map(|arg| Field {
+                clarity_name: arg.name.clone(),
+                rust_name: unique_name(&mut field_names, snake_case(&arg.name)),
+                rust_type: self
+                    .resolve_type(&arg.type_f, &format!("{}{}", camel, camel_case(&arg.name))),
+            })
+            .collect::<Vec<_>>();
+        let output_type = self.resolve_type(&function.outputs.type_f, &format!("{}Result", camel));
+
+        let mut out = String::new();
+        out.push_str(&format!("/// Arguments to `{}`\n", function.name));
+        out.push_str("#[derive(Debug, Clone, PartialEq)]\n");
+        out.push_str(&format!("pub struct {} {{\n", args_name));
+        for field in fields.iter() {
+            out.push_str(&format!(
+                "    pub {}: {},\n",
+                field.rust_name,
+                type_str(&field.rust_type)
+            ));
+        }
+        out.push_str("}\n\n");
+
+        out.push_str(&format!("impl {} {{\n", args_name));
+        out.push_str("    pub fn to_values(&self) -> runtime::Result<Vec<Value>> {\n");
+        out.push_str("        Ok(vec![\n");
+        for field in fields.iter() {
+            out.push_str(&format!(
+                "            {}?,\n",
+                to_value_expr(&field.rust_type, &format!("&self.{}", field.rust_name), 0)
+            ));
+        }
+        out.push_str("        ])\n");
+        out.push_str("    }\n\n");
+        out.push_str(&format!(
+            "    pub fn from_values(values: Vec<Value>) -> runtime::Result<{}> {{\n",
+            args_name
+        ));
+        out.push_str(&format!("        if values.len() != {} {{\n", fields.len()));
+        out.push_str(&format!(
+            "            return Err(runtime::Error::Conversion(format!(\"expected {} arguments, got {{}}\", values.len())));\n",
+            fields.len()
+        ));
+        out.push_str("        }\n");
+        if fields.is_empty() {
+            out.push_str(&format!("        Ok({} {{}})\n", args_name));
+        } else {
+            out.push_str("        let mut values = values.into_iter();\n");
+            out.push_str(&format!("        Ok({} {{\n", args_name));
+            for field in fields.iter() {
+                out.push_str(&format!(
+                    "            {}: {}?,\n",
+                    field.rust_name,
+                    from_value_expr(&field.rust_type, "values.next().unwrap()", 0)
+                ));
+            }
+            out.push_str("        })\n");
+        }
+        out.push_str("    }\n");
+        out.push_str("}\n\n");
+
+        out.push_str(&format!(
+            "/// The result of `{}`\npub type {} = {};\n\n",
+            function.name,
+            output_name,
+            type_str(&output_type)
+        ));
+        out.push_str(&format!(
+            "pub fn {}(value: Value) -> runtime::Result<{}> {{\n    {}\n}}\n\n",
+            decode_name,
+            output_name,
+            from_value_expr(&output_type, "value", 0)
+        ));
+
+        match function.access {
+            ContractInterfaceFunctionAccess::public => {
+                out.push_str(&format!(
+                    "/// The payload of a transaction that calls `{}`\n",
+                    function.name
+                ));
+                out.push_str(&format!(
+                    "pub fn {}(args: &{}) -> runtime::Result<TransactionPayload> {{\n",
+                    fn_name, args_name
+                ));
+                out.push_str(&format!(
+                    "    runtime::contract_call_payload(&contract_id(), \"{}\", args.to_values()?)\n",
+                    function.name
+                ));
+                out.push_str("}\n\n");
+            }
+            ContractInterfaceFunctionAccess::read_only => {
+                out.push_str(&format!(
+                    "/// Call `{}` through a node's RPC interface\n",
+                    function.name
+                ));
+                out.push_str(&format!(
+                    "pub fn {}(\n    client: &ReadOnlyClient,\n    sender: &PrincipalData,\n    args: &{},\n) -> runtime::Result<{}> {{\n",
+                    fn_name, args_name, output_name
+                ));
+                out.push_str(&format!(
+                    "    let value = client.call_read_only(&contract_id(), \"{}\", sender, args.to_values()?)?;\n",
+                    function.name
+                ));
+                out.push_str(&format!("    {}(value)\n", decode_name));
+                out.push_str("}\n\n");
+            }
+            ContractInterfaceFunctionAccess::private => {}
+        }
+        out
+    }
+}
+
+fn type_str(rust_type: &RustType) -> String {
+    match rust_type {
+        RustType::Int => "i128".to_string(),
+        RustType::UInt => "u128".to_string(),
+        RustType::Bool => "bool".to_string(),
+        RustType::Principal => "PrincipalData".to_string(),
+        RustType::Contract => "QualifiedContractIdentifier".to_string(),
+        RustType::Buffer => "Vec<u8>".to_string(),
+        RustType::StringAscii | RustType::StringUtf8 => "String".to_string(),
+        RustType::Unit => "()".to_string(),
+        RustType::Optional(inner) => format!("Option<{}>", type_str(inner)),
+        RustType::Response(ok, err) => format!("Result<{}, {}>", type_str(ok), type_str(err)),
+        RustType::List(item) => format!("Vec<{}>", type_str(item)),
+        RustType::Tuple(name) => name.clone(),
+    }
+}
+
+/// An expression converting `expr`, a reference to a value of `rust_type`, into a
+/// `runtime::Result<Value>`.  `depth` keeps closure argument names distinct.
+fn to_value_expr(rust_type: &RustType, expr: &str, depth: usize) -> String {
+    let var = format!("v{}", depth);
+    match rust_type {
+        RustType::Int => format!("Ok::<_, runtime::Error>(Value::Int(*{}))", expr),
+        RustType::UInt => format!("Ok::<_, runtime::Error>(Value::UInt(*{}))", expr),
+        RustType::Bool => format!("Ok::<_, runtime::Error>(Value::Bool(*{}))", expr),
+        RustType::Principal => format!(
+            "Ok::<_, runtime::Error>(Value::Principal(({}).clone()))",
+            expr
+        ),
+        RustType::Contract => format!(
+            "Ok::<_, runtime::Error>(Value::Principal(PrincipalData::Contract(({}).clone())))",
+            expr
+        ),
+        RustType::Buffer => format!("runtime::buff_to_value({})", expr),
+        RustType::StringAscii => format!("runtime::string_ascii_to_value({})", expr),
+        RustType::StringUtf8 => format!("runtime::string_utf8_to_value({})", expr),
+        RustType::Unit => "Ok::<_, runtime::Error>(Value::none())".to_string(),
+        RustType::Optional(inner) => format!(
+            "runtime::optional_to_value({}, |{}| {})",
+            expr,
+            var,
+            to_value_expr(inner, &var, depth + 1)
+        ),
+        RustType::Response(ok, err) => format!(
+            "runtime::response_to_value({}, |{}| {}, |{}| {})",
+            expr,
+            var,
+            to_value_expr(ok, &var, depth + 1),
+            var,
+            to_value_expr(err, &var, depth + 1)
+        ),
+        RustType::List(item) => format!(
+            "runtime::list_to_value({}, |{}| {})",
+            expr,
+            var,
+            to_value_expr(item, &var, depth + 1)
+        ),
+        RustType::Tuple(_) => format!("({}).to_value()", expr),
+    }
+}
+
+/// An expression converting `expr`, a `Value`, into a `runtime::Result` of `rust_type`.
+fn from_value_expr(rust_type: &RustType, expr: &str, depth: usize) -> String {
+    let var = format!("v{}", depth);
+    match rust_type {
+        RustType::Int => format!("runtime::int_from_value({})", expr),
+        RustType::UInt => format!("runtime::uint_from_value({})", expr),
+        RustType::Bool => format!("runtime::bool_from_value({})", expr),
+        RustType::Principal => format!("runtime::principal_from_value({})", expr),
+        RustType::Contract => format!("runtime::contract_from_value({})", expr),
+        RustType::Buffer => format!("runtime::buff_from_value({})", expr),
+        RustType::StringAscii => format!("runtime::string_ascii_from_value({})", expr),
+        RustType::StringUtf8 => format!("runtime::string_utf8_from_value({})", expr),
+        RustType::Unit => format!("runtime::unit_from_value({})", expr),
+        RustType::Optional(inner) => format!(
+            "runtime::optional_from_value({}, |{}| {})",
+            expr,
+            var,
+            from_value_expr(inner, &var, depth + 1)
+        ),
+        RustType::Response(ok, err) => format!(
+            "runtime::response_from_value({}, |{}| {}, |{}| {})",
+            expr,
+            var,
+            from_value_expr(ok, &var, depth + 1),
+            var,
+            from_value_expr(err, &var, depth + 1)
+        ),
+        RustType::List(item) => format!(
+            "runtime::list_from_value({}, |{}| {})",
+            expr,
+            var,
+            from_value_expr(item, &var, depth + 1)
+        ),
+        RustType::Tuple(name) => format!("{}::from_value({})", name, expr),
+    }
+}
+
+/// Generate the source of a Rust module with bindings for the public and read-only functions
+/// of the contract `contract_identifier`, whose interface is `interface`.
+pub fn generate_rust_bindings(
+    contract_identifier: &QualifiedContractIdentifier,
+    interface: &ContractInterface,
+) -> String {
+    let mut generator = Generator {
+        used_names: ["CONTRACT_ID", "contract_id"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect(),
+        tuple_structs: vec![],
+        uses_principal: false,
+    };
+
+    let functions: Vec<_> = interface
+        .functions
+        .iter()
+        .filter(|function| function.access != ContractInterfaceFunctionAccess::private)
+        .collect();
+    let has_public = functions
+        .iter()
+        .any(|function| function.access == ContractInterfaceFunctionAccess::public);
+    let has_read_only = functions
+        .iter()
+        .any(|function| function.access == ContractInterfaceFunctionAccess::read_only);
+
+    let mut function_bindings = String::new();
+    for function in functions.iter() {
+        function_bindings.push_str(&generator.function_bindings(function));
+    }
+
+    let mut out = String::new();
+    out.push_str(&format!(
+        "//! Bindings for the Clarity contract `{}`.\n//! Generated by `clarity-cli codegen`; do not edit.\n\n",
+        contract_identifier
+    ));
+    if has_public {
+        out.push_str("use blockstack_lib::chainstate::stacks::TransactionPayload;\n");
+    }
+    if has_read_only {
+        out.push_str("use blockstack_lib::codegen::runtime::{self, ReadOnlyClient};\n");
+    } else {
+        out.push_str("use blockstack_lib::codegen::runtime;\n");
+    }
+    if has_read_only || generator.uses_principal {
+        out.push_str(
+            "use blockstack_lib::vm::types::{PrincipalData, QualifiedContractIdentifier, Value};\n",
+        );
+    } else {
+        out.push_str("use blockstack_lib::vm::types::{QualifiedContractIdentifier, Value};\n");
+    }
+    out.push_str("\n");
+    out.push_str(&format!(
+        "pub const CONTRACT_ID: &str = \"{}\";\n\n",
+        contract_identifier
+    ));
+    out.push_str("pub fn contract_id() -> QualifiedContractIdentifier {\n");
+    out.push_str(
+        "    QualifiedContractIdentifier::parse(CONTRACT_ID).expect(\"invalid contract identifier\")\n",
+    );
+    out.push_str("}\n\n");
+    for tuple_struct in generator.tuple_structs.iter() {
+        out.push_str(tuple_struct);
+    }
+    out.push_str(&function_bindings);
+
+    // no trailing blank line
+    let trimmed_len = out.trim_end().len();
+    out.truncate(trimmed_len);
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests;
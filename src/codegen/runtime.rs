@@ -0,0 +1,449 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Support code for generated contract bindings: conversions between Rust values and Clarity
+//! `Value`s, contract-call payloads, and a minimal client for the node's read-only function
+//! RPC endpoint.
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::io;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+
+use chainstate::stacks::{TransactionContractCall, TransactionPayload};
+use net::{CallReadOnlyRequestBody, CallReadOnlyResponse};
+use vm::database::ClaritySerializable;
+use vm::errors::Error as ClarityError;
+use vm::types::{
+    CharType, PrincipalData, QualifiedContractIdentifier, SequenceData, TupleData, Value,
+};
+use vm::ClarityName;
+
+use crate::types::chainstate::StacksAddress;
+
+#[derive(Debug)]
+pub enum Error {
+    /// A value did not have the type the bindings expected, or did not fit its Clarity type
+    Conversion(String),
+    Io(io::Error),
+    /// The node's response could not be understood
+    Http(String),
+    /// The node could not evaluate the read-only function
+    CallFailed(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Conversion(msg) => write!(f, "Conversion error: {}", msg),
+            Error::Io(e) => write!(f, "IO error: {}", e),
+            Error::Http(msg) => write!(f, "HTTP error: {}", msg),
+            Error::CallFailed(cause) => write!(f, "Read-only call failed: {}", cause),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<ClarityError> for Error {
+    fn from(e: ClarityError) -> Error {
+        Error::Conversion(e.to_string())
+    }
+}
+
+fn unexpected(expected: &str, value: &Value) -> Error {
+    Error::Conversion(format!("expected {}, got {}", expected, value))
+}
+
+pub fn int_from_value(value: Value) -> Result<i128> {
+    match value {
+        Value::Int(x) => Ok(x),
+        _ => Err(unexpected("an int", &value)),
+    }
+}
+
+pub fn uint_from_value(value: Value) -> Result<u128> {
+    match value {
+        Value::UInt(x) => Ok(x),
+        _ => Err(unexpected("a uint", &value)),
+    }
+}
+
+pub fn bool_from_value(value: Value) -> Result<bool> {
+    match value {
+        Value::Bool(x) => Ok(x),
+        _ => Err(unexpected("a bool", &value)),
+    }
+}
+
+pub fn principal_from_value(value: Value) -> Result<PrincipalData> {
+    match value {
+        Value::Principal(x) => Ok(x),
+        _ => Err(unexpected("a principal", &value)),
+    }
+}
+
+pub fn contract_from_value(value: Value) -> Result<QualifiedContractIdentifier> {
+    match value {
+        Value::Principal(PrincipalData::Contract(x)) => Ok(x),
+        _ => Err(unexpected("a contract principal", &value)),
+    }
+}
+
+pub fn buff_from_value(value: Value) -> Result<Vec<u8>> {
+    match value {
+        Value::Sequence(SequenceData::Buffer(buff)) => Ok(buff.data),
+        _ => Err(unexpected("a buffer", &value)),
+    }
+}
+
+pub fn string_ascii_from_value(value: Value) -> Result<String> {
+    match value {
+        Value::Sequence(SequenceData::String(CharType::ASCII(string))) => {
+            String::from_utf8(string.data).map_err(|e| Error::Conversion(e.to_string()))
+        }
+        _ => Err(unexpected("an ASCII string", &value)),
+    }
+}
+
+pub fn string_utf8_from_value(value: Value) -> Result<String> {
+    match value {
+        Value::Sequence(SequenceData::String(CharType::UTF8(string))) => {
+            String::from_utf8(string.data.concat()).map_err(|e| Error::Conversion(e.to_string()))
+        }
+        _ => Err(unexpected("a UTF-8 string", &value)),
+    }
+}
+
+/// The `none` type only appears where no value is ever produced, such as the `err` side of a
+/// response that is always `ok`.
+pub fn unit_from_value(_value: Value) -> Result<()> {
+    Ok(())
+}
+
+pub fn optional_from_value<T, F>(value: Value, from_inner: F) -> Result<Option<T>>
+where
+    F: Fn(Value) -> Result<T>,
+{
+    match value {
+        Value::Optional(optional) => match optional.data {
+            Some(inner) => Ok(Some(from_inner(*inner)?)),
+            None => Ok(None),
+        },
+        _ => Err(unexpected("an optional", &value)),
+    }
+}
+
+pub fn response_from_value<T, E, F, G>(
+    value: Value,
+    from_ok: F,
+    from_err: G,
+) -> Result<std::result::Result<T, E>>
+where
+    F: Fn(Value) -> Result<T>,
+    G: Fn(Value) -> Result<E>,
+{
+    match value {
+        Value::Response(response) => {
+            if response.committed {
+                Ok(Ok(from_ok(*response.data)?))
+            } else {
+                Ok(Err(from_err(*response.data)?))
+            }
+        }
+        _ => Err(unexpected("a response", &value)),
+    }
+}
+
+pub fn list_from_value<T, F>(value: Value, from_item: F) -> Result<Vec<T>>
+where
+    F: Fn(Value) -> Result<T>,
+{
+    match value {
+        Value::Sequence(SequenceData::List(list)) => {
+            list.data.into_iter().map(|item| from_item(item)).collect()
+        }
+        _ => Err(unexpected("a list", &value)),
+    }
+}
+
+pub fn tuple_from_value(value: Value) -> Result<TupleData> {
+    match value {
+        Value::Tuple(tuple) => Ok(tuple),
+        _ => Err(unexpected("a tuple", &value)),
+    }
+}
+
+/// Remove a field from a tuple, so that its value can be converted without copying.
+pub fn take_field(tuple: &mut TupleData, name: &str) -> Result<Value> {
+    tuple
+        .data_map
+        .remove(name)
+        .ok_or_else(|| Error::Conversion(format!("tuple has no field '{}'", name)))
+}
+
+pub fn buff_to_value(bytes: &[u8]) -> Result<Value> {
+    Ok(Value::buff_from(bytes.to_vec())?)
+}
+
+pub fn string_ascii_to_value(string: &str) -> Result<Value> {
+    Ok(Value::string_ascii_from_bytes(string.as_bytes().to_vec())?)
+}
+
+pub fn string_utf8_to_value(string: &str) -> Result<Value> {
+    Ok(Value::string_utf8_from_bytes(string.as_bytes().to_vec())?)
+}
+
+pub fn optional_to_value<T, F>(optional: &Option<T>, to_inner: F) -> Result<Value>
+where
+    F: Fn(&T) -> Result<Value>,
+{
+    match optional {
+        Some(inner) => Ok(Value::some(to_inner(inner)?)?),
+        None => Ok(Value::none()),
+    }
+}
+
+pub fn response_to_value<T, E, F, G>(
+    response: &std::result::Result<T, E>,
+    to_ok: F,
+    to_err: G,
+) -> Result<Value>
+where
+    F: Fn(&T) -> Result<Value>,
+    G: Fn(&E) -> Result<Value>,
+{
+    match response {
+        Ok(data) => Ok(Value::okay(to_ok(data)?)?),
+        Err(data) => Ok(Value::error(to_err(data)?)?),
+    }
+}
+
+pub fn list_to_value<T, F>(items: &[T], to_item: F) -> Result<Value>
+where
+    F: Fn(&T) -> Result<Value>,
+{
+    let items = items
+        .iter()
+        .map(|item| to_item(item))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Value::list_from(items)?)
+}
+
+pub fn tuple_to_value(fields: Vec<(&str, Value)>) -> Result<Value> {
+    let fields = fields
+        .into_iter()
+        .map(|(name, value)| {
+            ClarityName::try_from(name.to_string())
+                .map(|name| (name, value))
+                .map_err(|e| Error::Conversion(e.to_string()))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Value::from(TupleData::from_data(fields)?))
+}
+
+/// The payload of a transaction that calls `function_name` on `contract`.
+pub fn contract_call_payload(
+    contract: &QualifiedContractIdentifier,
+    function_name: &str,
+    function_args: Vec<Value>,
+) -> Result<TransactionPayload> {
+    let function_name = ClarityName::try_from(function_name.to_string())
+        .map_err(|e| Error::Conversion(e.to_string()))?;
+    Ok(TransactionPayload::ContractCall(TransactionContractCall {
+        address: StacksAddress::from(contract.issuer.clone()),
+        contract_name: contract.name.clone(),
+        function_name,
+        function_args,
+    }))
+}
+
+/// Calls read-only functions through a node's `/v2/contracts/call-read` endpoint.
+pub struct ReadOnlyClient {
+    /// `host:port` of the node's RPC interface
+    node: String,
+}
+
+impl ReadOnlyClient {
+    pub fn new(node: &str) -> ReadOnlyClient {
+        ReadOnlyClient {
+            node: node.to_string(),
+        }
+    }
+
+    pub fn call_read_only(
+        &self,
+        contract: &QualifiedContractIdentifier,
+        function_name: &str,
+        sender: &PrincipalData,
+        function_args: Vec<Value>,
+    ) -> Result<Value> {
+        let path = format!(
+            "/v2/contracts/call-read/{}/{}/{}",
+            StacksAddress::from(contract.issuer.clone()),
+            contract.name.as_str(),
+            function_name
+        );
+        let body = serde_json::to_string(&CallReadOnlyRequestBody {
+            sender: sender.to_string(),
+            arguments: function_args.iter().map(|arg| arg.serialize()).collect(),
+            arguments_json: None,
+        })
+        .map_err(|e| Error::Http(e.to_string()))?;
+
+        let response_body = self.post_json(&path, &body)?;
+        let response: CallReadOnlyResponse = serde_json::from_slice(&response_body)
+            .map_err(|e| Error::Http(format!("invalid response body: {}", e)))?;
+        if !response.okay {
+            return Err(Error::CallFailed(
+                response.cause.unwrap_or_else(|| "unknown".to_string()),
+            ));
+        }
+        let result = response
+            .result
+            .ok_or_else(|| Error::Http("response has no result".to_string()))?;
+        Value::try_deserialize_hex_untyped(&result)
+            .map_err(|e| Error::Http(format!("invalid result value: {}", e)))
+    }
+
+    fn post_json(&self, path: &str, body: &str) -> Result<Vec<u8>> {
+        let mut stream = TcpStream::connect(&self.node)?;
+        write!(
+            stream,
+            "POST {} HTTP/1.0\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            path,
+            self.node,
+            body.len(),
+            body
+        )?;
+        stream.flush()?;
+
+        let mut response = vec![];
+        stream.read_to_end(&mut response)?;
+        let header_end = response
+            .windows(4)
+            .position(|window| window == b"\r\n\r\n")
+            .ok_or_else(|| Error::Http("truncated response".to_string()))?;
+        let headers = String::from_utf8_lossy(&response[..header_end]).to_lowercase();
+        let body = response[header_end + 4..].to_vec();
+
+        let status_line = headers.lines().next().unwrap_or("");
+        if status_line.split_whitespace().nth(1) != Some("200") {
+            return Err(Error::Http(format!(
+                "unexpected status '{}': {}",
+                status_line,
+                String::from_utf8_lossy(&body)
+            )));
+        }
+        if headers.contains("transfer-encoding: chunked") {
+            decode_chunked(&body)
+        } else {
+            Ok(body)
+        }
+    }
+}
+
+fn decode_chunked(mut chunked: &[u8]) -> Result<Vec<u8>> {
+    let mut body = vec![];
+    loop {
+        let line_end = chunked
+            .windows(2)
+            .position(|window| window == b"\r\n")
+            .ok_or_else(|| Error::Http("truncated chunk".to_string()))?;
+        let size_line = String::from_utf8_lossy(&chunked[..line_end]);
+        let size_hex = size_line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_hex, 16)
+            .map_err(|_| Error::Http(format!("invalid chunk size '{}'", size_hex)))?;
+        chunked = &chunked[line_end + 2..];
+        if size == 0 {
+            return Ok(body);
+        }
+        if chunked.len() < size {
+            return Err(Error::Http("truncated chunk".to_string()));
+        }
+        body.extend_from_slice(&chunked[..size]);
+        chunked = chunked.get(size + 2..).unwrap_or(&[]);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_chunked() {
+        assert_eq!(
+            decode_chunked(b"5\r\nhello\r\n7;ext=1\r\n, world\r\n0\r\n\r\n").unwrap(),
+            b"hello, world".to_vec()
+        );
+        assert!(decode_chunked(b"a\r\nshort\r\n").is_err());
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let value = response_to_value(
+            &Ok::<_, u128>(Some(vec![1i128, 2, 3])),
+            |ok| optional_to_value(ok, |list| list_to_value(list, |x| Ok(Value::Int(*x)))),
+            |err| Ok(Value::UInt(*err)),
+        )
+        .unwrap();
+        let decoded = response_from_value(
+            value,
+            |ok| optional_from_value(ok, |list| list_from_value(list, int_from_value)),
+            uint_from_value,
+        )
+        .unwrap();
+        assert_eq!(decoded, Ok(Some(vec![1, 2, 3])));
+
+        let mut tuple = tuple_from_value(
+            tuple_to_value(vec![
+                ("name", string_utf8_to_value("caf\u{e9}").unwrap()),
+                ("id", buff_to_value(&[1, 2]).unwrap()),
+            ])
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            string_utf8_from_value(take_field(&mut tuple, "name").unwrap()).unwrap(),
+            "caf\u{e9}"
+        );
+        assert_eq!(
+            buff_from_value(take_field(&mut tuple, "id").unwrap()).unwrap(),
+            vec![1, 2]
+        );
+        assert!(take_field(&mut tuple, "id").is_err());
+
+        assert!(int_from_value(Value::UInt(1)).is_err());
+        assert!(buff_to_value(&vec![0; 1024 * 1024 + 1]).is_err());
+    }
+}
@@ -0,0 +1,267 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chainstate::stacks::TransactionPayload;
+use codegen::runtime;
+use codegen::{camel_case, generate_rust_bindings, snake_case};
+use vm::analysis::contract_interface_builder::build_contract_interface;
+use vm::analysis::mem_type_check;
+use vm::execute;
+use vm::types::{PrincipalData, QualifiedContractIdentifier, Value};
+
+/// Bindings for `fixtures/example.clar`, checked in so that the suite compiles generated code
+#[allow(dead_code)]
+#[rustfmt::skip]
+#[path = "fixtures/example.rs"]
+mod example;
+
+const EXAMPLE_CONTRACT: &str = include_str!("fixtures/example.clar");
+const EXAMPLE_BINDINGS: &str = include_str!("fixtures/example.rs");
+
+fn generate(contract: &str) -> String {
+    let (_, analysis) = mem_type_check(contract).unwrap();
+    let contract_id =
+        QualifiedContractIdentifier::parse("SP000000000000000000002Q6VF78.example").unwrap();
+    generate_rust_bindings(&contract_id, &build_contract_interface(&analysis))
+}
+
+#[test]
+fn test_names() {
+    assert_eq!(snake_case("get-balance?"), "get_balance");
+    assert_eq!(snake_case("mint!"), "mint");
+    assert_eq!(snake_case("type"), "type_");
+    assert_eq!(camel_case("get-balance?"), "GetBalance");
+    assert_eq!(camel_case("nft-owner"), "NftOwner");
+}
+
+#[test]
+fn test_public_function() {
+    let bindings = generate(
+        "(define-public (transfer? (amount uint) (recipient principal) (memo (optional (buff 34))))
+            (ok amount))
+         (define-private (helper) 1)",
+    );
+
+    assert!(bindings
+        .contains("pub const CONTRACT_ID: &str = \"SP000000000000000000002Q6VF78.example\";"));
+    assert!(bindings.contains(
+        "pub struct TransferArgs {
+    pub amount: u128,
+    pub recipient: PrincipalData,
+    pub memo: Option<Vec<u8>>,
+}"
+    ));
+    assert!(bindings.contains(
+        "            runtime::optional_to_value(&self.memo, |v0| runtime::buff_to_value(v0))?,"
+    ));
+    assert!(bindings.contains(
+        "            memo: runtime::optional_from_value(values.next().unwrap(), |v0| runtime::buff_from_value(v0))?,"
+    ));
+    assert!(bindings.contains("pub type TransferOutput = Result<u128, ()>;"));
+    assert!(bindings.contains(
+        "pub fn transfer(args: &TransferArgs) -> runtime::Result<TransactionPayload> {
+    runtime::contract_call_payload(&contract_id(), \"transfer?\", args.to_values()?)
+}"
+    ));
+    assert!(!bindings.contains("ReadOnlyClient"));
+    assert!(!bindings.contains("helper"));
+}
+
+#[test]
+fn test_read_only_function_with_tuples() {
+    let bindings = generate(
+        "(define-read-only (get-info (who principal))
+            (ok {name: u\"someone\", stats: {score: 1, tags: (list \"a\")}}))",
+    );
+
+    assert!(bindings.contains("use blockstack_lib::codegen::runtime::{self, ReadOnlyClient};"));
+    assert!(bindings.contains(
+        "pub struct GetInfoResultOk {
+    pub name: String,
+    pub stats: GetInfoResultOkStats,
+}"
+    ));
+    assert!(bindings.contains(
+        "pub struct GetInfoResultOkStats {
+    pub score: i128,
+    pub tags: Vec<String>,
+}"
+    ));
+    assert!(bindings.contains(
+        "            (\"tags\", runtime::list_to_value(&self.tags, |v0| runtime::string_ascii_to_value(v0))?),"
+    ));
+    assert!(bindings.contains(
+        "            stats: GetInfoResultOkStats::from_value(runtime::take_field(&mut tuple, \"stats\")?)?,"
+    ));
+    assert!(bindings.contains("pub type GetInfoOutput = Result<GetInfoResultOk, ()>;"));
+    assert!(bindings.contains(
+        "pub fn get_info(
+    client: &ReadOnlyClient,
+    sender: &PrincipalData,
+    args: &GetInfoArgs,
+) -> runtime::Result<GetInfoOutput> {
+    let value = client.call_read_only(&contract_id(), \"get-info\", sender, args.to_values()?)?;
+    get_info_output_from_value(value)
+}"
+    ));
+}
+
+#[test]
+fn test_name_collisions() {
+    let bindings = generate(
+        "(define-public (mint) (ok true))
+         (define-public (mint!) (ok true))
+         (define-read-only (contract-id) 1)",
+    );
+    assert!(bindings.contains("pub fn mint(args: &MintArgs)"));
+    assert!(bindings.contains("pub fn mint2(args: &MintArgs2)"));
+    assert!(bindings.contains("pub fn contract_id2("));
+    assert!(bindings.contains("pub struct MintArgs {\n}"));
+    assert!(bindings.contains("        Ok(MintArgs {})"));
+}
+
+#[test]
+fn test_checked_in_bindings_are_current() {
+    assert!(
+        generate(EXAMPLE_CONTRACT) == EXAMPLE_BINDINGS,
+        "src/codegen/fixtures/example.rs is out of date; regenerate it with the command in example.clar"
+    );
+}
+
+#[test]
+fn test_bindings_args_round_trip() {
+    let owner = PrincipalData::parse("SP000000000000000000002Q6VF78").unwrap();
+    let token = QualifiedContractIdentifier::parse("SP000000000000000000002Q6VF78.token").unwrap();
+    let args = example::RegisterArgs {
+        name: "alice".to_string(),
+        display_name: "Alice \u{1f600}".to_string(),
+        owner: owner.clone(),
+        token: token.clone(),
+        salt: vec![0xde, 0xad],
+        scores: vec![-1, 2],
+        referrer: Some(owner.clone()),
+        settings: example::RegisterSettings {
+            limit: 10,
+            public: true,
+            tags: vec!["a".to_string(), "b".to_string()],
+        },
+    };
+
+    let values = args.to_values().unwrap();
+    assert_eq!(
+        values,
+        vec![
+            Value::string_ascii_from_bytes(b"alice".to_vec()).unwrap(),
+            Value::string_utf8_from_bytes("Alice \u{1f600}".as_bytes().to_vec()).unwrap(),
+            Value::Principal(owner.clone()),
+            Value::Principal(PrincipalData::Contract(token)),
+            Value::buff_from(vec![0xde, 0xad]).unwrap(),
+            Value::list_from(vec![Value::Int(-1), Value::Int(2)]).unwrap(),
+            Value::some(Value::Principal(owner)).unwrap(),
+            execute("{public: true, limit: u10, tags: (list \"a\" \"b\")}")
+                .unwrap()
+                .unwrap(),
+        ]
+    );
+    assert_eq!(
+        example::RegisterArgs::from_values(values.clone()).unwrap(),
+        args
+    );
+    assert!(example::RegisterArgs::from_values(values[1..].to_vec()).is_err());
+
+    match example::register(&args).unwrap() {
+        TransactionPayload::ContractCall(call) => {
+            assert_eq!(call.contract_name.as_str(), "example");
+            assert_eq!(call.function_name.as_str(), "register");
+            assert_eq!(call.function_args, values);
+        }
+        payload => panic!("Unexpected payload {:?}", payload),
+    }
+}
+
+#[test]
+fn test_bindings_outputs_round_trip() {
+    let call = |expr: &str| {
+        execute(&format!("{}\n{}", EXAMPLE_CONTRACT, expr))
+            .unwrap()
+            .unwrap()
+    };
+
+    let value = call("(get-profile 'SP000000000000000000002Q6VF78)");
+    let profile = example::get_profile_output_from_value(value.clone())
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        profile,
+        example::GetProfileResultOk {
+            display_name: "caf\u{e9}".to_string(),
+            note: Some("hello".to_string()),
+            owner: PrincipalData::parse("SP000000000000000000002Q6VF78").unwrap(),
+            stats: example::GetProfileResultOkStats {
+                badges: vec![vec![0x01], vec![0x02, 0x03]],
+                score: -7,
+            },
+        }
+    );
+    assert_eq!(Value::okay(profile.to_value().unwrap()).unwrap(), value);
+
+    let value = call("(lookup u1)");
+    let items = example::lookup_output_from_value(value.clone())
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        items,
+        vec![
+            example::LookupResultOkItem {
+                active: true,
+                id: 1,
+            },
+            example::LookupResultOkItem {
+                active: false,
+                id: 2,
+            },
+        ]
+    );
+    let encoded = runtime::response_to_value(
+        &example::LookupOutput::Ok(items),
+        |ok| runtime::list_to_value(ok, |item| item.to_value()),
+        |err| runtime::string_ascii_to_value(err),
+    )
+    .unwrap();
+    assert_eq!(encoded, value);
+
+    assert_eq!(
+        example::lookup_output_from_value(call("(lookup u11)")).unwrap(),
+        Err("not found".to_string())
+    );
+
+    let output: example::RegisterOutput = Ok(example::RegisterResultOk {
+        limit: 3,
+        name: "bob".to_string(),
+        referrer: None,
+    });
+    let encoded =
+        runtime::response_to_value(&output, |ok| ok.to_value(), |err| Ok(Value::UInt(*err)))
+            .unwrap();
+    assert_eq!(
+        example::register_output_from_value(encoded).unwrap(),
+        output
+    );
+
+    // values of the wrong type are refused
+    assert!(example::get_profile_output_from_value(Value::UInt(1)).is_err());
+    assert!(example::lookup_output_from_value(Value::okay(Value::UInt(1)).unwrap()).is_err());
+}
@@ -40,6 +40,7 @@ use chainstate::stacks::{
 use core::FIRST_BURNCHAIN_CONSENSUS_HASH;
 use core::FIRST_STACKS_BLOCK_HASH;
 use monitoring::increment_stx_mempool_gc;
+use util::db::query_row_columns;
 use util::db::query_rows;
 use util::db::tx_begin_immediate;
 use util::db::tx_busy_handler;
@@ -47,7 +48,7 @@ use util::db::u64_to_sql;
 use util::db::Error as db_error;
 use util::db::FromColumn;
 use util::db::{query_row, Error};
-use util::db::{sql_pragma, DBConn, DBTx, FromRow};
+use util::db::{sql_pragma, table_exists, DBConn, DBSchema, DBTx, FromRow, SchemaMigration};
 use util::get_epoch_time_secs;
 use vm::types::PrincipalData;
 
@@ -62,6 +63,146 @@ pub const MAXIMUM_MEMPOOL_TX_CHAINING: u64 = 25;
 pub struct MemPoolAdmitter {
     cur_block: BlockHeaderHash,
     cur_consensus_hash: ConsensusHash,
+    policy: MemPoolAdmissionPolicy,
+}
+
+/// Node-local rules for admitting transactions into the mempool, applied on top of the
+/// validity checks in `StacksChainState::will_admit_mempool_tx()`.  These are not consensus
+/// rules -- a block may contain transactions that this node's mempool would have rejected.
+/// A limit of `None` (or a minimum fee rate of 0) means the rule does not apply.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemPoolAdmissionPolicy {
+    /// maximum number of pending transactions from a single origin address
+    pub max_pending_txs_per_origin: Option<u64>,
+    /// minimum fee rates, in microSTX per byte, by payload type
+    pub min_fee_rate_token_transfer: u64,
+    pub min_fee_rate_contract_call: u64,
+    pub min_fee_rate_smart_contract: u64,
+    /// maximum size of a smart contract's code body, in bytes
+    pub max_contract_size: Option<u64>,
+    /// maximum distance between a transaction's origin nonce and the origin's next nonce
+    pub max_nonce_gap: Option<u64>,
+    /// maximum number of transactions the mempool holds before evicting the cheapest ones
+    pub max_mempool_txs: Option<u64>,
+    /// maximum total size, in bytes, of the transactions the mempool holds before evicting the
+    /// cheapest ones
+    pub max_mempool_bytes: Option<u64>,
+}
+
+impl std::default::Default for MemPoolAdmissionPolicy {
+    fn default() -> MemPoolAdmissionPolicy {
+        MemPoolAdmissionPolicy {
+            max_pending_txs_per_origin: None,
+            min_fee_rate_token_transfer: 0,
+            min_fee_rate_contract_call: 0,
+            min_fee_rate_smart_contract: 0,
+            max_contract_size: None,
+            max_nonce_gap: None,
+            max_mempool_txs: None,
+            max_mempool_bytes: None,
+        }
+    }
+}
+
+impl MemPoolAdmissionPolicy {
+    /// Check the rules that depend only on the transaction itself
+    pub fn check_tx(&self, tx: &StacksTransaction, tx_size: u64) -> Result<(), MemPoolRejection> {
+        if let (TransactionPayload::SmartContract(ref contract), Some(max_size)) =
+            (&tx.payload, self.max_contract_size)
+        {
+            let actual_size = contract.code_body.len() as u64;
+            if actual_size > max_size {
+                return Err(MemPoolRejection::ContractTooLarge {
+                    max_size,
+                    actual_size,
+                });
+            }
+        }
+
+        let min_fee_rate = match tx.payload {
+            TransactionPayload::TokenTransfer(..) => self.min_fee_rate_token_transfer,
+            TransactionPayload::ContractCall(..) => self.min_fee_rate_contract_call,
+            TransactionPayload::SmartContract(..) => self.min_fee_rate_smart_contract,
+            TransactionPayload::PoisonMicroblock(..) | TransactionPayload::Coinbase(..) => 0,
+        };
+        let fee = tx.get_tx_fee();
+        if tx_size > 0 && fee / tx_size < min_fee_rate {
+            return Err(MemPoolRejection::FeeRateTooLow {
+                payload_type: tx.payload.name(),
+                actual: fee / tx_size,
+                expected: min_fee_rate,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Check the rules that depend on what's already pending in the mempool.
+    /// `account_nonce` is the origin account's next nonce in the chain state.  Mempool
+    /// transactions with lower nonces have already been mined, so they don't count as pending.
+    pub fn check_pending(
+        &self,
+        mempool_conn: &DBConn,
+        tx: &StacksTransaction,
+        account_nonce: u64,
+    ) -> Result<(), MemPoolRejection> {
+        let origin_address = tx.origin_address();
+        let origin_nonce = tx.get_origin_nonce();
+
+        if let Some(max_pending) = self.max_pending_txs_per_origin {
+            // replacing a pending transaction doesn't add to the count
+            let is_replacement = MemPoolDB::get_tx_metadata_by_address(
+                mempool_conn,
+                true,
+                &origin_address,
+                origin_nonce,
+            )?
+            .is_some();
+            if !is_replacement
+                && MemPoolDB::get_num_pending_txs_for_origin(
+                    mempool_conn,
+                    &origin_address,
+                    account_nonce,
+                )? >= max_pending
+            {
+                return Err(MemPoolRejection::TooManyPendingTransactions {
+                    max_pending,
+                    principal: origin_address.into(),
+                });
+            }
+        }
+
+        if let Some(max_gap) = self.max_nonce_gap {
+            // only pending transactions that could be mined right now move the expected nonce, so
+            // a gapped transaction can't be used as a stepping stone for the next one
+            let expected_nonce = MemPoolDB::get_next_contiguous_nonce_for_origin(
+                mempool_conn,
+                &origin_address,
+                account_nonce,
+            )?;
+            if origin_nonce > expected_nonce.saturating_add(max_gap) {
+                return Err(MemPoolRejection::NonceGapTooLarge {
+                    max_gap,
+                    expected_nonce,
+                    actual_nonce: origin_nonce,
+                    principal: origin_address.into(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Is a mempool with this many transactions, totaling this many bytes, over budget?
+    pub fn is_over_budget(&self, num_txs: u64, num_bytes: u64) -> bool {
+        self.max_mempool_txs
+            .map(|max_txs| num_txs > max_txs)
+            .unwrap_or(false)
+            || self
+                .max_mempool_bytes
+                .map(|max_bytes| num_bytes > max_bytes)
+                .unwrap_or(false)
+    }
 }
 
 enum MemPoolWalkResult {
@@ -75,9 +216,14 @@ impl MemPoolAdmitter {
         MemPoolAdmitter {
             cur_block,
             cur_consensus_hash,
+            policy: MemPoolAdmissionPolicy::default(),
         }
     }
 
+    pub fn set_policy(&mut self, policy: MemPoolAdmissionPolicy) {
+        self.policy = policy;
+    }
+
     pub fn set_block(&mut self, cur_block: &BlockHeaderHash, cur_consensus_hash: ConsensusHash) {
         self.cur_consensus_hash = cur_consensus_hash.clone();
         self.cur_block = cur_block.clone();
@@ -85,10 +231,25 @@ impl MemPoolAdmitter {
     pub fn will_admit_tx(
         &mut self,
         chainstate: &mut StacksChainState,
+        mempool_conn: &DBConn,
         tx: &StacksTransaction,
         tx_size: u64,
     ) -> Result<(), MemPoolRejection> {
-        chainstate.will_admit_mempool_tx(&self.cur_consensus_hash, &self.cur_block, tx, tx_size)
+        chainstate.will_admit_mempool_tx(&self.cur_consensus_hash, &self.cur_block, tx, tx_size)?;
+        self.policy.check_tx(tx, tx_size)?;
+
+        let account_nonce = if self.policy.max_nonce_gap.is_some()
+            || self.policy.max_pending_txs_per_origin.is_some()
+        {
+            chainstate.get_mempool_account_nonce(
+                &self.cur_consensus_hash,
+                &self.cur_block,
+                &tx.origin_address().into(),
+            )?
+        } else {
+            0
+        };
+        self.policy.check_pending(mempool_conn, tx, account_nonce)
     }
 }
 
@@ -97,6 +258,7 @@ pub enum MemPoolDropReason {
     REPLACE_BY_FEE,
     STALE_COLLECT,
     TOO_EXPENSIVE,
+    MEMPOOL_FULL,
 }
 
 impl std::fmt::Display for MemPoolDropReason {
@@ -106,6 +268,7 @@ impl std::fmt::Display for MemPoolDropReason {
             MemPoolDropReason::TOO_EXPENSIVE => write!(f, "TooExpensive"),
             MemPoolDropReason::REPLACE_ACROSS_FORK => write!(f, "ReplaceAcrossFork"),
             MemPoolDropReason::REPLACE_BY_FEE => write!(f, "ReplaceByFee"),
+            MemPoolDropReason::MEMPOOL_FULL => write!(f, "MempoolFull"),
         }
     }
 }
@@ -225,8 +388,34 @@ const MEMPOOL_INITIAL_SCHEMA: &'static [&'static str] = &[
     "CREATE INDEX by_origin ON mempool(origin_address, origin_nonce);",
     "CREATE INDEX by_timestamp ON mempool(accept_time);",
     "CREATE INDEX by_chaintip ON mempool(consensus_hash,block_header_hash);",
+    "CREATE TABLE db_config(version TEXT NOT NULL);",
+];
+
+// Eviction walks the mempool from the lowest fee rate up, so the fee rate is stored and indexed
+// instead of being computed for every row on each lookup.  Fee rates are reals, since integer
+// division would round most of them to 0.
+const MEMPOOL_FEE_RATE_SCHEMA: &'static [&'static str] = &[
+    "ALTER TABLE mempool ADD COLUMN fee_rate REAL NOT NULL DEFAULT 0;",
+    "UPDATE mempool SET fee_rate = CAST(tx_fee AS REAL) / length;",
+    "CREATE INDEX by_fee_rate ON mempool(fee_rate ASC, accept_time DESC);",
 ];
 
+pub const MEMPOOL_SCHEMA: DBSchema = DBSchema {
+    name: "mempool",
+    migrations: &[
+        SchemaMigration {
+            version: 1,
+            description: "initial schema",
+            statements: MEMPOOL_INITIAL_SCHEMA,
+        },
+        SchemaMigration {
+            version: 2,
+            description: "index transactions by fee rate",
+            statements: MEMPOOL_FEE_RATE_SCHEMA,
+        },
+    ],
+};
+
 pub struct MemPoolDB {
     db: DBConn,
     path: String,
@@ -308,14 +497,32 @@ impl MemPoolDB {
 
         let tx = tx_begin_immediate(conn)?;
 
-        for cmd in MEMPOOL_INITIAL_SCHEMA {
-            tx.execute_batch(cmd).map_err(db_error::SqliteError)?;
-        }
+        MEMPOOL_SCHEMA.instantiate(&tx)?;
+
+        tx.execute(
+            "INSERT INTO db_config (version) VALUES (?1)",
+            &[&MEMPOOL_SCHEMA.latest_version().to_string()],
+        )
+        .map_err(db_error::SqliteError)?;
 
         tx.commit().map_err(db_error::SqliteError)?;
         Ok(())
     }
 
+    /// Mempools created before schema versioning have no db_config table.  Their schema is
+    /// version 1.
+    fn baseline_mempool_db(conn: &mut DBConn) -> Result<(), db_error> {
+        let tx = tx_begin_immediate(conn)?;
+        if !table_exists(&tx, "db_config")? {
+            tx.execute_batch("CREATE TABLE db_config(version TEXT NOT NULL);")
+                .map_err(db_error::SqliteError)?;
+            tx.execute("INSERT INTO db_config (version) VALUES ('1')", NO_PARAMS)
+                .map_err(db_error::SqliteError)?;
+        }
+        tx.commit().map_err(db_error::SqliteError)?;
+        Ok(())
+    }
+
     pub fn db_path(chainstate_root_path: &str) -> Result<String, db_error> {
         let mut path = PathBuf::from(chainstate_root_path);
 
@@ -368,6 +575,9 @@ impl MemPoolDB {
         if create_flag {
             // instantiate!
             MemPoolDB::instantiate_mempool_db(&mut conn)?;
+        } else {
+            MemPoolDB::baseline_mempool_db(&mut conn)?;
+            MEMPOOL_SCHEMA.migrate(&mut conn, true)?;
         }
 
         Ok(MemPoolDB {
@@ -438,6 +648,11 @@ impl MemPoolDB {
         &self.db
     }
 
+    /// Set the node-local admission policy applied to submitted transactions
+    pub fn set_admission_policy(&mut self, policy: MemPoolAdmissionPolicy) {
+        self.admitter.set_policy(policy);
+    }
+
     pub fn tx_begin<'a>(&'a mut self) -> Result<MemPoolTx<'a>, db_error> {
         let tx = tx_begin_immediate(&mut self.db)?;
         Ok(MemPoolTx::new(tx, &mut self.admitter))
@@ -601,6 +816,27 @@ impl MemPoolDB {
         query_row(conn, &sql, args)
     }
 
+    /// Get a transaction's metadata, given its txid.
+    fn get_tx_metadata_by_txid(
+        conn: &DBConn,
+        txid: &Txid,
+    ) -> Result<Option<MemPoolTxMetadata>, db_error> {
+        let sql = "SELECT 
+                          txid,
+                          origin_address,
+                          origin_nonce,
+                          sponsor_address,
+                          sponsor_nonce,
+                          tx_fee,
+                          length,
+                          consensus_hash,
+                          block_header_hash,
+                          height,
+                          accept_time
+                          FROM mempool WHERE txid = ?1";
+        query_row(conn, sql, &[txid as &dyn ToSql])
+    }
+
     fn get_next_nonce_as_participant_type(
         conn: &DBConn,
         addr: &StacksAddress,
@@ -617,6 +853,39 @@ impl MemPoolDB {
         }
     }
 
+    /// How many transactions from this origin address are in the mempool and not yet mined,
+    /// given the origin account's next nonce?
+    fn get_num_pending_txs_for_origin(
+        conn: &DBConn,
+        addr: &StacksAddress,
+        account_nonce: u64,
+    ) -> Result<u64, db_error> {
+        let sql = "SELECT COUNT(*) FROM mempool WHERE origin_address = ?1 AND origin_nonce >= ?2";
+        let args: &[&dyn ToSql] = &[&addr.to_string(), &u64_to_sql(account_nonce)?];
+        conn.query_row_and_then(sql, args, |row| u64::from_row(row))
+    }
+
+    /// Get the origin nonce that follows the unbroken run of pending transactions from this
+    /// origin address that starts at the account's next nonce.
+    fn get_next_contiguous_nonce_for_origin(
+        conn: &DBConn,
+        addr: &StacksAddress,
+        account_nonce: u64,
+    ) -> Result<u64, db_error> {
+        let sql = "SELECT DISTINCT origin_nonce FROM mempool WHERE origin_address = ?1 AND origin_nonce >= ?2 ORDER BY origin_nonce";
+        let args: &[&dyn ToSql] = &[&addr.to_string(), &u64_to_sql(account_nonce)?];
+        let nonces = query_row_columns::<u64, _>(conn, sql, args, "origin_nonce")?;
+
+        let mut next_nonce = account_nonce;
+        for nonce in nonces.into_iter() {
+            if nonce != next_nonce {
+                break;
+            }
+            next_nonce += 1;
+        }
+        Ok(next_nonce)
+    }
+
     pub fn get_next_nonce_for_address(
         conn: &DBConn,
         address: &StacksAddress,
@@ -745,8 +1014,9 @@ impl MemPoolDB {
             block_header_hash,
             height,
             accept_time,
-            tx)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)";
+            tx,
+            fee_rate)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)";
 
         let args: &[&dyn ToSql] = &[
             &txid,
@@ -761,6 +1031,7 @@ impl MemPoolDB {
             &u64_to_sql(height)?,
             &u64_to_sql(get_epoch_time_secs())?,
             &tx_bytes,
+            &((tx_fee as f64) / (length as f64)),
         ];
 
         tx.execute(sql, args)
@@ -796,6 +1067,184 @@ impl MemPoolDB {
         Ok(())
     }
 
+    /// Get the number of transactions in the mempool, and their total size in bytes
+    pub fn get_size(conn: &DBConn) -> Result<(u64, u64), db_error> {
+        let sql = "SELECT COUNT(*), IFNULL(SUM(length), 0) FROM mempool";
+        query_row(conn, sql, NO_PARAMS).map(|row_opt| row_opt.unwrap_or((0, 0)))
+    }
+
+    /// Get the transaction with the lowest fee rate that isn't already in `mempool_evictions`, and
+    /// that wouldn't be replaced by an incoming transaction with the given origin and sponsor
+    /// nonces.  Of equal fee rates, the most recently accepted transaction is the lowest.
+    fn get_lowest_fee_rate_tx(
+        conn: &DBConn,
+        origin_address: &StacksAddress,
+        origin_nonce: u64,
+        sponsor_address: &StacksAddress,
+        sponsor_nonce: u64,
+    ) -> Result<Option<MemPoolTxMetadata>, db_error> {
+        let sql = "SELECT 
+                          txid,
+                          origin_address,
+                          origin_nonce,
+                          sponsor_address,
+                          sponsor_nonce,
+                          tx_fee,
+                          length,
+                          consensus_hash,
+                          block_header_hash,
+                          height,
+                          accept_time
+                          FROM mempool
+                          WHERE txid NOT IN (SELECT txid FROM mempool_evictions)
+                          AND NOT (origin_address = ?1 AND origin_nonce = ?2)
+                          AND NOT (sponsor_address = ?3 AND sponsor_nonce = ?4)
+                          ORDER BY fee_rate ASC, accept_time DESC
+                          LIMIT 1";
+        let args: &[&dyn ToSql] = &[
+            &origin_address.to_string(),
+            &u64_to_sql(origin_nonce)?,
+            &sponsor_address.to_string(),
+            &u64_to_sql(sponsor_nonce)?,
+        ];
+        query_row(conn, sql, args)
+    }
+
+    /// Find the lowest fee-rate transactions (and their nonce descendants, which can't be mined
+    /// without them) that must be evicted for an incoming transaction to fit within the
+    /// admission policy's budget.  Transactions that the incoming one replaces don't count
+    /// against the budget.  If the incoming transaction would itself be evicted, then
+    /// `MemPoolRejection::MemPoolFull` is returned.  Nothing is removed; see evict_txs().
+    fn find_evictions(
+        tx: &mut MemPoolTx,
+        tx_fee: u64,
+        length: u64,
+        origin_address: &StacksAddress,
+        origin_nonce: u64,
+        sponsor_address: &StacksAddress,
+        sponsor_nonce: u64,
+    ) -> Result<Vec<Txid>, MemPoolRejection> {
+        let policy = tx.admitter.policy.clone();
+        if policy.max_mempool_txs.is_none() && policy.max_mempool_bytes.is_none() {
+            return Ok(vec![]);
+        }
+
+        let (num_txs, num_bytes) = MemPoolDB::get_size(tx)?;
+        let replaced_args: &[&dyn ToSql] = &[
+            &origin_address.to_string(),
+            &u64_to_sql(origin_nonce)?,
+            &sponsor_address.to_string(),
+            &u64_to_sql(sponsor_nonce)?,
+        ];
+        let (num_replaced, bytes_replaced): (u64, u64) = query_row(
+            tx,
+            "SELECT COUNT(*), IFNULL(SUM(length), 0) FROM mempool WHERE (origin_address = ?1 AND origin_nonce = ?2) OR (sponsor_address = ?3 AND sponsor_nonce = ?4)",
+            replaced_args,
+        )?
+        .unwrap_or((0, 0));
+
+        let kept_txs = (num_txs + 1).saturating_sub(num_replaced);
+        let kept_bytes = (num_bytes + length).saturating_sub(bytes_replaced);
+        let (mut num_txs, mut num_bytes) = (kept_txs, kept_bytes);
+        if !policy.is_over_budget(num_txs, num_bytes) {
+            return Ok(vec![]);
+        }
+
+        // the chosen transactions are kept in a temporary table, so the next candidate can be
+        // found without reading the whole mempool
+        tx.execute_batch(
+            "CREATE TEMP TABLE IF NOT EXISTS mempool_evictions(txid TEXT PRIMARY KEY, length INTEGER NOT NULL); DELETE FROM mempool_evictions;",
+        )
+        .map_err(|e| MemPoolRejection::DBError(db_error::SqliteError(e)))?;
+
+        let mut evicted_txids = vec![];
+        while policy.is_over_budget(num_txs, num_bytes) {
+            let lowest = match MemPoolDB::get_lowest_fee_rate_tx(
+                tx,
+                origin_address,
+                origin_nonce,
+                sponsor_address,
+                sponsor_nonce,
+            )? {
+                Some(md) => md,
+                None => break,
+            };
+
+            // the incoming transaction is the most recently accepted, so it goes first on a tie.
+            // It also goes if it depends on the lowest one.
+            let pays_less = (tx_fee as u128) * (lowest.len as u128)
+                <= (lowest.tx_fee as u128) * (length as u128);
+            let depends_on_lowest =
+                lowest.origin_address == *origin_address && lowest.origin_nonce < origin_nonce;
+            if pays_less || depends_on_lowest {
+                debug!("Mempool is full, and the incoming tx pays too little to stay";
+                       "lowest_txid" => %lowest.txid,
+                       "lowest_fee" => lowest.tx_fee,
+                       "lowest_len" => lowest.len);
+                return Err(MemPoolRejection::MemPoolFull);
+            }
+
+            let sql = "SELECT txid FROM mempool
+                       WHERE origin_address = ?1 AND origin_nonce >= ?2
+                       AND txid NOT IN (SELECT txid FROM mempool_evictions)
+                       AND NOT (origin_address = ?3 AND origin_nonce = ?4)
+                       AND NOT (sponsor_address = ?5 AND sponsor_nonce = ?6)";
+            let args: &[&dyn ToSql] = &[
+                &lowest.origin_address.to_string(),
+                &u64_to_sql(lowest.origin_nonce)?,
+                &origin_address.to_string(),
+                &u64_to_sql(origin_nonce)?,
+                &sponsor_address.to_string(),
+                &u64_to_sql(sponsor_nonce)?,
+            ];
+            let mut txids: Vec<Txid> = query_rows(tx, sql, args)?;
+
+            let sql = "INSERT INTO mempool_evictions (txid, length) SELECT txid, length FROM mempool WHERE txid = ?1";
+            for txid in txids.iter() {
+                tx.execute(sql, &[txid as &dyn ToSql])
+                    .map_err(|e| MemPoolRejection::DBError(db_error::SqliteError(e)))?;
+            }
+            evicted_txids.append(&mut txids);
+
+            let (num_evicted, bytes_evicted): (u64, u64) = query_row(
+                tx,
+                "SELECT COUNT(*), IFNULL(SUM(length), 0) FROM mempool_evictions",
+                NO_PARAMS,
+            )?
+            .unwrap_or((0, 0));
+            num_txs = kept_txs.saturating_sub(num_evicted);
+            num_bytes = kept_bytes.saturating_sub(bytes_evicted);
+        }
+
+        Ok(evicted_txids)
+    }
+
+    /// Remove transactions chosen by find_evictions() from the mempool
+    fn evict_txs(
+        tx: &mut MemPoolTx,
+        evicted_txids: Vec<Txid>,
+        event_observer: Option<&dyn MemPoolEventDispatcher>,
+    ) -> Result<(), MemPoolRejection> {
+        if evicted_txids.is_empty() {
+            return Ok(());
+        }
+
+        let sql = "DELETE FROM mempool WHERE txid = ?1";
+        for txid in evicted_txids.iter() {
+            tx.execute(sql, &[txid as &dyn ToSql])
+                .map_err(|e| MemPoolRejection::DBError(db_error::SqliteError(e)))?;
+        }
+
+        debug!(
+            "Evicted {} transactions from the full mempool",
+            evicted_txids.len()
+        );
+        if let Some(event_observer) = event_observer {
+            event_observer.mempool_txs_dropped(evicted_txids, MemPoolDropReason::MEMPOOL_FULL);
+        }
+        Ok(())
+    }
+
     #[cfg(test)]
     pub fn clear_before_height(&mut self, min_height: u64) -> Result<(), db_error> {
         let mut tx = self.tx_begin()?;
@@ -889,9 +1338,22 @@ impl MemPoolDB {
             mempool_tx
                 .admitter
                 .set_block(&block_hash, (*consensus_hash).clone());
-            mempool_tx.admitter.will_admit_tx(chainstate, tx, len)?;
+            mempool_tx
+                .admitter
+                .will_admit_tx(chainstate, &mempool_tx.tx, tx, len)?;
         }
 
+        // make room first, so that nothing is reported as replaced if there is no room
+        let evicted_txids = MemPoolDB::find_evictions(
+            mempool_tx,
+            tx_fee,
+            len,
+            &origin_address,
+            origin_nonce,
+            &sponsor_address,
+            sponsor_nonce,
+        )?;
+
         MemPoolDB::try_add_tx(
             mempool_tx,
             chainstate,
@@ -908,6 +1370,8 @@ impl MemPoolDB {
             event_observer,
         )?;
 
+        MemPoolDB::evict_txs(mempool_tx, evicted_txids, event_observer)?;
+
         if let Err(e) = monitoring::mempool_accepted(&txid, &chainstate.root_path) {
             warn!("Failed to monitor TX receive: {:?}", e; "txid" => %txid);
         }
@@ -1022,7 +1486,7 @@ mod tests {
     use core::FIRST_BURNCHAIN_CONSENSUS_HASH;
     use core::FIRST_STACKS_BLOCK_HASH;
     use net::Error as NetError;
-    use util::db::{DBConn, FromRow};
+    use util::db::{DBConn, DBSchema, FromRow};
     use util::hash::Hash160;
     use util::secp256k1::MessageSignature;
     use util::{hash::hex_bytes, hash::to_hex, hash::*, log, secp256k1::*, strings::StacksString};
@@ -1046,7 +1510,19 @@ mod tests {
         chainstate::stacks::db::StacksHeaderInfo, util::vrf::VRFProof, vm::costs::ExecutionCost,
     };
 
-    use super::MemPoolDB;
+    use std::cell::RefCell;
+    use std::convert::TryFrom;
+
+    use burnchains::Txid;
+    use chainstate::stacks::miner::test::make_user_stacks_transfer;
+    use vm::types::StandardPrincipalData;
+
+    use super::{
+        MemPoolAdmissionPolicy, MemPoolDB, MemPoolDropReason, MemPoolEventDispatcher,
+        MEMPOOL_INITIAL_SCHEMA, MEMPOOL_SCHEMA,
+    };
+    use rusqlite::NO_PARAMS;
+    use util::db::tx_begin_immediate;
 
     const FOO_CONTRACT: &'static str = "(define-public (foo) (ok 1))
                                         (define-public (bar (x uint)) (ok x))";
@@ -1908,4 +2384,406 @@ mod tests {
         assert_eq!(tx_info.metadata.len, second_len);
         assert_eq!(tx_info.metadata.tx_fee, 124);
     }
+
+    #[test]
+    fn mempool_admission_policy_tx_checks() {
+        let txs = codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            &TransactionAnchorMode::Any,
+            &TransactionPostConditionMode::Allow,
+        );
+
+        let policy = MemPoolAdmissionPolicy {
+            min_fee_rate_contract_call: 10,
+            max_contract_size: Some(4),
+            ..MemPoolAdmissionPolicy::default()
+        };
+
+        for mut tx in txs.into_iter() {
+            let tx_size = tx.serialize_to_vec().len() as u64;
+            tx.set_tx_fee(tx_size * 9);
+
+            // the default policy only defers to the consensus rules
+            MemPoolAdmissionPolicy::default()
+                .check_tx(&tx, tx_size)
+                .unwrap();
+
+            match tx.payload {
+                TransactionPayload::SmartContract(ref contract) => {
+                    match policy.check_tx(&tx, tx_size) {
+                        Err(MemPoolRejection::ContractTooLarge {
+                            max_size,
+                            actual_size,
+                        }) => {
+                            assert_eq!(max_size, 4);
+                            assert_eq!(actual_size, contract.code_body.len() as u64);
+                        }
+                        x => panic!("Expected ContractTooLarge, got {:?}", &x),
+                    }
+                }
+                TransactionPayload::ContractCall(..) => {
+                    match policy.check_tx(&tx, tx_size) {
+                        Err(MemPoolRejection::FeeRateTooLow {
+                            payload_type,
+                            actual,
+                            expected,
+                        }) => {
+                            assert_eq!(payload_type, "ContractCall");
+                            assert_eq!(actual, 9);
+                            assert_eq!(expected, 10);
+                        }
+                        x => panic!("Expected FeeRateTooLow, got {:?}", &x),
+                    }
+
+                    tx.set_tx_fee(tx_size * 10);
+                    policy.check_tx(&tx, tx_size).unwrap();
+                }
+                _ => {
+                    policy.check_tx(&tx, tx_size).unwrap();
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn mempool_admission_policy_pending_checks() {
+        let mut chainstate = instantiate_chainstate_with_balances(
+            false,
+            0x80000000,
+            "mempool_admission_policy_pending_checks",
+            vec![],
+        );
+        let chainstate_path = chainstate_path("mempool_admission_policy_pending_checks");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        let mut txs = codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            &TransactionAnchorMode::Any,
+            &TransactionPostConditionMode::Allow,
+        );
+        let mut tx = txs.pop().unwrap();
+        let origin_address = tx.origin_address();
+
+        // the origin has 3 pending transactions, with nonces 0, 1, 2
+        let mut mempool_tx = mempool.tx_begin().unwrap();
+        for nonce in 0..3 {
+            tx.set_origin_nonce(nonce);
+            tx.set_tx_fee(123);
+            MemPoolDB::try_add_tx(
+                &mut mempool_tx,
+                &mut chainstate,
+                &FIRST_BURNCHAIN_CONSENSUS_HASH,
+                &FIRST_STACKS_BLOCK_HASH,
+                tx.txid(),
+                tx.serialize_to_vec(),
+                tx.get_tx_fee(),
+                0,
+                &origin_address,
+                nonce,
+                &origin_address,
+                nonce,
+                None,
+            )
+            .unwrap();
+        }
+        mempool_tx.commit().unwrap();
+
+        let policy = MemPoolAdmissionPolicy {
+            max_pending_txs_per_origin: Some(3),
+            max_nonce_gap: Some(2),
+            ..MemPoolAdmissionPolicy::default()
+        };
+
+        // replacing a pending transaction is fine
+        tx.set_origin_nonce(2);
+        policy.check_pending(mempool.conn(), &tx, 0).unwrap();
+
+        // a 4th pending transaction is not
+        tx.set_origin_nonce(3);
+        match policy.check_pending(mempool.conn(), &tx, 0) {
+            Err(MemPoolRejection::TooManyPendingTransactions {
+                max_pending,
+                principal,
+            }) => {
+                assert_eq!(max_pending, 3);
+                assert_eq!(principal, PrincipalData::from(origin_address.clone()));
+            }
+            x => panic!("Expected TooManyPendingTransactions, got {:?}", &x),
+        }
+
+        // ...unless some of the pending transactions have been mined
+        policy.check_pending(mempool.conn(), &tx, 1).unwrap();
+
+        // the next nonce after the pending transactions is 3, so nonce 6 leaves a gap of 3
+        let policy = MemPoolAdmissionPolicy {
+            max_nonce_gap: Some(2),
+            ..MemPoolAdmissionPolicy::default()
+        };
+        tx.set_origin_nonce(5);
+        policy.check_pending(mempool.conn(), &tx, 0).unwrap();
+
+        tx.set_origin_nonce(6);
+        match policy.check_pending(mempool.conn(), &tx, 0) {
+            Err(MemPoolRejection::NonceGapTooLarge {
+                max_gap,
+                expected_nonce,
+                actual_nonce,
+                ..
+            }) => {
+                assert_eq!(max_gap, 2);
+                assert_eq!(expected_nonce, 3);
+                assert_eq!(actual_nonce, 6);
+            }
+            x => panic!("Expected NonceGapTooLarge, got {:?}", &x),
+        }
+
+        // the chain state's nonce counts too
+        policy.check_pending(mempool.conn(), &tx, 4).unwrap();
+    }
+
+    #[test]
+    fn mempool_admission_policy_nonce_gap_stepping() {
+        let mut chainstate = instantiate_chainstate_with_balances(
+            false,
+            0x80000000,
+            "mempool_admission_policy_nonce_gap_stepping",
+            vec![],
+        );
+        let chainstate_path = chainstate_path("mempool_admission_policy_nonce_gap_stepping");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+
+        let mut txs = codec_all_transactions(
+            &TransactionVersion::Testnet,
+            0x80000000,
+            &TransactionAnchorMode::Any,
+            &TransactionPostConditionMode::Allow,
+        );
+        let mut tx = txs.pop().unwrap();
+        let origin_address = tx.origin_address();
+
+        let policy = MemPoolAdmissionPolicy {
+            max_nonce_gap: Some(2),
+            ..MemPoolAdmissionPolicy::default()
+        };
+
+        // nonce 2 is within the gap of the account's next nonce, 0
+        tx.set_origin_nonce(2);
+        tx.set_tx_fee(123);
+        policy.check_pending(mempool.conn(), &tx, 0).unwrap();
+
+        let mut mempool_tx = mempool.tx_begin().unwrap();
+        MemPoolDB::try_add_tx(
+            &mut mempool_tx,
+            &mut chainstate,
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+            tx.txid(),
+            tx.serialize_to_vec(),
+            tx.get_tx_fee(),
+            0,
+            &origin_address,
+            2,
+            &origin_address,
+            2,
+            None,
+        )
+        .unwrap();
+        mempool_tx.commit().unwrap();
+
+        // but it can't be mined yet, so it doesn't extend the gap to nonces 5 or 8
+        for nonce in [5, 8].iter() {
+            tx.set_origin_nonce(*nonce);
+            match policy.check_pending(mempool.conn(), &tx, 0) {
+                Err(MemPoolRejection::NonceGapTooLarge {
+                    expected_nonce,
+                    actual_nonce,
+                    ..
+                }) => {
+                    assert_eq!(expected_nonce, 0);
+                    assert_eq!(actual_nonce, *nonce);
+                }
+                x => panic!("Expected NonceGapTooLarge, got {:?}", &x),
+            }
+        }
+
+        // once nonces 0 and 1 are mined, the run starting at 2 counts again
+        tx.set_origin_nonce(5);
+        policy.check_pending(mempool.conn(), &tx, 2).unwrap();
+    }
+
+    struct DropRecorder {
+        dropped: RefCell<Vec<(Vec<Txid>, String)>>,
+    }
+
+    impl MemPoolEventDispatcher for DropRecorder {
+        fn mempool_txs_dropped(&self, txids: Vec<Txid>, reason: MemPoolDropReason) {
+            self.dropped.borrow_mut().push((txids, reason.to_string()));
+        }
+    }
+
+    #[test]
+    fn mempool_evicts_lowest_fee_rate_when_full() {
+        let keys: Vec<StacksPrivateKey> = (0..6).map(|_| StacksPrivateKey::new()).collect();
+        let addrs: Vec<StacksAddress> = keys
+            .iter()
+            .map(|key| {
+                StacksAddress::from_public_keys(
+                    C32_ADDRESS_VERSION_TESTNET_SINGLESIG,
+                    &AddressHashMode::SerializeP2PKH,
+                    1,
+                    &vec![StacksPublicKey::from_private(key)],
+                )
+                .unwrap()
+            })
+            .collect();
+        let mut chainstate = instantiate_chainstate_with_balances(
+            false,
+            0x80000000,
+            "mempool_evicts_lowest_fee_rate_when_full",
+            addrs
+                .iter()
+                .map(|addr| (addr.clone(), 10_000_000))
+                .collect(),
+        );
+        let chainstate_path = chainstate_path("mempool_evicts_lowest_fee_rate_when_full");
+        let mut mempool = MemPoolDB::open(false, 0x80000000, &chainstate_path).unwrap();
+        mempool.set_admission_policy(MemPoolAdmissionPolicy {
+            max_mempool_txs: Some(3),
+            ..MemPoolAdmissionPolicy::default()
+        });
+
+        let recipient = PrincipalData::from(addrs[5].clone());
+        let recorder = DropRecorder {
+            dropped: RefCell::new(vec![]),
+        };
+
+        // (sender, nonce, fee)
+        let specs: Vec<(usize, u64, u64)> = vec![
+            (0, 0, 100_000),
+            (0, 1, 1_000_000),
+            (1, 0, 200_000),
+            (2, 0, 300_000),
+        ];
+        let mut txids = vec![];
+        for (sender, nonce, fee) in specs.into_iter() {
+            let tx = make_user_stacks_transfer(&keys[sender], nonce, fee, &recipient, 1);
+            mempool
+                .submit(
+                    &mut chainstate,
+                    &FIRST_BURNCHAIN_CONSENSUS_HASH,
+                    &FIRST_STACKS_BLOCK_HASH,
+                    &tx,
+                    Some(&recorder),
+                )
+                .unwrap();
+            txids.push(tx.txid());
+        }
+
+        // the 4th transaction pushed out the cheapest one, along with the higher-nonce
+        // transaction from the same origin that depends on it.
+        assert_eq!(MemPoolDB::get_size(mempool.conn()).unwrap().0, 2);
+        assert!(!MemPoolDB::db_has_tx(mempool.conn(), &txids[0]).unwrap());
+        assert!(!MemPoolDB::db_has_tx(mempool.conn(), &txids[1]).unwrap());
+        assert!(MemPoolDB::db_has_tx(mempool.conn(), &txids[2]).unwrap());
+        assert!(MemPoolDB::db_has_tx(mempool.conn(), &txids[3]).unwrap());
+        assert_eq!(
+            recorder.dropped.borrow_mut().split_off(0),
+            vec![(vec![txids[0], txids[1]], "MempoolFull".to_string())]
+        );
+
+        // fill it back up
+        let tx = make_user_stacks_transfer(&keys[3], 0, 400_000, &recipient, 1);
+        mempool
+            .submit(
+                &mut chainstate,
+                &FIRST_BURNCHAIN_CONSENSUS_HASH,
+                &FIRST_STACKS_BLOCK_HASH,
+                &tx,
+                Some(&recorder),
+            )
+            .unwrap();
+        assert_eq!(MemPoolDB::get_size(mempool.conn()).unwrap().0, 3);
+        assert!(recorder.dropped.borrow().is_empty());
+
+        // a transaction that pays less than everything else is turned away
+        let tx = make_user_stacks_transfer(&keys[4], 0, 200, &recipient, 1);
+        match mempool.submit(
+            &mut chainstate,
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+            &tx,
+            Some(&recorder),
+        ) {
+            Err(MemPoolRejection::MemPoolFull) => {}
+            x => panic!("Expected MemPoolFull, got {:?}", &x),
+        }
+        assert_eq!(MemPoolDB::get_size(mempool.conn()).unwrap().0, 3);
+        assert!(recorder.dropped.borrow().is_empty());
+
+        // a replacement that pays a higher fee, but is too big to fit, is turned away without
+        // reporting the transaction it would have replaced as dropped
+        let (_, num_bytes) = MemPoolDB::get_size(mempool.conn()).unwrap();
+        mempool.set_admission_policy(MemPoolAdmissionPolicy {
+            max_mempool_bytes: Some(num_bytes),
+            ..MemPoolAdmissionPolicy::default()
+        });
+        let contract_recipient = PrincipalData::Contract(QualifiedContractIdentifier::new(
+            StandardPrincipalData::from(addrs[5].clone()),
+            ContractName::try_from("a-contract-with-a-rather-long-name").unwrap(),
+        ));
+        let tx = make_user_stacks_transfer(&keys[1], 0, 200_001, &contract_recipient, 1);
+        match mempool.submit(
+            &mut chainstate,
+            &FIRST_BURNCHAIN_CONSENSUS_HASH,
+            &FIRST_STACKS_BLOCK_HASH,
+            &tx,
+            Some(&recorder),
+        ) {
+            Err(MemPoolRejection::MemPoolFull) => {}
+            x => panic!("Expected MemPoolFull, got {:?}", &x),
+        }
+        assert!(MemPoolDB::db_has_tx(mempool.conn(), &txids[2]).unwrap());
+        assert!(recorder.dropped.borrow().is_empty());
+    }
+
+    #[test]
+    fn mempool_fee_rate_migration() {
+        let mut conn = DBConn::open_in_memory().unwrap();
+        {
+            let tx = tx_begin_immediate(&mut conn).unwrap();
+            for stmt in MEMPOOL_INITIAL_SCHEMA.iter() {
+                tx.execute_batch(stmt).unwrap();
+            }
+            tx.execute("INSERT INTO db_config (version) VALUES ('1')", NO_PARAMS)
+                .unwrap();
+            tx.execute(
+                "INSERT INTO mempool (txid, origin_address, origin_nonce, sponsor_address, sponsor_nonce, tx_fee, length, consensus_hash, block_header_hash, height, accept_time, tx) VALUES ('00', 'a', 0, 'a', 0, 300, 200, '', '', 0, 0, x'00')",
+                NO_PARAMS,
+            )
+            .unwrap();
+            tx.commit().unwrap();
+        }
+
+        MEMPOOL_SCHEMA.migrate(&mut conn, true).unwrap();
+        assert_eq!(DBSchema::get_version(&conn).unwrap(), 2);
+
+        // existing transactions get their fee rates
+        let fee_rate: f64 = conn
+            .query_row("SELECT fee_rate FROM mempool", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(fee_rate, 1.5);
+
+        // the lowest fee rate is found without sorting the mempool
+        let plan: Vec<String> = conn
+            .prepare("EXPLAIN QUERY PLAN SELECT txid FROM mempool ORDER BY fee_rate ASC, accept_time DESC LIMIT 1")
+            .unwrap()
+            .query_map(NO_PARAMS, |row| row.get(3))
+            .unwrap()
+            .map(|detail| detail.unwrap())
+            .collect();
+        assert!(plan.iter().any(|detail| detail.contains("by_fee_rate")));
+        assert!(!plan.iter().any(|detail| detail.contains("TEMP B-TREE")));
+    }
 }
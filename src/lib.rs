@@ -44,6 +44,7 @@ extern crate url;
 #[macro_use(o, slog_log, slog_trace, slog_debug, slog_info, slog_warn, slog_error)]
 extern crate slog;
 extern crate chrono;
+extern crate libflate;
 #[cfg(feature = "slog_json")]
 extern crate slog_json;
 extern crate slog_term;
@@ -60,6 +61,11 @@ extern crate serde_json;
 #[macro_use]
 extern crate assert_json_diff;
 
+// lets the checked-in bindings in the codegen tests refer to this crate by name, as
+// generated code does
+#[cfg(test)]
+extern crate self as blockstack_lib;
+
 #[cfg(feature = "monitoring_prom")]
 #[macro_use]
 pub extern crate prometheus;
@@ -93,6 +99,12 @@ pub mod deps;
 
 pub mod clarity;
 
+/// Rust bindings generated from contract interfaces
+pub mod codegen;
+
+/// A language server for Clarity
+pub mod lsp;
+
 pub mod monitoring;
 pub mod types;
 
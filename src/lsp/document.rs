@@ -0,0 +1,248 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::ptr;
+
+use vm::analysis::ContractAnalysis;
+use vm::ast::build_ast_with_diagnostics;
+use vm::diagnostic::Diagnostic;
+use vm::representations::{ClarityName, Span, SymbolicExpression};
+use vm::types::{FunctionType, PrincipalData, QualifiedContractIdentifier, Value};
+use vm::DefineFunctions;
+
+use super::vm_state::VmState;
+
+/// An open Clarity source file, along with everything the parser and the analysis passes
+/// learned about it the last time it changed.
+pub struct Document {
+    pub contract_identifier: QualifiedContractIdentifier,
+    pub source: String,
+    /// Whatever top-level expressions could be parsed, even if the document has errors.
+    pub expressions: Vec<SymbolicExpression>,
+    /// Only present if the document parsed without errors.
+    pub analysis: Option<ContractAnalysis>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Document {
+    pub fn new(
+        contract_identifier: QualifiedContractIdentifier,
+        source: String,
+        vm_state: &mut VmState,
+    ) -> Document {
+        let (mut ast, mut diagnostics, success) =
+            build_ast_with_diagnostics(&contract_identifier, &source, &mut ());
+
+        let mut analysis = None;
+        if success {
+            match vm_state.analyze(&contract_identifier, &mut ast.expressions) {
+                Ok((contract_analysis, mut analysis_diagnostics)) => {
+                    diagnostics.append(&mut analysis_diagnostics);
+                    analysis = Some(contract_analysis);
+                }
+                Err(e) => {
+                    warn!("Failed to analyze {}: {}", &contract_identifier, &e);
+                }
+            }
+        }
+
+        Document {
+            contract_identifier,
+            source,
+            expressions: ast.expressions,
+            analysis,
+            diagnostics,
+        }
+    }
+
+    /// Get the nested expressions that contain the given (1-indexed) line and column, from the
+    /// top-level expression down to the innermost one.
+    pub fn expressions_at(&self, line: u32, column: u32) -> Vec<&SymbolicExpression> {
+        let mut path = vec![];
+        let mut candidates: &[SymbolicExpression] = &self.expressions;
+        while let Some(expr) = candidates
+            .iter()
+            .find(|expr| span_contains(&expr.span, line, column))
+        {
+            path.push(expr);
+            match expr.match_list() {
+                Some(list) => candidates = list,
+                None => break,
+            }
+        }
+        path
+    }
+
+    /// Get the name and kind of every top-level definition in this document.
+    pub fn definitions(&self) -> Vec<(&ClarityName, DefineFunctions)> {
+        self.expressions
+            .iter()
+            .filter_map(|expr| get_defined_name(expr))
+            .filter_map(|(define_type, name_expr)| {
+                name_expr.match_atom().map(|name| (name, define_type))
+            })
+            .collect()
+    }
+
+    /// If the (1-indexed) position is on the contract or function name of a `contract-call?`,
+    /// get the contract identifier and (if applicable) the function name.
+    pub fn contract_call_at(
+        &self,
+        line: u32,
+        column: u32,
+    ) -> Option<(QualifiedContractIdentifier, Option<&ClarityName>)> {
+        let path = self.expressions_at(line, column);
+        if path.len() < 2 {
+            return None;
+        }
+        let target = path[path.len() - 1];
+        let list = path[path.len() - 2].match_list()?;
+        if list.len() < 3 || list[0].match_atom().map(|x| x.as_str()) != Some("contract-call?") {
+            return None;
+        }
+
+        let contract_identifier = match list[1].match_literal_value() {
+            Some(Value::Principal(PrincipalData::Contract(contract_identifier))) => {
+                contract_identifier.clone()
+            }
+            _ => return None,
+        };
+
+        if ptr::eq(target, &list[1]) {
+            Some((contract_identifier, None))
+        } else if ptr::eq(target, &list[2]) {
+            Some((contract_identifier, list[2].match_atom()))
+        } else {
+            None
+        }
+    }
+
+    /// Describe the expression at the given (1-indexed) position, using the type information
+    /// from the analysis if it is available.
+    pub fn describe_at(&self, line: u32, column: u32) -> Option<String> {
+        let path = self.expressions_at(line, column);
+        let target = *path.last()?;
+        let analysis = self.analysis.as_ref();
+
+        if let Some(name) = target.match_atom() {
+            if let Some(analysis) = analysis {
+                if let Some(description) = describe_definition(analysis, name) {
+                    return Some(description);
+                }
+            }
+        }
+
+        let type_map = analysis.and_then(|analysis| analysis.type_map.as_ref())?;
+        let type_signature = type_map.get_type(target)?;
+        match target.match_atom() {
+            Some(name) => Some(format!("{}: {}", name, type_signature)),
+            None => Some(format!("{}", type_signature)),
+        }
+    }
+}
+
+/// If `expr` is a top-level definition, get its kind and the atom that names what it defines.
+pub fn get_defined_name(
+    expr: &SymbolicExpression,
+) -> Option<(DefineFunctions, &SymbolicExpression)> {
+    let list = expr.match_list()?;
+    let define_type = DefineFunctions::lookup_by_name(list.get(0)?.match_atom()?)?;
+    let name_expr = match define_type {
+        DefineFunctions::PublicFunction
+        | DefineFunctions::PrivateFunction
+        | DefineFunctions::ReadOnlyFunction => list.get(1)?.match_list()?.get(0)?,
+        DefineFunctions::ImplTrait => return None,
+        _ => list.get(1)?,
+    };
+    Some((define_type, name_expr))
+}
+
+/// Find the atom naming the top-level definition of `name`, if there is one.
+pub fn find_definition<'a>(
+    expressions: &'a [SymbolicExpression],
+    name: &str,
+) -> Option<&'a SymbolicExpression> {
+    expressions
+        .iter()
+        .filter_map(|expr| get_defined_name(expr))
+        .map(|(_, name_expr)| name_expr)
+        .find(|name_expr| name_expr.match_atom().map(|x| x.as_str()) == Some(name))
+}
+
+fn span_contains(span: &Span, line: u32, column: u32) -> bool {
+    if span.start_line == 0 {
+        return false;
+    }
+    let end = if span.end_line == 0 {
+        (span.start_line, span.start_column)
+    } else {
+        (span.end_line, span.end_column)
+    };
+    (span.start_line, span.start_column) <= (line, column) && (line, column) <= end
+}
+
+/// Describe a function, map, variable or token defined by the contract.
+fn describe_definition(analysis: &ContractAnalysis, name: &ClarityName) -> Option<String> {
+    if let Some(function_type) = analysis.public_function_types.get(name) {
+        return Some(describe_function("define-public", name, function_type));
+    }
+    if let Some(function_type) = analysis.read_only_function_types.get(name) {
+        return Some(describe_function("define-read-only", name, function_type));
+    }
+    if let Some(function_type) = analysis.private_function_types.get(name) {
+        return Some(describe_function("define-private", name, function_type));
+    }
+    if let Some((key_type, value_type)) = analysis.map_types.get(name) {
+        return Some(format!("(define-map {} {} {})", name, key_type, value_type));
+    }
+    if let Some(var_type) = analysis.persisted_variable_types.get(name) {
+        return Some(format!("(define-data-var {} {})", name, var_type));
+    }
+    if let Some(var_type) = analysis.variable_types.get(name) {
+        return Some(format!("(define-constant {}): {}", name, var_type));
+    }
+    if analysis.fungible_tokens.contains(name) {
+        return Some(format!("(define-fungible-token {})", name));
+    }
+    if let Some(asset_type) = analysis.non_fungible_tokens.get(name) {
+        return Some(format!(
+            "(define-non-fungible-token {} {})",
+            name, asset_type
+        ));
+    }
+    None
+}
+
+fn describe_function(define: &str, name: &ClarityName, function_type: &FunctionType) -> String {
+    match function_type {
+        FunctionType::Fixed(function) => {
+            let args: Vec<String> = function
+                .args
+                .iter()
+                .map(|arg| format!("({} {})", arg.name, arg.signature))
+                .collect();
+            format!(
+                "({} ({}{}{})): {}",
+                define,
+                name,
+                if args.len() > 0 { " " } else { "" },
+                args.join(" "),
+                function.returns
+            )
+        }
+        _ => format!("({} {}): {:?}", define, name, function_type),
+    }
+}
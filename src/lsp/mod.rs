@@ -0,0 +1,488 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A Language Server Protocol server for Clarity.
+//!
+//! Documents are checked with the error-recovering parser and analysis passes on every change,
+//! and the results are used to answer hover, go-to-definition and completion requests.  The
+//! server speaks JSON-RPC over stdin/stdout, and only supports full document synchronization.
+
+pub mod document;
+pub mod vm_state;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::io::{BufRead, Read, Write};
+use std::path::PathBuf;
+
+use serde_json::Value as JsonValue;
+use url::Url;
+
+use vm::ast::build_ast;
+use vm::diagnostic::{Diagnostic, Level};
+use vm::docs::{make_all_api_reference, ReferenceAPIs};
+use vm::representations::{ContractName, Span};
+use vm::types::{QualifiedContractIdentifier, StandardPrincipalData};
+use vm::DefineFunctions;
+
+use self::document::{find_definition, Document};
+use self::vm_state::VmState;
+
+/// JSON-RPC error codes used by the server
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_REQUEST: i64 = -32600;
+
+/// LSP `CompletionItemKind`s
+const COMPLETION_KIND_FUNCTION: u64 = 3;
+const COMPLETION_KIND_VARIABLE: u64 = 6;
+const COMPLETION_KIND_KEYWORD: u64 = 14;
+
+/// LSP `DiagnosticSeverity::Error`
+const DIAGNOSTIC_SEVERITY_ERROR: u64 = 1;
+const DIAGNOSTIC_SEVERITY_WARNING: u64 = 2;
+
+lazy_static! {
+    static ref API_REFERENCE: ReferenceAPIs = make_all_api_reference();
+}
+
+pub struct LanguageServer {
+    vm_state: VmState,
+    /// Issuer of contracts whose file name isn't a fully-qualified contract identifier.
+    deployer: StandardPrincipalData,
+    /// Where the sources of deployed contracts get written, so editors can open them.
+    sources_dir: PathBuf,
+    documents: HashMap<String, Document>,
+    shutdown_requested: bool,
+}
+
+impl LanguageServer {
+    pub fn new(
+        vm_state: VmState,
+        deployer: StandardPrincipalData,
+        sources_dir: PathBuf,
+    ) -> LanguageServer {
+        LanguageServer {
+            vm_state,
+            deployer,
+            sources_dir,
+            documents: HashMap::new(),
+            shutdown_requested: false,
+        }
+    }
+
+    /// Serve requests from `input` until the client sends `exit` or closes the stream.  Returns
+    /// the process exit code.
+    pub fn run<R: BufRead, W: Write>(&mut self, input: &mut R, output: &mut W) -> i32 {
+        loop {
+            let message = match read_message(input) {
+                Ok(Some(message)) => message,
+                Ok(None) => return 1,
+                Err(e) => {
+                    warn!("Failed to read LSP message: {:?}", &e);
+                    return 1;
+                }
+            };
+
+            if message["method"] == "exit" {
+                return if self.shutdown_requested { 0 } else { 1 };
+            }
+
+            for reply in self.handle_message(&message) {
+                if let Err(e) = write_message(output, &reply) {
+                    warn!("Failed to write LSP message: {:?}", &e);
+                    return 1;
+                }
+            }
+        }
+    }
+
+    /// Handle a single JSON-RPC request or notification, and get back the responses and
+    /// notifications to send to the client.
+    pub fn handle_message(&mut self, message: &JsonValue) -> Vec<JsonValue> {
+        let method = match message["method"].as_str() {
+            Some(method) => method,
+            None => {
+                // a response to a request we never made
+                return vec![];
+            }
+        };
+        let params = &message["params"];
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": { "name": "clarity-lsp" },
+            })),
+            "shutdown" => {
+                self.shutdown_requested = true;
+                Ok(JsonValue::Null)
+            }
+            "textDocument/didOpen" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+                return self.update_document(uri, text);
+            }
+            "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str());
+                return match text {
+                    Some(text) => self.update_document(uri, text),
+                    None => vec![],
+                };
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                self.documents.remove(uri);
+                return vec![json!({
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": { "uri": uri, "diagnostics": [] },
+                })];
+            }
+            "textDocument/hover" => self.with_position(params, |server, uri, line, column| {
+                server.hover(uri, line, column)
+            }),
+            "textDocument/definition" => self.with_position(params, |server, uri, line, column| {
+                server.definition(uri, line, column)
+            }),
+            "textDocument/completion" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                Ok(self.completion(uri))
+            }
+            _ => Err((METHOD_NOT_FOUND, format!("Unsupported method '{}'", method))),
+        };
+
+        match message.get("id") {
+            Some(id) => match result {
+                Ok(result) => vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })],
+                Err((code, message)) => vec![json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": code, "message": message },
+                })],
+            },
+            // notification -- no response
+            None => vec![],
+        }
+    }
+
+    /// Extract the document URI and position from a `TextDocumentPositionParams`, and convert
+    /// the position into the 1-indexed line and column that Clarity spans use.
+    fn with_position<F>(&mut self, params: &JsonValue, f: F) -> Result<JsonValue, (i64, String)>
+    where
+        F: FnOnce(&mut LanguageServer, &str, u32, u32) -> Option<JsonValue>,
+    {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .ok_or((INVALID_REQUEST, "Missing textDocument.uri".to_string()))?;
+        let line = params["position"]["line"]
+            .as_u64()
+            .ok_or((INVALID_REQUEST, "Missing position.line".to_string()))?;
+        let character = params["position"]["character"]
+            .as_u64()
+            .ok_or((INVALID_REQUEST, "Missing position.character".to_string()))?;
+        let line = u32::try_from(line + 1).unwrap_or(u32::max_value());
+        let column = u32::try_from(character + 1).unwrap_or(u32::max_value());
+        Ok(f(self, uri, line, column).unwrap_or(JsonValue::Null))
+    }
+
+    /// Re-check a document after it was opened or changed, and get the diagnostics to publish.
+    fn update_document(&mut self, uri: &str, text: &str) -> Vec<JsonValue> {
+        let contract_identifier = self.contract_identifier_for(uri);
+        let document = Document::new(contract_identifier, text.to_string(), &mut self.vm_state);
+        let diagnostics: Vec<JsonValue> = document
+            .diagnostics
+            .iter()
+            .map(diagnostic_to_json)
+            .collect();
+        self.documents.insert(uri.to_string(), document);
+
+        vec![json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        })]
+    }
+
+    /// Documents named after a fully-qualified contract identifier (like the deployed contract
+    /// sources we write out) are checked as that contract.  Otherwise, the file name is taken as
+    /// the contract name, and the contract is deployed by `self.deployer`.
+    fn contract_identifier_for(&self, uri: &str) -> QualifiedContractIdentifier {
+        let stem = Url::parse(uri)
+            .ok()
+            .and_then(|url| url.to_file_path().ok())
+            .and_then(|path| {
+                path.file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .map(|stem| stem.to_string())
+            });
+
+        let stem = match stem {
+            Some(stem) => stem,
+            None => return QualifiedContractIdentifier::transient(),
+        };
+
+        if let Ok(contract_identifier) = QualifiedContractIdentifier::parse(&stem) {
+            return contract_identifier;
+        }
+        match ContractName::try_from(stem) {
+            Ok(name) => QualifiedContractIdentifier::new(self.deployer.clone(), name),
+            Err(_) => QualifiedContractIdentifier::transient(),
+        }
+    }
+
+    fn hover(&mut self, uri: &str, line: u32, column: u32) -> Option<JsonValue> {
+        let document = self.documents.get(uri)?;
+        let path = document.expressions_at(line, column);
+        let target = *path.last()?;
+
+        let contents = match target.match_atom().and_then(|name| native_docs(name)) {
+            Some(docs) => docs,
+            None => format!("```clarity\n{}\n```", document.describe_at(line, column)?),
+        };
+
+        Some(json!({
+            "contents": { "kind": "markdown", "value": contents },
+            "range": span_to_range(&target.span),
+        }))
+    }
+
+    fn definition(&mut self, uri: &str, line: u32, column: u32) -> Option<JsonValue> {
+        let document = self.documents.get(uri)?;
+
+        if let Some((contract_identifier, function_name)) = document.contract_call_at(line, column)
+        {
+            let function_name = function_name.map(|name| name.to_string());
+            return self.deployed_definition(&contract_identifier, function_name);
+        }
+
+        let path = document.expressions_at(line, column);
+        let name = path.last()?.match_atom()?;
+        let definition = find_definition(&document.expressions, name)?;
+        Some(json!({
+            "uri": uri,
+            "range": span_to_range(&definition.span),
+        }))
+    }
+
+    /// Find a definition in a contract that is already deployed in the VM state.  Its source is
+    /// written out to `self.sources_dir` so that the editor has something to open.
+    fn deployed_definition(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+        function_name: Option<String>,
+    ) -> Option<JsonValue> {
+        let src = self.vm_state.get_contract_src(contract_identifier)?;
+        let path = self.write_contract_src(contract_identifier, &src)?;
+        let uri = Url::from_file_path(&path).ok()?;
+
+        let range = match function_name {
+            Some(function_name) => {
+                let ast = build_ast(contract_identifier, &src, &mut ()).ok()?;
+                let definition = find_definition(&ast.expressions, &function_name)?;
+                span_to_range(&definition.span)
+            }
+            None => json!({
+                "start": { "line": 0, "character": 0 },
+                "end": { "line": 0, "character": 0 },
+            }),
+        };
+
+        Some(json!({ "uri": uri.to_string(), "range": range }))
+    }
+
+    fn write_contract_src(
+        &self,
+        contract_identifier: &QualifiedContractIdentifier,
+        src: &str,
+    ) -> Option<PathBuf> {
+        let path = self
+            .sources_dir
+            .join(format!("{}.clar", contract_identifier));
+        if let Err(e) = fs::create_dir_all(&self.sources_dir) {
+            warn!("Failed to create {:?}: {:?}", &self.sources_dir, &e);
+            return None;
+        }
+        if let Err(e) = fs::write(&path, src) {
+            warn!("Failed to write {:?}: {:?}", &path, &e);
+            return None;
+        }
+        Some(path)
+    }
+
+    /// Offer every native function, define form and keyword, as well as everything the
+    /// document itself defines.
+    fn completion(&self, uri: &str) -> JsonValue {
+        let mut items = vec![];
+        for function in API_REFERENCE.functions.iter() {
+            items.push(json!({
+                "label": called_name(&function.name),
+                "kind": COMPLETION_KIND_FUNCTION,
+                "detail": function.signature,
+                "documentation": function.description,
+            }));
+        }
+        for keyword in API_REFERENCE.keywords.iter() {
+            items.push(json!({
+                "label": keyword.name,
+                "kind": COMPLETION_KIND_KEYWORD,
+                "detail": keyword.output_type,
+                "documentation": keyword.description,
+            }));
+        }
+
+        if let Some(document) = self.documents.get(uri) {
+            for (name, define_type) in document.definitions() {
+                let kind = match define_type {
+                    DefineFunctions::PublicFunction
+                    | DefineFunctions::PrivateFunction
+                    | DefineFunctions::ReadOnlyFunction => COMPLETION_KIND_FUNCTION,
+                    _ => COMPLETION_KIND_VARIABLE,
+                };
+                items.push(json!({
+                    "label": name.as_str(),
+                    "kind": kind,
+                    "detail": define_type.get_name_str(),
+                }));
+            }
+        }
+
+        JsonValue::Array(items)
+    }
+}
+
+/// Markdown documentation for a native function, define form or keyword.
+fn native_docs(name: &str) -> Option<String> {
+    if let Some(function) = API_REFERENCE
+        .functions
+        .iter()
+        .find(|f| called_name(&f.name) == name)
+    {
+        return Some(format!(
+            "```clarity\n{}\n```\n{}",
+            &function.signature, &function.description
+        ));
+    }
+    if let Some(keyword) = API_REFERENCE.keywords.iter().find(|k| k.name == name) {
+        return Some(format!(
+            "```clarity\n{}: {}\n```\n{}",
+            keyword.name, keyword.output_type, keyword.description
+        ));
+    }
+    None
+}
+
+/// The API reference names some native functions along with a description, e.g. `+ (add)`.
+/// This is the name they're called by.
+fn called_name(api_name: &str) -> &str {
+    api_name.split(' ').next().unwrap_or(api_name)
+}
+
+/// Convert a Clarity span (1-indexed, with an inclusive end) to an LSP range (0-indexed, with an
+/// exclusive end).  Spans with an unknown end cover a single character.
+fn span_to_range(span: &Span) -> JsonValue {
+    let start_line = span.start_line.saturating_sub(1);
+    let start_character = span.start_column.saturating_sub(1);
+    let (end_line, end_character) = if span.end_line == 0 {
+        (start_line, start_character + 1)
+    } else {
+        (span.end_line - 1, span.end_column)
+    };
+    json!({
+        "start": { "line": start_line, "character": start_character },
+        "end": { "line": end_line, "character": end_character },
+    })
+}
+
+fn diagnostic_to_json(diagnostic: &Diagnostic) -> JsonValue {
+    let range = match diagnostic.spans.first() {
+        Some(span) => span_to_range(span),
+        None => span_to_range(&Span {
+            start_line: 1,
+            start_column: 1,
+            end_line: 0,
+            end_column: 0,
+        }),
+    };
+    let message = match diagnostic.suggestion {
+        Some(ref suggestion) => format!("{}\n{}", &diagnostic.message, suggestion),
+        None => diagnostic.message.clone(),
+    };
+    let severity = match diagnostic.level {
+        Level::Error => DIAGNOSTIC_SEVERITY_ERROR,
+        Level::Warning => DIAGNOSTIC_SEVERITY_WARNING,
+    };
+    json!({
+        "range": range,
+        "severity": severity,
+        "source": "clarity",
+        "message": message,
+    })
+}
+
+/// Read one `Content-Length`-framed JSON-RPC message.  Returns Ok(None) at end of input.
+pub fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<JsonValue>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            if content_length.is_some() {
+                break;
+            }
+            // stray blank line between messages
+            continue;
+        }
+        let mut parts = header.splitn(2, ':');
+        let name = parts.next().unwrap_or("").trim();
+        let value = parts.next().unwrap_or("").trim();
+        if name.eq_ignore_ascii_case("Content-Length") {
+            content_length = Some(value.parse::<usize>().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "Invalid Content-Length")
+            })?);
+        }
+    }
+
+    let mut body = vec![0u8; content_length.unwrap_or(0)];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Write one `Content-Length`-framed JSON-RPC message.
+pub fn write_message<W: Write>(output: &mut W, message: &JsonValue) -> io::Result<()> {
+    let body = serde_json::to_string(message)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+#[cfg(test)]
+mod tests;
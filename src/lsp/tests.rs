@@ -0,0 +1,240 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::io::Cursor;
+
+use rand::Rng;
+
+use super::*;
+
+const CONTRACT: &str = "(define-data-var counter int 0)
+(define-read-only (get-counter) (var-get counter))
+(define-public (increment) (begin (var-set counter (+ (get-counter) 1)) (ok true)))
+(define-read-only (pox-info) (contract-call? 'ST000000000000000000002AMW42H.pox get-pox-info))";
+
+fn make_server() -> LanguageServer {
+    let sources_dir = std::env::temp_dir().join(format!(
+        "clarity-lsp-test-{}",
+        rand::thread_rng().gen::<u32>()
+    ));
+    LanguageServer::new(
+        VmState::new_memory(false),
+        StandardPrincipalData::transient(),
+        sources_dir,
+    )
+}
+
+fn open(server: &mut LanguageServer, uri: &str, text: &str) -> JsonValue {
+    let mut replies = server.handle_message(&json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": {
+            "textDocument": { "uri": uri, "languageId": "clarity", "version": 1, "text": text }
+        }
+    }));
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0]["method"], "textDocument/publishDiagnostics");
+    replies.pop().unwrap()
+}
+
+/// Send a request at the position of the first occurrence of `needle` on `line` (0-indexed)
+fn request_at(
+    server: &mut LanguageServer,
+    method: &str,
+    uri: &str,
+    line: usize,
+    needle: &str,
+) -> JsonValue {
+    let character = CONTRACT.lines().nth(line).unwrap().find(needle).unwrap();
+    let mut replies = server.handle_message(&json!({
+        "jsonrpc": "2.0",
+        "id": 7,
+        "method": method,
+        "params": {
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character }
+        }
+    }));
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0]["id"], 7);
+    replies.pop().unwrap()["result"].take()
+}
+
+#[test]
+fn test_message_framing() {
+    let message = json!({ "jsonrpc": "2.0", "id": 1, "method": "shutdown" });
+    let mut buf = vec![];
+    write_message(&mut buf, &message).unwrap();
+    write_message(&mut buf, &message).unwrap();
+
+    let mut input = Cursor::new(buf);
+    assert_eq!(read_message(&mut input).unwrap(), Some(message.clone()));
+    assert_eq!(read_message(&mut input).unwrap(), Some(message));
+    assert_eq!(read_message(&mut input).unwrap(), None);
+}
+
+#[test]
+fn test_session() {
+    let mut server = make_server();
+
+    let mut buf = vec![];
+    for message in [
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+        json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ]
+    .iter()
+    {
+        write_message(&mut buf, message).unwrap();
+    }
+
+    let mut output = vec![];
+    let exit_code = server.run(&mut Cursor::new(buf), &mut output);
+    assert_eq!(exit_code, 0);
+
+    let mut output = Cursor::new(output);
+    let reply = read_message(&mut output).unwrap().unwrap();
+    assert_eq!(reply["id"], 1);
+    assert_eq!(reply["result"]["capabilities"]["hoverProvider"], true);
+    let reply = read_message(&mut output).unwrap().unwrap();
+    assert_eq!(reply["id"], 2);
+    assert_eq!(reply["result"], JsonValue::Null);
+    assert_eq!(read_message(&mut output).unwrap(), None);
+}
+
+#[test]
+fn test_diagnostics() {
+    let mut server = make_server();
+
+    let published = open(
+        &mut server,
+        "file:///tmp/broken.clar",
+        "(define-constant a (+ 1 2)\n(define-constant b 1)\n(define-constant c 0xzz)",
+    );
+    let diagnostics = published["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0]["range"]["start"]["line"], 0);
+    assert_eq!(diagnostics[1]["range"]["start"]["line"], 2);
+
+    let published = open(
+        &mut server,
+        "file:///tmp/mistyped.clar",
+        "(define-read-only (f) (+ 1 u1))\n(define-read-only (g) (- u1 true))",
+    );
+    let diagnostics = published["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[1]["range"]["start"]["line"], 1);
+
+    let published = open(&mut server, "file:///tmp/counter.clar", CONTRACT);
+    assert_eq!(published["params"]["diagnostics"], json!([]));
+}
+
+#[test]
+fn test_hover_and_definition() {
+    let mut server = make_server();
+    let uri = "file:///tmp/counter.clar";
+    open(&mut server, uri, CONTRACT);
+
+    // native function docs
+    let hover = request_at(&mut server, "textDocument/hover", uri, 2, "+ ");
+    assert!(hover["contents"]["value"]
+        .as_str()
+        .unwrap()
+        .contains("(+ i1 i2...)"));
+
+    // user-defined function signature
+    let hover = request_at(&mut server, "textDocument/hover", uri, 2, "get-counter");
+    assert!(hover["contents"]["value"]
+        .as_str()
+        .unwrap()
+        .contains("(define-read-only (get-counter)): int"));
+
+    // inferred type of an expression
+    let hover = request_at(&mut server, "textDocument/hover", uri, 2, "(ok true)");
+    assert!(hover["contents"]["value"]
+        .as_str()
+        .unwrap()
+        .contains("(response bool UnknownType)"));
+
+    // definition of a function
+    let definition = request_at(
+        &mut server,
+        "textDocument/definition",
+        uri,
+        2,
+        "get-counter",
+    );
+    assert_eq!(definition["uri"], uri);
+    assert_eq!(definition["range"]["start"]["line"], 1);
+    assert_eq!(definition["range"]["start"]["character"], 19);
+
+    // definition of a data var
+    let definition = request_at(&mut server, "textDocument/definition", uri, 2, "counter ");
+    assert_eq!(definition["range"]["start"]["line"], 0);
+    assert_eq!(definition["range"]["start"]["character"], 17);
+
+    // definition of a function in a deployed contract
+    let definition = request_at(
+        &mut server,
+        "textDocument/definition",
+        uri,
+        3,
+        "get-pox-info",
+    );
+    let target = Url::parse(definition["uri"].as_str().unwrap())
+        .unwrap()
+        .to_file_path()
+        .unwrap();
+    assert!(target.ends_with("ST000000000000000000002AMW42H.pox.clar"));
+    let src = fs::read_to_string(&target).unwrap();
+    let line = definition["range"]["start"]["line"].as_u64().unwrap() as usize;
+    assert!(src.lines().nth(line).unwrap().contains("get-pox-info"));
+
+    // nothing to find on a literal
+    let definition = request_at(&mut server, "textDocument/definition", uri, 0, "0)");
+    assert_eq!(definition, JsonValue::Null);
+}
+
+#[test]
+fn test_completion() {
+    let mut server = make_server();
+    let uri = "file:///tmp/counter.clar";
+    open(&mut server, uri, CONTRACT);
+
+    let mut replies = server.handle_message(&json!({
+        "jsonrpc": "2.0",
+        "id": 3,
+        "method": "textDocument/completion",
+        "params": {
+            "textDocument": { "uri": uri },
+            "position": { "line": 0, "character": 0 }
+        }
+    }));
+    let items = replies.pop().unwrap()["result"].take();
+    let labels: Vec<&str> = items
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["label"].as_str().unwrap())
+        .collect();
+
+    assert!(labels.contains(&"map-get?"));
+    assert!(labels.contains(&"define-public"));
+    assert!(labels.contains(&"block-height"));
+    assert!(labels.contains(&"get-counter"));
+    assert!(labels.contains(&"counter"));
+}
@@ -0,0 +1,120 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use clarity::{
+    at_chaintip, install_boot_code, run_analysis_with_diagnostics, CLIHeadersDB, ClarityStorage,
+};
+use clarity_vm::database::marf::MarfedKV;
+use clarity_vm::database::MemoryBackingStore;
+use vm::analysis::ContractAnalysis;
+use vm::database::{ClarityDatabase, NULL_BURN_STATE_DB};
+use vm::diagnostic::Diagnostic;
+use vm::representations::SymbolicExpression;
+use vm::types::QualifiedContractIdentifier;
+
+/// The Clarity VM state that open documents are checked against, and from which the sources of
+/// already-deployed contracts are loaded.
+pub enum VmState {
+    /// A throwaway in-memory state that only contains the boot contracts.
+    Memory {
+        header_db: CLIHeadersDB,
+        store: MemoryBackingStore,
+    },
+    /// A `clarity-cli` VM state database, read (but never written) at its chain tip.
+    Persisted { path: String },
+}
+
+impl VmState {
+    pub fn new_memory(mainnet: bool) -> VmState {
+        let header_db = CLIHeadersDB::new_memory(mainnet);
+        let mut store = MemoryBackingStore::new();
+        install_boot_code(&header_db, &mut store);
+        VmState::Memory { header_db, store }
+    }
+
+    /// Use the `clarity-cli` VM state at `path`, which must already exist.
+    pub fn open(path: &str) -> Result<VmState, String> {
+        CLIHeadersDB::resume(path)?;
+        MarfedKV::open(path, None).map_err(|e| format!("{:?}", &e))?;
+        Ok(VmState::Persisted {
+            path: path.to_string(),
+        })
+    }
+
+    /// Run the analysis passes over `expressions`, collecting every diagnostic.
+    pub fn analyze(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+        expressions: &mut [SymbolicExpression],
+    ) -> Result<(ContractAnalysis, Vec<Diagnostic>), String> {
+        match self {
+            VmState::Memory { header_db, store } => Ok(run_analysis_with_diagnostics(
+                contract_identifier,
+                expressions,
+                header_db,
+                store,
+            )),
+            VmState::Persisted { path } => {
+                let header_db = CLIHeadersDB::resume(path)?;
+                let marf_kv = MarfedKV::open(path, None).map_err(|e| format!("{:?}", &e))?;
+                Ok(at_chaintip(path, marf_kv, |mut marf| {
+                    let result = run_analysis_with_diagnostics(
+                        contract_identifier,
+                        expressions,
+                        &header_db,
+                        &mut marf,
+                    );
+                    (marf, result)
+                }))
+            }
+        }
+    }
+
+    /// Get the source code of a deployed contract, if it exists.
+    pub fn get_contract_src(
+        &mut self,
+        contract_identifier: &QualifiedContractIdentifier,
+    ) -> Option<String> {
+        match self {
+            VmState::Memory { header_db, store } => read_contract_src(
+                store.get_clarity_db(header_db, &NULL_BURN_STATE_DB),
+                contract_identifier,
+            ),
+            VmState::Persisted { path } => {
+                let header_db = CLIHeadersDB::resume(path).ok()?;
+                let marf_kv = MarfedKV::open(path, None).ok()?;
+                at_chaintip(path, marf_kv, |mut marf| {
+                    let src = read_contract_src(
+                        marf.get_clarity_db(&header_db, &NULL_BURN_STATE_DB),
+                        contract_identifier,
+                    );
+                    (marf, src)
+                })
+            }
+        }
+    }
+}
+
+/// Read a contract's source in a nested context, which is rolled back afterwards.
+fn read_contract_src(
+    mut clarity_db: ClarityDatabase,
+    contract_identifier: &QualifiedContractIdentifier,
+) -> Option<String> {
+    clarity_db.begin();
+    let src = clarity_db.get_contract_src(contract_identifier);
+    clarity_db.roll_back();
+    src
+}
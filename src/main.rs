@@ -29,6 +29,7 @@ extern crate slog;
 
 use std::io;
 use std::io::prelude::*;
+use std::net::SocketAddr;
 use std::process;
 use std::{collections::HashMap, env};
 use std::{convert::TryFrom, fs};
@@ -44,11 +45,13 @@ use blockstack_lib::chainstate::stacks::db::ChainStateBootData;
 use blockstack_lib::chainstate::stacks::index::marf::MarfConnection;
 use blockstack_lib::chainstate::stacks::index::marf::MARF;
 use blockstack_lib::chainstate::stacks::*;
+use blockstack_lib::clarity_vm::database::marf::MarfedKV;
 use blockstack_lib::codec::StacksMessageCodec;
 use blockstack_lib::types::chainstate::{BlockHeaderHash, BurnchainHeaderHash, PoxId};
 use blockstack_lib::types::chainstate::{StacksBlockHeader, StacksBlockId};
 use blockstack_lib::types::proof::ClarityMarfTrieId;
 use blockstack_lib::util::get_epoch_time_ms;
+use blockstack_lib::util::get_epoch_time_secs;
 use blockstack_lib::util::hash::{hex_bytes, to_hex};
 use blockstack_lib::util::log;
 use blockstack_lib::util::retry::LogReader;
@@ -60,11 +63,17 @@ use blockstack_lib::{
         stacks::db::{StacksChainState, StacksHeaderInfo},
     },
     core::MemPoolDB,
+    util::db::Error as db_error,
     util::{hash::Hash160, vrf::VRFProof},
     vm::costs::ExecutionCost,
 };
 use blockstack_lib::{
-    net::{db::LocalPeer, p2p::PeerNetwork, PeerAddress},
+    net::{
+        connection::ConnectionOptions,
+        db::{LocalPeer, PeerDB},
+        p2p::PeerNetwork,
+        PeerAddress,
+    },
     vm::representations::UrlString,
 };
 
@@ -585,6 +594,107 @@ simulating a miner.
         process::exit(0);
     }
 
+    if argv[1] == "peerdb-reputations" {
+        let half_life = match argv.len() {
+            3 => Some(ConnectionOptions::default().peer_reputation_half_life),
+            5 if argv[3] == "--half-life" => argv[4].parse::<u64>().ok(),
+            _ => None,
+        };
+        let half_life = match half_life {
+            Some(half_life) => half_life,
+            None => {
+                eprintln!(
+                    "Usage: {} peerdb-reputations PEER_DB_PATH [--half-life SECONDS]",
+                    argv[0]
+                );
+                eprintln!(
+                    "       Scores decay with the node's connection_options.peer_reputation_half_life (default {})",
+                    ConnectionOptions::default().peer_reputation_half_life
+                );
+                process::exit(1);
+            }
+        };
+
+        let peerdb = match PeerDB::open(&argv[2], false) {
+            Ok(peerdb) => peerdb,
+            Err(db_error::SchemaTooOld(found, expected)) => {
+                // reputations arrive with a schema migration, which a read-only open can't apply
+                eprintln!(
+                    "Peer DB has no reputation data yet: schema version {} must be migrated to version {}. Run the node once to migrate it.",
+                    found, expected
+                );
+                process::exit(0);
+            }
+            Err(e) => {
+                eprintln!("Failed to open peer DB: {}", e);
+                process::exit(1);
+            }
+        };
+        let reputations =
+            PeerDB::get_all_peer_reputations(peerdb.conn()).expect("Failed to read peer DB");
+
+        let now = get_epoch_time_secs();
+        println!("network_id\tpeer\tscore\tnum_bans\tbanned_until");
+        for reputation in reputations.iter() {
+            println!(
+                "{}\t{}\t{:.2}\t{}\t{}{}",
+                reputation.network_id,
+                reputation.addrbytes.to_socketaddr(reputation.port),
+                reputation.get_score(half_life, now),
+                reputation.num_bans,
+                reputation.banned_until,
+                if reputation.is_banned(now) {
+                    " (banned)"
+                } else {
+                    ""
+                }
+            );
+        }
+        process::exit(0);
+    }
+
+    if argv[1] == "peerdb-clear-reputations" {
+        if argv.len() < 3 {
+            eprintln!(
+                "Usage: {} peerdb-clear-reputations PEER_DB_PATH [IP:PORT]",
+                argv[0]
+            );
+            eprintln!(
+                "       Clears every peer's reputation (and lifts its ban) if IP:PORT is not given"
+            );
+            process::exit(1);
+        }
+        let mut peerdb = PeerDB::open(&argv[2], true).expect("Failed to open peer DB");
+        let mut tx = peerdb
+            .tx_begin()
+            .expect("Failed to begin peer DB transaction");
+        let num_cleared = if argv.len() > 3 {
+            let addr: SocketAddr = argv[3].parse().expect("Invalid IP:PORT");
+            let addrbytes = PeerAddress::from_socketaddr(&addr);
+            let reputations =
+                PeerDB::get_all_peer_reputations(&tx).expect("Failed to read peer DB");
+            let mut num_cleared = 0;
+            for reputation in reputations.iter() {
+                if reputation.addrbytes == addrbytes && reputation.port == addr.port() {
+                    PeerDB::clear_peer_reputation(
+                        &mut tx,
+                        reputation.network_id,
+                        &reputation.addrbytes,
+                        reputation.port,
+                    )
+                    .expect("Failed to clear peer reputation");
+                    num_cleared += 1;
+                }
+            }
+            num_cleared
+        } else {
+            PeerDB::clear_all_peer_reputations(&mut tx).expect("Failed to clear peer reputations")
+        };
+        tx.commit().expect("Failed to commit peer DB transaction");
+        println!("Cleared {} peer reputation(s)", num_cleared);
+        process::exit(0);
+    }
+
     if argv[1] == "exec_program" {
         if argv.len() < 3 {
             eprintln!("Usage: {} exec_program [program-file.clar]", argv[0]);
@@ -617,6 +727,149 @@ simulating a miner.
         return;
     }
 
+    if argv[1] == "export-snapshot" {
+        if argv.len() < 6 {
+            eprintln!(
+                "Usage: {} export-snapshot CHAINSTATE_PATH SORTITION_DB_PATH INDEX_BLOCK_HASH SNAPSHOT_PATH",
+                argv[0]
+            );
+            process::exit(1);
+        }
+        let index_block_hash =
+            StacksBlockId::from_hex(&argv[4]).expect("Failed to parse index block hash");
+        match chainstate::snapshot::export_snapshot(&argv[2], &argv[3], &index_block_hash, &argv[5])
+        {
+            Ok(manifest) => {
+                println!(
+                    "Exported snapshot of block {} (Stacks height {}, burn height {}) to {}",
+                    &manifest.index_block_hash,
+                    manifest.stacks_block_height,
+                    manifest.burn_block_height,
+                    &argv[5]
+                );
+            }
+            Err(e) => {
+                eprintln!("Failed to export snapshot: {}", e);
+                process::exit(1);
+            }
+        }
+        process::exit(0);
+    }
+
+    if argv[1] == "import-snapshot" {
+        if argv.len() < 6 {
+            eprintln!(
+                "Usage: {} import-snapshot SNAPSHOT_PATH TRUSTED_INDEX_BLOCK_HASH CHAINSTATE_PATH SORTITION_DB_PATH",
+                argv[0]
+            );
+            eprintln!(
+                "       Verifies the snapshot against the trusted block before installing it"
+            );
+            eprintln!("       CHAINSTATE_PATH and SORTITION_DB_PATH must be missing or empty");
+            process::exit(1);
+        }
+        let trusted_block =
+            StacksBlockId::from_hex(&argv[3]).expect("Failed to parse index block hash");
+        match chainstate::snapshot::import_snapshot(&argv[2], &trusted_block, &argv[4], &argv[5]) {
+            Ok(manifest) => {
+                println!(
+                    "Imported snapshot of block {} (Stacks height {}, burn height {})",
+                    &manifest.index_block_hash,
+                    manifest.stacks_block_height,
+                    manifest.burn_block_height
+                );
+            }
+            Err(e) => {
+                eprintln!("Failed to import snapshot: {}", e);
+                process::exit(1);
+            }
+        }
+        process::exit(0);
+    }
+
+    if argv[1] == "prune-clarity-state" {
+        if argv.len() < 5 {
+            eprintln!(
+                "Usage: {} prune-clarity-state CHAINSTATE_PATH INDEX_BLOCK_HASH DEPTH",
+                argv[0]
+            );
+            eprintln!(
+                "       Discards Clarity state more than DEPTH blocks behind INDEX_BLOCK_HASH. Stop the node first."
+            );
+            process::exit(1);
+        }
+        let clarity_path = format!("{}/vm/clarity", &argv[2]);
+        if fs::metadata(&format!("{}/marf.sqlite", &clarity_path)).is_err() {
+            eprintln!("No Clarity MARF found under {}", &argv[2]);
+            process::exit(1);
+        }
+        let tip = StacksBlockId::from_hex(&argv[3]).expect("Failed to parse index block hash");
+        let depth: u32 = argv[4].parse().expect("Failed to parse DEPTH");
+
+        let mut marf_kv = MarfedKV::open(&clarity_path, None).expect("Failed to open Clarity MARF");
+        match marf_kv.prune(&tip, depth, usize::MAX) {
+            Ok((stats, pruned_values)) => {
+                marf_kv
+                    .get_marf()
+                    .sqlite_conn()
+                    .execute_batch("VACUUM")
+                    .expect("Failed to vacuum Clarity MARF");
+                println!(
+                    "Pruned {} tries ({} retained) behind block {} at height {}: {} -> {} trie bytes, {} side-store values removed",
+                    stats.pruned_tries,
+                    stats.retained_tries,
+                    &tip,
+                    stats.tip_height,
+                    stats.bytes_before,
+                    stats.bytes_after,
+                    pruned_values
+                );
+            }
+            Err(e) => {
+                eprintln!("Failed to prune Clarity state: {}", e);
+                process::exit(1);
+            }
+        }
+        process::exit(0);
+    }
+
+    if argv[1] == "drop-orphaned-blocks" {
+        if argv.len() < 3 {
+            eprintln!(
+                "Usage: {} drop-orphaned-blocks CHAINSTATE_PATH [--testnet]",
+                argv[0]
+            );
+            eprintln!(
+                "       Discards stored orphaned blocks and compacts the block packs. Stop the node first."
+            );
+            process::exit(1);
+        }
+        let (mainnet, chain_id) = if argv.len() > 3 && argv[3] == "--testnet" {
+            (false, core::CHAIN_ID_TESTNET)
+        } else {
+            (true, core::CHAIN_ID_MAINNET)
+        };
+        let (chain_state, _) = StacksChainState::open(mainnet, chain_id, &argv[2])
+            .expect("Failed to open stacks chain state");
+
+        match chain_state.drop_orphaned_blocks() {
+            Ok((dropped, compaction)) => {
+                println!(
+                    "Dropped {} orphaned blocks; rewrote {} block packs: {} -> {} bytes",
+                    dropped,
+                    compaction.packs_rewritten,
+                    compaction.bytes_before,
+                    compaction.bytes_after
+                );
+            }
+            Err(e) => {
+                eprintln!("Failed to drop orphaned blocks: {:?}", e);
+                process::exit(1);
+            }
+        }
+        process::exit(0);
+    }
+
     if argv[1] == "get-ancestors" {
         let path = &argv[2];
         let tip = BlockHeaderHash::from_hex(&argv[3]).unwrap();
@@ -27,7 +27,10 @@ use std::fs;
 use util::db::tx_begin_immediate;
 use util::db::DBConn;
 use util::db::Error as db_error;
-use util::db::{query_count, query_int, query_row, query_rows, u64_to_sql, FromColumn, FromRow};
+use util::db::{
+    query_count, query_int, query_row, query_rows, u64_to_sql, DBSchema, FromColumn, FromRow,
+    SchemaMigration,
+};
 
 use util;
 use util::hash::{bin_bytes, hex_bytes, to_bin, to_hex, Hash160};
@@ -44,8 +47,6 @@ use burnchains::Txid;
 
 use super::{AtlasConfig, Attachment, AttachmentInstance};
 
-pub const ATLASDB_VERSION: &'static str = "1";
-
 const ATLASDB_INITIAL_SCHEMA: &'static [&'static str] = &[
     r#"
     CREATE TABLE attachments(
@@ -71,6 +72,15 @@ const ATLASDB_INITIAL_SCHEMA: &'static [&'static str] = &[
     "CREATE TABLE db_config(version TEXT NOT NULL);",
 ];
 
+pub const ATLASDB_SCHEMA: DBSchema = DBSchema {
+    name: "atlas",
+    migrations: &[SchemaMigration {
+        version: 1,
+        description: "initial schema",
+        statements: ATLASDB_INITIAL_SCHEMA,
+    }],
+};
+
 impl FromRow<Attachment> for Attachment {
     fn from_row<'a>(row: &'a Row) -> Result<Attachment, db_error> {
         let content: Vec<u8> = row.get_unwrap("content");
@@ -124,13 +134,11 @@ impl AtlasDB {
 
         let tx = self.tx_begin()?;
 
-        for row_text in ATLASDB_INITIAL_SCHEMA {
-            tx.execute_batch(row_text).map_err(db_error::SqliteError)?;
-        }
+        ATLASDB_SCHEMA.instantiate(&tx)?;
 
         tx.execute(
             "INSERT INTO db_config (version) VALUES (?1)",
-            &[&ATLASDB_VERSION],
+            &[&ATLASDB_SCHEMA.latest_version().to_string()],
         )
         .map_err(db_error::SqliteError)?;
 
@@ -207,6 +215,8 @@ impl AtlasDB {
         };
         if create_flag {
             db.instantiate()?;
+        } else {
+            ATLASDB_SCHEMA.migrate(&mut db.conn, readwrite)?;
         }
         Ok(db)
     }
@@ -138,6 +138,9 @@ pub struct NeighborStats {
     pub microblocks_push_rx_counts: VecDeque<(u64, u64)>, // (count, num bytes)
     pub transaction_push_rx_counts: VecDeque<(u64, u64)>, // (count, num bytes)
     pub relayed_messages: HashMap<NeighborAddress, RelayStats>,
+    // errors and NACKs already charged to the peer's persistent reputation
+    pub reputation_msgs_err: u64,
+    pub reputation_nacks_rx: u64,
 }
 
 impl NeighborStats {
@@ -161,6 +164,8 @@ impl NeighborStats {
             microblocks_push_rx_counts: VecDeque::new(),
             transaction_push_rx_counts: VecDeque::new(),
             relayed_messages: HashMap::new(),
+            reputation_msgs_err: 0,
+            reputation_nacks_rx: 0,
         }
     }
 
@@ -282,6 +287,19 @@ impl NeighborStats {
     pub fn get_message_recv_count(&self, msg_id: StacksMessageID) -> u64 {
         *(self.msg_rx_counts.get(&msg_id).unwrap_or(&0))
     }
+
+    /// Get the reputation penalty for the errors and NACKs this peer has sent us since the last
+    /// time this method was called.
+    pub fn take_reputation_penalty(&mut self) -> f64 {
+        let nacks_rx = self.get_message_recv_count(StacksMessageID::Nack);
+        let penalty = (self.msgs_err.saturating_sub(self.reputation_msgs_err) as f64)
+            * PEER_PENALTY_INVALID_MESSAGE
+            + (nacks_rx.saturating_sub(self.reputation_nacks_rx) as f64) * PEER_PENALTY_NACK;
+
+        self.reputation_msgs_err = self.msgs_err;
+        self.reputation_nacks_rx = nacks_rx;
+        penalty
+    }
 }
 
 /// P2P ongoing conversation with another Stacks peer
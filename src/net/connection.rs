@@ -58,6 +58,7 @@ use net::neighbors::{
 use vm::{costs::ExecutionCost, types::BOUND_VALUE_SERIALIZATION_HEX};
 
 use chainstate::burn::ConsensusHash;
+use core::mempool::MemPoolAdmissionPolicy;
 
 use util::get_epoch_time_secs;
 use util::hash::to_hex;
@@ -374,6 +375,11 @@ pub struct ConnectionOptions {
     pub max_buffered_microblocks_available: u64,
     pub max_buffered_blocks: u64,
     pub max_buffered_microblocks: u64,
+    pub mempool_admission_policy: MemPoolAdmissionPolicy,
+    pub peer_reputation_ban_threshold: f64,
+    pub peer_reputation_half_life: u64,
+    pub peer_reputation_min_ban_duration: u64,
+    pub peer_reputation_max_ban_duration: u64,
 
     // fault injection
     pub disable_neighbor_walk: bool,
@@ -456,6 +462,11 @@ impl std::default::Default for ConnectionOptions {
             max_buffered_microblocks_available: 1,
             max_buffered_blocks: 1,
             max_buffered_microblocks: 10,
+            mempool_admission_policy: MemPoolAdmissionPolicy::default(), // only consensus checks by default
+            peer_reputation_ban_threshold: 100.0, // ban a peer once its misbehavior score reaches this
+            peer_reputation_half_life: 3600,      // a peer's misbehavior score halves every hour
+            peer_reputation_min_ban_duration: 3600, // first reputation ban lasts an hour...
+            peer_reputation_max_ban_duration: 7 * 86400, // ...and repeat bans double, up to a week
 
             // no faults on by default
             disable_neighbor_walk: false,
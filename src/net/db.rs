@@ -14,6 +14,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::cmp;
 use std::fmt;
 
 use rusqlite::types::ToSql;
@@ -28,7 +29,9 @@ use std::fs;
 use util::db::tx_begin_immediate;
 use util::db::DBConn;
 use util::db::Error as db_error;
-use util::db::{query_count, query_row, query_rows, u64_to_sql, FromColumn, FromRow};
+use util::db::{
+    query_count, query_row, query_rows, u64_to_sql, DBSchema, FromColumn, FromRow, SchemaMigration,
+};
 
 use util;
 use util::hash::{bin_bytes, hex_bytes, to_bin, to_hex, Hash160, Sha256Sum, Sha512Trunc256Sum};
@@ -61,8 +64,6 @@ use core::NETWORK_P2P_PORT;
 
 use util::strings::UrlString;
 
-pub const PEERDB_VERSION: &'static str = "1";
-
 const NUM_SLOTS: usize = 8;
 
 impl PeerAddress {
@@ -250,6 +251,105 @@ impl FromRow<ASEntry4> for ASEntry4 {
     }
 }
 
+/// A peer's persistent reputation.  Misbehavior adds to the peer's score, and the score decays
+/// exponentially over time.  Once the score reaches a threshold, the peer is banned for a while,
+/// and the ban gets longer each time it happens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerReputation {
+    pub network_id: u32,
+    pub addrbytes: PeerAddress,
+    pub port: u16,
+    /// accumulated penalty score, as of `last_update`
+    pub score: f64,
+    /// when `score` was last updated
+    pub last_update: u64,
+    /// how many times this peer has been banned for its score
+    pub num_bans: u64,
+    /// when the peer's current (or most recent) ban expires
+    pub banned_until: u64,
+}
+
+impl PeerReputation {
+    pub fn new(network_id: u32, addrbytes: &PeerAddress, port: u16, now: u64) -> PeerReputation {
+        PeerReputation {
+            network_id: network_id,
+            addrbytes: addrbytes.clone(),
+            port: port,
+            score: 0.0,
+            last_update: now,
+            num_bans: 0,
+            banned_until: 0,
+        }
+    }
+
+    /// What's the score at time `now`, given that it halves every `half_life` seconds?
+    pub fn get_score(&self, half_life: u64, now: u64) -> f64 {
+        if half_life == 0 {
+            return self.score;
+        }
+        let elapsed = now.saturating_sub(self.last_update) as f64;
+        self.score * (0.5f64).powf(elapsed / (half_life as f64))
+    }
+
+    /// Decay the score to `now`, and add the given penalty to it
+    pub fn add_penalty(&mut self, penalty: f64, half_life: u64, now: u64) {
+        self.score = self.get_score(half_life, now) + penalty;
+        self.last_update = now;
+    }
+
+    /// If the score has reached `ban_threshold`, then ban the peer and reset its score.  The ban
+    /// lasts `min_ban_duration` seconds the first time, and doubles each subsequent time up to
+    /// `max_ban_duration`.  Returns true if the peer was banned.
+    pub fn try_ban(
+        &mut self,
+        ban_threshold: f64,
+        min_ban_duration: u64,
+        max_ban_duration: u64,
+        now: u64,
+    ) -> bool {
+        if self.score < ban_threshold {
+            return false;
+        }
+        let ban_duration = if self.num_bans < 63 {
+            min_ban_duration.saturating_mul(1u64 << self.num_bans)
+        } else {
+            max_ban_duration
+        };
+        let ban_duration = cmp::min(ban_duration, max_ban_duration);
+
+        self.num_bans += 1;
+        self.banned_until = now + ban_duration;
+        self.score = 0.0;
+        true
+    }
+
+    pub fn is_banned(&self, now: u64) -> bool {
+        now < self.banned_until
+    }
+}
+
+impl FromRow<PeerReputation> for PeerReputation {
+    fn from_row<'a>(row: &'a Row) -> Result<PeerReputation, db_error> {
+        let network_id: u32 = row.get_unwrap("network_id");
+        let addrbytes: PeerAddress = PeerAddress::from_column(row, "addrbytes")?;
+        let port: u16 = row.get_unwrap("port");
+        let score: f64 = row.get_unwrap("score");
+        let last_update = u64::from_column(row, "last_update")?;
+        let num_bans = u64::from_column(row, "num_bans")?;
+        let banned_until = u64::from_column(row, "banned_until")?;
+
+        Ok(PeerReputation {
+            network_id,
+            addrbytes,
+            port,
+            score,
+            last_update,
+            num_bans,
+            banned_until,
+        })
+    }
+}
+
 impl FromRow<Neighbor> for Neighbor {
     fn from_row<'a>(row: &'a Row) -> Result<Neighbor, db_error> {
         let peer_version: u32 = row.get_unwrap("peer_version");
@@ -357,6 +457,38 @@ const PEERDB_INITIAL_SCHEMA: &'static [&'static str] = &[
     );"#,
 ];
 
+// Peer reputations are kept apart from the frontier, since a peer can be evicted from (and
+// re-inserted into) the frontier without its track record being forgotten.  Some databases got
+// this table before schema versioning existed, hence IF NOT EXISTS.
+const PEERDB_REPUTATION_SCHEMA: &'static [&'static str] = &[r#"
+    CREATE TABLE IF NOT EXISTS peer_reputation(
+        network_id INTEGER NOT NULL,
+        addrbytes TEXT NOT NULL,
+        port INTEGER NOT NULL,
+        score REAL NOT NULL,
+        last_update INTEGER NOT NULL,
+        num_bans INTEGER NOT NULL,
+        banned_until INTEGER NOT NULL,
+
+        PRIMARY KEY(network_id,addrbytes,port)
+    );"#];
+
+pub const PEERDB_SCHEMA: DBSchema = DBSchema {
+    name: "peer",
+    migrations: &[
+        SchemaMigration {
+            version: 1,
+            description: "initial schema",
+            statements: PEERDB_INITIAL_SCHEMA,
+        },
+        SchemaMigration {
+            version: 2,
+            description: "add peer reputations",
+            statements: PEERDB_REPUTATION_SCHEMA,
+        },
+    ],
+};
+
 #[derive(Debug)]
 pub struct PeerDB {
     pub conn: Connection,
@@ -388,13 +520,11 @@ impl PeerDB {
 
         let mut tx = self.tx_begin()?;
 
-        for row_text in PEERDB_INITIAL_SCHEMA {
-            tx.execute_batch(row_text).map_err(db_error::SqliteError)?;
-        }
+        PEERDB_SCHEMA.instantiate(&tx)?;
 
         tx.execute(
             "INSERT INTO db_config (version) VALUES (?1)",
-            &[&PEERDB_VERSION],
+            &[&PEERDB_SCHEMA.latest_version().to_string()],
         )
         .map_err(db_error::SqliteError)?;
 
@@ -480,6 +610,7 @@ impl PeerDB {
             debug!("Refresh deny {}/{}", &prefix, mask);
             PeerDB::apply_cidr_filter(tx, &prefix, mask, "denied", i64::max_value())?;
         }
+        PeerDB::refresh_reputation_denies(tx, util::get_epoch_time_secs())?;
         Ok(())
     }
 
@@ -566,6 +697,7 @@ impl PeerDB {
                 }
             }
         } else {
+            PEERDB_SCHEMA.migrate(&mut db.conn, readwrite)?;
             db.update_local_peer(network_id, parent_network_id, data_url, p2p_port)?;
 
             {
@@ -591,6 +723,31 @@ impl PeerDB {
         Ok(db)
     }
 
+    /// Open an existing peer database without touching its local peer or frontier state (e.g.
+    /// so an operator can inspect or clear peer reputations while the node is offline).
+    pub fn open(path: &str, readwrite: bool) -> Result<PeerDB, db_error> {
+        if fs::metadata(path).is_err() {
+            return Err(db_error::NoDBError);
+        }
+        let open_flags = if readwrite {
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+        };
+
+        let conn =
+            Connection::open_with_flags(path, open_flags).map_err(|e| db_error::SqliteError(e))?;
+
+        conn.busy_handler(Some(tx_busy_handler))?;
+        let mut db = PeerDB {
+            conn: conn,
+            readwrite: readwrite,
+        };
+
+        PEERDB_SCHEMA.migrate(&mut db.conn, readwrite)?;
+        Ok(db)
+    }
+
     /// Open a burn database in memory (used for testing)
     #[cfg(test)]
     pub fn connect_memory(
@@ -1375,6 +1532,140 @@ impl PeerDB {
         let rows = query_rows::<Neighbor, _>(conn, &qry, NO_PARAMS)?;
         Ok(rows)
     }
+
+    /// Get a peer's persistent reputation, if it has one
+    pub fn get_peer_reputation(
+        conn: &DBConn,
+        network_id: u32,
+        peer_addr: &PeerAddress,
+        peer_port: u16,
+    ) -> Result<Option<PeerReputation>, db_error> {
+        let qry =
+            "SELECT * FROM peer_reputation WHERE network_id = ?1 AND addrbytes = ?2 AND port = ?3";
+        let args: &[&dyn ToSql] = &[&network_id, &peer_addr.to_bin(), &peer_port];
+        query_row::<PeerReputation, _>(conn, qry, args)
+    }
+
+    /// Get all peers' persistent reputations, worst first
+    pub fn get_all_peer_reputations(conn: &DBConn) -> Result<Vec<PeerReputation>, db_error> {
+        let qry = "SELECT * FROM peer_reputation ORDER BY banned_until DESC, score DESC";
+        query_rows::<PeerReputation, _>(conn, qry, NO_PARAMS)
+    }
+
+    /// Store a peer's persistent reputation.  If the reputation carries an active ban, then the
+    /// peer is denied until it expires.
+    pub fn put_peer_reputation<'a>(
+        tx: &mut Transaction<'a>,
+        reputation: &PeerReputation,
+        now: u64,
+    ) -> Result<(), db_error> {
+        let args: &[&dyn ToSql] = &[
+            &reputation.network_id,
+            &reputation.addrbytes.to_bin(),
+            &reputation.port,
+            &reputation.score,
+            &u64_to_sql(reputation.last_update)?,
+            &u64_to_sql(reputation.num_bans)?,
+            &u64_to_sql(reputation.banned_until)?,
+        ];
+        tx.execute("INSERT OR REPLACE INTO peer_reputation (network_id, addrbytes, port, score, last_update, num_bans, banned_until) VALUES (?1,?2,?3,?4,?5,?6,?7)", args)
+            .map_err(db_error::SqliteError)?;
+
+        if reputation.is_banned(now) {
+            let neighbor_opt = PeerDB::get_peer(
+                tx,
+                reputation.network_id,
+                &reputation.addrbytes,
+                reputation.port,
+            )?;
+            let already_denied = match neighbor_opt {
+                Some(neighbor) => neighbor.denied >= (reputation.banned_until as i64),
+                None => false,
+            };
+            if !already_denied {
+                PeerDB::set_deny_peer(
+                    tx,
+                    reputation.network_id,
+                    &reputation.addrbytes,
+                    reputation.port,
+                    reputation.banned_until,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget a peer's persistent reputation, and lift any ban that came from it.
+    /// Returns true if the peer had a reputation.
+    pub fn clear_peer_reputation<'a>(
+        tx: &mut Transaction<'a>,
+        network_id: u32,
+        peer_addr: &PeerAddress,
+        peer_port: u16,
+    ) -> Result<bool, db_error> {
+        let reputation = match PeerDB::get_peer_reputation(tx, network_id, peer_addr, peer_port)? {
+            Some(reputation) => reputation,
+            None => {
+                return Ok(false);
+            }
+        };
+
+        let args: &[&dyn ToSql] = &[&network_id, &peer_addr.to_bin(), &peer_port];
+        tx.execute(
+            "DELETE FROM peer_reputation WHERE network_id = ?1 AND addrbytes = ?2 AND port = ?3",
+            args,
+        )
+        .map_err(db_error::SqliteError)?;
+
+        // only lift the deny if it's the one the reputation put there -- i.e. don't undo an
+        // operator's CIDR deny
+        let args: &[&dyn ToSql] = &[
+            &network_id,
+            &peer_addr.to_bin(),
+            &peer_port,
+            &u64_to_sql(reputation.banned_until)?,
+        ];
+        tx.execute("UPDATE frontier SET denied = 0 WHERE network_id = ?1 AND addrbytes = ?2 AND port = ?3 AND denied <= ?4", args)
+            .map_err(db_error::SqliteError)?;
+        Ok(true)
+    }
+
+    /// Forget all peers' persistent reputations, and lift any bans that came from them.
+    /// Returns the number of reputations cleared.
+    pub fn clear_all_peer_reputations<'a>(tx: &mut Transaction<'a>) -> Result<u64, db_error> {
+        let reputations = PeerDB::get_all_peer_reputations(tx)?;
+        for reputation in reputations.iter() {
+            PeerDB::clear_peer_reputation(
+                tx,
+                reputation.network_id,
+                &reputation.addrbytes,
+                reputation.port,
+            )?;
+        }
+        Ok(reputations.len() as u64)
+    }
+
+    /// Re-apply the bans from all peers' persistent reputations that have not yet expired
+    fn refresh_reputation_denies<'a>(tx: &mut Transaction<'a>, now: u64) -> Result<(), db_error> {
+        let qry = "SELECT * FROM peer_reputation WHERE banned_until > ?1";
+        let args: &[&dyn ToSql] = &[&u64_to_sql(now)?];
+        let reputations = query_rows::<PeerReputation, _>(tx, qry, args)?;
+        for reputation in reputations.into_iter() {
+            debug!(
+                "Refresh reputation deny {}:{} until {}",
+                &reputation.addrbytes, reputation.port, reputation.banned_until
+            );
+            let args: &[&dyn ToSql] = &[
+                &u64_to_sql(reputation.banned_until)?,
+                &reputation.network_id,
+                &reputation.addrbytes.to_bin(),
+                &reputation.port,
+            ];
+            tx.execute("UPDATE frontier SET denied = ?1 WHERE network_id = ?2 AND addrbytes = ?3 AND port = ?4 AND denied < ?1", args)
+                .map_err(db_error::SqliteError)?;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -1790,6 +2081,133 @@ mod test {
         assert_eq!(peer_allowed.allowed, 20000000);
     }
 
+    #[test]
+    fn test_peer_reputation_decay_and_ban() {
+        let mut rep = PeerReputation::new(0x9abcdef0, &PeerAddress([0x1; 16]), 12345, 1000);
+
+        // score halves every half-life
+        rep.add_penalty(80.0, 100, 1000);
+        assert_eq!(rep.get_score(100, 1000), 80.0);
+        assert_eq!(rep.get_score(100, 1100), 40.0);
+        assert_eq!(rep.get_score(100, 1200), 20.0);
+
+        // not enough to get banned
+        assert!(!rep.try_ban(100.0, 10, 35, 1100));
+        assert!(!rep.is_banned(1100));
+
+        // 40 + 60 is enough
+        rep.add_penalty(60.0, 100, 1100);
+        assert_eq!(rep.score, 100.0);
+        assert!(rep.try_ban(100.0, 10, 35, 1100));
+        assert!(rep.is_banned(1100));
+        assert!(!rep.is_banned(1110));
+        assert_eq!(rep.banned_until, 1110);
+        assert_eq!(rep.num_bans, 1);
+        assert_eq!(rep.score, 0.0);
+
+        // bans double in length, up to the max
+        rep.add_penalty(100.0, 100, 1200);
+        assert!(rep.try_ban(100.0, 10, 35, 1200));
+        assert_eq!(rep.banned_until, 1220);
+
+        rep.add_penalty(100.0, 100, 1300);
+        assert!(rep.try_ban(100.0, 10, 35, 1300));
+        assert_eq!(rep.banned_until, 1335);
+        assert_eq!(rep.num_bans, 3);
+    }
+
+    #[test]
+    fn test_peer_reputation_persistence() {
+        let mut db = PeerDB::connect_memory(
+            0x9abcdef0,
+            12345,
+            0,
+            "http://foo.com".into(),
+            &vec![],
+            &vec![],
+        )
+        .unwrap();
+
+        let now = util::get_epoch_time_secs();
+        let mut rep_banned = PeerReputation::new(0x9abcdef0, &PeerAddress([0x1; 16]), 12345, now);
+        rep_banned.add_penalty(100.0, 3600, now);
+        assert!(rep_banned.try_ban(100.0, 3600, 86400, now));
+
+        let mut rep_ok = PeerReputation::new(0x9abcdef0, &PeerAddress([0x2; 16]), 12345, now);
+        rep_ok.add_penalty(10.0, 3600, now);
+        assert!(!rep_ok.try_ban(100.0, 3600, 86400, now));
+
+        {
+            let mut tx = db.tx_begin().unwrap();
+            PeerDB::put_peer_reputation(&mut tx, &rep_banned, now).unwrap();
+            PeerDB::put_peer_reputation(&mut tx, &rep_ok, now).unwrap();
+            tx.commit().unwrap();
+        }
+
+        assert_eq!(
+            PeerDB::get_peer_reputation(db.conn(), 0x9abcdef0, &PeerAddress([0x1; 16]), 12345)
+                .unwrap()
+                .unwrap(),
+            rep_banned
+        );
+        assert_eq!(
+            PeerDB::get_all_peer_reputations(db.conn()).unwrap(),
+            vec![rep_banned.clone(), rep_ok.clone()]
+        );
+
+        // the banned peer is denied; the other one isn't even in the frontier
+        assert!(
+            PeerDB::is_peer_denied(db.conn(), 0x9abcdef0, &PeerAddress([0x1; 16]), 12345).unwrap()
+        );
+        assert!(
+            PeerDB::get_peer(db.conn(), 0x9abcdef0, &PeerAddress([0x2; 16]), 12345)
+                .unwrap()
+                .is_none()
+        );
+
+        // the ban survives a deny refresh (e.g. from a restart)
+        {
+            let mut tx = db.tx_begin().unwrap();
+            PeerDB::refresh_denies(&mut tx).unwrap();
+            tx.commit().unwrap();
+        }
+        assert_eq!(
+            PeerDB::get_peer(db.conn(), 0x9abcdef0, &PeerAddress([0x1; 16]), 12345)
+                .unwrap()
+                .unwrap()
+                .denied,
+            rep_banned.banned_until as i64
+        );
+
+        // clearing the reputation lifts the ban
+        {
+            let mut tx = db.tx_begin().unwrap();
+            assert!(PeerDB::clear_peer_reputation(
+                &mut tx,
+                0x9abcdef0,
+                &PeerAddress([0x1; 16]),
+                12345
+            )
+            .unwrap());
+            tx.commit().unwrap();
+        }
+        assert!(
+            !PeerDB::is_peer_denied(db.conn(), 0x9abcdef0, &PeerAddress([0x1; 16]), 12345).unwrap()
+        );
+        assert!(
+            PeerDB::get_peer_reputation(db.conn(), 0x9abcdef0, &PeerAddress([0x1; 16]), 12345)
+                .unwrap()
+                .is_none()
+        );
+
+        {
+            let mut tx = db.tx_begin().unwrap();
+            assert_eq!(PeerDB::clear_all_peer_reputations(&mut tx).unwrap(), 1);
+            tx.commit().unwrap();
+        }
+        assert_eq!(PeerDB::get_all_peer_reputations(db.conn()).unwrap(), vec![]);
+    }
+
     #[test]
     fn test_peer_cidr_lists() {
         let mut db = PeerDB::connect_memory(
@@ -41,6 +41,7 @@ use chainstate::burn::ConsensusHash;
 use chainstate::stacks::{StacksBlock, StacksMicroblock, StacksPublicKey, StacksTransaction};
 use deps::httparse;
 use net::atlas::Attachment;
+use net::CallReadOnlyArguments;
 use net::CallReadOnlyRequestBody;
 use net::ClientError;
 use net::Error as net_error;
@@ -58,8 +59,10 @@ use net::NeighborAddress;
 use net::PeerAddress;
 use net::PeerHost;
 use net::ProtocolFamily;
+use net::SortitionRequest;
 use net::StacksHttpMessage;
 use net::StacksHttpPreamble;
+use net::TipRequest;
 use net::UnconfirmedTransactionResponse;
 use net::UnconfirmedTransactionStatus;
 use net::HTTP_PREAMBLE_MAX_ENCODED_SIZE;
@@ -91,6 +94,14 @@ use crate::types::chainstate::{BlockHeaderHash, StacksAddress, StacksBlockId};
 lazy_static! {
     static ref PATH_GETINFO: Regex = Regex::new(r#"^/v2/info$"#).unwrap();
     static ref PATH_GETPOXINFO: Regex = Regex::new(r#"^/v2/pox$"#).unwrap();
+    static ref PATH_GETPOX_REWARD_SET: Regex =
+        Regex::new(r#"^/v2/pox/reward_set/([0-9]{1,20})$"#).unwrap();
+    static ref PATH_GETPOX_PAYOUTS: Regex =
+        Regex::new(r#"^/v2/pox/payouts/([0-9]{1,20})$"#).unwrap();
+    static ref PATH_GETSORTITION_HEIGHT: Regex =
+        Regex::new(r#"^/v2/sortitions/burn_height/([0-9]{1,20})$"#).unwrap();
+    static ref PATH_GETSORTITION_CONSENSUS: Regex =
+        Regex::new(r#"^/v2/sortitions/consensus/([0-9a-f]{40})$"#).unwrap();
     static ref PATH_GETNEIGHBORS: Regex = Regex::new(r#"^/v2/neighbors$"#).unwrap();
     static ref PATH_GETBLOCK: Regex = Regex::new(r#"^/v2/blocks/([0-9a-f]{64})$"#).unwrap();
     static ref PATH_GETMICROBLOCKS_INDEXED: Regex =
@@ -1449,6 +1460,26 @@ impl HttpRequestType {
         )] = &[
             ("GET", &PATH_GETINFO, &HttpRequestType::parse_getinfo),
             ("GET", &PATH_GETPOXINFO, &HttpRequestType::parse_getpoxinfo),
+            (
+                "GET",
+                &PATH_GETPOX_REWARD_SET,
+                &HttpRequestType::parse_getpox_reward_set,
+            ),
+            (
+                "GET",
+                &PATH_GETPOX_PAYOUTS,
+                &HttpRequestType::parse_getpox_payouts,
+            ),
+            (
+                "GET",
+                &PATH_GETSORTITION_HEIGHT,
+                &HttpRequestType::parse_getsortition_height,
+            ),
+            (
+                "GET",
+                &PATH_GETSORTITION_CONSENSUS,
+                &HttpRequestType::parse_getsortition_consensus,
+            ),
             (
                 "GET",
                 &PATH_GETNEIGHBORS,
@@ -1622,6 +1653,115 @@ impl HttpRequestType {
         ))
     }
 
+    /// Parse the reward cycle number out of a /v2/pox/reward_set or /v2/pox/payouts path
+    fn parse_reward_cycle_capture(
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+    ) -> Result<u64, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body".to_string(),
+            ));
+        }
+
+        captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to reward cycle group".to_string(),
+            ))?
+            .as_str()
+            .parse::<u64>()
+            .map_err(|_| net_error::DeserializeError("Failed to parse reward cycle".to_string()))
+    }
+
+    fn parse_getpox_reward_set<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let reward_cycle = HttpRequestType::parse_reward_cycle_capture(preamble, captures)?;
+        Ok(HttpRequestType::GetPoxRewardSet(
+            HttpRequestMetadata::from_preamble(preamble),
+            reward_cycle,
+        ))
+    }
+
+    fn parse_getpox_payouts<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        let reward_cycle = HttpRequestType::parse_reward_cycle_capture(preamble, captures)?;
+        Ok(HttpRequestType::GetPoxPayouts(
+            HttpRequestMetadata::from_preamble(preamble),
+            reward_cycle,
+        ))
+    }
+
+    fn parse_getsortition_height<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetSortition".to_string(),
+            ));
+        }
+
+        let height = captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to burn block height group".to_string(),
+            ))?
+            .as_str()
+            .parse::<u64>()
+            .map_err(|_| {
+                net_error::DeserializeError("Failed to parse burn block height".to_string())
+            })?;
+
+        Ok(HttpRequestType::GetSortition(
+            HttpRequestMetadata::from_preamble(preamble),
+            SortitionRequest::BurnHeight(height),
+        ))
+    }
+
+    fn parse_getsortition_consensus<R: Read>(
+        _protocol: &mut StacksHttp,
+        preamble: &HttpRequestPreamble,
+        captures: &Captures,
+        _query: Option<&str>,
+        _fd: &mut R,
+    ) -> Result<HttpRequestType, net_error> {
+        if preamble.get_content_length() != 0 {
+            return Err(net_error::DeserializeError(
+                "Invalid Http request: expected 0-length body for GetSortition".to_string(),
+            ));
+        }
+
+        let consensus_hash_hex = captures
+            .get(1)
+            .ok_or(net_error::DeserializeError(
+                "Failed to match path to consensus hash group".to_string(),
+            ))?
+            .as_str();
+
+        let consensus_hash = ConsensusHash::from_hex(consensus_hash_hex).map_err(|_| {
+            net_error::DeserializeError("Failed to parse consensus hash".to_string())
+        })?;
+
+        Ok(HttpRequestType::GetSortition(
+            HttpRequestMetadata::from_preamble(preamble),
+            SortitionRequest::ConsensusHash(consensus_hash),
+        ))
+    }
+
     fn parse_getneighbors<R: Read>(
         _protocol: &mut StacksHttp,
         preamble: &HttpRequestPreamble,
@@ -1675,8 +1815,8 @@ impl HttpRequestType {
     }
 
     /// get the chain tip optional query argument (`tip`)
-    /// Take the first value we can parse.
-    fn get_chain_tip_query(query: Option<&str>) -> Option<StacksBlockId> {
+    /// Take the first value we can parse.  `latest` selects the unconfirmed chain tip.
+    fn get_chain_tip_query(query: Option<&str>) -> TipRequest {
         match query {
             Some(query_string) => {
                 for (key, value) in form_urlencoded::parse(query_string.as_bytes()) {
@@ -1684,14 +1824,17 @@ impl HttpRequestType {
                         continue;
                     }
 
+                    if value == "latest" {
+                        return TipRequest::UseLatestUnconfirmedTip;
+                    }
                     if let Ok(tip) = StacksBlockId::from_hex(&value) {
-                        return Some(tip);
+                        return TipRequest::SpecificTip(tip);
                     }
                 }
-                return None;
+                return TipRequest::UseLatestAnchoredTip;
             }
             None => {
-                return None;
+                return TipRequest::UseLatestAnchoredTip;
             }
         }
     }
@@ -1808,14 +1951,25 @@ impl HttpRequestType {
         let sender = PrincipalData::parse(&body.sender)
             .map_err(|_e| net_error::DeserializeError("Failed to parse sender principal".into()))?;
 
-        let arguments = body
-            .arguments
-            .into_iter()
-            .map(|hex| Value::try_deserialize_hex_untyped(&hex).ok())
-            .collect::<Option<Vec<Value>>>()
-            .ok_or_else(|| {
-                net_error::DeserializeError("Failed to deserialize argument value".into())
-            })?;
+        let arguments = match body.arguments_json {
+            Some(arguments_json) => {
+                if !body.arguments.is_empty() {
+                    return Err(net_error::DeserializeError(
+                        "Only one of arguments and arguments_json may be given".into(),
+                    ));
+                }
+                CallReadOnlyArguments::JSON(arguments_json)
+            }
+            None => body
+                .arguments
+                .into_iter()
+                .map(|hex| Value::try_deserialize_hex_untyped(&hex).ok())
+                .collect::<Option<Vec<Value>>>()
+                .map(CallReadOnlyArguments::Serialized)
+                .ok_or_else(|| {
+                    net_error::DeserializeError("Failed to deserialize argument value".into())
+                })?,
+        };
 
         let tip = HttpRequestType::get_chain_tip_query(query);
 
@@ -2412,6 +2566,9 @@ impl HttpRequestType {
         match *self {
             HttpRequestType::GetInfo(ref md) => md,
             HttpRequestType::GetPoxInfo(ref md, _) => md,
+            HttpRequestType::GetPoxRewardSet(ref md, _) => md,
+            HttpRequestType::GetPoxPayouts(ref md, _) => md,
+            HttpRequestType::GetSortition(ref md, _) => md,
             HttpRequestType::GetNeighbors(ref md) => md,
             HttpRequestType::GetBlock(ref md, _) => md,
             HttpRequestType::GetMicroblocksIndexed(ref md, _) => md,
@@ -2439,6 +2596,9 @@ impl HttpRequestType {
         match *self {
             HttpRequestType::GetInfo(ref mut md) => md,
             HttpRequestType::GetPoxInfo(ref mut md, _) => md,
+            HttpRequestType::GetPoxRewardSet(ref mut md, _) => md,
+            HttpRequestType::GetPoxPayouts(ref mut md, _) => md,
+            HttpRequestType::GetSortition(ref mut md, _) => md,
             HttpRequestType::GetNeighbors(ref mut md) => md,
             HttpRequestType::GetBlock(ref mut md, _) => md,
             HttpRequestType::GetMicroblocksIndexed(ref mut md, _) => md,
@@ -2462,23 +2622,45 @@ impl HttpRequestType {
         }
     }
 
-    fn make_query_string(tip_opt: Option<&StacksBlockId>, with_proof: bool) -> String {
-        if let Some(tip) = tip_opt {
-            format!("?tip={}{}", tip, if with_proof { "" } else { "&proof=0" })
-        } else if !with_proof {
-            format!("?proof=0")
-        } else {
-            "".to_string()
+    fn make_query_string(tip_req: &TipRequest, with_proof: bool) -> String {
+        match tip_req {
+            TipRequest::UseLatestUnconfirmedTip => {
+                format!("?tip=latest{}", if with_proof { "" } else { "&proof=0" })
+            }
+            TipRequest::SpecificTip(tip) => {
+                format!("?tip={}{}", tip, if with_proof { "" } else { "&proof=0" })
+            }
+            TipRequest::UseLatestAnchoredTip => {
+                if !with_proof {
+                    format!("?proof=0")
+                } else {
+                    "".to_string()
+                }
+            }
         }
     }
 
     pub fn request_path(&self) -> String {
         match self {
             HttpRequestType::GetInfo(_md) => "/v2/info".to_string(),
-            HttpRequestType::GetPoxInfo(_md, tip_opt) => format!(
+            HttpRequestType::GetPoxInfo(_md, tip_req) => format!(
                 "/v2/pox{}",
-                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+                HttpRequestType::make_query_string(tip_req, true)
             ),
+            HttpRequestType::GetPoxRewardSet(_md, reward_cycle) => {
+                format!("/v2/pox/reward_set/{}", reward_cycle)
+            }
+            HttpRequestType::GetPoxPayouts(_md, reward_cycle) => {
+                format!("/v2/pox/payouts/{}", reward_cycle)
+            }
+            HttpRequestType::GetSortition(_md, sortition_req) => match sortition_req {
+                SortitionRequest::BurnHeight(height) => {
+                    format!("/v2/sortitions/burn_height/{}", height)
+                }
+                SortitionRequest::ConsensusHash(consensus_hash) => {
+                    format!("/v2/sortitions/consensus/{}", consensus_hash)
+                }
+            },
             HttpRequestType::GetNeighbors(_md) => "/v2/neighbors".to_string(),
             HttpRequestType::GetBlock(_md, block_hash) => {
                 format!("/v2/blocks/{}", block_hash.to_hex())
@@ -2499,14 +2681,14 @@ impl HttpRequestType {
             }
             HttpRequestType::PostTransaction(_md, ..) => "/v2/transactions".to_string(),
             HttpRequestType::PostBlock(_md, ch, ..) => format!("/v2/blocks/upload/{}", &ch),
-            HttpRequestType::PostMicroblock(_md, _, tip_opt) => format!(
+            HttpRequestType::PostMicroblock(_md, _, tip_req) => format!(
                 "/v2/microblocks{}",
-                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+                HttpRequestType::make_query_string(tip_req, true)
             ),
-            HttpRequestType::GetAccount(_md, principal, tip_opt, with_proof) => format!(
+            HttpRequestType::GetAccount(_md, principal, tip_req, with_proof) => format!(
                 "/v2/accounts/{}{}",
                 &principal.to_string(),
-                HttpRequestType::make_query_string(tip_opt.as_ref(), *with_proof)
+                HttpRequestType::make_query_string(tip_req, *with_proof)
             ),
             HttpRequestType::GetMapEntry(
                 _md,
@@ -2514,40 +2696,40 @@ impl HttpRequestType {
                 contract_name,
                 map_name,
                 _key,
-                tip_opt,
+                tip_req,
                 with_proof,
             ) => format!(
                 "/v2/map_entry/{}/{}/{}{}",
                 &contract_addr.to_string(),
                 contract_name.as_str(),
                 map_name.as_str(),
-                HttpRequestType::make_query_string(tip_opt.as_ref(), *with_proof)
+                HttpRequestType::make_query_string(tip_req, *with_proof)
             ),
             HttpRequestType::GetTransferCost(_md) => "/v2/fees/transfer".into(),
-            HttpRequestType::GetContractABI(_, contract_addr, contract_name, tip_opt) => format!(
+            HttpRequestType::GetContractABI(_, contract_addr, contract_name, tip_req) => format!(
                 "/v2/contracts/interface/{}/{}{}",
                 contract_addr,
                 contract_name.as_str(),
-                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+                HttpRequestType::make_query_string(tip_req, true)
             ),
             HttpRequestType::GetContractSrc(
                 _,
                 contract_addr,
                 contract_name,
-                tip_opt,
+                tip_req,
                 with_proof,
             ) => format!(
                 "/v2/contracts/source/{}/{}{}",
                 contract_addr,
                 contract_name.as_str(),
-                HttpRequestType::make_query_string(tip_opt.as_ref(), *with_proof)
+                HttpRequestType::make_query_string(tip_req, *with_proof)
             ),
             HttpRequestType::GetIsTraitImplemented(
                 _,
                 contract_addr,
                 contract_name,
                 trait_id,
-                tip_opt,
+                tip_req,
             ) => format!(
                 "/v2/traits/{}/{}/{}/{}/{}{}",
                 contract_addr,
@@ -2555,7 +2737,7 @@ impl HttpRequestType {
                 trait_id.name.to_string(),
                 StacksAddress::from(trait_id.clone().contract_identifier.issuer),
                 trait_id.contract_identifier.name.as_str(),
-                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+                HttpRequestType::make_query_string(tip_req, true)
             ),
             HttpRequestType::CallReadOnlyFunction(
                 _,
@@ -2564,13 +2746,13 @@ impl HttpRequestType {
                 _,
                 func_name,
                 _,
-                tip_opt,
+                tip_req,
             ) => format!(
                 "/v2/contracts/call-read/{}/{}/{}{}",
                 contract_addr,
                 contract_name.as_str(),
                 func_name.as_str(),
-                HttpRequestType::make_query_string(tip_opt.as_ref(), true)
+                HttpRequestType::make_query_string(tip_req, true)
             ),
             HttpRequestType::OptionsPreflight(_md, path) => path.to_string(),
             HttpRequestType::GetAttachmentsInv(_md, index_block_hash, pages_indexes) => {
@@ -2602,6 +2784,14 @@ impl HttpRequestType {
         match self {
             HttpRequestType::GetInfo(..) => "/v2/info",
             HttpRequestType::GetPoxInfo(..) => "/v2/pox",
+            HttpRequestType::GetPoxRewardSet(..) => "/v2/pox/reward_set/:cycle",
+            HttpRequestType::GetPoxPayouts(..) => "/v2/pox/payouts/:cycle",
+            HttpRequestType::GetSortition(_, SortitionRequest::BurnHeight(_)) => {
+                "/v2/sortitions/burn_height/:height"
+            }
+            HttpRequestType::GetSortition(_, SortitionRequest::ConsensusHash(_)) => {
+                "/v2/sortitions/consensus/:consensus_hash"
+            }
             HttpRequestType::GetNeighbors(..) => "/v2/neighbors",
             HttpRequestType::GetBlock(..) => "/v2/blocks/:hash",
             HttpRequestType::GetMicroblocksIndexed(..) => "/v2/microblocks/:hash",
@@ -2746,17 +2936,26 @@ impl HttpRequestType {
                 func_args,
                 ..,
             ) => {
-                let mut args = vec![];
-                for arg in func_args.iter() {
-                    let mut arg_bytes = vec![];
-                    arg.serialize_write(&mut arg_bytes)
-                        .map_err(net_error::WriteError)?;
-                    args.push(to_hex(&arg_bytes));
-                }
-
-                let request_body = CallReadOnlyRequestBody {
-                    sender: sender.to_string(),
-                    arguments: args,
+                let request_body = match func_args {
+                    CallReadOnlyArguments::Serialized(func_args) => {
+                        let mut args = vec![];
+                        for arg in func_args.iter() {
+                            let mut arg_bytes = vec![];
+                            arg.serialize_write(&mut arg_bytes)
+                                .map_err(net_error::WriteError)?;
+                            args.push(to_hex(&arg_bytes));
+                        }
+                        CallReadOnlyRequestBody {
+                            sender: sender.to_string(),
+                            arguments: args,
+                            arguments_json: None,
+                        }
+                    }
+                    CallReadOnlyArguments::JSON(func_args) => CallReadOnlyRequestBody {
+                        sender: sender.to_string(),
+                        arguments: vec![],
+                        arguments_json: Some(func_args.clone()),
+                    },
                 };
 
                 let mut request_body_bytes = vec![];
@@ -3025,6 +3224,19 @@ impl HttpResponseType {
         )] = &[
             (&PATH_GETINFO, &HttpResponseType::parse_peerinfo),
             (&PATH_GETPOXINFO, &HttpResponseType::parse_poxinfo),
+            (
+                &PATH_GETPOX_REWARD_SET,
+                &HttpResponseType::parse_pox_reward_set,
+            ),
+            (&PATH_GETPOX_PAYOUTS, &HttpResponseType::parse_pox_payouts),
+            (
+                &PATH_GETSORTITION_HEIGHT,
+                &HttpResponseType::parse_sortition,
+            ),
+            (
+                &PATH_GETSORTITION_CONSENSUS,
+                &HttpResponseType::parse_sortition,
+            ),
             (&PATH_GETNEIGHBORS, &HttpResponseType::parse_neighbors),
             (&PATH_GETBLOCK, &HttpResponseType::parse_block),
             (&PATH_GET_MAP_ENTRY, &HttpResponseType::parse_get_map_entry),
@@ -3156,6 +3368,51 @@ impl HttpResponseType {
         ))
     }
 
+    fn parse_pox_reward_set<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let reward_set_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::PoxRewardSet(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            reward_set_data,
+        ))
+    }
+
+    fn parse_pox_payouts<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let payouts_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::PoxPayouts(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            payouts_data,
+        ))
+    }
+
+    fn parse_sortition<R: Read>(
+        _protocol: &mut StacksHttp,
+        request_version: HttpVersion,
+        preamble: &HttpResponsePreamble,
+        fd: &mut R,
+        len_hint: Option<usize>,
+    ) -> Result<HttpResponseType, net_error> {
+        let sortition_data =
+            HttpResponseType::parse_json(preamble, fd, len_hint, MAX_MESSAGE_LEN as u64)?;
+        Ok(HttpResponseType::Sortition(
+            HttpResponseMetadata::from_preamble(request_version, preamble),
+            sortition_data,
+        ))
+    }
+
     fn parse_neighbors<R: Read>(
         _protocol: &mut StacksHttp,
         request_version: HttpVersion,
@@ -3485,6 +3742,9 @@ impl HttpResponseType {
         match *self {
             HttpResponseType::PeerInfo(ref md, _) => md,
             HttpResponseType::PoxInfo(ref md, _) => md,
+            HttpResponseType::PoxRewardSet(ref md, _) => md,
+            HttpResponseType::PoxPayouts(ref md, _) => md,
+            HttpResponseType::Sortition(ref md, _) => md,
             HttpResponseType::Neighbors(ref md, _) => md,
             HttpResponseType::Block(ref md, _) => md,
             HttpResponseType::BlockStream(ref md) => md,
@@ -3615,6 +3875,18 @@ impl HttpResponseType {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, pox_info)?;
             }
+            HttpResponseType::PoxRewardSet(ref md, ref reward_set_data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, reward_set_data)?;
+            }
+            HttpResponseType::PoxPayouts(ref md, ref payouts_data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, payouts_data)?;
+            }
+            HttpResponseType::Sortition(ref md, ref sortition_data) => {
+                HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
+                HttpResponseType::send_json(protocol, md, fd, sortition_data)?;
+            }
             HttpResponseType::Neighbors(ref md, ref neighbor_data) => {
                 HttpResponsePreamble::ok_JSON_from_md(fd, md)?;
                 HttpResponseType::send_json(protocol, md, fd, neighbor_data)?;
@@ -3815,6 +4087,9 @@ impl MessageSequence for StacksHttpMessage {
             StacksHttpMessage::Request(ref req) => match req {
                 HttpRequestType::GetInfo(_) => "HTTP(GetInfo)",
                 HttpRequestType::GetPoxInfo(_, _) => "HTTP(GetPoxInfo)",
+                HttpRequestType::GetPoxRewardSet(_, _) => "HTTP(GetPoxRewardSet)",
+                HttpRequestType::GetPoxPayouts(_, _) => "HTTP(GetPoxPayouts)",
+                HttpRequestType::GetSortition(_, _) => "HTTP(GetSortition)",
                 HttpRequestType::GetNeighbors(_) => "HTTP(GetNeighbors)",
                 HttpRequestType::GetBlock(_, _) => "HTTP(GetBlock)",
                 HttpRequestType::GetMicroblocksIndexed(_, _) => "HTTP(GetMicroblocksIndexed)",
@@ -3852,6 +4127,9 @@ impl MessageSequence for StacksHttpMessage {
                 HttpResponseType::GetAttachmentsInv(_, _) => "HTTP(GetAttachmentsInv)",
                 HttpResponseType::PeerInfo(_, _) => "HTTP(PeerInfo)",
                 HttpResponseType::PoxInfo(_, _) => "HTTP(PeerInfo)",
+                HttpResponseType::PoxRewardSet(_, _) => "HTTP(PoxRewardSet)",
+                HttpResponseType::PoxPayouts(_, _) => "HTTP(PoxPayouts)",
+                HttpResponseType::Sortition(_, _) => "HTTP(Sortition)",
                 HttpResponseType::Neighbors(_, _) => "HTTP(Neighbors)",
                 HttpResponseType::Block(_, _) => "HTTP(Block)",
                 HttpResponseType::BlockStream(_) => "HTTP(BlockStream)",
@@ -5345,6 +5623,10 @@ mod test {
 
         let tests = vec![
             HttpRequestType::GetNeighbors(http_request_metadata_ip.clone()),
+            HttpRequestType::GetSortition(
+                http_request_metadata_dns.clone(),
+                SortitionRequest::ConsensusHash(ConsensusHash([4u8; 20])),
+            ),
             HttpRequestType::GetBlock(http_request_metadata_dns.clone(), StacksBlockId([2u8; 32])),
             HttpRequestType::GetMicroblocksIndexed(
                 http_request_metadata_ip.clone(),
@@ -5384,6 +5666,14 @@ mod test {
                 http_request_metadata_ip.peer.port(),
                 http_request_metadata_ip.keep_alive,
             ),
+            HttpRequestPreamble::new(
+                HttpVersion::Http11,
+                "GET".to_string(),
+                format!("/v2/sortitions/consensus/{}", ConsensusHash([4u8; 20])),
+                http_request_metadata_dns.peer.hostname(),
+                http_request_metadata_dns.peer.port(),
+                http_request_metadata_dns.keep_alive,
+            ),
             HttpRequestPreamble::new(
                 HttpVersion::Http11,
                 "GET".to_string(),
@@ -5411,7 +5701,7 @@ mod test {
             ),
         ];
 
-        let expected_http_bodies = vec![vec![], vec![], vec![], tx_body];
+        let expected_http_bodies = vec![vec![], vec![], vec![], vec![], tx_body];
 
         for (test, (expected_http_preamble, expected_http_body)) in tests.iter().zip(
             expected_http_preambles
@@ -6296,45 +6586,66 @@ mod test {
     fn test_http_parse_proof_tip_query() {
         let query_txt = "tip=7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392";
         assert_eq!(
-            HttpRequestType::get_chain_tip_query(Some(query_txt)).unwrap(),
-            StacksBlockId::from_hex(
-                "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
+            HttpRequestType::get_chain_tip_query(Some(query_txt)),
+            TipRequest::SpecificTip(
+                StacksBlockId::from_hex(
+                    "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
+                )
+                .unwrap()
             )
-            .unwrap()
         );
 
         // first parseable tip is taken
         let query_txt_dup = "tip=7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392&tip=03e26bd68a8722f8b3861e2058edcafde094ad059e152754986c3573306698f1";
         assert_eq!(
-            HttpRequestType::get_chain_tip_query(Some(query_txt_dup)).unwrap(),
-            StacksBlockId::from_hex(
-                "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
+            HttpRequestType::get_chain_tip_query(Some(query_txt_dup)),
+            TipRequest::SpecificTip(
+                StacksBlockId::from_hex(
+                    "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
+                )
+                .unwrap()
             )
-            .unwrap()
         );
 
         // first parseable tip is taken
         let query_txt_dup = "tip=bad&tip=7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392&tip=03e26bd68a8722f8b3861e2058edcafde094ad059e152754986c3573306698f1";
         assert_eq!(
-            HttpRequestType::get_chain_tip_query(Some(query_txt_dup)).unwrap(),
-            StacksBlockId::from_hex(
-                "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
+            HttpRequestType::get_chain_tip_query(Some(query_txt_dup)),
+            TipRequest::SpecificTip(
+                StacksBlockId::from_hex(
+                    "7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392"
+                )
+                .unwrap()
             )
-            .unwrap()
         );
 
         // tip can be skipped
         let query_txt_bad = "tip=bad";
         assert_eq!(
             HttpRequestType::get_chain_tip_query(Some(query_txt_bad)),
-            None
+            TipRequest::UseLatestAnchoredTip
         );
 
         // tip can be skipped
         let query_txt_none = "tip=bad";
         assert_eq!(
             HttpRequestType::get_chain_tip_query(Some(query_txt_none)),
-            None
+            TipRequest::UseLatestAnchoredTip
+        );
+
+        // the unconfirmed tip can be requested
+        let query_txt_latest = "tip=latest";
+        assert_eq!(
+            HttpRequestType::get_chain_tip_query(Some(query_txt_latest)),
+            TipRequest::UseLatestUnconfirmedTip
+        );
+
+        // ...and it's subject to the same first-parseable-tip rule
+        let query_txt_latest_dup =
+            "tip=bad&tip=latest&tip=7070f213d719143d6045e08fd80f85014a161f8bbd3a42d1251576740826a392";
+        assert_eq!(
+            HttpRequestType::get_chain_tip_query(Some(query_txt_latest_dup)),
+            TipRequest::UseLatestUnconfirmedTip
         );
     }
 
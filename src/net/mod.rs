@@ -78,7 +78,7 @@ use vm::{
 use crate::codec::BURNCHAIN_HEADER_HASH_ENCODED_SIZE;
 use crate::types::chainstate::BlockHeaderHash;
 use crate::types::chainstate::PoxId;
-use crate::types::chainstate::{BurnchainHeaderHash, StacksAddress, StacksBlockId};
+use crate::types::chainstate::{BurnchainHeaderHash, StacksAddress, StacksBlockId, VRFSeed};
 use crate::types::StacksPublicKeyBuffer;
 use crate::util::hash::Sha256Sum;
 
@@ -1020,6 +1020,119 @@ pub struct RPCPoxInfoData {
     pub next_reward_cycle_in: u64,
 }
 
+/// The data we return on GET /v2/pox/reward_set/:cycle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCPoxRewardSetData {
+    pub reward_cycle: u64,
+    pub reward_cycle_start_height: u64,
+    /// the PoX anchor block chosen for this reward cycle, if there was one
+    pub anchor_block: Option<BlockHeaderHash>,
+    /// false if an anchor block was chosen, but this node never processed it
+    pub reward_set_known: bool,
+    /// one PoX address per reward slot.  Empty if the reward cycle pays out to the burn address.
+    pub reward_set: Vec<String>,
+}
+
+/// How much a PoX address was paid in a burnchain block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCPoxPayout {
+    pub recipient: String,
+    pub amount: u64,
+}
+
+/// The PoX payouts made by all of the block-commits in a burnchain block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCBurnBlockPayouts {
+    pub burn_block_height: u64,
+    pub burn_header_hash: String,
+    pub consensus_hash: ConsensusHash,
+    pub reward_recipients: Vec<RPCPoxPayout>,
+    pub burned: u64,
+}
+
+/// The data we return on GET /v2/pox/payouts/:cycle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCPoxPayoutsData {
+    pub reward_cycle: u64,
+    pub reward_cycle_start_height: u64,
+    pub blocks: Vec<RPCBurnBlockPayouts>,
+}
+
+/// A leader key registered in a sortition, as reported by GET /v2/sortitions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCSortitionLeaderKey {
+    pub txid: String,
+    pub vtxindex: u32,
+    pub public_key: String,
+    pub address: String,
+}
+
+/// A block-commit mined in a sortition, as reported by GET /v2/sortitions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCSortitionBlockCommit {
+    pub txid: String,
+    pub vtxindex: u32,
+    pub block_header_hash: BlockHeaderHash,
+    pub new_seed: VRFSeed,
+    pub parent_block_ptr: u32,
+    pub parent_vtxindex: u16,
+    pub key_block_ptr: u32,
+    pub key_vtxindex: u16,
+    /// address of the leader key this commit was made with, if it could be found
+    pub miner_address: Option<String>,
+    pub burn_fee: u64,
+    pub sunset_burn: u64,
+    pub input_txid: String,
+    pub input_vout: u32,
+    pub commit_outs: Vec<String>,
+    pub won: bool,
+}
+
+/// A block-commit that was intended for a sortition, but was mined in a later burnchain block
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCSortitionMissedCommit {
+    pub txid: String,
+    pub input_txid: String,
+    pub input_vout: u32,
+}
+
+/// One point of the median-window burn distribution that the sortition sampled from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCSortitionBurnSample {
+    pub txid: String,
+    pub burns: u64,
+    pub range_start: String,
+    pub range_end: String,
+}
+
+/// The data we return on GET /v2/sortitions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RPCSortitionData {
+    pub burn_block_height: u64,
+    pub burn_header_hash: String,
+    pub burn_header_timestamp: u64,
+    pub consensus_hash: ConsensusHash,
+    pub sortition_id: String,
+    pub parent_sortition_id: String,
+    pub pox_valid: bool,
+    pub total_burn: u64,
+    pub sortition: bool,
+    pub sortition_hash: String,
+    /// VRF seed of the winning block-commit, if there was a winner
+    pub vrf_seed: Option<VRFSeed>,
+    pub winning_block_txid: Option<String>,
+    pub winning_stacks_block_hash: Option<BlockHeaderHash>,
+    /// whether or not this node ever received the winning Stacks block
+    pub winning_block_received: bool,
+    /// whether or not the winning Stacks block was processed on this sortition's fork
+    pub winning_block_accepted: bool,
+    pub leader_keys: Vec<RPCSortitionLeaderKey>,
+    pub block_commits: Vec<RPCSortitionBlockCommit>,
+    pub missed_commits: Vec<RPCSortitionMissedCommit>,
+    /// None if this sortition was processed before burn distributions were stored
+    pub burn_distribution: Option<Vec<RPCSortitionBurnSample>>,
+}
+
 #[derive(Debug, Clone, PartialEq, Copy, Hash)]
 #[repr(u8)]
 pub enum HttpVersion {
@@ -1064,6 +1177,10 @@ pub struct CallReadOnlyResponse {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub result: Option<String>,
+    /// the result in the canonical JSON encoding of Clarity values
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_json: Option<serde_json::Value>,
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cause: Option<String>,
@@ -1173,7 +1290,21 @@ impl HttpRequestMetadata {
 #[derive(Serialize, Deserialize)]
 pub struct CallReadOnlyRequestBody {
     pub sender: String,
+    #[serde(default)]
     pub arguments: Vec<String>,
+    /// arguments in the canonical JSON encoding of Clarity values, as an alternative to
+    ///   hex-serialized `arguments`
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub arguments_json: Option<Vec<serde_json::Value>>,
+}
+
+/// Arguments to a read-only function call.  JSON-encoded arguments can only be decoded once
+///   the function's signature has been loaded from the chain state.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallReadOnlyArguments {
+    Serialized(Vec<Value>),
+    JSON(Vec<serde_json::Value>),
 }
 
 /// Items in the NeighborsInfo -- combines NeighborKey and NeighborAddress
@@ -1209,11 +1340,37 @@ pub struct RPCNeighborsInfo {
     pub outbound: Vec<RPCNeighbor>,
 }
 
+/// Which Stacks chain tip a request should be evaluated against, as given by the optional
+/// `tip` query parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TipRequest {
+    /// No `tip` was given -- use the canonical anchored chain tip
+    UseLatestAnchoredTip,
+    /// `tip=latest` -- use the canonical unconfirmed (microblock) chain tip if there is one, and
+    /// the canonical anchored chain tip if not
+    UseLatestUnconfirmedTip,
+    /// `tip=<index block hash>` -- use this chain tip
+    SpecificTip(StacksBlockId),
+}
+
+/// Which sortition a GET /v2/sortitions request is asking about
+#[derive(Debug, Clone, PartialEq)]
+pub enum SortitionRequest {
+    /// `/v2/sortitions/burn_height/:height` -- the sortition at this height in the canonical
+    /// burnchain fork
+    BurnHeight(u64),
+    /// `/v2/sortitions/consensus/:consensus_hash` -- the sortition with this consensus hash
+    ConsensusHash(ConsensusHash),
+}
+
 /// All HTTP request paths we support, and the arguments they carry in their paths
 #[derive(Debug, Clone, PartialEq)]
 pub enum HttpRequestType {
     GetInfo(HttpRequestMetadata),
-    GetPoxInfo(HttpRequestMetadata, Option<StacksBlockId>),
+    GetPoxInfo(HttpRequestMetadata, TipRequest),
+    GetPoxRewardSet(HttpRequestMetadata, u64),
+    GetPoxPayouts(HttpRequestMetadata, u64),
+    GetSortition(HttpRequestMetadata, SortitionRequest),
     GetNeighbors(HttpRequestMetadata),
     GetBlock(HttpRequestMetadata, StacksBlockId),
     GetMicroblocksIndexed(HttpRequestMetadata, StacksBlockId),
@@ -1222,20 +1379,15 @@ pub enum HttpRequestType {
     GetTransactionUnconfirmed(HttpRequestMetadata, Txid),
     PostTransaction(HttpRequestMetadata, StacksTransaction, Option<Attachment>),
     PostBlock(HttpRequestMetadata, ConsensusHash, StacksBlock),
-    PostMicroblock(HttpRequestMetadata, StacksMicroblock, Option<StacksBlockId>),
-    GetAccount(
-        HttpRequestMetadata,
-        PrincipalData,
-        Option<StacksBlockId>,
-        bool,
-    ),
+    PostMicroblock(HttpRequestMetadata, StacksMicroblock, TipRequest),
+    GetAccount(HttpRequestMetadata, PrincipalData, TipRequest, bool),
     GetMapEntry(
         HttpRequestMetadata,
         StacksAddress,
         ContractName,
         ClarityName,
         Value,
-        Option<StacksBlockId>,
+        TipRequest,
         bool,
     ),
     CallReadOnlyFunction(
@@ -1244,23 +1396,18 @@ pub enum HttpRequestType {
         ContractName,
         PrincipalData,
         ClarityName,
-        Vec<Value>,
-        Option<StacksBlockId>,
+        CallReadOnlyArguments,
+        TipRequest,
     ),
     GetTransferCost(HttpRequestMetadata),
     GetContractSrc(
         HttpRequestMetadata,
         StacksAddress,
         ContractName,
-        Option<StacksBlockId>,
+        TipRequest,
         bool,
     ),
-    GetContractABI(
-        HttpRequestMetadata,
-        StacksAddress,
-        ContractName,
-        Option<StacksBlockId>,
-    ),
+    GetContractABI(HttpRequestMetadata, StacksAddress, ContractName, TipRequest),
     OptionsPreflight(HttpRequestMetadata, String),
     GetAttachment(HttpRequestMetadata, Hash160),
     GetAttachmentsInv(HttpRequestMetadata, StacksBlockId, HashSet<u32>),
@@ -1269,7 +1416,7 @@ pub enum HttpRequestType {
         StacksAddress,
         ContractName,
         TraitIdentifier,
-        Option<StacksBlockId>,
+        TipRequest,
     ),
     /// catch-all for any errors we should surface from parsing
     ClientError(HttpRequestMetadata, ClientError),
@@ -1347,6 +1494,9 @@ impl From<&HttpRequestType> for HttpResponseMetadata {
 pub enum HttpResponseType {
     PeerInfo(HttpResponseMetadata, RPCPeerInfoData),
     PoxInfo(HttpResponseMetadata, RPCPoxInfoData),
+    PoxRewardSet(HttpResponseMetadata, RPCPoxRewardSetData),
+    PoxPayouts(HttpResponseMetadata, RPCPoxPayoutsData),
+    Sortition(HttpResponseMetadata, RPCSortitionData),
     Neighbors(HttpResponseMetadata, RPCNeighborsInfo),
     Block(HttpResponseMetadata, StacksBlock),
     BlockStream(HttpResponseMetadata),
@@ -1661,6 +1811,11 @@ pub const DENY_BAN_DURATION: u64 = 86400; // seconds (1 day)
 
 pub const DENY_MIN_BAN_DURATION: u64 = 2;
 
+// how much a peer's persistent reputation score goes up when it misbehaves
+pub const PEER_PENALTY_INVALID_MESSAGE: f64 = 10.0; // malformed or invalid message
+pub const PEER_PENALTY_NACK: f64 = 1.0; // each NACK is cheap, but a flood of them adds up
+pub const PEER_PENALTY_BAN: f64 = 50.0; // banned outright (e.g. for sending an invalid block)
+
 /// Result of doing network work
 pub struct NetworkResult {
     pub download_pox_id: Option<PoxId>, // PoX ID as it was when we begin downloading blocks (set if we have downloaded new blocks)
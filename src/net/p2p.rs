@@ -33,6 +33,7 @@ use mio;
 use mio::net as mio_net;
 use rand::prelude::*;
 use rand::thread_rng;
+use rusqlite::Transaction;
 
 use burnchains::Address;
 use burnchains::Burnchain;
@@ -53,6 +54,7 @@ use net::connection::ReplyHandleHttp;
 use net::connection::ReplyHandleP2P;
 use net::db::LocalPeer;
 use net::db::PeerDB;
+use net::db::PeerReputation;
 use net::download::BlockDownloader;
 use net::inv::*;
 use net::neighbors::*;
@@ -1107,6 +1109,16 @@ impl PeerNetwork {
                 now + DENY_BAN_DURATION
             };
 
+            // repeat offenders get banned for longer
+            let reputation = PeerNetwork::penalize_peer(
+                &mut tx,
+                &self.connection_opts,
+                &neighbor_key,
+                PEER_PENALTY_BAN,
+                now,
+            )?;
+            let penalty = cmp::max(penalty, reputation.banned_until);
+
             debug!(
                 "Ban peer {:?} for {}s until {}",
                 &neighbor_key,
@@ -1127,6 +1139,104 @@ impl PeerNetwork {
         Ok(disconnect)
     }
 
+    /// Add a penalty to a peer's persistent reputation, and ban the peer if its score gets too
+    /// high.  Returns the peer's updated reputation.
+    fn penalize_peer<'a>(
+        tx: &mut Transaction<'a>,
+        connection_opts: &ConnectionOptions,
+        neighbor_key: &NeighborKey,
+        penalty: f64,
+        now: u64,
+    ) -> Result<PeerReputation, net_error> {
+        let mut reputation = match PeerDB::get_peer_reputation(
+            tx,
+            neighbor_key.network_id,
+            &neighbor_key.addrbytes,
+            neighbor_key.port,
+        )? {
+            Some(reputation) => reputation,
+            None => PeerReputation::new(
+                neighbor_key.network_id,
+                &neighbor_key.addrbytes,
+                neighbor_key.port,
+                now,
+            ),
+        };
+
+        reputation.add_penalty(penalty, connection_opts.peer_reputation_half_life, now);
+        if reputation.try_ban(
+            connection_opts.peer_reputation_ban_threshold,
+            connection_opts.peer_reputation_min_ban_duration,
+            connection_opts.peer_reputation_max_ban_duration,
+            now,
+        ) {
+            info!(
+                "Ban peer {:?} until {} for its poor reputation (ban #{})",
+                neighbor_key, reputation.banned_until, reputation.num_bans
+            );
+        }
+
+        PeerDB::put_peer_reputation(tx, &reputation, now)?;
+        Ok(reputation)
+    }
+
+    /// Charge each peer's persistent reputation for the invalid messages and NACKs it has sent
+    /// since the last time this was called.  The peer DB is only written to if some peer has
+    /// something to be charged for.  Return the vec of event IDs of peers that are now banned, so
+    /// they can be disconnected.
+    fn process_peer_reputations(&mut self) -> Result<Vec<usize>, net_error> {
+        if cfg!(test) && self.connection_opts.disable_network_bans {
+            return Ok(vec![]);
+        }
+
+        let penalties: Vec<(usize, f64)> = self
+            .peers
+            .iter_mut()
+            .filter_map(|(event_id, convo)| {
+                let penalty = convo.stats.take_reputation_penalty();
+                if penalty > 0.0 {
+                    Some((*event_id, penalty))
+                } else {
+                    None
+                }
+            })
+            .collect();
+        if penalties.is_empty() {
+            // reputations only decay when they're charged, so there's nothing to write
+            return Ok(vec![]);
+        }
+
+        let now = get_epoch_time_secs();
+        let mut tx = self.peerdb.tx_begin()?;
+        let mut disconnect = vec![];
+        for (event_id, penalty) in penalties.into_iter() {
+            let convo = match self.peers.get(&event_id) {
+                Some(convo) => convo,
+                None => continue,
+            };
+            if let Some(neighbor) = Neighbor::from_conversation(&tx, convo)? {
+                if neighbor.is_allowed() {
+                    continue;
+                }
+            }
+
+            let neighbor_key = convo.to_neighbor_key();
+            let reputation = PeerNetwork::penalize_peer(
+                &mut tx,
+                &self.connection_opts,
+                &neighbor_key,
+                penalty,
+                now,
+            )?;
+            if reputation.is_banned(now) {
+                disconnect.push(event_id);
+            }
+        }
+
+        tx.commit()?;
+        Ok(disconnect)
+    }
+
     /// Get the neighbor if we know of it and it's public key is unexpired.
     fn lookup_peer(
         &self,
@@ -4210,7 +4320,10 @@ impl PeerNetwork {
             // prune back our connections if it's been a while
             // (only do this if we're done with all other tasks).
             // Also, process banned peers.
-            let mut dead_events = self.process_bans()?;
+            let mut dead_events = self.process_peer_reputations()?;
+            dead_events.append(&mut self.process_bans()?);
+            dead_events.sort();
+            dead_events.dedup();
             for dead in dead_events.drain(..) {
                 debug!(
                     "{:?}: Banned connection on event {}",
@@ -17,6 +17,7 @@
  along with Blockstack. If not, see <http://www.gnu.org/licenses/>.
 */
 
+use std::cmp;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::VecDeque;
@@ -35,7 +36,12 @@ use burnchains::Burnchain;
 use burnchains::BurnchainView;
 use burnchains::*;
 use chainstate::burn::db::sortdb::SortitionDB;
+use chainstate::burn::BlockSnapshot;
 use chainstate::burn::ConsensusHash;
+use chainstate::coordinator::{
+    calculate_paid_commit_rewards, get_reward_cycle_info, Error as coordinator_error,
+    OnChainRewardSetProvider, PoxAnchorBlockStatus,
+};
 use chainstate::stacks::db::blocks::CheckError;
 use chainstate::stacks::db::{
     blocks::MINIMUM_TX_FEE_RATE_PER_BYTE, BlockStreamData, StacksChainState,
@@ -69,18 +75,24 @@ use net::ProtocolFamily;
 use net::StacksHttp;
 use net::StacksHttpMessage;
 use net::StacksMessageType;
+use net::TipRequest;
 use net::UnconfirmedTransactionResponse;
 use net::UnconfirmedTransactionStatus;
 use net::UrlString;
 use net::HTTP_REQUEST_ID_RESERVED;
 use net::MAX_NEIGHBORS_DATA_LEN;
 use net::{
-    AccountEntryResponse, AttachmentPage, CallReadOnlyResponse, ContractSrcResponse,
-    GetAttachmentResponse, GetAttachmentsInvResponse, MapEntryResponse,
+    AccountEntryResponse, AttachmentPage, CallReadOnlyArguments, CallReadOnlyResponse,
+    ContractSrcResponse, GetAttachmentResponse, GetAttachmentsInvResponse, MapEntryResponse,
 };
 use net::{BlocksData, GetIsTraitImplementedResponse};
+use net::{RPCBurnBlockPayouts, RPCPoxPayout, RPCPoxPayoutsData, RPCPoxRewardSetData};
 use net::{RPCNeighbor, RPCNeighborsInfo};
 use net::{RPCPeerInfoData, RPCPoxInfoData};
+use net::{
+    RPCSortitionBlockCommit, RPCSortitionBurnSample, RPCSortitionData, RPCSortitionLeaderKey,
+    RPCSortitionMissedCommit, SortitionRequest,
+};
 use util::db::DBConn;
 use util::db::Error as db_error;
 use util::get_epoch_time_secs;
@@ -97,14 +109,17 @@ use vm::{
     errors::Error as ClarityRuntimeError,
     errors::Error::Unchecked,
     errors::InterpreterError,
-    types::{PrincipalData, QualifiedContractIdentifier, StandardPrincipalData},
+    types::{
+        FixedFunction, FunctionType, PrincipalData, QualifiedContractIdentifier,
+        StandardPrincipalData, TypeSignature,
+    },
     ClarityName, ContractName, SymbolicExpression, Value,
 };
 
 use crate::clarity_vm::database::marf::MarfedKV;
 use crate::types::chainstate::BlockHeaderHash;
 use crate::types::chainstate::{
-    BurnchainHeaderHash, StacksAddress, StacksBlockHeader, StacksBlockId,
+    BurnchainHeaderHash, SortitionId, StacksAddress, StacksBlockHeader, StacksBlockId,
 };
 use crate::{
     chainstate::burn::operations::leader_block_commit::OUTPUTS_PER_COMMIT, types, util,
@@ -122,6 +137,16 @@ pub struct RPCHandlerArgs<'a> {
     pub event_observer: Option<&'a dyn MemPoolEventDispatcher>,
 }
 
+/// Reward sets already served over RPC, keyed by reward cycle.  Computing one reruns the chains
+/// coordinator's reward set calculation, so each is computed at most once per canonical
+/// sortition tip.  The whole cache is dropped when the tip changes, since a burnchain reorg can
+/// change the anchor block of any cycle that is still in the reorged fork.
+#[derive(Debug, Default)]
+pub struct RPCRewardSetCache {
+    tip: Option<SortitionId>,
+    reward_sets: HashMap<u64, RPCPoxRewardSetData>,
+}
+
 pub struct ConversationHttp {
     network_id: u32,
     connection: ConnectionHttp,
@@ -415,6 +440,327 @@ impl RPCPoxInfoData {
     }
 }
 
+/// Convert a coordinator error encountered while serving a request into a net error
+fn coordinator_to_net_error(e: coordinator_error) -> net_error {
+    match e {
+        coordinator_error::DBError(e) => net_error::DBError(e),
+        coordinator_error::ChainstateError(e) => net_error::from(e),
+        e => net_error::ChainstateError(format!("{:?}", &e)),
+    }
+}
+
+/// Find the first burnchain block height of a reward cycle that has started in the canonical
+/// burnchain fork.  The cycle comes straight from a request URL, so it is bounded by the tip's
+/// cycle before it is converted to a height, which would otherwise overflow.
+fn started_reward_cycle_start_height(
+    burnchain: &Burnchain,
+    tip_height: u64,
+    reward_cycle: u64,
+) -> Result<u64, net_error> {
+    match burnchain.block_height_to_reward_cycle(tip_height) {
+        Some(tip_cycle) if reward_cycle <= tip_cycle => {}
+        _ => return Err(net_error::NotFoundError),
+    }
+    let start_height = burnchain.reward_cycle_to_block_height(reward_cycle);
+    if start_height > tip_height {
+        return Err(net_error::NotFoundError);
+    }
+    Ok(start_height)
+}
+
+impl RPCPoxRewardSetData {
+    /// Recompute the reward set of a reward cycle that has started in the canonical burnchain
+    /// fork, exactly as the chains coordinator did when it processed the cycle's first block.
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        burnchain: &Burnchain,
+        reward_cycle: u64,
+    ) -> Result<RPCPoxRewardSetData, net_error> {
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
+        RPCPoxRewardSetData::from_db_at_tip(sortdb, chainstate, burnchain, &tip, reward_cycle)
+    }
+
+    /// Like `from_db()`, but serve the reward set out of `cache` if it was already computed at
+    /// the current canonical sortition tip.
+    pub fn from_cache_or_db(
+        cache: &mut RPCRewardSetCache,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        burnchain: &Burnchain,
+        reward_cycle: u64,
+    ) -> Result<RPCPoxRewardSetData, net_error> {
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
+        if cache.tip.as_ref() != Some(&tip.sortition_id) {
+            cache.reward_sets.clear();
+            cache.tip = Some(tip.sortition_id.clone());
+        }
+        if let Some(reward_set_data) = cache.reward_sets.get(&reward_cycle) {
+            return Ok(reward_set_data.clone());
+        }
+
+        let reward_set_data =
+            RPCPoxRewardSetData::from_db_at_tip(sortdb, chainstate, burnchain, &tip, reward_cycle)?;
+        cache
+            .reward_sets
+            .insert(reward_cycle, reward_set_data.clone());
+        Ok(reward_set_data)
+    }
+
+    fn from_db_at_tip(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        burnchain: &Burnchain,
+        tip: &BlockSnapshot,
+        reward_cycle: u64,
+    ) -> Result<RPCPoxRewardSetData, net_error> {
+        let start_height =
+            started_reward_cycle_start_height(burnchain, tip.block_height, reward_cycle)?;
+        let start_snapshot = SortitionDB::get_ancestor_snapshot(
+            &sortdb.index_conn(),
+            start_height,
+            &tip.sortition_id,
+        )?
+        .ok_or(net_error::NotFoundError)?;
+
+        let reward_cycle_info = get_reward_cycle_info(
+            start_height,
+            &start_snapshot.parent_burn_header_hash,
+            &start_snapshot.parent_sortition_id,
+            burnchain,
+            chainstate,
+            sortdb,
+            &OnChainRewardSetProvider(),
+        )
+        .map_err(coordinator_to_net_error)?
+        .ok_or(net_error::NotFoundError)?;
+
+        let reward_set_known = reward_cycle_info.is_reward_info_known();
+        let (anchor_block, reward_set) = match reward_cycle_info.anchor_status {
+            PoxAnchorBlockStatus::SelectedAndKnown(anchor_block, reward_set) => (
+                Some(anchor_block),
+                reward_set.iter().map(|addr| addr.to_string()).collect(),
+            ),
+            PoxAnchorBlockStatus::SelectedAndUnknown(anchor_block) => (Some(anchor_block), vec![]),
+            PoxAnchorBlockStatus::NotSelected => (None, vec![]),
+        };
+
+        Ok(RPCPoxRewardSetData {
+            reward_cycle,
+            reward_cycle_start_height: start_height,
+            anchor_block,
+            reward_set_known,
+            reward_set,
+        })
+    }
+}
+
+impl RPCPoxPayoutsData {
+    /// Load the PoX payouts made by the block-commits in each burnchain block of a reward cycle
+    /// in the canonical burnchain fork, up to the burnchain tip.
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        burnchain: &Burnchain,
+        reward_cycle: u64,
+    ) -> Result<RPCPoxPayoutsData, net_error> {
+        let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
+        let start_height =
+            started_reward_cycle_start_height(burnchain, tip.block_height, reward_cycle)?;
+        let last_height = cmp::min(
+            start_height.saturating_add((burnchain.pox_constants.reward_cycle_length as u64) - 1),
+            tip.block_height,
+        );
+
+        // walk back from the last block in the cycle via parent pointers, which is cheaper than
+        // an ancestor lookup per block
+        let mut cursor = SortitionDB::get_ancestor_snapshot(
+            &sortdb.index_conn(),
+            last_height,
+            &tip.sortition_id,
+        )?
+        .ok_or(net_error::NotFoundError)?;
+        let mut blocks = vec![];
+        loop {
+            let commits =
+                SortitionDB::get_block_commits_by_block(sortdb.conn(), &cursor.sortition_id)?;
+            let paid_rewards = calculate_paid_commit_rewards(commits.iter());
+            let mut reward_recipients: Vec<_> = paid_rewards
+                .pox
+                .into_iter()
+                .map(|(addr, amount)| RPCPoxPayout {
+                    recipient: addr.to_string(),
+                    amount,
+                })
+                .collect();
+            reward_recipients.sort_by(|a, b| a.recipient.cmp(&b.recipient));
+
+            blocks.push(RPCBurnBlockPayouts {
+                burn_block_height: cursor.block_height,
+                burn_header_hash: cursor.burn_header_hash.to_hex(),
+                consensus_hash: cursor.consensus_hash.clone(),
+                reward_recipients,
+                burned: paid_rewards.burns,
+            });
+
+            if cursor.block_height <= start_height {
+                break;
+            }
+            cursor = SortitionDB::get_block_snapshot(sortdb.conn(), &cursor.parent_sortition_id)?
+                .ok_or(net_error::NotFoundError)?;
+        }
+        blocks.reverse();
+
+        Ok(RPCPoxPayoutsData {
+            reward_cycle,
+            reward_cycle_start_height: start_height,
+            blocks,
+        })
+    }
+}
+
+impl RPCSortitionData {
+    /// Load the results of a sortition -- the commits and keys that were mined in it, the burn
+    /// distribution that was sampled, and its winner.
+    pub fn from_db(
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        sortition_req: &SortitionRequest,
+    ) -> Result<RPCSortitionData, net_error> {
+        let snapshot = match sortition_req {
+            SortitionRequest::BurnHeight(height) => {
+                let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn())?;
+                if *height > tip.block_height {
+                    return Err(net_error::NotFoundError);
+                }
+                SortitionDB::get_ancestor_snapshot(
+                    &sortdb.index_conn(),
+                    *height,
+                    &tip.sortition_id,
+                )?
+            }
+            SortitionRequest::ConsensusHash(consensus_hash) => {
+                SortitionDB::get_block_snapshot_consensus(sortdb.conn(), consensus_hash)?
+            }
+        }
+        .ok_or(net_error::NotFoundError)?;
+
+        let leader_keys =
+            SortitionDB::get_leader_keys_by_block(sortdb.conn(), &snapshot.sortition_id)?
+                .into_iter()
+                .map(|key| RPCSortitionLeaderKey {
+                    txid: key.txid.to_hex(),
+                    vtxindex: key.vtxindex,
+                    public_key: key.public_key.to_hex(),
+                    address: key.address.to_string(),
+                })
+                .collect();
+
+        let commits =
+            SortitionDB::get_block_commits_by_block(sortdb.conn(), &snapshot.sortition_id)?;
+        let mut block_commits = Vec::with_capacity(commits.len());
+        let mut vrf_seed = None;
+        for commit in commits.into_iter() {
+            let won = snapshot.sortition && commit.txid == snapshot.winning_block_txid;
+            if won {
+                vrf_seed = Some(commit.new_seed.clone());
+            }
+            let miner_address = SortitionDB::get_leader_key_at(
+                &sortdb.index_conn(),
+                commit.key_block_ptr as u64,
+                commit.key_vtxindex as u32,
+                &snapshot.sortition_id,
+            )?
+            .map(|key| key.address.to_string());
+
+            block_commits.push(RPCSortitionBlockCommit {
+                txid: commit.txid.to_hex(),
+                vtxindex: commit.vtxindex,
+                block_header_hash: commit.block_header_hash,
+                new_seed: commit.new_seed,
+                parent_block_ptr: commit.parent_block_ptr,
+                parent_vtxindex: commit.parent_vtxindex,
+                key_block_ptr: commit.key_block_ptr,
+                key_vtxindex: commit.key_vtxindex,
+                miner_address,
+                burn_fee: commit.burn_fee,
+                sunset_burn: commit.sunset_burn,
+                input_txid: commit.input.0.to_hex(),
+                input_vout: commit.input.1,
+                commit_outs: commit
+                    .commit_outs
+                    .iter()
+                    .map(|addr| addr.to_string())
+                    .collect(),
+                won,
+            });
+        }
+
+        let missed_commits =
+            SortitionDB::get_missed_commits_by_intended(sortdb.conn(), &snapshot.sortition_id)?
+                .into_iter()
+                .map(|missed| RPCSortitionMissedCommit {
+                    txid: missed.txid.to_hex(),
+                    input_txid: missed.input.0.to_hex(),
+                    input_vout: missed.input.1,
+                })
+                .collect();
+
+        let burn_distribution =
+            sortdb
+                .get_burn_distribution(&snapshot.sortition_id)?
+                .map(|samples| {
+                    samples
+                        .into_iter()
+                        .map(|sample| RPCSortitionBurnSample {
+                            txid: sample.candidate.txid.to_hex(),
+                            burns: u64::try_from(sample.burns).unwrap_or(u64::MAX),
+                            range_start: format!("{}", sample.range_start),
+                            range_end: format!("{}", sample.range_end),
+                        })
+                        .collect()
+                });
+
+        let (winning_block_txid, winning_stacks_block_hash, winning_block_received) =
+            if snapshot.sortition {
+                let received = StacksChainState::get_staging_block_status(
+                    chainstate.db(),
+                    &snapshot.consensus_hash,
+                    &snapshot.winning_stacks_block_hash,
+                )?
+                .is_some();
+                (
+                    Some(snapshot.winning_block_txid.to_hex()),
+                    Some(snapshot.winning_stacks_block_hash.clone()),
+                    received,
+                )
+            } else {
+                (None, None, false)
+            };
+
+        Ok(RPCSortitionData {
+            burn_block_height: snapshot.block_height,
+            burn_header_hash: snapshot.burn_header_hash.to_hex(),
+            burn_header_timestamp: snapshot.burn_header_timestamp,
+            consensus_hash: snapshot.consensus_hash,
+            sortition_id: snapshot.sortition_id.to_hex(),
+            parent_sortition_id: snapshot.parent_sortition_id.to_hex(),
+            pox_valid: snapshot.pox_valid,
+            total_burn: snapshot.total_burn,
+            sortition: snapshot.sortition,
+            sortition_hash: snapshot.sortition_hash.to_hex(),
+            vrf_seed,
+            winning_block_txid,
+            winning_stacks_block_hash,
+            winning_block_received,
+            winning_block_accepted: snapshot.sortition && snapshot.stacks_block_accepted,
+            leader_keys,
+            block_commits,
+            missed_commits,
+            burn_distribution,
+        })
+    }
+}
+
 impl RPCNeighborsInfo {
     /// Load neighbor address information from the peer network
     pub fn from_p2p(
@@ -676,6 +1022,122 @@ impl ConversationHttp {
         }
     }
 
+    /// Handle a GET PoX reward set.
+    /// The response will be synchronously written to the given fd (so use a fd that can buffer!)
+    fn handle_getpox_reward_set<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        burnchain: &Burnchain,
+        reward_set_cache: &mut RPCRewardSetCache,
+        reward_cycle: u64,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        match RPCPoxRewardSetData::from_cache_or_db(
+            reward_set_cache,
+            sortdb,
+            chainstate,
+            burnchain,
+            reward_cycle,
+        ) {
+            Ok(reward_set_data) => {
+                let response = HttpResponseType::PoxRewardSet(response_metadata, reward_set_data);
+                response.send(http, fd)
+            }
+            Err(net_error::NotFoundError) => {
+                debug!("Reward cycle not found: {:?}", req);
+                let response = HttpResponseType::NotFound(
+                    response_metadata,
+                    "Reward cycle has not started".to_string(),
+                );
+                response.send(http, fd)
+            }
+            Err(e) => {
+                warn!("Failed to get PoX reward set {:?}: {:?}", req, &e);
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to compute reward set".to_string(),
+                );
+                response.send(http, fd)
+            }
+        }
+    }
+
+    /// Handle a GET PoX payouts.
+    /// The response will be synchronously written to the given fd (so use a fd that can buffer!)
+    fn handle_getpox_payouts<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        burnchain: &Burnchain,
+        reward_cycle: u64,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        match RPCPoxPayoutsData::from_db(sortdb, burnchain, reward_cycle) {
+            Ok(payouts_data) => {
+                let response = HttpResponseType::PoxPayouts(response_metadata, payouts_data);
+                response.send(http, fd)
+            }
+            Err(net_error::NotFoundError) => {
+                debug!("Reward cycle not found: {:?}", req);
+                let response = HttpResponseType::NotFound(
+                    response_metadata,
+                    "Reward cycle has not started".to_string(),
+                );
+                response.send(http, fd)
+            }
+            Err(e) => {
+                warn!("Failed to get PoX payouts {:?}: {:?}", req, &e);
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to query PoX payouts".to_string(),
+                );
+                response.send(http, fd)
+            }
+        }
+    }
+
+    /// Handle a GET sortition.
+    /// The response will be synchronously written to the given fd (so use a fd that can buffer!)
+    fn handle_getsortition<W: Write>(
+        http: &mut StacksHttp,
+        fd: &mut W,
+        req: &HttpRequestType,
+        sortdb: &SortitionDB,
+        chainstate: &StacksChainState,
+        sortition_req: &SortitionRequest,
+    ) -> Result<(), net_error> {
+        let response_metadata = HttpResponseMetadata::from(req);
+
+        match RPCSortitionData::from_db(sortdb, chainstate, sortition_req) {
+            Ok(sortition_data) => {
+                let response = HttpResponseType::Sortition(response_metadata, sortition_data);
+                response.send(http, fd)
+            }
+            Err(net_error::NotFoundError) => {
+                debug!("Sortition not found: {:?}", req);
+                let response = HttpResponseType::NotFound(
+                    response_metadata,
+                    "Failed to find sortition".to_string(),
+                );
+                response.send(http, fd)
+            }
+            Err(e) => {
+                warn!("Failed to get sortition {:?}: {:?}", req, &e);
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    "Failed to query sortition".to_string(),
+                );
+                response.send(http, fd)
+            }
+        }
+    }
+
     fn handle_getattachmentsinv<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
@@ -1190,16 +1652,51 @@ impl ConversationHttp {
         contract_name: &ContractName,
         function: &ClarityName,
         sender: &PrincipalData,
-        args: &[Value],
+        args: &CallReadOnlyArguments,
         options: &ConnectionOptions,
     ) -> Result<(), net_error> {
         let response_metadata = HttpResponseMetadata::from(req);
         let contract_identifier =
             QualifiedContractIdentifier::new(contract_addr.clone().into(), contract_name.clone());
 
+        let args = match args {
+            CallReadOnlyArguments::Serialized(args) => args.clone(),
+            CallReadOnlyArguments::JSON(args_json) => {
+                match ConversationHttp::decode_json_call_arguments(
+                    sortdb,
+                    chainstate,
+                    tip,
+                    &contract_identifier,
+                    function,
+                    args_json,
+                ) {
+                    Ok(Some(args)) => args,
+                    Ok(None) => {
+                        let response = HttpResponseType::NotFound(
+                            response_metadata,
+                            "Chain tip not found".into(),
+                        );
+                        return response.send(http, fd).map(|_| ());
+                    }
+                    Err(cause) => {
+                        let response = HttpResponseType::CallReadOnlyFunction(
+                            response_metadata,
+                            CallReadOnlyResponse {
+                                okay: false,
+                                result: None,
+                                result_json: None,
+                                cause: Some(cause),
+                            },
+                        );
+                        return response.send(http, fd).map(|_| ());
+                    }
+                }
+            }
+        };
+
         let args: Vec<_> = args
-            .iter()
-            .map(|x| SymbolicExpression::atom_value(x.clone()))
+            .into_iter()
+            .map(SymbolicExpression::atom_value)
             .collect();
         let mainnet = chainstate.mainnet;
         let mut cost_limit = options.read_only_call_limit.clone();
@@ -1233,6 +1730,7 @@ impl ConversationHttp {
                 CallReadOnlyResponse {
                     okay: true,
                     result: Some(format!("0x{}", data.serialize())),
+                    result_json: Some(data.to_json()),
                     cause: None,
                 },
             ),
@@ -1245,6 +1743,7 @@ impl ConversationHttp {
                         CallReadOnlyResponse {
                             okay: false,
                             result: None,
+                            result_json: None,
                             cause: Some("NotReadOnly".to_string()),
                         },
                     )
@@ -1254,6 +1753,7 @@ impl ConversationHttp {
                     CallReadOnlyResponse {
                         okay: false,
                         result: None,
+                        result_json: None,
                         cause: Some(e.to_string()),
                     },
                 ),
@@ -1265,6 +1765,49 @@ impl ConversationHttp {
         response.send(http, fd).map(|_| ())
     }
 
+    /// Decode JSON-encoded arguments to a read-only function call against the function's
+    /// declared argument types.  Returns Ok(None) if the chain tip doesn't exist, and Err with
+    /// the cause if the function doesn't exist or the arguments don't match its signature.
+    fn decode_json_call_arguments(
+        sortdb: &SortitionDB,
+        chainstate: &mut StacksChainState,
+        tip: &StacksBlockId,
+        contract_identifier: &QualifiedContractIdentifier,
+        function: &ClarityName,
+        args_json: &[serde_json::Value],
+    ) -> Result<Option<Vec<Value>>, String> {
+        let function_type_res =
+            chainstate.maybe_read_only_clarity_tx(&sortdb.index_conn(), tip, |clarity_tx| {
+                clarity_tx.with_analysis_db_readonly(|analysis_db| {
+                    match analysis_db
+                        .get_read_only_function_type(contract_identifier, function.as_str())?
+                    {
+                        Some(function_type) => Ok(Some(function_type)),
+                        None => analysis_db
+                            .get_public_function_type(contract_identifier, function.as_str()),
+                    }
+                })
+            });
+
+        let function_type = match function_type_res {
+            Ok(Some(Ok(Some(function_type)))) => function_type,
+            Ok(Some(Ok(None))) => {
+                return Err(CheckErrors::UndefinedFunction(function.to_string()).to_string())
+            }
+            Ok(Some(Err(e))) => return Err(e.to_string()),
+            Ok(None) | Err(_) => return Ok(None),
+        };
+        let arg_types: Vec<_> = match function_type {
+            FunctionType::Fixed(FixedFunction { args, .. }) => {
+                args.into_iter().map(|arg| arg.signature).collect()
+            }
+            _ => return Err(format!("Unsupported function type for {}", function)),
+        };
+        TypeSignature::args_from_json(&arg_types, args_json)
+            .map(Some)
+            .map_err(|e| format!("Failed to decode arguments_json: {}", e))
+    }
+
     /// Handle a GET to fetch a contract's source code, given the chain tip.  Optionally returns a
     /// MARF proof as well.
     fn handle_get_contract_src<W: Write>(
@@ -1548,61 +2091,99 @@ impl ConversationHttp {
     /// Load up the canonical Stacks chain tip.  Note that this is subject to both burn chain block
     /// Stacks block availability -- different nodes with different partial replicas of the Stacks chain state
     /// will return different values here.
-    /// tip_opt is given by the HTTP request as the optional query parameter for the chain tip
-    /// hash.  It will be None if there was no paramter given.
+    /// tip_req is given by the HTTP request as the optional query parameter for the chain tip
+    /// hash.  It will be UseLatestAnchoredTip if there was no paramter given.
     /// The order of chain tips this method prefers is as follows:
-    /// * tip_opt, if it's Some(..),
-    /// * the unconfirmed canonical stacks chain tip, if initialized
+    /// * the given tip, if it's SpecificTip(..),
+    /// * the unconfirmed canonical stacks chain tip, if it's UseLatestUnconfirmedTip and the
+    /// unconfirmed state is readable
     /// * the confirmed canonical stacks chain tip
     fn handle_load_stacks_chain_tip<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        tip_opt: Option<&StacksBlockId>,
+        tip_req: &TipRequest,
         sortdb: &SortitionDB,
-        chainstate: &StacksChainState,
+        chainstate: &mut StacksChainState,
     ) -> Result<Option<StacksBlockId>, net_error> {
-        match tip_opt {
-            Some(tip) => Ok(Some(*tip).clone()),
-            None => match chainstate.get_stacks_chain_tip(sortdb)? {
-                Some(tip) => Ok(Some(StacksBlockHeader::make_index_block_hash(
-                    &tip.consensus_hash,
-                    &tip.anchored_block_hash,
-                ))),
-                None => {
+        match tip_req {
+            TipRequest::SpecificTip(tip) => {
+                if chainstate.is_clarity_state_pruned(tip)? {
                     let response_metadata = HttpResponseMetadata::from(req);
-                    warn!("Failed to load Stacks chain tip");
-                    let response = HttpResponseType::ServerError(
+                    let response = HttpResponseType::BadRequest(
                         response_metadata,
-                        format!("Failed to load Stacks chain tip"),
+                        format!(
+                            "The state at chain tip {} has been pruned by this node",
+                            tip
+                        ),
                     );
-                    response.send(http, fd).and_then(|_| Ok(None))
+                    return response.send(http, fd).and_then(|_| Ok(None));
                 }
-            },
+                return Ok(Some(tip.clone()));
+            }
+            TipRequest::UseLatestUnconfirmedTip => {
+                if let Some(unconfirmed_tip) =
+                    ConversationHttp::get_readable_unconfirmed_tip(chainstate)
+                {
+                    return Ok(Some(unconfirmed_tip));
+                }
+            }
+            TipRequest::UseLatestAnchoredTip => {}
+        }
+
+        match chainstate.get_stacks_chain_tip(sortdb)? {
+            Some(tip) => Ok(Some(StacksBlockHeader::make_index_block_hash(
+                &tip.consensus_hash,
+                &tip.anchored_block_hash,
+            ))),
+            None => {
+                let response_metadata = HttpResponseMetadata::from(req);
+                warn!("Failed to load Stacks chain tip");
+                let response = HttpResponseType::ServerError(
+                    response_metadata,
+                    format!("Failed to load Stacks chain tip"),
+                );
+                response.send(http, fd).and_then(|_| Ok(None))
+            }
+        }
+    }
+
+    /// Get the unconfirmed chain tip, if there is unconfirmed microblock state that can be read
+    fn get_readable_unconfirmed_tip(chainstate: &StacksChainState) -> Option<StacksBlockId> {
+        match chainstate.unconfirmed_state {
+            Some(ref unconfirmed) if unconfirmed.is_readable() => {
+                Some(unconfirmed.unconfirmed_chain_tip.clone())
+            }
+            _ => None,
         }
     }
 
+    /// Load up the consensus hash and block hash of the anchored block a request refers to.
+    /// Microblocks only ever build on anchored blocks, so UseLatestUnconfirmedTip resolves to the
+    /// canonical anchored tip here.
     fn handle_load_stacks_chain_tip_hashes<W: Write>(
         http: &mut StacksHttp,
         fd: &mut W,
         req: &HttpRequestType,
-        tip_opt: Option<&StacksBlockId>,
+        tip_req: &TipRequest,
         sortdb: &SortitionDB,
         chainstate: &StacksChainState,
     ) -> Result<Option<(ConsensusHash, BlockHeaderHash)>, net_error> {
-        match tip_opt {
-            Some(tip) => match chainstate.get_block_header_hashes(&tip)? {
+        match tip_req {
+            TipRequest::SpecificTip(tip) => match chainstate.get_block_header_hashes(&tip)? {
                 Some((ch, bl)) => {
                     return Ok(Some((ch, bl)));
                 }
                 None => {}
             },
-            None => match chainstate.get_stacks_chain_tip(sortdb)? {
-                Some(tip) => {
-                    return Ok(Some((tip.consensus_hash, tip.anchored_block_hash)));
+            TipRequest::UseLatestAnchoredTip | TipRequest::UseLatestUnconfirmedTip => {
+                match chainstate.get_stacks_chain_tip(sortdb)? {
+                    Some(tip) => {
+                        return Ok(Some((tip.consensus_hash, tip.anchored_block_hash)));
+                    }
+                    None => {}
                 }
-                None => {}
-            },
+            }
         }
         let response_metadata = HttpResponseMetadata::from(req);
         warn!("Failed to load Stacks chain tip");
@@ -1855,6 +2436,7 @@ impl ConversationHttp {
         atlasdb: &mut AtlasDB,
         chainstate: &mut StacksChainState,
         mempool: &mut MemPoolDB,
+        reward_set_cache: &mut RPCRewardSetCache,
         handler_opts: &RPCHandlerArgs,
     ) -> Result<Option<StacksMessageType>, net_error> {
         let mut reply = self.connection.make_relay_handle(self.conn_id)?;
@@ -1875,12 +2457,12 @@ impl ConversationHttp {
                 )?;
                 None
             }
-            HttpRequestType::GetPoxInfo(ref _md, ref tip_opt) => {
+            HttpRequestType::GetPoxInfo(ref _md, ref tip_req) => {
                 if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
                     &mut self.connection.protocol,
                     &mut reply,
                     &req,
-                    tip_opt.as_ref(),
+                    tip_req,
                     sortdb,
                     chainstate,
                 )? {
@@ -1958,12 +2540,12 @@ impl ConversationHttp {
                 )?;
                 None
             }
-            HttpRequestType::GetAccount(ref _md, ref principal, ref tip_opt, ref with_proof) => {
+            HttpRequestType::GetAccount(ref _md, ref principal, ref tip_req, ref with_proof) => {
                 if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
                     &mut self.connection.protocol,
                     &mut reply,
                     &req,
-                    tip_opt.as_ref(),
+                    tip_req,
                     sortdb,
                     chainstate,
                 )? {
@@ -1986,14 +2568,14 @@ impl ConversationHttp {
                 ref contract_name,
                 ref map_name,
                 ref key,
-                ref tip_opt,
+                ref tip_req,
                 ref with_proof,
             ) => {
                 if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
                     &mut self.connection.protocol,
                     &mut reply,
                     &req,
-                    tip_opt.as_ref(),
+                    tip_req,
                     sortdb,
                     chainstate,
                 )? {
@@ -2025,13 +2607,13 @@ impl ConversationHttp {
                 ref _md,
                 ref contract_addr,
                 ref contract_name,
-                ref tip_opt,
+                ref tip_req,
             ) => {
                 if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
                     &mut self.connection.protocol,
                     &mut reply,
                     &req,
-                    tip_opt.as_ref(),
+                    tip_req,
                     sortdb,
                     chainstate,
                 )? {
@@ -2055,13 +2637,13 @@ impl ConversationHttp {
                 ref as_sender,
                 ref func_name,
                 ref args,
-                ref tip_opt,
+                ref tip_req,
             ) => {
                 if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
                     &mut self.connection.protocol,
                     &mut reply,
                     &req,
-                    tip_opt.as_ref(),
+                    tip_req,
                     sortdb,
                     chainstate,
                 )? {
@@ -2086,14 +2668,14 @@ impl ConversationHttp {
                 ref _md,
                 ref contract_addr,
                 ref contract_name,
-                ref tip_opt,
+                ref tip_req,
                 ref with_proof,
             ) => {
                 if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
                     &mut self.connection.protocol,
                     &mut reply,
                     &req,
-                    tip_opt.as_ref(),
+                    tip_req,
                     sortdb,
                     chainstate,
                 )? {
@@ -2144,6 +2726,41 @@ impl ConversationHttp {
                 }
                 None
             }
+            HttpRequestType::GetPoxRewardSet(ref _md, ref reward_cycle) => {
+                ConversationHttp::handle_getpox_reward_set(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    chainstate,
+                    &self.burnchain,
+                    reward_set_cache,
+                    *reward_cycle,
+                )?;
+                None
+            }
+            HttpRequestType::GetPoxPayouts(ref _md, ref reward_cycle) => {
+                ConversationHttp::handle_getpox_payouts(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    &self.burnchain,
+                    *reward_cycle,
+                )?;
+                None
+            }
+            HttpRequestType::GetSortition(ref _md, ref sortition_req) => {
+                ConversationHttp::handle_getsortition(
+                    &mut self.connection.protocol,
+                    &mut reply,
+                    &req,
+                    sortdb,
+                    chainstate,
+                    sortition_req,
+                )?;
+                None
+            }
             HttpRequestType::GetAttachment(ref _md, ref content_hash) => {
                 ConversationHttp::handle_getattachment(
                     &mut self.connection.protocol,
@@ -2188,13 +2805,13 @@ impl ConversationHttp {
                 }
                 None
             }
-            HttpRequestType::PostMicroblock(ref _md, ref mblock, ref tip_opt) => {
+            HttpRequestType::PostMicroblock(ref _md, ref mblock, ref tip_req) => {
                 if let Some((consensus_hash, block_hash)) =
                     ConversationHttp::handle_load_stacks_chain_tip_hashes(
                         &mut self.connection.protocol,
                         &mut reply,
                         &req,
-                        tip_opt.as_ref(),
+                        tip_req,
                         sortdb,
                         chainstate,
                     )?
@@ -2233,13 +2850,13 @@ impl ConversationHttp {
                 ref contract_addr,
                 ref contract_name,
                 ref trait_id,
-                ref tip_opt,
+                ref tip_req,
             ) => {
                 if let Some(tip) = ConversationHttp::handle_load_stacks_chain_tip(
                     &mut self.connection.protocol,
                     &mut reply,
                     &req,
-                    tip_opt.as_ref(),
+                    tip_req,
                     sortdb,
                     chainstate,
                 )? {
@@ -2524,6 +3141,7 @@ impl ConversationHttp {
         atlasdb: &mut AtlasDB,
         chainstate: &mut StacksChainState,
         mempool: &mut MemPoolDB,
+        reward_set_cache: &mut RPCRewardSetCache,
         handler_args: &RPCHandlerArgs,
     ) -> Result<Vec<StacksMessageType>, net_error> {
         // if we have an in-flight error, then don't take any more requests.
@@ -2559,6 +3177,7 @@ impl ConversationHttp {
                             atlasdb,
                             chainstate,
                             mempool,
+                            reward_set_cache,
                             handler_args,
                         )
                     })?;
@@ -2652,23 +3271,47 @@ impl ConversationHttp {
     }
 
     /// Make a new getinfo request to this endpoint
-    pub fn new_getpoxinfo(&self, tip_opt: Option<StacksBlockId>) -> HttpRequestType {
+    pub fn new_getpoxinfo(&self, tip_req: TipRequest) -> HttpRequestType {
         HttpRequestType::GetPoxInfo(
             HttpRequestMetadata::from_host(self.peer_host.clone()),
-            tip_opt,
+            tip_req,
         )
     }
 
-    /// Make a new getneighbors request to this endpoint
-    pub fn new_getneighbors(&self) -> HttpRequestType {
-        HttpRequestType::GetNeighbors(HttpRequestMetadata::from_host(self.peer_host.clone()))
+    /// Make a new get PoX reward set request to this endpoint
+    pub fn new_getpox_reward_set(&self, reward_cycle: u64) -> HttpRequestType {
+        HttpRequestType::GetPoxRewardSet(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            reward_cycle,
+        )
     }
 
-    /// Make a new getblock request to this endpoint
-    pub fn new_getblock(&self, index_block_hash: StacksBlockId) -> HttpRequestType {
-        HttpRequestType::GetBlock(
+    /// Make a new get PoX payouts request to this endpoint
+    pub fn new_getpox_payouts(&self, reward_cycle: u64) -> HttpRequestType {
+        HttpRequestType::GetPoxPayouts(
             HttpRequestMetadata::from_host(self.peer_host.clone()),
-            index_block_hash,
+            reward_cycle,
+        )
+    }
+
+    /// Make a new getsortition request to this endpoint
+    pub fn new_getsortition(&self, sortition_req: SortitionRequest) -> HttpRequestType {
+        HttpRequestType::GetSortition(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            sortition_req,
+        )
+    }
+
+    /// Make a new getneighbors request to this endpoint
+    pub fn new_getneighbors(&self) -> HttpRequestType {
+        HttpRequestType::GetNeighbors(HttpRequestMetadata::from_host(self.peer_host.clone()))
+    }
+
+    /// Make a new getblock request to this endpoint
+    pub fn new_getblock(&self, index_block_hash: StacksBlockId) -> HttpRequestType {
+        HttpRequestType::GetBlock(
+            HttpRequestMetadata::from_host(self.peer_host.clone()),
+            index_block_hash,
         )
     }
 
@@ -2737,12 +3380,12 @@ impl ConversationHttp {
     pub fn new_post_microblock(
         &self,
         mblock: StacksMicroblock,
-        tip_opt: Option<StacksBlockId>,
+        tip_req: TipRequest,
     ) -> HttpRequestType {
         HttpRequestType::PostMicroblock(
             HttpRequestMetadata::from_host(self.peer_host.clone()),
             mblock,
-            tip_opt,
+            tip_req,
         )
     }
 
@@ -2750,13 +3393,13 @@ impl ConversationHttp {
     pub fn new_getaccount(
         &self,
         principal: PrincipalData,
-        tip_opt: Option<StacksBlockId>,
+        tip_req: TipRequest,
         with_proof: bool,
     ) -> HttpRequestType {
         HttpRequestType::GetAccount(
             HttpRequestMetadata::from_host(self.peer_host.clone()),
             principal,
-            tip_opt,
+            tip_req,
             with_proof,
         )
     }
@@ -2768,7 +3411,7 @@ impl ConversationHttp {
         contract_name: ContractName,
         map_name: ClarityName,
         key: Value,
-        tip_opt: Option<StacksBlockId>,
+        tip_req: TipRequest,
         with_proof: bool,
     ) -> HttpRequestType {
         HttpRequestType::GetMapEntry(
@@ -2777,7 +3420,7 @@ impl ConversationHttp {
             contract_name,
             map_name,
             key,
-            tip_opt,
+            tip_req,
             with_proof,
         )
     }
@@ -2787,14 +3430,14 @@ impl ConversationHttp {
         &self,
         contract_addr: StacksAddress,
         contract_name: ContractName,
-        tip_opt: Option<StacksBlockId>,
+        tip_req: TipRequest,
         with_proof: bool,
     ) -> HttpRequestType {
         HttpRequestType::GetContractSrc(
             HttpRequestMetadata::from_host(self.peer_host.clone()),
             contract_addr,
             contract_name,
-            tip_opt,
+            tip_req,
             with_proof,
         )
     }
@@ -2804,13 +3447,13 @@ impl ConversationHttp {
         &self,
         contract_addr: StacksAddress,
         contract_name: ContractName,
-        tip_opt: Option<StacksBlockId>,
+        tip_req: TipRequest,
     ) -> HttpRequestType {
         HttpRequestType::GetContractABI(
             HttpRequestMetadata::from_host(self.peer_host.clone()),
             contract_addr,
             contract_name,
-            tip_opt,
+            tip_req,
         )
     }
 
@@ -2821,8 +3464,8 @@ impl ConversationHttp {
         contract_name: ContractName,
         sender: PrincipalData,
         function_name: ClarityName,
-        function_args: Vec<Value>,
-        tip_opt: Option<StacksBlockId>,
+        function_args: CallReadOnlyArguments,
+        tip_req: TipRequest,
     ) -> HttpRequestType {
         HttpRequestType::CallReadOnlyFunction(
             HttpRequestMetadata::from_host(self.peer_host.clone()),
@@ -2831,7 +3474,7 @@ impl ConversationHttp {
             sender,
             function_name,
             function_args,
-            tip_opt,
+            tip_req,
         )
     }
 
@@ -3261,6 +3904,7 @@ mod test {
                 &mut peer_1.network.atlasdb,
                 &mut peer_1_stacks_node.chainstate,
                 &mut peer_1_mempool,
+                &mut RPCRewardSetCache::default(),
                 &RPCHandlerArgs::default(),
             )
             .unwrap();
@@ -3288,6 +3932,7 @@ mod test {
                 &mut peer_2.network.atlasdb,
                 &mut peer_2_stacks_node.chainstate,
                 &mut peer_2_mempool,
+                &mut RPCRewardSetCache::default(),
                 &RPCHandlerArgs::default(),
             )
             .unwrap();
@@ -3329,6 +3974,7 @@ mod test {
                 &mut peer_1.network.atlasdb,
                 &mut peer_1_stacks_node.chainstate,
                 &mut peer_1_mempool,
+                &mut RPCRewardSetCache::default(),
                 &RPCHandlerArgs::default(),
             )
             .unwrap();
@@ -3422,7 +4068,7 @@ mod test {
                 )
                 .unwrap();
                 *pox_server_info.borrow_mut() = Some(pox_info);
-                convo_client.new_getpoxinfo(None)
+                convo_client.new_getpoxinfo(TipRequest::UseLatestAnchoredTip)
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
@@ -3440,6 +4086,328 @@ mod test {
         );
     }
 
+    #[test]
+    #[ignore]
+    fn test_rpc_getpox_reward_set() {
+        let reward_set_server_info = RefCell::new(None);
+        test_rpc(
+            "test_rpc_getpox_reward_set",
+            40006,
+            40007,
+            50006,
+            50007,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let sortdb = peer_server.sortdb.as_ref().unwrap();
+                let chainstate = &mut peer_server.stacks_node.as_mut().unwrap().chainstate;
+                let reward_set_info = RPCPoxRewardSetData::from_db(
+                    sortdb,
+                    chainstate,
+                    &peer_client.config.burnchain,
+                    0,
+                )
+                .unwrap();
+                assert_eq!(reward_set_info.reward_cycle, 0);
+                assert_eq!(
+                    reward_set_info.reward_cycle_start_height,
+                    peer_client.config.burnchain.reward_cycle_to_block_height(0)
+                );
+
+                // a cached reward set is the one the coordinator would compute
+                let mut cache = RPCRewardSetCache::default();
+                for _ in 0..2 {
+                    let cached_info = RPCPoxRewardSetData::from_cache_or_db(
+                        &mut cache,
+                        sortdb,
+                        chainstate,
+                        &peer_client.config.burnchain,
+                        0,
+                    )
+                    .unwrap();
+                    assert_eq!(cached_info, reward_set_info);
+                }
+                assert_eq!(cache.reward_sets.len(), 1);
+
+                *reward_set_server_info.borrow_mut() = Some(reward_set_info);
+                convo_client.new_getpox_reward_set(0)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::PoxRewardSet(response_md, reward_set_data) => {
+                        assert_eq!(
+                            Some((*reward_set_data).clone()),
+                            *reward_set_server_info.borrow()
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getpox_payouts() {
+        let payouts_server_info = RefCell::new(None);
+        test_rpc(
+            "test_rpc_getpox_payouts",
+            40008,
+            40009,
+            50008,
+            50009,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let sortdb = peer_server.sortdb.as_ref().unwrap();
+                let burnchain = &peer_client.config.burnchain;
+                let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn()).unwrap();
+                let payouts_info = RPCPoxPayoutsData::from_db(sortdb, burnchain, 0).unwrap();
+
+                // one entry per burnchain block in the cycle, in height order
+                let start_height = burnchain.reward_cycle_to_block_height(0);
+                let last_height = cmp::min(
+                    start_height + (burnchain.pox_constants.reward_cycle_length as u64) - 1,
+                    tip.block_height,
+                );
+                assert_eq!(
+                    payouts_info.blocks.len() as u64,
+                    last_height - start_height + 1
+                );
+                for (i, block) in payouts_info.blocks.iter().enumerate() {
+                    assert_eq!(block.burn_block_height, start_height + (i as u64));
+                }
+
+                *payouts_server_info.borrow_mut() = Some(payouts_info);
+                convo_client.new_getpox_payouts(0)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::PoxPayouts(response_md, payouts_data) => {
+                        assert_eq!(Some((*payouts_data).clone()), *payouts_server_info.borrow());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getpox_payouts_not_started() {
+        test_rpc(
+            "test_rpc_getpox_payouts_not_started",
+            40012,
+            40013,
+            50012,
+            50013,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| { convo_client.new_getpox_payouts(1_000_000) },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => {
+                        assert_eq!(msg, "Reward cycle has not started");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getpox_payouts_max_reward_cycle() {
+        test_rpc(
+            "test_rpc_getpox_payouts_max_reward_cycle",
+            40014,
+            40015,
+            50014,
+            50015,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| { convo_client.new_getpox_payouts(u64::MAX) },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => {
+                        assert_eq!(msg, "Reward cycle has not started");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getpox_reward_set_max_reward_cycle() {
+        test_rpc(
+            "test_rpc_getpox_reward_set_max_reward_cycle",
+            40016,
+            40017,
+            50016,
+            50017,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| { convo_client.new_getpox_reward_set(u64::MAX) },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => {
+                        assert_eq!(msg, "Reward cycle has not started");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    fn test_started_reward_cycle_start_height() {
+        let burnchain = Burnchain::default_unittest(100, &BurnchainHeaderHash([0u8; 32]));
+        let cycle_len = burnchain.pox_constants.reward_cycle_length as u64;
+        let tip_height = 100 + 3 * cycle_len + 1;
+
+        assert_eq!(
+            started_reward_cycle_start_height(&burnchain, tip_height, 3).unwrap(),
+            tip_height
+        );
+        assert_eq!(
+            started_reward_cycle_start_height(&burnchain, tip_height, 0).unwrap(),
+            101
+        );
+        for reward_cycle in [4, u64::MAX / cycle_len + 1, u64::MAX].iter() {
+            match started_reward_cycle_start_height(&burnchain, tip_height, *reward_cycle) {
+                Err(net_error::NotFoundError) => {}
+                x => panic!("Expected NotFoundError, got {:?}", &x),
+            }
+        }
+
+        // a tip before the first block height has no started cycles
+        match started_reward_cycle_start_height(&burnchain, 50, 0) {
+            Err(net_error::NotFoundError) => {}
+            x => panic!("Expected NotFoundError, got {:?}", &x),
+        }
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getsortition() {
+        let sortition_server_info = RefCell::new(None);
+        test_rpc(
+            "test_rpc_getsortition",
+            40002,
+            40003,
+            50002,
+            50003,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let sortdb = peer_server.sortdb.as_ref().unwrap();
+                let chainstate = &peer_server.stacks_node.as_ref().unwrap().chainstate;
+                let tip = SortitionDB::get_canonical_burn_chain_tip(sortdb.conn()).unwrap();
+                let sortition_req = SortitionRequest::BurnHeight(tip.block_height);
+                let sortition_info =
+                    RPCSortitionData::from_db(sortdb, chainstate, &sortition_req).unwrap();
+
+                assert_eq!(sortition_info.consensus_hash, tip.consensus_hash);
+                assert_eq!(sortition_info.sortition, tip.sortition);
+                if tip.sortition {
+                    assert!(sortition_info.vrf_seed.is_some());
+                    assert_eq!(
+                        sortition_info
+                            .block_commits
+                            .iter()
+                            .filter(|commit| commit.won)
+                            .count(),
+                        1
+                    );
+                }
+
+                *sortition_server_info.borrow_mut() = Some(sortition_info);
+                convo_client.new_getsortition(sortition_req)
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::Sortition(response_md, sortition_data) => {
+                        assert_eq!(
+                            Some((*sortition_data).clone()),
+                            *sortition_server_info.borrow()
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_getsortition_not_found() {
+        test_rpc(
+            "test_rpc_getsortition_not_found",
+            40004,
+            40005,
+            50004,
+            50005,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client
+                    .new_getsortition(SortitionRequest::ConsensusHash(ConsensusHash([0x11; 20])))
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::NotFound(response_md, msg) => {
+                        assert_eq!(msg, "Failed to find sortition");
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response: {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
     #[test]
     #[ignore]
     fn test_rpc_getneighbors() {
@@ -4127,7 +5095,7 @@ mod test {
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
                         .unwrap(),
                     "hello-world".try_into().unwrap(),
-                    None,
+                    TipRequest::UseLatestAnchoredTip,
                     false,
                 )
             },
@@ -4171,7 +5139,7 @@ mod test {
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
                         .unwrap(),
                     "hello-world".try_into().unwrap(),
-                    Some(unconfirmed_tip),
+                    TipRequest::SpecificTip(unconfirmed_tip),
                     false,
                 )
             },
@@ -4208,7 +5176,7 @@ mod test {
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
                         .unwrap()
                         .to_account_principal(),
-                    None,
+                    TipRequest::UseLatestAnchoredTip,
                     false,
                 )
             },
@@ -4254,7 +5222,7 @@ mod test {
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
                         .unwrap()
                         .to_account_principal(),
-                    Some(unconfirmed_tip),
+                    TipRequest::SpecificTip(unconfirmed_tip),
                     false,
                 )
             },
@@ -4302,7 +5270,7 @@ mod test {
                         TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
                             .unwrap(),
                     ),
-                    None,
+                    TipRequest::UseLatestAnchoredTip,
                     false,
                 )
             },
@@ -4362,7 +5330,7 @@ mod test {
                         TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
                             .unwrap(),
                     ),
-                    Some(unconfirmed_tip),
+                    TipRequest::SpecificTip(unconfirmed_tip),
                     false,
                 )
             },
@@ -4406,7 +5374,7 @@ mod test {
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
                         .unwrap(),
                     "hello-world-unconfirmed".try_into().unwrap(),
-                    None,
+                    TipRequest::UseLatestAnchoredTip,
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
@@ -4449,7 +5417,7 @@ mod test {
                     StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
                         .unwrap(),
                     "hello-world-unconfirmed".try_into().unwrap(),
-                    Some(unconfirmed_tip),
+                    TipRequest::SpecificTip(unconfirmed_tip),
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
@@ -4486,8 +5454,8 @@ mod test {
                         .unwrap()
                         .to_account_principal(),
                     "ro-test".try_into().unwrap(),
-                    vec![],
-                    None,
+                    CallReadOnlyArguments::Serialized(vec![]),
+                    TipRequest::UseLatestAnchoredTip,
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
@@ -4537,14 +5505,116 @@ mod test {
                         .unwrap()
                         .to_account_principal(),
                     "ro-test".try_into().unwrap(),
-                    vec![],
-                    Some(unconfirmed_tip),
+                    CallReadOnlyArguments::Serialized(vec![]),
+                    TipRequest::SpecificTip(unconfirmed_tip),
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
+                        assert!(data.okay);
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&data.result.clone().unwrap())
+                                .unwrap(),
+                            Value::okay(Value::Int(1)).unwrap()
+                        );
+                        assert!(data.cause.is_none());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_get_map_entry_latest_tip() {
+        test_rpc(
+            "test_rpc_get_map_entry_latest_tip",
+            40190,
+            40191,
+            50190,
+            50191,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                let principal =
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal();
+                convo_client.new_getmapentry(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    "unit-map".try_into().unwrap(),
+                    Value::Tuple(
+                        TupleData::from_data(vec![("account".into(), Value::Principal(principal))])
+                            .unwrap(),
+                    ),
+                    TipRequest::UseLatestUnconfirmedTip,
+                    false,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::GetMapEntry(response_md, data) => {
+                        // same as the unconfirmed-tip value
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&data.data).unwrap(),
+                            Value::some(Value::Tuple(
+                                TupleData::from_data(vec![("units".into(), Value::Int(1))])
+                                    .unwrap()
+                            ))
+                            .unwrap()
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_call_read_only_latest_tip() {
+        test_rpc(
+            "test_rpc_call_read_only_latest_tip",
+            40200,
+            40201,
+            50200,
+            50201,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_callreadonlyfunction(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world-unconfirmed".try_into().unwrap(),
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    "ro-test".try_into().unwrap(),
+                    CallReadOnlyArguments::Serialized(vec![]),
+                    TipRequest::UseLatestUnconfirmedTip,
                 )
             },
             |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
                 let req_md = http_request.metadata().clone();
                 match http_response {
                     HttpResponseType::CallReadOnlyFunction(response_md, data) => {
+                        // the contract only exists in the unconfirmed state
                         assert!(data.okay);
                         assert_eq!(
                             Value::try_deserialize_hex_untyped(&data.result.clone().unwrap())
@@ -4563,6 +5633,97 @@ mod test {
         );
     }
 
+    #[test]
+    #[ignore]
+    fn test_rpc_call_read_only_json() {
+        test_rpc(
+            "test_rpc_call_read_only_json",
+            40210,
+            40211,
+            50210,
+            50211,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_callreadonlyfunction(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    "get-bar".try_into().unwrap(),
+                    CallReadOnlyArguments::JSON(vec![]),
+                    TipRequest::UseLatestAnchoredTip,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
+                        assert!(data.okay);
+                        assert_eq!(data.result_json, Some(json!({ "ok": "0" })));
+                        assert_eq!(
+                            Value::try_deserialize_hex_untyped(&data.result.clone().unwrap())
+                                .unwrap(),
+                            Value::okay(Value::Int(0)).unwrap()
+                        );
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
+    #[test]
+    #[ignore]
+    fn test_rpc_call_read_only_json_args() {
+        test_rpc(
+            "test_rpc_call_read_only_json_args",
+            40220,
+            40221,
+            50220,
+            50221,
+            |ref mut peer_client,
+             ref mut convo_client,
+             ref mut peer_server,
+             ref mut convo_server| {
+                convo_client.new_callreadonlyfunction(
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap(),
+                    "hello-world".try_into().unwrap(),
+                    StacksAddress::from_string("ST2DS4MSWSGJ3W9FBC6BVT0Y92S345HY8N3T6AV7R")
+                        .unwrap()
+                        .to_account_principal(),
+                    "set-bar".try_into().unwrap(),
+                    // ints as a canonical string and as a plain JSON number
+                    CallReadOnlyArguments::JSON(vec![json!("6"), json!(0)]),
+                    TipRequest::UseLatestAnchoredTip,
+                )
+            },
+            |ref http_request, ref http_response, ref mut peer_client, ref mut peer_server| {
+                let req_md = http_request.metadata().clone();
+                match http_response {
+                    HttpResponseType::CallReadOnlyFunction(response_md, data) => {
+                        // the arguments were decoded, so the call got as far as dividing
+                        assert!(!data.okay);
+                        assert!(data.cause.clone().unwrap().find("DivisionByZero").is_some());
+                        true
+                    }
+                    _ => {
+                        error!("Invalid response; {:?}", &http_response);
+                        false
+                    }
+                }
+            },
+        );
+    }
+
     #[test]
     #[ignore]
     fn test_rpc_getattachmentsinv_limit_reached() {
@@ -81,6 +81,9 @@ pub struct HttpPeer {
 
     // connection options
     pub connection_opts: ConnectionOptions,
+
+    // reward sets served to RPC clients at the current sortition tip
+    pub reward_set_cache: RPCRewardSetCache,
 }
 
 impl HttpPeer {
@@ -102,6 +105,7 @@ impl HttpPeer {
 
             burnchain: burnchain,
             connection_opts: conn_opts,
+            reward_set_cache: RPCRewardSetCache::default(),
         }
     }
 
@@ -444,6 +448,7 @@ impl HttpPeer {
         event_id: usize,
         client_sock: &mut mio_net::TcpStream,
         convo: &mut ConversationHttp,
+        reward_set_cache: &mut RPCRewardSetCache,
         handler_args: &RPCHandlerArgs,
     ) -> Result<(bool, Vec<StacksMessageType>), net_error> {
         // get incoming bytes and update the state of this conversation.
@@ -516,6 +521,7 @@ impl HttpPeer {
             atlasdb,
             chainstate,
             mempool,
+            reward_set_cache,
             handler_args,
         ) {
             Ok(msgs) => msgs,
@@ -629,6 +635,7 @@ impl HttpPeer {
                         *event_id,
                         client_sock,
                         convo,
+                        &mut self.reward_set_cache,
                         handler_args,
                     ) {
                         Ok((alive, mut new_msgs)) => {
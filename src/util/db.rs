@@ -37,6 +37,8 @@ use rusqlite::types::{
 };
 use rusqlite::Connection;
 use rusqlite::Error as sqlite_error;
+use rusqlite::OpenFlags;
+use rusqlite::OptionalExtension;
 use rusqlite::Row;
 use rusqlite::Transaction;
 use rusqlite::TransactionBehavior;
@@ -91,6 +93,10 @@ pub enum Error {
     IOError(IOError),
     /// MARF index error
     IndexError(MARFError),
+    /// Database schema is newer than the latest version this code supports (found, supported)
+    SchemaTooNew(u32, u32),
+    /// Database schema must be migrated, but the database is opened read-only (found, expected)
+    SchemaTooOld(u32, u32),
     /// Other error
     Other(String),
 }
@@ -112,6 +118,16 @@ impl fmt::Display for Error {
             Error::IOError(ref e) => fmt::Display::fmt(e, f),
             Error::SqliteError(ref e) => fmt::Display::fmt(e, f),
             Error::IndexError(ref e) => fmt::Display::fmt(e, f),
+            Error::SchemaTooNew(found, supported) => write!(
+                f,
+                "Database schema version {} is newer than the supported version {}",
+                found, supported
+            ),
+            Error::SchemaTooOld(found, expected) => write!(
+                f,
+                "Database schema version {} must be migrated to version {}",
+                found, expected
+            ),
             Error::Other(ref s) => fmt::Display::fmt(s, f),
         }
     }
@@ -134,6 +150,8 @@ impl error::Error for Error {
             Error::SqliteError(ref e) => Some(e),
             Error::IOError(ref e) => Some(e),
             Error::IndexError(ref e) => Some(e),
+            Error::SchemaTooNew(..) => None,
+            Error::SchemaTooOld(..) => None,
             Error::Other(ref _s) => None,
         }
     }
@@ -417,6 +435,149 @@ pub fn db_mkdirs(path_str: &str) -> Result<(String, String), Error> {
     Ok((data_path, marf_path))
 }
 
+/// Does the given table exist?
+pub fn table_exists(conn: &Connection, table_name: &str) -> Result<bool, Error> {
+    let sql = "SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1";
+    let mut stmt = conn.prepare(sql)?;
+    let exists = stmt.exists(&[table_name])?;
+    Ok(exists)
+}
+
+/// One step in the evolution of a database's schema.  Applying it to a database at schema
+/// version `version - 1` brings it to schema version `version`.
+pub struct SchemaMigration {
+    pub version: u32,
+    pub description: &'static str,
+    pub statements: &'static [&'static str],
+}
+
+/// The ordered list of migrations that build up a database's schema.  Migration `i` (counting
+/// from 1) must have version `i`, and migration 1 creates the database's initial schema.
+///
+/// A database's schema version is stored in the `version` column of its `db_config` table, which
+/// migration 1 must create.
+pub struct DBSchema {
+    pub name: &'static str,
+    pub migrations: &'static [SchemaMigration],
+}
+
+impl DBSchema {
+    /// The schema version that this code expects
+    pub fn latest_version(&self) -> u32 {
+        self.migrations.len() as u32
+    }
+
+    /// Create the schema in a new database, by running every migration.  The caller must insert
+    /// the `db_config` row, with `latest_version()` as its version, before committing `tx`.
+    pub fn instantiate(&self, tx: &Connection) -> Result<(), Error> {
+        for (i, migration) in self.migrations.iter().enumerate() {
+            assert_eq!(
+                migration.version as usize,
+                i + 1,
+                "BUG: {} schema migrations are out of order",
+                self.name
+            );
+            for cmd in migration.statements.iter() {
+                tx.execute_batch(cmd)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the schema version of an instantiated database
+    pub fn get_version(conn: &Connection) -> Result<u32, Error> {
+        let version: Option<String> = conn
+            .query_row("SELECT version FROM db_config LIMIT 1", NO_PARAMS, |row| {
+                row.get(0)
+            })
+            .optional()?;
+
+        match version {
+            Some(version) => version.parse::<u32>().map_err(|_e| {
+                error!("Invalid database schema version '{}'", &version);
+                Error::Corruption
+            }),
+            None => {
+                error!("No db_config row found");
+                Err(Error::Corruption)
+            }
+        }
+    }
+
+    /// Bring an instantiated database up to `latest_version()`, applying each pending migration
+    /// in its own transaction.  Refuses to touch a database whose schema is newer than this code
+    /// knows about.  A read-only caller can't migrate, so it gets an error if any migrations are
+    /// pending.  Returns the schema version the database was at when it was opened.
+    pub fn migrate(&self, conn: &mut Connection, readwrite: bool) -> Result<u32, Error> {
+        let latest = self.latest_version();
+        let opened_version = DBSchema::get_version(conn)?;
+
+        if opened_version > latest {
+            error!(
+                "{} database schema version {} is newer than the latest supported version {}",
+                self.name, opened_version, latest
+            );
+            return Err(Error::SchemaTooNew(opened_version, latest));
+        }
+        if opened_version == latest {
+            return Ok(opened_version);
+        }
+        if !readwrite {
+            error!(
+                "{} database schema version {} must be migrated to version {}, but it is opened read-only",
+                self.name, opened_version, latest
+            );
+            return Err(Error::SchemaTooOld(opened_version, latest));
+        }
+
+        loop {
+            let tx = tx_begin_immediate(conn)?;
+
+            // someone else may have migrated the database while we waited for the lock
+            let version = DBSchema::get_version(&tx)?;
+            if version >= latest {
+                break;
+            }
+
+            let migration = &self.migrations[version as usize];
+            assert_eq!(
+                migration.version,
+                version + 1,
+                "BUG: {} schema migrations are out of order",
+                self.name
+            );
+
+            info!(
+                "Migrate {} database schema from version {} to {}: {}",
+                self.name, version, migration.version, migration.description
+            );
+            for cmd in migration.statements.iter() {
+                tx.execute_batch(cmd)?;
+            }
+            tx.execute(
+                "UPDATE db_config SET version = ?1",
+                &[&migration.version.to_string()],
+            )?;
+            tx.commit()?;
+        }
+
+        Ok(opened_version)
+    }
+
+    /// Migrate the database at the given path.  Used for databases that are opened through a MARF,
+    /// which doesn't hand out a connection that can begin its own transactions.
+    pub fn migrate_path(&self, db_path: &str, readwrite: bool) -> Result<u32, Error> {
+        let open_flags = if readwrite {
+            OpenFlags::SQLITE_OPEN_READ_WRITE
+        } else {
+            OpenFlags::SQLITE_OPEN_READ_ONLY
+        };
+        let mut conn = Connection::open_with_flags(db_path, open_flags)?;
+        conn.busy_handler(Some(tx_busy_handler))?;
+        self.migrate(&mut conn, readwrite)
+    }
+}
+
 /// Read-only connection to a MARF-indexed DB
 pub struct IndexDBConn<'a, C, T: MarfTrieId> {
     pub index: &'a MARF<T>,
@@ -746,3 +907,131 @@ impl<'a, C: Clone, T: MarfTrieId> Drop for IndexDBTx<'a, C, T> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEST_SCHEMA_V1: &'static [&'static str] = &[
+        "CREATE TABLE foo(a INTEGER NOT NULL);",
+        "CREATE TABLE db_config(version TEXT NOT NULL);",
+    ];
+    const TEST_SCHEMA_V2: &'static [&'static str] = &["ALTER TABLE foo ADD COLUMN b INTEGER;"];
+    const TEST_SCHEMA_V3: &'static [&'static str] = &["CREATE INDEX foo_by_b ON foo(b);"];
+
+    const TEST_DB_SCHEMA_V1: DBSchema = DBSchema {
+        name: "test",
+        migrations: &[SchemaMigration {
+            version: 1,
+            description: "initial schema",
+            statements: TEST_SCHEMA_V1,
+        }],
+    };
+
+    const TEST_DB_SCHEMA_V3: DBSchema = DBSchema {
+        name: "test",
+        migrations: &[
+            SchemaMigration {
+                version: 1,
+                description: "initial schema",
+                statements: TEST_SCHEMA_V1,
+            },
+            SchemaMigration {
+                version: 2,
+                description: "add foo.b",
+                statements: TEST_SCHEMA_V2,
+            },
+            SchemaMigration {
+                version: 3,
+                description: "index foo.b",
+                statements: TEST_SCHEMA_V3,
+            },
+        ],
+    };
+
+    fn instantiate_test_db(schema: &DBSchema) -> DBConn {
+        let mut conn = Connection::open_in_memory().unwrap();
+        let tx = tx_begin_immediate(&mut conn).unwrap();
+        schema.instantiate(&tx).unwrap();
+        tx.execute(
+            "INSERT INTO db_config (version) VALUES (?1)",
+            &[&schema.latest_version().to_string()],
+        )
+        .unwrap();
+        tx.commit().unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_schema_migrate() {
+        let mut conn = instantiate_test_db(&TEST_DB_SCHEMA_V1);
+        assert_eq!(DBSchema::get_version(&conn).unwrap(), 1);
+        assert!(table_exists(&conn, "foo").unwrap());
+        assert!(!table_exists(&conn, "bar").unwrap());
+
+        // nothing to do
+        assert_eq!(TEST_DB_SCHEMA_V1.migrate(&mut conn, true).unwrap(), 1);
+
+        // read-only openers can't migrate
+        match TEST_DB_SCHEMA_V3.migrate(&mut conn, false) {
+            Err(Error::SchemaTooOld(1, 3)) => {}
+            x => panic!("Unexpected result {:?}", &x),
+        }
+        assert_eq!(DBSchema::get_version(&conn).unwrap(), 1);
+
+        assert_eq!(TEST_DB_SCHEMA_V3.migrate(&mut conn, true).unwrap(), 1);
+        assert_eq!(DBSchema::get_version(&conn).unwrap(), 3);
+        conn.execute("INSERT INTO foo (a, b) VALUES (1, 2)", NO_PARAMS)
+            .unwrap();
+        assert_eq!(
+            query_int(&conn, "SELECT b FROM foo INDEXED BY foo_by_b", NO_PARAMS).unwrap(),
+            2
+        );
+
+        // idempotent
+        assert_eq!(TEST_DB_SCHEMA_V3.migrate(&mut conn, true).unwrap(), 3);
+
+        // older code refuses the newer schema
+        match TEST_DB_SCHEMA_V1.migrate(&mut conn, true) {
+            Err(Error::SchemaTooNew(3, 1)) => {}
+            x => panic!("Unexpected result {:?}", &x),
+        }
+    }
+
+    #[test]
+    fn test_schema_instantiate_latest() {
+        let mut conn = instantiate_test_db(&TEST_DB_SCHEMA_V3);
+        assert_eq!(DBSchema::get_version(&conn).unwrap(), 3);
+        assert_eq!(TEST_DB_SCHEMA_V3.migrate(&mut conn, false).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_schema_migration_rolls_back_on_failure() {
+        const BAD_SCHEMA: DBSchema = DBSchema {
+            name: "test",
+            migrations: &[
+                SchemaMigration {
+                    version: 1,
+                    description: "initial schema",
+                    statements: TEST_SCHEMA_V1,
+                },
+                SchemaMigration {
+                    version: 2,
+                    description: "broken",
+                    statements: &[
+                        "ALTER TABLE foo ADD COLUMN b INTEGER;",
+                        "ALTER TABLE no_such_table ADD COLUMN c INTEGER;",
+                    ],
+                },
+            ],
+        };
+
+        let mut conn = instantiate_test_db(&TEST_DB_SCHEMA_V1);
+        assert!(BAD_SCHEMA.migrate(&mut conn, true).is_err());
+
+        // the failed migration left no trace
+        assert_eq!(DBSchema::get_version(&conn).unwrap(), 1);
+        assert_eq!(TEST_DB_SCHEMA_V3.migrate(&mut conn, true).unwrap(), 1);
+        assert_eq!(DBSchema::get_version(&conn).unwrap(), 3);
+    }
+}
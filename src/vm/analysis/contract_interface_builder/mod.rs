@@ -15,8 +15,13 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::collections::{BTreeMap, BTreeSet};
+use vm::analysis::cost_estimator::CostEstimate;
 use vm::analysis::types::ContractAnalysis;
-use vm::types::{FixedFunction, FunctionArg, FunctionType, TupleTypeSignature, TypeSignature};
+use vm::functions::define::DefineFunctionsParsed;
+use vm::types::signatures::FunctionSignature;
+use vm::types::{
+    FixedFunction, FunctionArg, FunctionType, TraitIdentifier, TupleTypeSignature, TypeSignature,
+};
 use vm::ClarityName;
 
 pub fn build_contract_interface(contract_analysis: &ContractAnalysis) -> ContractInterface {
@@ -32,13 +37,16 @@ pub fn build_contract_interface(contract_analysis: &ContractAnalysis) -> Contrac
         fungible_tokens,
         non_fungible_tokens,
         defined_traits: _,
-        implemented_traits: _,
-        expressions: _,
+        implemented_traits,
+        referenced_traits,
+        expressions,
         contract_identifier: _,
         type_map: _,
         cost_track: _,
         contract_interface: _,
         is_cost_contract_eligible: _,
+        lint_warnings: _,
+        cost_estimates,
     } = contract_analysis;
 
     contract_interface
@@ -46,6 +54,7 @@ pub fn build_contract_interface(contract_analysis: &ContractAnalysis) -> Contrac
         .append(&mut ContractInterfaceFunction::from_map(
             private_function_types,
             ContractInterfaceFunctionAccess::private,
+            cost_estimates,
         ));
 
     contract_interface
@@ -53,6 +62,7 @@ pub fn build_contract_interface(contract_analysis: &ContractAnalysis) -> Contrac
         .append(&mut ContractInterfaceFunction::from_map(
             public_function_types,
             ContractInterfaceFunctionAccess::public,
+            cost_estimates,
         ));
 
     contract_interface
@@ -60,6 +70,7 @@ pub fn build_contract_interface(contract_analysis: &ContractAnalysis) -> Contrac
         .append(&mut ContractInterfaceFunction::from_map(
             read_only_function_types,
             ContractInterfaceFunctionAccess::read_only,
+            cost_estimates,
         ));
 
     contract_interface
@@ -90,6 +101,39 @@ pub fn build_contract_interface(contract_analysis: &ContractAnalysis) -> Contrac
             fungible_tokens,
         ));
 
+    // `use-trait` adds the referenced trait's signature to `defined_traits`, under the
+    //  trait's own name -- which a local trait may share.  So the contract's own traits are
+    //  taken from its `define-trait` expressions instead.
+    let defined_traits: BTreeMap<_, _> = expressions
+        .iter()
+        .filter_map(
+            |expression| match DefineFunctionsParsed::try_parse(expression) {
+                Ok(Some(DefineFunctionsParsed::Trait { name, functions })) => {
+                    TypeSignature::parse_trait_type_repr(functions, &mut ())
+                        .ok()
+                        .map(|signature| (name.clone(), signature))
+                }
+                _ => None,
+            },
+        )
+        .collect();
+    contract_interface
+        .defined_traits
+        .append(&mut ContractInterfaceTrait::from_map(&defined_traits));
+
+    contract_interface.implemented_traits.append(
+        &mut implemented_traits
+            .iter()
+            .map(|trait_identifier| trait_identifier.to_string())
+            .collect(),
+    );
+
+    contract_interface
+        .referenced_traits
+        .append(&mut ContractInterfaceReferencedTrait::from_map(
+            referenced_traits,
+        ));
+
     contract_interface
 }
 
@@ -243,12 +287,16 @@ pub struct ContractInterfaceFunction {
     pub access: ContractInterfaceFunctionAccess,
     pub args: Vec<ContractInterfaceFunctionArg>,
     pub outputs: ContractInterfaceFunctionOutput,
+    /// Only present if the cost estimator was run on the contract
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cost_estimate: Option<CostEstimate>,
 }
 
 impl ContractInterfaceFunction {
     pub fn from_map(
         map: &BTreeMap<ClarityName, FunctionType>,
         access: ContractInterfaceFunctionAccess,
+        cost_estimates: &BTreeMap<ClarityName, CostEstimate>,
     ) -> Vec<ContractInterfaceFunction> {
         map.iter()
             .map(|(name, function_type)| ContractInterfaceFunction {
@@ -270,6 +318,7 @@ impl ContractInterfaceFunction {
                     }
                     _ => panic!("Contract functions should only have fixed function arguments!"),
                 },
+                cost_estimate: cost_estimates.get(name).cloned(),
             })
             .collect()
     }
@@ -348,6 +397,79 @@ impl ContractInterfaceMap {
     }
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractInterfaceTraitFunctionArg {
+    #[serde(rename = "type")]
+    pub type_f: ContractInterfaceAtomType,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractInterfaceTraitFunction {
+    pub name: String,
+    pub args: Vec<ContractInterfaceTraitFunctionArg>,
+    pub outputs: ContractInterfaceFunctionOutput,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractInterfaceTrait {
+    pub name: String,
+    pub functions: Vec<ContractInterfaceTraitFunction>,
+}
+
+impl ContractInterfaceTrait {
+    pub fn from_map(
+        traits: &BTreeMap<ClarityName, BTreeMap<ClarityName, FunctionSignature>>,
+    ) -> Vec<ContractInterfaceTrait> {
+        traits
+            .iter()
+            .map(|(name, functions)| ContractInterfaceTrait {
+                name: name.to_string(),
+                functions: functions
+                    .iter()
+                    .map(|(name, signature)| ContractInterfaceTraitFunction {
+                        name: name.to_string(),
+                        args: signature
+                            .args
+                            .iter()
+                            .map(|arg| ContractInterfaceTraitFunctionArg {
+                                type_f: ContractInterfaceAtomType::from_type_signature(arg),
+                            })
+                            .collect(),
+                        outputs: ContractInterfaceFunctionOutput {
+                            type_f: ContractInterfaceAtomType::from_type_signature(
+                                &signature.returns,
+                            ),
+                        },
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+/// A `use-trait` declaration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContractInterfaceReferencedTrait {
+    pub name: String,
+    pub trait_identifier: String,
+}
+
+impl ContractInterfaceReferencedTrait {
+    pub fn from_map(
+        traits: &BTreeMap<ClarityName, TraitIdentifier>,
+    ) -> Vec<ContractInterfaceReferencedTrait> {
+        traits
+            .iter()
+            .map(
+                |(alias, trait_identifier)| ContractInterfaceReferencedTrait {
+                    name: alias.to_string(),
+                    trait_identifier: trait_identifier.to_string(),
+                },
+            )
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ContractInterface {
     pub functions: Vec<ContractInterfaceFunction>,
@@ -355,6 +477,14 @@ pub struct ContractInterface {
     pub maps: Vec<ContractInterfaceMap>,
     pub fungible_tokens: Vec<ContractInterfaceFungibleTokens>,
     pub non_fungible_tokens: Vec<ContractInterfaceNonFungibleTokens>,
+    // interfaces stored before traits were included don't have these
+    #[serde(default)]
+    pub defined_traits: Vec<ContractInterfaceTrait>,
+    /// Fully-qualified trait identifiers, i.e. `SP000000000000000000002Q6VF78.contract.trait`
+    #[serde(default)]
+    pub implemented_traits: Vec<String>,
+    #[serde(default)]
+    pub referenced_traits: Vec<ContractInterfaceReferencedTrait>,
 }
 
 impl ContractInterface {
@@ -365,6 +495,9 @@ impl ContractInterface {
             maps: Vec::new(),
             fungible_tokens: Vec::new(),
             non_fungible_tokens: Vec::new(),
+            defined_traits: Vec::new(),
+            implemented_traits: Vec::new(),
+            referenced_traits: Vec::new(),
         }
     }
 
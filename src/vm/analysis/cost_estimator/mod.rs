@@ -0,0 +1,554 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use vm::analysis::types::{AnalysisPass, ContractAnalysis};
+use vm::callables::CallableType;
+use vm::costs::cost_functions::ClarityCostFunction;
+use vm::costs::{CostTracker, ExecutionCost, LimitedCostTracker};
+use vm::functions::define::DefineFunctionsParsed;
+use vm::functions::{lookup_reserved_functions, NativeFunctions};
+use vm::representations::SymbolicExpressionType::{
+    Atom, AtomValue, Field, List, LiteralValue, TraitReference,
+};
+use vm::representations::{ClarityName, SymbolicExpression};
+use vm::types::{FunctionType, SequenceSubtype, StringSubtype, TypeSignature};
+use vm::variables::NativeVariables;
+
+use super::errors::{CheckErrors, CheckResult};
+use super::AnalysisDatabase;
+
+#[cfg(test)]
+mod tests;
+
+/// An upper bound on what it costs to call a function, if every list, buffer and string it
+/// handles is as long as its type allows and every branch takes the most expensive path.
+/// The cost of loading the contract, which is charged once per transaction, is not included.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CostEstimate {
+    pub cost: ExecutionCost,
+    /// The function (or a function it calls) uses `contract-call?`.  Only the cost of making
+    ///  the call is included, since the callee's cost can't be known statically.
+    pub calls_other_contracts: bool,
+}
+
+impl CostEstimate {
+    fn zero() -> CostEstimate {
+        CostEstimate {
+            cost: ExecutionCost::zero(),
+            calls_other_contracts: false,
+        }
+    }
+
+    fn add(&mut self, other: &CostEstimate) -> CheckResult<()> {
+        self.cost.add(&other.cost)?;
+        self.calls_other_contracts |= other.calls_other_contracts;
+        Ok(())
+    }
+
+    fn multiply(&mut self, times: u64) -> CheckResult<()> {
+        self.cost.multiply(times)?;
+        Ok(())
+    }
+
+    /// Either of two branches may be taken, so take the largest of each dimension.
+    fn max(first: CostEstimate, second: CostEstimate) -> CostEstimate {
+        CostEstimate {
+            cost: ExecutionCost::max_cost(first.cost, second.cost),
+            calls_other_contracts: first.calls_other_contracts || second.calls_other_contracts,
+        }
+    }
+}
+
+///
+/// A static-analysis pass that computes the worst-case cost of each public and
+///  read-only function, by applying the same cost functions the interpreter
+///  charges at runtime to the maximum sizes allowed by the type checker's types.
+/// Clarity does not allow recursion, and every loop is over a sequence with
+///  a bounded length, so the bound is always finite.
+///
+pub struct CostEstimator<'a, T: CostTracker> {
+    contract_analysis: &'a ContractAnalysis,
+    cost_tracker: &'a mut T,
+    function_bodies: HashMap<&'a ClarityName, &'a SymbolicExpression>,
+    /// The cost of applying each user-defined function that has been estimated so far
+    application_costs: HashMap<ClarityName, CostEstimate>,
+    in_progress: HashSet<ClarityName>,
+}
+
+impl<'a> AnalysisPass for CostEstimator<'a, LimitedCostTracker> {
+    fn run_pass(
+        contract_analysis: &mut ContractAnalysis,
+        _analysis_db: &mut AnalysisDatabase,
+    ) -> CheckResult<()> {
+        estimate_contract_costs(contract_analysis)
+    }
+}
+
+/// Estimate the cost of every public and read-only function in a type-checked contract using
+/// the analysis' own cost tracker, and store the estimates in `contract_analysis.cost_estimates`.
+pub fn estimate_contract_costs(contract_analysis: &mut ContractAnalysis) -> CheckResult<()> {
+    let mut cost_tracker = contract_analysis.take_contract_cost_tracker();
+    let result = estimate_costs(contract_analysis, &mut cost_tracker);
+    contract_analysis.replace_contract_cost_tracker(cost_tracker);
+    contract_analysis.cost_estimates = result?;
+    Ok(())
+}
+
+/// Estimate the cost of every public and read-only function in a type-checked contract, using
+/// the cost functions of `cost_tracker`.  The tracker's running total is not affected.
+pub fn estimate_costs<T: CostTracker>(
+    contract_analysis: &ContractAnalysis,
+    cost_tracker: &mut T,
+) -> CheckResult<BTreeMap<ClarityName, CostEstimate>> {
+    let mut function_bodies = HashMap::new();
+    for expr in contract_analysis.expressions.iter() {
+        use vm::functions::define::DefineFunctionsParsed::*;
+        match DefineFunctionsParsed::try_parse(expr)? {
+            Some(PrivateFunction { signature, body })
+            | Some(ReadOnlyFunction { signature, body })
+            | Some(PublicFunction { signature, body }) => {
+                let name = signature
+                    .get(0)
+                    .and_then(|name| name.match_atom())
+                    .ok_or(CheckErrors::RequiresAtLeastArguments(1, 0))?;
+                function_bodies.insert(name, body);
+            }
+            _ => {}
+        }
+    }
+
+    let mut estimator = CostEstimator {
+        contract_analysis,
+        cost_tracker,
+        function_bodies,
+        application_costs: HashMap::new(),
+        in_progress: HashSet::new(),
+    };
+
+    let mut estimates = BTreeMap::new();
+    for name in contract_analysis
+        .public_function_types
+        .keys()
+        .chain(contract_analysis.read_only_function_types.keys())
+    {
+        estimates.insert(name.clone(), estimator.estimate_user_function(name)?);
+    }
+    Ok(estimates)
+}
+
+impl<'a, T: CostTracker> CostEstimator<'a, T> {
+    fn compute(
+        &mut self,
+        cost_function: ClarityCostFunction,
+        input: u64,
+    ) -> CheckResult<CostEstimate> {
+        let cost = self.cost_tracker.compute_cost(cost_function, &[input])?;
+        Ok(CostEstimate {
+            cost,
+            calls_other_contracts: false,
+        })
+    }
+
+    fn get_type(&self, expr: &SymbolicExpression) -> Option<&'a TypeSignature> {
+        let contract_analysis: &'a ContractAnalysis = self.contract_analysis;
+        contract_analysis.type_map.as_ref()?.get_type(expr)
+    }
+
+    /// The largest size a value of `expr`'s type can have.
+    fn type_size(&self, expr: &SymbolicExpression) -> u64 {
+        self.get_type(expr)
+            .map_or(0, |type_signature| type_signature.size() as u64)
+    }
+
+    /// The largest number of items a sequence of `expr`'s type can have.  For a type that
+    ///  isn't a sequence, this falls back to the type's size, which is still an upper bound.
+    fn max_len(&self, expr: &SymbolicExpression) -> CheckResult<u64> {
+        let len = match self.get_type(expr) {
+            Some(TypeSignature::SequenceType(SequenceSubtype::ListType(list))) => {
+                list.get_max_len()
+            }
+            Some(TypeSignature::SequenceType(SequenceSubtype::BufferType(len))) => len.into(),
+            Some(TypeSignature::SequenceType(SequenceSubtype::StringType(
+                StringSubtype::ASCII(len),
+            ))) => len.into(),
+            Some(TypeSignature::SequenceType(SequenceSubtype::StringType(
+                StringSubtype::UTF8(len),
+            ))) => len.into(),
+            Some(type_signature) => type_signature.size(),
+            None => return Err(CheckErrors::TypeAnnotationExpectedFailure.into()),
+        };
+        Ok(len as u64)
+    }
+
+    fn get_arg_types(&self, name: &ClarityName) -> Vec<&'a TypeSignature> {
+        let contract_analysis: &'a ContractAnalysis = self.contract_analysis;
+        let function_type = contract_analysis
+            .private_function_types
+            .get(name)
+            .or_else(|| contract_analysis.public_function_types.get(name))
+            .or_else(|| contract_analysis.read_only_function_types.get(name));
+        match function_type {
+            Some(FunctionType::Fixed(function)) => {
+                function.args.iter().map(|arg| &arg.signature).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// The cost of applying a user-defined function to already-evaluated arguments.
+    fn estimate_user_function(&mut self, name: &ClarityName) -> CheckResult<CostEstimate> {
+        if let Some(estimate) = self.application_costs.get(name) {
+            return Ok(estimate.clone());
+        }
+        let body = *self
+            .function_bodies
+            .get(name)
+            .ok_or_else(|| CheckErrors::UndefinedFunction(name.to_string()))?;
+        if !self.in_progress.insert(name.clone()) {
+            return Err(CheckErrors::CircularReference(vec![name.to_string()]).into());
+        }
+
+        let arg_types = self.get_arg_types(name);
+        let mut estimate = self.compute(
+            ClarityCostFunction::UserFunctionApplication,
+            arg_types.len() as u64,
+        )?;
+        for arg_type in arg_types.iter() {
+            estimate.add(&self.compute(
+                ClarityCostFunction::InnerTypeCheckCost,
+                arg_type.size() as u64,
+            )?)?;
+        }
+        estimate.add(&self.estimate_expression(body, 0)?)?;
+
+        self.in_progress.remove(name);
+        self.application_costs
+            .insert(name.clone(), estimate.clone());
+        Ok(estimate)
+    }
+
+    /// The cost of applying the function named by `function_name` to `arg_count` values,
+    /// as `map`, `filter` and `fold` do for each item.
+    fn estimate_function_value(
+        &mut self,
+        function_name: &SymbolicExpression,
+        arg_count: u64,
+    ) -> CheckResult<CostEstimate> {
+        let name = function_name
+            .match_atom()
+            .ok_or(CheckErrors::ExpectedName)?;
+        match lookup_reserved_functions(name) {
+            Some(CallableType::NativeFunction(_, _, cost_function)) => {
+                self.compute(cost_function, arg_count)
+            }
+            Some(_) => Ok(CostEstimate::zero()),
+            None => self.estimate_user_function(name),
+        }
+    }
+
+    /// `depth` is the number of nested `let` and `match` scopes, which the interpreter charges
+    /// for on every variable lookup.
+    fn estimate_expression(
+        &mut self,
+        expr: &SymbolicExpression,
+        depth: u64,
+    ) -> CheckResult<CostEstimate> {
+        match expr.expr {
+            AtomValue(_) | LiteralValue(_) | Field(_) | TraitReference(_, _) => {
+                Ok(CostEstimate::zero())
+            }
+            Atom(ref name) => {
+                if let Some(variable) = NativeVariables::lookup_by_name(name) {
+                    return match variable {
+                        NativeVariables::BlockHeight
+                        | NativeVariables::BurnBlockHeight
+                        | NativeVariables::TotalLiquidMicroSTX => {
+                            self.compute(ClarityCostFunction::FetchVar, 1)
+                        }
+                        _ => Ok(CostEstimate::zero()),
+                    };
+                }
+                let mut estimate = self.compute(ClarityCostFunction::LookupVariableDepth, depth)?;
+                let size = self.type_size(expr);
+                estimate.add(&self.compute(ClarityCostFunction::LookupVariableSize, size)?)?;
+                Ok(estimate)
+            }
+            List(ref list) => {
+                let (function_name, args) = list
+                    .split_first()
+                    .ok_or(CheckErrors::NonFunctionApplication)?;
+                let name = function_name
+                    .match_atom()
+                    .ok_or(CheckErrors::BadFunctionName)?;
+
+                let mut estimate = self.compute(ClarityCostFunction::LookupFunction, 0)?;
+                let application = match NativeFunctions::lookup_by_name(name) {
+                    Some(native_function) => {
+                        self.estimate_native_function(native_function, args, depth)?
+                    }
+                    None => {
+                        let mut application = self.estimate_all(args, depth)?;
+                        application.add(&self.estimate_user_function(name)?)?;
+                        application
+                    }
+                };
+                estimate.add(&application)?;
+                Ok(estimate)
+            }
+        }
+    }
+
+    fn estimate_all(
+        &mut self,
+        exprs: &[SymbolicExpression],
+        depth: u64,
+    ) -> CheckResult<CostEstimate> {
+        let mut estimate = CostEstimate::zero();
+        for expr in exprs.iter() {
+            estimate.add(&self.estimate_expression(expr, depth)?)?;
+        }
+        Ok(estimate)
+    }
+
+    fn estimate_native_function(
+        &mut self,
+        native_function: NativeFunctions,
+        args: &[SymbolicExpression],
+        depth: u64,
+    ) -> CheckResult<CostEstimate> {
+        use vm::functions::NativeFunctions::*;
+
+        // native functions evaluate all of their arguments, and are charged by argument count
+        if let Some(CallableType::NativeFunction(_, _, cost_function)) =
+            lookup_reserved_functions(native_function.get_name_str())
+        {
+            let mut estimate = self.estimate_all(args, depth)?;
+            estimate.add(&self.compute(cost_function, args.len() as u64)?)?;
+            return Ok(estimate);
+        }
+
+        // the rest are special functions, which each charge for themselves
+        let contract_analysis: &'a ContractAnalysis = self.contract_analysis;
+        let name_arg = || {
+            args.get(0)
+                .and_then(|name| name.match_atom())
+                .ok_or(CheckErrors::ExpectedName)
+        };
+        let (cost_function, input, evaluated) = match native_function {
+            And => (ClarityCostFunction::And, args.len() as u64, args),
+            Or => (ClarityCostFunction::Or, args.len() as u64, args),
+            If => {
+                if args.len() != 3 {
+                    return Err(CheckErrors::IncorrectArgumentCount(3, args.len()).into());
+                }
+                let mut estimate = self.compute(ClarityCostFunction::If, 0)?;
+                estimate.add(&self.estimate_expression(&args[0], depth)?)?;
+                let then_branch = self.estimate_expression(&args[1], depth)?;
+                let else_branch = self.estimate_expression(&args[2], depth)?;
+                estimate.add(&CostEstimate::max(then_branch, else_branch))?;
+                return Ok(estimate);
+            }
+            Let => {
+                let bindings = args
+                    .get(0)
+                    .and_then(|bindings| bindings.match_list())
+                    .ok_or(CheckErrors::BadLetSyntax)?;
+                let mut estimate = self.compute(ClarityCostFunction::Let, bindings.len() as u64)?;
+                for binding in bindings.iter() {
+                    let value = binding
+                        .match_list()
+                        .and_then(|pair| pair.get(1))
+                        .ok_or(CheckErrors::BadSyntaxBinding)?;
+                    estimate.add(&self.estimate_expression(value, depth + 1)?)?;
+                }
+                estimate.add(&self.estimate_all(&args[1..], depth + 1)?)?;
+                return Ok(estimate);
+            }
+            Match => {
+                // (match opt some-name some-branch none-branch) or
+                // (match resp ok-name ok-branch err-name err-branch)
+                let mut estimate = self.compute(ClarityCostFunction::Match, 0)?;
+                let (first_branch, second_branch) = match args.len() {
+                    4 => (&args[2], &args[3]),
+                    5 => (&args[2], &args[4]),
+                    n => return Err(CheckErrors::IncorrectArgumentCount(4, n).into()),
+                };
+                estimate.add(&self.estimate_expression(&args[0], depth)?)?;
+                let first_branch = self.estimate_expression(first_branch, depth + 1)?;
+                let second_branch = self.estimate_expression(second_branch, depth + 1)?;
+                estimate.add(&CostEstimate::max(first_branch, second_branch))?;
+                return Ok(estimate);
+            }
+            Map | Filter | Fold => {
+                if args.len() < 2 {
+                    return Err(CheckErrors::RequiresAtLeastArguments(2, args.len()).into());
+                }
+                let (mut estimate, sequences, arg_count) = match native_function {
+                    Map => (
+                        self.compute(ClarityCostFunction::Map, args.len() as u64)?,
+                        &args[1..],
+                        args.len() as u64 - 1,
+                    ),
+                    Filter => (
+                        self.compute(ClarityCostFunction::Filter, 0)?,
+                        &args[1..2],
+                        1,
+                    ),
+                    _ => (self.compute(ClarityCostFunction::Fold, 0)?, &args[1..2], 2),
+                };
+                estimate.add(&self.estimate_all(&args[1..], depth)?)?;
+                estimate.add(&self.compute(ClarityCostFunction::LookupFunction, 0)?)?;
+
+                // `map` stops at the end of its shortest sequence
+                let mut iterations = None;
+                for sequence in sequences.iter() {
+                    let len = self.max_len(sequence)?;
+                    iterations = Some(iterations.map_or(len, |min_len: u64| min_len.min(len)));
+                }
+                let iterations = iterations.unwrap_or(0);
+                let mut per_item = self.estimate_function_value(&args[0], arg_count)?;
+                per_item.multiply(iterations)?;
+                estimate.add(&per_item)?;
+                return Ok(estimate);
+            }
+            Concat => {
+                let input: u64 = args.iter().map(|arg| self.type_size(arg)).sum();
+                (ClarityCostFunction::Concat, input, args)
+            }
+            AsMaxLen => (ClarityCostFunction::AsMaxLen, 0, &args[..1]),
+            Append => {
+                let entry_size = match args.get(0).and_then(|list| self.get_type(list)) {
+                    Some(TypeSignature::SequenceType(SequenceSubtype::ListType(list))) => {
+                        list.get_list_item_type().size() as u64
+                    }
+                    _ => 0,
+                };
+                let element_size = args.get(1).map_or(0, |element| self.type_size(element));
+                (
+                    ClarityCostFunction::Append,
+                    entry_size.max(element_size),
+                    args,
+                )
+            }
+            ListCons => {
+                let input: u64 = args.iter().map(|arg| self.type_size(arg)).sum();
+                (ClarityCostFunction::ListCons, input, args)
+            }
+            FetchVar | SetVar => {
+                let size = contract_analysis
+                    .persisted_variable_types
+                    .get(name_arg()?)
+                    .map_or(0, |var_type| var_type.size() as u64);
+                let cost_function = if native_function == FetchVar {
+                    ClarityCostFunction::FetchVar
+                } else {
+                    ClarityCostFunction::SetVar
+                };
+                (cost_function, size, &args[1..])
+            }
+            FetchEntry | SetEntry | InsertEntry | DeleteEntry => {
+                let size = contract_analysis
+                    .map_types
+                    .get(name_arg()?)
+                    .map_or(0, |(key_type, value_type)| {
+                        key_type.size() as u64 + value_type.size() as u64
+                    });
+                let cost_function = if native_function == FetchEntry {
+                    ClarityCostFunction::FetchEntry
+                } else {
+                    ClarityCostFunction::SetEntry
+                };
+                (cost_function, size, &args[1..])
+            }
+            TupleCons => {
+                let mut estimate =
+                    self.compute(ClarityCostFunction::TupleCons, args.len() as u64)?;
+                for binding in args.iter() {
+                    let value = binding
+                        .match_list()
+                        .and_then(|pair| pair.get(1))
+                        .ok_or(CheckErrors::BadSyntaxBinding)?;
+                    estimate.add(&self.estimate_expression(value, depth)?)?;
+                }
+                return Ok(estimate);
+            }
+            TupleGet => {
+                let fields = match args.get(1).and_then(|tuple| self.get_type(tuple)) {
+                    Some(TypeSignature::TupleType(tuple)) => tuple.get_type_map().len(),
+                    Some(TypeSignature::OptionalType(inner)) => match **inner {
+                        TypeSignature::TupleType(ref tuple) => tuple.get_type_map().len(),
+                        _ => 0,
+                    },
+                    _ => 0,
+                };
+                (ClarityCostFunction::TupleGet, fields as u64, &args[1..])
+            }
+            Secp256k1Recover => (ClarityCostFunction::Secp256k1recover, 0, args),
+            Secp256k1Verify => (ClarityCostFunction::Secp256k1verify, 0, args),
+            Print => {
+                let size = args.get(0).map_or(0, |arg| self.type_size(arg));
+                (ClarityCostFunction::Print, size, args)
+            }
+            ContractCall => {
+                let mut estimate = self.compute(ClarityCostFunction::ContractCall, 0)?;
+                if args.len() > 2 {
+                    estimate.add(&self.estimate_all(&args[2..], depth)?)?;
+                }
+                estimate.calls_other_contracts = true;
+                return Ok(estimate);
+            }
+            AsContract => return self.estimate_all(args, depth),
+            ContractOf => (ClarityCostFunction::ContractOf, 0, &args[..0]),
+            PrincipalOf => (ClarityCostFunction::PrincipalOf, 0, args),
+            GetBlockInfo => (ClarityCostFunction::BlockInfo, 0, &args[1..]),
+            AtBlock => (ClarityCostFunction::AtBlock, 0, args),
+            Asserts => (ClarityCostFunction::Asserts, 0, args),
+            GetStxBalance => (ClarityCostFunction::StxBalance, 0, args),
+            StxTransfer | StxBurn => (ClarityCostFunction::StxTransfer, 0, args),
+            MintToken => (ClarityCostFunction::FtMint, 0, &args[1..]),
+            TransferToken => (ClarityCostFunction::FtTransfer, 0, &args[1..]),
+            GetTokenBalance => (ClarityCostFunction::FtBalance, 0, &args[1..]),
+            GetTokenSupply => (ClarityCostFunction::FtSupply, 0, &args[1..]),
+            BurnToken => (ClarityCostFunction::FtBurn, 0, &args[1..]),
+            MintAsset | TransferAsset | GetAssetOwner | BurnAsset => {
+                let size = contract_analysis
+                    .non_fungible_tokens
+                    .get(name_arg()?)
+                    .map_or(0, |asset_type| asset_type.size() as u64);
+                let cost_function = match native_function {
+                    MintAsset => ClarityCostFunction::NftMint,
+                    TransferAsset => ClarityCostFunction::NftTransfer,
+                    GetAssetOwner => ClarityCostFunction::NftOwner,
+                    _ => ClarityCostFunction::NftBurn,
+                };
+                let mut estimate = self.estimate_all(&args[1..], depth)?;
+                if native_function == BurnAsset {
+                    // nft-burn? is charged once before it checks the asset type, and again after
+                    estimate.add(&self.compute(ClarityCostFunction::NftBurn, 0)?)?;
+                }
+                estimate.add(&self.compute(cost_function, size)?)?;
+                return Ok(estimate);
+            }
+            _ => return self.estimate_all(args, depth),
+        };
+
+        let mut estimate = self.estimate_all(evaluated, depth)?;
+        estimate.add(&self.compute(cost_function, input)?)?;
+        Ok(estimate)
+    }
+}
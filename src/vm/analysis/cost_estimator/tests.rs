@@ -0,0 +1,142 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::BTreeMap;
+
+use vm::analysis::cost_estimator::{estimate_costs, CostEstimate};
+use vm::analysis::mem_type_check;
+use vm::costs::cost_functions::ClarityCostFunction;
+use vm::costs::{CostErrors, CostTracker, ExecutionCost};
+use vm::types::QualifiedContractIdentifier;
+use vm::ClarityName;
+
+/// Charges one unit of runtime for every cost function, and remembers what it was asked for.
+struct RecordingTracker {
+    charges: Vec<(ClarityCostFunction, u64)>,
+}
+
+impl CostTracker for RecordingTracker {
+    fn compute_cost(
+        &mut self,
+        cost_function: ClarityCostFunction,
+        input: &[u64],
+    ) -> Result<ExecutionCost, CostErrors> {
+        self.charges.push((cost_function, input[0]));
+        Ok(ExecutionCost::runtime(1))
+    }
+    fn add_cost(&mut self, _cost: ExecutionCost) -> Result<(), CostErrors> {
+        Ok(())
+    }
+    fn add_memory(&mut self, _memory: u64) -> Result<(), CostErrors> {
+        Ok(())
+    }
+    fn drop_memory(&mut self, _memory: u64) {}
+    fn reset_memory(&mut self) {}
+    fn short_circuit_contract_call(
+        &mut self,
+        _contract: &QualifiedContractIdentifier,
+        _function: &ClarityName,
+        _input: &[u64],
+    ) -> Result<bool, CostErrors> {
+        Ok(false)
+    }
+}
+
+fn estimate(contract: &str) -> (BTreeMap<ClarityName, CostEstimate>, RecordingTracker) {
+    let (_, analysis) = mem_type_check(contract).unwrap();
+    let mut tracker = RecordingTracker { charges: vec![] };
+    let estimates = estimate_costs(&analysis, &mut tracker).unwrap();
+    (estimates, tracker)
+}
+
+fn runtime(estimates: &BTreeMap<ClarityName, CostEstimate>, name: &str) -> u64 {
+    estimates[&ClarityName::from(name)].cost.runtime
+}
+
+#[test]
+fn test_charges_match_interpreter() {
+    let (estimates, tracker) = estimate("(define-read-only (f (x int)) (+ x 1))");
+    assert_eq!(runtime(&estimates, "f"), 6);
+    assert_eq!(
+        tracker.charges,
+        vec![
+            (ClarityCostFunction::UserFunctionApplication, 1),
+            (ClarityCostFunction::InnerTypeCheckCost, 16),
+            (ClarityCostFunction::LookupFunction, 0),
+            (ClarityCostFunction::LookupVariableDepth, 0),
+            (ClarityCostFunction::LookupVariableSize, 16),
+            (ClarityCostFunction::Add, 2),
+        ]
+    );
+}
+
+#[test]
+fn test_only_public_and_read_only_functions() {
+    let (estimates, _) = estimate(
+        "(define-private (a) 1)
+         (define-public (b) (ok (a)))
+         (define-read-only (c) (a))",
+    );
+    let names: Vec<_> = estimates.keys().map(|name| name.to_string()).collect();
+    assert_eq!(names, vec!["b", "c"]);
+}
+
+#[test]
+fn test_loops_scale_with_max_length() {
+    let contract = |len: u32| {
+        format!(
+            "(define-private (add (x int) (sum int)) (+ x sum))
+             (define-read-only (total (l (list {} int))) (fold add l 0))",
+            len
+        )
+    };
+    let (short, _) = estimate(&contract(10));
+    let (long, _) = estimate(&contract(20));
+    let (longer, _) = estimate(&contract(30));
+
+    let per_ten_items = runtime(&long, "total") - runtime(&short, "total");
+    assert!(per_ten_items > 0);
+    assert_eq!(
+        runtime(&longer, "total") - runtime(&long, "total"),
+        per_ten_items
+    );
+}
+
+#[test]
+fn test_branches_take_the_maximum() {
+    let (estimates, _) = estimate(
+        "(define-read-only (cheap (x int)) x)
+         (define-read-only (expensive (x int)) (+ (* x x) (* x x)))
+         (define-read-only (branch (x int)) (if (> x 0) (+ (* x x) (* x x)) x))",
+    );
+    // `if` and the comparison, on top of the expensive branch
+    assert_eq!(
+        runtime(&estimates, "branch"),
+        runtime(&estimates, "expensive") + 6
+    );
+    assert!(runtime(&estimates, "cheap") < runtime(&estimates, "expensive"));
+}
+
+#[test]
+fn test_calls_other_contracts() {
+    let (estimates, _) = estimate(
+        "(define-trait callee ((f () (response int int))))
+         (define-public (dynamic (contract <callee>)) (contract-call? contract f))
+         (define-public (local) (ok 1))",
+    );
+    assert!(estimates[&ClarityName::from("dynamic")].calls_other_contracts);
+    assert!(!estimates[&ClarityName::from("local")].calls_other_contracts);
+}
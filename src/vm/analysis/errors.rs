@@ -198,6 +198,18 @@ impl CheckError {
         self.expressions.is_some()
     }
 
+    /// Cost errors mean the analysis budget is exhausted, so it makes no sense to keep
+    /// checking after one.
+    pub fn is_cost_error(&self) -> bool {
+        match self.err {
+            CheckErrors::CostOverflow
+            | CheckErrors::CostBalanceExceeded(..)
+            | CheckErrors::MemoryBalanceExceeded(..)
+            | CheckErrors::CostComputationFailed(..) => true,
+            _ => false,
+        }
+    }
+
     pub fn set_expression(&mut self, expr: &SymbolicExpression) {
         self.diagnostic.spans = vec![expr.span.clone()];
         self.expressions.replace(vec![expr.clone()]);
@@ -0,0 +1,404 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use std::collections::HashSet;
+
+use vm::analysis::type_checker::contexts::TypeMap;
+use vm::analysis::types::{AnalysisPass, ContractAnalysis};
+use vm::diagnostic::{Diagnostic, Level};
+use vm::functions::define::DefineFunctionsParsed;
+use vm::representations::{ClarityName, Span, SymbolicExpression};
+use vm::types::{SequenceSubtype, TypeSignature};
+
+use super::errors::CheckResult;
+use super::AnalysisDatabase;
+
+#[cfg(test)]
+mod tests;
+
+define_named_enum!(LintRule {
+    UnusedLetBinding("unused-let-binding"),
+    UnusedPrivateFunction("unused-private-function"),
+    DiscardedResponse("discarded-response"),
+    UnwrapPanicOnInput("unwrap-panic-on-input"),
+    TxSenderAuthorization("tx-sender-authorization"),
+    UnguardedVarSet("unguarded-var-set"),
+});
+
+/// Which lint rules to run.  All of them are enabled by default.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct LintConfig {
+    disabled: HashSet<LintRule>,
+}
+
+impl LintConfig {
+    pub fn disable(&mut self, rule: LintRule) {
+        self.disabled.insert(rule);
+    }
+
+    pub fn is_enabled(&self, rule: LintRule) -> bool {
+        !self.disabled.contains(&rule)
+    }
+
+    /// Make a config that disables the rules in a comma-separated list of rule names.
+    pub fn from_disabled_names(names: &str) -> Result<LintConfig, String> {
+        let mut config = LintConfig::default();
+        for name in names.split(',').map(|name| name.trim()) {
+            if name.len() == 0 {
+                continue;
+            }
+            let rule = LintRule::lookup_by_name(name).ok_or_else(|| {
+                format!(
+                    "Unknown lint rule '{}'. Expected one of: {}",
+                    name,
+                    LintRule::ALL_NAMES.join(", ")
+                )
+            })?;
+            config.disable(rule);
+        }
+        Ok(config)
+    }
+}
+
+///
+/// An optional static-analysis pass that looks for common contract bugs.
+/// Unlike the other passes, it never fails: it only produces warnings.
+///  It expects a contract that has already been type checked, since it
+///  uses the type map to find discarded responses.
+///
+pub struct LintChecker<'a> {
+    config: &'a LintConfig,
+    type_map: Option<&'a TypeMap>,
+    warnings: Vec<Diagnostic>,
+}
+
+/// What the checker knows about the function whose body it is in.
+struct FunctionContext {
+    is_public: bool,
+    arg_names: Vec<ClarityName>,
+    has_asserts: bool,
+    first_var_set: Option<Span>,
+}
+
+impl FunctionContext {
+    fn new(is_public: bool, arg_names: Vec<ClarityName>) -> FunctionContext {
+        FunctionContext {
+            is_public,
+            arg_names,
+            has_asserts: false,
+            first_var_set: None,
+        }
+    }
+}
+
+impl<'a> AnalysisPass for LintChecker<'a> {
+    fn run_pass(
+        contract_analysis: &mut ContractAnalysis,
+        _analysis_db: &mut AnalysisDatabase,
+    ) -> CheckResult<()> {
+        let warnings = LintChecker::run(contract_analysis, &LintConfig::default());
+        contract_analysis.lint_warnings = warnings;
+        Ok(())
+    }
+}
+
+impl<'a> LintChecker<'a> {
+    /// Run the enabled rules over the contract, returning a warning for each problem found,
+    /// in source order.
+    pub fn run(contract_analysis: &'a ContractAnalysis, config: &'a LintConfig) -> Vec<Diagnostic> {
+        let mut checker = LintChecker {
+            config,
+            type_map: contract_analysis.type_map.as_ref(),
+            warnings: vec![],
+        };
+
+        checker.check_unused_private_functions(&contract_analysis.expressions);
+        for expr in contract_analysis.expressions.iter() {
+            checker.check_top_level(expr);
+        }
+
+        let mut warnings = checker.warnings;
+        warnings.sort_by_key(|warning| {
+            warning
+                .spans
+                .first()
+                .map(|span| (span.start_line, span.start_column))
+        });
+        warnings
+    }
+
+    fn warn(&mut self, rule: LintRule, span: &Span, message: String, suggestion: &str) {
+        if !self.config.is_enabled(rule) {
+            return;
+        }
+        self.warnings.push(Diagnostic {
+            level: Level::Warning,
+            message: format!("{} ({})", message, rule),
+            spans: vec![span.clone()],
+            suggestion: Some(suggestion.to_string()),
+        });
+    }
+
+    fn check_top_level(&mut self, expr: &SymbolicExpression) {
+        use vm::functions::define::DefineFunctionsParsed::*;
+        match DefineFunctionsParsed::try_parse(expr) {
+            Ok(Some(PublicFunction { signature, body })) => {
+                self.check_function(signature, body, true)
+            }
+            Ok(Some(PrivateFunction { signature, body }))
+            | Ok(Some(ReadOnlyFunction { signature, body })) => {
+                self.check_function(signature, body, false)
+            }
+            Ok(Some(Constant { value, .. })) => {
+                self.check_expression(value, &mut FunctionContext::new(false, vec![]))
+            }
+            _ => {}
+        }
+    }
+
+    fn check_function(
+        &mut self,
+        signature: &[SymbolicExpression],
+        body: &SymbolicExpression,
+        is_public: bool,
+    ) {
+        let arg_names = signature
+            .iter()
+            .skip(1)
+            .filter_map(|arg| arg.match_list()?.get(0)?.match_atom().cloned())
+            .collect();
+        let mut context = FunctionContext::new(is_public, arg_names);
+        self.check_expression(body, &mut context);
+
+        if is_public && !context.has_asserts {
+            if let Some(span) = context.first_var_set {
+                self.warn(
+                    LintRule::UnguardedVarSet,
+                    &span,
+                    "public function writes a data var without asserting anything".to_string(),
+                    "Check who is calling with `asserts!` before writing contract state.",
+                );
+            }
+        }
+    }
+
+    fn check_expression(&mut self, expr: &SymbolicExpression, context: &mut FunctionContext) {
+        let list = match expr.match_list() {
+            Some(list) => list,
+            None => return,
+        };
+
+        if let Some((function_name, args)) = list.split_first() {
+            match function_name.match_atom().map(|name| name.as_str()) {
+                Some("let") => self.check_let(args),
+                Some("begin") => self.check_statements(args),
+                Some(unwrap @ "unwrap-panic") | Some(unwrap @ "unwrap-err-panic") => {
+                    let on_input = args.iter().any(|arg| {
+                        context
+                            .arg_names
+                            .iter()
+                            .any(|name| references_name(arg, name))
+                    });
+                    if context.is_public && on_input {
+                        self.warn(
+                            LintRule::UnwrapPanicOnInput,
+                            &expr.span,
+                            format!("'{}' on a value derived from a function argument", unwrap),
+                            "Use `unwrap!` with an error code, so that callers can tell what went wrong.",
+                        );
+                    }
+                }
+                Some("asserts!") => {
+                    context.has_asserts = true;
+                    if let Some(comparison) = args.get(0).and_then(find_tx_sender_comparison) {
+                        self.warn(
+                            LintRule::TxSenderAuthorization,
+                            &comparison.span,
+                            "authorization checks 'tx-sender'".to_string(),
+                            "`tx-sender` is the origin of the transaction, even inside a `contract-call?`. Use `contract-caller` to authorize the immediate caller.",
+                        );
+                    }
+                }
+                Some("var-set") => {
+                    if context.first_var_set.is_none() {
+                        context.first_var_set = Some(expr.span.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        for child in list.iter() {
+            self.check_expression(child, context);
+        }
+    }
+
+    fn check_let(&mut self, args: &[SymbolicExpression]) {
+        let bindings = match args.get(0).and_then(|bindings| bindings.match_list()) {
+            Some(bindings) => bindings,
+            None => return,
+        };
+        let body = &args[1..];
+
+        for (i, binding) in bindings.iter().enumerate() {
+            let name_expr = match binding.match_list().and_then(|pair| pair.get(0)) {
+                Some(name_expr) => name_expr,
+                None => continue,
+            };
+            let name = match name_expr.match_atom() {
+                Some(name) => name,
+                None => continue,
+            };
+
+            // Clarity doesn't allow shadowing, so any later reference is to this binding
+            let used_by_binding = bindings[i + 1..].iter().any(|later| {
+                later
+                    .match_list()
+                    .and_then(|pair| pair.get(1))
+                    .map_or(false, |value| references_name(value, name))
+            });
+            let used_by_body = body.iter().any(|expr| references_name(expr, name));
+            if !used_by_binding && !used_by_body {
+                self.warn(
+                    LintRule::UnusedLetBinding,
+                    &name_expr.span,
+                    format!("let binding '{}' is never used", name),
+                    "Remove the binding if it is not needed.",
+                );
+            }
+        }
+
+        self.check_statements(body);
+    }
+
+    /// Check that none of the statements before the last one discard a response.  The type
+    /// checker already rejects statements that evaluate to a response, so this catches
+    /// responses that are inspected and then dropped, or wrapped in another value.
+    fn check_statements(&mut self, statements: &[SymbolicExpression]) {
+        if statements.len() < 2 {
+            return;
+        }
+        for statement in statements[..statements.len() - 1].iter() {
+            let tested_by = statement
+                .match_list()
+                .and_then(|list| list.get(0))
+                .and_then(|function_name| function_name.match_atom())
+                .filter(|function_name| {
+                    function_name.as_str() == "is-ok" || function_name.as_str() == "is-err"
+                });
+            let message = if let Some(function_name) = tested_by {
+                format!("the result of '{}' is discarded", function_name)
+            } else if self
+                .type_map
+                .and_then(|type_map| type_map.get_type(statement))
+                .map_or(false, contains_response)
+            {
+                "a value containing a response is discarded".to_string()
+            } else {
+                continue;
+            };
+            self.warn(
+                LintRule::DiscardedResponse,
+                &statement.span,
+                message,
+                "Use `try!`, `unwrap!` or `asserts!`, so that an error aborts the transaction.",
+            );
+        }
+    }
+
+    fn check_unused_private_functions(&mut self, expressions: &[SymbolicExpression]) {
+        let mut private_functions = vec![];
+        let mut referenced = HashSet::new();
+        for expr in expressions.iter() {
+            use vm::functions::define::DefineFunctionsParsed::*;
+            match DefineFunctionsParsed::try_parse(expr) {
+                Ok(Some(PrivateFunction { signature, body })) => {
+                    if let Some(name_expr) = signature.get(0) {
+                        private_functions.push(name_expr);
+                    }
+                    collect_atoms(body, &mut referenced);
+                }
+                Ok(Some(PublicFunction { body, .. })) | Ok(Some(ReadOnlyFunction { body, .. })) => {
+                    collect_atoms(body, &mut referenced)
+                }
+                _ => collect_atoms(expr, &mut referenced),
+            }
+        }
+
+        for name_expr in private_functions.into_iter() {
+            if let Some(name) = name_expr.match_atom() {
+                if !referenced.contains(name) {
+                    self.warn(
+                        LintRule::UnusedPrivateFunction,
+                        &name_expr.span,
+                        format!("private function '{}' is never called", name),
+                        "Remove the function if it is not needed.",
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn references_name(expr: &SymbolicExpression, name: &ClarityName) -> bool {
+    if let Some(atom) = expr.match_atom() {
+        return atom == name;
+    }
+    match expr.match_list() {
+        Some(list) => list.iter().any(|child| references_name(child, name)),
+        None => false,
+    }
+}
+
+fn collect_atoms(expr: &SymbolicExpression, atoms: &mut HashSet<ClarityName>) {
+    if let Some(atom) = expr.match_atom() {
+        atoms.insert(atom.clone());
+    }
+    if let Some(list) = expr.match_list() {
+        for child in list.iter() {
+            collect_atoms(child, atoms);
+        }
+    }
+}
+
+/// Find an `(is-eq ...)` that compares against `tx-sender`.
+fn find_tx_sender_comparison(expr: &SymbolicExpression) -> Option<&SymbolicExpression> {
+    let list = expr.match_list()?;
+    let is_comparison = list
+        .get(0)
+        .and_then(|function_name| function_name.match_atom())
+        .map_or(false, |function_name| function_name.as_str() == "is-eq");
+    if is_comparison
+        && list[1..]
+            .iter()
+            .any(|arg| arg.match_atom().map(|name| name.as_str()) == Some("tx-sender"))
+    {
+        return Some(expr);
+    }
+    list.iter().filter_map(find_tx_sender_comparison).next()
+}
+
+fn contains_response(type_signature: &TypeSignature) -> bool {
+    match type_signature {
+        TypeSignature::ResponseType(_) => true,
+        TypeSignature::OptionalType(inner) => contains_response(inner),
+        TypeSignature::TupleType(tuple) => tuple.get_type_map().values().any(contains_response),
+        TypeSignature::SequenceType(SequenceSubtype::ListType(list)) => {
+            contains_response(list.get_list_item_type())
+        }
+        _ => false,
+    }
+}
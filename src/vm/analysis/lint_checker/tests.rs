@@ -0,0 +1,156 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use chainstate::stacks::boot::STACKS_BOOT_CODE_MAINNET;
+use vm::analysis::lint_checker::{LintChecker, LintConfig, LintRule};
+use vm::analysis::mem_type_check;
+use vm::diagnostic::{Diagnostic, Level};
+
+fn lint(contract: &str, config: &LintConfig) -> Vec<Diagnostic> {
+    let (_, analysis) = mem_type_check(contract).unwrap();
+    LintChecker::run(&analysis, config)
+}
+
+/// Get the rule name and line of each warning
+fn lint_summary(contract: &str) -> Vec<(String, u32)> {
+    lint(contract, &LintConfig::default())
+        .into_iter()
+        .map(|warning| {
+            assert_eq!(warning.level, Level::Warning);
+            let rule_start = warning.message.rfind('(').unwrap();
+            (
+                warning.message[rule_start + 1..warning.message.len() - 1].to_string(),
+                warning.spans[0].start_line,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn test_unused_let_binding() {
+    let contract = "(define-read-only (f (x int))
+  (let ((a (+ x 1))
+        (b (* a 2))
+        (c 3))
+    b))";
+    assert_eq!(
+        lint_summary(contract),
+        vec![("unused-let-binding".to_string(), 4)]
+    );
+}
+
+#[test]
+fn test_unused_private_function() {
+    let contract = "(define-private (helper (x int)) (+ x 1))
+(define-private (unused) 1)
+(define-private (double (x int)) (* x 2))
+(define-read-only (f) (helper 1))
+(define-read-only (g (l (list 5 int))) (map double l))";
+    assert_eq!(
+        lint_summary(contract),
+        vec![("unused-private-function".to_string(), 2)]
+    );
+}
+
+#[test]
+fn test_discarded_response() {
+    let contract =
+        "(define-private (pay (recipient principal)) (stx-transfer? u10 tx-sender recipient))
+(define-public (pay-all (recipients (list 5 principal)))
+  (begin
+    (map pay recipients)
+    (is-ok (pay tx-sender))
+    (ok true)))
+(define-public (pay-checked (recipients (list 5 principal)))
+  (begin
+    (try! (pay tx-sender))
+    (ok (map pay recipients))))";
+    assert_eq!(
+        lint_summary(contract),
+        vec![
+            ("discarded-response".to_string(), 4),
+            ("discarded-response".to_string(), 5),
+        ]
+    );
+}
+
+#[test]
+fn test_unwrap_panic_on_input() {
+    let contract = "(define-map balances principal uint)
+(define-public (withdraw (who principal))
+  (ok (unwrap-panic (map-get? balances who))))
+(define-public (total)
+  (ok (unwrap-panic (map-get? balances tx-sender))))
+(define-read-only (peek (who principal))
+  (unwrap-panic (map-get? balances who)))";
+    assert_eq!(
+        lint_summary(contract),
+        vec![("unwrap-panic-on-input".to_string(), 3)]
+    );
+}
+
+#[test]
+fn test_tx_sender_authorization_and_unguarded_var_set() {
+    let contract = "(define-data-var owner principal tx-sender)
+(define-public (set-owner (new-owner principal))
+  (begin
+    (asserts! (is-eq tx-sender (var-get owner)) (err u1))
+    (ok (var-set owner new-owner))))
+(define-public (set-owner-safely (new-owner principal))
+  (begin
+    (asserts! (is-eq contract-caller (var-get owner)) (err u1))
+    (ok (var-set owner new-owner))))
+(define-public (take-ownership)
+  (ok (var-set owner tx-sender)))";
+    assert_eq!(
+        lint_summary(contract),
+        vec![
+            ("tx-sender-authorization".to_string(), 4),
+            ("unguarded-var-set".to_string(), 11),
+        ]
+    );
+}
+
+#[test]
+fn test_lint_config() {
+    let contract = "(define-private (unused) 1)
+(define-read-only (f) (let ((a 1)) 2))";
+    assert_eq!(lint(contract, &LintConfig::default()).len(), 2);
+
+    let config = LintConfig::from_disabled_names("unused-private-function").unwrap();
+    assert!(!config.is_enabled(LintRule::UnusedPrivateFunction));
+    let warnings = lint(contract, &config);
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(
+        warnings[0].message,
+        "let binding 'a' is never used (unused-let-binding)"
+    );
+    assert!(warnings[0].suggestion.is_some());
+
+    let config =
+        LintConfig::from_disabled_names("unused-private-function, unused-let-binding").unwrap();
+    assert_eq!(lint(contract, &config).len(), 0);
+
+    assert!(LintConfig::from_disabled_names("no-such-rule").is_err());
+}
+
+#[test]
+fn test_boot_contracts() {
+    // the lint pass should handle everything in the boot contracts without panicking
+    for (_, code) in STACKS_BOOT_CODE_MAINNET.iter() {
+        lint(code, &LintConfig::default());
+    }
+}
@@ -17,7 +17,9 @@
 pub mod analysis_db;
 pub mod arithmetic_checker;
 pub mod contract_interface_builder;
+pub mod cost_estimator;
 pub mod errors;
+pub mod lint_checker;
 pub mod read_only_checker;
 pub mod trait_checker;
 pub mod type_checker;
@@ -26,6 +28,7 @@ pub mod types;
 pub use self::types::{AnalysisPass, ContractAnalysis};
 use vm::costs::LimitedCostTracker;
 use vm::database::STORE_CONTRACT_SRC_INTERFACE;
+use vm::diagnostic::Diagnostic;
 use vm::representations::SymbolicExpression;
 use vm::types::{QualifiedContractIdentifier, TypeSignature};
 
@@ -114,5 +117,41 @@ pub fn run_analysis(
     }
 }
 
+/// Run the analysis passes in a mode that keeps going after an error: the read-only and type
+/// checkers record a diagnostic for each top-level expression that fails, and the remaining
+/// passes only run if those found no errors.  The contract is never saved.  Returns the
+/// (possibly partial) analysis along with every diagnostic found.
+pub fn run_analysis_with_diagnostics(
+    contract_identifier: &QualifiedContractIdentifier,
+    expressions: &mut [SymbolicExpression],
+    analysis_db: &mut AnalysisDatabase,
+    cost_tracker: LimitedCostTracker,
+) -> (ContractAnalysis, Vec<Diagnostic>) {
+    let mut contract_analysis = ContractAnalysis::new(
+        contract_identifier.clone(),
+        expressions.to_vec(),
+        cost_tracker,
+    );
+    let mut diagnostics = vec![];
+    let result: CheckResult<()> = analysis_db.execute(|db| {
+        ReadOnlyChecker::run_pass_with_diagnostics(&mut contract_analysis, db, &mut diagnostics)?;
+        TypeChecker::run_pass_with_diagnostics(&mut contract_analysis, db, &mut diagnostics)?;
+        if diagnostics.is_empty() {
+            TraitChecker::run_pass(&mut contract_analysis, db)?;
+            ArithmeticOnlyChecker::check_contract_cost_eligible(&mut contract_analysis);
+
+            if STORE_CONTRACT_SRC_INTERFACE {
+                let interface = build_contract_interface(&contract_analysis);
+                contract_analysis.contract_interface = Some(interface);
+            }
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        diagnostics.push(e.diagnostic);
+    }
+    (contract_analysis, diagnostics)
+}
+
 #[cfg(test)]
 mod tests;
@@ -15,7 +15,8 @@
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
 use vm::analysis::types::{AnalysisPass, ContractAnalysis};
-use vm::functions::define::DefineFunctionsParsed;
+use vm::diagnostic::Diagnostic;
+use vm::functions::define::{DefineFunctions, DefineFunctionsParsed};
 use vm::functions::tuples;
 use vm::functions::NativeFunctions;
 use vm::representations::SymbolicExpressionType::{
@@ -59,20 +60,64 @@ impl<'a, 'b> ReadOnlyChecker<'a, 'b> {
         }
     }
 
+    /// Like `run_pass`, but collects a diagnostic for every top-level expression that fails the
+    /// read-only checks instead of returning the first error.
+    pub fn run_pass_with_diagnostics(
+        contract_analysis: &mut ContractAnalysis,
+        analysis_db: &mut AnalysisDatabase,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> CheckResult<()> {
+        let mut command = ReadOnlyChecker::new(analysis_db);
+        command.run_with_diagnostics(contract_analysis, diagnostics)
+    }
+
     pub fn run(&mut self, contract_analysis: &mut ContractAnalysis) -> CheckResult<()> {
         for exp in contract_analysis.expressions.iter() {
-            let mut result = self.check_reads_only_valid(&exp);
-            if let Err(ref mut error) = result {
-                if !error.has_expression() {
-                    error.set_expression(&exp);
+            self.check_top_level(&exp)?;
+        }
+
+        Ok(())
+    }
+
+    /// Like `run`, but rather than stopping at the first error, records a diagnostic for each
+    /// top-level expression that fails the check and moves on to the next one.  Cost errors
+    /// still abort the pass.  A function whose definition fails is registered as read-only,
+    /// so that calls to it aren't reported as well.
+    pub fn run_with_diagnostics(
+        &mut self,
+        contract_analysis: &mut ContractAnalysis,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> CheckResult<()> {
+        for exp in contract_analysis.expressions.iter() {
+            if let Err(error) = self.check_top_level(&exp) {
+                if error.is_cost_error() {
+                    return Err(error);
+                }
+                match DefineFunctions::try_parse_name(&exp) {
+                    Some((DefineFunctions::PrivateFunction, name))
+                    | Some((DefineFunctions::PublicFunction, name))
+                    | Some((DefineFunctions::ReadOnlyFunction, name)) => {
+                        self.defined_functions.entry(name.clone()).or_insert(true);
+                    }
+                    _ => {}
                 }
+                diagnostics.push(error.diagnostic);
             }
-            result?
         }
 
         Ok(())
     }
 
+    fn check_top_level(&mut self, exp: &SymbolicExpression) -> CheckResult<()> {
+        let mut result = self.check_reads_only_valid(&exp);
+        if let Err(ref mut error) = result {
+            if !error.has_expression() {
+                error.set_expression(&exp);
+            }
+        }
+        result
+    }
+
     fn check_define_function(
         &mut self,
         signature: &[SymbolicExpression],
@@ -47,6 +47,7 @@ pub struct ContractContext {
     non_fungible_tokens: HashMap<ClarityName, TypeSignature>,
     traits: HashMap<ClarityName, BTreeMap<ClarityName, FunctionSignature>>,
     pub implemented_traits: HashSet<TraitIdentifier>,
+    referenced_traits: HashMap<ClarityName, TraitIdentifier>,
 }
 
 impl TypeMap {
@@ -86,6 +87,7 @@ impl ContractContext {
             non_fungible_tokens: HashMap::new(),
             traits: HashMap::new(),
             implemented_traits: HashSet::new(),
+            referenced_traits: HashMap::new(),
         }
     }
 
@@ -210,6 +212,16 @@ impl ContractContext {
         Ok(())
     }
 
+    /// Record a `use-trait` alias.  The trait's signature is added with `add_trait`.
+    pub fn add_referenced_trait(
+        &mut self,
+        alias: ClarityName,
+        trait_identifier: TraitIdentifier,
+    ) -> CheckResult<()> {
+        self.referenced_traits.insert(alias, trait_identifier);
+        Ok(())
+    }
+
     pub fn get_trait(&self, trait_name: &str) -> Option<&BTreeMap<ClarityName, FunctionSignature>> {
         self.traits.get(trait_name)
     }
@@ -278,6 +290,10 @@ impl ContractContext {
         for trait_identifier in self.implemented_traits.drain() {
             contract_analysis.add_implemented_trait(trait_identifier);
         }
+
+        for (alias, trait_identifier) in self.referenced_traits.drain() {
+            contract_analysis.add_referenced_trait(alias, trait_identifier);
+        }
     }
 }
 
@@ -18,13 +18,14 @@ pub mod contexts;
 //mod maps;
 pub mod natives;
 
-use std::collections::{BTreeMap, HashMap};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::convert::TryInto;
 use vm::costs::{
     analysis_typecheck_cost, cost_functions, runtime_cost, ClarityCostFunctionReference,
     CostErrors, CostOverflowingMath, CostTracker, ExecutionCost, LimitedCostTracker,
 };
-use vm::functions::define::DefineFunctionsParsed;
+use vm::diagnostic::Diagnostic;
+use vm::functions::define::{DefineFunctions, DefineFunctionsParsed};
 use vm::functions::NativeFunctions;
 use vm::representations::SymbolicExpressionType::{
     Atom, AtomValue, Field, List, LiteralValue, TraitReference,
@@ -133,8 +134,41 @@ impl AnalysisPass for TypeChecker<'_, '_> {
     }
 }
 
+impl TypeChecker<'_, '_> {
+    /// Like `run_pass`, but collects a diagnostic for every top-level expression that fails to
+    /// type check instead of returning the first error.
+    pub fn run_pass_with_diagnostics(
+        contract_analysis: &mut ContractAnalysis,
+        analysis_db: &mut AnalysisDatabase,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> CheckResult<()> {
+        let cost_track = contract_analysis.take_contract_cost_tracker();
+        let mut command = TypeChecker::new(analysis_db, cost_track);
+        match command.run_with_diagnostics(contract_analysis, diagnostics) {
+            Ok(_) => {
+                let cost_track = command.into_contract_analysis(contract_analysis);
+                contract_analysis.replace_contract_cost_tracker(cost_track);
+                Ok(())
+            }
+            err => {
+                let TypeChecker { cost_track, .. } = command;
+                contract_analysis.replace_contract_cost_tracker(cost_track);
+                err
+            }
+        }
+    }
+}
+
 pub type TypeResult = CheckResult<TypeSignature>;
 
+fn refers_to_any(expression: &SymbolicExpression, names: &HashSet<ClarityName>) -> bool {
+    match expression.expr {
+        Atom(ref name) | TraitReference(ref name, _) => names.contains(name),
+        List(ref expressions) => expressions.iter().any(|e| refers_to_any(e, names)),
+        _ => false,
+    }
+}
+
 impl FunctionType {
     pub fn check_args<T: CostTracker>(
         &self,
@@ -365,8 +399,67 @@ impl<'a, 'b> TypeChecker<'a, 'b> {
     }
 
     pub fn run(&mut self, contract_analysis: &mut ContractAnalysis) -> CheckResult<()> {
-        // charge for the eventual storage cost of the analysis --
-        //  it is linear in the size of the AST.
+        self.charge_analysis_storage(contract_analysis)?;
+
+        let mut local_context = TypingContext::new();
+
+        for exp in contract_analysis.expressions.iter() {
+            self.type_check_top_level(&exp, &mut local_context)?;
+        }
+        Ok(())
+    }
+
+    /// Like `run`, but rather than stopping at the first error, records a diagnostic for each
+    /// top-level expression that fails to type check and moves on to the next one.  Cost errors
+    /// still abort the pass.
+    ///
+    /// A definition that fails is registered with a placeholder signature, so that its uses
+    /// don't cascade into further errors.  Maps, data variables and tokens are registered with
+    /// their declared types, if those parse.  Any other failed definition has no type to
+    /// register, so it is recorded as failed, and the top-level expressions that refer to it
+    /// are not checked (and fail in turn, if they are definitions).
+    pub fn run_with_diagnostics(
+        &mut self,
+        contract_analysis: &mut ContractAnalysis,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> CheckResult<()> {
+        self.charge_analysis_storage(contract_analysis)?;
+
+        let mut local_context = TypingContext::new();
+        let mut failed_definitions = HashSet::new();
+
+        for exp in contract_analysis.expressions.iter() {
+            if refers_to_any(&exp, &failed_definitions) {
+                if let Some((_, name)) = DefineFunctions::try_parse_name(&exp) {
+                    failed_definitions.insert(name.clone());
+                }
+                continue;
+            }
+            if let Err(mut error) = self.type_check_top_level(&exp, &mut local_context) {
+                if error.is_cost_error() {
+                    return Err(error);
+                }
+                // a function definition can fail before its return tracker is reset.
+                self.function_return_tracker = None;
+                if !self.try_register_placeholder(&exp) {
+                    if let Some((_, name)) = DefineFunctions::try_parse_name(&exp) {
+                        if self.contract_context.check_name_used(name).is_ok() {
+                            failed_definitions.insert(name.clone());
+                        }
+                    }
+                }
+                if !error.has_expression() {
+                    error.set_expression(&exp);
+                }
+                diagnostics.push(error.diagnostic);
+            }
+        }
+        Ok(())
+    }
+
+    // charge for the eventual storage cost of the analysis --
+    //  it is linear in the size of the AST.
+    fn charge_analysis_storage(&mut self, contract_analysis: &ContractAnalysis) -> CheckResult<()> {
         let mut size: u64 = 0;
         for exp in contract_analysis.expressions.iter() {
             depth_traverse(exp, |_x| match size.cost_overflow_add(1) {
@@ -379,22 +472,72 @@ impl<'a, 'b> TypeChecker<'a, 'b> {
         }
 
         runtime_cost(ClarityCostFunction::AnalysisStorage, self, size)?;
+        Ok(())
+    }
 
-        let mut local_context = TypingContext::new();
-
-        for exp in contract_analysis.expressions.iter() {
-            let mut result_res = self.try_type_check_define(&exp, &mut local_context);
-            if let Err(ref mut error) = result_res {
-                if !error.has_expression() {
-                    error.set_expression(&exp);
+    // Registers a failed map, data variable or token definition with its declared types.
+    //  Returns false if the expression is some other definition, or if its types don't parse.
+    fn try_register_placeholder(&mut self, expression: &SymbolicExpression) -> bool {
+        let define_type = match DefineFunctionsParsed::try_parse(expression) {
+            Ok(Some(define_type)) => define_type,
+            _ => return false,
+        };
+        // if the name is already taken, uses of it check against the existing definition,
+        //  so a placeholder that can't be added is fine.
+        let _ = match define_type {
+            DefineFunctionsParsed::Map {
+                name,
+                key_type,
+                value_type,
+            } => {
+                let key_type = TypeSignature::parse_type_repr(key_type, &mut ());
+                let value_type = TypeSignature::parse_type_repr(value_type, &mut ());
+                match (key_type, value_type) {
+                    (Ok(key_type), Ok(value_type)) => self
+                        .contract_context
+                        .add_map_type(name.clone(), (key_type, value_type)),
+                    _ => return false,
                 }
             }
-            let result = result_res?;
-            if result.is_none() {
-                // was _not_ a define statement, so handle like a normal statement.
-                self.type_check(&exp, &local_context)?;
+            DefineFunctionsParsed::PersistedVariable {
+                name, data_type, ..
+            } => match TypeSignature::parse_type_repr(data_type, &mut ()) {
+                Ok(data_type) => self
+                    .contract_context
+                    .add_persisted_variable_type(name.clone(), data_type),
+                Err(_) => return false,
+            },
+            DefineFunctionsParsed::BoundedFungibleToken { name, .. }
+            | DefineFunctionsParsed::UnboundedFungibleToken { name } => {
+                self.contract_context.add_ft(name.clone())
+            }
+            DefineFunctionsParsed::NonFungibleToken { name, nft_type } => {
+                match TypeSignature::parse_type_repr(nft_type, &mut ()) {
+                    Ok(nft_type) => self.contract_context.add_nft(name.clone(), nft_type),
+                    Err(_) => return false,
+                }
+            }
+            _ => return false,
+        };
+        true
+    }
+
+    fn type_check_top_level(
+        &mut self,
+        exp: &SymbolicExpression,
+        local_context: &mut TypingContext,
+    ) -> CheckResult<()> {
+        let mut result_res = self.try_type_check_define(&exp, local_context);
+        if let Err(ref mut error) = result_res {
+            if !error.has_expression() {
+                error.set_expression(&exp);
             }
         }
+        let result = result_res?;
+        if result.is_none() {
+            // was _not_ a define statement, so handle like a normal statement.
+            self.type_check(&exp, &local_context)?;
+        }
         Ok(())
     }
 
@@ -896,7 +1039,9 @@ impl<'a, 'b> TypeChecker<'a, 'b> {
                             )?;
                             runtime_cost(ClarityCostFunction::AnalysisBindName, self, type_size)?;
                             self.contract_context
-                                .add_trait(trait_identifier.name.clone(), trait_sig)?
+                                .add_trait(trait_identifier.name.clone(), trait_sig)?;
+                            self.contract_context
+                                .add_referenced_trait(name.clone(), trait_identifier.clone())?
                         }
                         None => {
                             // still had to do a db read, even if it didn't exist!
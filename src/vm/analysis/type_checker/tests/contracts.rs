@@ -18,7 +18,7 @@ use assert_json_diff;
 use serde_json;
 
 use crate::clarity_vm::database::MemoryBackingStore;
-use vm::analysis::errors::CheckErrors;
+use vm::analysis::errors::{CheckError, CheckErrors};
 use vm::analysis::mem_type_check;
 use vm::analysis::type_check;
 use vm::analysis::{contract_interface_builder::build_contract_interface, AnalysisDatabase};
@@ -367,7 +367,10 @@ fn test_names_tokens_contracts_interface() {
             { "name": "d-var3", "access": "variable", "type": { "buffer": { "length": 5 } } }
         ],
         "fungible_tokens": [],
-        "non_fungible_tokens": []
+        "non_fungible_tokens": [],
+        "defined_traits": [],
+        "implemented_traits": [],
+        "referenced_traits": []
     }"#).unwrap();
 
     eprintln!("{}", test_contract_json_str);
@@ -375,6 +378,99 @@ fn test_names_tokens_contracts_interface() {
     assert_json_eq!(test_contract_json, test_contract_json_expected);
 }
 
+#[test]
+fn test_traits_contract_interface() {
+    let trait_contract_src = "(define-trait token-trait
+            ((transfer? (principal principal uint) (response uint uint))
+             (get-balance (principal) (response uint uint))))";
+    let impl_contract_src = "(use-trait token .defun.token-trait)
+        (impl-trait .defun.token-trait)
+        (define-trait local-trait ((ping () (response bool uint))))
+        (define-public (transfer? (from principal) (to principal) (amount uint)) (ok amount))
+        (define-public (get-balance (owner principal)) (ok u0))
+        (define-public (forward (contract <token>) (owner principal))
+            (contract-call? contract get-balance owner))";
+    // a local trait that shares its name with a referenced trait
+    let shadow_contract_src = "(use-trait token .defun.token-trait)
+        (define-trait token-trait ((ping () (response bool uint))))";
+    let trait_contract_id = QualifiedContractIdentifier::local("defun").unwrap();
+    let impl_contract_id = QualifiedContractIdentifier::local("implem").unwrap();
+    let shadow_contract_id = QualifiedContractIdentifier::local("shadow").unwrap();
+    let mut trait_contract = parse(&trait_contract_id, trait_contract_src).unwrap();
+    let mut impl_contract = parse(&impl_contract_id, impl_contract_src).unwrap();
+    let mut shadow_contract = parse(&shadow_contract_id, shadow_contract_src).unwrap();
+    let mut marf = MemoryBackingStore::new();
+    let mut db = marf.as_analysis_db();
+
+    let (trait_analysis, impl_analysis, shadow_analysis) = db
+        .execute(|db| {
+            let trait_analysis = type_check(&trait_contract_id, &mut trait_contract, db, true)?;
+            let impl_analysis = type_check(&impl_contract_id, &mut impl_contract, db, true)?;
+            let shadow_analysis = type_check(&shadow_contract_id, &mut shadow_contract, db, false)?;
+            Ok::<_, CheckError>((trait_analysis, impl_analysis, shadow_analysis))
+        })
+        .unwrap();
+
+    let trait_interface = build_contract_interface(&trait_analysis);
+    let trait_interface_json = serde_json::to_value(&trait_interface.defined_traits).unwrap();
+    let trait_interface_expected: serde_json::Value = serde_json::from_str(
+        r#"[{
+            "name": "token-trait",
+            "functions": [
+                { "name": "get-balance",
+                  "args": [{ "type": "principal" }],
+                  "outputs": { "type": { "response": { "ok": "uint128", "error": "uint128" } } } },
+                { "name": "transfer?",
+                  "args": [{ "type": "principal" }, { "type": "principal" }, { "type": "uint128" }],
+                  "outputs": { "type": { "response": { "ok": "uint128", "error": "uint128" } } } }
+            ]
+        }]"#,
+    )
+    .unwrap();
+    assert_json_eq!(trait_interface_json, trait_interface_expected);
+    assert!(trait_interface.implemented_traits.is_empty());
+    assert!(trait_interface.referenced_traits.is_empty());
+
+    let impl_interface = build_contract_interface(&impl_analysis);
+    let defined_names: Vec<_> = impl_interface
+        .defined_traits
+        .iter()
+        .map(|defined| defined.name.as_str())
+        .collect();
+    assert_eq!(defined_names, vec!["local-trait"]);
+    assert_eq!(
+        impl_interface.implemented_traits,
+        vec!["S1G2081040G2081040G2081040G208105NK8PE5.defun.token-trait".to_string()]
+    );
+    let referenced_json = serde_json::to_value(&impl_interface.referenced_traits).unwrap();
+    assert_json_eq!(
+        referenced_json,
+        json!([{
+            "name": "token",
+            "trait_identifier": "S1G2081040G2081040G2081040G208105NK8PE5.defun.token-trait"
+        }])
+    );
+
+    // the interface stored with the analysis is the same one
+    assert_eq!(impl_analysis.contract_interface, Some(impl_interface));
+
+    // the local trait is listed, with its own signature
+    let shadow_interface = build_contract_interface(&shadow_analysis);
+    let shadow_json = serde_json::to_value(&shadow_interface.defined_traits).unwrap();
+    assert_json_eq!(
+        shadow_json,
+        json!([{
+            "name": "token-trait",
+            "functions": [
+                { "name": "ping",
+                  "args": [],
+                  "outputs": { "type": { "response": { "ok": "bool", "error": "uint128" } } } }
+            ]
+        }])
+    );
+    assert_eq!(shadow_interface.referenced_traits.len(), 1);
+}
+
 #[test]
 fn test_names_tokens_contracts() {
     let tokens_contract_id = QualifiedContractIdentifier::local("tokens").unwrap();
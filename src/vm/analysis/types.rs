@@ -17,9 +17,11 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap};
 use vm::analysis::analysis_db::AnalysisDatabase;
 use vm::analysis::contract_interface_builder::ContractInterface;
+use vm::analysis::cost_estimator::CostEstimate;
 use vm::analysis::errors::{CheckErrors, CheckResult};
 use vm::analysis::type_checker::contexts::TypeMap;
 use vm::costs::{CostTracker, ExecutionCost, LimitedCostTracker};
+use vm::diagnostic::Diagnostic;
 use vm::types::signatures::FunctionSignature;
 use vm::types::{FunctionType, QualifiedContractIdentifier, TraitIdentifier, TypeSignature};
 use vm::{ClarityName, SymbolicExpression};
@@ -49,6 +51,9 @@ pub struct ContractAnalysis {
     pub non_fungible_tokens: BTreeMap<ClarityName, TypeSignature>,
     pub defined_traits: BTreeMap<ClarityName, BTreeMap<ClarityName, FunctionSignature>>,
     pub implemented_traits: BTreeSet<TraitIdentifier>,
+    /// `use-trait` aliases.  The traits' signatures are also in `defined_traits`.
+    #[serde(default)]
+    pub referenced_traits: BTreeMap<ClarityName, TraitIdentifier>,
     pub contract_interface: Option<ContractInterface>,
     pub is_cost_contract_eligible: bool,
     #[serde(skip)]
@@ -57,6 +62,12 @@ pub struct ContractAnalysis {
     pub type_map: Option<TypeMap>,
     #[serde(skip)]
     pub cost_track: Option<LimitedCostTracker>,
+    /// Only filled in by the lint pass
+    #[serde(skip)]
+    pub lint_warnings: Vec<Diagnostic>,
+    /// Only filled in by the cost estimator
+    #[serde(skip)]
+    pub cost_estimates: BTreeMap<ClarityName, CostEstimate>,
 }
 
 impl ContractAnalysis {
@@ -78,10 +89,13 @@ impl ContractAnalysis {
             persisted_variable_types: BTreeMap::new(),
             defined_traits: BTreeMap::new(),
             implemented_traits: BTreeSet::new(),
+            referenced_traits: BTreeMap::new(),
             fungible_tokens: BTreeSet::new(),
             non_fungible_tokens: BTreeMap::new(),
             cost_track: Some(cost_track),
             is_cost_contract_eligible: false,
+            lint_warnings: vec![],
+            cost_estimates: BTreeMap::new(),
         }
     }
 
@@ -150,6 +164,10 @@ impl ContractAnalysis {
         self.implemented_traits.insert(trait_identifier);
     }
 
+    pub fn add_referenced_trait(&mut self, alias: ClarityName, trait_identifier: TraitIdentifier) {
+        self.referenced_traits.insert(alias, trait_identifier);
+    }
+
     pub fn get_public_function_type(&self, name: &str) -> Option<&FunctionType> {
         self.public_function_types.get(name)
     }
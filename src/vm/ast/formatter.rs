@@ -0,0 +1,394 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Canonical formatting of Clarity source code.
+//!
+//! The rules are deliberately simple, so that formatting is idempotent:
+//! * every top-level form starts on its own line, and runs of blank lines between top-level
+//!   forms collapse into one
+//! * a list or tuple that fits within `MAX_LINE_WIDTH` and contains no comments goes on one line
+//! * otherwise, a list whose head is an atom keeps the head and its first argument on the first
+//!   line, and puts every other element on its own line, indented by `INDENT`.  Lists whose head
+//!   is not an atom (such as `let` bindings), and tuples, put one element per line, aligned
+//!   just inside the opening bracket.
+//! * comments that followed another element on the same line stay there, and all others go on
+//!   their own line.
+
+use vm::ast::errors::{ParseError, ParseErrors, ParseResult};
+use vm::ast::parser;
+use vm::ast::parser::cst::{parse_cst, CstNode};
+use vm::representations::{PreSymbolicExpression, PreSymbolicExpressionType};
+
+pub const MAX_LINE_WIDTH: usize = 80;
+pub const INDENT: usize = 2;
+
+/// An element of a list, tuple or the top level, with the whitespace around it boiled down to
+/// what the formatter cares about.
+enum Item<'a> {
+    Node(&'a CstNode),
+    Colon,
+    Comma,
+    /// `trailing` if the comment was on the same line as the element before it
+    Comment {
+        text: &'a str,
+        trailing: bool,
+    },
+    /// One or more blank lines
+    BlankLine,
+}
+
+#[derive(PartialEq)]
+enum Previous {
+    Open,
+    Node,
+    Separator,
+    Comment,
+}
+
+fn get_items(nodes: &[CstNode]) -> Vec<Item> {
+    let mut items = vec![];
+    let mut newlines = 0;
+    for node in nodes.iter() {
+        match node {
+            CstNode::Whitespace(text) => {
+                newlines += text.matches('\n').count();
+                continue;
+            }
+            _ => {}
+        }
+
+        if newlines >= 2 && items.len() > 0 {
+            items.push(Item::BlankLine);
+        }
+        let item = match node {
+            CstNode::Comment(text) => Item::Comment {
+                text,
+                trailing: newlines == 0 && items.len() > 0,
+            },
+            CstNode::Colon => Item::Colon,
+            CstNode::Comma => Item::Comma,
+            _ => Item::Node(node),
+        };
+        items.push(item);
+        newlines = 0;
+    }
+    items
+}
+
+/// Render `node` on a single line, if it has no comments.
+fn format_flat(node: &CstNode) -> Option<String> {
+    match node {
+        CstNode::Token(text) => Some(text.clone()),
+        CstNode::List(children) | CstNode::Tuple(children) => {
+            let is_tuple = match node {
+                CstNode::Tuple(_) => true,
+                _ => false,
+            };
+            let mut out = String::new();
+            out.push(if is_tuple { '{' } else { '(' });
+            let mut previous = Previous::Open;
+            for item in get_items(children).iter() {
+                match item {
+                    Item::Node(child) => {
+                        if previous != Previous::Open {
+                            out.push(' ');
+                        }
+                        out.push_str(&format_flat(child)?);
+                        previous = Previous::Node;
+                    }
+                    Item::Colon => {
+                        out.push(':');
+                        previous = Previous::Separator;
+                    }
+                    Item::Comma => {
+                        out.push(',');
+                        previous = Previous::Separator;
+                    }
+                    Item::Comment { .. } => return None,
+                    Item::BlankLine => {}
+                }
+            }
+            out.push(if is_tuple { '}' } else { ')' });
+            Some(out)
+        }
+        CstNode::Colon => Some(":".to_string()),
+        CstNode::Comma => Some(",".to_string()),
+        CstNode::Comment(_) => None,
+        CstNode::Whitespace(_) => Some("".to_string()),
+    }
+}
+
+/// The column that the next character written to `out` will be in, if `out` was started at
+/// `start_column`.
+fn current_column(out: &str, start_column: usize) -> usize {
+    match out.rfind('\n') {
+        Some(index) => out.len() - index - 1,
+        None => start_column + out.len(),
+    }
+}
+
+fn push_line(out: &mut String, column: usize) {
+    out.push('\n');
+    for _ in 0..column {
+        out.push(' ');
+    }
+}
+
+/// Render `node`, which starts at `column`.
+fn format_node(node: &CstNode, column: usize) -> String {
+    if let Some(flat) = format_flat(node) {
+        if column + flat.len() <= MAX_LINE_WIDTH {
+            return flat;
+        }
+    }
+
+    let (children, is_tuple) = match node {
+        CstNode::List(children) => (children, false),
+        CstNode::Tuple(children) => (children, true),
+        _ => return format_flat(node).unwrap_or_default(),
+    };
+
+    let items = get_items(children);
+    let atom_head = match items.first() {
+        Some(Item::Node(CstNode::Token(_))) => !is_tuple,
+        _ => false,
+    };
+    let child_column = if atom_head {
+        column + INDENT
+    } else {
+        column + 1
+    };
+
+    let mut out = String::new();
+    out.push(if is_tuple { '{' } else { '(' });
+    let mut previous = Previous::Open;
+    let mut num_nodes = 0;
+    for item in items.iter() {
+        match item {
+            Item::Node(child) => {
+                let same_line = match previous {
+                    Previous::Open => true,
+                    Previous::Node => atom_head && num_nodes == 1,
+                    // the value following a tuple key's colon
+                    Previous::Separator => is_tuple && out.ends_with(':'),
+                    Previous::Comment => false,
+                };
+                if same_line {
+                    if previous != Previous::Open {
+                        out.push(' ');
+                    }
+                } else {
+                    push_line(&mut out, child_column);
+                }
+                let child_start = current_column(&out, column);
+                out.push_str(&format_node(child, child_start));
+                num_nodes += 1;
+                previous = Previous::Node;
+            }
+            Item::Colon | Item::Comma => {
+                if previous == Previous::Comment {
+                    push_line(&mut out, child_column);
+                }
+                out.push(match item {
+                    Item::Colon => ':',
+                    _ => ',',
+                });
+                previous = Previous::Separator;
+            }
+            Item::Comment { text, trailing } => {
+                if *trailing && previous != Previous::Comment {
+                    out.push(' ');
+                } else {
+                    push_line(&mut out, child_column);
+                }
+                out.push_str(text);
+                previous = Previous::Comment;
+            }
+            Item::BlankLine => {}
+        }
+    }
+    if previous == Previous::Comment {
+        push_line(&mut out, column);
+    }
+    out.push(if is_tuple { '}' } else { ')' });
+    out
+}
+
+/// Format a concrete syntax tree.
+pub fn format_cst(nodes: &[CstNode]) -> String {
+    let mut out = String::new();
+    let mut blank_line = false;
+    for item in get_items(nodes).iter() {
+        match item {
+            Item::BlankLine => {
+                blank_line = true;
+            }
+            Item::Comment {
+                text,
+                trailing: true,
+            } => {
+                out.push(' ');
+                out.push_str(text);
+            }
+            _ => {
+                if out.len() > 0 {
+                    out.push('\n');
+                    if blank_line {
+                        out.push('\n');
+                    }
+                }
+                blank_line = false;
+                match item {
+                    Item::Node(node) => out.push_str(&format_node(node, 0)),
+                    Item::Comment { text, .. } => out.push_str(text),
+                    Item::Colon => out.push(':'),
+                    Item::Comma => out.push(','),
+                    Item::BlankLine => {}
+                }
+            }
+        }
+    }
+    if out.len() > 0 {
+        out.push('\n');
+    }
+    out
+}
+
+fn clear_spans(expressions: &mut [PreSymbolicExpression]) {
+    for expr in expressions.iter_mut() {
+        expr.set_span(0, 0, 0, 0);
+        match expr.pre_expr {
+            PreSymbolicExpressionType::List(ref mut children)
+            | PreSymbolicExpressionType::Tuple(ref mut children) => clear_spans(children),
+            _ => {}
+        }
+    }
+}
+
+/// Format Clarity source code.  Fails if the source does not parse, or (as a safeguard) if the
+/// formatted code would parse differently from the original.
+pub fn format_source(source: &str) -> ParseResult<String> {
+    let mut original = parser::parse(source)?;
+    let formatted = format_cst(&parse_cst(source)?);
+
+    let mut reparsed = parser::parse(&formatted)
+        .map_err(|_| ParseError::new(ParseErrors::FormattingChangedProgram))?;
+    clear_spans(&mut original);
+    clear_spans(&mut reparsed);
+    if original != reparsed {
+        return Err(ParseError::new(ParseErrors::FormattingChangedProgram));
+    }
+
+    Ok(formatted)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use chainstate::stacks::boot::STACKS_BOOT_CODE_MAINNET;
+    use std::fs;
+
+    fn assert_formats_to(input: &str, expected: &str) {
+        let formatted = format_source(input).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format_source(&formatted).unwrap(), formatted);
+    }
+
+    #[test]
+    fn test_format_short_forms() {
+        assert_formats_to(
+            "(define-data-var   counter int 0)   (define-constant ERR (err u1))\n\n\n\n(define-map m {a: int,  b: uint} bool)",
+            "(define-data-var counter int 0)\n(define-constant ERR (err u1))\n\n(define-map m {a: int, b: uint} bool)\n",
+        );
+        assert_formats_to("", "");
+        assert_formats_to(";; just a comment", ";; just a comment\n");
+    }
+
+    #[test]
+    fn test_format_long_forms() {
+        let input = "(define-public (transfer (amount uint) (sender principal) (recipient principal))
+  (begin (asserts! (is-eq tx-sender sender) (err u1)) (ft-transfer? token amount sender recipient)))";
+        let expected =
+            "(define-public (transfer (amount uint) (sender principal) (recipient principal))
+  (begin (asserts! (is-eq tx-sender sender) (err u1))
+    (ft-transfer? token amount sender recipient)))
+";
+        assert_formats_to(input, expected);
+
+        let input = "(define-read-only (get-info) (let ((a (var-get some-long-variable-name)) (b (var-get another-long-variable-name))) {first: a, second: b, third: (+ a b), fourth: (- a b)}))";
+        let expected = "(define-read-only (get-info)
+  (let ((a (var-get some-long-variable-name))
+        (b (var-get another-long-variable-name)))
+    {first: a, second: b, third: (+ a b), fourth: (- a b)}))
+";
+        assert_formats_to(input, expected);
+
+        let input = "(define-constant config {name: \"a very long name for a contract constant\", owner: tx-sender, enabled: true,})";
+        let expected = "(define-constant config
+  {name: \"a very long name for a contract constant\",
+   owner: tx-sender,
+   enabled: true,})
+";
+        assert_formats_to(input, expected);
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let input = ";; header
+
+
+;; about counter
+(define-data-var counter int 0) ;; starts at zero
+(define-public (increment) ;; bump it
+    ;; add one
+    (ok (var-set counter (+ (var-get counter) 1))) ;; done
+)";
+        let expected = ";; header
+
+;; about counter
+(define-data-var counter int 0) ;; starts at zero
+(define-public (increment) ;; bump it
+  ;; add one
+  (ok (var-set counter (+ (var-get counter) 1))) ;; done
+)
+";
+        assert_formats_to(input, expected);
+    }
+
+    #[test]
+    fn test_format_rejects_invalid_source() {
+        assert_eq!(
+            format_source("(define-constant a 1").unwrap_err().err,
+            ParseErrors::ClosingParenthesisExpected
+        );
+    }
+
+    #[test]
+    fn test_format_contracts() {
+        let mut sources: Vec<String> = STACKS_BOOT_CODE_MAINNET
+            .iter()
+            .map(|(_, code)| code.to_string())
+            .collect();
+        for name in ["tokens", "names"].iter() {
+            sources.push(fs::read_to_string(format!("sample-contracts/{}.clar", name)).unwrap());
+        }
+
+        for source in sources.iter() {
+            let formatted = format_source(source).unwrap();
+            assert_eq!(format_source(&formatted).unwrap(), formatted);
+        }
+    }
+}
@@ -16,6 +16,7 @@
 
 pub mod definition_sorter;
 pub mod expression_identifier;
+pub mod formatter;
 pub mod parser;
 pub mod traits_resolver;
 
@@ -24,13 +25,14 @@ pub mod stack_depth_checker;
 pub mod sugar_expander;
 pub mod types;
 use vm::costs::{cost_functions, runtime_cost, CostTracker, LimitedCostTracker};
+use vm::diagnostic::Diagnostic;
 use vm::errors::{Error, RuntimeErrorType};
 
 use vm::representations::SymbolicExpression;
 use vm::types::QualifiedContractIdentifier;
 
 use self::definition_sorter::DefinitionSorter;
-use self::errors::ParseResult;
+use self::errors::{ParseError, ParseResult};
 use self::expression_identifier::ExpressionIdentifier;
 use self::stack_depth_checker::StackDepthChecker;
 use self::sugar_expander::SugarExpander;
@@ -60,15 +62,52 @@ pub fn build_ast<T: CostTracker>(
     )?;
     let pre_expressions = parser::parse(source_code)?;
     let mut contract_ast = ContractAST::new(contract_identifier.clone(), pre_expressions);
-    StackDepthChecker::run_pass(&mut contract_ast)?;
-    ExpressionIdentifier::run_pre_expression_pass(&mut contract_ast)?;
-    DefinitionSorter::run_pass(&mut contract_ast, cost_track)?;
-    TraitsResolver::run_pass(&mut contract_ast)?;
-    SugarExpander::run_pass(&mut contract_ast)?;
-    ExpressionIdentifier::run_expression_pass(&mut contract_ast)?;
+    run_ast_passes(&mut contract_ast, cost_track)?;
     Ok(contract_ast)
 }
 
+/// Build an AST, but rather than stopping at the first parse error, skip to the next top-level
+/// form and keep going.  Returns the AST of whatever could be parsed, a diagnostic for every
+/// error encountered, and whether or not the AST was built without errors.
+pub fn build_ast_with_diagnostics<T: CostTracker>(
+    contract_identifier: &QualifiedContractIdentifier,
+    source_code: &str,
+    cost_track: &mut T,
+) -> (ContractAST, Vec<Diagnostic>, bool) {
+    let mut contract_ast = ContractAST::new(contract_identifier.clone(), vec![]);
+    if let Err(e) = runtime_cost(
+        ClarityCostFunction::AstParse,
+        cost_track,
+        source_code.len() as u64,
+    ) {
+        return (contract_ast, vec![ParseError::from(e).diagnostic], false);
+    }
+
+    let (pre_expressions, errors) = parser::parse_collect_errors(source_code);
+    let mut diagnostics: Vec<Diagnostic> = errors.into_iter().map(|e| e.diagnostic).collect();
+
+    contract_ast.pre_expressions = pre_expressions;
+    if let Err(e) = run_ast_passes(&mut contract_ast, cost_track) {
+        diagnostics.push(e.diagnostic);
+    }
+
+    let success = diagnostics.is_empty();
+    (contract_ast, diagnostics, success)
+}
+
+fn run_ast_passes<T: CostTracker>(
+    contract_ast: &mut ContractAST,
+    cost_track: &mut T,
+) -> ParseResult<()> {
+    StackDepthChecker::run_pass(contract_ast)?;
+    ExpressionIdentifier::run_pre_expression_pass(contract_ast)?;
+    DefinitionSorter::run_pass(contract_ast, cost_track)?;
+    TraitsResolver::run_pass(contract_ast)?;
+    SugarExpander::run_pass(contract_ast)?;
+    ExpressionIdentifier::run_expression_pass(contract_ast)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
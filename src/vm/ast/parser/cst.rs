@@ -0,0 +1,187 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A lossless concrete syntax tree for Clarity source code.  Unlike the pre-expressions that
+//! `parse` produces, the CST keeps comments, whitespace and the exact text of every token, so
+//! the source can be reproduced byte-for-byte with `to_source`.
+
+use vm::ast::errors::{ParseError, ParseErrors, ParseResult};
+
+use super::{lex_with_text, LexItem};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CstNode {
+    /// An atom or literal, exactly as written
+    Token(String),
+    /// `( ... )`
+    List(Vec<CstNode>),
+    /// `{ ... }`
+    Tuple(Vec<CstNode>),
+    Colon,
+    Comma,
+    /// A `;;` comment, not including the line break that ends it
+    Comment(String),
+    /// Spaces, tabs and line breaks
+    Whitespace(String),
+}
+
+impl CstNode {
+    fn write_source(&self, out: &mut String) {
+        match self {
+            CstNode::Token(text) | CstNode::Comment(text) | CstNode::Whitespace(text) => {
+                out.push_str(text)
+            }
+            CstNode::List(children) => {
+                out.push('(');
+                for child in children.iter() {
+                    child.write_source(out);
+                }
+                out.push(')');
+            }
+            CstNode::Tuple(children) => {
+                out.push('{');
+                for child in children.iter() {
+                    child.write_source(out);
+                }
+                out.push('}');
+            }
+            CstNode::Colon => out.push(':'),
+            CstNode::Comma => out.push(','),
+        }
+    }
+}
+
+/// Reproduce the source code that `nodes` were parsed from.
+pub fn to_source(nodes: &[CstNode]) -> String {
+    let mut out = String::new();
+    for node in nodes.iter() {
+        node.write_source(&mut out);
+    }
+    out
+}
+
+/// Parse `input` into a concrete syntax tree.  This only checks that the input lexes and that
+/// its parentheses and braces balance; use `parse` to check that it is a valid program.
+pub fn parse_cst(input: &str) -> ParseResult<Vec<CstNode>> {
+    let lexed = lex_with_text(input)?;
+
+    // each entry is an open list or tuple, and whether or not it's a tuple
+    let mut stack: Vec<(Vec<CstNode>, bool)> = vec![];
+    let mut output = vec![];
+
+    for (item, text, line, column) in lexed.into_iter() {
+        let node = match item {
+            LexItem::LeftParen => {
+                stack.push((vec![], false));
+                continue;
+            }
+            LexItem::LeftCurly => {
+                stack.push((vec![], true));
+                continue;
+            }
+            LexItem::RightParen => match stack.pop() {
+                Some((children, false)) => CstNode::List(children),
+                Some((_, true)) => {
+                    let mut error = ParseError::new(ParseErrors::ClosingTupleLiteralExpected);
+                    error.diagnostic.add_span(line, column, line, column);
+                    return Err(error);
+                }
+                None => return Err(ParseError::new(ParseErrors::ClosingParenthesisUnexpected)),
+            },
+            LexItem::RightCurly => match stack.pop() {
+                Some((children, true)) => CstNode::Tuple(children),
+                Some((_, false)) => {
+                    let mut error = ParseError::new(ParseErrors::ClosingParenthesisExpected);
+                    error.diagnostic.add_span(line, column, line, column);
+                    return Err(error);
+                }
+                None => return Err(ParseError::new(ParseErrors::ClosingTupleLiteralUnexpected)),
+            },
+            LexItem::ColonSeparator => CstNode::Colon,
+            LexItem::CommaSeparator => CstNode::Comma,
+            LexItem::Whitespace => {
+                if text.starts_with(";;") {
+                    CstNode::Comment(text.to_string())
+                } else {
+                    CstNode::Whitespace(text.to_string())
+                }
+            }
+            LexItem::LiteralValue(..)
+            | LexItem::SugaredContractIdentifier(..)
+            | LexItem::SugaredFieldIdentifier(..)
+            | LexItem::FieldIdentifier(..)
+            | LexItem::TraitReference(..)
+            | LexItem::Variable(..) => CstNode::Token(text.to_string()),
+        };
+
+        match stack.last_mut() {
+            Some((children, _)) => children.push(node),
+            None => output.push(node),
+        }
+    }
+
+    match stack.pop() {
+        Some((_, false)) => Err(ParseError::new(ParseErrors::ClosingParenthesisExpected)),
+        Some((_, true)) => Err(ParseError::new(ParseErrors::ClosingTupleLiteralExpected)),
+        None => Ok(output),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_cst_lossless() {
+        let input = r#";; a counter
+(define-data-var counter int 0) ;; starts at zero
+
+(define-public (increment)   ;; bump it
+    (begin
+        (var-set counter (+ (var-get counter) 1))
+        (ok {value: (var-get counter), label: u"n\u{2764}",})))
+"#;
+        let cst = parse_cst(input).unwrap();
+        assert_eq!(to_source(&cst), input);
+
+        assert_eq!(cst[0], CstNode::Comment(";; a counter".to_string()));
+        assert_eq!(cst[1], CstNode::Whitespace("\n".to_string()));
+        match cst[2] {
+            CstNode::List(ref children) => {
+                assert_eq!(children[0], CstNode::Token("define-data-var".to_string()));
+                assert_eq!(children[1], CstNode::Whitespace(" ".to_string()));
+            }
+            _ => panic!("expected a list"),
+        }
+        assert_eq!(cst[4], CstNode::Comment(";; starts at zero".to_string()));
+    }
+
+    #[test]
+    fn test_parse_cst_unbalanced() {
+        assert_eq!(
+            parse_cst("(a (b)").unwrap_err().err,
+            ParseErrors::ClosingParenthesisExpected
+        );
+        assert_eq!(
+            parse_cst("(a))").unwrap_err().err,
+            ParseErrors::ClosingParenthesisUnexpected
+        );
+        assert_eq!(
+            parse_cst("{a: 1)").unwrap_err().err,
+            ParseErrors::ClosingTupleLiteralExpected
+        );
+    }
+}
@@ -14,6 +14,8 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
+pub mod cst;
+
 use address::c32::c32_address_decode;
 use regex::{Captures, Regex};
 use std::cmp;
@@ -127,6 +129,16 @@ lazy_static! {
 }
 
 pub fn lex(input: &str) -> ParseResult<Vec<(LexItem, u32, u32)>> {
+    let lexed = lex_with_text(input)?;
+    Ok(lexed
+        .into_iter()
+        .map(|(item, _text, line, column)| (item, line, column))
+        .collect())
+}
+
+/// Like `lex`, but also returns the exact text of each token, including whitespace and
+/// comments, so that the input can be reconstructed from the tokens.
+pub fn lex_with_text(input: &str) -> ParseResult<Vec<(LexItem, &str, u32, u32)>> {
     // Aaron: I'd like these to be static, but that'd require using
     //    lazy_static (or just hand implementing that), and I'm not convinced
     //    it's worth either (1) an extern macro, or (2) the complexity of hand implementing.
@@ -410,7 +422,12 @@ pub fn lex(input: &str) -> ParseResult<Vec<(LexItem, u32, u32)>> {
                     }
                 }?;
 
-                result.push((token, current_line, column_pos));
+                result.push((
+                    token,
+                    &current_slice[..whole_match.end()],
+                    current_line,
+                    column_pos,
+                ));
                 column_pos += whole_match.end() as u32;
                 did_match = true;
                 break;
@@ -693,6 +710,275 @@ pub fn parse(input: &str) -> ParseResult<Vec<PreSymbolicExpression>> {
     parse_lexed(lexed)
 }
 
+/// A top-level form found by `split_top_level_forms`: its byte range in the input, and the
+/// line and column at which it starts and ends.
+struct TopLevelForm {
+    start: usize,
+    end: usize,
+    start_line: u32,
+    start_column: u32,
+    end_line: u32,
+    end_column: u32,
+}
+
+/// Split `input` into top-level forms by balancing parentheses and curly braces, skipping over
+/// string literals and `;;` comments.  This is deliberately more forgiving than the lexer, since
+/// it is only used to find somewhere to resume parsing after an error:
+/// * a stray closing paren or brace is a form by itself
+/// * an opening paren or brace in the first column of a line is assumed to start a new form,
+///   even if the previous form was never closed.
+fn split_top_level_forms(input: &str) -> Vec<TopLevelForm> {
+    let bytes = input.as_bytes();
+    let mut forms = vec![];
+
+    let mut depth: u64 = 0;
+    let mut start: Option<(usize, u32, u32)> = None;
+    let mut in_string = false;
+    let mut in_comment = false;
+    let mut escaped = false;
+
+    let mut line: u32 = 1;
+    let mut column: u32 = 1;
+    let mut last_line: u32 = 1;
+    let mut last_column: u32 = 1;
+
+    for (i, byte) in bytes.iter().enumerate() {
+        if in_comment {
+            in_comment = *byte != b'\n';
+        } else if in_string {
+            if escaped {
+                escaped = false;
+            } else if *byte == b'\\' {
+                escaped = true;
+            } else if *byte == b'"' || *byte == b'\n' {
+                // string literals cannot span lines, so give up on this one at the line break.
+                in_string = false;
+            }
+        } else {
+            match byte {
+                b';' if bytes.get(i + 1) == Some(&b';') => {
+                    if let Some((form_start, start_line, start_column)) = start {
+                        if depth == 0 {
+                            // end of a top-level atom
+                            forms.push(TopLevelForm {
+                                start: form_start,
+                                end: i,
+                                start_line,
+                                start_column,
+                                end_line: last_line,
+                                end_column: last_column,
+                            });
+                            start = None;
+                        }
+                    }
+                    in_comment = true;
+                }
+                b'(' | b'{' => {
+                    if let Some((form_start, start_line, start_column)) = start {
+                        if depth > 0 && column == 1 {
+                            // previous form was never closed -- start over here.
+                            forms.push(TopLevelForm {
+                                start: form_start,
+                                end: i,
+                                start_line,
+                                start_column,
+                                end_line: last_line,
+                                end_column: last_column,
+                            });
+                            start = None;
+                            depth = 0;
+                        }
+                    }
+                    if start.is_none() {
+                        start = Some((i, line, column));
+                    }
+                    depth += 1;
+                }
+                b')' | b'}' => match start {
+                    Some((form_start, start_line, start_column)) if depth > 0 => {
+                        depth -= 1;
+                        if depth == 0 {
+                            forms.push(TopLevelForm {
+                                start: form_start,
+                                end: i + 1,
+                                start_line,
+                                start_column,
+                                end_line: line,
+                                end_column: column,
+                            });
+                            start = None;
+                        }
+                    }
+                    Some((form_start, start_line, start_column)) => {
+                        // a top-level atom directly followed by a closing paren
+                        forms.push(TopLevelForm {
+                            start: form_start,
+                            end: i,
+                            start_line,
+                            start_column,
+                            end_line: last_line,
+                            end_column: last_column,
+                        });
+                        forms.push(TopLevelForm {
+                            start: i,
+                            end: i + 1,
+                            start_line: line,
+                            start_column: column,
+                            end_line: line,
+                            end_column: column,
+                        });
+                        start = None;
+                    }
+                    None => {
+                        forms.push(TopLevelForm {
+                            start: i,
+                            end: i + 1,
+                            start_line: line,
+                            start_column: column,
+                            end_line: line,
+                            end_column: column,
+                        });
+                    }
+                },
+                b' ' | b'\t' | b'\r' | b'\n' => {
+                    if let Some((form_start, start_line, start_column)) = start {
+                        if depth == 0 {
+                            // end of a top-level atom
+                            forms.push(TopLevelForm {
+                                start: form_start,
+                                end: i,
+                                start_line,
+                                start_column,
+                                end_line: last_line,
+                                end_column: last_column,
+                            });
+                            start = None;
+                        }
+                    }
+                }
+                b'"' => {
+                    in_string = true;
+                    if start.is_none() {
+                        start = Some((i, line, column));
+                    }
+                }
+                _ => {
+                    if start.is_none() {
+                        start = Some((i, line, column));
+                    }
+                }
+            }
+        }
+
+        last_line = line;
+        last_column = column;
+        if *byte == b'\n' {
+            line = line.saturating_add(1);
+            column = 1;
+        } else {
+            column = column.saturating_add(1);
+        }
+    }
+
+    if let Some((form_start, start_line, start_column)) = start {
+        forms.push(TopLevelForm {
+            start: form_start,
+            end: bytes.len(),
+            start_line,
+            start_column,
+            end_line: last_line,
+            end_column: last_column,
+        });
+    }
+
+    forms
+}
+
+/// Translate a position within a top-level form into a position within the whole input.
+fn offset_position(line: u32, column: u32, form: &TopLevelForm) -> (u32, u32) {
+    if line == 0 {
+        // unknown position
+        (line, column)
+    } else if line == 1 {
+        (form.start_line, column + form.start_column - 1)
+    } else {
+        (line + form.start_line - 1, column)
+    }
+}
+
+#[cfg(feature = "developer-mode")]
+fn offset_pre_expression_span(expr: &mut PreSymbolicExpression, form: &TopLevelForm) {
+    let (start_line, start_column) =
+        offset_position(expr.span.start_line, expr.span.start_column, form);
+    let (end_line, end_column) = offset_position(expr.span.end_line, expr.span.end_column, form);
+    expr.set_span(start_line, start_column, end_line, end_column);
+
+    match expr.pre_expr {
+        PreSymbolicExpressionType::List(ref mut exprs)
+        | PreSymbolicExpressionType::Tuple(ref mut exprs) => {
+            for expr in exprs.iter_mut() {
+                offset_pre_expression_span(expr, form);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(not(feature = "developer-mode"))]
+fn offset_pre_expression_span(_expr: &mut PreSymbolicExpression, _form: &TopLevelForm) {}
+
+/// Parse `input`, recovering from errors by skipping to the next top-level form.  Returns every
+/// expression that could be parsed, in order, along with an error for each form that could not.
+/// If the whole input parses, this is equivalent to `parse`.
+pub fn parse_collect_errors(input: &str) -> (Vec<PreSymbolicExpression>, Vec<ParseError>) {
+    let first_error = match parse(input) {
+        Ok(pre_expressions) => return (pre_expressions, vec![]),
+        Err(e) => e,
+    };
+
+    let mut pre_expressions = vec![];
+    let mut errors = vec![];
+    for form in split_top_level_forms(input).iter() {
+        match parse(&input[form.start..form.end]) {
+            Ok(mut parsed) => {
+                for expr in parsed.iter_mut() {
+                    offset_pre_expression_span(expr, form);
+                }
+                pre_expressions.append(&mut parsed);
+            }
+            Err(mut error) => {
+                for span in error.diagnostic.spans.iter_mut() {
+                    let (start_line, start_column) =
+                        offset_position(span.start_line, span.start_column, form);
+                    let (end_line, end_column) =
+                        offset_position(span.end_line, span.end_column, form);
+                    span.start_line = start_line;
+                    span.start_column = start_column;
+                    span.end_line = end_line;
+                    span.end_column = end_column;
+                }
+                if error.diagnostic.spans.is_empty() {
+                    error.diagnostic.add_span(
+                        form.start_line,
+                        form.start_column,
+                        form.end_line,
+                        form.end_column,
+                    );
+                }
+                errors.push(error);
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        // the error wasn't confined to any one form (e.g. the input is too large), so report
+        // the original error.
+        return (vec![], vec![first_error]);
+    }
+
+    (pre_expressions, errors)
+}
+
 #[cfg(test)]
 mod test {
     use vm::ast;
@@ -1229,4 +1515,44 @@ mod test {
             }
         );
     }
+
+    #[test]
+    fn test_parse_collect_errors() {
+        let input = "(define-constant a 1)
+(define-constant b (+ 1 2)
+(define-constant c 3)
+(define-constant d 0xzz)
+)
+(define-constant e 5)";
+
+        let (pre_expressions, errors) = ast::parser::parse_collect_errors(input);
+        assert_eq!(pre_expressions.len(), 3);
+        assert_eq!(errors.len(), 3);
+
+        // unclosed form on line 2
+        assert_eq!(errors[0].err, ParseErrors::ClosingParenthesisExpected);
+        assert_eq!(errors[0].diagnostic.spans[0].start_line, 2);
+        assert_eq!(errors[0].diagnostic.spans[0].start_column, 1);
+
+        // bad hex literal on line 4
+        assert_eq!(errors[1].diagnostic.spans[0].start_line, 4);
+
+        // stray closing paren on line 5
+        assert_eq!(errors[2].err, ParseErrors::ClosingParenthesisUnexpected);
+        assert_eq!(errors[2].diagnostic.spans[0].start_line, 5);
+
+        // spans of the recovered expressions are relative to the whole input
+        assert_eq!(pre_expressions[1].span.start_line, 3);
+        assert_eq!(pre_expressions[2].span.start_line, 6);
+        assert_eq!(
+            pre_expressions[2].match_list().unwrap()[2],
+            make_atom_value(Value::Int(5), 6, 20, 6, 20)
+        );
+
+        // well-formed input is parsed exactly as `parse` would
+        let input = "(define-constant a 1) (define-constant b 2)";
+        let (pre_expressions, errors) = ast::parser::parse_collect_errors(input);
+        assert!(errors.is_empty());
+        assert_eq!(pre_expressions, ast::parser::parse(input).unwrap());
+    }
 }
@@ -179,7 +179,7 @@ impl DefinedFunction {
         match result {
             Ok(r) => Ok(r),
             Err(e) => match e {
-                Error::ShortReturn(v) => Ok(v.into()),
+                Error::ShortReturn(v, _) => Ok(v.into()),
                 _ => Err(e),
             },
         }
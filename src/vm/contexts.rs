@@ -33,7 +33,7 @@ use vm::database::{
 };
 use vm::errors::{CheckErrors, InterpreterError, InterpreterResult as Result, RuntimeErrorType};
 use vm::functions::handle_contract_call_special_cases;
-use vm::representations::{ClarityName, ContractName, SymbolicExpression};
+use vm::representations::{ClarityName, ContractName, Span, SymbolicExpression};
 use vm::stx_transfer_consolidated;
 use vm::types::signatures::FunctionSignature;
 use vm::types::{
@@ -961,7 +961,7 @@ impl<'a, 'b> Environment<'a, 'b> {
 
             match res {
                 Ok(value) => {
-                    handle_contract_call_special_cases(&mut self.global_context, self.sender.as_ref(), contract_identifier, tx_name, &value)?;
+                    handle_contract_call_special_cases(&mut self.global_context, self.sender.as_ref(), contract_identifier, tx_name, &args, &value)?;
                     Ok(value)
                 },
                 Err(e) => Err(e)
@@ -1158,13 +1158,14 @@ impl<'a, 'b> Environment<'a, 'b> {
         }
     }
 
-    pub fn register_print_event(&mut self, value: Value) -> Result<()> {
+    pub fn register_print_event(&mut self, value: Value, span: Span) -> Result<()> {
         let print_event = SmartContractEventData {
             key: (
                 self.contract_context.contract_identifier.clone(),
                 "print".to_string(),
             ),
             value,
+            span,
         };
 
         if let Some(batch) = self.global_context.event_batches.last_mut() {
@@ -17,11 +17,12 @@
 use std::fmt;
 use vm::representations::Span;
 
-/// In a near future, we can go further in our static analysis and provide different levels
-/// of diagnostics, such as warnings, hints, best practices, etc.
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+/// Errors stop a contract from being deployed.  Warnings come from optional passes, such as
+/// the linter, and are only advisory.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub enum Level {
     Error,
+    Warning,
 }
 
 pub trait DiagnosableError {
@@ -29,7 +30,7 @@ pub trait DiagnosableError {
     fn suggestion(&self) -> Option<String>;
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct Diagnostic {
     pub level: Level,
     pub message: String,
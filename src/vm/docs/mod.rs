@@ -24,27 +24,27 @@ use vm::variables::NativeVariables;
 pub mod contracts;
 
 #[derive(Serialize)]
-struct ReferenceAPIs {
-    functions: Vec<FunctionAPI>,
-    keywords: Vec<KeywordAPI>,
+pub struct ReferenceAPIs {
+    pub functions: Vec<FunctionAPI>,
+    pub keywords: Vec<KeywordAPI>,
 }
 
 #[derive(Serialize, Clone)]
-struct KeywordAPI {
-    name: &'static str,
-    output_type: &'static str,
-    description: &'static str,
-    example: &'static str,
+pub struct KeywordAPI {
+    pub name: &'static str,
+    pub output_type: &'static str,
+    pub description: &'static str,
+    pub example: &'static str,
 }
 
 #[derive(Serialize)]
-struct FunctionAPI {
-    name: String,
-    input_type: String,
-    output_type: String,
-    signature: String,
-    description: String,
-    example: String,
+pub struct FunctionAPI {
+    pub name: String,
+    pub input_type: String,
+    pub output_type: String,
+    pub signature: String,
+    pub description: String,
+    pub example: String,
 }
 
 struct SimpleFunctionAPI {
@@ -1704,7 +1704,7 @@ fn make_define_reference(define_type: &DefineFunctions) -> FunctionAPI {
     }
 }
 
-fn make_all_api_reference() -> ReferenceAPIs {
+pub fn make_all_api_reference() -> ReferenceAPIs {
     let mut functions: Vec<_> = NativeFunctions::ALL
         .iter()
         .map(|x| make_api_reference(x))
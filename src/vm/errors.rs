@@ -26,7 +26,8 @@ pub use vm::analysis::errors::{check_argument_count, check_arguments_at_least};
 use vm::ast::errors::ParseError;
 use vm::contexts::StackTrace;
 use vm::costs::CostErrors;
-use vm::types::{TypeSignature, Value};
+use vm::representations::Span;
+use vm::types::{QualifiedContractIdentifier, TypeSignature, Value};
 
 #[derive(Debug)]
 pub struct IncomparableError<T> {
@@ -40,8 +41,16 @@ pub enum Error {
     ///   trigger these errors.
     Unchecked(CheckErrors),
     Interpreter(InterpreterError),
-    Runtime(RuntimeErrorType, Option<StackTrace>),
-    ShortReturn(ShortReturnType),
+    Runtime(RuntimeErrorType, Option<StackTrace>, Option<ErrorLocation>),
+    ShortReturn(ShortReturnType, Option<ErrorLocation>),
+}
+
+/// The contract and source span of the innermost expression that raised
+///   a runtime error or short return.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorLocation {
+    pub contract_identifier: QualifiedContractIdentifier,
+    pub span: Span,
 }
 
 /// InterpreterErrors are errors that *should never* occur.
@@ -115,9 +124,9 @@ impl<T> PartialEq<IncomparableError<T>> for IncomparableError<T> {
 impl PartialEq<Error> for Error {
     fn eq(&self, other: &Error) -> bool {
         match (self, other) {
-            (Error::Runtime(x, _), Error::Runtime(y, _)) => x == y,
+            (Error::Runtime(x, _, _), Error::Runtime(y, _, _)) => x == y,
             (Error::Unchecked(x), Error::Unchecked(y)) => x == y,
-            (Error::ShortReturn(x), Error::ShortReturn(y)) => x == y,
+            (Error::ShortReturn(x, _), Error::ShortReturn(y, _)) => x == y,
             (Error::Interpreter(x), Error::Interpreter(y)) => x == y,
             _ => false,
         }
@@ -127,7 +136,7 @@ impl PartialEq<Error> for Error {
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Error::Runtime(ref err, ref stack) => {
+            Error::Runtime(ref err, ref stack, _) => {
                 match err {
                     _ => write!(f, "{}", err),
                 }?;
@@ -151,6 +160,26 @@ impl fmt::Display for RuntimeErrorType {
     }
 }
 
+impl Error {
+    /// Where this error was raised, if it is a runtime error or short return
+    ///   that escaped contract code.
+    pub fn location(&self) -> Option<&ErrorLocation> {
+        match self {
+            Error::Runtime(_, _, location) | Error::ShortReturn(_, location) => location.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+impl ErrorLocation {
+    pub fn json_serialize(&self) -> serde_json::Value {
+        json!({
+            "contract_identifier": self.contract_identifier.to_string(),
+            "span": self.span,
+        })
+    }
+}
+
 impl error::Error for Error {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         None
@@ -183,7 +212,7 @@ impl From<SerdeJSONErr> for Error {
 
 impl From<RuntimeErrorType> for Error {
     fn from(err: RuntimeErrorType) -> Self {
-        Error::Runtime(err, None)
+        Error::Runtime(err, None, None)
     }
 }
 
@@ -195,7 +224,7 @@ impl From<CheckErrors> for Error {
 
 impl From<ShortReturnType> for Error {
     fn from(err: ShortReturnType) -> Self {
-        Error::ShortReturn(err)
+        Error::ShortReturn(err, None)
     }
 }
 
@@ -235,18 +264,44 @@ _native_:native_div
         assert_eq!(format!("{}", execute(t).unwrap_err()), expected);
     }
 
+    #[test]
+    fn error_locations() {
+        let t = "(define-private (f (x int)) (/ x 0))
+(f 10)";
+        let err = execute(t).unwrap_err();
+        let location = err.location().unwrap();
+        assert_eq!(
+            location.contract_identifier,
+            QualifiedContractIdentifier::transient()
+        );
+        assert_eq!(
+            location.span,
+            Span {
+                start_line: 1,
+                start_column: 29,
+                end_line: 1,
+                end_column: 35,
+            }
+        );
+
+        let err = execute("(asserts! false (err u1))").unwrap_err();
+        assert_eq!(err.location().unwrap().span.start_column, 1);
+
+        assert!(execute("(+ 1 u1)").unwrap_err().location().is_none());
+    }
+
     #[test]
     fn equality() {
         assert_eq!(
-            Error::ShortReturn(ShortReturnType::ExpectedValue(Value::Bool(true))),
-            Error::ShortReturn(ShortReturnType::ExpectedValue(Value::Bool(true)))
+            Error::ShortReturn(ShortReturnType::ExpectedValue(Value::Bool(true)), None),
+            Error::ShortReturn(ShortReturnType::ExpectedValue(Value::Bool(true)), None)
         );
         assert_eq!(
             Error::Interpreter(InterpreterError::InterpreterError("".to_string())),
             Error::Interpreter(InterpreterError::InterpreterError("".to_string()))
         );
         assert!(
-            Error::ShortReturn(ShortReturnType::ExpectedValue(Value::Bool(true)))
+            Error::ShortReturn(ShortReturnType::ExpectedValue(Value::Bool(true)), None)
                 != Error::Interpreter(InterpreterError::InterpreterError("".to_string()))
         );
     }
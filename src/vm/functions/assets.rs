@@ -304,7 +304,7 @@ pub fn special_mint_asset(
             &asset,
             expected_asset_type,
         ) {
-            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _)) => Ok(()),
+            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _, _)) => Ok(()),
             Ok(_owner) => return clarity_ecode!(MintAssetErrorCodes::ALREADY_EXIST),
             Err(e) => Err(e),
         }?;
@@ -374,7 +374,7 @@ pub fn special_transfer_asset(
             expected_asset_type,
         ) {
             Ok(owner) => Ok(owner),
-            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _)) => {
+            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _, _)) => {
                 return clarity_ecode!(TransferAssetErrorCodes::DOES_NOT_EXIST)
             }
             Err(e) => Err(e),
@@ -590,7 +590,7 @@ pub fn special_get_owner(
             Ok(Value::some(Value::Principal(owner))
                 .expect("Principal should always fit in optional."))
         }
-        Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _)) => Ok(Value::none()),
+        Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _, _)) => Ok(Value::none()),
         Err(e) => Err(e),
     }
 }
@@ -718,7 +718,7 @@ pub fn special_burn_asset(
             &asset,
             expected_asset_type,
         ) {
-            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _)) => {
+            Err(Error::Runtime(RuntimeErrorType::NoSuchToken, _, _)) => {
                 return clarity_ecode!(BurnAssetErrorCodes::DOES_NOT_EXIST)
             }
             Ok(owner) => Ok(owner),
@@ -273,6 +273,20 @@ impl DefineFunctions {
         let define_type = DefineFunctions::lookup_by_name(function_name)?;
         Some((define_type, args))
     }
+
+    /// Try to get the name bound by a Top-Level define expression, without checking
+    /// the rest of the expression. `impl-trait` binds no name.
+    pub fn try_parse_name(
+        expression: &SymbolicExpression,
+    ) -> Option<(DefineFunctions, &ClarityName)> {
+        let (define_type, args) = DefineFunctions::try_parse(expression)?;
+        let first = args.get(0)?;
+        let name = match first.match_list() {
+            Some(signature) => signature.get(0)?.match_atom()?,
+            None => first.match_atom()?,
+        };
+        Some((define_type, name))
+    }
 }
 
 impl<'a> DefineFunctionsParsed<'a> {
@@ -445,7 +445,7 @@ fn special_print(
         info!("{}", &input);
     }
 
-    env.register_print_event(input.clone())?;
+    env.register_print_event(input.clone(), args[0].span.clone())?;
     Ok(input)
 }
 
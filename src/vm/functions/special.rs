@@ -31,7 +31,10 @@ use vm::types::{
 use crate::types::chainstate::StacksMicroblockHeader;
 use crate::util::boot::boot_code_id;
 use chainstate::stacks::db::StacksChainState;
-use chainstate::stacks::events::{STXEventType, STXLockEventData, StacksTransactionEvent};
+use chainstate::stacks::events::{
+    STXDelegateEventData, STXDelegateStackEventData, STXEventType, STXLockEventData,
+    STXRevokeDelegateEventData, StacksTransactionEvent,
+};
 
 use util::hash::Hash160;
 
@@ -70,13 +73,80 @@ fn parse_pox_stacking_result(
     }
 }
 
+/// Push an event into the current event batch, if there is one
+fn push_pox_event(global_context: &mut GlobalContext, event: STXEventType) {
+    if let Some(batch) = global_context.event_batches.last_mut() {
+        batch.events.push(StacksTransactionEvent::STXEvent(event));
+    }
+}
+
+/// Report delegation changes made through the PoX API contract.  These don't touch any balances,
+/// so the contract's own checks are all that's needed -- we only report successful calls.
+fn handle_pox_delegation_call(
+    global_context: &mut GlobalContext,
+    sender_opt: Option<&PrincipalData>,
+    function_name: &str,
+    args: &[Value],
+    value: &Value,
+) {
+    let stacker = match (sender_opt, value) {
+        (Some(sender), Value::Response(ref response)) if response.committed => sender.clone(),
+        _ => {
+            // nothing to do -- the function failed
+            return;
+        }
+    };
+
+    if function_name == "delegate-stx" {
+        // (delegate-stx (amount-ustx uint) (delegate-to principal) (until-burn-ht (optional uint))
+        //               (pox-addr (optional (tuple (version (buff 1)) (hashbytes (buff 20))))))
+        if args.len() != 4 {
+            return;
+        }
+        let amount_ustx = args[0].clone().expect_u128();
+        let delegate_to = args[1].clone().expect_principal();
+        let until_burn_height = args[2]
+            .clone()
+            .expect_optional()
+            .map(|height| height.expect_u128());
+        let pox_addr = args[3].clone().expect_optional();
+
+        push_pox_event(
+            global_context,
+            STXEventType::STXDelegateEvent(STXDelegateEventData {
+                stacker,
+                delegate_to,
+                amount_ustx,
+                until_burn_height,
+                pox_addr,
+            }),
+        );
+    } else if function_name == "revoke-delegate-stx" {
+        // returns (ok true) only if there was a delegation to remove
+        if let Value::Response(ref response) = value {
+            if *response.data != Value::Bool(true) {
+                return;
+            }
+        }
+        push_pox_event(
+            global_context,
+            STXEventType::STXRevokeDelegateEvent(STXRevokeDelegateEventData { stacker }),
+        );
+    }
+}
+
 /// Handle special cases when calling into the PoX API contract
 fn handle_pox_api_contract_call(
     global_context: &mut GlobalContext,
-    _sender_opt: Option<&PrincipalData>,
+    sender_opt: Option<&PrincipalData>,
     function_name: &str,
+    args: &[Value],
     value: &Value,
 ) -> Result<()> {
+    if function_name == "delegate-stx" || function_name == "revoke-delegate-stx" {
+        handle_pox_delegation_call(global_context, sender_opt, function_name, args, value);
+        return Ok(());
+    }
     if function_name == "stack-stx" || function_name == "delegate-stack-stx" {
         debug!(
             "Handle special-case contract-call to {:?} {} (which returned {:?})",
@@ -103,15 +173,33 @@ fn handle_pox_api_contract_call(
                     unlock_height as u64,
                 ) {
                     Ok(_) => {
-                        if let Some(batch) = global_context.event_batches.last_mut() {
-                            batch.events.push(StacksTransactionEvent::STXEvent(
-                                STXEventType::STXLockEvent(STXLockEventData {
-                                    locked_amount,
-                                    unlock_height,
-                                    locked_address: stacker,
-                                }),
-                            ));
+                        if function_name == "delegate-stack-stx" {
+                            // (delegate-stack-stx (stacker principal) (amount-ustx uint)
+                            //                     (pox-addr (tuple ...)) (start-burn-ht uint)
+                            //                     (lock-period uint))
+                            if let (Some(delegate), Some(pox_addr)) = (sender_opt, args.get(2)) {
+                                push_pox_event(
+                                    global_context,
+                                    STXEventType::STXDelegateStackEvent(
+                                        STXDelegateStackEventData {
+                                            stacker: stacker.clone(),
+                                            delegate: delegate.clone(),
+                                            locked_amount,
+                                            unlock_height,
+                                            pox_addr: pox_addr.clone(),
+                                        },
+                                    ),
+                                );
+                            }
                         }
+                        push_pox_event(
+                            global_context,
+                            STXEventType::STXLockEvent(STXLockEventData {
+                                locked_amount,
+                                unlock_height,
+                                locked_address: stacker,
+                            }),
+                        );
                     }
                     Err(e) => {
                         panic!(
@@ -139,10 +227,11 @@ pub fn handle_contract_call_special_cases(
     sender: Option<&PrincipalData>,
     contract_id: &QualifiedContractIdentifier,
     function_name: &str,
+    args: &[Value],
     result: &Value,
 ) -> Result<()> {
     if *contract_id == boot_code_id("pox", global_context.mainnet) {
-        return handle_pox_api_contract_call(global_context, sender, function_name, result);
+        return handle_pox_api_contract_call(global_context, sender, function_name, args, result);
     }
     // TODO: insert more special cases here, as needed
     Ok(())
@@ -50,7 +50,8 @@ use vm::costs::{
     MemoryConsumer,
 };
 use vm::errors::{
-    CheckErrors, Error, InterpreterError, InterpreterResult as Result, RuntimeErrorType,
+    CheckErrors, Error, ErrorLocation, InterpreterError, InterpreterResult as Result,
+    RuntimeErrorType,
 };
 use vm::functions::define::DefineResult;
 pub use vm::types::Value;
@@ -63,6 +64,7 @@ pub use vm::representations::{
 use std::convert::{TryFrom, TryInto};
 pub use vm::contexts::MAX_CONTEXT_DEPTH;
 use vm::costs::cost_functions::ClarityCostFunction;
+pub use vm::functions::define::DefineFunctions;
 pub use vm::functions::stx_transfer_consolidated;
 
 const MAX_CALL_STACK_DEPTH: usize = 64;
@@ -116,13 +118,29 @@ pub fn lookup_function(name: &str, env: &mut Environment) -> Result<CallableType
 }
 
 fn add_stack_trace(result: &mut Result<Value>, env: &Environment) {
-    if let Err(Error::Runtime(_, ref mut stack_trace)) = result {
+    if let Err(Error::Runtime(_, ref mut stack_trace, _)) = result {
         if stack_trace.is_none() {
             stack_trace.replace(env.call_stack.make_stack_trace());
         }
     }
 }
 
+/// Errors are located at the innermost expression that raised them, so this
+///   only fills in a location that hasn't been set yet.
+fn add_error_location(result: &mut Result<Value>, exp: &SymbolicExpression, env: &Environment) {
+    let location = match result {
+        Err(Error::Runtime(_, _, ref mut location))
+        | Err(Error::ShortReturn(_, ref mut location)) => location,
+        _ => return,
+    };
+    if location.is_none() {
+        location.replace(ErrorLocation {
+            contract_identifier: env.contract_context.contract_identifier.clone(),
+            span: exp.span.clone(),
+        });
+    }
+}
+
 pub fn apply(
     function: &CallableType,
     args: &[SymbolicExpression],
@@ -217,7 +235,9 @@ pub fn eval<'a>(
                 .match_atom()
                 .ok_or(CheckErrors::BadFunctionName)?;
             let f = lookup_function(&function_name, env)?;
-            apply(&f, &rest, env, context)
+            let mut resp = apply(&f, &rest, env, context);
+            add_error_location(&mut resp, exp, env);
+            resp
         }
         TraitReference(_, _) | Field(_) => unreachable!("can't be evaluated"),
     }
@@ -884,7 +884,7 @@ fn test_total_supply(owned_env: &mut OwnedEnvironment) {
     .unwrap_err();
     println!("{}", err);
     assert!(match err {
-        Error::Runtime(RuntimeErrorType::SupplyOverflow(x, y), _) => (x, y) == (6, 5),
+        Error::Runtime(RuntimeErrorType::SupplyOverflow(x, y), _, _) => (x, y) == (6, 5),
         _ => false,
     });
 }
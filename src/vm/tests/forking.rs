@@ -171,7 +171,7 @@ fn test_at_block_good() {
             let resp = branch(x, 1, "reset").unwrap_err();
             eprintln!("{}", resp);
             match resp {
-                Error::Runtime(x, _) => assert_eq!(
+                Error::Runtime(x, _, _) => assert_eq!(
                     x,
                     RuntimeErrorType::UnknownBlockHeaderHash(BlockHeaderHash::from(
                         vec![2 as u8; 32].as_slice()
@@ -1003,12 +1003,14 @@ fn test_asserts_short_circuit() {
     ];
 
     let expectations: &[Error] = &[
-        Error::ShortReturn(ShortReturnType::AssertionFailed(
-            Value::error(Value::Int(0)).unwrap(),
-        )),
-        Error::ShortReturn(ShortReturnType::AssertionFailed(
-            Value::error(Value::Int(1)).unwrap(),
-        )),
+        Error::ShortReturn(
+            ShortReturnType::AssertionFailed(Value::error(Value::Int(0)).unwrap()),
+            None,
+        ),
+        Error::ShortReturn(
+            ShortReturnType::AssertionFailed(Value::error(Value::Int(1)).unwrap()),
+            None,
+        ),
     ];
 
     tests
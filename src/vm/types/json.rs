@@ -0,0 +1,411 @@
+// Copyright (C) 2013-2020 Blockstack PBC, a public benefit corporation
+// Copyright (C) 2020 Stacks Open Internet Foundation
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Canonical JSON encoding of Clarity values.
+//!
+//! The encoding is stable and round-trips through `Value::from_json` given the value's
+//! type signature:
+//!
+//! * `int` and `uint` are decimal strings (`"-12"`, `"340282366920938463463374607431768211455"`),
+//!   so that 128-bit values survive JSON parsers that use doubles.
+//! * `bool` is a JSON boolean.
+//! * `principal` is its string form (`"SP000...0002Q6VF78"`, `"SP000...0002Q6VF78.pox"`).
+//! * `(buff n)` is a `0x`-prefixed lowercase hex string.
+//! * `(string-ascii n)` and `(string-utf8 n)` are JSON strings.
+//! * `(list n t)` is a JSON array.
+//! * tuples are JSON objects keyed by field name.
+//! * `(optional t)` is `null` for `none` and `{"some": v}` for `(some v)`.
+//! * `(response ok err)` is `{"ok": v}` or `{"err": v}`.
+//!
+//! Since the encoding of a value does not depend on its declared type, encoding is infallible.
+//! Decoding needs the expected type: it disambiguates `int` from `uint` and `(string-ascii n)`
+//! from `(string-utf8 n)`, and bounds the lengths of sequences.  As a convenience, decoding
+//! also accepts JSON numbers for `int` and `uint` when they are exact integers, and principals
+//! with a leading `'`.
+
+use serde_json::{Map, Value as JSONValue};
+
+use util::hash::{hex_bytes, to_hex};
+use vm::types::serialization::SerializationError;
+use vm::types::{
+    CharType, PrincipalData, SequenceData, SequenceSubtype, StringSubtype, TupleData,
+    TypeSignature, Value,
+};
+
+impl Value {
+    /// Encode this value in the canonical JSON encoding.
+    pub fn to_json(&self) -> JSONValue {
+        match self {
+            Value::Int(value) => JSONValue::String(value.to_string()),
+            Value::UInt(value) => JSONValue::String(value.to_string()),
+            Value::Bool(value) => JSONValue::Bool(*value),
+            Value::Principal(principal) => JSONValue::String(principal.to_string()),
+            Value::Sequence(SequenceData::Buffer(buff)) => {
+                JSONValue::String(format!("0x{}", to_hex(&buff.data)))
+            }
+            Value::Sequence(SequenceData::String(CharType::ASCII(ascii))) => {
+                JSONValue::String(String::from_utf8_lossy(&ascii.data).into_owned())
+            }
+            Value::Sequence(SequenceData::String(CharType::UTF8(utf8))) => {
+                let bytes: Vec<u8> = utf8.data.iter().flatten().cloned().collect();
+                JSONValue::String(String::from_utf8_lossy(&bytes).into_owned())
+            }
+            Value::Sequence(SequenceData::List(list)) => {
+                JSONValue::Array(list.data.iter().map(|item| item.to_json()).collect())
+            }
+            Value::Tuple(tuple) => {
+                let mut fields = Map::new();
+                for (name, value) in tuple.data_map.iter() {
+                    fields.insert(name.to_string(), value.to_json());
+                }
+                JSONValue::Object(fields)
+            }
+            Value::Optional(optional) => match optional.data {
+                Some(ref value) => json!({ "some": value.to_json() }),
+                None => JSONValue::Null,
+            },
+            Value::Response(response) => {
+                if response.committed {
+                    json!({ "ok": response.data.to_json() })
+                } else {
+                    json!({ "err": response.data.to_json() })
+                }
+            }
+        }
+    }
+
+    /// Decode a value of type `expected` from its canonical JSON encoding.
+    pub fn from_json(
+        json: &JSONValue,
+        expected: &TypeSignature,
+    ) -> Result<Value, SerializationError> {
+        let value = Value::inner_from_json(json, expected)?;
+        if !expected.admits(&value) {
+            return Err(SerializationError::DeserializeExpected(expected.clone()));
+        }
+        Ok(value)
+    }
+
+    fn inner_from_json(
+        json: &JSONValue,
+        expected: &TypeSignature,
+    ) -> Result<Value, SerializationError> {
+        let mismatch = || SerializationError::DeserializeExpected(expected.clone());
+        match expected {
+            TypeSignature::IntType => {
+                let value = match json {
+                    JSONValue::String(s) => s.parse::<i128>().ok(),
+                    JSONValue::Number(n) => n.as_i64().map(i128::from),
+                    _ => None,
+                };
+                value.map(Value::Int).ok_or_else(mismatch)
+            }
+            TypeSignature::UIntType => {
+                let value = match json {
+                    JSONValue::String(s) => s.parse::<u128>().ok(),
+                    JSONValue::Number(n) => n.as_u64().map(u128::from),
+                    _ => None,
+                };
+                value.map(Value::UInt).ok_or_else(mismatch)
+            }
+            TypeSignature::BoolType => json.as_bool().map(Value::Bool).ok_or_else(mismatch),
+            TypeSignature::PrincipalType | TypeSignature::TraitReferenceType(_) => {
+                let literal = json.as_str().ok_or_else(mismatch)?;
+                let literal = literal.trim_start_matches('\'');
+                let principal = PrincipalData::parse(literal).map_err(|_| {
+                    SerializationError::DeserializationError(format!(
+                        "Invalid principal: {}",
+                        literal
+                    ))
+                })?;
+                Ok(Value::Principal(principal))
+            }
+            TypeSignature::SequenceType(SequenceSubtype::BufferType(_)) => {
+                let literal = json.as_str().ok_or_else(mismatch)?;
+                if !literal.starts_with("0x") {
+                    return Err(SerializationError::DeserializationError(
+                        "Buffers must be encoded as 0x-prefixed hex strings".into(),
+                    ));
+                }
+                let bytes = hex_bytes(&literal[2..]).map_err(|_| "Bad hex string")?;
+                Value::buff_from(bytes).map_err(|_| mismatch())
+            }
+            TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::ASCII(_))) => {
+                let literal = json.as_str().ok_or_else(mismatch)?;
+                Value::string_ascii_from_bytes(literal.as_bytes().to_vec()).map_err(|_| mismatch())
+            }
+            TypeSignature::SequenceType(SequenceSubtype::StringType(StringSubtype::UTF8(_))) => {
+                let literal = json.as_str().ok_or_else(mismatch)?;
+                Value::string_utf8_from_bytes(literal.as_bytes().to_vec()).map_err(|_| mismatch())
+            }
+            TypeSignature::SequenceType(SequenceSubtype::ListType(list_type)) => {
+                let items = json.as_array().ok_or_else(mismatch)?;
+                if items.len() > list_type.get_max_len() as usize {
+                    return Err(mismatch());
+                }
+                let item_type = list_type.get_list_item_type();
+                let items = items
+                    .iter()
+                    .map(|item| Value::inner_from_json(item, item_type))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Value::list_with_type(items, list_type.clone()).map_err(|_| mismatch())
+            }
+            TypeSignature::TupleType(tuple_type) => {
+                let fields = json.as_object().ok_or_else(mismatch)?;
+                let field_types = tuple_type.get_type_map();
+                if fields.len() != field_types.len() {
+                    return Err(mismatch());
+                }
+                let mut data = Vec::with_capacity(fields.len());
+                for (name, field_type) in field_types.iter() {
+                    let field = fields.get(name.as_str()).ok_or_else(mismatch)?;
+                    data.push((name.clone(), Value::inner_from_json(field, field_type)?));
+                }
+                TupleData::from_data_typed(data, tuple_type)
+                    .map(Value::from)
+                    .map_err(|_| mismatch())
+            }
+            TypeSignature::OptionalType(some_type) => match json {
+                JSONValue::Null => Ok(Value::none()),
+                _ => {
+                    let inner = Value::single_field(json, "some").ok_or_else(mismatch)?;
+                    Value::some(Value::inner_from_json(inner, some_type)?).map_err(|_| mismatch())
+                }
+            },
+            TypeSignature::ResponseType(response_type) => {
+                let (ok_type, err_type) = response_type.as_ref();
+                if let Some(inner) = Value::single_field(json, "ok") {
+                    Value::okay(Value::inner_from_json(inner, ok_type)?).map_err(|_| mismatch())
+                } else if let Some(inner) = Value::single_field(json, "err") {
+                    Value::error(Value::inner_from_json(inner, err_type)?).map_err(|_| mismatch())
+                } else {
+                    Err(mismatch())
+                }
+            }
+            TypeSignature::NoType => Err(mismatch()),
+        }
+    }
+
+    /// If `json` is an object with exactly one field named `name`, return that field.
+    fn single_field<'a>(json: &'a JSONValue, name: &str) -> Option<&'a JSONValue> {
+        match json.as_object() {
+            Some(fields) if fields.len() == 1 => fields.get(name),
+            _ => None,
+        }
+    }
+}
+
+impl TypeSignature {
+    /// Decode a list of function arguments from their canonical JSON encodings, checking each
+    /// one against the corresponding declared argument type.
+    pub fn args_from_json(
+        arg_types: &[TypeSignature],
+        args: &[JSONValue],
+    ) -> Result<Vec<Value>, SerializationError> {
+        if arg_types.len() != args.len() {
+            return Err(SerializationError::DeserializationError(format!(
+                "Expected {} arguments, got {}",
+                arg_types.len(),
+                args.len()
+            )));
+        }
+        arg_types
+            .iter()
+            .zip(args.iter())
+            .map(|(arg_type, arg)| Value::from_json(arg, arg_type))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use vm::types::QualifiedContractIdentifier;
+
+    fn round_trip(value: Value, type_str: &str, expected_json: JSONValue) {
+        let json = value.to_json();
+        assert_eq!(json, expected_json);
+        assert_eq!(
+            Value::from_json(&json, &TypeSignature::from(type_str)).unwrap(),
+            value
+        );
+        // the encoding survives a trip through its textual form
+        let reparsed: JSONValue = serde_json::from_str(&json.to_string()).unwrap();
+        assert_eq!(
+            Value::from_json(&reparsed, &TypeSignature::from(type_str)).unwrap(),
+            value
+        );
+    }
+
+    fn decode(json: JSONValue, type_str: &str) -> Result<Value, SerializationError> {
+        Value::from_json(&json, &TypeSignature::from(type_str))
+    }
+
+    #[test]
+    fn test_round_trips() {
+        round_trip(Value::Int(-12), "int", json!("-12"));
+        round_trip(
+            Value::Int(i128::min_value()),
+            "int",
+            json!(i128::min_value().to_string()),
+        );
+        round_trip(
+            Value::UInt(u128::max_value()),
+            "uint",
+            json!(u128::max_value().to_string()),
+        );
+        round_trip(Value::Bool(true), "bool", json!(true));
+        round_trip(
+            Value::Principal(
+                PrincipalData::parse("S1G2081040G2081040G2081040G208105NK8PE5").unwrap(),
+            ),
+            "principal",
+            json!("S1G2081040G2081040G2081040G208105NK8PE5"),
+        );
+        round_trip(
+            Value::Principal(PrincipalData::Contract(
+                QualifiedContractIdentifier::parse(
+                    "S1G2081040G2081040G2081040G208105NK8PE5.tokens",
+                )
+                .unwrap(),
+            )),
+            "principal",
+            json!("S1G2081040G2081040G2081040G208105NK8PE5.tokens"),
+        );
+        round_trip(
+            Value::buff_from(vec![0xde, 0xad, 0xbe, 0xef]).unwrap(),
+            "(buff 4)",
+            json!("0xdeadbeef"),
+        );
+        round_trip(
+            Value::string_ascii_from_bytes(b"hello \"world\"".to_vec()).unwrap(),
+            "(string-ascii 20)",
+            json!("hello \"world\""),
+        );
+        round_trip(
+            Value::string_utf8_from_bytes("hélló 🌍".as_bytes().to_vec()).unwrap(),
+            "(string-utf8 7)",
+            json!("hélló 🌍"),
+        );
+        round_trip(Value::none(), "(optional int)", json!(null));
+        round_trip(
+            Value::some(Value::none()).unwrap(),
+            "(optional (optional int))",
+            json!({ "some": null }),
+        );
+        round_trip(
+            Value::okay(Value::UInt(1)).unwrap(),
+            "(response uint int)",
+            json!({ "ok": "1" }),
+        );
+        round_trip(
+            Value::error(Value::Int(-1)).unwrap(),
+            "(response uint int)",
+            json!({ "err": "-1" }),
+        );
+        let list_type = TypeSignature::from("(list 3 (tuple (a int) (b (buff 1))))");
+        let list_type = match list_type {
+            TypeSignature::SequenceType(SequenceSubtype::ListType(list_type)) => list_type,
+            _ => unreachable!(),
+        };
+        let tuple = Value::from(
+            TupleData::from_data(vec![
+                ("a".into(), Value::Int(1)),
+                ("b".into(), Value::buff_from_byte(2)),
+            ])
+            .unwrap(),
+        );
+        round_trip(
+            Value::list_with_type(vec![tuple.clone(), tuple], list_type).unwrap(),
+            "(list 3 (tuple (a int) (b (buff 1))))",
+            json!([{ "a": "1", "b": "0x02" }, { "a": "1", "b": "0x02" }]),
+        );
+    }
+
+    #[test]
+    fn test_lenient_inputs() {
+        assert_eq!(decode(json!(-5), "int").unwrap(), Value::Int(-5));
+        assert_eq!(decode(json!(5), "uint").unwrap(), Value::UInt(5));
+        assert_eq!(
+            decode(
+                json!("'S1G2081040G2081040G2081040G208105NK8PE5"),
+                "principal"
+            )
+            .unwrap(),
+            Value::Principal(
+                PrincipalData::parse("S1G2081040G2081040G2081040G208105NK8PE5").unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_rejects_ill_typed() {
+        let expected = |type_str: &str| {
+            Err(SerializationError::DeserializeExpected(
+                TypeSignature::from(type_str),
+            ))
+        };
+        assert_eq!(decode(json!("-1"), "uint"), expected("uint"));
+        assert_eq!(decode(json!(1.5), "int"), expected("int"));
+        assert_eq!(decode(json!("1"), "bool"), expected("bool"));
+        assert_eq!(decode(json!("0x0102"), "(buff 1)"), expected("(buff 1)"));
+        assert_eq!(
+            decode(json!("hello"), "(string-ascii 4)"),
+            expected("(string-ascii 4)")
+        );
+        assert_eq!(
+            decode(json!("ééé"), "(string-utf8 2)"),
+            expected("(string-utf8 2)")
+        );
+        assert_eq!(
+            decode(json!(["1", "2"]), "(list 1 int)"),
+            expected("(list 1 int)")
+        );
+        assert_eq!(
+            decode(json!({ "a": "1", "c": "2" }), "(tuple (a int) (b int))"),
+            expected("(tuple (a int) (b int))")
+        );
+        assert_eq!(
+            decode(json!({ "ok": "1", "err": "1" }), "(response int int)"),
+            expected("(response int int)")
+        );
+        assert_eq!(
+            decode(json!("1"), "(optional int)"),
+            expected("(optional int)")
+        );
+        // errors deep inside a value report the innermost type
+        assert_eq!(
+            decode(json!({ "some": ["1", true] }), "(optional (list 2 int))"),
+            expected("int")
+        );
+        assert!(decode(json!("deadbeef"), "(buff 4)").is_err());
+        assert!(decode(json!("SPnotaprincipal"), "principal").is_err());
+    }
+
+    #[test]
+    fn test_args_from_json() {
+        let arg_types = vec![
+            TypeSignature::UIntType,
+            TypeSignature::from("(optional bool)"),
+        ];
+        assert_eq!(
+            TypeSignature::args_from_json(&arg_types, &[json!("1"), json!({ "some": false })])
+                .unwrap(),
+            vec![Value::UInt(1), Value::some(Value::Bool(false)).unwrap()]
+        );
+        assert!(TypeSignature::args_from_json(&arg_types, &[json!("1")]).is_err());
+    }
+}
@@ -46,7 +46,7 @@ use stacks::deps::bitcoin::blockdata::opcodes;
 use stacks::deps::bitcoin::blockdata::script::{Builder, Script};
 use stacks::deps::bitcoin::blockdata::transaction::{OutPoint, Transaction, TxIn, TxOut};
 use stacks::deps::bitcoin::network::encodable::ConsensusEncodable;
-use stacks::deps::bitcoin::network::serialize::RawEncoder;
+use stacks::deps::bitcoin::network::serialize::{deserialize, RawEncoder};
 use stacks::deps::bitcoin::util::hash::Sha256dHash;
 use stacks::util::hash::{hex_bytes, Hash160};
 use stacks::util::secp256k1::Secp256k1PublicKey;
@@ -62,7 +62,7 @@ use stacks::types::chainstate::BurnchainHeaderHash;
 ///  passed since the UTXO cache was last refreshed before
 ///  the cache is force-reset.
 const UTXO_CACHE_STALENESS_LIMIT: u64 = 6;
-const DUST_UTXO_LIMIT: u64 = 5500;
+pub const DUST_UTXO_LIMIT: u64 = 5500;
 
 pub struct BitcoinRegtestController {
     config: Config,
@@ -1211,13 +1211,16 @@ impl BitcoinRegtestController {
         } else {
             spent_in_rbf + tx_size // we're spending 1 sat / byte in RBF
         };
-        self.serialize_tx(
+        let signed = self.serialize_tx(
             tx,
             spent_in_outputs + tx_size * fee_rate + rbf_fee,
             utxos_set,
             signer,
         );
         signer.dispose();
+        if !signed {
+            return None;
+        }
         Some(())
     }
 
@@ -1266,7 +1269,9 @@ impl BitcoinRegtestController {
             debug!("Not enough change to clear dust limit. Not adding change address.");
         }
 
-        for (i, utxo) in utxos_set.utxos.iter().enumerate() {
+        // add every input before signing any of them, since each signature commits to all of
+        // the transaction's inputs
+        for utxo in utxos_set.utxos.iter() {
             let input = TxIn {
                 previous_output: OutPoint {
                     txid: utxo.txid,
@@ -1277,15 +1282,50 @@ impl BitcoinRegtestController {
                 witness: vec![],
             };
             tx.input.push(input);
+        }
+
+        // a remote signer checks the spent outputs against the transactions that created them
+        let mut funding_txs: Vec<Transaction> = vec![];
+        if signer.is_remote() {
+            for utxo in utxos_set.utxos.iter() {
+                if funding_txs
+                    .iter()
+                    .any(|funding_tx| funding_tx.txid() == utxo.txid)
+                {
+                    continue;
+                }
+                match BitcoinRPCRequest::get_transaction(&self.config, &utxo.txid) {
+                    Ok(funding_tx) => funding_txs.push(funding_tx),
+                    Err(e) => {
+                        warn!(
+                            "Unable to fetch funding transaction {}: {:?}",
+                            utxo.txid.be_hex_string(),
+                            e
+                        );
+                        return false;
+                    }
+                }
+            }
+        }
 
+        for (i, utxo) in utxos_set.utxos.iter().enumerate() {
             let script_pub_key = utxo.script_pub_key.clone();
             let sig_hash_all = 0x01;
-            let sig_hash = tx.signature_hash(i, &script_pub_key, sig_hash_all);
 
             let sig1_der = {
-                let message = signer
-                    .sign_message(sig_hash.as_bytes())
-                    .expect("Unable to sign message");
+                let message = match signer.sign_tx_input(
+                    tx,
+                    i,
+                    &script_pub_key,
+                    sig_hash_all,
+                    &funding_txs,
+                ) {
+                    Some(message) => message,
+                    None => {
+                        warn!("Unable to sign input {} of burnchain transaction", i);
+                        return false;
+                    }
+                };
                 message
                     .to_secp256k1_recoverable()
                     .expect("Unable to get recoverable signature")
@@ -1889,6 +1929,30 @@ impl BitcoinRPCRequest {
         Ok(())
     }
 
+    /// Calls `gettransaction` to fetch a wallet transaction, including watch-only ones
+    pub fn get_transaction(config: &Config, txid: &Sha256dHash) -> RPCResult<Transaction> {
+        let payload = BitcoinRPCRequest {
+            method: "gettransaction".to_string(),
+            params: vec![txid.be_hex_string().into(), true.into()],
+            id: "stacks".to_string(),
+            jsonrpc: "2.0".to_string(),
+        };
+
+        let json_resp = BitcoinRPCRequest::send(&config, payload)?;
+        let tx_hex = json_resp
+            .get("result")
+            .and_then(|result| result.get("hex"))
+            .and_then(|hex| hex.as_str())
+            .ok_or(RPCError::Parsing(format!(
+                "Failed to get transaction: {}",
+                json_resp
+            )))?;
+        let tx_bytes = hex_bytes(tx_hex)
+            .map_err(|_| RPCError::Parsing("Failed to decode transaction".to_string()))?;
+        deserialize(&tx_bytes)
+            .map_err(|_| RPCError::Parsing("Failed to deserialize transaction".to_string()))
+    }
+
     pub fn import_public_key(config: &Config, public_key: &Secp256k1PublicKey) -> RPCResult<()> {
         let rescan = true;
         let label = "";
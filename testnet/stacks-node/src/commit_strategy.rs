@@ -0,0 +1,527 @@
+use std::collections::BTreeMap;
+
+use stacks::burnchains::{Burnchain, BurnchainSigner};
+use stacks::chainstate::burn::db::sortdb::SortitionDB;
+use stacks::chainstate::burn::operations::leader_block_commit::OUTPUTS_PER_COMMIT;
+use stacks::chainstate::burn::BlockSnapshot;
+use stacks::chainstate::stacks::db::StacksChainState;
+use stacks::util::db::Error as DBError;
+use stacks::util::get_epoch_time_secs;
+
+use super::burnchains::bitcoin_regtest_controller::DUST_UTXO_LIMIT;
+use super::Config;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Smallest commit worth sending over `pox_outputs` outputs.  A reward-phase commit is split
+/// evenly over its PoX outputs, and bitcoind rejects the transaction if any of them falls
+/// below the dust limit.  Prepare-phase and post-sunset commits pay a single burn output, and
+/// have no floor.
+pub fn min_commit(pox_outputs: usize) -> u64 {
+    if pox_outputs == OUTPUTS_PER_COMMIT {
+        DUST_UTXO_LIMIT * (OUTPUTS_PER_COMMIT as u64)
+    } else {
+        0
+    }
+}
+
+/// What the rest of the network has recently been committing, as observed in the burn
+/// distributions of the last few sortitions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompetitionEstimate {
+    /// Median, over the sampled sortitions, of the total burn from everyone but us
+    pub competing_burn: u64,
+    /// Number of sortitions with a stored burn distribution that went into the estimate
+    pub sample_size: usize,
+}
+
+impl CompetitionEstimate {
+    /// Estimate competing burns from the burn distributions of up to `lookback` sortitions
+    /// ending at `burn_tip`.  Sample points whose commit came from `my_signer` are left out.
+    pub fn from_sortdb(
+        sortdb: &SortitionDB,
+        burn_tip: &BlockSnapshot,
+        lookback: u64,
+        my_signer: &BurnchainSigner,
+    ) -> Result<CompetitionEstimate, DBError> {
+        let mut competing_burns = vec![];
+        let mut cursor = burn_tip.clone();
+        for _ in 0..lookback {
+            if cursor.block_height <= sortdb.first_block_height {
+                break;
+            }
+            if let Some(distribution) = sortdb.get_burn_distribution(&cursor.sortition_id)? {
+                let burns: u128 = distribution
+                    .iter()
+                    .filter(|point| point.candidate.apparent_sender != *my_signer)
+                    .map(|point| point.burns)
+                    .sum();
+                competing_burns.push(burns.min(u64::max_value() as u128) as u64);
+            }
+            cursor = match SortitionDB::get_block_snapshot(
+                sortdb.conn(),
+                &cursor.parent_sortition_id,
+            )? {
+                Some(sn) => sn,
+                None => break,
+            };
+        }
+        Ok(CompetitionEstimate::from_samples(competing_burns))
+    }
+
+    pub fn from_samples(mut competing_burns: Vec<u64>) -> CompetitionEstimate {
+        competing_burns.sort();
+        let sample_size = competing_burns.len();
+        let competing_burn = if sample_size == 0 {
+            0
+        } else if sample_size % 2 == 0 {
+            let hi = competing_burns[sample_size / 2];
+            let lo = competing_burns[sample_size / 2 - 1];
+            lo / 2 + hi / 2 + (lo % 2 + hi % 2) / 2
+        } else {
+            competing_burns[sample_size / 2]
+        };
+        CompetitionEstimate {
+            competing_burn,
+            sample_size,
+        }
+    }
+
+    /// Probability that a commit of `commit` sats wins the next sortition, if the rest of the
+    /// network keeps committing what it has been.  Sortition picks a winner with probability
+    /// proportional to its (min-median windowed) burn, so this is only accurate once our own
+    /// commits have been steady over the mining commitment window.
+    pub fn win_probability(&self, commit: u64) -> f64 {
+        let total = (commit as f64) + (self.competing_burn as f64);
+        if total == 0.0 {
+            return 0.0;
+        }
+        (commit as f64) / total
+    }
+
+    /// Smallest commit that reaches a win probability of `target`.  Returns None if `target` is
+    /// not in (0, 1).
+    pub fn commit_for_win_probability(&self, target: f64) -> Option<u64> {
+        if !(target > 0.0 && target < 1.0) {
+            return None;
+        }
+        let commit = (target * (self.competing_burn as f64) / (1.0 - target)).ceil();
+        if commit >= u64::max_value() as f64 {
+            return Some(u64::max_value());
+        }
+        Some((commit as u64).max(1))
+    }
+}
+
+/// Decides how much to commit in each block-commit.
+pub trait CommitStrategy: Send {
+    fn name(&self) -> &'static str;
+
+    /// Sats to commit (burns plus PoX payouts), given recent competition.
+    fn commit_amount(&mut self, estimate: &CompetitionEstimate) -> u64;
+
+    /// Most the strategy will ever commit
+    fn max_commit(&self) -> u64;
+}
+
+/// Always commit the same amount.  This is the `fixed` strategy, and the default.
+pub struct FixedCommitStrategy {
+    pub commit: u64,
+}
+
+impl CommitStrategy for FixedCommitStrategy {
+    fn name(&self) -> &'static str {
+        "fixed"
+    }
+
+    fn commit_amount(&mut self, _estimate: &CompetitionEstimate) -> u64 {
+        self.commit
+    }
+
+    fn max_commit(&self) -> u64 {
+        self.commit
+    }
+}
+
+/// Commit just enough to win with a target probability, but never more than `max_commit`.
+/// Without any observed competition, commits `max_commit`.  This is the `win_rate` strategy.
+pub struct WinRateCommitStrategy {
+    pub target_win_probability: f64,
+    pub max_commit: u64,
+}
+
+impl CommitStrategy for WinRateCommitStrategy {
+    fn name(&self) -> &'static str {
+        "win_rate"
+    }
+
+    fn commit_amount(&mut self, estimate: &CompetitionEstimate) -> u64 {
+        if estimate.sample_size == 0 {
+            return self.max_commit;
+        }
+        match estimate.commit_for_win_probability(self.target_win_probability) {
+            Some(commit) if commit <= self.max_commit => commit,
+            _ => {
+                warn!(
+                    "Target win probability is out of reach under the commit cap";
+                    "target_win_probability" => self.target_win_probability,
+                    "max_commit" => self.max_commit,
+                    "competing_burn" => estimate.competing_burn
+                );
+                self.max_commit
+            }
+        }
+    }
+
+    fn max_commit(&self) -> u64 {
+        self.max_commit
+    }
+}
+
+/// Caps on BTC spent on block-commits, over the trailing day and over the current reward
+/// cycle.  Spends are tracked in memory, so the caps restart with the node.
+#[derive(Debug, Clone, Default)]
+pub struct SpendBudget {
+    pub daily_cap: Option<u64>,
+    pub cycle_cap: Option<u64>,
+    /// burn block height targeted by a commit => (time sent, reward cycle, sats)
+    spends: BTreeMap<u64, (u64, u64, u64)>,
+}
+
+impl SpendBudget {
+    pub fn new(daily_cap: Option<u64>, cycle_cap: Option<u64>) -> SpendBudget {
+        SpendBudget {
+            daily_cap,
+            cycle_cap,
+            spends: BTreeMap::new(),
+        }
+    }
+
+    fn spent_since(&self, since: u64) -> u64 {
+        self.spends
+            .values()
+            .filter(|(sent, _, _)| *sent >= since)
+            .fold(0u64, |total, (_, _, sats)| total.saturating_add(*sats))
+    }
+
+    fn spent_in_cycle(&self, reward_cycle: u64) -> u64 {
+        self.spends
+            .values()
+            .filter(|(_, cycle, _)| *cycle == reward_cycle)
+            .fold(0u64, |total, (_, _, sats)| total.saturating_add(*sats))
+    }
+
+    /// Sats that may still be spent at time `now` in `reward_cycle`.  Spends already recorded
+    /// for `burn_height` are not counted, since a new commit for it replaces the old one.
+    pub fn remaining(&self, now: u64, reward_cycle: u64, burn_height: u64) -> u64 {
+        let replaced = self.spends.get(&burn_height);
+        let mut remaining = u64::max_value();
+        if let Some(daily_cap) = self.daily_cap {
+            let mut spent = self.spent_since(now.saturating_sub(SECONDS_PER_DAY));
+            if let Some((sent, _, sats)) = replaced {
+                if *sent >= now.saturating_sub(SECONDS_PER_DAY) {
+                    spent = spent.saturating_sub(*sats);
+                }
+            }
+            remaining = remaining.min(daily_cap.saturating_sub(spent));
+        }
+        if let Some(cycle_cap) = self.cycle_cap {
+            let mut spent = self.spent_in_cycle(reward_cycle);
+            if let Some((_, cycle, sats)) = replaced {
+                if *cycle == reward_cycle {
+                    spent = spent.saturating_sub(*sats);
+                }
+            }
+            remaining = remaining.min(cycle_cap.saturating_sub(spent));
+        }
+        remaining
+    }
+
+    /// Record a commit for `burn_height`.  A later commit for the same height (i.e. an RBF)
+    /// replaces it.
+    pub fn record(&mut self, now: u64, reward_cycle: u64, burn_height: u64, sats: u64) {
+        self.spends.insert(burn_height, (now, reward_cycle, sats));
+        let day_ago = now.saturating_sub(SECONDS_PER_DAY);
+        self.spends
+            .retain(|_, (sent, cycle, _)| *sent >= day_ago || *cycle == reward_cycle);
+    }
+}
+
+/// Why a block-commit was not planned
+#[derive(Debug, Clone, PartialEq)]
+enum SkippedCommit {
+    /// the strategy's commit cap is below the dust floor of the commit's PoX outputs
+    CapBelowFloor,
+    /// the spend budget can't cover the smallest commit and its transaction fee
+    BudgetExhausted,
+}
+
+/// Raise `commit` to at least `min_commit`, then trim it so that it and its transaction fee
+/// fit in the `remaining` spend budget.  The commit never goes over `max_commit`.
+fn hold_to_budget(
+    commit: u64,
+    min_commit: u64,
+    max_commit: u64,
+    estimated_tx_fee: u64,
+    remaining: u64,
+) -> Result<u64, SkippedCommit> {
+    if max_commit < min_commit {
+        return Err(SkippedCommit::CapBelowFloor);
+    }
+    let available = remaining
+        .checked_sub(estimated_tx_fee)
+        .filter(|available| *available >= min_commit)
+        .ok_or(SkippedCommit::BudgetExhausted)?;
+    Ok(commit.min(max_commit).max(min_commit).min(available))
+}
+
+/// Plans each block-commit: asks the configured strategy how much to commit, holds it to the
+/// spend budget, and logs the expected value of the commit.
+pub struct BurnCommitPlanner {
+    strategy: Box<dyn CommitStrategy>,
+    budget: SpendBudget,
+    lookback: u64,
+    estimated_tx_fee: u64,
+}
+
+impl BurnCommitPlanner {
+    pub fn new(
+        strategy: Box<dyn CommitStrategy>,
+        budget: SpendBudget,
+        lookback: u64,
+        estimated_tx_fee: u64,
+    ) -> BurnCommitPlanner {
+        BurnCommitPlanner {
+            strategy,
+            budget,
+            lookback,
+            estimated_tx_fee,
+        }
+    }
+
+    pub fn from_config(config: &Config) -> BurnCommitPlanner {
+        let burnchain = &config.burnchain;
+        let strategy: Box<dyn CommitStrategy> = match burnchain.commit_strategy.as_str() {
+            "win_rate" => Box::new(WinRateCommitStrategy {
+                target_win_probability: burnchain
+                    .target_win_probability
+                    .expect("FATAL: win_rate commit strategy requires a target win probability"),
+                max_commit: burnchain.burn_fee_cap,
+            }),
+            _ => Box::new(FixedCommitStrategy {
+                commit: burnchain.burn_fee_cap,
+            }),
+        };
+        BurnCommitPlanner::new(
+            strategy,
+            SpendBudget::new(burnchain.daily_spend_budget, burnchain.cycle_spend_budget),
+            burnchain.commit_lookback_blocks,
+            burnchain.block_commit_tx_estimated_size * burnchain.satoshis_per_byte,
+        )
+    }
+
+    /// Decide how many sats to commit, over `pox_outputs` outputs, for the sortition after
+    /// `burn_tip`.  Returns None if the commit cap or the spend budget doesn't leave room for a
+    /// commit.
+    pub fn plan_commit(
+        &mut self,
+        sortdb: &SortitionDB,
+        burnchain: &Burnchain,
+        burn_tip: &BlockSnapshot,
+        my_signer: &BurnchainSigner,
+        pox_outputs: usize,
+    ) -> Option<u64> {
+        let burn_height = burn_tip.block_height + 1;
+        let estimate =
+            match CompetitionEstimate::from_sortdb(sortdb, burn_tip, self.lookback, my_signer) {
+                Ok(estimate) => estimate,
+                Err(e) => {
+                    warn!("Failed to load recent burn distributions: {:?}", &e);
+                    CompetitionEstimate::from_samples(vec![])
+                }
+            };
+
+        let reward_cycle = burnchain
+            .block_height_to_reward_cycle(burn_height)
+            .unwrap_or(0);
+        let remaining = self
+            .budget
+            .remaining(get_epoch_time_secs(), reward_cycle, burn_height);
+        let min_commit = min_commit(pox_outputs);
+        let commit = match hold_to_budget(
+            self.strategy.commit_amount(&estimate),
+            min_commit,
+            self.strategy.max_commit(),
+            self.estimated_tx_fee,
+            remaining,
+        ) {
+            Ok(commit) => commit,
+            Err(SkippedCommit::CapBelowFloor) => {
+                warn!(
+                    "Not committing: the commit cap is below the dust floor of the commit's PoX outputs";
+                    "burn_height" => burn_height,
+                    "max_commit" => self.strategy.max_commit(),
+                    "min_commit" => min_commit,
+                    "pox_outputs" => pox_outputs
+                );
+                return None;
+            }
+            Err(SkippedCommit::BudgetExhausted) => {
+                info!(
+                    "Not committing: BTC spend budget exhausted";
+                    "burn_height" => burn_height,
+                    "remaining" => remaining,
+                    "min_commit" => min_commit,
+                    "daily_cap" => ?self.budget.daily_cap,
+                    "cycle_cap" => ?self.budget.cycle_cap
+                );
+                return None;
+            }
+        };
+
+        let win_probability = estimate.win_probability(commit);
+        let coinbase_ustx =
+            StacksChainState::get_coinbase_reward(burn_height, burnchain.first_block_height);
+        let expected_ustx = win_probability * (coinbase_ustx as f64);
+        let spend = commit.saturating_add(self.estimated_tx_fee);
+        info!(
+            "Planned block-commit";
+            "strategy" => self.strategy.name(),
+            "burn_height" => burn_height,
+            "commit" => commit,
+            "estimated_tx_fee" => self.estimated_tx_fee,
+            "competing_burn" => estimate.competing_burn,
+            "samples" => estimate.sample_size,
+            "win_probability" => win_probability,
+            "expected_coinbase_ustx" => expected_ustx,
+            "expected_sats_per_stx" => if expected_ustx > 0.0 {
+                (spend as f64) / (expected_ustx / 1_000_000.0)
+            } else {
+                f64::INFINITY
+            },
+            "budget_remaining" => remaining
+        );
+
+        Some(commit)
+    }
+
+    /// Count a submitted commit of `commit` sats, for the sortition after `burn_tip`, against
+    /// the spend budget.
+    pub fn record_commit(&mut self, burnchain: &Burnchain, burn_tip: &BlockSnapshot, commit: u64) {
+        let burn_height = burn_tip.block_height + 1;
+        let reward_cycle = burnchain
+            .block_height_to_reward_cycle(burn_height)
+            .unwrap_or(0);
+        self.budget.record(
+            get_epoch_time_secs(),
+            reward_cycle,
+            burn_height,
+            commit.saturating_add(self.estimated_tx_fee),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn competition_estimate_win_probability() {
+        let estimate = CompetitionEstimate::from_samples(vec![3000, 1000, 2000, 9000]);
+        // median of 1000, 2000, 3000, 9000
+        assert_eq!(estimate.competing_burn, 2500);
+        assert_eq!(estimate.sample_size, 4);
+
+        assert_eq!(estimate.win_probability(2500), 0.5);
+        assert_eq!(estimate.commit_for_win_probability(0.5), Some(2500));
+        assert_eq!(estimate.commit_for_win_probability(0.25), Some(834));
+        assert_eq!(estimate.commit_for_win_probability(1.0), None);
+        assert_eq!(estimate.commit_for_win_probability(0.0), None);
+
+        let mut strategy = WinRateCommitStrategy {
+            target_win_probability: 0.5,
+            max_commit: 2000,
+        };
+        assert_eq!(strategy.commit_amount(&estimate), 2000);
+        strategy.max_commit = 10000;
+        assert_eq!(strategy.commit_amount(&estimate), 2500);
+
+        // nothing observed yet
+        let empty = CompetitionEstimate::from_samples(vec![]);
+        assert_eq!(strategy.commit_amount(&empty), 10000);
+    }
+
+    #[test]
+    fn planned_commits_clear_dust_limit() {
+        // no competing burn: the win-rate strategy only needs a single sat
+        let estimate = CompetitionEstimate::from_samples(vec![0, 0, 0]);
+        let mut strategy = WinRateCommitStrategy {
+            target_win_probability: 0.9,
+            max_commit: 20000,
+        };
+        assert_eq!(strategy.commit_amount(&estimate), 1);
+        let floor = min_commit(OUTPUTS_PER_COMMIT);
+        assert_eq!(floor, 11000);
+        assert_eq!(
+            hold_to_budget(
+                strategy.commit_amount(&estimate),
+                floor,
+                strategy.max_commit(),
+                1000,
+                u64::max_value()
+            ),
+            Ok(11000)
+        );
+
+        // trimmed to the budget, but never below the dust floor
+        assert_eq!(hold_to_budget(20000, floor, 20000, 1000, 16000), Ok(15000));
+        assert_eq!(hold_to_budget(20000, floor, 20000, 1000, 12000), Ok(11000));
+        assert_eq!(
+            hold_to_budget(20000, floor, 20000, 1000, 11999),
+            Err(SkippedCommit::BudgetExhausted)
+        );
+        assert_eq!(
+            hold_to_budget(20000, floor, 20000, 1000, 500),
+            Err(SkippedCommit::BudgetExhausted)
+        );
+
+        // never raised past the commit cap: a cap below the floor skips the commit
+        assert_eq!(
+            hold_to_budget(1, floor, 10000, 1000, u64::max_value()),
+            Err(SkippedCommit::CapBelowFloor)
+        );
+
+        // single-output commits (prepare phase, post-sunset) have no floor
+        assert_eq!(min_commit(1), 0);
+        assert_eq!(
+            hold_to_budget(1, min_commit(1), 10000, 1000, u64::max_value()),
+            Ok(1)
+        );
+        assert_eq!(
+            hold_to_budget(20000, min_commit(1), 10000, 1000, u64::max_value()),
+            Ok(10000)
+        );
+    }
+
+    #[test]
+    fn spend_budget_caps() {
+        let mut budget = SpendBudget::new(Some(10000), Some(6000));
+        assert_eq!(budget.remaining(100_000, 1, 10), 6000);
+
+        budget.record(100_000, 1, 10, 4000);
+        assert_eq!(budget.remaining(100_010, 1, 11), 2000);
+
+        // an RBF of the same commit replaces it
+        assert_eq!(budget.remaining(100_010, 1, 10), 6000);
+        budget.record(100_010, 1, 10, 5000);
+        assert_eq!(budget.remaining(100_020, 1, 11), 1000);
+
+        // a new reward cycle resets the cycle cap, but not the daily cap
+        assert_eq!(budget.remaining(100_020, 2, 15), 5000);
+        budget.record(100_020, 2, 15, 5000);
+        assert_eq!(budget.remaining(100_030, 2, 16), 0);
+
+        // a day later, only the cycle cap applies
+        assert_eq!(budget.remaining(100_030 + SECONDS_PER_DAY, 2, 16), 1000);
+    }
+}
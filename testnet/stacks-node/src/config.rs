@@ -7,9 +7,11 @@ use rand::RngCore;
 
 use stacks::burnchains::bitcoin::BitcoinNetworkType;
 use stacks::burnchains::{MagicBytes, BLOCKSTACK_MAGIC_MAINNET};
+use stacks::chainstate::stacks::TransactionPayloadID;
+use stacks::core::mempool::MemPoolAdmissionPolicy;
 use stacks::core::{
     BLOCK_LIMIT_MAINNET, CHAIN_ID_MAINNET, CHAIN_ID_TESTNET, HELIUM_BLOCK_LIMIT,
-    PEER_VERSION_MAINNET, PEER_VERSION_TESTNET,
+    MINING_COMMITMENT_WINDOW, PEER_VERSION_MAINNET, PEER_VERSION_TESTNET,
 };
 use stacks::net::connection::ConnectionOptions;
 use stacks::net::{Neighbor, NeighborKey, PeerAddress};
@@ -34,6 +36,7 @@ pub struct ConfigFile {
     pub ustx_balance: Option<Vec<InitialBalanceFile>>,
     pub events_observer: Option<Vec<EventObserverConfigFile>>,
     pub connection_options: Option<ConnectionOptionsFile>,
+    pub signer: Option<SignerConfigFile>,
 }
 
 #[derive(Clone, Deserialize, Default)]
@@ -88,6 +91,89 @@ mod tests {
             "STRYYQQ9M8KAF4NS7WNZQYY59X93XEKR31JP64CP"
         );
     }
+
+    #[test]
+    fn should_load_event_observer_filter_toml() {
+        let config = ConfigFile::from_str(
+            r#"
+            [[events_observer]]
+            endpoint = "localhost:3700"
+            events_keys = ["*"]
+
+            [[events_observer]]
+            endpoint = "localhost:3701"
+            events_keys = ["stx"]
+
+            [events_observer.filter]
+            principals = ["ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.my-contract"]
+            payload_types = ["contract_call", "token_transfer"]
+            asset_identifiers = ["stx", "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.my-contract::my-token"]
+            print_topics = ["new-order"]
+            include_raw_tx = false
+            "#,
+        );
+        let mut observers = config.events_observer.unwrap();
+        assert_eq!(observers.len(), 2);
+
+        assert!(observers[0].filter.is_none());
+
+        let filter =
+            EventObserverFilter::from_config_file(observers[1].filter.take().unwrap()).unwrap();
+        assert_eq!(
+            filter.principals,
+            vec![
+                PrincipalData::parse("ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.my-contract")
+                    .unwrap()
+            ]
+        );
+        assert_eq!(
+            filter.payload_types,
+            vec![
+                TransactionPayloadID::ContractCall,
+                TransactionPayloadID::TokenTransfer
+            ]
+        );
+        assert_eq!(
+            filter.asset_identifiers,
+            vec![AssetIdentifier {
+                contract_identifier: QualifiedContractIdentifier::parse(
+                    "ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.my-contract"
+                )
+                .unwrap(),
+                asset_name: "my-token".to_string().try_into().unwrap(),
+            }]
+        );
+        assert!(filter.stx_asset);
+        assert_eq!(filter.print_topics, vec!["new-order".to_string()]);
+        assert!(!filter.include_raw_tx);
+        assert!(!filter.is_empty());
+
+        assert!(EventObserverFilter::default().is_empty());
+        assert!(EventObserverFilter::default().include_raw_tx);
+    }
+
+    #[test]
+    fn should_reject_invalid_event_observer_filter() {
+        let bad_filters = [
+            r#"principals = ["not-a-principal"]"#,
+            r#"payload_types = ["not_a_payload_type"]"#,
+            r#"asset_identifiers = ["ST1PQHQKV0RJXZFY1DGX8MNSNYVE3VGZJSRTPGZGM.my-contract"]"#,
+        ];
+        for bad_filter in bad_filters.iter() {
+            let config_file = ConfigFile::from_str(&format!(
+                r#"
+                [[events_observer]]
+                endpoint = "localhost:3700"
+                events_keys = ["*"]
+
+                [events_observer.filter]
+                {}
+                "#,
+                bad_filter
+            ));
+            assert!(Config::from_config_file(config_file).is_err());
+        }
+    }
 }
 
 impl ConfigFile {
@@ -406,6 +492,7 @@ pub struct Config {
     pub events_observers: Vec<EventObserverConfig>,
     pub connection_options: ConnectionOptions,
     pub block_limit: ExecutionCost,
+    pub signer: SignerConfig,
 }
 
 lazy_static! {
@@ -440,7 +527,7 @@ lazy_static! {
 }
 
 impl Config {
-    pub fn from_config_file(config_file: ConfigFile) -> Config {
+    pub fn from_config_file(config_file: ConfigFile) -> Result<Config, String> {
         let default_node_config = NodeConfig::default();
         let (mut node, bootstrap_node, deny_nodes) = match config_file.node {
             Some(node) => {
@@ -488,6 +575,10 @@ impl Config {
                         .pox_sync_sample_secs
                         .unwrap_or(default_node_config.pox_sync_sample_secs),
                     use_test_genesis_chainstate: node.use_test_genesis_chainstate,
+                    prune_depth: node.prune_depth,
+                    compress_blocks: node
+                        .compress_blocks
+                        .unwrap_or(default_node_config.compress_blocks),
                 };
                 (node_config, node.bootstrap_node, node.deny_nodes)
             }
@@ -603,6 +694,15 @@ impl Config {
                     rbf_fee_increment: burnchain
                         .rbf_fee_increment
                         .unwrap_or(default_burnchain_config.rbf_fee_increment),
+                    commit_strategy: burnchain
+                        .commit_strategy
+                        .unwrap_or(default_burnchain_config.commit_strategy),
+                    target_win_probability: burnchain.target_win_probability,
+                    commit_lookback_blocks: burnchain
+                        .commit_lookback_blocks
+                        .unwrap_or(default_burnchain_config.commit_lookback_blocks),
+                    daily_spend_budget: burnchain.daily_spend_budget,
+                    cycle_spend_budget: burnchain.cycle_spend_budget,
                 }
             }
             None => default_burnchain_config,
@@ -623,6 +723,18 @@ impl Config {
             panic!("Config is missing the setting `burnchain.local_mining_public_key` (mandatory for helium)")
         }
 
+        match burnchain.commit_strategy.as_str() {
+            "fixed" => {}
+            "win_rate" => match burnchain.target_win_probability {
+                Some(p) if p > 0.0 && p < 1.0 => {}
+                _ => panic!("Config setting `burnchain.target_win_probability` must be between 0 and 1 (exclusive) for the win_rate commit strategy"),
+            },
+            s => panic!(
+                "Setting burnchain.commit_strategy '{}' not supported (should be: fixed, win_rate)",
+                s
+            ),
+        }
+
         if let Some(bootstrap_node) = bootstrap_node {
             node.set_bootstrap_nodes(bootstrap_node, burnchain.chain_id, burnchain.peer_version);
         } else {
@@ -668,9 +780,15 @@ impl Config {
 
                     let endpoint = format!("{}", observer.endpoint);
 
+                    let filter = match observer.filter {
+                        Some(filter) => EventObserverFilter::from_config_file(filter)?,
+                        None => EventObserverFilter::default(),
+                    };
+
                     observers.push(EventObserverConfig {
                         endpoint,
                         events_keys,
+                        filter,
                     });
                 }
                 observers
@@ -683,6 +801,7 @@ impl Config {
             Ok(val) => events_observers.push(EventObserverConfig {
                 endpoint: val,
                 events_keys: vec![EventKeyType::AnyEvent],
+                filter: EventObserverFilter::default(),
             }),
             _ => (),
         };
@@ -715,8 +834,38 @@ impl Config {
                 opts.read_only_call_limit_runtime.map(|x| {
                     read_only_call_limit.runtime = x;
                 });
+                let default_mempool_policy = HELIUM_DEFAULT_CONNECTION_OPTIONS
+                    .mempool_admission_policy
+                    .clone();
+                let mempool_admission_policy = MemPoolAdmissionPolicy {
+                    max_pending_txs_per_origin: opts
+                        .mempool_max_pending_txs_per_origin
+                        .or(default_mempool_policy.max_pending_txs_per_origin),
+                    min_fee_rate_token_transfer: opts
+                        .mempool_min_fee_rate_token_transfer
+                        .unwrap_or(default_mempool_policy.min_fee_rate_token_transfer),
+                    min_fee_rate_contract_call: opts
+                        .mempool_min_fee_rate_contract_call
+                        .unwrap_or(default_mempool_policy.min_fee_rate_contract_call),
+                    min_fee_rate_smart_contract: opts
+                        .mempool_min_fee_rate_smart_contract
+                        .unwrap_or(default_mempool_policy.min_fee_rate_smart_contract),
+                    max_contract_size: opts
+                        .mempool_max_contract_size
+                        .or(default_mempool_policy.max_contract_size),
+                    max_nonce_gap: opts
+                        .mempool_max_nonce_gap
+                        .or(default_mempool_policy.max_nonce_gap),
+                    max_mempool_txs: opts
+                        .mempool_max_txs
+                        .or(default_mempool_policy.max_mempool_txs),
+                    max_mempool_bytes: opts
+                        .mempool_max_bytes
+                        .or(default_mempool_policy.max_mempool_bytes),
+                };
                 ConnectionOptions {
                     read_only_call_limit,
+                    mempool_admission_policy,
                     inbox_maxlen: opts
                         .inbox_maxlen
                         .unwrap_or_else(|| HELIUM_DEFAULT_CONNECTION_OPTIONS.inbox_maxlen.clone()),
@@ -834,6 +983,24 @@ impl Config {
                     handshake_timeout: opts.connect_timeout.unwrap_or(5),
                     max_sockets: opts.max_sockets.unwrap_or(800) as usize,
                     antientropy_public: opts.antientropy_public.unwrap_or(true),
+                    peer_reputation_ban_threshold: opts
+                        .peer_reputation_ban_threshold
+                        .unwrap_or_else(|| {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS.peer_reputation_ban_threshold
+                        }),
+                    peer_reputation_half_life: opts.peer_reputation_half_life.unwrap_or_else(
+                        || HELIUM_DEFAULT_CONNECTION_OPTIONS.peer_reputation_half_life,
+                    ),
+                    peer_reputation_min_ban_duration: opts
+                        .peer_reputation_min_ban_duration
+                        .unwrap_or_else(|| {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS.peer_reputation_min_ban_duration
+                        }),
+                    peer_reputation_max_ban_duration: opts
+                        .peer_reputation_max_ban_duration
+                        .unwrap_or_else(|| {
+                            HELIUM_DEFAULT_CONNECTION_OPTIONS.peer_reputation_max_ban_duration
+                        }),
                     ..ConnectionOptions::default()
                 }
             }
@@ -842,14 +1009,33 @@ impl Config {
 
         let block_limit = BLOCK_LIMIT_MAINNET.clone();
 
-        Config {
+        let default_signer_config = SignerConfig::default();
+        let signer = match config_file.signer {
+            Some(signer) => SignerConfig {
+                endpoint: signer.endpoint,
+                bind: signer.bind.unwrap_or(default_signer_config.bind),
+                auth_token: signer.auth_token,
+                timeout: signer.timeout.unwrap_or(default_signer_config.timeout),
+                max_tx_fee: signer.max_tx_fee,
+                max_burn_spend: signer.max_burn_spend,
+                max_burn_fee: signer.max_burn_fee,
+                burn_spend_window: signer
+                    .burn_spend_window
+                    .unwrap_or(default_signer_config.burn_spend_window),
+                state_path: signer.state_path,
+            },
+            None => default_signer_config,
+        };
+
+        Ok(Config {
             node,
             burnchain,
             initial_balances,
             events_observers,
             connection_options,
             block_limit,
-        }
+            signer,
+        })
     }
 
     fn get_burnchain_path(&self) -> PathBuf {
@@ -962,6 +1148,7 @@ impl std::default::Default for Config {
             events_observers: vec![],
             connection_options,
             block_limit,
+            signer: SignerConfig::default(),
         }
     }
 }
@@ -990,6 +1177,11 @@ pub struct BurnchainConfig {
     pub leader_key_tx_estimated_size: u64,
     pub block_commit_tx_estimated_size: u64,
     pub rbf_fee_increment: u64,
+    pub commit_strategy: String,
+    pub target_win_probability: Option<f64>,
+    pub commit_lookback_blocks: u64,
+    pub daily_spend_budget: Option<u64>,
+    pub cycle_spend_budget: Option<u64>,
 }
 
 impl BurnchainConfig {
@@ -1017,6 +1209,11 @@ impl BurnchainConfig {
             leader_key_tx_estimated_size: LEADER_KEY_TX_ESTIM_SIZE,
             block_commit_tx_estimated_size: BLOCK_COMMIT_TX_ESTIM_SIZE,
             rbf_fee_increment: DEFAULT_RBF_FEE_RATE_INCREMENT,
+            commit_strategy: "fixed".to_string(),
+            target_win_probability: None,
+            commit_lookback_blocks: MINING_COMMITMENT_WINDOW as u64,
+            daily_spend_budget: None,
+            cycle_spend_budget: None,
         }
     }
 
@@ -1070,6 +1267,11 @@ pub struct BurnchainConfigFile {
     pub block_commit_tx_estimated_size: Option<u64>,
     pub rbf_fee_increment: Option<u64>,
     pub max_rbf: Option<u64>,
+    pub commit_strategy: Option<String>,
+    pub target_win_probability: Option<f64>,
+    pub commit_lookback_blocks: Option<u64>,
+    pub daily_spend_budget: Option<u64>,
+    pub cycle_spend_budget: Option<u64>,
 }
 
 #[derive(Clone, Debug, Default)]
@@ -1093,6 +1295,13 @@ pub struct NodeConfig {
     pub prometheus_bind: Option<String>,
     pub pox_sync_sample_secs: u64,
     pub use_test_genesis_chainstate: Option<bool>,
+    /// If set, the chains coordinator prunes Clarity state older than this
+    /// many blocks behind the canonical tip as it processes blocks.  `None`
+    /// keeps a full archive.  Blocks that fork off of, or `at-block` into,
+    /// pruned state can't be processed, and stall the node if canonical.
+    pub prune_depth: Option<u32>,
+    /// Whether to store newly-received anchored blocks deflate-compressed.
+    pub compress_blocks: bool,
 }
 
 impl NodeConfig {
@@ -1134,6 +1343,8 @@ impl NodeConfig {
             prometheus_bind: None,
             pox_sync_sample_secs: 30,
             use_test_genesis_chainstate: None,
+            prune_depth: None,
+            compress_blocks: false,
         }
     }
 
@@ -1254,6 +1465,18 @@ pub struct ConnectionOptionsFile {
     pub disable_block_download: Option<bool>,
     pub force_disconnect_interval: Option<u64>,
     pub antientropy_public: Option<bool>,
+    pub mempool_max_pending_txs_per_origin: Option<u64>,
+    pub mempool_min_fee_rate_token_transfer: Option<u64>,
+    pub mempool_min_fee_rate_contract_call: Option<u64>,
+    pub mempool_min_fee_rate_smart_contract: Option<u64>,
+    pub mempool_max_contract_size: Option<u64>,
+    pub mempool_max_nonce_gap: Option<u64>,
+    pub mempool_max_txs: Option<u64>,
+    pub mempool_max_bytes: Option<u64>,
+    pub peer_reputation_ban_threshold: Option<f64>,
+    pub peer_reputation_half_life: Option<u64>,
+    pub peer_reputation_min_ban_duration: Option<u64>,
+    pub peer_reputation_max_ban_duration: Option<u64>,
 }
 
 #[derive(Clone, Deserialize, Default)]
@@ -1277,18 +1500,199 @@ pub struct NodeConfigFile {
     pub prometheus_bind: Option<String>,
     pub pox_sync_sample_secs: Option<u64>,
     pub use_test_genesis_chainstate: Option<bool>,
+    pub prune_depth: Option<u32>,
+    pub compress_blocks: Option<bool>,
+}
+
+/// Settings for the remote signer.  The node reads `endpoint`, `auth_token` and `timeout`; the
+/// `stacks-node signer` process reads `bind`, `auth_token` and the policy settings.
+#[derive(Clone, Debug)]
+pub struct SignerConfig {
+    /// `host:port` of the signer process.  If set, the miner's keys are not derived from
+    /// `node.seed` -- all signing goes to the signer.
+    pub endpoint: Option<String>,
+    /// `host:port` for `stacks-node signer` to listen on.  Must be a loopback address.
+    pub bind: String,
+    /// Shared secret that every request must carry.  Required by `stacks-node signer`.
+    pub auth_token: Option<String>,
+    /// Seconds to wait for the signer to answer a request
+    pub timeout: u64,
+    /// Largest fee, in uSTX, the signer will sign a Stacks transaction for
+    pub max_tx_fee: Option<u64>,
+    /// Largest amount, in sats, that the burnchain transactions signed within any
+    /// `burn_spend_window` may spend in total, in fees and in outputs to addresses other than
+    /// the signer's own.  Required by `stacks-node signer`.
+    pub max_burn_spend: Option<u64>,
+    /// Largest fee, in sats, the signer will sign a burnchain transaction for.  Required by
+    /// `stacks-node signer`.
+    pub max_burn_fee: Option<u64>,
+    /// Seconds over which `max_burn_spend` applies
+    pub burn_spend_window: u64,
+    /// File where `stacks-node signer` keeps the keys it has issued and what it has signed, so
+    /// that its policy still holds after a restart.  Required by `stacks-node signer`.
+    pub state_path: Option<String>,
+}
+
+impl Default for SignerConfig {
+    fn default() -> SignerConfig {
+        SignerConfig {
+            endpoint: None,
+            bind: "127.0.0.1:20445".to_string(),
+            auth_token: None,
+            timeout: 30,
+            max_tx_fee: None,
+            max_burn_spend: None,
+            max_burn_fee: None,
+            burn_spend_window: 86400,
+            state_path: None,
+        }
+    }
+}
+
+#[derive(Clone, Deserialize, Default)]
+pub struct SignerConfigFile {
+    pub endpoint: Option<String>,
+    pub bind: Option<String>,
+    pub auth_token: Option<String>,
+    pub timeout: Option<u64>,
+    pub max_tx_fee: Option<u64>,
+    pub max_burn_spend: Option<u64>,
+    pub max_burn_fee: Option<u64>,
+    pub burn_spend_window: Option<u64>,
+    pub state_path: Option<String>,
 }
 
 #[derive(Clone, Deserialize, Default)]
 pub struct EventObserverConfigFile {
     pub endpoint: String,
     pub events_keys: Vec<String>,
+    pub filter: Option<EventObserverFilterFile>,
+}
+
+#[derive(Clone, Deserialize, Default)]
+pub struct EventObserverFilterFile {
+    pub principals: Option<Vec<String>>,
+    pub payload_types: Option<Vec<String>>,
+    pub asset_identifiers: Option<Vec<String>>,
+    pub print_topics: Option<Vec<String>>,
+    pub include_raw_tx: Option<bool>,
 }
 
 #[derive(Clone, Default)]
 pub struct EventObserverConfig {
     pub endpoint: String,
     pub events_keys: Vec<EventKeyType>,
+    pub filter: EventObserverFilter,
+}
+
+/// Server-side filter applied to the payloads sent to an event observer, on top of the
+/// subscriptions given by its `events_keys`.  An empty list means "don't filter on this".
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventObserverFilter {
+    /// Only send transactions and events that involve one of these principals
+    pub principals: Vec<PrincipalData>,
+    /// Only send transactions (and their events) with one of these payload types
+    pub payload_types: Vec<TransactionPayloadID>,
+    /// Only send FT and NFT events for these assets
+    pub asset_identifiers: Vec<AssetIdentifier>,
+    /// Whether or not to send STX events when filtering on assets (`stx` in the config file)
+    pub stx_asset: bool,
+    /// Only send `print` events whose topic is one of these
+    pub print_topics: Vec<String>,
+    /// Whether or not to include the raw transaction bytes in block and microblock payloads
+    pub include_raw_tx: bool,
+}
+
+impl Default for EventObserverFilter {
+    fn default() -> EventObserverFilter {
+        EventObserverFilter {
+            principals: vec![],
+            payload_types: vec![],
+            asset_identifiers: vec![],
+            stx_asset: false,
+            print_topics: vec![],
+            include_raw_tx: true,
+        }
+    }
+}
+
+impl EventObserverFilter {
+    fn from_config_file(filter: EventObserverFilterFile) -> Result<EventObserverFilter, String> {
+        let mut principals = vec![];
+        for p in filter.principals.unwrap_or(vec![]).iter() {
+            let principal = PrincipalData::parse(p)
+                .map_err(|_e| format!("Invalid principal '{}' in event observer filter", p))?;
+            principals.push(principal);
+        }
+
+        let mut payload_types = vec![];
+        for t in filter.payload_types.unwrap_or(vec![]).iter() {
+            let payload_type = EventObserverFilter::payload_type_from_string(t)
+                .ok_or_else(|| format!("Invalid payload type '{}' in event observer filter", t))?;
+            payload_types.push(payload_type);
+        }
+
+        let mut asset_identifiers = vec![];
+        let mut stx_asset = false;
+        for a in filter.asset_identifiers.unwrap_or(vec![]).iter() {
+            if a == "stx" {
+                stx_asset = true;
+                continue;
+            }
+            let asset_identifier = EventObserverFilter::asset_identifier_from_string(a)
+                .ok_or_else(|| {
+                    format!("Invalid asset identifier '{}' in event observer filter", a)
+                })?;
+            asset_identifiers.push(asset_identifier);
+        }
+
+        Ok(EventObserverFilter {
+            principals,
+            payload_types,
+            asset_identifiers,
+            stx_asset,
+            print_topics: filter.print_topics.unwrap_or(vec![]),
+            include_raw_tx: filter.include_raw_tx.unwrap_or(true),
+        })
+    }
+
+    /// Parses an asset identifier as events report it: `<contract identifier>::<asset name>`
+    fn asset_identifier_from_string(raw_asset: &str) -> Option<AssetIdentifier> {
+        let comps: Vec<_> = raw_asset.split("::").collect();
+        if comps.len() != 2 {
+            return None;
+        }
+        let contract_identifier = QualifiedContractIdentifier::parse(comps[0]).ok()?;
+        let asset_name = comps[1].to_string().try_into().ok()?;
+        Some(AssetIdentifier {
+            contract_identifier,
+            asset_name,
+        })
+    }
+
+    fn payload_type_from_string(raw_type: &str) -> Option<TransactionPayloadID> {
+        match raw_type {
+            "token_transfer" => Some(TransactionPayloadID::TokenTransfer),
+            "smart_contract" => Some(TransactionPayloadID::SmartContract),
+            "contract_call" => Some(TransactionPayloadID::ContractCall),
+            "poison_microblock" => Some(TransactionPayloadID::PoisonMicroblock),
+            "coinbase" => Some(TransactionPayloadID::Coinbase),
+            _ => None,
+        }
+    }
+
+    /// Does this filter let every asset event through?
+    pub fn is_asset_filter_empty(&self) -> bool {
+        self.asset_identifiers.is_empty() && !self.stx_asset
+    }
+
+    /// Does this filter let everything through?
+    pub fn is_empty(&self) -> bool {
+        self.principals.is_empty()
+            && self.payload_types.is_empty()
+            && self.is_asset_filter_empty()
+            && self.print_topics.is_empty()
+    }
 }
 
 #[derive(Clone)]
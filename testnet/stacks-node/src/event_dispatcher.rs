@@ -23,7 +23,8 @@ use stacks::chainstate::stacks::events::{
 };
 use stacks::chainstate::stacks::StacksBlock;
 use stacks::chainstate::stacks::{
-    db::accounts::MinerReward, db::MinerRewardInfo, StacksTransaction,
+    db::accounts::MinerReward, db::MinerRewardInfo, StacksTransaction, TransactionPayload,
+    TransactionPayloadID,
 };
 use stacks::codec::StacksMessageCodec;
 use stacks::core::mempool::{MemPoolDropReason, MemPoolEventDispatcher};
@@ -31,15 +32,18 @@ use stacks::net::atlas::{Attachment, AttachmentInstance};
 use stacks::types::chainstate::{BurnchainHeaderHash, StacksAddress, StacksBlockId};
 use stacks::util::hash::bytes_to_hex;
 use stacks::vm::analysis::contract_interface_builder::build_contract_interface;
-use stacks::vm::types::{AssetIdentifier, QualifiedContractIdentifier, Value};
+use stacks::vm::types::{
+    AssetIdentifier, CharType, PrincipalData, QualifiedContractIdentifier, SequenceData, Value,
+};
 
-use super::config::{EventKeyType, EventObserverConfig};
+use super::config::{EventKeyType, EventObserverConfig, EventObserverFilter};
 use super::node::ChainTip;
 use stacks::chainstate::stacks::db::unconfirmed::ProcessedUnconfirmedState;
 
 #[derive(Debug, Clone)]
 struct EventObserver {
     endpoint: String,
+    filter: EventObserverFilter,
     should_keep_running: Arc<AtomicBool>,
 }
 
@@ -49,6 +53,7 @@ struct ReceiptPayloadInfo<'a> {
     raw_result: String,
     raw_tx: String,
     contract_interface_json: serde_json::Value,
+    vm_error_json: serde_json::Value,
 }
 
 const STATUS_RESP_TRUE: &str = "success";
@@ -173,7 +178,7 @@ impl EventObserver {
         })
     }
 
-    /// Returns tuple of (txid, success, raw_result, raw_tx, contract_interface_json)
+    /// Returns tuple of (txid, success, raw_result, raw_tx, contract_interface_json, vm_error_json)
     fn generate_payload_info_for_receipt(receipt: &StacksTransactionReceipt) -> ReceiptPayloadInfo {
         let tx = &receipt.transaction;
 
@@ -208,12 +213,17 @@ impl EventObserver {
                 None => json!(null),
             }
         };
+        let vm_error_json = match &receipt.vm_error {
+            Some(vm_error) => vm_error.json_serialize(),
+            None => json!(null),
+        };
         ReceiptPayloadInfo {
             txid,
             success,
             raw_result,
             raw_tx,
             contract_interface_json,
+            vm_error_json,
         }
     }
 
@@ -221,6 +231,7 @@ impl EventObserver {
     fn make_new_block_txs_payload(
         receipt: &StacksTransactionReceipt,
         tx_index: u32,
+        include_raw_tx: bool,
     ) -> serde_json::Value {
         let receipt_payload_info = EventObserver::generate_payload_info_for_receipt(receipt);
 
@@ -229,9 +240,10 @@ impl EventObserver {
             "tx_index": tx_index,
             "status": receipt_payload_info.success,
             "raw_result": format!("0x{}", &receipt_payload_info.raw_result),
-            "raw_tx": format!("0x{}", &receipt_payload_info.raw_tx),
+            "raw_tx": EventObserver::make_raw_tx_field(&receipt_payload_info, include_raw_tx),
             "contract_abi": receipt_payload_info.contract_interface_json,
             "execution_cost": receipt.execution_cost,
+            "vm_error": receipt_payload_info.vm_error_json,
         })
     }
 
@@ -240,6 +252,7 @@ impl EventObserver {
         receipt: &StacksTransactionReceipt,
         tx_index: u32,
         sequence: u16,
+        include_raw_tx: bool,
     ) -> serde_json::Value {
         let receipt_payload_info = EventObserver::generate_payload_info_for_receipt(receipt);
 
@@ -248,13 +261,256 @@ impl EventObserver {
             "tx_index": tx_index,
             "status": receipt_payload_info.success,
             "raw_result": format!("0x{}", &receipt_payload_info.raw_result),
-            "raw_tx": format!("0x{}", &receipt_payload_info.raw_tx),
+            "raw_tx": EventObserver::make_raw_tx_field(&receipt_payload_info, include_raw_tx),
             "contract_abi": receipt_payload_info.contract_interface_json,
             "execution_cost": receipt.execution_cost,
+            "vm_error": receipt_payload_info.vm_error_json,
             "sequence": sequence,
         })
     }
 
+    /// Observers can opt out of receiving raw transaction bytes, in which case `raw_tx` is null
+    fn make_raw_tx_field(
+        receipt_payload_info: &ReceiptPayloadInfo,
+        include_raw_tx: bool,
+    ) -> serde_json::Value {
+        if include_raw_tx {
+            json!(format!("0x{}", &receipt_payload_info.raw_tx))
+        } else {
+            json!(null)
+        }
+    }
+
+    /// Returns the principals that a transaction involves: its origin and sponsor, the
+    /// recipient of a token transfer, the contract called, or the contract instantiated.
+    fn get_tx_principals(tx: &StacksTransaction) -> Vec<PrincipalData> {
+        let mut principals: Vec<PrincipalData> = vec![tx.origin_address().into()];
+        if let Some(sponsor) = tx.sponsor_address() {
+            principals.push(sponsor.into());
+        }
+        match tx.payload {
+            TransactionPayload::TokenTransfer(ref recipient, ..) => {
+                principals.push(recipient.clone());
+            }
+            TransactionPayload::ContractCall(ref cc) => {
+                principals.push(PrincipalData::Contract(QualifiedContractIdentifier::new(
+                    cc.address.clone().into(),
+                    cc.contract_name.clone(),
+                )));
+            }
+            TransactionPayload::SmartContract(ref sc) => {
+                principals.push(PrincipalData::Contract(QualifiedContractIdentifier::new(
+                    tx.origin_address().into(),
+                    sc.name.clone(),
+                )));
+            }
+            TransactionPayload::PoisonMicroblock(..) | TransactionPayload::Coinbase(..) => {}
+        }
+        principals
+    }
+
+    /// Returns the principals that an event involves
+    fn get_event_principals(event: &StacksTransactionEvent) -> Vec<&PrincipalData> {
+        match event {
+            StacksTransactionEvent::SmartContractEvent(_) => vec![],
+            StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(event_data)) => {
+                vec![&event_data.sender, &event_data.recipient]
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(event_data)) => {
+                vec![&event_data.recipient]
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(event_data)) => {
+                vec![&event_data.sender]
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(event_data)) => {
+                vec![&event_data.locked_address]
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXUnlockEvent(event_data)) => {
+                vec![&event_data.unlocked_address]
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXDelegateEvent(event_data)) => {
+                vec![&event_data.stacker, &event_data.delegate_to]
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXRevokeDelegateEvent(event_data)) => {
+                vec![&event_data.stacker]
+            }
+            StacksTransactionEvent::STXEvent(STXEventType::STXDelegateStackEvent(event_data)) => {
+                vec![&event_data.stacker, &event_data.delegate]
+            }
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(event_data)) => {
+                vec![&event_data.sender, &event_data.recipient]
+            }
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(event_data)) => {
+                vec![&event_data.recipient]
+            }
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTBurnEvent(event_data)) => {
+                vec![&event_data.sender]
+            }
+            StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(event_data)) => {
+                vec![&event_data.sender, &event_data.recipient]
+            }
+            StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(event_data)) => {
+                vec![&event_data.recipient]
+            }
+            StacksTransactionEvent::FTEvent(FTEventType::FTBurnEvent(event_data)) => {
+                vec![&event_data.sender]
+            }
+        }
+    }
+
+    /// Returns the topic of a `print` event, if it has one.  The topic of a printed string
+    /// is the string itself, and the topic of a printed tuple is its `topic` field.
+    fn get_print_topic(value: &Value) -> Option<String> {
+        match value {
+            Value::Sequence(SequenceData::String(CharType::ASCII(data))) => {
+                String::from_utf8(data.data.clone()).ok()
+            }
+            Value::Sequence(SequenceData::String(CharType::UTF8(data))) => {
+                let bytes: Vec<u8> = data.data.iter().flatten().cloned().collect();
+                String::from_utf8(bytes).ok()
+            }
+            Value::Tuple(tuple_data) => match tuple_data.get("topic") {
+                Ok(topic) => EventObserver::get_print_topic(topic),
+                Err(_) => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Does this transaction have one of the payload types the observer asked for?
+    fn tx_payload_type_passes_filter(&self, tx: &TransactionOrigin) -> bool {
+        if self.filter.payload_types.is_empty() {
+            return true;
+        }
+        let payload_type = match tx {
+            TransactionOrigin::Stacks(ref tx) => match tx.payload {
+                TransactionPayload::TokenTransfer(..) => TransactionPayloadID::TokenTransfer,
+                TransactionPayload::ContractCall(..) => TransactionPayloadID::ContractCall,
+                TransactionPayload::SmartContract(..) => TransactionPayloadID::SmartContract,
+                TransactionPayload::PoisonMicroblock(..) => TransactionPayloadID::PoisonMicroblock,
+                TransactionPayload::Coinbase(..) => TransactionPayloadID::Coinbase,
+            },
+            // burnchain operations have no payload type
+            TransactionOrigin::Burn(_) => return false,
+        };
+        self.filter.payload_types.contains(&payload_type)
+    }
+
+    /// Does this transaction involve one of the principals the observer asked for?
+    fn tx_principals_pass_filter(&self, tx: &TransactionOrigin) -> bool {
+        if self.filter.principals.is_empty() {
+            return true;
+        }
+        match tx {
+            TransactionOrigin::Stacks(ref tx) => EventObserver::get_tx_principals(tx)
+                .iter()
+                .any(|p| self.filter.principals.contains(p)),
+            TransactionOrigin::Burn(_) => false,
+        }
+    }
+
+    /// Does this event involve one of the principals the observer asked for?
+    fn event_principals_pass_filter(&self, event: &StacksTransactionEvent) -> bool {
+        if self.filter.principals.is_empty() {
+            return true;
+        }
+        match event {
+            StacksTransactionEvent::SmartContractEvent(event_data) => self
+                .filter
+                .principals
+                .contains(&PrincipalData::Contract(event_data.key.0.clone())),
+            _ => EventObserver::get_event_principals(event)
+                .iter()
+                .any(|p| self.filter.principals.contains(p)),
+        }
+    }
+
+    /// Is this event about one of the assets the observer asked for?  Events that aren't about
+    /// an asset aren't filtered on assets.
+    fn event_asset_passes_filter(&self, event: &StacksTransactionEvent) -> bool {
+        if self.filter.is_asset_filter_empty() {
+            return true;
+        }
+        let asset_identifier = match event {
+            StacksTransactionEvent::SmartContractEvent(_) => return true,
+            StacksTransactionEvent::STXEvent(_) => return self.filter.stx_asset,
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTTransferEvent(event_data)) => {
+                &event_data.asset_identifier
+            }
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTMintEvent(event_data)) => {
+                &event_data.asset_identifier
+            }
+            StacksTransactionEvent::NFTEvent(NFTEventType::NFTBurnEvent(event_data)) => {
+                &event_data.asset_identifier
+            }
+            StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(event_data)) => {
+                &event_data.asset_identifier
+            }
+            StacksTransactionEvent::FTEvent(FTEventType::FTMintEvent(event_data)) => {
+                &event_data.asset_identifier
+            }
+            StacksTransactionEvent::FTEvent(FTEventType::FTBurnEvent(event_data)) => {
+                &event_data.asset_identifier
+            }
+        };
+        self.filter.asset_identifiers.contains(asset_identifier)
+    }
+
+    /// Does this event, emitted by the given transaction, pass the observer's filter?
+    fn event_passes_filter(&self, event: &StacksTransactionEvent, tx: &TransactionOrigin) -> bool {
+        if let StacksTransactionEvent::SmartContractEvent(event_data) = event {
+            if event_data.key.1 == "print" && !self.filter.print_topics.is_empty() {
+                match EventObserver::get_print_topic(&event_data.value) {
+                    Some(topic) if self.filter.print_topics.contains(&topic) => {}
+                    _ => return false,
+                }
+            }
+        }
+
+        self.event_asset_passes_filter(event)
+            && self.tx_payload_type_passes_filter(tx)
+            && (self.tx_principals_pass_filter(tx) || self.event_principals_pass_filter(event))
+    }
+
+    /// Drops the events that don't pass this observer's filter
+    fn apply_event_filter<'a>(
+        &self,
+        filtered_events: Vec<(usize, &'a (bool, Txid, &'a StacksTransactionEvent))>,
+        receipts: &HashMap<Txid, &StacksTransactionReceipt>,
+    ) -> Vec<(usize, &'a (bool, Txid, &'a StacksTransactionEvent))> {
+        if self.filter.is_empty() {
+            return filtered_events;
+        }
+        filtered_events
+            .into_iter()
+            .filter(|(_, (_, txid, event))| match receipts.get(txid) {
+                Some(receipt) => self.event_passes_filter(event, &receipt.transaction),
+                None => false,
+            })
+            .collect()
+    }
+
+    /// Should this receipt be included in the `transactions` list sent to this observer?
+    /// Unfiltered observers receive every transaction.  Filtered observers receive only the
+    /// transactions that pass their filter, or that emitted an event sent to them.
+    fn should_send_receipt(
+        &self,
+        receipt: &StacksTransactionReceipt,
+        sent_txids: &HashSet<Txid>,
+    ) -> bool {
+        if self.filter.is_empty() {
+            return true;
+        }
+        if sent_txids.contains(&receipt.transaction.txid()) {
+            return true;
+        }
+        if self.filter.principals.is_empty() && self.filter.payload_types.is_empty() {
+            return false;
+        }
+        self.tx_payload_type_passes_filter(&receipt.transaction)
+            && self.tx_principals_pass_filter(&receipt.transaction)
+    }
+
     fn make_new_attachment_payload(
         attachment: &(AttachmentInstance, Attachment),
     ) -> serde_json::Value {
@@ -283,8 +539,38 @@ impl EventObserver {
         &self,
         parent_index_block_hash: StacksBlockId,
         filtered_events: Vec<(usize, &(bool, Txid, &StacksTransactionEvent))>,
-        serialized_txs: &Vec<serde_json::Value>,
+        processed_unconfirmed_state: &ProcessedUnconfirmedState,
     ) {
+        let receipts_by_txid: HashMap<Txid, &StacksTransactionReceipt> =
+            processed_unconfirmed_state
+                .receipts
+                .iter()
+                .flat_map(|(_, receipts)| receipts.iter())
+                .map(|receipt| (receipt.transaction.txid(), receipt))
+                .collect();
+        let filtered_events = self.apply_event_filter(filtered_events, &receipts_by_txid);
+        let sent_txids: HashSet<Txid> = filtered_events
+            .iter()
+            .map(|(_, (_, txid, _))| txid.clone())
+            .collect();
+
+        // Serialize receipts
+        let mut serialized_txs = vec![];
+        for (curr_sequence_number, receipts) in processed_unconfirmed_state.receipts.iter() {
+            for (tx_index, receipt) in receipts.iter().enumerate() {
+                if !self.should_send_receipt(receipt, &sent_txids) {
+                    continue;
+                }
+                let payload = EventObserver::make_new_microblock_txs_payload(
+                    receipt,
+                    tx_index as u32,
+                    *curr_sequence_number,
+                    self.filter.include_raw_tx,
+                );
+                serialized_txs.push(payload);
+            }
+        }
+
         // Serialize events to JSON
         let serialized_events: Vec<serde_json::Value> = filtered_events
             .iter()
@@ -319,6 +605,18 @@ impl EventObserver {
         winner_txid: &Txid,
         mature_rewards: &serde_json::Value,
     ) {
+        let receipts_by_txid: HashMap<Txid, &StacksTransactionReceipt> = chain_tip
+            .receipts
+            .iter()
+            .chain(boot_receipts.iter())
+            .map(|receipt| (receipt.transaction.txid(), receipt))
+            .collect();
+        let filtered_events = self.apply_event_filter(filtered_events, &receipts_by_txid);
+        let sent_txids: HashSet<Txid> = filtered_events
+            .iter()
+            .map(|(_, (_, txid, _))| txid.clone())
+            .collect();
+
         // Serialize events to JSON
         let serialized_events: Vec<serde_json::Value> = filtered_events
             .iter()
@@ -327,13 +625,23 @@ impl EventObserver {
             })
             .collect();
 
-        let mut tx_index: u32 = 0;
         let mut serialized_txs = vec![];
 
-        for receipt in chain_tip.receipts.iter().chain(boot_receipts.iter()) {
-            let payload = EventObserver::make_new_block_txs_payload(receipt, tx_index);
+        for (tx_index, receipt) in chain_tip
+            .receipts
+            .iter()
+            .chain(boot_receipts.iter())
+            .enumerate()
+        {
+            if !self.should_send_receipt(receipt, &sent_txids) {
+                continue;
+            }
+            let payload = EventObserver::make_new_block_txs_payload(
+                receipt,
+                tx_index as u32,
+                self.filter.include_raw_tx,
+            );
             serialized_txs.push(payload);
-            tx_index += 1;
         }
 
         // Wrap events
@@ -514,7 +822,11 @@ impl EventDispatcher {
                     StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(_))
                     | StacksTransactionEvent::STXEvent(STXEventType::STXMintEvent(_))
                     | StacksTransactionEvent::STXEvent(STXEventType::STXBurnEvent(_))
-                    | StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(_)) => {
+                    | StacksTransactionEvent::STXEvent(STXEventType::STXLockEvent(_))
+                    | StacksTransactionEvent::STXEvent(STXEventType::STXUnlockEvent(_))
+                    | StacksTransactionEvent::STXEvent(STXEventType::STXDelegateEvent(_))
+                    | StacksTransactionEvent::STXEvent(STXEventType::STXRevokeDelegateEvent(_))
+                    | StacksTransactionEvent::STXEvent(STXEventType::STXDelegateStackEvent(_)) => {
                         for o_i in &self.stx_observers_lookup {
                             dispatch_matrix[*o_i as usize].insert(i);
                         }
@@ -675,23 +987,6 @@ impl EventDispatcher {
         let (dispatch_matrix, events) =
             self.create_dispatch_matrix_and_event_vector(&flattened_receipts);
 
-        // Serialize receipts
-        let mut tx_index;
-        let mut serialized_txs = Vec::new();
-
-        for (curr_sequence_number, receipts) in processed_unconfirmed_state.receipts.iter() {
-            tx_index = 0;
-            for receipt in receipts.iter() {
-                let payload = EventObserver::make_new_microblock_txs_payload(
-                    receipt,
-                    tx_index,
-                    *curr_sequence_number,
-                );
-                serialized_txs.push(payload);
-                tx_index += 1;
-            }
-        }
-
         for (obs_id, observer) in interested_observers.iter() {
             let filtered_events_ids = &dispatch_matrix[*obs_id];
             let filtered_events: Vec<_> = filtered_events_ids
@@ -702,7 +997,7 @@ impl EventDispatcher {
             observer.send_new_microblocks(
                 parent_index_block_hash,
                 filtered_events,
-                &serialized_txs,
+                &processed_unconfirmed_state,
             );
         }
     }
@@ -801,6 +1096,7 @@ impl EventDispatcher {
         info!("Registering event observer at: {}", conf.endpoint);
         let event_observer = EventObserver {
             endpoint: conf.endpoint.clone(),
+            filter: conf.filter.clone(),
             should_keep_running,
         };
 
@@ -856,3 +1152,207 @@ impl EventDispatcher {
         self.registered_observers.push(event_observer);
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::convert::TryFrom;
+
+    use stacks::chainstate::stacks::events::{
+        FTTransferEventData, STXTransferEventData, SmartContractEventData,
+    };
+    use stacks::chainstate::stacks::{
+        StacksPrivateKey, TokenTransferMemo, TransactionAuth, TransactionContractCall,
+        TransactionVersion,
+    };
+    use stacks::vm::representations::Span;
+    use stacks::vm::types::{StandardPrincipalData, TupleData};
+    use stacks::vm::{ClarityName, ContractName};
+
+    use super::*;
+
+    fn make_observer(filter: EventObserverFilter) -> EventObserver {
+        EventObserver {
+            endpoint: "localhost:3700".to_string(),
+            filter,
+            should_keep_running: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    fn contract_id(name: &str) -> QualifiedContractIdentifier {
+        QualifiedContractIdentifier::new(
+            StandardPrincipalData::transient(),
+            ContractName::try_from(name).unwrap(),
+        )
+    }
+
+    fn asset_id(contract: &str) -> AssetIdentifier {
+        AssetIdentifier {
+            contract_identifier: contract_id(contract),
+            asset_name: ClarityName::try_from("token").unwrap(),
+        }
+    }
+
+    fn make_tx(sender: &StacksPrivateKey, payload: TransactionPayload) -> TransactionOrigin {
+        TransactionOrigin::Stacks(StacksTransaction::new(
+            TransactionVersion::Testnet,
+            TransactionAuth::from_p2pkh(sender).unwrap(),
+            payload,
+        ))
+    }
+
+    fn make_transfer_tx(sender: &StacksPrivateKey, recipient: &PrincipalData) -> TransactionOrigin {
+        make_tx(
+            sender,
+            TransactionPayload::TokenTransfer(recipient.clone(), 1, TokenTransferMemo([0; 34])),
+        )
+    }
+
+    fn make_call_tx(sender: &StacksPrivateKey, contract: &str) -> TransactionOrigin {
+        let contract = contract_id(contract);
+        make_tx(
+            sender,
+            TransactionPayload::ContractCall(TransactionContractCall {
+                address: contract.issuer.into(),
+                contract_name: contract.name,
+                function_name: ClarityName::try_from("foo").unwrap(),
+                function_args: vec![],
+            }),
+        )
+    }
+
+    fn make_stx_event(sender: &PrincipalData, recipient: &PrincipalData) -> StacksTransactionEvent {
+        StacksTransactionEvent::STXEvent(STXEventType::STXTransferEvent(STXTransferEventData {
+            sender: sender.clone(),
+            recipient: recipient.clone(),
+            amount: 1,
+        }))
+    }
+
+    fn make_ft_event(
+        contract: &str,
+        sender: &PrincipalData,
+        recipient: &PrincipalData,
+    ) -> StacksTransactionEvent {
+        StacksTransactionEvent::FTEvent(FTEventType::FTTransferEvent(FTTransferEventData {
+            asset_identifier: asset_id(contract),
+            sender: sender.clone(),
+            recipient: recipient.clone(),
+            amount: 1,
+        }))
+    }
+
+    fn make_print_event(contract: &str, value: Value) -> StacksTransactionEvent {
+        StacksTransactionEvent::SmartContractEvent(SmartContractEventData {
+            key: (contract_id(contract), "print".to_string()),
+            value,
+            span: Span::zero(),
+        })
+    }
+
+    #[test]
+    fn test_event_filter_principals() {
+        let sender_key = StacksPrivateKey::new();
+        let sender = PrincipalData::from(StandardPrincipalData(26, [3; 20]));
+        let alice = PrincipalData::from(StandardPrincipalData(26, [1; 20]));
+        let bob = PrincipalData::from(StandardPrincipalData(26, [2; 20]));
+
+        let observer = make_observer(EventObserverFilter {
+            principals: vec![bob.clone()],
+            ..EventObserverFilter::default()
+        });
+
+        // the transaction involves bob, so all of its events pass
+        let tx = make_transfer_tx(&sender_key, &bob);
+        assert!(observer.event_passes_filter(&make_stx_event(&sender, &alice), &tx));
+
+        // otherwise, only the events that involve bob pass
+        let tx = make_transfer_tx(&sender_key, &alice);
+        assert!(observer.event_passes_filter(&make_stx_event(&sender, &bob), &tx));
+        assert!(observer.event_passes_filter(&make_ft_event("tokens", &bob, &alice), &tx));
+        assert!(!observer.event_passes_filter(&make_stx_event(&sender, &alice), &tx));
+        assert!(!observer.event_passes_filter(&make_ft_event("tokens", &sender, &alice), &tx));
+
+        // a contract's print events involve the contract
+        let observer = make_observer(EventObserverFilter {
+            principals: vec![PrincipalData::Contract(contract_id("orders"))],
+            ..EventObserverFilter::default()
+        });
+        let print = Value::string_ascii_from_bytes("hello".as_bytes().to_vec()).unwrap();
+        assert!(observer.event_passes_filter(&make_print_event("orders", print.clone()), &tx));
+        assert!(!observer.event_passes_filter(&make_print_event("other", print), &tx));
+    }
+
+    #[test]
+    fn test_event_filter_payload_types() {
+        let sender_key = StacksPrivateKey::new();
+        let alice = PrincipalData::from(StandardPrincipalData(26, [1; 20]));
+        let bob = PrincipalData::from(StandardPrincipalData(26, [2; 20]));
+        let observer = make_observer(EventObserverFilter {
+            payload_types: vec![TransactionPayloadID::ContractCall],
+            ..EventObserverFilter::default()
+        });
+
+        let event = make_ft_event("tokens", &alice, &bob);
+        assert!(observer.event_passes_filter(&event, &make_call_tx(&sender_key, "tokens")));
+        assert!(!observer.event_passes_filter(&event, &make_transfer_tx(&sender_key, &bob)));
+        assert!(!observer.event_passes_filter(&event, &TransactionOrigin::Burn(Txid([0; 32]))));
+    }
+
+    #[test]
+    fn test_event_filter_asset_identifiers() {
+        let sender_key = StacksPrivateKey::new();
+        let alice = PrincipalData::from(StandardPrincipalData(26, [1; 20]));
+        let bob = PrincipalData::from(StandardPrincipalData(26, [2; 20]));
+        let tx = make_call_tx(&sender_key, "tokens");
+        let print = make_print_event(
+            "tokens",
+            Value::string_ascii_from_bytes("hello".as_bytes().to_vec()).unwrap(),
+        );
+
+        let observer = make_observer(EventObserverFilter {
+            asset_identifiers: vec![asset_id("tokens")],
+            ..EventObserverFilter::default()
+        });
+        assert!(observer.event_passes_filter(&make_ft_event("tokens", &alice, &bob), &tx));
+        assert!(!observer.event_passes_filter(&make_ft_event("other", &alice, &bob), &tx));
+        assert!(!observer.event_passes_filter(&make_stx_event(&alice, &bob), &tx));
+        // events that aren't about an asset aren't filtered on assets
+        assert!(observer.event_passes_filter(&print, &tx));
+
+        let observer = make_observer(EventObserverFilter {
+            stx_asset: true,
+            ..EventObserverFilter::default()
+        });
+        assert!(!observer.filter.is_empty());
+        assert!(observer.event_passes_filter(&make_stx_event(&alice, &bob), &tx));
+        assert!(!observer.event_passes_filter(&make_ft_event("tokens", &alice, &bob), &tx));
+    }
+
+    #[test]
+    fn test_event_filter_print_topics() {
+        let sender_key = StacksPrivateKey::new();
+        let alice = PrincipalData::from(StandardPrincipalData(26, [1; 20]));
+        let bob = PrincipalData::from(StandardPrincipalData(26, [2; 20]));
+        let tx = make_call_tx(&sender_key, "orders");
+        let observer = make_observer(EventObserverFilter {
+            print_topics: vec!["new-order".to_string()],
+            ..EventObserverFilter::default()
+        });
+
+        let topic = |t: &str| Value::string_ascii_from_bytes(t.as_bytes().to_vec()).unwrap();
+        let tuple = Value::Tuple(
+            TupleData::from_data(vec![
+                ("topic".into(), topic("new-order")),
+                ("id".into(), Value::UInt(1)),
+            ])
+            .unwrap(),
+        );
+
+        assert!(observer.event_passes_filter(&make_print_event("orders", topic("new-order")), &tx));
+        assert!(observer.event_passes_filter(&make_print_event("orders", tuple), &tx));
+        assert!(!observer.event_passes_filter(&make_print_event("orders", topic("other")), &tx));
+        assert!(!observer.event_passes_filter(&make_print_event("orders", Value::UInt(1)), &tx));
+        // other events aren't filtered on print topics
+        assert!(observer.event_passes_filter(&make_ft_event("tokens", &alice, &bob), &tx));
+    }
+}
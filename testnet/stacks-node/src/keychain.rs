@@ -1,15 +1,174 @@
 use std::collections::HashMap;
+use std::fmt;
+use std::thread;
+use std::time::Duration;
 
 use stacks::address::AddressHashMode;
 use stacks::burnchains::{BurnchainSigner, PrivateKey};
+use stacks::chainstate::stacks::miner::MicroblockHeaderSigner;
 use stacks::chainstate::stacks::{
-    StacksPrivateKey, StacksPublicKey, StacksTransactionSigner, TransactionAuth,
+    Error as ChainstateError, StacksPrivateKey, StacksPublicKey, StacksTransactionSigner,
+    TransactionAuth, TransactionSpendingCondition,
 };
-use stacks::types::chainstate::StacksAddress;
+use stacks::net::Error as NetError;
+use stacks::types::chainstate::{StacksAddress, StacksMicroblockHeader};
 use stacks::util::hash::{Hash160, Sha256Sum};
 use stacks::util::vrf::{VRFPrivateKey, VRFProof, VRFPublicKey, VRF};
 
 use super::operations::BurnchainOpSigner;
+use super::remote_signer::RemoteSigner;
+use super::Config;
+
+#[derive(Debug)]
+pub enum SignerError {
+    /// The signer refused to sign under its policy
+    Denied(String),
+    /// The signer does not hold the requested key
+    UnknownKey(String),
+    /// Could not reach the signer, or it sent back something unparseable
+    Transport(String),
+    /// The signer's answer does not verify
+    BadSignature(String),
+}
+
+impl fmt::Display for SignerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SignerError::Denied(ref s) => write!(f, "Signer denied request: {}", s),
+            SignerError::UnknownKey(ref s) => write!(f, "Signer has no such key: {}", s),
+            SignerError::Transport(ref s) => write!(f, "Failed to reach signer: {}", s),
+            SignerError::BadSignature(ref s) => write!(f, "Signer produced a bad signature: {}", s),
+        }
+    }
+}
+
+/// The signing operations a miner performs.  `Keychain` performs them in-process, from keys
+/// derived from `node.seed`.  `RemoteSigner` forwards them to a `stacks-node signer` process, so
+/// that the node never holds the keys.
+pub trait MinerSigner: Send {
+    /// The burnchain identity the miner signs burnchain operations as.  This also determines its
+    /// Stacks address.
+    fn get_burnchain_signer(&self) -> BurnchainSigner;
+
+    /// Add the miner's origin signatures to a Stacks transaction
+    fn sign_as_origin(
+        &mut self,
+        tx_signer: &mut StacksTransactionSigner,
+    ) -> Result<(), SignerError>;
+
+    /// Derive a new VRF keypair from the given burnchain block height
+    fn rotate_vrf_keypair(&mut self, block_height: u64) -> Result<VRFPublicKey, SignerError>;
+
+    /// Given a VRF public key, generates a VRF Proof
+    fn generate_proof(&mut self, vrf_pk: &VRFPublicKey, bytes: &[u8; 32]) -> Option<VRFProof>;
+
+    /// Derive a new microblock keypair for a tenure starting at the given burnchain block height
+    fn rotate_microblock_keypair(
+        &mut self,
+        burn_block_height: u64,
+    ) -> Result<StacksPublicKey, SignerError>;
+
+    /// The public key of the most recently derived microblock keypair
+    fn get_microblock_public_key(&self) -> Option<StacksPublicKey>;
+
+    /// Sign a microblock header with the microblock key for `pubkey`
+    fn sign_microblock_header(
+        &mut self,
+        pubkey: &StacksPublicKey,
+        header: &mut StacksMicroblockHeader,
+    ) -> Result<(), SignerError>;
+
+    /// A signer for the burnchain transactions that carry block-commits and key registrations
+    fn generate_op_signer(&self) -> BurnchainOpSigner;
+
+    /// Given the miner's public keys, computes and returns the corresponding Stacks address.
+    fn get_address(&self, is_mainnet: bool) -> StacksAddress {
+        Keychain::address_from_burnchain_signer(&self.get_burnchain_signer(), is_mainnet)
+    }
+
+    fn get_transaction_auth(&self) -> Option<TransactionAuth> {
+        Keychain::transaction_auth_from_burnchain_signer(&self.get_burnchain_signer())
+    }
+
+    fn origin_address(&self, is_mainnet: bool) -> Option<StacksAddress> {
+        match self.get_transaction_auth() {
+            Some(auth) => {
+                let address = if is_mainnet {
+                    auth.origin().address_mainnet()
+                } else {
+                    auth.origin().address_testnet()
+                };
+                Some(address)
+            }
+            None => None,
+        }
+    }
+}
+
+/// Number of times the node tries to reach the remote signer when it starts, since the signer
+/// may be starting up alongside it
+const SIGNER_CONNECT_ATTEMPTS: u32 = 10;
+
+/// Time between attempts to reach the remote signer
+const SIGNER_CONNECT_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Instantiate the miner's signer: a client for the remote signer if `signer.endpoint` is set,
+/// and otherwise a keychain derived from `node.seed`.  Fails if the remote signer can't be
+/// reached after `SIGNER_CONNECT_ATTEMPTS` tries, or if it refuses to answer.
+pub fn miner_signer_from_config(config: &Config) -> Result<Box<dyn MinerSigner>, SignerError> {
+    let endpoint = match config.signer.endpoint {
+        Some(ref endpoint) => endpoint,
+        None => return Ok(Box::new(Keychain::default(config.node.seed.clone()))),
+    };
+
+    info!("Miner keys are held by the remote signer at {}", endpoint);
+    let mut attempt = 1;
+    loop {
+        match RemoteSigner::connect(endpoint, &config.signer) {
+            Ok(signer) => return Ok(Box::new(signer)),
+            Err(SignerError::Transport(e)) if attempt < SIGNER_CONNECT_ATTEMPTS => {
+                warn!(
+                    "Failed to reach signer at {} (attempt {} of {}): {}",
+                    endpoint, attempt, SIGNER_CONNECT_ATTEMPTS, e
+                );
+                thread::sleep(SIGNER_CONNECT_INTERVAL);
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Signs microblock headers through a `MinerSigner`, so the microblock miner never needs the
+/// microblock private key.
+pub struct MinerMicroblockSigner<'a> {
+    signer: &'a mut dyn MinerSigner,
+    pubkey: StacksPublicKey,
+}
+
+impl<'a> MinerMicroblockSigner<'a> {
+    pub fn new(
+        signer: &'a mut dyn MinerSigner,
+        pubkey: StacksPublicKey,
+    ) -> MinerMicroblockSigner<'a> {
+        MinerMicroblockSigner { signer, pubkey }
+    }
+}
+
+impl<'a> MicroblockHeaderSigner for MinerMicroblockSigner<'a> {
+    fn microblock_pubkey_hash(&self) -> Hash160 {
+        Hash160::from_node_public_key(&self.pubkey)
+    }
+
+    fn sign_microblock_header(
+        &mut self,
+        header: &mut StacksMicroblockHeader,
+    ) -> Result<(), ChainstateError> {
+        self.signer
+            .sign_microblock_header(&self.pubkey, header)
+            .map_err(|e| ChainstateError::NetError(NetError::SigningError(e.to_string())))
+    }
+}
 
 #[derive(Clone)]
 pub struct Keychain {
@@ -200,6 +359,37 @@ impl Keychain {
         .unwrap()
     }
 
+    pub fn transaction_auth_from_burnchain_signer(
+        signer: &BurnchainSigner,
+    ) -> Option<TransactionAuth> {
+        let num_sigs = signer.num_sigs as u16;
+        let public_keys = signer.public_keys.clone();
+        let condition = match signer.hash_mode {
+            AddressHashMode::SerializeP2PKH => {
+                TransactionSpendingCondition::new_singlesig_p2pkh(public_keys.first()?.clone())
+            }
+            AddressHashMode::SerializeP2SH => {
+                TransactionSpendingCondition::new_multisig_p2sh(num_sigs, public_keys)
+            }
+            AddressHashMode::SerializeP2WPKH => {
+                TransactionSpendingCondition::new_singlesig_p2wpkh(public_keys.first()?.clone())
+            }
+            AddressHashMode::SerializeP2WSH => {
+                TransactionSpendingCondition::new_multisig_p2wsh(num_sigs, public_keys)
+            }
+        };
+        condition.map(TransactionAuth::Standard)
+    }
+
+    /// Find the microblock secret key for the given public key
+    pub fn get_microblock_key_for(&self, pubkey: &StacksPublicKey) -> Option<StacksPrivateKey> {
+        self.microblocks_secret_keys
+            .iter()
+            .rev()
+            .find(|sk| StacksPublicKey::from_private(sk) == *pubkey)
+            .cloned()
+    }
+
     pub fn get_burnchain_signer(&self) -> BurnchainSigner {
         let public_keys = self
             .secret_keys
@@ -244,3 +434,55 @@ impl Keychain {
         BurnchainOpSigner::new(self.secret_keys[0], false)
     }
 }
+
+impl MinerSigner for Keychain {
+    fn get_burnchain_signer(&self) -> BurnchainSigner {
+        Keychain::get_burnchain_signer(self)
+    }
+
+    fn sign_as_origin(
+        &mut self,
+        tx_signer: &mut StacksTransactionSigner,
+    ) -> Result<(), SignerError> {
+        Keychain::sign_as_origin(self, tx_signer);
+        Ok(())
+    }
+
+    fn rotate_vrf_keypair(&mut self, block_height: u64) -> Result<VRFPublicKey, SignerError> {
+        Ok(Keychain::rotate_vrf_keypair(self, block_height))
+    }
+
+    fn generate_proof(&mut self, vrf_pk: &VRFPublicKey, bytes: &[u8; 32]) -> Option<VRFProof> {
+        Keychain::generate_proof(self, vrf_pk, bytes)
+    }
+
+    fn rotate_microblock_keypair(
+        &mut self,
+        burn_block_height: u64,
+    ) -> Result<StacksPublicKey, SignerError> {
+        let sk = Keychain::rotate_microblock_keypair(self, burn_block_height);
+        Ok(StacksPublicKey::from_private(&sk))
+    }
+
+    fn get_microblock_public_key(&self) -> Option<StacksPublicKey> {
+        self.get_microblock_key()
+            .map(|sk| StacksPublicKey::from_private(&sk))
+    }
+
+    fn sign_microblock_header(
+        &mut self,
+        pubkey: &StacksPublicKey,
+        header: &mut StacksMicroblockHeader,
+    ) -> Result<(), SignerError> {
+        let sk = self
+            .get_microblock_key_for(pubkey)
+            .ok_or_else(|| SignerError::UnknownKey(pubkey.to_hex()))?;
+        header
+            .sign(&sk)
+            .map_err(|e| SignerError::BadSignature(e.to_string()))
+    }
+
+    fn generate_op_signer(&self) -> BurnchainOpSigner {
+        Keychain::generate_op_signer(self)
+    }
+}
@@ -21,6 +21,7 @@ use stacks::util::hash::hex_bytes;
 pub mod monitoring;
 
 pub mod burnchains;
+pub mod commit_strategy;
 pub mod config;
 pub mod event_dispatcher;
 pub mod genesis_data;
@@ -28,6 +29,7 @@ pub mod keychain;
 pub mod neon_node;
 pub mod node;
 pub mod operations;
+pub mod remote_signer;
 pub mod run_loop;
 pub mod syncctl;
 pub mod tenure;
@@ -130,11 +132,29 @@ fn main() {
             println!("{}", &version());
             return;
         }
+        "signer" => {
+            let config_path: String = args.value_from_str("--config").unwrap();
+            args.finish().unwrap();
+            info!("Loading config at path {}", config_path);
+            let conf = load_config(ConfigFile::from_path(&config_path));
+            let mut signer = match remote_signer::SignerServer::from_config(&conf) {
+                Ok(signer) => signer,
+                Err(e) => {
+                    error!("Failed to start signer: {}", e);
+                    process::exit(1);
+                }
+            };
+            if let Err(e) = signer.serve() {
+                error!("Signer exited: {}", e);
+                process::exit(1);
+            }
+            return;
+        }
         "key-for-seed" => {
             let seed = {
                 let config_path: Option<String> = args.opt_value_from_str("--config").unwrap();
                 if let Some(config_path) = config_path {
-                    let conf = Config::from_config_file(ConfigFile::from_path(&config_path));
+                    let conf = load_config(ConfigFile::from_path(&config_path));
                     args.finish().unwrap();
                     conf.node.seed
                 } else {
@@ -162,7 +182,7 @@ fn main() {
         }
     };
 
-    let conf = Config::from_config_file(config_file);
+    let conf = load_config(config_file);
     debug!("node configuration {:?}", &conf.node);
     debug!("burnchain configuration {:?}", &conf.burnchain);
     debug!("connection configuration {:?}", &conf.connection_options);
@@ -188,6 +208,16 @@ fn main() {
     }
 }
 
+fn load_config(config_file: ConfigFile) -> Config {
+    match Config::from_config_file(config_file) {
+        Ok(conf) => conf,
+        Err(e) => {
+            error!("Invalid config: {}", e);
+            process::exit(1);
+        }
+    }
+}
+
 fn version() -> String {
     stacks::version_string(
         "stacks-node",
@@ -231,6 +261,14 @@ start\t\tStart a node with a config of your own. Can be used for joining a netwo
 \t\tExample:
 \t\t  stacks-node start --config=/path/to/config.toml
 
+signer\t\tRun a signer that holds a miner's keys, so that the mining node does not have to.
+\t\tThe signer derives its keys from `node.seed`, and listens on `signer.bind`. Point the miner
+\t\tat it by setting `signer.endpoint` in the miner's config.
+\t\tArguments:
+\t\t  --config: path of the config.
+\t\tExample:
+\t\t  stacks-node signer --config=/path/to/signer.toml
+
 version\t\tDisplay information about the current version and our release cycle.
 
 key-for-seed\tOutput the associated secret key for a burnchain signer created with a given seed.
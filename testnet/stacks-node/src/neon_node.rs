@@ -59,10 +59,12 @@ use stacks::util::vrf::VRFPublicKey;
 use stacks::vm::costs::ExecutionCost;
 
 use crate::burnchains::bitcoin_regtest_controller::BitcoinRegtestController;
+use crate::commit_strategy::BurnCommitPlanner;
 use crate::run_loop::RegisteredKey;
 use crate::syncctl::PoxSyncWatchdogComms;
 use crate::ChainTip;
 
+use super::keychain::{MinerMicroblockSigner, MinerSigner};
 use super::{BurnchainController, BurnchainTip, Config, EventDispatcher, Keychain};
 use stacks::monitoring;
 
@@ -78,7 +80,7 @@ struct AssembledAnchorBlock {
 struct MicroblockMinerState {
     parent_consensus_hash: ConsensusHash,
     parent_block_hash: BlockHeaderHash,
-    miner_key: Secp256k1PrivateKey,
+    miner_pubkey: StacksPublicKey,
     frequency: u64,
     last_mined: u128,
     quantity: u64,
@@ -109,7 +111,7 @@ pub struct InitializedNeonNode {
 
 pub struct NeonGenesisNode {
     pub config: Config,
-    keychain: Keychain,
+    keychain: Box<dyn MinerSigner>,
     event_dispatcher: EventDispatcher,
     burnchain: Burnchain,
 }
@@ -180,11 +182,11 @@ fn inner_process_tenure(
 }
 
 fn inner_generate_coinbase_tx(
-    keychain: &mut Keychain,
+    keychain: &mut dyn MinerSigner,
     nonce: u64,
     is_mainnet: bool,
     chain_id: u32,
-) -> StacksTransaction {
+) -> Option<StacksTransaction> {
     let mut tx_auth = keychain.get_transaction_auth().unwrap();
     tx_auth.set_origin_nonce(nonce);
 
@@ -201,18 +203,21 @@ fn inner_generate_coinbase_tx(
     tx.chain_id = chain_id;
     tx.anchor_mode = TransactionAnchorMode::OnChainOnly;
     let mut tx_signer = StacksTransactionSigner::new(&tx);
-    keychain.sign_as_origin(&mut tx_signer);
+    if let Err(e) = keychain.sign_as_origin(&mut tx_signer) {
+        error!("Failed to sign transaction: {}", e);
+        return None;
+    }
 
-    tx_signer.get_tx().unwrap()
+    tx_signer.get_tx()
 }
 
 fn inner_generate_poison_microblock_tx(
-    keychain: &mut Keychain,
+    keychain: &mut dyn MinerSigner,
     nonce: u64,
     poison_payload: TransactionPayload,
     is_mainnet: bool,
     chain_id: u32,
-) -> StacksTransaction {
+) -> Option<StacksTransaction> {
     let mut tx_auth = keychain.get_transaction_auth().unwrap();
     tx_auth.set_origin_nonce(nonce);
 
@@ -225,9 +230,12 @@ fn inner_generate_poison_microblock_tx(
     tx.chain_id = chain_id;
     tx.anchor_mode = TransactionAnchorMode::OnChainOnly;
     let mut tx_signer = StacksTransactionSigner::new(&tx);
-    keychain.sign_as_origin(&mut tx_signer);
+    if let Err(e) = keychain.sign_as_origin(&mut tx_signer) {
+        error!("Failed to sign transaction: {}", e);
+        return None;
+    }
 
-    tx_signer.get_tx().unwrap()
+    tx_signer.get_tx()
 }
 
 /// Constructs and returns a LeaderKeyRegisterOp out of the provided params
@@ -250,11 +258,17 @@ fn inner_generate_leader_key_register_op(
 
 fn rotate_vrf_and_register(
     is_mainnet: bool,
-    keychain: &mut Keychain,
+    keychain: &mut dyn MinerSigner,
     burn_block: &BlockSnapshot,
     btc_controller: &mut BitcoinRegtestController,
 ) -> bool {
-    let vrf_pk = keychain.rotate_vrf_keypair(burn_block.block_height);
+    let vrf_pk = match keychain.rotate_vrf_keypair(burn_block.block_height) {
+        Ok(vrf_pk) => vrf_pk,
+        Err(e) => {
+            error!("Failed to rotate VRF keypair: {}", e);
+            return false;
+        }
+    };
     let burnchain_tip_consensus_hash = &burn_block.consensus_hash;
     let op = inner_generate_leader_key_register_op(
         keychain.get_address(is_mainnet),
@@ -305,6 +319,7 @@ fn inner_generate_block_commit_op(
 
 /// Mine and broadcast a single microblock, unconditionally.
 fn mine_one_microblock(
+    keychain: &mut dyn MinerSigner,
     microblock_state: &mut MicroblockMinerState,
     sortdb: &SortitionDB,
     chainstate: &mut StacksChainState,
@@ -342,7 +357,10 @@ fn mine_one_microblock(
         };
 
         let t1 = get_epoch_time_ms();
-        let mblock = microblock_miner.mine_next_microblock(mempool, &microblock_state.miner_key)?;
+        let mut microblock_signer =
+            MinerMicroblockSigner::new(keychain, microblock_state.miner_pubkey.clone());
+        let mblock =
+            microblock_miner.mine_next_microblock_with_signer(mempool, &mut microblock_signer)?;
         let new_cost_so_far = microblock_miner.get_cost_so_far().expect("BUG: cannot read cost so far from miner -- indicates that the underlying Clarity Tx is somehow in use still.");
         let t2 = get_epoch_time_ms();
 
@@ -380,15 +398,16 @@ fn mine_one_microblock(
 
 fn try_mine_microblock(
     config: &Config,
+    keychain: &mut dyn MinerSigner,
     microblock_miner_state: &mut Option<MicroblockMinerState>,
     chainstate: &mut StacksChainState,
     sortdb: &SortitionDB,
     mem_pool: &MemPoolDB,
-    winning_tip: (ConsensusHash, BlockHeaderHash, Secp256k1PrivateKey),
+    winning_tip: (ConsensusHash, BlockHeaderHash, StacksPublicKey),
 ) -> Result<Option<StacksMicroblock>, NetError> {
     let ch = winning_tip.0;
     let bhh = winning_tip.1;
-    let microblock_privkey = winning_tip.2;
+    let microblock_pubkey = winning_tip.2;
 
     let mut next_microblock = None;
     if microblock_miner_state.is_none() {
@@ -408,7 +427,7 @@ fn try_mine_microblock(
                 microblock_miner_state.replace(MicroblockMinerState {
                     parent_consensus_hash: ch.clone(),
                     parent_block_hash: bhh.clone(),
-                    miner_key: microblock_privkey.clone(),
+                    miner_pubkey: microblock_pubkey.clone(),
                     frequency: config.node.microblock_frequency,
                     last_mined: 0,
                     quantity: 0,
@@ -444,8 +463,13 @@ fn try_mine_microblock(
                     get_epoch_time_secs() - 600,
                 )?;
                 if num_attachable == 0 {
-                    match mine_one_microblock(&mut microblock_miner, sortdb, chainstate, &mem_pool)
-                    {
+                    match mine_one_microblock(
+                        keychain,
+                        &mut microblock_miner,
+                        sortdb,
+                        chainstate,
+                        &mem_pool,
+                    ) {
                         Ok(microblock) => {
                             // will need to relay this
                             next_microblock = Some(microblock);
@@ -473,12 +497,13 @@ fn try_mine_microblock(
 
 fn run_microblock_tenure(
     config: &Config,
+    keychain: &mut dyn MinerSigner,
     microblock_miner_state: &mut Option<MicroblockMinerState>,
     chainstate: &mut StacksChainState,
     sortdb: &mut SortitionDB,
     mem_pool: &MemPoolDB,
     relayer: &mut Relayer,
-    miner_tip: (ConsensusHash, BlockHeaderHash, Secp256k1PrivateKey),
+    miner_tip: (ConsensusHash, BlockHeaderHash, StacksPublicKey),
     microblocks_processed: BlocksProcessedCounter,
 ) {
     // TODO: this is sensitive to poll latency -- can we call this on a fixed
@@ -494,6 +519,7 @@ fn run_microblock_tenure(
     // Mine microblocks, if we're active
     let next_microblock_opt = match try_mine_microblock(
         &config,
+        keychain,
         microblock_miner_state,
         chainstate,
         sortdb,
@@ -616,6 +642,7 @@ fn spawn_peer(
         &stacks_chainstate_path,
     )
     .map_err(NetError::DBError)?;
+    mem_pool.set_admission_policy(config.connection_options.mempool_admission_policy.clone());
 
     // buffer up blocks to store without stalling the p2p thread
     let mut results_with_data = VecDeque::new();
@@ -768,7 +795,7 @@ fn spawn_miner_relayer(
     mut relayer: Relayer,
     local_peer: LocalPeer,
     config: Config,
-    mut keychain: Keychain,
+    mut keychain: Box<dyn MinerSigner>,
     burn_db_path: String,
     stacks_chainstate_path: String,
     relay_channel: Receiver<RelayerDirective>,
@@ -800,9 +827,9 @@ fn spawn_miner_relayer(
 
     let mut last_mined_blocks: HashMap<
         BurnchainHeaderHash,
-        Vec<(AssembledAnchorBlock, Secp256k1PrivateKey)>,
+        Vec<(AssembledAnchorBlock, StacksPublicKey)>,
     > = HashMap::new();
-    let burn_fee_cap = config.burnchain.burn_fee_cap;
+    let mut commit_planner = BurnCommitPlanner::from_config(&config);
 
     let mut failed_to_mine_in_block: Option<BurnchainHeaderHash> = None;
 
@@ -860,7 +887,7 @@ fn spawn_miner_relayer(
                     if let Some(last_mined_blocks_at_burn_hash) =
                         last_mined_blocks.remove(&burn_hash)
                     {
-                        for (last_mined_block, microblock_privkey) in
+                        for (last_mined_block, microblock_pubkey) in
                             last_mined_blocks_at_burn_hash.into_iter()
                         {
                             let AssembledAnchorBlock {
@@ -949,7 +976,7 @@ fn spawn_miner_relayer(
                                         "Microblock miner tip is now {}/{} ({})",
                                         &consensus_hash, &block_header_hash, StacksBlockHeader::make_index_block_hash(&consensus_hash, &block_header_hash)
                                     );
-                                    miner_tip = Some((ch, bh, microblock_privkey));
+                                    miner_tip = Some((ch, bh, microblock_pubkey));
 
                                     Relayer::refresh_unconfirmed(&mut chainstate, &mut sortdb);
                                     send_unconfirmed_txs(&chainstate, unconfirmed_txs.clone());
@@ -997,19 +1024,19 @@ fn spawn_miner_relayer(
                         &mut sortdb,
                         &burnchain,
                         last_burn_block,
-                        &mut keychain,
+                        keychain.as_mut(),
                         &mut mem_pool,
-                        burn_fee_cap,
+                        &mut commit_planner,
                         &mut bitcoin_controller,
                         &last_mined_blocks_vec.iter().map(|(blk, _)| blk).collect(),
                         &event_dispatcher,
                     );
-                    if let Some((last_mined_block, microblock_privkey)) = last_mined_block_opt {
+                    if let Some((last_mined_block, microblock_pubkey)) = last_mined_block_opt {
                         if last_mined_blocks_vec.len() == 0 {
                             // (for testing) only bump once per epoch
                             bump_processed_counter(&blocks_processed);
                         }
-                        last_mined_blocks_vec.push((last_mined_block, microblock_privkey));
+                        last_mined_blocks_vec.push((last_mined_block, microblock_pubkey));
                     } else {
                         failed_to_mine_in_block = Some(burn_chain_tip);
                     }
@@ -1020,7 +1047,7 @@ fn spawn_miner_relayer(
                 RelayerDirective::RegisterKey(ref last_burn_block) => {
                     rotate_vrf_and_register(
                         is_mainnet,
-                        &mut keychain,
+                        keychain.as_mut(),
                         last_burn_block,
                         &mut bitcoin_controller,
                     );
@@ -1050,6 +1077,7 @@ fn spawn_miner_relayer(
 
                         run_microblock_tenure(
                             &config,
+                            keychain.as_mut(),
                             &mut microblock_miner_state,
                             &mut chainstate,
                             &mut sortdb,
@@ -1088,7 +1116,7 @@ enum LeaderKeyRegistrationState {
 impl InitializedNeonNode {
     fn new(
         config: Config,
-        mut keychain: Keychain,
+        mut keychain: Box<dyn MinerSigner>,
         event_dispatcher: EventDispatcher,
         last_burn_block: Option<BurnchainTip>,
         miner: bool,
@@ -1251,7 +1279,9 @@ impl InitializedNeonNode {
 
         let leader_key_registration_state = if config.node.mock_mining {
             // mock mining, pretend to have a registered key
-            let vrf_public_key = keychain.rotate_vrf_keypair(1);
+            let vrf_public_key = keychain
+                .rotate_vrf_keypair(1)
+                .expect("FATAL: failed to rotate VRF keypair for mock mining");
             LeaderKeyRegistrationState::Active(RegisteredKey {
                 block_height: 1,
                 op_vtxindex: 1,
@@ -1408,13 +1438,13 @@ impl InitializedNeonNode {
         burn_db: &mut SortitionDB,
         burnchain: &Burnchain,
         burn_block: BlockSnapshot,
-        keychain: &mut Keychain,
+        keychain: &mut dyn MinerSigner,
         mem_pool: &mut MemPoolDB,
-        burn_fee_cap: u64,
+        commit_planner: &mut BurnCommitPlanner,
         bitcoin_controller: &mut BitcoinRegtestController,
         last_mined_blocks: &Vec<&AssembledAnchorBlock>,
         event_observer: &EventDispatcher,
-    ) -> Option<(AssembledAnchorBlock, Secp256k1PrivateKey)> {
+    ) -> Option<(AssembledAnchorBlock, StacksPublicKey)> {
         let (
             mut stacks_parent_header,
             parent_consensus_hash,
@@ -1653,8 +1683,8 @@ impl InitializedNeonNode {
 
         // Generates a new secret key for signing the trail of microblocks
         // of the upcoming tenure.
-        let microblock_secret_key = if attempt > 1 {
-            match keychain.get_microblock_key() {
+        let microblock_public_key = if attempt > 1 {
+            match keychain.get_microblock_public_key() {
                 Some(k) => k,
                 None => {
                     error!(
@@ -1665,17 +1695,28 @@ impl InitializedNeonNode {
                 }
             }
         } else {
-            keychain.rotate_microblock_keypair(burn_block.block_height)
+            match keychain.rotate_microblock_keypair(burn_block.block_height) {
+                Ok(k) => k,
+                Err(e) => {
+                    error!("Failed to rotate microblock keypair: {}", e);
+                    return None;
+                }
+            }
         };
-        let mblock_pubkey_hash =
-            Hash160::from_node_public_key(&StacksPublicKey::from_private(&microblock_secret_key));
+        let mblock_pubkey_hash = Hash160::from_node_public_key(&microblock_public_key);
 
-        let coinbase_tx = inner_generate_coinbase_tx(
+        let coinbase_tx = match inner_generate_coinbase_tx(
             keychain,
             coinbase_nonce,
             config.is_mainnet(),
             config.burnchain.chain_id,
-        );
+        ) {
+            Some(tx) => tx,
+            None => {
+                error!("Failed to sign coinbase transaction");
+                return None;
+            }
+        };
 
         // find the longest microblock tail we can build off of
         let microblock_info_opt =
@@ -1734,17 +1775,19 @@ impl InitializedNeonNode {
 
                 // submit the poison payload, privately, so we'll mine it when building the
                 // anchored block.
-                if let Err(e) = mem_pool.submit(
-                    chain_state,
-                    &parent_consensus_hash,
-                    &stacks_parent_header.anchored_header.block_hash(),
-                    &poison_microblock_tx,
-                    Some(event_observer),
-                ) {
-                    warn!(
-                        "Detected but failed to mine poison-microblock transaction: {:?}",
-                        &e
-                    );
+                if let Some(poison_microblock_tx) = poison_microblock_tx {
+                    if let Err(e) = mem_pool.submit(
+                        chain_state,
+                        &parent_consensus_hash,
+                        &stacks_parent_header.anchored_header.block_hash(),
+                        &poison_microblock_tx,
+                        Some(event_observer),
+                    ) {
+                        warn!(
+                            "Detected but failed to mine poison-microblock transaction: {:?}",
+                            &e
+                        );
+                    }
                 }
             }
         }
@@ -1842,9 +1885,6 @@ impl InitializedNeonNode {
             }
         };
 
-        let sunset_burn = burnchain.expected_sunset_burn(burn_block.block_height + 1, burn_fee_cap);
-        let rest_commit = burn_fee_cap - sunset_burn;
-
         let commit_outs = if burn_block.block_height + 1 < burnchain.pox_constants.sunset_end
             && !burnchain.is_in_prepare_phase(burn_block.block_height + 1)
         {
@@ -1853,6 +1893,20 @@ impl InitializedNeonNode {
             vec![StacksAddress::burn_address(config.is_mainnet())]
         };
 
+        let total_commit = match commit_planner.plan_commit(
+            burn_db,
+            burnchain,
+            &burn_block,
+            &keychain.get_burnchain_signer(),
+            commit_outs.len(),
+        ) {
+            Some(commit) => commit,
+            None => return None,
+        };
+
+        let sunset_burn = burnchain.expected_sunset_burn(burn_block.block_height + 1, total_commit);
+        let rest_commit = total_commit - sunset_burn;
+
         // let's commit
         let op = inner_generate_block_commit_op(
             keychain.get_burnchain_signer(),
@@ -1884,6 +1938,7 @@ impl InitializedNeonNode {
             warn!("Failed to submit Bitcoin transaction");
             return None;
         }
+        commit_planner.record_commit(burnchain, &burn_block, total_commit);
 
         Some((
             AssembledAnchorBlock {
@@ -1892,7 +1947,7 @@ impl InitializedNeonNode {
                 anchored_block,
                 attempt,
             },
-            microblock_secret_key,
+            microblock_public_key,
         ))
     }
 
@@ -1988,11 +2043,11 @@ impl NeonGenesisNode {
     /// Instantiate and initialize a new node, given a config
     pub fn new(
         config: Config,
+        keychain: Box<dyn MinerSigner>,
         mut event_dispatcher: EventDispatcher,
         burnchain: Burnchain,
         boot_block_exec: Box<dyn FnOnce(&mut ClarityTx) -> ()>,
     ) -> Self {
-        let keychain = Keychain::default(config.node.seed.clone());
         let initial_balances = config
             .initial_balances
             .iter()
@@ -203,6 +203,7 @@ fn spawn_peer(
                     continue;
                 }
             };
+            mem_pool.set_admission_policy(this.connection_opts.mempool_admission_policy.clone());
 
             let mut expected_attachments = match attachments_rx.try_recv() {
                 Ok(expected_attachments) => expected_attachments,
@@ -814,15 +815,13 @@ impl Node {
         // Handle events
         let receipts = processed_block.tx_receipts;
         let metadata = processed_block.header;
-        let block: StacksBlock = {
-            let block_path = StacksChainState::get_block_path(
-                &self.chain_state.blocks_path,
-                &metadata.consensus_hash,
-                &metadata.anchored_header.block_hash(),
-            )
-            .unwrap();
-            StacksChainState::consensus_load(&block_path).unwrap()
-        };
+        let block: StacksBlock = StacksChainState::load_block(
+            &self.chain_state.blocks_path,
+            &metadata.consensus_hash,
+            &metadata.anchored_header.block_hash(),
+        )
+        .unwrap()
+        .expect("FATAL: processed block is not stored");
 
         let parent_index_hash = StacksBlockHeader::make_index_block_hash(
             &parent_consensus_hash,
@@ -1,8 +1,18 @@
+use stacks::deps::bitcoin::blockdata::script::Script;
+use stacks::deps::bitcoin::blockdata::transaction::Transaction;
 use stacks::util::secp256k1::{MessageSignature, Secp256k1PrivateKey, Secp256k1PublicKey};
 use stacks::{burnchains::PrivateKey, util::hash::hex_bytes};
 
+use super::remote_signer::RemoteSigner;
+
+enum OpSigningKey {
+    Local(Secp256k1PrivateKey),
+    /// the key is held by a signer process; we only know its public key
+    Remote(RemoteSigner, Secp256k1PublicKey),
+}
+
 pub struct BurnchainOpSigner {
-    secret_key: Secp256k1PrivateKey,
+    key: OpSigningKey,
     is_one_off: bool,
     is_disposed: bool,
     usages: u8,
@@ -11,44 +21,136 @@ pub struct BurnchainOpSigner {
 impl BurnchainOpSigner {
     pub fn new(secret_key: Secp256k1PrivateKey, is_one_off: bool) -> BurnchainOpSigner {
         BurnchainOpSigner {
-            secret_key,
+            key: OpSigningKey::Local(secret_key),
             usages: 0,
             is_one_off,
             is_disposed: false,
         }
     }
 
+    /// An op signer whose key is held by a remote signer
+    pub fn remote(
+        signer: RemoteSigner,
+        public_key: Secp256k1PublicKey,
+        is_one_off: bool,
+    ) -> BurnchainOpSigner {
+        BurnchainOpSigner {
+            key: OpSigningKey::Remote(signer, public_key),
+            usages: 0,
+            is_one_off,
+            is_disposed: false,
+        }
+    }
+
+    fn secret_key(&self) -> &Secp256k1PrivateKey {
+        match self.key {
+            OpSigningKey::Local(ref secret_key) => secret_key,
+            OpSigningKey::Remote(..) => {
+                panic!("BUG: the secret key of a remote op signer is not available")
+            }
+        }
+    }
+
+    /// Panics if the key is held by a remote signer
     pub fn get_sk_as_wif(&self) -> String {
-        let hex_encoded = self.secret_key.to_hex();
+        let hex_encoded = self.secret_key().to_hex();
         let mut as_bytes = hex_bytes(&hex_encoded).unwrap();
         as_bytes.insert(0, 0x80);
         stacks::address::b58::check_encode_slice(&as_bytes)
     }
 
+    /// Panics if the key is held by a remote signer
     pub fn get_sk_as_hex(&self) -> String {
-        self.secret_key.to_hex()
+        self.secret_key().to_hex()
     }
 
     pub fn get_public_key(&mut self) -> Secp256k1PublicKey {
-        let public_key = Secp256k1PublicKey::from_private(&self.secret_key);
-        public_key
+        match self.key {
+            OpSigningKey::Local(ref secret_key) => Secp256k1PublicKey::from_private(secret_key),
+            OpSigningKey::Remote(_, ref public_key) => public_key.clone(),
+        }
+    }
+
+    /// Whether the key is held by a remote signer, which needs to see the transactions a
+    /// burnchain transaction spends before it will sign it
+    pub fn is_remote(&self) -> bool {
+        match self.key {
+            OpSigningKey::Local(..) => false,
+            OpSigningKey::Remote(..) => true,
+        }
     }
 
+    fn record_usage(&mut self) {
+        self.usages += 1;
+
+        if self.is_one_off && self.usages == 1 {
+            self.is_disposed = true;
+        }
+    }
+
+    /// Sign a message hash.  A remote signer won't sign a bare hash (it can't check what it's
+    /// signing), so this always fails for remote keys -- use `sign_tx_input` instead.
     pub fn sign_message(&mut self, hash: &[u8]) -> Option<MessageSignature> {
         if self.is_disposed {
             return None;
         }
 
-        let signature = match self.secret_key.sign(hash) {
-            Ok(r) => r,
-            _ => return None,
+        let signature = match self.key {
+            OpSigningKey::Local(ref secret_key) => match secret_key.sign(hash) {
+                Ok(r) => r,
+                _ => return None,
+            },
+            OpSigningKey::Remote(..) => {
+                warn!("Remote signers do not sign bare message hashes");
+                return None;
+            }
         };
-        self.usages += 1;
+        self.record_usage();
 
-        if self.is_one_off && self.usages == 1 {
-            self.is_disposed = true;
+        Some(signature)
+    }
+
+    /// Sign input `input_index` of `tx`, which spends an output locked by `script_pub_key`.
+    /// Remote signers are sent the whole transaction and `funding_txs`, the transactions whose
+    /// outputs it spends, so they can check what they are signing and what it costs.
+    pub fn sign_tx_input(
+        &mut self,
+        tx: &Transaction,
+        input_index: usize,
+        script_pub_key: &Script,
+        sighash_type: u32,
+        funding_txs: &[Transaction],
+    ) -> Option<MessageSignature> {
+        if self.is_disposed {
+            return None;
         }
 
+        let signature = match self.key {
+            OpSigningKey::Local(ref secret_key) => {
+                let sig_hash = tx.signature_hash(input_index, script_pub_key, sighash_type);
+                match secret_key.sign(sig_hash.as_bytes()) {
+                    Ok(r) => r,
+                    _ => return None,
+                }
+            }
+            OpSigningKey::Remote(ref mut signer, _) => {
+                match signer.sign_burnchain_input(
+                    tx,
+                    input_index,
+                    script_pub_key,
+                    sighash_type,
+                    funding_txs,
+                ) {
+                    Ok(r) => r,
+                    Err(e) => {
+                        warn!("Failed to sign burnchain transaction input: {}", e);
+                        return None;
+                    }
+                }
+            }
+        };
+        self.record_usage();
+
         Some(signature)
     }
 
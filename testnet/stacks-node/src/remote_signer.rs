@@ -0,0 +1,1638 @@
+//! Remote signing for miners.
+//!
+//! A miner normally derives all of its keys from `node.seed`.  With a remote signer, the keys live
+//! in a separate `stacks-node signer` process instead, and the node asks that process for every
+//! signature it needs: Stacks transactions (coinbases and poison-microblock reports), VRF keys and
+//! proofs, microblock keys and header signatures, and burnchain transaction inputs.
+//!
+//! The node and the signer exchange one JSON object per line over a TCP connection, one request
+//! per connection.  The signer only listens on loopback addresses, checks a shared auth token, and
+//! applies a `SignerPolicy` to everything it is asked to sign.  The node in turn verifies every
+//! signature it gets back before using it.
+
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use stacks::burnchains::bitcoin::address::BitcoinAddress;
+use stacks::burnchains::{BurnchainSigner, MagicBytes, PublicKey};
+use stacks::chainstate::burn::Opcodes;
+use stacks::chainstate::stacks::{
+    StacksPublicKey, StacksTransaction, StacksTransactionSigner, TransactionPayload,
+    TransactionVersion,
+};
+use stacks::codec::StacksMessageCodec;
+use stacks::deps::bitcoin::blockdata::script::Script;
+use stacks::deps::bitcoin::blockdata::transaction::{OutPoint, Transaction};
+use stacks::deps::bitcoin::network::serialize::{deserialize, serialize};
+use stacks::types::chainstate::{BlockHeaderHash, StacksMicroblockHeader};
+use stacks::util::get_epoch_time_secs;
+use stacks::util::hash::{hex_bytes, to_hex, Hash160, Sha512Trunc256Sum};
+use stacks::util::secp256k1::{MessageSignature, Secp256k1PublicKey};
+use stacks::util::vrf::{VRFProof, VRFPublicKey, VRF};
+
+use super::config::SignerConfig;
+use super::keychain::{MinerSigner, SignerError};
+use super::operations::BurnchainOpSigner;
+use super::{Config, Keychain};
+
+/// The only sighash type the signer will sign burnchain inputs with
+const SIGHASH_ALL: u32 = 0x01;
+
+/// Number of microblock keys whose signed headers the signer remembers
+const MAX_TRACKED_MICROBLOCK_KEYS: usize = 16;
+
+/// Largest request or response line either side will read
+const MAX_MESSAGE_LEN: u64 = 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum SignerRequest {
+    GetPublicKeys,
+    SignTransaction {
+        /// hex-encoded Stacks transaction
+        tx: String,
+    },
+    RotateVrfKey {
+        block_height: u64,
+    },
+    ProveVrf {
+        public_key: String,
+        /// hex-encoded 32-byte message
+        message: String,
+    },
+    RotateMicroblockKey {
+        burn_block_height: u64,
+    },
+    SignMicroblockHeader {
+        public_key: String,
+        header: StacksMicroblockHeader,
+    },
+    SignBurnchainInput {
+        /// hex-encoded bitcoin transaction
+        tx: String,
+        input_index: usize,
+        /// hex-encoded script of the output being spent
+        script_pub_key: String,
+        sighash_type: u32,
+        /// hex-encoded bitcoin transactions whose outputs `tx` spends
+        funding_txs: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SignerRequestEnvelope {
+    pub auth_token: Option<String>,
+    pub request: SignerRequest,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum SignerResponse {
+    PublicKeys {
+        burnchain_signer: BurnchainSigner,
+        op_public_key: String,
+        microblock_public_key: Option<String>,
+    },
+    Transaction {
+        tx: String,
+    },
+    VrfKey {
+        public_key: String,
+    },
+    VrfProof {
+        proof: String,
+    },
+    MicroblockKey {
+        public_key: String,
+    },
+    Signature {
+        signature: MessageSignature,
+    },
+    Denied {
+        reason: String,
+    },
+    UnknownKey {
+        key: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// Largest amount of time the signer waits for a whole request line, so that a local process
+/// that connects and goes quiet can't hold up the miner's requests for long
+const REQUEST_DEADLINE: Duration = Duration::from_secs(2);
+
+/// Reads from a stream until a deadline, no matter how slowly the peer trickles in bytes
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = self
+            .deadline
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| *remaining > Duration::from_millis(0))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::TimedOut, "request deadline passed"))?;
+        let mut stream = self.stream;
+        stream.set_read_timeout(Some(remaining))?;
+        stream.read(buf)
+    }
+}
+
+fn read_message<T: serde::de::DeserializeOwned, R: Read>(reader: R) -> Result<T, String> {
+    let mut line = String::new();
+    BufReader::new(reader)
+        .take(MAX_MESSAGE_LEN)
+        .read_line(&mut line)
+        .map_err(|e| e.to_string())?;
+    serde_json::from_str(&line).map_err(|e| e.to_string())
+}
+
+fn write_message<T: serde::Serialize>(mut stream: &TcpStream, msg: &T) -> Result<(), String> {
+    let mut bytes = serde_json::to_vec(msg).map_err(|e| e.to_string())?;
+    bytes.push(b'\n');
+    stream.write_all(&bytes).map_err(|e| e.to_string())?;
+    stream.flush().map_err(|e| e.to_string())
+}
+
+/// The node's side of the remote signer.  It caches the signer's public keys, and verifies every
+/// signature the signer hands back.
+#[derive(Clone)]
+pub struct RemoteSigner {
+    endpoint: String,
+    auth_token: Option<String>,
+    timeout: Duration,
+    burnchain_signer: BurnchainSigner,
+    op_public_key: Secp256k1PublicKey,
+    microblock_public_key: Option<StacksPublicKey>,
+}
+
+/// Send one request to the signer at `endpoint`, and read back its response
+fn send_request(
+    endpoint: &str,
+    auth_token: &Option<String>,
+    timeout: Duration,
+    request: SignerRequest,
+) -> Result<SignerResponse, SignerError> {
+    let addr = endpoint
+        .to_socket_addrs()
+        .map_err(|e| SignerError::Transport(e.to_string()))?
+        .next()
+        .ok_or_else(|| SignerError::Transport(format!("Could not resolve {}", endpoint)))?;
+
+    let stream = TcpStream::connect_timeout(&addr, timeout)
+        .map_err(|e| SignerError::Transport(e.to_string()))?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|_| stream.set_write_timeout(Some(timeout)))
+        .map_err(|e| SignerError::Transport(e.to_string()))?;
+
+    let envelope = SignerRequestEnvelope {
+        auth_token: auth_token.clone(),
+        request,
+    };
+    write_message(&stream, &envelope).map_err(SignerError::Transport)?;
+    let response = read_message(&stream).map_err(SignerError::Transport)?;
+
+    match response {
+        SignerResponse::Denied { reason } => Err(SignerError::Denied(reason)),
+        SignerResponse::UnknownKey { key } => Err(SignerError::UnknownKey(key)),
+        SignerResponse::Error { message } => Err(SignerError::Transport(message)),
+        response => Ok(response),
+    }
+}
+
+impl RemoteSigner {
+    /// Connect to the signer at `endpoint` and fetch its public keys
+    pub fn connect(endpoint: &str, config: &SignerConfig) -> Result<RemoteSigner, SignerError> {
+        let timeout = Duration::from_secs(config.timeout);
+        let response = send_request(
+            endpoint,
+            &config.auth_token,
+            timeout,
+            SignerRequest::GetPublicKeys,
+        )?;
+
+        match response {
+            SignerResponse::PublicKeys {
+                burnchain_signer,
+                op_public_key,
+                microblock_public_key,
+            } => {
+                let op_public_key = Secp256k1PublicKey::from_hex(&op_public_key)
+                    .map_err(|e| SignerError::Transport(e.to_string()))?;
+                let microblock_public_key = match microblock_public_key {
+                    Some(pubk) => Some(
+                        StacksPublicKey::from_hex(&pubk)
+                            .map_err(|e| SignerError::Transport(e.to_string()))?,
+                    ),
+                    None => None,
+                };
+                Ok(RemoteSigner {
+                    endpoint: endpoint.to_string(),
+                    auth_token: config.auth_token.clone(),
+                    timeout,
+                    burnchain_signer,
+                    op_public_key,
+                    microblock_public_key,
+                })
+            }
+            other => Err(unexpected_response(other)),
+        }
+    }
+
+    fn request(&self, request: SignerRequest) -> Result<SignerResponse, SignerError> {
+        send_request(&self.endpoint, &self.auth_token, self.timeout, request)
+    }
+
+    /// Have the signer sign input `input_index` of a burnchain transaction.  `funding_txs` are
+    /// the transactions whose outputs `tx` spends.  The signature is checked against the op
+    /// signing key before it is returned.
+    pub fn sign_burnchain_input(
+        &mut self,
+        tx: &Transaction,
+        input_index: usize,
+        script_pub_key: &Script,
+        sighash_type: u32,
+        funding_txs: &[Transaction],
+    ) -> Result<MessageSignature, SignerError> {
+        let encode = |tx: &Transaction| {
+            serialize(tx)
+                .map(|tx_bytes| to_hex(&tx_bytes))
+                .map_err(|e| SignerError::Transport(format!("{:?}", e)))
+        };
+        let request = SignerRequest::SignBurnchainInput {
+            tx: encode(tx)?,
+            input_index,
+            script_pub_key: to_hex(script_pub_key.as_bytes()),
+            sighash_type,
+            funding_txs: funding_txs
+                .iter()
+                .map(encode)
+                .collect::<Result<Vec<_>, _>>()?,
+        };
+
+        let signature = match self.request(request)? {
+            SignerResponse::Signature { signature } => signature,
+            other => return Err(unexpected_response(other)),
+        };
+
+        let sig_hash = tx.signature_hash(input_index, script_pub_key, sighash_type);
+        match self.op_public_key.verify(sig_hash.as_bytes(), &signature) {
+            Ok(true) => Ok(signature),
+            _ => Err(SignerError::BadSignature(
+                "burnchain input signature does not verify".to_string(),
+            )),
+        }
+    }
+}
+
+fn unexpected_response(response: SignerResponse) -> SignerError {
+    SignerError::Transport(format!("Unexpected response from signer: {:?}", &response))
+}
+
+impl MinerSigner for RemoteSigner {
+    fn get_burnchain_signer(&self) -> BurnchainSigner {
+        self.burnchain_signer.clone()
+    }
+
+    fn sign_as_origin(
+        &mut self,
+        tx_signer: &mut StacksTransactionSigner,
+    ) -> Result<(), SignerError> {
+        let unsigned_tx = tx_signer.get_tx_incomplete();
+        let request = SignerRequest::SignTransaction {
+            tx: to_hex(&unsigned_tx.serialize_to_vec()),
+        };
+
+        let signed_tx = match self.request(request)? {
+            SignerResponse::Transaction { tx } => {
+                let tx_bytes = hex_bytes(&tx).map_err(|e| SignerError::Transport(e.to_string()))?;
+                StacksTransaction::consensus_deserialize(&mut &tx_bytes[..])
+                    .map_err(|e| SignerError::Transport(e.to_string()))?
+            }
+            other => return Err(unexpected_response(other)),
+        };
+
+        // the signer may only have filled in the origin's signatures
+        let mut stripped_tx = signed_tx.clone();
+        stripped_tx.auth = unsigned_tx.auth.clone();
+        if stripped_tx != unsigned_tx
+            || signed_tx.auth.origin().address_testnet()
+                != unsigned_tx.auth.origin().address_testnet()
+        {
+            return Err(SignerError::BadSignature(
+                "signer changed the transaction".to_string(),
+            ));
+        }
+        signed_tx
+            .verify()
+            .map_err(|e| SignerError::BadSignature(e.to_string()))?;
+
+        tx_signer.resume(&signed_tx);
+        Ok(())
+    }
+
+    fn rotate_vrf_keypair(&mut self, block_height: u64) -> Result<VRFPublicKey, SignerError> {
+        match self.request(SignerRequest::RotateVrfKey { block_height })? {
+            SignerResponse::VrfKey { public_key } => VRFPublicKey::from_hex(&public_key)
+                .ok_or_else(|| SignerError::Transport("invalid VRF public key".to_string())),
+            other => Err(unexpected_response(other)),
+        }
+    }
+
+    fn generate_proof(&mut self, vrf_pk: &VRFPublicKey, bytes: &[u8; 32]) -> Option<VRFProof> {
+        let request = SignerRequest::ProveVrf {
+            public_key: vrf_pk.to_hex(),
+            message: to_hex(bytes),
+        };
+
+        let proof = match self.request(request) {
+            Ok(SignerResponse::VrfProof { proof }) => VRFProof::from_hex(&proof),
+            Ok(other) => {
+                warn!("{}", unexpected_response(other));
+                return None;
+            }
+            Err(e) => {
+                warn!("Failed to generate VRF proof for {:?}: {}", vrf_pk, e);
+                return None;
+            }
+        }?;
+
+        match VRF::verify(vrf_pk, &proof, &bytes.to_vec()) {
+            Ok(true) => Some(proof),
+            _ => {
+                warn!("Signer produced an invalid VRF proof for {:?}", vrf_pk);
+                None
+            }
+        }
+    }
+
+    fn rotate_microblock_keypair(
+        &mut self,
+        burn_block_height: u64,
+    ) -> Result<StacksPublicKey, SignerError> {
+        let pubkey = match self.request(SignerRequest::RotateMicroblockKey { burn_block_height })? {
+            SignerResponse::MicroblockKey { public_key } => StacksPublicKey::from_hex(&public_key)
+                .map_err(|e| SignerError::Transport(e.to_string()))?,
+            other => return Err(unexpected_response(other)),
+        };
+
+        self.microblock_public_key = Some(pubkey.clone());
+        Ok(pubkey)
+    }
+
+    fn get_microblock_public_key(&self) -> Option<StacksPublicKey> {
+        self.microblock_public_key.clone()
+    }
+
+    fn sign_microblock_header(
+        &mut self,
+        pubkey: &StacksPublicKey,
+        header: &mut StacksMicroblockHeader,
+    ) -> Result<(), SignerError> {
+        let request = SignerRequest::SignMicroblockHeader {
+            public_key: pubkey.to_hex(),
+            header: header.clone(),
+        };
+
+        let signature = match self.request(request)? {
+            SignerResponse::Signature { signature } => signature,
+            other => return Err(unexpected_response(other)),
+        };
+
+        let mut signed_header = header.clone();
+        signed_header.signature = signature;
+        signed_header
+            .verify(&Hash160::from_node_public_key(pubkey))
+            .map_err(|e| SignerError::BadSignature(e.to_string()))?;
+
+        *header = signed_header;
+        Ok(())
+    }
+
+    fn generate_op_signer(&self) -> BurnchainOpSigner {
+        BurnchainOpSigner::remote(self.clone(), self.op_public_key.clone(), false)
+    }
+}
+
+/// What the signer is willing to sign
+#[derive(Debug, Clone)]
+pub struct SignerPolicy {
+    pub chain_id: u32,
+    pub tx_version: TransactionVersion,
+    pub magic_bytes: MagicBytes,
+    /// Largest fee, in uSTX, of a Stacks transaction
+    pub max_tx_fee: Option<u64>,
+    /// Largest amount, in sats, the burnchain transactions signed within `burn_spend_window`
+    /// may spend in total, in fees and in outputs to addresses other than the signer's own
+    pub max_burn_spend: u64,
+    /// Largest fee, in sats, of a burnchain transaction
+    pub max_burn_fee: u64,
+    pub burn_spend_window: Duration,
+}
+
+impl SignerPolicy {
+    pub fn from_config(config: &Config) -> Result<SignerPolicy, String> {
+        let max_burn_spend = config
+            .signer
+            .max_burn_spend
+            .ok_or_else(|| "The signer requires signer.max_burn_spend to be set".to_string())?;
+        let max_burn_fee = config
+            .signer
+            .max_burn_fee
+            .ok_or_else(|| "The signer requires signer.max_burn_fee to be set".to_string())?;
+
+        Ok(SignerPolicy {
+            chain_id: config.burnchain.chain_id,
+            tx_version: if config.is_mainnet() {
+                TransactionVersion::Mainnet
+            } else {
+                TransactionVersion::Testnet
+            },
+            magic_bytes: config.burnchain.magic_bytes.clone(),
+            max_tx_fee: config.signer.max_tx_fee,
+            max_burn_spend,
+            max_burn_fee,
+            burn_spend_window: Duration::from_secs(config.signer.burn_spend_window),
+        })
+    }
+}
+
+/// What a signed burnchain transaction spends, in fees and in outputs to other addresses
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct BurnSpend {
+    /// the outputs the transaction spends, as sorted `txid:vout` strings
+    prevouts: Vec<String>,
+    amount: u64,
+    /// seconds since the epoch
+    signed_at: u64,
+}
+
+fn sorted_prevouts(tx: &Transaction) -> Vec<String> {
+    let mut prevouts: Vec<OutPoint> = tx.input.iter().map(|input| input.previous_output).collect();
+    prevouts.sort();
+    prevouts
+        .iter()
+        .map(|prevout| format!("{}:{}", prevout.txid.be_hex_string(), prevout.vout))
+        .collect()
+}
+
+/// Everything the signer has to remember across restarts for its policy to hold: the keys it has
+/// issued, the microblock headers it has signed with them, and its recent burnchain spends.
+/// Keys are stored as the heights they were derived from, and re-derived from `node.seed`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+struct SignerState {
+    vrf_key_heights: Vec<u64>,
+    /// each microblock key is derived from the one before it, so all of them are kept in order
+    microblock_key_heights: Vec<u64>,
+    /// (key hash, prev_block, sequence, header hash) for each header signed with a tracked key
+    signed_microblocks: Vec<(Hash160, BlockHeaderHash, u16, BlockHeaderHash)>,
+    burn_spends: Vec<BurnSpend>,
+}
+
+impl SignerState {
+    fn load(path: &Path) -> Result<SignerState, String> {
+        match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .map_err(|e| format!("Corrupt signer state {}: {}", path.display(), e)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(SignerState::default()),
+            Err(e) => Err(format!(
+                "Failed to read signer state {}: {}",
+                path.display(),
+                e
+            )),
+        }
+    }
+
+    /// Write the state to a temporary file, and move it into place once it's on disk, so a crash
+    /// never leaves a partially-written state behind
+    fn store(&self, path: &Path) -> Result<(), String> {
+        let tmp_path = path.with_extension("tmp");
+        let bytes = serde_json::to_vec(self).map_err(|e| e.to_string())?;
+        let mut file = fs::File::create(&tmp_path).map_err(|e| e.to_string())?;
+        file.write_all(&bytes)
+            .and_then(|_| file.sync_all())
+            .and_then(|_| fs::rename(&tmp_path, path))
+            .map_err(|e| format!("Failed to write signer state {}: {}", path.display(), e))
+    }
+}
+
+/// Find the Stacks opcode carried in an OP_RETURN output, if the output carries a Stacks operation
+/// with the expected magic bytes.
+fn burnchain_op_opcode(script: &Script, magic_bytes: &MagicBytes) -> Option<u8> {
+    let bytes = script.as_bytes();
+    if !script.is_op_return() || bytes.len() < 2 {
+        return None;
+    }
+
+    // OP_RETURN <push> <data>
+    let data = match bytes[1] {
+        len @ 0x01..=0x4b => bytes.get(2..2 + len as usize)?,
+        0x4c => {
+            let len = *bytes.get(2)? as usize;
+            bytes.get(3..3 + len)?
+        }
+        _ => return None,
+    };
+
+    if data.len() < 3 || &data[0..2] != magic_bytes.as_bytes() {
+        return None;
+    }
+    Some(data[2])
+}
+
+/// The `stacks-node signer` process.  It holds the miner's keychain, and signs on behalf of the
+/// node only what its policy allows.
+pub struct SignerServer {
+    keychain: Keychain,
+    bind: SocketAddr,
+    auth_token: String,
+    policy: SignerPolicy,
+    /// where the signer's state is kept.  If not set, it is only kept in memory.
+    state_path: Option<PathBuf>,
+    vrf_key_heights: Vec<u64>,
+    microblock_key_heights: Vec<u64>,
+    /// (prev_block, sequence) => microblock header hash, for each recently-issued microblock key
+    signed_microblocks: HashMap<Hash160, HashMap<(BlockHeaderHash, u16), BlockHeaderHash>>,
+    microblock_keys: VecDeque<Hash160>,
+    /// burnchain transactions signed within the last `burn_spend_window`
+    burn_spends: Vec<BurnSpend>,
+}
+
+impl SignerServer {
+    pub fn new(
+        keychain: Keychain,
+        bind: SocketAddr,
+        auth_token: String,
+        policy: SignerPolicy,
+    ) -> SignerServer {
+        SignerServer {
+            keychain,
+            bind,
+            auth_token,
+            policy,
+            state_path: None,
+            vrf_key_heights: vec![],
+            microblock_key_heights: vec![],
+            signed_microblocks: HashMap::new(),
+            microblock_keys: VecDeque::new(),
+            burn_spends: vec![],
+        }
+    }
+
+    /// Keep the signer's state in the file at `path`, restoring whatever it already holds.  A
+    /// signer that forgot what it had signed could be made to fork its own microblock stream, or
+    /// to spend its burnchain budget all over again.
+    pub fn with_state_file(mut self, path: &Path) -> Result<SignerServer, String> {
+        let state = SignerState::load(path)?;
+        for height in state.vrf_key_heights.iter() {
+            self.rotate_vrf_keypair(*height);
+        }
+        for height in state.microblock_key_heights.iter() {
+            self.rotate_microblock_keypair(*height);
+        }
+        for (pubkey_hash, prev_block, sequence, header_hash) in state.signed_microblocks.into_iter()
+        {
+            if self.microblock_keys.contains(&pubkey_hash) {
+                self.signed_microblocks
+                    .entry(pubkey_hash)
+                    .or_insert_with(HashMap::new)
+                    .insert((prev_block, sequence), header_hash);
+            }
+        }
+        self.burn_spends = state.burn_spends;
+        self.state_path = Some(path.to_path_buf());
+
+        info!("Loaded signer state from {}", path.display();
+              "vrf_keys" => self.vrf_key_heights.len(),
+              "microblock_keys" => self.microblock_key_heights.len(),
+              "burn_spends" => self.burn_spends.len());
+        Ok(self)
+    }
+
+    fn store_state(&self) -> Result<(), SignerResponse> {
+        let path = match self.state_path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let mut signed_microblocks = vec![];
+        for (pubkey_hash, signed) in self.signed_microblocks.iter() {
+            for ((prev_block, sequence), header_hash) in signed.iter() {
+                signed_microblocks.push((
+                    pubkey_hash.clone(),
+                    prev_block.clone(),
+                    *sequence,
+                    header_hash.clone(),
+                ));
+            }
+        }
+        let state = SignerState {
+            vrf_key_heights: self.vrf_key_heights.clone(),
+            microblock_key_heights: self.microblock_key_heights.clone(),
+            signed_microblocks,
+            burn_spends: self.burn_spends.clone(),
+        };
+        state.store(path).map_err(|message| {
+            error!("{}", &message);
+            SignerResponse::Error { message }
+        })
+    }
+
+    fn rotate_vrf_keypair(&mut self, block_height: u64) -> VRFPublicKey {
+        self.vrf_key_heights.push(block_height);
+        self.keychain.rotate_vrf_keypair(block_height)
+    }
+
+    fn rotate_microblock_keypair(&mut self, burn_block_height: u64) -> StacksPublicKey {
+        self.microblock_key_heights.push(burn_block_height);
+        let sk = self.keychain.rotate_microblock_keypair(burn_block_height);
+        let pubkey = StacksPublicKey::from_private(&sk);
+
+        self.microblock_keys
+            .push_back(Hash160::from_node_public_key(&pubkey));
+        if self.microblock_keys.len() > MAX_TRACKED_MICROBLOCK_KEYS {
+            if let Some(oldest) = self.microblock_keys.pop_front() {
+                self.signed_microblocks.remove(&oldest);
+            }
+        }
+        pubkey
+    }
+
+    pub fn from_config(config: &Config) -> Result<SignerServer, String> {
+        let bind = config
+            .signer
+            .bind
+            .to_socket_addrs()
+            .map_err(|e| format!("Invalid signer bind address {}: {}", &config.signer.bind, e))?
+            .next()
+            .ok_or_else(|| format!("Could not resolve {}", &config.signer.bind))?;
+        let auth_token = match config.signer.auth_token {
+            Some(ref auth_token) if !auth_token.is_empty() => auth_token.clone(),
+            _ => return Err("The signer requires signer.auth_token to be set".to_string()),
+        };
+
+        let state_path = config
+            .signer
+            .state_path
+            .as_ref()
+            .ok_or_else(|| "The signer requires signer.state_path to be set".to_string())?;
+
+        SignerServer::new(
+            Keychain::default(config.node.seed.clone()),
+            bind,
+            auth_token,
+            SignerPolicy::from_config(config)?,
+        )
+        .with_state_file(Path::new(state_path))
+    }
+
+    /// Serve requests until the listener fails.  Requests are handled one at a time.
+    pub fn serve(&mut self) -> Result<(), String> {
+        if !self.bind.ip().is_loopback() {
+            return Err(format!(
+                "Refusing to bind signer to non-loopback address {}",
+                &self.bind
+            ));
+        }
+        let listener = TcpListener::bind(&self.bind).map_err(|e| e.to_string())?;
+        info!("Signer listening on {}", &self.bind);
+
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(s) => s,
+                Err(e) => {
+                    warn!("Failed to accept signer connection: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = self.handle_connection(&stream) {
+                warn!("Failed to handle signer request: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_connection(&mut self, stream: &TcpStream) -> Result<(), String> {
+        stream
+            .set_write_timeout(Some(REQUEST_DEADLINE))
+            .map_err(|e| e.to_string())?;
+
+        let reader = DeadlineReader {
+            stream,
+            deadline: Instant::now() + REQUEST_DEADLINE,
+        };
+        let response = match read_message::<SignerRequestEnvelope, _>(reader) {
+            Ok(envelope) => self.handle_envelope(envelope),
+            Err(e) => SignerResponse::Error {
+                message: format!("Malformed request: {}", e),
+            },
+        };
+        write_message(stream, &response)
+    }
+
+    pub fn handle_envelope(&mut self, envelope: SignerRequestEnvelope) -> SignerResponse {
+        let authorized = match envelope.auth_token {
+            Some(ref auth_token) => auth_tokens_match(&self.auth_token, auth_token),
+            None => false,
+        };
+        if !authorized {
+            return SignerResponse::Denied {
+                reason: "bad auth token".to_string(),
+            };
+        }
+        match self.handle_request(envelope.request) {
+            Ok(response) => response,
+            Err(response) => {
+                info!("Signer refused request: {:?}", &response);
+                response
+            }
+        }
+    }
+
+    fn handle_request(&mut self, request: SignerRequest) -> Result<SignerResponse, SignerResponse> {
+        match request {
+            SignerRequest::GetPublicKeys => {
+                let burnchain_signer = self.keychain.get_burnchain_signer();
+                let op_public_key = self.keychain.generate_op_signer().get_public_key();
+                Ok(SignerResponse::PublicKeys {
+                    burnchain_signer,
+                    op_public_key: op_public_key.to_hex(),
+                    microblock_public_key: self
+                        .keychain
+                        .get_microblock_key()
+                        .map(|sk| StacksPublicKey::from_private(&sk).to_hex()),
+                })
+            }
+            SignerRequest::SignTransaction { tx } => {
+                let tx_bytes = hex_bytes(&tx).map_err(|e| malformed(e.to_string()))?;
+                let tx = StacksTransaction::consensus_deserialize(&mut &tx_bytes[..])
+                    .map_err(|e| malformed(e.to_string()))?;
+                self.check_stacks_transaction(&tx).map_err(denied)?;
+
+                let mut tx_signer = StacksTransactionSigner::new(&tx);
+                self.keychain.sign_as_origin(&mut tx_signer);
+                Ok(SignerResponse::Transaction {
+                    tx: to_hex(&tx_signer.get_tx_incomplete().serialize_to_vec()),
+                })
+            }
+            SignerRequest::RotateVrfKey { block_height } => {
+                let public_key = self.rotate_vrf_keypair(block_height);
+                self.store_state()?;
+                Ok(SignerResponse::VrfKey {
+                    public_key: public_key.to_hex(),
+                })
+            }
+            SignerRequest::ProveVrf {
+                public_key,
+                message,
+            } => {
+                let vrf_pk = VRFPublicKey::from_hex(&public_key)
+                    .ok_or_else(|| malformed("invalid VRF public key".to_string()))?;
+                let message_bytes = hex_bytes(&message).map_err(|e| malformed(e.to_string()))?;
+                if message_bytes.len() != 32 {
+                    return Err(malformed("VRF message must be 32 bytes".to_string()));
+                }
+                let mut message = [0u8; 32];
+                message.copy_from_slice(&message_bytes);
+
+                let proof = self
+                    .keychain
+                    .generate_proof(&vrf_pk, &message)
+                    .ok_or_else(|| SignerResponse::UnknownKey { key: public_key })?;
+                Ok(SignerResponse::VrfProof {
+                    proof: proof.to_hex(),
+                })
+            }
+            SignerRequest::RotateMicroblockKey { burn_block_height } => {
+                let pubkey = self.rotate_microblock_keypair(burn_block_height);
+                self.store_state()?;
+                Ok(SignerResponse::MicroblockKey {
+                    public_key: pubkey.to_hex(),
+                })
+            }
+            SignerRequest::SignMicroblockHeader {
+                public_key,
+                mut header,
+            } => {
+                let pubkey =
+                    StacksPublicKey::from_hex(&public_key).map_err(|e| malformed(e.to_string()))?;
+                let pubkey_hash = Hash160::from_node_public_key(&pubkey);
+                if !self.microblock_keys.contains(&pubkey_hash) {
+                    return Err(SignerResponse::UnknownKey { key: public_key });
+                }
+
+                header.signature = MessageSignature::empty();
+                let header_hash = header.block_hash();
+                let signed = self
+                    .signed_microblocks
+                    .entry(pubkey_hash)
+                    .or_insert_with(HashMap::new);
+                match signed.get(&(header.prev_block.clone(), header.sequence)) {
+                    Some(prior_hash) if *prior_hash != header_hash => {
+                        return Err(denied(format!(
+                            "already signed microblock {} at sequence {} off of {}",
+                            prior_hash, header.sequence, &header.prev_block
+                        )));
+                    }
+                    _ => {}
+                }
+
+                self.keychain
+                    .sign_microblock_header(&pubkey, &mut header)
+                    .map_err(|e| SignerResponse::Error {
+                        message: e.to_string(),
+                    })?;
+                signed.insert((header.prev_block.clone(), header.sequence), header_hash);
+                self.store_state()?;
+
+                Ok(SignerResponse::Signature {
+                    signature: header.signature,
+                })
+            }
+            SignerRequest::SignBurnchainInput {
+                tx,
+                input_index,
+                script_pub_key,
+                sighash_type,
+                funding_txs,
+            } => {
+                let decode = |tx: &str| -> Result<Transaction, SignerResponse> {
+                    let tx_bytes = hex_bytes(tx).map_err(|e| malformed(e.to_string()))?;
+                    deserialize(&tx_bytes).map_err(|e| malformed(format!("{:?}", e)))
+                };
+                let tx = decode(tx.as_str())?;
+                let funding_txs = funding_txs
+                    .iter()
+                    .map(|funding_tx| decode(funding_tx.as_str()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let script_pub_key =
+                    Script::from(hex_bytes(&script_pub_key).map_err(|e| malformed(e.to_string()))?);
+
+                let now = get_epoch_time_secs();
+                let spend = self
+                    .check_burnchain_input(
+                        &tx,
+                        input_index,
+                        &script_pub_key,
+                        sighash_type,
+                        &funding_txs,
+                        now,
+                    )
+                    .map_err(denied)?;
+
+                let mut op_signer = self.keychain.generate_op_signer();
+                let signature = op_signer
+                    .sign_tx_input(&tx, input_index, &script_pub_key, sighash_type, &[])
+                    .ok_or_else(|| SignerResponse::Error {
+                        message: "failed to sign input".to_string(),
+                    })?;
+                // the spend is on disk before the signature leaves the signer
+                self.record_burn_spend(&tx, spend, now);
+                self.store_state()?;
+                Ok(SignerResponse::Signature { signature })
+            }
+        }
+    }
+
+    /// The signer only signs coinbases and poison-microblock reports from its own account
+    fn check_stacks_transaction(&self, tx: &StacksTransaction) -> Result<(), String> {
+        match tx.payload {
+            TransactionPayload::Coinbase(..) | TransactionPayload::PoisonMicroblock(..) => {}
+            _ => return Err("only coinbase and poison-microblock transactions are signed".into()),
+        }
+        if tx.chain_id != self.policy.chain_id {
+            return Err(format!("wrong chain ID {}", tx.chain_id));
+        }
+        if tx.version != self.policy.tx_version {
+            return Err(format!("wrong transaction version {:?}", tx.version));
+        }
+        if let Some(max_tx_fee) = self.policy.max_tx_fee {
+            if tx.get_tx_fee() > max_tx_fee {
+                return Err(format!(
+                    "fee {} exceeds max_tx_fee {}",
+                    tx.get_tx_fee(),
+                    max_tx_fee
+                ));
+            }
+        }
+        if tx.auth.origin().address_testnet() != self.keychain.get_address(false) {
+            return Err("transaction is not from the signer's account".into());
+        }
+        Ok(())
+    }
+
+    /// The signer only signs its own UTXOs into block-commits and leader key registrations,
+    /// only for transactions whose every input spends an output of one of `funding_txs`, with
+    /// a fee up to `max_burn_fee`, and only up to `max_burn_spend` in each `burn_spend_window`.
+    /// Returns what the transaction spends.
+    fn check_burnchain_input(
+        &self,
+        tx: &Transaction,
+        input_index: usize,
+        script_pub_key: &Script,
+        sighash_type: u32,
+        funding_txs: &[Transaction],
+        now: u64,
+    ) -> Result<u64, String> {
+        if sighash_type != SIGHASH_ALL {
+            return Err(format!("unsupported sighash type {}", sighash_type));
+        }
+        if input_index >= tx.input.len() {
+            return Err(format!("no input {}", input_index));
+        }
+
+        let public_key = self.keychain.get_burnchain_signer().public_keys[0].clone();
+        let own_script =
+            BitcoinAddress::to_p2pkh_tx_out(&Hash160::from_data(&public_key.to_bytes()), 0)
+                .script_pubkey;
+        if *script_pub_key != own_script {
+            return Err("input does not spend the signer's own output".into());
+        }
+
+        let mut input_value: u64 = 0;
+        for (i, input) in tx.input.iter().enumerate() {
+            let prevout = &input.previous_output;
+            let spent_output = funding_txs
+                .iter()
+                .find(|funding_tx| funding_tx.txid() == prevout.txid)
+                .and_then(|funding_tx| funding_tx.output.get(prevout.vout as usize))
+                .ok_or_else(|| {
+                    format!(
+                        "input {} spends {}:{}, which is not an output of the funding transactions",
+                        i,
+                        prevout.txid.be_hex_string(),
+                        prevout.vout
+                    )
+                })?;
+            if i == input_index && spent_output.script_pubkey != *script_pub_key {
+                return Err(format!("input {} does not spend script_pub_key", i));
+            }
+            input_value = input_value
+                .checked_add(spent_output.value)
+                .ok_or_else(|| "input values overflow".to_string())?;
+        }
+
+        let opcode = tx
+            .output
+            .first()
+            .and_then(|out| burnchain_op_opcode(&out.script_pubkey, &self.policy.magic_bytes))
+            .ok_or_else(|| "transaction does not carry a Stacks operation".to_string())?;
+        if opcode != Opcodes::LeaderBlockCommit as u8 && opcode != Opcodes::LeaderKeyRegister as u8
+        {
+            return Err(format!("operation {} is not signed", opcode as char));
+        }
+
+        let output_value = tx
+            .output
+            .iter()
+            .try_fold(0u64, |total, out| total.checked_add(out.value))
+            .ok_or_else(|| "output values overflow".to_string())?;
+        let fee = input_value.checked_sub(output_value).ok_or_else(|| {
+            format!(
+                "outputs worth {} exceed inputs worth {}",
+                output_value, input_value
+            )
+        })?;
+        if fee > self.policy.max_burn_fee {
+            return Err(format!(
+                "fee {} exceeds max_burn_fee {}",
+                fee, self.policy.max_burn_fee
+            ));
+        }
+
+        let sent: u64 = tx
+            .output
+            .iter()
+            .filter(|out| !out.script_pubkey.is_op_return() && out.script_pubkey != own_script)
+            .map(|out| out.value)
+            .sum();
+        // can't overflow: the outputs and the fee add up to the inputs
+        let spend = fee + sent;
+        let window_spend = self.burn_spend_in_window(tx, spend, now);
+        if window_spend > self.policy.max_burn_spend {
+            return Err(format!(
+                "spend {} brings the spend over the last {}s to {}, over max_burn_spend {}",
+                spend,
+                self.policy.burn_spend_window.as_secs(),
+                window_spend,
+                self.policy.max_burn_spend
+            ));
+        }
+        Ok(spend)
+    }
+
+    /// Total spend within the window if `tx`, which spends `spend`, is signed.  A transaction
+    /// that spends exactly the same outputs as an earlier one (like a fee-bumped replacement)
+    /// can't confirm alongside it, so only the larger of the two counts.
+    fn burn_spend_in_window(&self, tx: &Transaction, spend: u64, now: u64) -> u64 {
+        let prevouts = sorted_prevouts(tx);
+        let mut total = spend;
+        for prior in self.burn_spends.iter() {
+            if now.saturating_sub(prior.signed_at) >= self.policy.burn_spend_window.as_secs() {
+                continue;
+            }
+            if prior.prevouts == prevouts {
+                total = total.saturating_add(prior.amount.saturating_sub(spend));
+            } else {
+                total = total.saturating_add(prior.amount);
+            }
+        }
+        total
+    }
+
+    fn record_burn_spend(&mut self, tx: &Transaction, spend: u64, now: u64) {
+        let window = self.policy.burn_spend_window.as_secs();
+        self.burn_spends
+            .retain(|prior| now.saturating_sub(prior.signed_at) < window);
+
+        let prevouts = sorted_prevouts(tx);
+        match self
+            .burn_spends
+            .iter_mut()
+            .find(|prior| prior.prevouts == prevouts)
+        {
+            Some(prior) => {
+                prior.amount = cmp::max(prior.amount, spend);
+                prior.signed_at = now;
+            }
+            None => self.burn_spends.push(BurnSpend {
+                prevouts,
+                amount: spend,
+                signed_at: now,
+            }),
+        }
+    }
+}
+
+fn denied(reason: String) -> SignerResponse {
+    SignerResponse::Denied { reason }
+}
+
+fn malformed(message: String) -> SignerResponse {
+    SignerResponse::Error {
+        message: format!("Malformed request: {}", message),
+    }
+}
+
+/// Compare auth tokens in constant time, by comparing their (fixed-length) hashes without
+/// short-circuiting
+fn auth_tokens_match(expected: &str, given: &str) -> bool {
+    let expected = Sha512Trunc256Sum::from_data(expected.as_bytes());
+    let given = Sha512Trunc256Sum::from_data(given.as_bytes());
+    expected
+        .0
+        .iter()
+        .zip(given.0.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+#[cfg(test)]
+mod test {
+    use stacks::chainstate::stacks::StacksPrivateKey;
+    use stacks::chainstate::stacks::{
+        CoinbasePayload, StacksTransaction, StacksTransactionSigner, TokenTransferMemo,
+        TransactionPayload, TransactionVersion,
+    };
+    use stacks::codec::StacksMessageCodec;
+    use stacks::deps::bitcoin::blockdata::opcodes;
+    use stacks::deps::bitcoin::blockdata::script::Builder;
+    use stacks::deps::bitcoin::blockdata::transaction::{TxIn, TxOut};
+    use stacks::deps::bitcoin::util::hash::Sha256dHash;
+    use stacks::types::chainstate::{BlockHeaderHash, StacksAddress, StacksMicroblockHeader};
+    use stacks::util::hash::{to_hex, Sha512Trunc256Sum};
+    use stacks::util::secp256k1::MessageSignature;
+    use stacks::vm::types::PrincipalData;
+
+    use super::*;
+
+    const MAX_BURN_SPEND: u64 = 100_000;
+    const MAX_BURN_FEE: u64 = 10_000;
+    const BURN_SPEND_WINDOW: Duration = Duration::from_secs(86400);
+
+    fn make_server(max_tx_fee: Option<u64>) -> SignerServer {
+        let policy = SignerPolicy {
+            chain_id: 0x80000000,
+            tx_version: TransactionVersion::Testnet,
+            magic_bytes: MagicBytes::default(),
+            max_tx_fee,
+            max_burn_spend: MAX_BURN_SPEND,
+            max_burn_fee: MAX_BURN_FEE,
+            burn_spend_window: BURN_SPEND_WINDOW,
+        };
+        SignerServer::new(
+            Keychain::default(vec![0x01; 32]),
+            "127.0.0.1:0".parse().unwrap(),
+            "secret".to_string(),
+            policy,
+        )
+    }
+
+    fn make_tx(server: &SignerServer, payload: TransactionPayload, fee: u64) -> StacksTransaction {
+        let mut tx = StacksTransaction::new(
+            TransactionVersion::Testnet,
+            server.keychain.get_transaction_auth().unwrap(),
+            payload,
+        );
+        tx.chain_id = 0x80000000;
+        tx.set_tx_fee(fee);
+        tx
+    }
+
+    fn request(server: &mut SignerServer, request: SignerRequest) -> SignerResponse {
+        server.handle_envelope(SignerRequestEnvelope {
+            auth_token: Some("secret".to_string()),
+            request,
+        })
+    }
+
+    #[test]
+    fn test_sign_transaction_policy() {
+        let mut server = make_server(Some(1000));
+
+        let coinbase = make_tx(
+            &server,
+            TransactionPayload::Coinbase(CoinbasePayload([0u8; 32])),
+            0,
+        );
+        let sign_coinbase = SignerRequest::SignTransaction {
+            tx: to_hex(&coinbase.serialize_to_vec()),
+        };
+
+        // wrong token
+        match server.handle_envelope(SignerRequestEnvelope {
+            auth_token: Some("wrong".to_string()),
+            request: sign_coinbase.clone(),
+        }) {
+            SignerResponse::Denied { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // no token
+        match server.handle_envelope(SignerRequestEnvelope {
+            auth_token: None,
+            request: sign_coinbase.clone(),
+        }) {
+            SignerResponse::Denied { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // the signed coinbase verifies, and is otherwise unchanged
+        match request(&mut server, sign_coinbase) {
+            SignerResponse::Transaction { tx } => {
+                let tx_bytes = hex_bytes(&tx).unwrap();
+                let signed = StacksTransaction::consensus_deserialize(&mut &tx_bytes[..]).unwrap();
+                signed.verify().unwrap();
+
+                let mut tx_signer = StacksTransactionSigner::new(&coinbase);
+                tx_signer.resume(&signed);
+                assert!(tx_signer.get_tx().is_some());
+            }
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // too expensive
+        let expensive = make_tx(
+            &server,
+            TransactionPayload::Coinbase(CoinbasePayload([0u8; 32])),
+            1001,
+        );
+        match request(
+            &mut server,
+            SignerRequest::SignTransaction {
+                tx: to_hex(&expensive.serialize_to_vec()),
+            },
+        ) {
+            SignerResponse::Denied { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // not a miner transaction
+        let transfer = make_tx(
+            &server,
+            TransactionPayload::TokenTransfer(
+                PrincipalData::from(StacksAddress::burn_address(false)),
+                123,
+                TokenTransferMemo([0u8; 34]),
+            ),
+            0,
+        );
+        match request(
+            &mut server,
+            SignerRequest::SignTransaction {
+                tx: to_hex(&transfer.serialize_to_vec()),
+            },
+        ) {
+            SignerResponse::Denied { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+    }
+
+    #[test]
+    fn test_microblock_equivocation() {
+        let mut server = make_server(None);
+
+        let pubkey = match request(
+            &mut server,
+            SignerRequest::RotateMicroblockKey {
+                burn_block_height: 100,
+            },
+        ) {
+            SignerResponse::MicroblockKey { public_key } => public_key,
+            r => panic!("Unexpected response {:?}", r),
+        };
+
+        let header = StacksMicroblockHeader {
+            version: 0,
+            sequence: 0,
+            prev_block: BlockHeaderHash([0x11; 32]),
+            tx_merkle_root: Sha512Trunc256Sum([0x22; 32]),
+            signature: MessageSignature::empty(),
+        };
+        let mut conflicting_header = header.clone();
+        conflicting_header.tx_merkle_root = Sha512Trunc256Sum([0x33; 32]);
+
+        let sign = |header: &StacksMicroblockHeader| SignerRequest::SignMicroblockHeader {
+            public_key: pubkey.clone(),
+            header: header.clone(),
+        };
+
+        let signature = match request(&mut server, sign(&header)) {
+            SignerResponse::Signature { signature } => signature,
+            r => panic!("Unexpected response {:?}", r),
+        };
+        let mut signed_header = header.clone();
+        signed_header.signature = signature;
+        signed_header
+            .verify(&Hash160::from_node_public_key(
+                &StacksPublicKey::from_hex(&pubkey).unwrap(),
+            ))
+            .unwrap();
+
+        // re-signing the same header is fine
+        match request(&mut server, sign(&header)) {
+            SignerResponse::Signature { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // signing a different header at the same position is not
+        match request(&mut server, sign(&conflicting_header)) {
+            SignerResponse::Denied { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // keys the signer did not issue are unknown
+        let other_pubkey = StacksPublicKey::from_private(&StacksPrivateKey::new());
+        match request(
+            &mut server,
+            SignerRequest::SignMicroblockHeader {
+                public_key: other_pubkey.to_hex(),
+                header: header.clone(),
+            },
+        ) {
+            SignerResponse::UnknownKey { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+    }
+
+    fn own_script(server: &SignerServer) -> Script {
+        let public_key = server.keychain.get_burnchain_signer().public_keys[0].clone();
+        BitcoinAddress::to_p2pkh_tx_out(&Hash160::from_data(&public_key.to_bytes()), 0)
+            .script_pubkey
+    }
+
+    fn make_input(previous_output: OutPoint) -> TxIn {
+        TxIn {
+            previous_output,
+            script_sig: Script::new(),
+            sequence: 0xFFFFFFFD,
+            witness: vec![],
+        }
+    }
+
+    /// A transaction paying `value` to `script_pubkey`.  `nonce` tells apart the txids of
+    /// otherwise-identical transactions.
+    fn make_funding_tx(script_pubkey: &Script, value: u64, nonce: u32) -> Transaction {
+        Transaction {
+            version: 1,
+            lock_time: nonce,
+            input: vec![make_input(OutPoint {
+                txid: Sha256dHash([0x01; 32]),
+                vout: 0,
+            })],
+            output: vec![TxOut {
+                value,
+                script_pubkey: script_pubkey.clone(),
+            }],
+        }
+    }
+
+    /// A block-commit spending the first output of `funding_tx`, sending `sent` to another
+    /// address and `change` back to the signer
+    fn make_commit_tx(
+        server: &SignerServer,
+        funding_tx: &Transaction,
+        sent: u64,
+        change: u64,
+    ) -> Transaction {
+        let mut op_data = server.policy.magic_bytes.as_bytes().to_vec();
+        op_data.push(Opcodes::LeaderBlockCommit as u8);
+        op_data.extend_from_slice(&[0u8; 16]);
+
+        Transaction {
+            version: 1,
+            lock_time: 0,
+            input: vec![make_input(OutPoint {
+                txid: funding_tx.txid(),
+                vout: 0,
+            })],
+            output: vec![
+                TxOut {
+                    value: 0,
+                    script_pubkey: Builder::new()
+                        .push_opcode(opcodes::All::OP_RETURN)
+                        .push_slice(&op_data)
+                        .into_script(),
+                },
+                BitcoinAddress::to_p2pkh_tx_out(&Hash160([0x11; 20]), sent),
+                TxOut {
+                    value: change,
+                    script_pubkey: own_script(server),
+                },
+            ],
+        }
+    }
+
+    fn sign_input(
+        server: &mut SignerServer,
+        tx: &Transaction,
+        funding_txs: &[&Transaction],
+    ) -> SignerResponse {
+        let script_pub_key = own_script(server);
+        request(
+            server,
+            SignerRequest::SignBurnchainInput {
+                tx: to_hex(&serialize(tx).unwrap()),
+                input_index: 0,
+                script_pub_key: to_hex(script_pub_key.as_bytes()),
+                sighash_type: SIGHASH_ALL,
+                funding_txs: funding_txs
+                    .iter()
+                    .map(|funding_tx| to_hex(&serialize(*funding_tx).unwrap()))
+                    .collect(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_sign_burnchain_input_policy() {
+        let mut server = make_server(None);
+        let own = own_script(&server);
+        let other = BitcoinAddress::to_p2pkh_tx_out(&Hash160([0x22; 20]), 0).script_pubkey;
+
+        let funding_a = make_funding_tx(&own, 100_000, 1);
+        let funding_b = make_funding_tx(&own, 100_000, 2);
+        let funding_c = make_funding_tx(&own, 100_000, 3);
+        let funding_other = make_funding_tx(&other, 100_000, 4);
+
+        // spends 40_000 + a 1_000 fee
+        let commit_a = make_commit_tx(&server, &funding_a, 40_000, 59_000);
+
+        // the funding transaction must be given, and must be the one the input spends
+        for funding_txs in [vec![], vec![&funding_b]].iter() {
+            match sign_input(&mut server, &commit_a, funding_txs) {
+                SignerResponse::Denied { .. } => {}
+                r => panic!("Unexpected response {:?}", r),
+            }
+        }
+
+        // the spent output must be the signer's own
+        let commit_other = make_commit_tx(&server, &funding_other, 40_000, 59_000);
+        match sign_input(&mut server, &commit_other, &[&funding_other]) {
+            SignerResponse::Denied { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // fee too high, and outputs worth more than the inputs
+        for (sent, change) in [(40_000, 40_000), (40_000, 70_000)].iter() {
+            let commit = make_commit_tx(&server, &funding_a, *sent, *change);
+            match sign_input(&mut server, &commit, &[&funding_a]) {
+                SignerResponse::Denied { .. } => {}
+                r => panic!("Unexpected response {:?}", r),
+            }
+        }
+
+        let signature = match sign_input(&mut server, &commit_a, &[&funding_a]) {
+            SignerResponse::Signature { signature } => signature,
+            r => panic!("Unexpected response {:?}", r),
+        };
+        let sig_hash = commit_a.signature_hash(0, &own, SIGHASH_ALL);
+        let op_public_key = server.keychain.generate_op_signer().get_public_key();
+        assert!(op_public_key
+            .verify(sig_hash.as_bytes(), &signature)
+            .unwrap());
+
+        // a replacement spending the same output only counts once: 47_000 spent so far
+        let commit_a_rbf = make_commit_tx(&server, &funding_a, 45_000, 53_000);
+        match sign_input(&mut server, &commit_a_rbf, &[&funding_a]) {
+            SignerResponse::Signature { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // 98_000 spent so far
+        let commit_b = make_commit_tx(&server, &funding_b, 50_000, 49_000);
+        match sign_input(&mut server, &commit_b, &[&funding_b]) {
+            SignerResponse::Signature { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // another 6_000 goes over max_burn_spend...
+        let commit_c = make_commit_tx(&server, &funding_c, 5_000, 94_000);
+        match sign_input(&mut server, &commit_c, &[&funding_c]) {
+            SignerResponse::Denied { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // ...until the earlier spends fall out of the window
+        assert_eq!(
+            server.check_burnchain_input(
+                &commit_c,
+                0,
+                &own,
+                SIGHASH_ALL,
+                &[funding_c.clone()],
+                get_epoch_time_secs() + BURN_SPEND_WINDOW.as_secs()
+            ),
+            Ok(6_000)
+        );
+    }
+
+    fn state_path(test_name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{}.json", test_name));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_state_survives_restart() {
+        let path = state_path("test_signer_state_survives_restart");
+        let mut server = make_server(None).with_state_file(&path).unwrap();
+
+        let vrf_pk = match request(
+            &mut server,
+            SignerRequest::RotateVrfKey { block_height: 100 },
+        ) {
+            SignerResponse::VrfKey { public_key } => public_key,
+            r => panic!("Unexpected response {:?}", r),
+        };
+        let mut microblock_pks = vec![];
+        for burn_block_height in 100..102 {
+            match request(
+                &mut server,
+                SignerRequest::RotateMicroblockKey { burn_block_height },
+            ) {
+                SignerResponse::MicroblockKey { public_key } => microblock_pks.push(public_key),
+                r => panic!("Unexpected response {:?}", r),
+            }
+        }
+
+        let header = StacksMicroblockHeader {
+            version: 0,
+            sequence: 0,
+            prev_block: BlockHeaderHash([0x11; 32]),
+            tx_merkle_root: Sha512Trunc256Sum([0x22; 32]),
+            signature: MessageSignature::empty(),
+        };
+        let mut conflicting_header = header.clone();
+        conflicting_header.tx_merkle_root = Sha512Trunc256Sum([0x33; 32]);
+        let sign = |header: &StacksMicroblockHeader| SignerRequest::SignMicroblockHeader {
+            public_key: microblock_pks[1].clone(),
+            header: header.clone(),
+        };
+        match request(&mut server, sign(&header)) {
+            SignerResponse::Signature { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // 98_000 of the burnchain budget is spent
+        let own = own_script(&server);
+        let funding = make_funding_tx(&own, 100_000, 1);
+        let commit = make_commit_tx(&server, &funding, 97_000, 2_000);
+        match sign_input(&mut server, &commit, &[&funding]) {
+            SignerResponse::Signature { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // restart
+        let mut server = make_server(None).with_state_file(&path).unwrap();
+
+        // the keys it issued are still usable, and the microblock key chain carries on from them
+        match request(
+            &mut server,
+            SignerRequest::ProveVrf {
+                public_key: vrf_pk,
+                message: to_hex(&[0x44; 32]),
+            },
+        ) {
+            SignerResponse::VrfProof { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+        match request(&mut server, SignerRequest::GetPublicKeys) {
+            SignerResponse::PublicKeys {
+                microblock_public_key,
+                ..
+            } => assert_eq!(microblock_public_key.as_ref(), microblock_pks.last()),
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // it still won't fork its microblock stream...
+        match request(&mut server, sign(&conflicting_header)) {
+            SignerResponse::Denied { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+        match request(&mut server, sign(&header)) {
+            SignerResponse::Signature { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // ...or spend its burnchain budget again
+        let funding = make_funding_tx(&own, 100_000, 2);
+        let commit = make_commit_tx(&server, &funding, 5_000, 94_000);
+        match sign_input(&mut server, &commit, &[&funding]) {
+            SignerResponse::Denied { .. } => {}
+            r => panic!("Unexpected response {:?}", r),
+        }
+
+        // a state file that can't be parsed is an error, not a fresh start
+        fs::write(&path, b"{").unwrap();
+        assert!(make_server(None).with_state_file(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_signer_required_settings() {
+        let mut config = Config::default();
+        config.signer.bind = "127.0.0.1:0".to_string();
+        config.signer.max_burn_spend = Some(MAX_BURN_SPEND);
+        config.signer.max_burn_fee = Some(MAX_BURN_FEE);
+        assert!(SignerServer::from_config(&config).is_err());
+
+        config.signer.auth_token = Some("".to_string());
+        assert!(SignerServer::from_config(&config).is_err());
+
+        config.signer.auth_token = Some("secret".to_string());
+        assert!(SignerServer::from_config(&config).is_err());
+
+        let state_path = state_path("test_signer_required_settings");
+        config.signer.state_path = Some(state_path.to_str().unwrap().to_string());
+        assert!(SignerServer::from_config(&config).is_ok());
+
+        // the burnchain spend limits are required too
+        config.signer.max_burn_spend = None;
+        assert!(SignerServer::from_config(&config).is_err());
+
+        config.signer.max_burn_spend = Some(MAX_BURN_SPEND);
+        config.signer.max_burn_fee = None;
+        assert!(SignerServer::from_config(&config).is_err());
+
+        assert!(auth_tokens_match("secret", "secret"));
+        assert!(!auth_tokens_match("secret", "secreT"));
+        assert!(!auth_tokens_match("secret", "secret2"));
+        assert!(!auth_tokens_match("secret", ""));
+    }
+
+    #[test]
+    fn test_request_deadline() {
+        let mut server = make_server(None);
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        // a client that connects, trickles in bytes and never finishes its request
+        let client = std::thread::spawn(move || {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            for _ in 0..10 {
+                if stream.write_all(b" ").is_err() {
+                    break;
+                }
+                std::thread::sleep(Duration::from_millis(500));
+            }
+        });
+
+        let (stream, _) = listener.accept().unwrap();
+        let start = Instant::now();
+        server.handle_connection(&stream).unwrap();
+        assert!(start.elapsed() < REQUEST_DEADLINE + Duration::from_secs(1));
+
+        drop(stream);
+        client.join().unwrap();
+    }
+}
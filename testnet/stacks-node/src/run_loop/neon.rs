@@ -19,6 +19,7 @@ use stacks::net::atlas::{AtlasConfig, Attachment};
 use stacks::vm::types::{PrincipalData, Value};
 use stx_genesis::GenesisData;
 
+use crate::keychain::miner_signer_from_config;
 use crate::monitoring::start_serving_monitoring_metrics;
 use crate::node::use_test_genesis_chainstate;
 use crate::syncctl::PoxSyncWatchdog;
@@ -132,8 +133,15 @@ impl RunLoop {
         );
         let pox_constants = burnchain.get_pox_constants();
 
+        let keychain = match miner_signer_from_config(&self.config) {
+            Ok(keychain) => keychain,
+            Err(e) => {
+                error!("Failed to set up the miner's signer: {}", e);
+                return;
+            }
+        };
+
         let is_miner = if self.config.node.miner {
-            let keychain = Keychain::default(self.config.node.seed.clone());
             let node_address = Keychain::address_from_burnchain_signer(
                 &keychain.get_burnchain_signer(),
                 self.config.is_mainnet(),
@@ -239,8 +247,13 @@ impl RunLoop {
         .unwrap();
         coordinator_dispatcher.dispatch_boot_receipts(receipts);
 
+        if let Err(e) = chain_state_db.set_block_compression(self.config.node.compress_blocks) {
+            warn!("Failed to set block compression: {:?}", &e);
+        }
+
         let atlas_config = AtlasConfig::default(mainnet);
         let moved_atlas_config = atlas_config.clone();
+        let prune_depth = self.config.node.prune_depth;
 
         let coordinator_thread_handle = thread::Builder::new()
             .name("chains-coordinator".to_string())
@@ -252,6 +265,7 @@ impl RunLoop {
                     &mut coordinator_dispatcher,
                     coordinator_receivers,
                     moved_atlas_config,
+                    prune_depth,
                 );
             })
             .unwrap();
@@ -281,6 +295,7 @@ impl RunLoop {
         // setup genesis
         let node = NeonGenesisNode::new(
             self.config.clone(),
+            keychain,
             event_dispatcher,
             burnchain_config.clone(),
             Box::new(|_| {}),
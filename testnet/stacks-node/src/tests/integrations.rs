@@ -603,7 +603,8 @@ fn integration_test_get_info() {
 
                 let body = CallReadOnlyRequestBody {
                     sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
-                    arguments: vec![Value::UInt(3).serialize()]
+                    arguments: vec![Value::UInt(3).serialize()],
+                    arguments_json: None,
                 };
 
                 let res = client.post(&path)
@@ -618,13 +619,28 @@ fn integration_test_get_info() {
                                                                        "(get-exotic-data-info u3)");
                 assert_eq!(result_data, expected_data);
 
+                // the same call, with arguments and result in the canonical JSON encoding
+                let body = CallReadOnlyRequestBody {
+                    sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
+                    arguments: vec![],
+                    arguments_json: Some(vec![json!("3")]),
+                };
+
+                let res = client.post(&path)
+                    .json(&body)
+                    .send()
+                    .unwrap().json::<serde_json::Value>().unwrap();
+                assert!(res["okay"].as_bool().unwrap());
+                assert_eq!(res["result_json"], expected_data.to_json());
+
                 // how about a non read-only function call which does not modify anything
                 let path = format!("{}/v2/contracts/call-read/{}/{}/{}", &http_origin, &contract_addr, "main", "public-no-write");
                 eprintln!("Test: POST {}", path);
 
                 let body = CallReadOnlyRequestBody {
                     sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
-                    arguments: vec![]
+                    arguments: vec![],
+                    arguments_json: None,
                 };
 
                 let res = client.post(&path)
@@ -650,7 +666,8 @@ fn integration_test_get_info() {
 
                 let body = CallReadOnlyRequestBody {
                     sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
-                    arguments: vec![]
+                    arguments: vec![],
+                    arguments_json: None,
                 };
 
                 let res = client.post(&path)
@@ -668,7 +685,8 @@ fn integration_test_get_info() {
 
                 let body = CallReadOnlyRequestBody {
                     sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
-                    arguments: vec![Value::UInt(3).serialize()]
+                    arguments: vec![Value::UInt(3).serialize()],
+                    arguments_json: None,
                 };
 
                 let res = client.post(&path)
@@ -690,7 +708,8 @@ fn integration_test_get_info() {
 
                 let body = CallReadOnlyRequestBody {
                     sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
-                    arguments: vec![Value::UInt(100).serialize()]
+                    arguments: vec![Value::UInt(100).serialize()],
+                    arguments_json: None,
                 };
 
                 let res = client.post(&path)
@@ -708,7 +727,8 @@ fn integration_test_get_info() {
 
                 let body = CallReadOnlyRequestBody {
                     sender: "'SP139Q3N9RXCJCD1XVA4N5RYWQ5K9XQ0T9PKQ8EE5".into(),
-                    arguments: vec![]
+                    arguments: vec![],
+                    arguments_json: None,
                 };
 
                 let res = client.post(&path)
@@ -57,8 +57,9 @@ use stacks::{
 
 use crate::{
     burnchains::bitcoin_regtest_controller::UTXO, config::EventKeyType,
-    config::EventObserverConfig, config::InitialBalance, neon, operations::BurnchainOpSigner,
-    BitcoinRegtestController, BurnchainController, Config, ConfigFile, Keychain,
+    config::EventObserverConfig, config::EventObserverFilter, config::InitialBalance, neon,
+    operations::BurnchainOpSigner, BitcoinRegtestController, BurnchainController, Config,
+    ConfigFile, Keychain,
 };
 
 use crate::util::hash::{MerkleTree, Sha512Trunc256Sum};
@@ -92,6 +93,7 @@ fn neon_integration_test_conf() -> (Config, StacksAddress) {
 
     // test to make sure config file parsing is correct
     let magic_bytes = Config::from_config_file(ConfigFile::xenon())
+        .unwrap()
         .burnchain
         .magic_bytes;
     assert_eq!(magic_bytes.as_bytes(), &['X' as u8, '6' as u8]);
@@ -691,6 +693,7 @@ fn liquid_ustx_integration() {
     conf.events_observers.push(EventObserverConfig {
         endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
         events_keys: vec![EventKeyType::AnyEvent],
+        filter: EventObserverFilter::default(),
     });
 
     let spender_bal = 10_000_000_000 * (core::MICROSTACKS_PER_STACKS as u64);
@@ -819,6 +822,7 @@ fn lockup_integration() {
     conf.events_observers.push(EventObserverConfig {
         endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
         events_keys: vec![EventKeyType::AnyEvent],
+        filter: EventObserverFilter::default(),
     });
 
     let mut btcd_controller = BitcoinCoreController::new(conf.clone());
@@ -1371,6 +1375,7 @@ fn microblock_integration_test() {
     conf.events_observers.push(EventObserverConfig {
         endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
         events_keys: vec![EventKeyType::AnyEvent],
+        filter: EventObserverFilter::default(),
     });
 
     let mut btcd_controller = BitcoinCoreController::new(conf.clone());
@@ -1992,6 +1997,7 @@ fn size_overflow_unconfirmed_microblocks_integration_test() {
     conf.events_observers.push(EventObserverConfig {
         endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
         events_keys: vec![EventKeyType::AnyEvent],
+        filter: EventObserverFilter::default(),
     });
 
     let mut btcd_controller = BitcoinCoreController::new(conf.clone());
@@ -2182,6 +2188,7 @@ fn size_overflow_unconfirmed_stream_microblocks_integration_test() {
     conf.events_observers.push(EventObserverConfig {
         endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
         events_keys: vec![EventKeyType::AnyEvent],
+        filter: EventObserverFilter::default(),
     });
 
     let mut btcd_controller = BitcoinCoreController::new(conf.clone());
@@ -2377,6 +2384,7 @@ fn size_overflow_unconfirmed_invalid_stream_microblocks_integration_test() {
     conf.events_observers.push(EventObserverConfig {
         endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
         events_keys: vec![EventKeyType::AnyEvent],
+        filter: EventObserverFilter::default(),
     });
 
     let mut btcd_controller = BitcoinCoreController::new(conf.clone());
@@ -2636,6 +2644,7 @@ fn runtime_overflow_unconfirmed_microblocks_integration_test() {
     conf.events_observers.push(EventObserverConfig {
         endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
         events_keys: vec![EventKeyType::AnyEvent],
+        filter: EventObserverFilter::default(),
     });
 
     let mut btcd_controller = BitcoinCoreController::new(conf.clone());
@@ -2802,6 +2811,7 @@ fn block_replay_integration_test() {
     conf.events_observers.push(EventObserverConfig {
         endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
         events_keys: vec![EventKeyType::AnyEvent],
+        filter: EventObserverFilter::default(),
     });
 
     let mut btcd_controller = BitcoinCoreController::new(conf.clone());
@@ -2926,6 +2936,7 @@ fn cost_voting_integration() {
     conf.events_observers.push(EventObserverConfig {
         endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
         events_keys: vec![EventKeyType::AnyEvent],
+        filter: EventObserverFilter::default(),
     });
 
     let spender_bal = 10_000_000_000 * (core::MICROSTACKS_PER_STACKS as u64);
@@ -3349,6 +3360,7 @@ fn pox_integration_test() {
     conf.events_observers.push(EventObserverConfig {
         endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
         events_keys: vec![EventKeyType::AnyEvent],
+        filter: EventObserverFilter::default(),
     });
 
     let first_bal = 6_000_000_000 * (core::MICROSTACKS_PER_STACKS as u64);
@@ -3895,6 +3907,7 @@ fn atlas_integration_test() {
         .push(EventObserverConfig {
             endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
             events_keys: vec![EventKeyType::AnyEvent],
+            filter: EventObserverFilter::default(),
         });
 
     // Our 2 nodes will share the bitcoind node
@@ -4422,6 +4435,7 @@ fn antientropy_integration_test() {
         .push(EventObserverConfig {
             endpoint: format!("localhost:{}", test_observer::EVENT_OBSERVER_PORT),
             events_keys: vec![EventKeyType::AnyEvent],
+            filter: EventObserverFilter::default(),
         });
 
     // Our 2 nodes will share the bitcoind node